@@ -0,0 +1,9 @@
+#![no_main]
+
+use identity_server::verify::signature::parse;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: (&str, &str)| {
+    let (signature, signer) = data;
+    let _ = parse(signature, signer);
+});
@@ -1,14 +1,14 @@
 use std::collections::HashSet;
 
 use async_trait::async_trait;
-use sqlx::AnyPool;
-use sqlx::any::AnyPoolOptions;
+use sqlx::Row;
 
 use crate::admins::{AdminStorage, error::Error};
 use crate::identity::UserAddress;
+use crate::pools::{self, MeteredPool};
 
 pub struct DatabaseAdminStorage {
-    pool: AnyPool,
+    pool: MeteredPool,
 }
 
 impl DatabaseAdminStorage {
@@ -17,17 +17,18 @@ impl DatabaseAdminStorage {
         admins: HashSet<UserAddress>,
         moderators: HashSet<UserAddress>,
     ) -> Result<Self, Error> {
-        sqlx::any::install_default_drivers();
-        let pool = AnyPoolOptions::new()
-            .max_connections(1)
-            .connect(url)
-            .await?;
+        let pool = pools::connect("admins", url).await?;
         sqlx::query("CREATE TABLE IF NOT EXISTS admins (user TEXT PRIMARY KEY)")
             .execute(&pool)
             .await?;
         sqlx::query("CREATE TABLE IF NOT EXISTS moderators (user TEXT PRIMARY KEY)")
             .execute(&pool)
             .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS moderator_successors (moderator TEXT PRIMARY KEY, successor TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
         for admin in admins {
             sqlx::query("INSERT OR IGNORE INTO admins (user) VALUES (?)")
                 .bind(admin)
@@ -42,6 +43,10 @@ impl DatabaseAdminStorage {
         }
         Ok(Self { pool })
     }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
 }
 
 #[async_trait]
@@ -111,6 +116,41 @@ impl AdminStorage for DatabaseAdminStorage {
             .await?;
         Ok(())
     }
+
+    async fn set_successor(
+        &self,
+        caller: &UserAddress,
+        moderator: UserAddress,
+        successor: Option<UserAddress>,
+    ) -> Result<(), Error> {
+        self.check_admin(caller).await?;
+        match successor {
+            Some(successor) => {
+                sqlx::query(
+                    "INSERT OR REPLACE INTO moderator_successors (moderator, successor) VALUES (?, ?)",
+                )
+                .bind(moderator)
+                .bind(successor)
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM moderator_successors WHERE moderator = ?")
+                    .bind(moderator)
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn successor(&self, moderator: &UserAddress) -> Result<Option<UserAddress>, Error> {
+        let row = sqlx::query("SELECT successor FROM moderator_successors WHERE moderator = ?")
+            .bind(moderator)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| row.get::<String, _>(0)))
+    }
 }
 
 #[cfg(test)]
@@ -251,4 +291,37 @@ mod tests {
                 .is_err()
         );
     }
+
+    #[async_std::test]
+    async fn test_successors() {
+        let admin = "admin".to_string();
+        let admins = HashSet::from([admin.clone()]);
+        let storage = DatabaseAdminStorage::new("sqlite::memory:", admins, HashSet::new())
+            .await
+            .unwrap();
+        let (moderator, successor) = ("moderator".to_string(), "successor".to_string());
+        assert_eq!(storage.successor(&moderator).await.unwrap(), None);
+
+        // only admins can designate a successor
+        assert!(
+            storage
+                .set_successor(&successor, moderator.clone(), Some(successor.clone()))
+                .await
+                .is_err()
+        );
+        storage
+            .set_successor(&admin, moderator.clone(), Some(successor.clone()))
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.successor(&moderator).await.unwrap(),
+            Some(successor)
+        );
+
+        storage
+            .set_successor(&admin, moderator.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(storage.successor(&moderator).await.unwrap(), None);
+    }
 }
@@ -6,4 +6,7 @@ pub enum Error {
     NoModeratorPrivilege,
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
+    #[cfg(feature = "sled")]
+    #[error("Storage error: {0}")]
+    StorageError(#[from] crate::kv::error::Error),
 }
@@ -2,7 +2,7 @@ use async_std::sync::RwLock;
 use async_trait::async_trait;
 
 use crate::{admins::error::Error, identity::UserAddress};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 pub mod db;
 pub mod error;
@@ -23,6 +23,14 @@ pub trait AdminStorage: Send + Sync {
         caller: &UserAddress,
         moderator: UserAddress,
     ) -> Result<(), Error>;
+    // successor designated to take over the proofs of a retiring moderator
+    async fn set_successor(
+        &self,
+        caller: &UserAddress,
+        moderator: UserAddress,
+        successor: Option<UserAddress>,
+    ) -> Result<(), Error>;
+    async fn successor(&self, moderator: &UserAddress) -> Result<Option<UserAddress>, Error>;
 }
 
 // In-memory implementation of AdminStorage
@@ -30,6 +38,7 @@ pub trait AdminStorage: Send + Sync {
 pub struct InMemoryAdminStorage {
     admins: RwLock<HashSet<UserAddress>>,
     moderators: RwLock<HashSet<UserAddress>>,
+    successors: RwLock<HashMap<UserAddress, UserAddress>>,
 }
 
 impl InMemoryAdminStorage {
@@ -37,6 +46,7 @@ impl InMemoryAdminStorage {
         Self {
             admins: RwLock::new(admins),
             moderators: RwLock::new(moderators),
+            successors: RwLock::default(),
         }
     }
 }
@@ -102,6 +112,28 @@ impl AdminStorage for InMemoryAdminStorage {
         self.moderators.write().await.remove(&moderator);
         Ok(())
     }
+
+    async fn set_successor(
+        &self,
+        caller: &UserAddress,
+        moderator: UserAddress,
+        successor: Option<UserAddress>,
+    ) -> Result<(), Error> {
+        let admins_lock = self.admins.read().await;
+        if !admins_lock.contains(caller) {
+            return Err(Error::NoAdminPrivilege);
+        }
+        let mut successors = self.successors.write().await;
+        match successor {
+            Some(successor) => successors.insert(moderator, successor),
+            None => successors.remove(&moderator),
+        };
+        Ok(())
+    }
+
+    async fn successor(&self, moderator: &UserAddress) -> Result<Option<UserAddress>, Error> {
+        Ok(self.successors.read().await.get(moderator).cloned())
+    }
 }
 
 #[cfg(test)]
@@ -241,4 +273,35 @@ mod tests {
                 .is_err()
         );
     }
+
+    #[async_std::test]
+    async fn test_successors() {
+        let admin = "admin".to_string();
+        let admins = HashSet::from([admin.clone()]);
+        let storage = InMemoryAdminStorage::new(admins, HashSet::new());
+        let (moderator, successor) = ("moderator".to_string(), "successor".to_string());
+        assert_eq!(storage.successor(&moderator).await.unwrap(), None);
+
+        // only admins can designate a successor
+        assert!(
+            storage
+                .set_successor(&successor, moderator.clone(), Some(successor.clone()))
+                .await
+                .is_err()
+        );
+        storage
+            .set_successor(&admin, moderator.clone(), Some(successor.clone()))
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.successor(&moderator).await.unwrap(),
+            Some(successor)
+        );
+
+        storage
+            .set_successor(&admin, moderator.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(storage.successor(&moderator).await.unwrap(), None);
+    }
 }
@@ -0,0 +1,112 @@
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    archive::{ArchivedUser, error::Error, storage::ArchiveStorage},
+    encryption::{FieldCipher, rotate_column},
+    identity::UserAddress,
+    pools::{self, MeteredPool},
+};
+
+// records are stored as JSON, encrypted as a whole since they contain user addresses
+pub struct DatabaseArchiveStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabaseArchiveStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("archive", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS archived_users (user TEXT PRIMARY KEY, archived_at INTEGER NOT NULL, data TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "archived_users", "user").await?;
+        rotate_column(&pool, &cipher, "archived_users", "data").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl ArchiveStorage for DatabaseArchiveStorage {
+    async fn archive(&self, record: ArchivedUser) -> Result<(), Error> {
+        let data = serde_json::to_string(&record)?;
+        sqlx::query("REPLACE INTO archived_users (user, archived_at, data) VALUES (?, ?, ?)")
+            .bind(self.cipher.encode(&record.user))
+            .bind(record.archived_at as i64)
+            .bind(self.cipher.encode(&data))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn archived(&self, user: &UserAddress) -> Result<Option<ArchivedUser>, Error> {
+        let row = sqlx::query("SELECT data FROM archived_users WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let data = self.cipher.decode(&row.get::<String, _>(0))?;
+        Ok(Some(serde_json::from_str(&data)?))
+    }
+
+    async fn remove(&self, user: &UserAddress) -> Result<(), Error> {
+        sqlx::query("DELETE FROM archived_users WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::identity::{IdtAmount, ModeratorProof, SystemPenalty};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseArchiveStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        let user = "user".to_string();
+        assert!(storage.archived(&user).await.unwrap().is_none());
+
+        let record = ArchivedUser {
+            user: user.clone(),
+            archived_at: 10,
+            proof: Some(ModeratorProof {
+                moderator: "moderator".to_string(),
+                amount: IdtAmount::new(5),
+                proof_id: 1,
+                timestamp: 2,
+            }),
+            moderator_penalty: None,
+            forgotten: BTreeMap::from([(
+                "other".to_string(),
+                SystemPenalty {
+                    amount: IdtAmount::new(50),
+                    timestamp: 3,
+                },
+            )]),
+        };
+        storage.archive(record.clone()).await.unwrap();
+        assert_eq!(storage.archived(&user).await.unwrap(), Some(record));
+
+        storage.remove(&user).await.unwrap();
+        assert!(storage.archived(&user).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,14 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Identity error: {0}")]
+    IdentityError(#[from] crate::identity::error::Error),
+    #[error("Encryption error: {0}")]
+    EncryptionError(#[from] crate::encryption::error::Error),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[cfg(feature = "sled")]
+    #[error("Storage error: {0}")]
+    StorageError(#[from] crate::kv::error::Error),
+}
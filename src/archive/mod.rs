@@ -0,0 +1,351 @@
+// Archival of dead users.
+//
+// A user is dead when the balance stayed at zero for `archive.zero_balance_period` seconds
+// and nobody vouches for the user and the user vouches for nobody. The proof and penalties
+// of dead users are moved to the archive storage, so they are no longer loaded by balance
+// computations and tree walks. Admins can restore an archived user with
+// `POST /restore_user/:user`.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    archive::{error::Error, storage::ArchiveStorage},
+    config::ArchiveSection,
+    identity::{IdentityService, ModeratorProof, SystemPenalty, UserAddress, idt::balance},
+};
+
+pub mod db;
+pub mod error;
+pub mod storage;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedUser {
+    pub user: UserAddress,
+    pub archived_at: u64,
+    pub proof: Option<ModeratorProof>,
+    pub moderator_penalty: Option<ModeratorProof>,
+    // key - forgotten user
+    pub forgotten: BTreeMap<UserAddress, SystemPenalty>,
+}
+
+// user has local or external vouchers or vouches for someone
+pub async fn has_edges(service: &IdentityService, user: &UserAddress) -> Result<bool, Error> {
+    Ok(!service.vouchers_with_time(user).await?.is_empty()
+        || !service.vouchees_with_time(user).await?.is_empty()
+        || service
+            .external_vouches
+            .vouchers_with_time(user)
+            .await?
+            .values()
+            .any(|vouchers| !vouchers.is_empty()))
+}
+
+// moves the proof and penalties of the user to the archive
+pub async fn archive_user(
+    service: &IdentityService,
+    archive: &dyn ArchiveStorage,
+    user: &UserAddress,
+) -> Result<ArchivedUser, Error> {
+    let mut forgotten = BTreeMap::new();
+    for forgotten_user in service.forgotten_users(user).await? {
+        if let Some(penalty) = service.forgotten_penalty(user, &forgotten_user).await? {
+            forgotten.insert(forgotten_user, penalty);
+        }
+    }
+    let record = ArchivedUser {
+        user: user.clone(),
+        archived_at: service.now(),
+        proof: service.proof(user).await?,
+        moderator_penalty: service.moderator_penalty(user).await?,
+        forgotten,
+    };
+    // archive first, so a failure below does not lose data
+    archive.archive(record.clone()).await?;
+    service.proofs.remove_proof(user).await?;
+    service.penalties.remove_moderator_penalty(user).await?;
+    for forgotten_user in record.forgotten.keys() {
+        service
+            .penalties
+            .remove_forgotten(user.clone(), forgotten_user)
+            .await?;
+    }
+    Ok(record)
+}
+
+// moves the archived records back, records written after archival are kept.
+// Returns None if the user is not archived.
+pub async fn restore_user(
+    service: &IdentityService,
+    archive: &dyn ArchiveStorage,
+    user: &UserAddress,
+) -> Result<Option<ArchivedUser>, Error> {
+    let Some(record) = archive.archived(user).await? else {
+        return Ok(None);
+    };
+    if let Some(proof) = &record.proof {
+        if service.proof(user).await?.is_none() {
+            service
+                .proofs
+                .set_proof(user.clone(), proof.clone())
+                .await?;
+        }
+    }
+    if let Some(penalty) = &record.moderator_penalty {
+        if service.moderator_penalty(user).await?.is_none() {
+            service
+                .penalties
+                .set_moderator_penalty(user.clone(), penalty.clone())
+                .await?;
+        }
+    }
+    for (forgotten_user, penalty) in &record.forgotten {
+        if service
+            .forgotten_penalty(user, forgotten_user)
+            .await?
+            .is_none()
+        {
+            service
+                .penalties
+                .set_forgotten_penalty(user.clone(), forgotten_user.clone(), penalty.clone())
+                .await?;
+        }
+    }
+    archive.remove(user).await?;
+    Ok(Some(record))
+}
+
+// archives users with a proof whose balance has been zero for `zero_balance_period` seconds.
+// `zero_since` keeps the time each user was first seen with zero balance between runs.
+// Users with a genesis balance are never archived since genesis is loaded from the config.
+pub async fn archive_dead_users(
+    service: &IdentityService,
+    archive: &dyn ArchiveStorage,
+    zero_balance_period: u64,
+    zero_since: &mut HashMap<UserAddress, u64>,
+) -> Result<Vec<UserAddress>, Error> {
+    let now = service.now();
+    let mut users: Vec<UserAddress> = service.proofs.proven_users().await?.into_iter().collect();
+    users.sort();
+    let mut dead = HashSet::new();
+    let mut archived = vec![];
+    for user in users {
+        if service.genesis_balance(&user).await?.is_some()
+            || has_edges(service, &user).await?
+            || !balance(service, &user).await?.is_zero()
+        {
+            continue;
+        }
+        let since = *zero_since.entry(user.clone()).or_insert(now);
+        if now.saturating_sub(since) < zero_balance_period {
+            dead.insert(user);
+            continue;
+        }
+        archive_user(service, archive, &user).await?;
+        archived.push(user);
+    }
+    zero_since.retain(|user, _| dead.contains(user));
+    Ok(archived)
+}
+
+// archives dead users forever
+pub async fn archive_periodically(
+    service: IdentityService,
+    archive: std::sync::Arc<dyn ArchiveStorage>,
+    config: ArchiveSection,
+) {
+    let interval = Duration::from_secs(config.check_interval.max(1));
+    let mut zero_since = HashMap::new();
+    loop {
+        match archive_dead_users(
+            &service,
+            &*archive,
+            config.zero_balance_period,
+            &mut zero_since,
+        )
+        .await
+        {
+            Ok(archived) if !archived.is_empty() => {
+                log::info!("Archived {} users", archived.len())
+            }
+            Ok(_) => {}
+            Err(e) => log::error!("Failed to archive users: {:?}", e),
+        }
+        async_std::task::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        archive::storage::InMemoryArchiveStorage,
+        identity::{
+            IdtAmount,
+            forget::forget,
+            punish::punish,
+            tests::{MODERATOR, PROOF_ID, USER_A, service_with_mock_clock},
+            vouch::vouch,
+        },
+    };
+
+    const DAY: u64 = 86400;
+
+    #[async_std::test]
+    async fn test_archive_dead_users() {
+        let user_b = "userB".to_string();
+        let (service, clock) = service_with_mock_clock();
+        let archive = InMemoryArchiveStorage::default();
+        let mut zero_since = HashMap::new();
+        service
+            .prove_with_timestamp(
+                USER_A.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(2),
+                PROOF_ID,
+                service.now(),
+            )
+            .await
+            .unwrap();
+        service
+            .prove_with_timestamp(
+                user_b.clone(),
+                MODERATOR.to_string(),
+                IdtAmount::new(2),
+                PROOF_ID,
+                service.now(),
+            )
+            .await
+            .unwrap();
+        punish(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(1),
+            2,
+        )
+        .await
+        .unwrap();
+        vouch(&service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+        forget(&service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+        vouch(&service, user_b.clone(), "userC".to_string())
+            .await
+            .unwrap();
+
+        // proofs decayed, but zero balance is only noticed now
+        clock.advance(3 * DAY);
+        assert!(
+            archive_dead_users(&service, &archive, 10 * DAY, &mut zero_since)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        clock.advance(10 * DAY);
+        // user B still vouches for user C
+        assert_eq!(
+            archive_dead_users(&service, &archive, 10 * DAY, &mut zero_since)
+                .await
+                .unwrap(),
+            vec![USER_A.to_string()]
+        );
+        assert!(service.proof(&USER_A.to_string()).await.unwrap().is_none());
+        assert!(
+            service
+                .moderator_penalty(&USER_A.to_string())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            service
+                .forgotten_users(&USER_A.to_string())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(service.proof(&user_b).await.unwrap().is_some());
+
+        let record = archive
+            .archived(&USER_A.to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.proof.unwrap().amount, IdtAmount::new(2));
+        assert_eq!(record.moderator_penalty.unwrap().amount, IdtAmount::new(1));
+        assert!(record.forgotten.contains_key(&user_b));
+    }
+
+    #[async_std::test]
+    async fn test_restore() {
+        let (service, _clock) = service_with_mock_clock();
+        let archive = InMemoryArchiveStorage::default();
+        let user = USER_A.to_string();
+        assert!(
+            restore_user(&service, &archive, &user)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        service
+            .prove_with_timestamp(
+                user.clone(),
+                MODERATOR.to_string(),
+                IdtAmount::new(5),
+                PROOF_ID,
+                1,
+            )
+            .await
+            .unwrap();
+        punish(
+            &service,
+            user.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(1),
+            2,
+        )
+        .await
+        .unwrap();
+        archive_user(&service, &archive, &user).await.unwrap();
+        assert_eq!(balance(&service, &user).await.unwrap(), IdtAmount::new(0));
+
+        // proof received after archival is kept
+        service
+            .prove_with_timestamp(
+                user.clone(),
+                MODERATOR.to_string(),
+                IdtAmount::new(100),
+                3,
+                service.now(),
+            )
+            .await
+            .unwrap();
+        assert!(
+            restore_user(&service, &archive, &user)
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(
+            service.proof(&user).await.unwrap().unwrap().amount,
+            IdtAmount::new(100)
+        );
+        assert_eq!(
+            service
+                .moderator_penalty(&user)
+                .await
+                .unwrap()
+                .unwrap()
+                .amount,
+            IdtAmount::new(1)
+        );
+        assert!(archive.archived(&user).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::{
+    archive::{ArchivedUser, error::Error},
+    identity::UserAddress,
+};
+
+#[async_trait]
+pub trait ArchiveStorage: Send + Sync {
+    // replaces the previous record of the same user
+    async fn archive(&self, record: ArchivedUser) -> Result<(), Error>;
+    async fn archived(&self, user: &UserAddress) -> Result<Option<ArchivedUser>, Error>;
+    async fn remove(&self, user: &UserAddress) -> Result<(), Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryArchiveStorage {
+    // key - archived user
+    records: RwLock<HashMap<UserAddress, ArchivedUser>>,
+}
+
+#[async_trait]
+impl ArchiveStorage for InMemoryArchiveStorage {
+    async fn archive(&self, record: ArchivedUser) -> Result<(), Error> {
+        self.records
+            .write()
+            .await
+            .insert(record.user.clone(), record);
+        Ok(())
+    }
+
+    async fn archived(&self, user: &UserAddress) -> Result<Option<ArchivedUser>, Error> {
+        Ok(self.records.read().await.get(user).cloned())
+    }
+
+    async fn remove(&self, user: &UserAddress) -> Result<(), Error> {
+        self.records.write().await.remove(user);
+        Ok(())
+    }
+}
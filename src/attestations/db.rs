@@ -0,0 +1,182 @@
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    attestations::{Attestation, Challenge, error::Error, storage::AttestationStorage},
+    encryption::{FieldCipher, rotate_column},
+    identity::UserAddress,
+    pools::{self, MeteredPool},
+};
+
+// records are stored as JSON, encrypted as a whole since they contain user addresses
+pub struct DatabaseAttestationStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabaseAttestationStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("attestations", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS attestation_challenges (challenge TEXT PRIMARY KEY, expires_at INTEGER NOT NULL, data TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS attestations (provider TEXT NOT NULL, account TEXT NOT NULL, user TEXT NOT NULL, data TEXT NOT NULL, PRIMARY KEY(provider, account))",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "attestation_challenges", "data").await?;
+        rotate_column(&pool, &cipher, "attestations", "user").await?;
+        rotate_column(&pool, &cipher, "attestations", "data").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl AttestationStorage for DatabaseAttestationStorage {
+    async fn add_challenge(&self, challenge: Challenge) -> Result<(), Error> {
+        let data = serde_json::to_string(&challenge)?;
+        sqlx::query(
+            "REPLACE INTO attestation_challenges (challenge, expires_at, data) VALUES (?, ?, ?)",
+        )
+        .bind(&challenge.challenge)
+        .bind(challenge.expires_at as i64)
+        .bind(self.cipher.encode(&data))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn take_challenge(&self, challenge: &str) -> Result<Option<Challenge>, Error> {
+        let row = sqlx::query("SELECT data FROM attestation_challenges WHERE challenge = ?")
+            .bind(challenge)
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        // only the request that deleted the row may complete the challenge
+        let deleted = sqlx::query("DELETE FROM attestation_challenges WHERE challenge = ?")
+            .bind(challenge)
+            .execute(&self.pool)
+            .await?;
+        if deleted.rows_affected() == 0 {
+            return Ok(None);
+        }
+        let data = self.cipher.decode(&row.get::<String, _>(0))?;
+        Ok(Some(serde_json::from_str(&data)?))
+    }
+
+    async fn remove_expired(&self, now: u64) -> Result<(), Error> {
+        sqlx::query("DELETE FROM attestation_challenges WHERE expires_at < ?")
+            .bind(now.min(i64::MAX as u64) as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn link(&self, attestation: Attestation) -> Result<(), Error> {
+        let data = serde_json::to_string(&attestation)?;
+        sqlx::query(
+            "REPLACE INTO attestations (provider, account, user, data) VALUES (?, ?, ?, ?)",
+        )
+        .bind(&attestation.provider)
+        .bind(&attestation.account)
+        .bind(self.cipher.encode(&attestation.user))
+        .bind(self.cipher.encode(&data))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn linked(&self, provider: &str, account: &str) -> Result<Option<Attestation>, Error> {
+        let row = sqlx::query("SELECT data FROM attestations WHERE provider = ? AND account = ?")
+            .bind(provider)
+            .bind(account)
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let data = self.cipher.decode(&row.get::<String, _>(0))?;
+        Ok(Some(serde_json::from_str(&data)?))
+    }
+
+    async fn attestations(&self, user: &UserAddress) -> Result<Vec<Attestation>, Error> {
+        let rows =
+            sqlx::query("SELECT data FROM attestations WHERE user = ? ORDER BY provider, account")
+                .bind(self.cipher.encode(user))
+                .fetch_all(&self.pool)
+                .await?;
+        let mut attestations = vec![];
+        for row in rows {
+            let data = self.cipher.decode(&row.get::<String, _>(0))?;
+            attestations.push(serde_json::from_str(&data)?);
+        }
+        Ok(attestations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseAttestationStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        let user = "user".to_string();
+        let challenge = Challenge {
+            challenge: "abc".to_string(),
+            user: user.clone(),
+            provider: "github".to_string(),
+            expires_at: 10,
+        };
+        storage.add_challenge(challenge.clone()).await.unwrap();
+        assert_eq!(
+            storage.take_challenge("abc").await.unwrap(),
+            Some(challenge.clone())
+        );
+        assert!(storage.take_challenge("abc").await.unwrap().is_none());
+        storage.add_challenge(challenge).await.unwrap();
+        storage.remove_expired(11).await.unwrap();
+        assert!(storage.take_challenge("abc").await.unwrap().is_none());
+
+        let attestation = Attestation {
+            user: user.clone(),
+            provider: "github".to_string(),
+            account: "alice".to_string(),
+            verifier: "verifier".to_string(),
+            proof_id: 1,
+            attested_at: 5,
+        };
+        assert!(storage.linked("github", "alice").await.unwrap().is_none());
+        storage.link(attestation.clone()).await.unwrap();
+        assert_eq!(
+            storage.linked("github", "alice").await.unwrap(),
+            Some(attestation.clone())
+        );
+        assert_eq!(
+            storage.attestations(&user).await.unwrap(),
+            vec![attestation]
+        );
+        assert!(
+            storage
+                .attestations(&"other".to_string())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,22 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Unknown provider {0}")]
+    UnknownProvider(String),
+    #[error("Verifier is not trusted")]
+    UnknownVerifier,
+    #[error("Challenge is unknown or expired")]
+    InvalidChallenge,
+    #[error("Account is linked to another user")]
+    AccountLinked,
+    #[error("Identity error: {0}")]
+    IdentityError(#[from] crate::identity::error::Error),
+    #[error("Encryption error: {0}")]
+    EncryptionError(#[from] crate::encryption::error::Error),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[cfg(feature = "sled")]
+    #[error("Storage error: {0}")]
+    StorageError(#[from] crate::kv::error::Error),
+}
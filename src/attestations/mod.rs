@@ -0,0 +1,283 @@
+// Moderator proofs issued for verified external accounts.
+//
+// A user starts an attestation with `POST /attestations/start/:user` and receives a single
+// use challenge. The challenge is passed to a trusted verifier service, which runs the
+// OAuth flow of the provider (e.g. GitHub) and signs that the account belongs to whoever
+// holds the challenge. `POST /attestations/complete/:user` checks the verifier signature,
+// links the account to the user and gives the user a proof of the amount configured for
+// the provider. The verifier acts as the moderator of the proof, so its proofs can be
+// revoked like those of any other moderator. An account can be linked to a single user.
+
+use ethers_core::{rand, utils::keccak256};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    attestations::{error::Error, storage::AttestationStorage},
+    config::AttestationsSection,
+    identity::{IdentityService, ProofId, UserAddress},
+};
+
+pub mod db;
+pub mod error;
+pub mod storage;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Challenge {
+    pub challenge: String,
+    pub user: UserAddress,
+    pub provider: String,
+    pub expires_at: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attestation {
+    pub user: UserAddress,
+    pub provider: String,
+    // account id at the provider, e.g. GitHub login
+    pub account: String,
+    pub verifier: UserAddress,
+    pub proof_id: ProofId,
+    pub attested_at: u64,
+}
+
+// statement of the verifier that the account belongs to the holder of the challenge
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Claim {
+    pub user: UserAddress,
+    pub provider: String,
+    pub account: String,
+    pub challenge: String,
+    pub verifier: UserAddress,
+}
+
+// the same account always gets the same proof id, so repeated attestations replace the proof
+pub fn attestation_proof_id(provider: &str, account: &str) -> ProofId {
+    let hash = keccak256(format!("attestation/{provider}/{account}"));
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash[..8]);
+    ProofId::from_be_bytes(bytes)
+}
+
+pub async fn start(
+    storage: &dyn AttestationStorage,
+    config: &AttestationsSection,
+    user: UserAddress,
+    provider: String,
+    now: u64,
+) -> Result<Challenge, Error> {
+    if !config.providers.contains_key(&provider) {
+        return Err(Error::UnknownProvider(provider));
+    }
+    storage.remove_expired(now).await?;
+    let challenge = Challenge {
+        challenge: hex::encode(rand::random::<[u8; 32]>()),
+        user,
+        provider,
+        expires_at: now.saturating_add(config.challenge_ttl),
+    };
+    storage.add_challenge(challenge.clone()).await?;
+    Ok(challenge)
+}
+
+// links the account attested by the verifier and proves the user. The verifier signature
+// is checked by the caller. An existing proof is only replaced if it gives less IDT.
+pub async fn complete(
+    service: &IdentityService,
+    storage: &dyn AttestationStorage,
+    config: &AttestationsSection,
+    claim: Claim,
+) -> Result<Attestation, Error> {
+    if !config.verifiers.contains(&claim.verifier) {
+        return Err(Error::UnknownVerifier);
+    }
+    let Some(&amount) = config.providers.get(&claim.provider) else {
+        return Err(Error::UnknownProvider(claim.provider));
+    };
+    let now = service.now();
+    match storage.take_challenge(&claim.challenge).await? {
+        Some(c) if c.user == claim.user && c.provider == claim.provider && c.expires_at >= now => {}
+        _ => return Err(Error::InvalidChallenge),
+    }
+    if let Some(linked) = storage.linked(&claim.provider, &claim.account).await? {
+        if linked.user != claim.user {
+            return Err(Error::AccountLinked);
+        }
+    }
+    let attestation = Attestation {
+        proof_id: attestation_proof_id(&claim.provider, &claim.account),
+        user: claim.user,
+        provider: claim.provider,
+        account: claim.account,
+        verifier: claim.verifier,
+        attested_at: now,
+    };
+    storage.link(attestation.clone()).await?;
+    let current = service.proof(&attestation.user).await?;
+    if current.is_none_or(|proof| proof.amount < amount) {
+        service
+            .prove_with_timestamp(
+                attestation.user.clone(),
+                attestation.verifier.clone(),
+                amount,
+                attestation.proof_id,
+                now,
+            )
+            .await?;
+    }
+    Ok(attestation)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::*;
+    use crate::{
+        attestations::storage::InMemoryAttestationStorage,
+        identity::{
+            IdtAmount,
+            tests::{START_TIMESTAMP, USER_A, service_with_mock_clock},
+        },
+    };
+
+    const VERIFIER: &str = "verifier";
+
+    fn config() -> AttestationsSection {
+        AttestationsSection {
+            verifiers: HashSet::from([VERIFIER.to_string()]),
+            providers: HashMap::from([("github".to_string(), IdtAmount::new(100))]),
+            challenge_ttl: 60,
+        }
+    }
+
+    async fn complete_github(
+        service: &IdentityService,
+        storage: &dyn AttestationStorage,
+        user: &str,
+        account: &str,
+        challenge: &str,
+    ) -> Result<Attestation, Error> {
+        let claim = Claim {
+            user: user.to_string(),
+            provider: "github".to_string(),
+            account: account.to_string(),
+            challenge: challenge.to_string(),
+            verifier: VERIFIER.to_string(),
+        };
+        complete(service, storage, &config(), claim).await
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (service, clock) = service_with_mock_clock();
+        let storage = InMemoryAttestationStorage::default();
+        let user_b = "userB".to_string();
+
+        let unknown = start(
+            &storage,
+            &config(),
+            USER_A.to_string(),
+            "twitter".to_string(),
+            START_TIMESTAMP,
+        )
+        .await;
+        assert!(matches!(unknown, Err(Error::UnknownProvider(_))));
+
+        let challenge = start(
+            &storage,
+            &config(),
+            USER_A.to_string(),
+            "github".to_string(),
+            START_TIMESTAMP,
+        )
+        .await
+        .unwrap();
+        assert_eq!(challenge.expires_at, START_TIMESTAMP + 60);
+        // challenge of another user
+        let result =
+            complete_github(&service, &storage, &user_b, "alice", &challenge.challenge).await;
+        assert!(matches!(result, Err(Error::InvalidChallenge)));
+
+        let challenge = start(
+            &storage,
+            &config(),
+            USER_A.to_string(),
+            "github".to_string(),
+            START_TIMESTAMP,
+        )
+        .await
+        .unwrap();
+        let attestation =
+            complete_github(&service, &storage, USER_A, "alice", &challenge.challenge)
+                .await
+                .unwrap();
+        assert_eq!(
+            attestation.proof_id,
+            attestation_proof_id("github", "alice")
+        );
+        let proof = service.proof(&USER_A.to_string()).await.unwrap().unwrap();
+        assert_eq!(proof.moderator, VERIFIER);
+        assert_eq!(proof.amount, IdtAmount::new(100));
+        assert_eq!(
+            storage.attestations(&USER_A.to_string()).await.unwrap(),
+            vec![attestation]
+        );
+
+        // challenge is single use
+        let result =
+            complete_github(&service, &storage, USER_A, "alice", &challenge.challenge).await;
+        assert!(matches!(result, Err(Error::InvalidChallenge)));
+
+        let challenge = start(
+            &storage,
+            &config(),
+            user_b.clone(),
+            "github".to_string(),
+            START_TIMESTAMP,
+        )
+        .await
+        .unwrap();
+        let result =
+            complete_github(&service, &storage, &user_b, "alice", &challenge.challenge).await;
+        assert!(matches!(result, Err(Error::AccountLinked)));
+
+        let challenge = start(
+            &storage,
+            &config(),
+            user_b.clone(),
+            "github".to_string(),
+            START_TIMESTAMP,
+        )
+        .await
+        .unwrap();
+        clock.advance(61);
+        let result =
+            complete_github(&service, &storage, &user_b, "bob", &challenge.challenge).await;
+        assert!(matches!(result, Err(Error::InvalidChallenge)));
+    }
+
+    #[async_std::test]
+    async fn test_unknown_verifier() {
+        let (service, _) = service_with_mock_clock();
+        let storage = InMemoryAttestationStorage::default();
+        let challenge = start(
+            &storage,
+            &config(),
+            USER_A.to_string(),
+            "github".to_string(),
+            START_TIMESTAMP,
+        )
+        .await
+        .unwrap();
+        let claim = Claim {
+            user: USER_A.to_string(),
+            provider: "github".to_string(),
+            account: "alice".to_string(),
+            challenge: challenge.challenge,
+            verifier: "other".to_string(),
+        };
+        let result = complete(&service, &storage, &config(), claim).await;
+        assert!(matches!(result, Err(Error::UnknownVerifier)));
+        assert!(service.proof(&USER_A.to_string()).await.unwrap().is_none());
+    }
+}
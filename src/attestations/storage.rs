@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::{
+    attestations::{Attestation, Challenge, error::Error},
+    identity::UserAddress,
+};
+
+#[async_trait]
+pub trait AttestationStorage: Send + Sync {
+    async fn add_challenge(&self, challenge: Challenge) -> Result<(), Error>;
+    // removes the challenge, so it can be completed only once
+    async fn take_challenge(&self, challenge: &str) -> Result<Option<Challenge>, Error>;
+    // drops challenges that expired before `now`
+    async fn remove_expired(&self, now: u64) -> Result<(), Error>;
+    // replaces the previous link of the same account
+    async fn link(&self, attestation: Attestation) -> Result<(), Error>;
+    async fn linked(&self, provider: &str, account: &str) -> Result<Option<Attestation>, Error>;
+    // attestations of the user ordered by provider and account
+    async fn attestations(&self, user: &UserAddress) -> Result<Vec<Attestation>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryAttestationStorage {
+    // key - challenge
+    challenges: RwLock<HashMap<String, Challenge>>,
+    // key - (provider, account)
+    attestations: RwLock<HashMap<(String, String), Attestation>>,
+}
+
+#[async_trait]
+impl AttestationStorage for InMemoryAttestationStorage {
+    async fn add_challenge(&self, challenge: Challenge) -> Result<(), Error> {
+        self.challenges
+            .write()
+            .await
+            .insert(challenge.challenge.clone(), challenge);
+        Ok(())
+    }
+
+    async fn take_challenge(&self, challenge: &str) -> Result<Option<Challenge>, Error> {
+        Ok(self.challenges.write().await.remove(challenge))
+    }
+
+    async fn remove_expired(&self, now: u64) -> Result<(), Error> {
+        self.challenges
+            .write()
+            .await
+            .retain(|_, c| c.expires_at >= now);
+        Ok(())
+    }
+
+    async fn link(&self, attestation: Attestation) -> Result<(), Error> {
+        let key = (attestation.provider.clone(), attestation.account.clone());
+        self.attestations.write().await.insert(key, attestation);
+        Ok(())
+    }
+
+    async fn linked(&self, provider: &str, account: &str) -> Result<Option<Attestation>, Error> {
+        let key = (provider.to_string(), account.to_string());
+        Ok(self.attestations.read().await.get(&key).cloned())
+    }
+
+    async fn attestations(&self, user: &UserAddress) -> Result<Vec<Attestation>, Error> {
+        let mut attestations: Vec<_> = self
+            .attestations
+            .read()
+            .await
+            .values()
+            .filter(|a| &a.user == user)
+            .cloned()
+            .collect();
+        attestations.sort_by(|a, b| (&a.provider, &a.account).cmp(&(&b.provider, &b.account)));
+        Ok(attestations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_challenges() {
+        let storage = InMemoryAttestationStorage::default();
+        for (challenge, expires_at) in [("a", 10), ("b", 20)] {
+            storage
+                .add_challenge(Challenge {
+                    challenge: challenge.to_string(),
+                    user: "user".to_string(),
+                    provider: "github".to_string(),
+                    expires_at,
+                })
+                .await
+                .unwrap();
+        }
+        storage.remove_expired(15).await.unwrap();
+        assert!(storage.take_challenge("a").await.unwrap().is_none());
+        assert_eq!(
+            storage
+                .take_challenge("b")
+                .await
+                .unwrap()
+                .unwrap()
+                .expires_at,
+            20
+        );
+        assert!(storage.take_challenge("b").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,395 @@
+// load-test harness: builds an in-memory server with a synthetic vouch graph and
+// reports latency percentiles of /idt, /vouch and /punish requests
+//
+// usage: idt-bench [--users N] [--shape tree|chain|star|random] [--degree D]
+//                  [--requests R] [--seed S] [--json]
+//
+// NOTE: tree walk does not memoize branches, so dense random graphs (degree > 1)
+// take exponential time to resolve even with a few dozen users
+
+use std::{
+    collections::HashSet,
+    env, process,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use ethers_core::rand::{Rng, SeedableRng, rngs::StdRng};
+use identity_server::{
+    admins::InMemoryAdminStorage,
+    identity::{
+        IdtAmount, UserAddress,
+        proof::{MAX_IDT_BY_PROOF, prove},
+        vouch::vouch,
+    },
+    routes::{self, State},
+    verify::{expires_in, punish::punish_sign, random_keypair, vouch::vouch_sign},
+};
+use serde_json::json;
+use tide::{
+    Server,
+    http::{Method, Request, Url},
+};
+
+const BASE_URL: &str = "http://localhost";
+// seconds the benchmark signatures stay valid
+const SIGNATURE_TTL: u64 = 600;
+
+#[derive(Clone, Copy, Debug)]
+enum Shape {
+    // every user is vouched by a single earlier user
+    Tree,
+    // user N vouches for user N + 1
+    Chain,
+    // the first user vouches for everyone else
+    Star,
+    // every user vouches for `degree` random users
+    Random,
+}
+
+impl Shape {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "tree" => Some(Shape::Tree),
+            "chain" => Some(Shape::Chain),
+            "star" => Some(Shape::Star),
+            "random" => Some(Shape::Random),
+            _ => None,
+        }
+    }
+}
+
+struct Options {
+    users: usize,
+    shape: Shape,
+    degree: usize,
+    requests: usize,
+    seed: u64,
+    json: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            users: 1000,
+            shape: Shape::Tree,
+            degree: 1,
+            requests: 200,
+            seed: 42,
+            json: false,
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: idt-bench [--users N] [--shape tree|chain|star|random] [--degree D] [--requests R] [--seed S] [--json]"
+    );
+    process::exit(2);
+}
+
+fn parse_options() -> Options {
+    let mut options = Options::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--json" {
+            options.json = true;
+            continue;
+        }
+        if arg == "--help" || arg == "-h" {
+            usage();
+        }
+        let Some(value) = args.next() else {
+            usage();
+        };
+        let number = || value.parse::<u64>().unwrap_or_else(|_| usage());
+        match arg.as_str() {
+            "--users" => options.users = number() as usize,
+            "--degree" => options.degree = number() as usize,
+            "--requests" => options.requests = number() as usize,
+            "--seed" => options.seed = number(),
+            "--shape" => options.shape = Shape::parse(&value).unwrap_or_else(|| usage()),
+            _ => usage(),
+        }
+    }
+    if options.users < 2 || options.requests == 0 {
+        usage();
+    }
+    options
+}
+
+struct User {
+    private_key: String,
+    address: UserAddress,
+}
+
+fn generate_edges(options: &Options, rng: &mut StdRng) -> Vec<(usize, usize)> {
+    let n = options.users;
+    match options.shape {
+        Shape::Tree => (1..n).map(|i| (rng.gen_range(0..i), i)).collect(),
+        Shape::Chain => (1..n).map(|i| (i - 1, i)).collect(),
+        Shape::Star => (1..n).map(|i| (0, i)).collect(),
+        Shape::Random => {
+            let mut edges = HashSet::new();
+            for from in 0..n {
+                for _ in 0..options.degree {
+                    let to = rng.gen_range(0..n);
+                    if to != from {
+                        edges.insert((from, to));
+                    }
+                }
+            }
+            edges.into_iter().collect()
+        }
+    }
+}
+
+struct Report {
+    endpoint: &'static str,
+    samples: Vec<Duration>,
+    errors: usize,
+}
+
+impl Report {
+    fn new(endpoint: &'static str) -> Self {
+        Self {
+            endpoint,
+            samples: vec![],
+            errors: 0,
+        }
+    }
+
+    fn percentile(&self, p: f64) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+        sorted[index]
+    }
+
+    fn mean(&self) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    fn max(&self) -> Duration {
+        self.samples.iter().max().copied().unwrap_or_default()
+    }
+}
+
+async fn send(
+    server: &Server<State>,
+    method: Method,
+    path: &str,
+    body: Option<serde_json::Value>,
+) -> (Duration, bool) {
+    let mut req = Request::new(method, Url::parse(&format!("{BASE_URL}{path}")).unwrap());
+    if let Some(body) = body {
+        req.set_body(body.to_string());
+        req.set_content_type(tide::http::mime::JSON);
+    }
+    let start = Instant::now();
+    let res: tide::http::Response = server.respond(req).await.unwrap();
+    let elapsed = start.elapsed();
+    (elapsed, res.status().is_success())
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn print_report(options: &Options, edges: usize, setup: Duration, reports: &[Report]) {
+    if options.json {
+        let endpoints: Vec<serde_json::Value> = reports
+            .iter()
+            .map(|r| {
+                json!({
+                    "endpoint": r.endpoint,
+                    "requests": r.samples.len(),
+                    "errors": r.errors,
+                    "p50_ms": ms(r.percentile(0.5)),
+                    "p99_ms": ms(r.percentile(0.99)),
+                    "mean_ms": ms(r.mean()),
+                    "max_ms": ms(r.max()),
+                })
+            })
+            .collect();
+        let report = json!({
+            "users": options.users,
+            "edges": edges,
+            "shape": format!("{:?}", options.shape).to_lowercase(),
+            "seed": options.seed,
+            "setup_ms": ms(setup),
+            "endpoints": endpoints,
+        });
+        println!("{report:#}");
+        return;
+    }
+    println!(
+        "graph: {} users, {} edges, shape {:?}, seed {}, setup {:.1} ms",
+        options.users,
+        edges,
+        options.shape,
+        options.seed,
+        ms(setup)
+    );
+    println!(
+        "{:<10} {:>8} {:>7} {:>10} {:>10} {:>10} {:>10}",
+        "endpoint", "requests", "errors", "p50 ms", "p99 ms", "mean ms", "max ms"
+    );
+    for r in reports {
+        println!(
+            "{:<10} {:>8} {:>7} {:>10.3} {:>10.3} {:>10.3} {:>10.3}",
+            r.endpoint,
+            r.samples.len(),
+            r.errors,
+            ms(r.percentile(0.5)),
+            ms(r.percentile(0.99)),
+            ms(r.mean()),
+            ms(r.max())
+        );
+    }
+}
+
+#[async_std::main]
+async fn main() {
+    let options = parse_options();
+    let mut rng = StdRng::seed_from_u64(options.seed);
+
+    let (moderator_key, moderator) = random_keypair();
+    let state = State {
+        admin_storage: Arc::new(InMemoryAdminStorage::new(
+            HashSet::new(),
+            HashSet::from([moderator.clone()]),
+        )),
+        ..Default::default()
+    };
+    let service = state.identity_service.clone();
+    let nonce_manager = state.nonce_manager.clone();
+    let domain = state.server_identity.address.clone();
+    let mut server = tide::with_state(state);
+    routes::setup_routes(&mut server);
+
+    // populate the graph directly through the service, only requests are measured
+    let setup_start = Instant::now();
+    let users: Vec<User> = (0..options.users)
+        .map(|_| {
+            let (private_key, address) = random_keypair();
+            User {
+                private_key,
+                address,
+            }
+        })
+        .collect();
+    for (i, user) in users.iter().enumerate() {
+        let amount = IdtAmount::new(rng.gen_range(0..=MAX_IDT_BY_PROOF.units()));
+        prove(
+            &service,
+            user.address.clone(),
+            moderator.clone(),
+            amount,
+            i as u64,
+        )
+        .await
+        .expect("proof should be stored");
+    }
+    let edges = generate_edges(&options, &mut rng);
+    for (from, to) in &edges {
+        vouch(
+            &service,
+            users[*from].address.clone(),
+            users[*to].address.clone(),
+        )
+        .await
+        .expect("vouch should be stored");
+    }
+    let setup = setup_start.elapsed();
+
+    let mut idt = Report::new("/idt");
+    for _ in 0..options.requests {
+        let user = &users[rng.gen_range(0..users.len())];
+        let (elapsed, ok) = send(
+            &server,
+            Method::Get,
+            &format!("/idt/{}", user.address),
+            None,
+        )
+        .await;
+        idt.samples.push(elapsed);
+        idt.errors += usize::from(!ok);
+    }
+
+    let mut vouch_report = Report::new("/vouch");
+    for _ in 0..options.requests {
+        let from = &users[rng.gen_range(0..users.len())];
+        let to = &users[rng.gen_range(0..users.len())];
+        // signing is not a part of the measured latency
+        let signature = vouch_sign(
+            &from.private_key,
+            &domain,
+            to.address.clone(),
+            expires_in(SIGNATURE_TTL),
+            &*nonce_manager,
+        )
+        .await
+        .expect("vouch should be signed");
+        let body = json!({
+            "from": { "user": from.address },
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let (elapsed, ok) = send(
+            &server,
+            Method::Post,
+            &format!("/vouch/{}", to.address),
+            Some(body),
+        )
+        .await;
+        vouch_report.samples.push(elapsed);
+        vouch_report.errors += usize::from(!ok);
+    }
+
+    let mut punish = Report::new("/punish");
+    for i in 0..options.requests {
+        let user = &users[rng.gen_range(0..users.len())];
+        let amount = IdtAmount::new(rng.gen_range(1..=MAX_IDT_BY_PROOF.units()));
+        let proof_id = (options.users + i) as u64;
+        let signature = punish_sign(
+            &moderator_key,
+            &domain,
+            user.address.clone(),
+            amount,
+            proof_id,
+            expires_in(SIGNATURE_TTL),
+            &*nonce_manager,
+        )
+        .await
+        .expect("punishment should be signed");
+        let body = json!({
+            "from": moderator,
+            "amount": amount,
+            "proof_id": proof_id,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let (elapsed, ok) = send(
+            &server,
+            Method::Post,
+            &format!("/punish/{}", user.address),
+            Some(body),
+        )
+        .await;
+        punish.samples.push(elapsed);
+        punish.errors += usize::from(!ok);
+    }
+
+    print_report(&options, edges.len(), setup, &[idt, vouch_report, punish]);
+}
@@ -0,0 +1,82 @@
+// copies the data of the SQL storage to a sled database, so the server can be switched
+// to `STORAGE=sled` without losing data
+//
+// usage: migrate-sled [--database URL] [--path PATH]
+//
+// the database defaults to the MYSQL_* variables and the path to SLED_PATH, encrypted
+// addresses are decrypted with DB_ENCRYPTION_KEYS
+
+use std::{collections::HashSet, env, process};
+
+use identity_server::{
+    encryption::FieldCipher,
+    kv::{SledStorage, migrate::migrate},
+    storage::{DEFAULT_SLED_PATH, setup_database_url},
+};
+
+struct Options {
+    database: String,
+    path: String,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: migrate-sled [--database URL] [--path PATH]");
+    process::exit(2);
+}
+
+fn parse_options() -> Options {
+    let path = env::var("SLED_PATH").unwrap_or_default();
+    let mut options = Options {
+        database: setup_database_url(),
+        path: if path.is_empty() {
+            DEFAULT_SLED_PATH.to_string()
+        } else {
+            path
+        },
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--help" || arg == "-h" {
+            usage();
+        }
+        let Some(value) = args.next() else {
+            usage();
+        };
+        match arg.as_str() {
+            "--database" => options.database = value,
+            "--path" => options.path = value,
+            _ => usage(),
+        }
+    }
+    options
+}
+
+#[async_std::main]
+async fn main() {
+    let _ = dotenv::dotenv();
+    let options = parse_options();
+    let cipher = FieldCipher::from_env().unwrap_or_else(|e| {
+        eprintln!("Invalid encryption keys: {}", e);
+        process::exit(1);
+    });
+    // admins and moderators are copied from the database
+    let storage =
+        SledStorage::open(&options.path, HashSet::new(), HashSet::new()).unwrap_or_else(|e| {
+            eprintln!("Failed to open {}: {}", options.path, e);
+            process::exit(1);
+        });
+    let copied = match migrate(&options.database, &cipher, &storage).await {
+        Ok(copied) => copied,
+        Err(e) => {
+            eprintln!("Migration failed: {}", e);
+            process::exit(1);
+        }
+    };
+    if let Err(e) = storage.flush().await {
+        eprintln!("Failed to flush {}: {}", options.path, e);
+        process::exit(1);
+    }
+    for (table, rows) in copied {
+        println!("{table}: {rows}");
+    }
+}
@@ -0,0 +1,217 @@
+use async_std::sync::Mutex;
+use async_trait::async_trait;
+use sqlx::{Row, any::AnyRow};
+
+use crate::{
+    changes::storage::{ChangeLog, Version},
+    encryption::{FieldCipher, rotate_column},
+    events::{Event, RecordedEvent},
+    identity::{UserAddress, error::Error},
+    pools::{self, MeteredPool},
+};
+
+// changes are stored as JSON, encrypted as a whole since they contain user addresses
+pub struct DatabaseChangeLog {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+    // appends are serialized, so changes become visible in the order of their seq
+    last_seq: Mutex<u64>,
+}
+
+impl DatabaseChangeLog {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("changes", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS changes (seq INTEGER PRIMARY KEY, recorded_at INTEGER NOT NULL, data TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS change_versions (user TEXT PRIMARY KEY, seq INTEGER NOT NULL, recorded_at INTEGER NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS change_users (user TEXT NOT NULL, seq INTEGER NOT NULL, PRIMARY KEY(user, seq))",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "changes", "data").await?;
+        rotate_column(&pool, &cipher, "change_versions", "user").await?;
+        rotate_column(&pool, &cipher, "change_users", "user").await?;
+        let last_seq = sqlx::query("SELECT seq FROM changes ORDER BY seq DESC LIMIT 1")
+            .fetch_optional(&pool)
+            .await?
+            .map(|row| row.get::<i64, _>(0) as u64)
+            .unwrap_or_default();
+        Ok(Self {
+            pool,
+            cipher,
+            last_seq: Mutex::new(last_seq),
+        })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+impl DatabaseChangeLog {
+    // rows of (seq, recorded_at, data)
+    fn decode_rows(&self, rows: Vec<AnyRow>) -> Result<Vec<RecordedEvent>, Error> {
+        let mut changes = vec![];
+        for row in rows {
+            let data = self.cipher.decode(&row.get::<String, _>(2))?;
+            changes.push(RecordedEvent {
+                seq: row.get::<i64, _>(0) as u64,
+                recorded_at: row.get::<i64, _>(1) as u64,
+                event: serde_json::from_str(&data)?,
+            });
+        }
+        Ok(changes)
+    }
+}
+
+#[async_trait]
+impl ChangeLog for DatabaseChangeLog {
+    async fn append(&self, change: Event, recorded_at: u64) -> Result<RecordedEvent, Error> {
+        let mut last_seq = self.last_seq.lock().await;
+        let recorded = RecordedEvent {
+            seq: *last_seq + 1,
+            recorded_at,
+            event: change,
+        };
+        let data = serde_json::to_string(&recorded.event)?;
+        sqlx::query("INSERT INTO changes (seq, recorded_at, data) VALUES (?, ?, ?)")
+            .bind(recorded.seq as i64)
+            .bind(recorded.recorded_at as i64)
+            .bind(self.cipher.encode(&data))
+            .execute(&self.pool)
+            .await?;
+        for user in recorded.event.users() {
+            sqlx::query("REPLACE INTO change_versions (user, seq, recorded_at) VALUES (?, ?, ?)")
+                .bind(self.cipher.encode(user))
+                .bind(recorded.seq as i64)
+                .bind(recorded.recorded_at as i64)
+                .execute(&self.pool)
+                .await?;
+            sqlx::query("INSERT INTO change_users (user, seq) VALUES (?, ?)")
+                .bind(self.cipher.encode(user))
+                .bind(recorded.seq as i64)
+                .execute(&self.pool)
+                .await?;
+        }
+        *last_seq = recorded.seq;
+        Ok(recorded)
+    }
+
+    async fn changes(&self, after_seq: u64, limit: usize) -> Result<Vec<RecordedEvent>, Error> {
+        let rows = sqlx::query(
+            "SELECT seq, recorded_at, data FROM changes WHERE seq > ? ORDER BY seq LIMIT ?",
+        )
+        .bind(after_seq.min(i64::MAX as u64) as i64)
+        .bind(limit.min(i64::MAX as usize) as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        self.decode_rows(rows)
+    }
+
+    async fn latest(&self) -> Result<Option<Version>, Error> {
+        let row = sqlx::query("SELECT seq, recorded_at FROM changes ORDER BY seq DESC LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| Version {
+            seq: row.get::<i64, _>(0) as u64,
+            recorded_at: row.get::<i64, _>(1) as u64,
+        }))
+    }
+
+    async fn user_version(&self, user: &UserAddress) -> Result<Option<Version>, Error> {
+        let row = sqlx::query("SELECT seq, recorded_at FROM change_versions WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| Version {
+            seq: row.get::<i64, _>(0) as u64,
+            recorded_at: row.get::<i64, _>(1) as u64,
+        }))
+    }
+
+    async fn user_changes(
+        &self,
+        user: &UserAddress,
+        after_seq: u64,
+        limit: usize,
+    ) -> Result<Vec<RecordedEvent>, Error> {
+        let rows = sqlx::query(
+            "SELECT c.seq, c.recorded_at, c.data FROM change_users u JOIN changes c ON c.seq = u.seq WHERE u.user = ? AND u.seq > ? ORDER BY u.seq LIMIT ?",
+        )
+        .bind(self.cipher.encode(user))
+        .bind(after_seq.min(i64::MAX as u64) as i64)
+        .bind(limit.min(i64::MAX as usize) as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        self.decode_rows(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let log = DatabaseChangeLog::new("sqlite::memory:").await.unwrap();
+        assert!(log.changes(0, 10).await.unwrap().is_empty());
+        for seq in 1..=3 {
+            let change = Event::Vouch {
+                voucher: "a".to_string(),
+                vouchee: format!("user{seq}"),
+                timestamp: seq,
+            };
+            let recorded = log.append(change.clone(), seq * 10).await.unwrap();
+            assert_eq!(recorded.seq, seq);
+            assert_eq!(recorded.event, change);
+        }
+
+        let changes = log.changes(1, 1).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].seq, 2);
+        assert_eq!(changes[0].recorded_at, 20);
+        assert_eq!(log.changes(0, 10).await.unwrap().len(), 3);
+        assert!(log.changes(3, 10).await.unwrap().is_empty());
+        assert_eq!(log.latest().await.unwrap().unwrap().seq, 3);
+        let version = log.user_version(&"a".to_string()).await.unwrap().unwrap();
+        assert_eq!(version.seq, 3);
+        assert_eq!(version.recorded_at, 30);
+        assert_eq!(
+            log.user_version(&"user1".to_string())
+                .await
+                .unwrap()
+                .unwrap()
+                .seq,
+            1
+        );
+        assert_eq!(log.user_version(&"b".to_string()).await.unwrap(), None);
+
+        let changes = log.user_changes(&"a".to_string(), 1, 10).await.unwrap();
+        assert_eq!(
+            changes.iter().map(|c| c.seq).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(changes[0].recorded_at, 20);
+        let changes = log.user_changes(&"user2".to_string(), 0, 10).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].seq, 2);
+        assert!(
+            log.user_changes(&"a".to_string(), 0, 0)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use serde::Serialize;
+
+use crate::{
+    changes::storage::ChangeLog,
+    events::projection::Projection,
+    identity::{ModeratorProof, UserAddress, error::Error},
+};
+
+// changes read from the log at once while building the diff
+const PAGE_SIZE: usize = 1000;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Edge {
+    pub voucher: UserAddress,
+    pub vouchee: UserAddress,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Proof {
+    pub user: UserAddress,
+    #[serde(flatten)]
+    pub proof: ModeratorProof,
+}
+
+// net difference of the vouch graph between two points in time. Refreshed vouches are not
+// listed, a replaced proof is listed as removed and added.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GraphDiff {
+    pub added_edges: Vec<Edge>,
+    pub removed_edges: Vec<Edge>,
+    pub added_proofs: Vec<Proof>,
+    pub removed_proofs: Vec<Proof>,
+    // changes recorded within the window
+    pub changes: u64,
+}
+
+// compares the state after all changes recorded up to `from_ts` with the state after all
+// changes recorded up to `to_ts`
+pub async fn diff(log: &dyn ChangeLog, from_ts: u64, to_ts: u64) -> Result<GraphDiff, Error> {
+    let mut state = Projection::default();
+    let mut before = None;
+    let mut changes = 0;
+    let mut seq = 0;
+    'pages: loop {
+        let page = log.changes(seq, PAGE_SIZE).await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        seq = last.seq;
+        for recorded in page {
+            if recorded.recorded_at > to_ts {
+                break 'pages;
+            }
+            if recorded.recorded_at > from_ts {
+                if before.is_none() {
+                    before = Some(state.clone());
+                }
+                changes += 1;
+            }
+            state.apply(&recorded.event);
+        }
+    }
+    let before = before.unwrap_or_else(|| state.clone());
+    Ok(GraphDiff {
+        added_edges: missing_edges(&state, &before),
+        removed_edges: missing_edges(&before, &state),
+        added_proofs: missing_proofs(&state, &before),
+        removed_proofs: missing_proofs(&before, &state),
+        changes,
+    })
+}
+
+// edges of `state` that are not in `other`
+fn missing_edges(state: &Projection, other: &Projection) -> Vec<Edge> {
+    let mut edges = vec![];
+    for (voucher, vouchees) in &state.vouchees {
+        let other_vouchees = other.vouchees.get(voucher);
+        for (vouchee, timestamp) in vouchees {
+            if other_vouchees.is_some_and(|v| v.contains_key(vouchee)) {
+                continue;
+            }
+            edges.push(Edge {
+                voucher: voucher.clone(),
+                vouchee: vouchee.clone(),
+                timestamp: *timestamp,
+            });
+        }
+    }
+    edges
+}
+
+// proofs of `state` that are missing or different in `other`
+fn missing_proofs(state: &Projection, other: &Projection) -> Vec<Proof> {
+    state
+        .proofs
+        .iter()
+        .filter(|(user, proof)| other.proofs.get(*user) != Some(proof))
+        .map(|(user, proof)| Proof {
+            user: user.clone(),
+            proof: proof.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{changes::storage::InMemoryChangeLog, events::Event, identity::IdtAmount};
+
+    fn proof(amount: u64) -> ModeratorProof {
+        ModeratorProof {
+            moderator: "moderator".to_string(),
+            amount: IdtAmount::new(amount),
+            proof_id: 1,
+            timestamp: 5,
+        }
+    }
+
+    fn vouch(voucher: &str, vouchee: &str, timestamp: u64) -> Event {
+        Event::Vouch {
+            voucher: voucher.to_string(),
+            vouchee: vouchee.to_string(),
+            timestamp,
+        }
+    }
+
+    #[async_std::test]
+    async fn test_diff() {
+        let log = InMemoryChangeLog::default();
+        let events = [
+            (10, vouch("a", "b", 10)),
+            (10, vouch("a", "c", 10)),
+            (
+                10,
+                Event::SetProof {
+                    user: "a".to_string(),
+                    proof: proof(100),
+                },
+            ),
+            // window starts
+            (20, vouch("a", "b", 20)),
+            (
+                20,
+                Event::RemoveVouch {
+                    voucher: "a".to_string(),
+                    vouchee: "c".to_string(),
+                },
+            ),
+            (30, vouch("b", "c", 30)),
+            (30, vouch("c", "d", 30)),
+            (
+                30,
+                Event::RemoveVouch {
+                    voucher: "c".to_string(),
+                    vouchee: "d".to_string(),
+                },
+            ),
+            (
+                30,
+                Event::SetProof {
+                    user: "a".to_string(),
+                    proof: proof(200),
+                },
+            ),
+            (
+                30,
+                Event::SetProof {
+                    user: "b".to_string(),
+                    proof: proof(300),
+                },
+            ),
+            // window ends
+            (40, vouch("d", "e", 40)),
+        ];
+        for (recorded_at, event) in events {
+            log.append(event, recorded_at).await.unwrap();
+        }
+
+        let window = diff(&log, 10, 30).await.unwrap();
+        assert_eq!(window.changes, 7);
+        // the refreshed vouch for "b" and the vouch added and removed within the window are
+        // not listed
+        assert_eq!(
+            window.added_edges,
+            vec![Edge {
+                voucher: "b".to_string(),
+                vouchee: "c".to_string(),
+                timestamp: 30,
+            }]
+        );
+        assert_eq!(
+            window.removed_edges,
+            vec![Edge {
+                voucher: "a".to_string(),
+                vouchee: "c".to_string(),
+                timestamp: 10,
+            }]
+        );
+        assert_eq!(
+            window
+                .added_proofs
+                .iter()
+                .map(|p| (p.user.as_str(), p.proof.amount.units()))
+                .collect::<Vec<_>>(),
+            vec![("a", 200), ("b", 300)]
+        );
+        assert_eq!(
+            window
+                .removed_proofs
+                .iter()
+                .map(|p| (p.user.as_str(), p.proof.amount.units()))
+                .collect::<Vec<_>>(),
+            vec![("a", 100)]
+        );
+
+        assert_eq!(diff(&log, 40, 50).await.unwrap(), GraphDiff::default());
+    }
+}
@@ -0,0 +1,12 @@
+// Change log of vouches, proofs and penalties for mirrors and federated peers.
+//
+// `ChangeRecorder` wraps the vouch, proof and penalty storages and appends every applied
+// mutation to the change log. Peers poll `GET /changes?since=<cursor>` and apply the
+// returned changes in order, passing the cursor of the response to the next poll.
+// Genesis balances are loaded from the config on every start, so they are not recorded.
+// `GET /diff` replays the log to compare the vouch graph at two points in time.
+
+pub mod db;
+pub mod diff;
+pub mod recorder;
+pub mod storage;
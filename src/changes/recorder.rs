@@ -0,0 +1,343 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    changes::storage::ChangeLog,
+    events::Event,
+    identity::{
+        IdtAmount, ModeratorProof, SystemPenalty, UserAddress, clock::Clock, error::Error,
+        proof::storage::ProofStorage, punish::storage::PenaltyStorage,
+        vouch::storage::VouchStorage,
+    },
+    storage::Storage,
+};
+
+// vouch, proof and penalty storage that appends every mutation of the wrapped storages to
+// the change log. A change is recorded only after the wrapped storage accepted it.
+pub struct ChangeRecorder {
+    vouches: Arc<dyn VouchStorage>,
+    proofs: Arc<dyn ProofStorage>,
+    penalties: Arc<dyn PenaltyStorage>,
+    log: Arc<dyn ChangeLog>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ChangeRecorder {
+    pub fn new(
+        vouches: Arc<dyn VouchStorage>,
+        proofs: Arc<dyn ProofStorage>,
+        penalties: Arc<dyn PenaltyStorage>,
+        log: Arc<dyn ChangeLog>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            vouches,
+            proofs,
+            penalties,
+            log,
+            clock,
+        }
+    }
+
+    async fn record(&self, change: Event) -> Result<(), Error> {
+        self.log.append(change, self.clock.now()).await?;
+        Ok(())
+    }
+}
+
+// replaces vouch, proof and penalty storages of the bundle with a recorder writing to
+// its change log
+pub fn record_changes(mut storage: Storage, clock: Arc<dyn Clock>) -> Storage {
+    let recorder = Arc::new(ChangeRecorder::new(
+        storage.vouch_storage,
+        storage.proof_storage,
+        storage.penalty_storage,
+        storage.change_log.clone(),
+        clock,
+    ));
+    storage.vouch_storage = recorder.clone();
+    storage.proof_storage = recorder.clone();
+    storage.penalty_storage = recorder;
+    storage
+}
+
+#[async_trait]
+impl VouchStorage for ChangeRecorder {
+    async fn vouch(&self, from: UserAddress, to: UserAddress, timestamp: u64) -> Result<(), Error> {
+        self.vouches
+            .vouch(from.clone(), to.clone(), timestamp)
+            .await?;
+        self.record(Event::Vouch {
+            voucher: from,
+            vouchee: to,
+            timestamp,
+        })
+        .await
+    }
+
+    async fn vouchers_with_time(
+        &self,
+        user: &UserAddress,
+    ) -> Result<HashMap<UserAddress, u64>, Error> {
+        self.vouches.vouchers_with_time(user).await
+    }
+
+    async fn vouchees_with_time(
+        &self,
+        user: &UserAddress,
+    ) -> Result<HashMap<UserAddress, u64>, Error> {
+        self.vouches.vouchees_with_time(user).await
+    }
+
+    async fn remove_vouch(&self, voucher: UserAddress, vouchee: UserAddress) -> Result<(), Error> {
+        self.vouches
+            .remove_vouch(voucher.clone(), vouchee.clone())
+            .await?;
+        self.record(Event::RemoveVouch { voucher, vouchee }).await
+    }
+
+    async fn all_vouches(&self) -> Result<Vec<(UserAddress, UserAddress, u64)>, Error> {
+        self.vouches.all_vouches().await
+    }
+}
+
+#[async_trait]
+impl ProofStorage for ChangeRecorder {
+    async fn set_genesis(&self, users: HashMap<UserAddress, IdtAmount>) -> Result<(), Error> {
+        self.proofs.set_genesis(users).await
+    }
+
+    async fn genesis_balance(&self, user: &UserAddress) -> Result<Option<IdtAmount>, Error> {
+        self.proofs.genesis_balance(user).await
+    }
+
+    async fn genesis(&self) -> Result<HashMap<UserAddress, IdtAmount>, Error> {
+        self.proofs.genesis().await
+    }
+
+    async fn set_proof(&self, user: UserAddress, proof: ModeratorProof) -> Result<(), Error> {
+        self.proofs.set_proof(user.clone(), proof.clone()).await?;
+        self.record(Event::SetProof { user, proof }).await
+    }
+
+    async fn set_proofs(&self, proofs: Vec<(UserAddress, ModeratorProof)>) -> Result<(), Error> {
+        self.proofs.set_proofs(proofs.clone()).await?;
+        for (user, proof) in proofs {
+            self.record(Event::SetProof { user, proof }).await?;
+        }
+        Ok(())
+    }
+
+    async fn proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        self.proofs.proof(user).await
+    }
+
+    async fn remove_proof(&self, user: &UserAddress) -> Result<(), Error> {
+        self.proofs.remove_proof(user).await?;
+        self.record(Event::RemoveProof { user: user.clone() }).await
+    }
+
+    async fn proven_users(&self) -> Result<HashSet<UserAddress>, Error> {
+        self.proofs.proven_users().await
+    }
+
+    async fn revoke_proofs(&self, moderator: &UserAddress) -> Result<Vec<UserAddress>, Error> {
+        let users = self.proofs.revoke_proofs(moderator).await?;
+        if !users.is_empty() {
+            self.record(Event::RevokeProofs {
+                moderator: moderator.clone(),
+            })
+            .await?;
+        }
+        Ok(users)
+    }
+
+    async fn revoked_proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        self.proofs.revoked_proof(user).await
+    }
+}
+
+#[async_trait]
+impl PenaltyStorage for ChangeRecorder {
+    async fn set_moderator_penalty(
+        &self,
+        user: UserAddress,
+        proof: ModeratorProof,
+    ) -> Result<(), Error> {
+        self.penalties
+            .set_moderator_penalty(user.clone(), proof.clone())
+            .await?;
+        self.record(Event::SetModeratorPenalty {
+            user,
+            penalty: proof,
+        })
+        .await
+    }
+
+    async fn set_forgotten_penalty(
+        &self,
+        user: UserAddress,
+        vouchee: UserAddress,
+        penalty: SystemPenalty,
+    ) -> Result<(), Error> {
+        self.penalties
+            .set_forgotten_penalty(user.clone(), vouchee.clone(), penalty.clone())
+            .await?;
+        self.record(Event::SetForgottenPenalty {
+            user,
+            forgotten: vouchee,
+            penalty,
+        })
+        .await
+    }
+
+    async fn remove_forgotten(
+        &self,
+        user: UserAddress,
+        forgotten: &UserAddress,
+    ) -> Result<(), Error> {
+        self.penalties
+            .remove_forgotten(user.clone(), forgotten)
+            .await?;
+        self.record(Event::RemoveForgottenPenalty {
+            user,
+            forgotten: forgotten.clone(),
+        })
+        .await
+    }
+
+    async fn moderator_penalty(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        self.penalties.moderator_penalty(user).await
+    }
+
+    async fn remove_moderator_penalty(&self, user: &UserAddress) -> Result<(), Error> {
+        self.penalties.remove_moderator_penalty(user).await?;
+        self.record(Event::RemoveModeratorPenalty { user: user.clone() })
+            .await
+    }
+
+    async fn forgotten_penalty(
+        &self,
+        user: &UserAddress,
+        forgotten: &UserAddress,
+    ) -> Result<Option<SystemPenalty>, Error> {
+        self.penalties.forgotten_penalty(user, forgotten).await
+    }
+
+    async fn forgotten_users(&self, user: &UserAddress) -> Result<HashSet<UserAddress>, Error> {
+        self.penalties.forgotten_users(user).await
+    }
+
+    async fn all_moderator_penalties(&self) -> Result<Vec<(UserAddress, ModeratorProof)>, Error> {
+        self.penalties.all_moderator_penalties().await
+    }
+
+    async fn recent_moderator_penalties(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<(UserAddress, ModeratorProof)>, Error> {
+        self.penalties.recent_moderator_penalties(limit).await
+    }
+
+    async fn all_forgotten_penalties(
+        &self,
+    ) -> Result<Vec<(UserAddress, UserAddress, SystemPenalty)>, Error> {
+        self.penalties.all_forgotten_penalties().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        changes::storage::InMemoryChangeLog,
+        identity::{
+            IdentityService,
+            forget::forget,
+            proof::{prove, storage::InMemoryProofStorage},
+            punish::{punish, storage::InMemoryPenaltyStorage},
+            tests::{MODERATOR, PROOF_ID, START_TIMESTAMP, USER_A},
+            vouch::{storage::InMemoryVouchStorage, vouch},
+        },
+    };
+
+    #[async_std::test]
+    async fn test_records_mutations() {
+        let log = Arc::new(InMemoryChangeLog::default());
+        let (service, _) = crate::identity::tests::service_with_mock_clock();
+        let recorder = Arc::new(ChangeRecorder::new(
+            Arc::new(InMemoryVouchStorage::default()),
+            Arc::new(InMemoryProofStorage::default()),
+            Arc::new(InMemoryPenaltyStorage::default()),
+            log.clone(),
+            service.clock.clone(),
+        ));
+        let service = IdentityService {
+            vouches: recorder.clone(),
+            proofs: recorder.clone(),
+            penalties: recorder,
+            ..service
+        };
+        let user_b = "userB".to_string();
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+        punish(
+            &service,
+            user_b.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(10),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        forget(&service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+
+        let changes = log.changes(0, 100).await.unwrap();
+        assert!(changes.iter().all(|c| c.recorded_at == START_TIMESTAMP));
+        assert!(matches!(&changes[0].event, Event::SetProof { user, .. } if user == USER_A));
+        assert_eq!(
+            changes[1].event,
+            Event::Vouch {
+                voucher: USER_A.to_string(),
+                vouchee: user_b.clone(),
+                timestamp: START_TIMESTAMP,
+            }
+        );
+        assert!(changes.iter().any(
+            |c| matches!(&c.event, Event::SetModeratorPenalty { user, .. } if user == &user_b)
+        ));
+        assert!(changes.iter().any(|c| c.event
+            == Event::RemoveVouch {
+                voucher: USER_A.to_string(),
+                vouchee: user_b.clone(),
+            }));
+        assert!(
+            changes
+                .iter()
+                .any(|c| matches!(&c.event, Event::SetForgottenPenalty { forgotten, .. } if forgotten == &user_b))
+        );
+        assert!(
+            service
+                .vouchers_with_time(&user_b)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::{
+    events::{Event, RecordedEvent},
+    identity::{UserAddress, error::Error},
+};
+
+// position of the latest change in the log
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Version {
+    pub seq: u64,
+    pub recorded_at: u64,
+}
+
+impl From<&RecordedEvent> for Version {
+    fn from(recorded: &RecordedEvent) -> Self {
+        Self {
+            seq: recorded.seq,
+            recorded_at: recorded.recorded_at,
+        }
+    }
+}
+
+#[async_trait]
+pub trait ChangeLog: Send + Sync {
+    // stores the change with the next sequence number and returns it
+    async fn append(&self, change: Event, recorded_at: u64) -> Result<RecordedEvent, Error>;
+    // at most `limit` changes after `after_seq`, ordered by seq
+    async fn changes(&self, after_seq: u64, limit: usize) -> Result<Vec<RecordedEvent>, Error>;
+    // latest change of the whole log, None if nothing was recorded yet
+    async fn latest(&self) -> Result<Option<Version>, Error>;
+    // latest change listing the user, see `Event::users`
+    async fn user_version(&self, user: &UserAddress) -> Result<Option<Version>, Error>;
+    // at most `limit` changes listing the user after `after_seq`, ordered by seq
+    async fn user_changes(
+        &self,
+        user: &UserAddress,
+        after_seq: u64,
+        limit: usize,
+    ) -> Result<Vec<RecordedEvent>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryChangeLog {
+    changes: RwLock<Vec<RecordedEvent>>,
+    // seqs of the changes listing the user
+    users: RwLock<HashMap<UserAddress, Vec<u64>>>,
+}
+
+#[async_trait]
+impl ChangeLog for InMemoryChangeLog {
+    async fn append(&self, change: Event, recorded_at: u64) -> Result<RecordedEvent, Error> {
+        let mut changes = self.changes.write().await;
+        let recorded = RecordedEvent {
+            seq: changes.len() as u64 + 1,
+            recorded_at,
+            event: change,
+        };
+        let mut users = self.users.write().await;
+        for user in recorded.event.users() {
+            users.entry(user.clone()).or_default().push(recorded.seq);
+        }
+        changes.push(recorded.clone());
+        Ok(recorded)
+    }
+
+    async fn changes(&self, after_seq: u64, limit: usize) -> Result<Vec<RecordedEvent>, Error> {
+        Ok(self
+            .changes
+            .read()
+            .await
+            .iter()
+            .skip(after_seq.min(usize::MAX as u64) as usize)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn latest(&self) -> Result<Option<Version>, Error> {
+        Ok(self.changes.read().await.last().map(Version::from))
+    }
+
+    async fn user_version(&self, user: &UserAddress) -> Result<Option<Version>, Error> {
+        let changes = self.changes.read().await;
+        Ok(self
+            .users
+            .read()
+            .await
+            .get(user)
+            .and_then(|seqs| seqs.last())
+            .map(|seq| Version::from(&changes[*seq as usize - 1])))
+    }
+
+    async fn user_changes(
+        &self,
+        user: &UserAddress,
+        after_seq: u64,
+        limit: usize,
+    ) -> Result<Vec<RecordedEvent>, Error> {
+        let changes = self.changes.read().await;
+        let users = self.users.read().await;
+        let Some(seqs) = users.get(user) else {
+            return Ok(vec![]);
+        };
+        Ok(seqs
+            .iter()
+            .filter(|seq| **seq > after_seq)
+            .take(limit)
+            .map(|seq| changes[*seq as usize - 1].clone())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let log = InMemoryChangeLog::default();
+        assert!(log.changes(0, 10).await.unwrap().is_empty());
+        for i in 1..=3 {
+            let recorded = log
+                .append(
+                    Event::RemoveProof {
+                        user: format!("user{i}"),
+                    },
+                    i * 10,
+                )
+                .await
+                .unwrap();
+            assert_eq!(recorded.seq, i);
+        }
+        let changes = log.changes(1, 10).await.unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].seq, 2);
+        assert_eq!(changes[0].recorded_at, 20);
+        assert_eq!(log.changes(0, 1).await.unwrap().len(), 1);
+        assert!(log.changes(3, 10).await.unwrap().is_empty());
+        assert_eq!(
+            log.latest().await.unwrap(),
+            Some(Version {
+                seq: 3,
+                recorded_at: 30
+            })
+        );
+        assert_eq!(
+            log.user_version(&"user2".to_string()).await.unwrap(),
+            Some(Version {
+                seq: 2,
+                recorded_at: 20
+            })
+        );
+        assert_eq!(log.user_version(&"user4".to_string()).await.unwrap(), None);
+    }
+
+    #[async_std::test]
+    async fn test_user_changes() {
+        let log = InMemoryChangeLog::default();
+        let (a, b) = ("a".to_string(), "b".to_string());
+        for (seq, vouchee) in [&b, &a, &b].into_iter().enumerate() {
+            let change = Event::Vouch {
+                voucher: "c".to_string(),
+                vouchee: vouchee.clone(),
+                timestamp: seq as u64,
+            };
+            log.append(change, seq as u64).await.unwrap();
+        }
+        let seqs = |changes: Vec<RecordedEvent>| -> Vec<u64> {
+            changes.into_iter().map(|c| c.seq).collect()
+        };
+        assert_eq!(seqs(log.user_changes(&b, 0, 10).await.unwrap()), vec![1, 3]);
+        assert_eq!(seqs(log.user_changes(&b, 1, 10).await.unwrap()), vec![3]);
+        assert_eq!(seqs(log.user_changes(&b, 0, 1).await.unwrap()), vec![1]);
+        assert_eq!(
+            seqs(log.user_changes(&"c".to_string(), 0, 10).await.unwrap()),
+            vec![1, 2, 3]
+        );
+        assert!(
+            log.user_changes(&"d".to_string(), 0, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,400 @@
+// Fault injection for resilience tests.
+//
+// `ChaosStorage` wraps vouch, proof and penalty storages of any backend, delays every call by
+// `storage.chaos.latency_ms` and fails it with probability `storage.chaos.error_rate`.
+// Injected failures are database errors, so routes see them as an unavailable database.
+// Only built with the `chaos` feature.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use ethers_core::rand::{Rng, SeedableRng, rngs::StdRng, thread_rng};
+
+use crate::{
+    config::ChaosSection,
+    identity::{
+        IdtAmount, ModeratorProof, SystemPenalty, UserAddress,
+        error::Error,
+        proof::storage::ProofStorage,
+        punish::storage::{PenaltyStorage, PenaltyWrite},
+        vouch::storage::{VouchStorage, VouchWrite},
+    },
+    storage::Storage,
+};
+
+pub struct ChaosStorage {
+    vouches: Arc<dyn VouchStorage>,
+    proofs: Arc<dyn ProofStorage>,
+    penalties: Arc<dyn PenaltyStorage>,
+    latency: Duration,
+    error_rate: f64,
+    rng: Mutex<StdRng>,
+    faults: AtomicU64,
+}
+
+impl ChaosStorage {
+    pub fn new(
+        vouches: Arc<dyn VouchStorage>,
+        proofs: Arc<dyn ProofStorage>,
+        penalties: Arc<dyn PenaltyStorage>,
+        config: &ChaosSection,
+    ) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_rng(thread_rng()).expect("thread rng never fails"),
+        };
+        Self {
+            vouches,
+            proofs,
+            penalties,
+            latency: Duration::from_millis(config.latency_ms),
+            error_rate: config.error_rate.clamp(0.0, 1.0),
+            rng: Mutex::new(rng),
+            faults: AtomicU64::new(0),
+        }
+    }
+
+    // calls failed by the wrapper
+    pub fn faults(&self) -> u64 {
+        self.faults.load(Ordering::Relaxed)
+    }
+
+    // delays the call and decides whether it fails
+    async fn inject(&self, operation: &str) -> Result<(), Error> {
+        if !self.latency.is_zero() {
+            async_std::task::sleep(self.latency).await;
+        }
+        let fail = self
+            .rng
+            .lock()
+            .map(|mut rng| rng.gen_bool(self.error_rate))
+            .unwrap_or(false);
+        if !fail {
+            return Ok(());
+        }
+        self.faults.fetch_add(1, Ordering::Relaxed);
+        Err(sqlx::Error::Io(io::Error::other(format!("injected fault in {operation}"))).into())
+    }
+}
+
+// replaces vouch, proof and penalty storages of the bundle with a wrapper injecting faults
+pub fn inject_faults(mut storage: Storage, config: &ChaosSection) -> Storage {
+    let chaos = Arc::new(ChaosStorage::new(
+        storage.vouch_storage,
+        storage.proof_storage,
+        storage.penalty_storage,
+        config,
+    ));
+    storage.vouch_storage = chaos.clone();
+    storage.proof_storage = chaos.clone();
+    storage.penalty_storage = chaos;
+    storage
+}
+
+#[async_trait]
+impl VouchStorage for ChaosStorage {
+    async fn vouch(&self, from: UserAddress, to: UserAddress, timestamp: u64) -> Result<(), Error> {
+        self.inject("vouch").await?;
+        self.vouches.vouch(from, to, timestamp).await
+    }
+
+    async fn vouchers_with_time(
+        &self,
+        user: &UserAddress,
+    ) -> Result<HashMap<UserAddress, u64>, Error> {
+        self.inject("vouchers_with_time").await?;
+        self.vouches.vouchers_with_time(user).await
+    }
+
+    async fn vouchees_with_time(
+        &self,
+        user: &UserAddress,
+    ) -> Result<HashMap<UserAddress, u64>, Error> {
+        self.inject("vouchees_with_time").await?;
+        self.vouches.vouchees_with_time(user).await
+    }
+
+    async fn remove_vouch(&self, voucher: UserAddress, vouchee: UserAddress) -> Result<(), Error> {
+        self.inject("remove_vouch").await?;
+        self.vouches.remove_vouch(voucher, vouchee).await
+    }
+
+    async fn all_vouches(&self) -> Result<Vec<(UserAddress, UserAddress, u64)>, Error> {
+        self.inject("all_vouches").await?;
+        self.vouches.all_vouches().await
+    }
+
+    // a batch fails as a whole, like a failed transaction
+    async fn write_batch(&self, writes: Vec<VouchWrite>) -> Result<(), Error> {
+        self.inject("write_batch").await?;
+        self.vouches.write_batch(writes).await
+    }
+}
+
+#[async_trait]
+impl ProofStorage for ChaosStorage {
+    async fn set_genesis(&self, users: HashMap<UserAddress, IdtAmount>) -> Result<(), Error> {
+        self.inject("set_genesis").await?;
+        self.proofs.set_genesis(users).await
+    }
+
+    async fn genesis_balance(&self, user: &UserAddress) -> Result<Option<IdtAmount>, Error> {
+        self.inject("genesis_balance").await?;
+        self.proofs.genesis_balance(user).await
+    }
+
+    async fn genesis(&self) -> Result<HashMap<UserAddress, IdtAmount>, Error> {
+        self.inject("genesis").await?;
+        self.proofs.genesis().await
+    }
+
+    async fn set_proof(&self, user: UserAddress, proof: ModeratorProof) -> Result<(), Error> {
+        self.inject("set_proof").await?;
+        self.proofs.set_proof(user, proof).await
+    }
+
+    async fn set_proofs(&self, proofs: Vec<(UserAddress, ModeratorProof)>) -> Result<(), Error> {
+        self.inject("set_proofs").await?;
+        self.proofs.set_proofs(proofs).await
+    }
+
+    async fn proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        self.inject("proof").await?;
+        self.proofs.proof(user).await
+    }
+
+    async fn remove_proof(&self, user: &UserAddress) -> Result<(), Error> {
+        self.inject("remove_proof").await?;
+        self.proofs.remove_proof(user).await
+    }
+
+    async fn proven_users(&self) -> Result<HashSet<UserAddress>, Error> {
+        self.inject("proven_users").await?;
+        self.proofs.proven_users().await
+    }
+
+    async fn revoke_proofs(&self, moderator: &UserAddress) -> Result<Vec<UserAddress>, Error> {
+        self.inject("revoke_proofs").await?;
+        self.proofs.revoke_proofs(moderator).await
+    }
+
+    async fn revoked_proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        self.inject("revoked_proof").await?;
+        self.proofs.revoked_proof(user).await
+    }
+}
+
+#[async_trait]
+impl PenaltyStorage for ChaosStorage {
+    async fn set_moderator_penalty(
+        &self,
+        user: UserAddress,
+        proof: ModeratorProof,
+    ) -> Result<(), Error> {
+        self.inject("set_moderator_penalty").await?;
+        self.penalties.set_moderator_penalty(user, proof).await
+    }
+
+    async fn set_forgotten_penalty(
+        &self,
+        user: UserAddress,
+        vouchee: UserAddress,
+        penalty: SystemPenalty,
+    ) -> Result<(), Error> {
+        self.inject("set_forgotten_penalty").await?;
+        self.penalties
+            .set_forgotten_penalty(user, vouchee, penalty)
+            .await
+    }
+
+    async fn remove_forgotten(
+        &self,
+        user: UserAddress,
+        forgotten: &UserAddress,
+    ) -> Result<(), Error> {
+        self.inject("remove_forgotten").await?;
+        self.penalties.remove_forgotten(user, forgotten).await
+    }
+
+    async fn moderator_penalty(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        self.inject("moderator_penalty").await?;
+        self.penalties.moderator_penalty(user).await
+    }
+
+    async fn remove_moderator_penalty(&self, user: &UserAddress) -> Result<(), Error> {
+        self.inject("remove_moderator_penalty").await?;
+        self.penalties.remove_moderator_penalty(user).await
+    }
+
+    async fn forgotten_penalty(
+        &self,
+        user: &UserAddress,
+        forgotten: &UserAddress,
+    ) -> Result<Option<SystemPenalty>, Error> {
+        self.inject("forgotten_penalty").await?;
+        self.penalties.forgotten_penalty(user, forgotten).await
+    }
+
+    async fn forgotten_users(&self, user: &UserAddress) -> Result<HashSet<UserAddress>, Error> {
+        self.inject("forgotten_users").await?;
+        self.penalties.forgotten_users(user).await
+    }
+
+    async fn all_moderator_penalties(&self) -> Result<Vec<(UserAddress, ModeratorProof)>, Error> {
+        self.inject("all_moderator_penalties").await?;
+        self.penalties.all_moderator_penalties().await
+    }
+
+    async fn recent_moderator_penalties(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<(UserAddress, ModeratorProof)>, Error> {
+        self.inject("recent_moderator_penalties").await?;
+        self.penalties.recent_moderator_penalties(limit).await
+    }
+
+    async fn all_forgotten_penalties(
+        &self,
+    ) -> Result<Vec<(UserAddress, UserAddress, SystemPenalty)>, Error> {
+        self.inject("all_forgotten_penalties").await?;
+        self.penalties.all_forgotten_penalties().await
+    }
+
+    async fn write_batch(&self, writes: Vec<PenaltyWrite>) -> Result<(), Error> {
+        self.inject("write_batch").await?;
+        self.penalties.write_batch(writes).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{Config, ServerSection},
+        identity::{
+            IdentityService,
+            proof::{prove, storage::InMemoryProofStorage},
+            punish::storage::InMemoryPenaltyStorage,
+            tests::{MODERATOR, PROOF_ID, USER_A},
+            vouch::storage::InMemoryVouchStorage,
+        },
+        routes::{State, setup_routes},
+    };
+    use tide::http::{Method, Request as HttpRequest, Response, Url};
+
+    fn chaos(config: ChaosSection) -> Arc<ChaosStorage> {
+        Arc::new(ChaosStorage::new(
+            Arc::new(InMemoryVouchStorage::default()),
+            Arc::new(InMemoryProofStorage::default()),
+            Arc::new(InMemoryPenaltyStorage::default()),
+            &config,
+        ))
+    }
+
+    fn chaos_state(chaos: Arc<ChaosStorage>, server: ServerSection) -> State {
+        State {
+            identity_service: IdentityService {
+                vouches: chaos.clone(),
+                proofs: chaos.clone(),
+                penalties: chaos,
+                ..Default::default()
+            },
+            config: Arc::new(Config {
+                server,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    async fn get(state: State, path: &str) -> Response {
+        let mut server = tide::with_state(state);
+        setup_routes(&mut server);
+        let req = HttpRequest::new(
+            Method::Get,
+            Url::parse(&format!("http://example.com{path}")).unwrap(),
+        );
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_error_rate() {
+        let storage = chaos(ChaosSection {
+            error_rate: 0.5,
+            seed: Some(1),
+            ..Default::default()
+        });
+        let mut failed = 0;
+        for _ in 0..200 {
+            if storage.proof(&USER_A.to_string()).await.is_err() {
+                failed += 1;
+            }
+        }
+        assert_eq!(storage.faults(), failed);
+        assert!((50..150).contains(&failed));
+
+        let storage = chaos(ChaosSection::default());
+        for _ in 0..10 {
+            storage.proof(&USER_A.to_string()).await.unwrap();
+        }
+        assert_eq!(storage.faults(), 0);
+    }
+
+    #[async_std::test]
+    async fn test_route_errors() {
+        let storage = chaos(ChaosSection::default());
+        let state = chaos_state(storage, ServerSection::default());
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        assert_eq!(get(state, "/idt/userA").await.status(), 200);
+
+        // every storage call fails
+        let storage = chaos(ChaosSection {
+            error_rate: 1.0,
+            ..Default::default()
+        });
+        let response = get(
+            chaos_state(storage.clone(), ServerSection::default()),
+            "/idt/userA",
+        )
+        .await;
+        assert_eq!(response.status(), 500);
+        assert!(storage.faults() > 0);
+        let response = get(
+            chaos_state(storage, ServerSection::default()),
+            "/penalties/userA",
+        )
+        .await;
+        assert_eq!(response.status(), 500);
+    }
+
+    #[async_std::test]
+    async fn test_route_latency() {
+        let storage = chaos(ChaosSection {
+            latency_ms: 200,
+            ..Default::default()
+        });
+        let server = ServerSection {
+            request_timeout_ms: 20,
+            ..Default::default()
+        };
+        let response = get(chaos_state(storage.clone(), server), "/idt/userA").await;
+        assert_eq!(response.status(), 504);
+        assert_eq!(storage.faults(), 0);
+    }
+}
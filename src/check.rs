@@ -0,0 +1,511 @@
+// `--check-config` mode: loads the configuration the server would start with and reports
+// every problem found instead of panicking on the first one during startup.
+
+use std::{collections::HashMap, env, fmt, path::Path, str::FromStr};
+
+use ethers_core::types::H160;
+use sqlx::any::AnyPoolOptions;
+use tide::http::Url;
+
+use crate::{
+    config::{Config, load_config, load_genesis},
+    encryption::FieldCipher,
+    identity::{IdtAmount, UserAddress, proof::MAX_IDT_BY_PROOF, screening::DenylistScreening},
+    notifications::ContactKind,
+    personhood::RegistryKind,
+    storage::{MEMORY_STORAGE_URL, StorageRegistry, storage_url_from_env},
+    verify::{address_to_string, private_key_to_address},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Ok,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    // config key or resource the finding is about
+    pub subject: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    fn add(&mut self, severity: Severity, subject: impl Into<String>, message: impl Into<String>) {
+        self.findings.push(Finding {
+            severity,
+            subject: subject.into(),
+            message: message.into(),
+        });
+    }
+
+    fn ok(&mut self, subject: impl Into<String>, message: impl Into<String>) {
+        self.add(Severity::Ok, subject, message);
+    }
+
+    fn warning(&mut self, subject: impl Into<String>, message: impl Into<String>) {
+        self.add(Severity::Warning, subject, message);
+    }
+
+    fn error(&mut self, subject: impl Into<String>, message: impl Into<String>) {
+        self.add(Severity::Error, subject, message);
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for finding in &self.findings {
+            let label = match finding.severity {
+                Severity::Ok => "ok",
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            };
+            writeln!(f, "[{label}] {}: {}", finding.subject, finding.message)?;
+        }
+        writeln!(
+            f,
+            "{} errors, {} warnings",
+            self.count(Severity::Error),
+            self.count(Severity::Warning)
+        )
+    }
+}
+
+// signers are recovered as lowercase `0x` addresses, anything else never matches them
+fn address_error(address: &str) -> Option<String> {
+    match H160::from_str(address) {
+        Ok(parsed) if address_to_string(&parsed) == address => None,
+        Ok(parsed) => Some(format!(
+            "{address} should be written as {}",
+            address_to_string(&parsed)
+        )),
+        Err(e) => Some(format!("{address} is not an address: {e}")),
+    }
+}
+
+fn check_http_url(subject: &str, url: &str, report: &mut Report) {
+    match Url::parse(url) {
+        Ok(parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => {
+            report.ok(subject, url)
+        }
+        Ok(parsed) => report.error(subject, format!("unsupported scheme {}", parsed.scheme())),
+        Err(e) => report.error(subject, format!("{url}: {e}")),
+    }
+}
+
+pub fn check_config(config: &Config, report: &mut Report) {
+    for (subject, users) in [
+        ("admins.admins", &config.admins.admins),
+        ("admins.moderators", &config.admins.moderators),
+    ] {
+        let errors: Vec<String> = users.iter().filter_map(|u| address_error(u)).collect();
+        if errors.is_empty() {
+            report.ok(subject, format!("{} addresses", users.len()));
+        }
+        for error in errors {
+            report.error(subject, error);
+        }
+    }
+    if config.admins.admins.is_empty() {
+        report.warning("admins.admins", "no admins, admin routes cannot be used");
+    }
+
+    for (i, step) in config.identity.maturity_bonus.iter().enumerate() {
+        let subject = format!("identity.maturity_bonus[{i}].ratio");
+        if step.ratio.denominator() == 0 {
+            report.error(subject, "denominator must not be zero");
+        } else if step.ratio.numerator() > step.ratio.denominator() {
+            report.warning(subject, format!("{} is greater than 1", step.ratio));
+        }
+    }
+
+    let damping = config.scoring.pagerank.damping;
+    if !(0.0..=1.0).contains(&damping) {
+        report.error(
+            "scoring.pagerank.damping",
+            format!("{damping} is not between 0 and 1"),
+        );
+    }
+    for (subject, weight) in [
+        ("scoring.local_weight", config.scoring.local_weight),
+        ("scoring.external_weight", config.scoring.external_weight),
+        ("scoring.penalty_weight", config.scoring.penalty_weight),
+    ] {
+        if !weight.is_finite() || weight < 0.0 {
+            report.error(subject, format!("{weight} is not a non-negative number"));
+        }
+    }
+    if config.scoring.full_score_idt.is_zero() {
+        report.error("scoring.full_score_idt", "must be greater than zero");
+    }
+
+    for (subject, url) in [
+        ("reminders.webhook", &config.reminders.webhook),
+        ("ens.rpc_url", &config.ens.rpc_url),
+    ] {
+        if let Some(url) = url {
+            check_http_url(subject, url, report);
+        }
+    }
+    if config.ens.rpc_url.is_some() && config.ens.registry.parse::<H160>().is_err() {
+        report.error(
+            "ens.registry",
+            format!("{} is not an address", config.ens.registry),
+        );
+    }
+
+    for verifier in &config.attestations.verifiers {
+        if let Some(error) = address_error(verifier) {
+            report.error("attestations.verifiers", error);
+        }
+    }
+    for (provider, amount) in &config.attestations.providers {
+        if *amount > MAX_IDT_BY_PROOF {
+            report.error(
+                format!("attestations.providers.{provider}"),
+                format!("{amount} IDT is more than a proof can give ({MAX_IDT_BY_PROOF})"),
+            );
+        }
+    }
+    if !config.attestations.providers.is_empty() && config.attestations.verifiers.is_empty() {
+        report.warning(
+            "attestations.verifiers",
+            "no verifiers, attestations cannot be completed",
+        );
+    }
+
+    for (name, registry) in &config.personhood.registries {
+        let subject = format!("personhood.registries.{name}");
+        match RegistryKind::parse(&registry.kind) {
+            Some(RegistryKind::Rpc) => {
+                check_http_url(&format!("{subject}.url"), &registry.url, report);
+                if registry.contract.parse::<H160>().is_err() {
+                    report.error(
+                        format!("{subject}.contract"),
+                        format!("{} is not an address", registry.contract),
+                    );
+                }
+            }
+            Some(RegistryKind::Api) => {
+                if !registry.url.contains("{address}") {
+                    report.error(format!("{subject}.url"), "no {address} placeholder");
+                } else {
+                    check_http_url(&format!("{subject}.url"), &registry.url, report);
+                }
+            }
+            None => report.error(
+                format!("{subject}.kind"),
+                format!("unknown registry kind {}", registry.kind),
+            ),
+        }
+        if registry.amount > MAX_IDT_BY_PROOF {
+            report.error(
+                format!("{subject}.amount"),
+                format!(
+                    "{} IDT is more than a proof can give ({MAX_IDT_BY_PROOF})",
+                    registry.amount
+                ),
+            );
+        }
+    }
+
+    for (i, contact) in config.summaries.contacts.iter().enumerate() {
+        if ContactKind::parse(&contact.kind).is_none() {
+            report.error(
+                format!("summaries.contacts[{i}].kind"),
+                format!("unknown contact kind {}", contact.kind),
+            );
+        }
+    }
+    if config.summaries.enabled && config.summaries.contacts.is_empty() {
+        report.warning(
+            "summaries.contacts",
+            "no contacts, summaries are only served by /reports/latest",
+        );
+    }
+
+    if let Some(epsilon) = config.privacy.epsilon {
+        if !epsilon.is_finite() || epsilon <= 0.0 {
+            report.error(
+                "privacy.epsilon",
+                format!("{epsilon} is not a positive number"),
+            );
+        }
+    }
+
+    if config.signatures.allow_legacy {
+        report.warning(
+            "signatures.allow_legacy",
+            "signatures without the server address are accepted",
+        );
+    }
+    if config.signatures.allow_legacy && config.signatures.allow_unbound_admin {
+        report.warning(
+            "signatures.allow_unbound_admin",
+            "admin signatures can be replayed on other servers",
+        );
+    }
+}
+
+pub fn check_genesis(genesis: &HashMap<UserAddress, IdtAmount>, report: &mut Report) {
+    let mut valid = true;
+    for (user, amount) in genesis {
+        // genesis users only need an address to sign their own requests
+        if let Some(error) = address_error(user) {
+            report.warning("genesis", error);
+            valid = false;
+        }
+        if *amount > MAX_IDT_BY_PROOF {
+            report.warning(
+                "genesis",
+                format!("{user} has {amount} IDT, more than a proof can give ({MAX_IDT_BY_PROOF})"),
+            );
+            valid = false;
+        }
+    }
+    if valid {
+        report.ok("genesis", format!("{} users", genesis.len()));
+    }
+}
+
+pub async fn check_database(url: &str, report: &mut Report) {
+    let scheme = url.split(':').next().unwrap_or_default();
+    if !StorageRegistry::default()
+        .schemes()
+        .iter()
+        .any(|s| s == scheme)
+    {
+        report.error("storage", format!("unsupported storage scheme {scheme}"));
+        return;
+    }
+    if url.starts_with(MEMORY_STORAGE_URL) {
+        report.warning("storage", "data is kept in memory and lost on restart");
+        return;
+    }
+    if scheme == "sled" {
+        report.ok("storage", format!("embedded database {url}"));
+        return;
+    }
+    sqlx::any::install_default_drivers();
+    let connected = async {
+        let pool = AnyPoolOptions::new()
+            .max_connections(1)
+            .connect(url)
+            .await?;
+        sqlx::query("SELECT 1").execute(&pool).await?;
+        pool.close().await;
+        Ok::<_, sqlx::Error>(())
+    }
+    .await;
+    // the url may contain a password, only the scheme is reported
+    match connected {
+        Ok(()) => report.ok("storage", format!("connected to {scheme} database")),
+        Err(e) => report.error("storage", format!("failed to connect: {e}")),
+    }
+}
+
+// checks the files, environment variables and database the server would start with
+pub async fn run(config_path: &str, genesis_path: &str) -> Report {
+    let mut report = Report::default();
+
+    if !Path::new(config_path).exists() {
+        report.warning(config_path, "not found, defaults are used");
+    }
+    let config = match load_config(config_path).await {
+        Ok(config) => {
+            report.ok(config_path, "parsed");
+            check_config(&config, &mut report);
+            Some(config)
+        }
+        Err(e) => {
+            report.error(config_path, e.to_string());
+            None
+        }
+    };
+
+    let genesis_section = config
+        .as_ref()
+        .map(|config| config.genesis.clone())
+        .unwrap_or_default();
+    // a remote genesis is downloaded if missing
+    if genesis_section.url.is_none() && !Path::new(genesis_path).exists() {
+        report.warning(genesis_path, "not found, there are no genesis users");
+    }
+    match load_genesis(genesis_path, &genesis_section).await {
+        Ok(genesis) => check_genesis(&genesis, &mut report),
+        Err(e) => report.error(genesis_path, e.to_string()),
+    }
+
+    if let Some(path) = config
+        .as_ref()
+        .and_then(|config| config.screening.denylist.as_ref())
+    {
+        match DenylistScreening::load(path).await {
+            Ok(denylist) => report.ok(path, format!("{} blocked addresses", denylist.len())),
+            Err(e) => report.error(path, e.to_string()),
+        }
+    }
+
+    match env::var("SERVER_PRIVATE_KEY") {
+        Ok(key) if !key.is_empty() => match private_key_to_address(&key) {
+            Ok(address) => report.ok("SERVER_PRIVATE_KEY", address),
+            Err(e) => report.error("SERVER_PRIVATE_KEY", e.to_string()),
+        },
+        _ => report.warning(
+            "SERVER_PRIVATE_KEY",
+            "not set, a random key is generated on every start",
+        ),
+    }
+    match FieldCipher::from_env() {
+        Ok(cipher) if cipher.is_enabled() => report.ok("DB_ENCRYPTION_KEYS", "parsed"),
+        Ok(_) => {}
+        Err(e) => report.error("DB_ENCRYPTION_KEYS", e.to_string()),
+    }
+
+    let url = config
+        .and_then(|config| config.storage.url)
+        .unwrap_or_else(storage_url_from_env);
+    check_database(&url, &mut report).await;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use async_std::{fs::File, io::WriteExt};
+    use serde_json::json;
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::verify::random_keypair;
+
+    fn errors(report: &Report) -> Vec<String> {
+        report
+            .findings
+            .iter()
+            .filter(|f| f.severity == Severity::Error)
+            .map(|f| f.subject.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_check_config() {
+        let (_, admin) = random_keypair();
+        let json = format!(
+            r#"{{
+                "admins": {{"admins": ["{admin}"], "moderators": ["moderator"]}},
+                "identity": {{"maturity_bonus": [{{"age": 1, "ratio": {{"numerator": 1, "denominator": 0}}}}]}},
+                "scoring": {{"pagerank": {{"damping": 1.5}}}},
+                "reminders": {{"webhook": "ftp://example.com"}},
+                "attestations": {{"verifiers": ["verifier"], "providers": {{"github": 100000}}}},
+                "summaries": {{"contacts": [{{"kind": "pager", "address": "123"}}]}},
+                "privacy": {{"epsilon": 0}},
+                "ens": {{"rpc_url": "http://localhost:8545", "registry": "registry"}},
+                "personhood": {{"registries": {{"poh": {{"kind": "rpc", "url": "http://localhost:8545", "contract": "poh", "amount": 100}}}}}}
+            }}"#
+        );
+        let config: Config = serde_json::from_str(&json).unwrap();
+        let mut report = Report::default();
+        check_config(&config, &mut report);
+        assert_eq!(
+            errors(&report),
+            vec![
+                "admins.moderators",
+                "identity.maturity_bonus[0].ratio",
+                "scoring.pagerank.damping",
+                "reminders.webhook",
+                "ens.registry",
+                "attestations.verifiers",
+                "attestations.providers.github",
+                "personhood.registries.poh.contract",
+                "summaries.contacts[0].kind",
+                "privacy.epsilon",
+            ]
+        );
+
+        let mut report = Report::default();
+        check_config(&Config::default(), &mut report);
+        assert!(!report.has_errors());
+        assert_eq!(report.count(Severity::Warning), 1);
+    }
+
+    #[test]
+    fn test_check_genesis() {
+        let (_, user) = random_keypair();
+        let mut report = Report::default();
+        check_genesis(
+            &HashMap::from([(user.clone(), IdtAmount::new(100))]),
+            &mut report,
+        );
+        assert_eq!(report.count(Severity::Ok), 1);
+
+        let mut report = Report::default();
+        check_genesis(
+            &HashMap::from([(user.to_uppercase(), MAX_IDT_BY_PROOF + IdtAmount::new(1))]),
+            &mut report,
+        );
+        assert_eq!(report.count(Severity::Warning), 2);
+        assert!(!report.has_errors());
+    }
+
+    #[async_std::test]
+    async fn test_check_database() {
+        let mut report = Report::default();
+        check_database("sqlite::memory:", &mut report).await;
+        check_database(MEMORY_STORAGE_URL, &mut report).await;
+        assert!(!report.has_errors());
+
+        check_database("postgres://localhost/identity", &mut report).await;
+        check_database("sqlite:///nonexistent/dir/identity.db", &mut report).await;
+        assert_eq!(errors(&report), vec!["storage", "storage"]);
+    }
+
+    #[async_std::test]
+    async fn test_run() {
+        let dir = TempDir::new("check").unwrap();
+        let config_path = dir.path().join("config.json");
+        let genesis_path = dir.path().join("genesis.json");
+        let denylist_path = dir.path().join("denylist.txt");
+        let config = json!({
+            "storage": {"url": "memory://"},
+            "screening": {"denylist": denylist_path},
+        });
+        File::create(&config_path)
+            .await
+            .unwrap()
+            .write_all(config.to_string().as_bytes())
+            .await
+            .unwrap();
+        File::create(&genesis_path)
+            .await
+            .unwrap()
+            .write_all(b"{\"alice\": \"many\"}")
+            .await
+            .unwrap();
+        let report = run(
+            config_path.to_str().unwrap(),
+            genesis_path.to_str().unwrap(),
+        )
+        .await;
+        assert!(errors(&report).contains(&genesis_path.display().to_string()));
+        assert!(errors(&report).contains(&denylist_path.display().to_string()));
+        assert!(report.to_string().contains("[ok] "));
+    }
+}
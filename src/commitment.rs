@@ -0,0 +1,236 @@
+// Commitments to the voucher set and balance of a user for zero knowledge proofs made
+// outside of the server.
+//
+// Vouchers are the leaves of a keccak256 Merkle tree, sorted by address and padded with zero
+// leaves to a power of two. The balance is hidden in `keccak256(balance || blinding)`, both
+// as 32 byte big endian words, so a circuit can prove e.g. `balance > X` knowing the
+// blinding. The server signs the user, both commitments and the time, so a verifier that
+// trusts the server only needs the signed commitments and the proof. The blinding is derived
+// from the server key and the committed values, the same state always gives the same
+// commitment.
+
+use ethers_core::utils::keccak256;
+
+use crate::identity::{
+    IdentityService, IdtAmount, UserAddress, error::Error, idt::balance, vouch::vouchers,
+};
+
+pub type Hash = [u8; 32];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VoucherLeaf {
+    pub voucher: UserAddress,
+    pub timestamp: u64,
+    pub leaf: Hash,
+    // sibling hashes from the leaf up to the root
+    pub proof: Vec<Hash>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Commitment {
+    pub user: UserAddress,
+    pub timestamp: u64,
+    pub vouchers: Vec<VoucherLeaf>,
+    pub voucher_root: Hash,
+    pub balance: IdtAmount,
+    pub blinding: Hash,
+    pub balance_commitment: Hash,
+}
+
+fn word(value: u64) -> Hash {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+    let mut data = [0u8; 64];
+    data[..32].copy_from_slice(left);
+    data[32..].copy_from_slice(right);
+    keccak256(data)
+}
+
+pub fn voucher_leaf(voucher: &UserAddress, timestamp: u64) -> Hash {
+    let mut data = voucher.as_bytes().to_vec();
+    data.extend_from_slice(&word(timestamp));
+    keccak256(data)
+}
+
+// levels of the tree from the padded leaves up to the root
+fn tree_levels(leaves: &[Hash]) -> Vec<Vec<Hash>> {
+    let mut level = leaves.to_vec();
+    level.resize(leaves.len().next_power_of_two(), [0u8; 32]);
+    let mut levels = vec![level];
+    while levels[levels.len() - 1].len() > 1 {
+        let next = levels[levels.len() - 1]
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], &pair[1]))
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+// root of an empty tree is zero
+pub fn merkle_root(leaves: &[Hash]) -> Hash {
+    if leaves.is_empty() {
+        return [0u8; 32];
+    }
+    tree_levels(leaves).last().expect("tree has a root")[0]
+}
+
+pub fn merkle_proof(leaves: &[Hash], index: usize) -> Vec<Hash> {
+    let levels = tree_levels(leaves);
+    let mut proof = vec![];
+    let mut index = index;
+    for level in &levels[..levels.len() - 1] {
+        proof.push(level[index ^ 1]);
+        index /= 2;
+    }
+    proof
+}
+
+pub fn verify_merkle_proof(leaf: &Hash, index: usize, proof: &[Hash], root: &Hash) -> bool {
+    let mut hash = *leaf;
+    let mut index = index;
+    for sibling in proof {
+        hash = if index % 2 == 0 {
+            hash_pair(&hash, sibling)
+        } else {
+            hash_pair(sibling, &hash)
+        };
+        index /= 2;
+    }
+    &hash == root
+}
+
+pub fn balance_commitment(balance: IdtAmount, blinding: &Hash) -> Hash {
+    hash_pair(&word(balance.milli()), blinding)
+}
+
+fn blinding(server_key: &str, user: &UserAddress, balance: IdtAmount, root: &Hash) -> Hash {
+    keccak256(format!(
+        "commitment/{server_key}/{user}/{balance}/{}",
+        hex::encode(root)
+    ))
+}
+
+pub async fn commit(
+    service: &IdentityService,
+    user: &UserAddress,
+    server_key: &str,
+) -> Result<Commitment, Error> {
+    let times = service.vouchers_with_time(user).await?;
+    let mut leaves = vec![];
+    for voucher in vouchers(service, user).await? {
+        let timestamp = times[&voucher];
+        leaves.push((
+            voucher.clone(),
+            timestamp,
+            voucher_leaf(&voucher, timestamp),
+        ));
+    }
+    let hashes: Vec<Hash> = leaves.iter().map(|(.., leaf)| *leaf).collect();
+    let voucher_root = merkle_root(&hashes);
+    let balance = balance(service, user).await?;
+    let blinding = blinding(server_key, user, balance, &voucher_root);
+    Ok(Commitment {
+        user: user.clone(),
+        timestamp: service.now(),
+        vouchers: leaves
+            .into_iter()
+            .enumerate()
+            .map(|(index, (voucher, timestamp, leaf))| VoucherLeaf {
+                voucher,
+                timestamp,
+                leaf,
+                proof: merkle_proof(&hashes, index),
+            })
+            .collect(),
+        voucher_root,
+        balance,
+        blinding,
+        balance_commitment: balance_commitment(balance, &blinding),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{
+        proof::prove,
+        tests::{MODERATOR, PROOF_ID, USER_A, service_with_mock_clock},
+        vouch::vouch,
+    };
+
+    #[test]
+    fn test_merkle() {
+        assert_eq!(merkle_root(&[]), [0u8; 32]);
+        let leaves: Vec<Hash> = (0..5)
+            .map(|i| voucher_leaf(&format!("user{i}"), i))
+            .collect();
+        assert_eq!(merkle_root(&leaves[..1]), leaves[0]);
+        assert_eq!(merkle_root(&leaves[..2]), hash_pair(&leaves[0], &leaves[1]));
+        let root = merkle_root(&leaves);
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = merkle_proof(&leaves, index);
+            assert_eq!(proof.len(), 3);
+            assert!(verify_merkle_proof(leaf, index, &proof, &root));
+            assert!(!verify_merkle_proof(leaf, index ^ 1, &proof, &root));
+        }
+        assert_ne!(
+            voucher_leaf(&"a".to_string(), 1),
+            voucher_leaf(&"a".to_string(), 2)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_deterministic() {
+        let (service, clock) = service_with_mock_clock();
+        let user_b = "userB".to_string();
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+        vouch(&service, MODERATOR.to_string(), user_b.clone())
+            .await
+            .unwrap();
+
+        let commitment = commit(&service, &user_b, "key").await.unwrap();
+        assert_eq!(commit(&service, &user_b, "key").await.unwrap(), commitment);
+        assert_eq!(commitment.vouchers.len(), 2);
+        // sorted by voucher
+        assert_eq!(commitment.vouchers[0].voucher, MODERATOR);
+        for (index, leaf) in commitment.vouchers.iter().enumerate() {
+            assert!(verify_merkle_proof(
+                &leaf.leaf,
+                index,
+                &leaf.proof,
+                &commitment.voucher_root
+            ));
+        }
+        assert_eq!(
+            commitment.balance_commitment,
+            balance_commitment(commitment.balance, &commitment.blinding)
+        );
+        // blinding is secret to the server key
+        let other = commit(&service, &user_b, "other").await.unwrap();
+        assert_eq!(other.voucher_root, commitment.voucher_root);
+        assert_ne!(other.balance_commitment, commitment.balance_commitment);
+
+        clock.advance(1);
+        vouch(&service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+        let refreshed = commit(&service, &user_b, "key").await.unwrap();
+        assert_ne!(refreshed.voucher_root, commitment.voucher_root);
+    }
+}
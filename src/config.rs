@@ -4,14 +4,23 @@ use std::{
 };
 
 use async_std::fs;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use crate::identity::{IdtAmount, UserAddress};
+use crate::{
+    identity::{
+        IdtAmount, UserAddress, categories::CategoryPolicy, genesis::GenesisDriftPolicy,
+        idt::MaturityStep, penalty_reasons::PenaltyReasonPolicy, proof::MAX_IDT_BY_PROOF,
+        stakes::StakeSlashingPolicy, tree_size::TreeSizeLimits, vouch::VouchRefreshPolicy,
+    },
+    numbers::Rounding,
+    scoring::strategy::StrategyKind,
+};
 
 pub const DEFAULT_CONFIG_PATH: &str = "config.json";
 pub const DEFAULT_GENESIS_PATH: &str = "genesis.json";
 
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct AdminsSection {
     #[serde(default)]
     pub admins: HashSet<String>,
@@ -19,10 +28,751 @@ pub struct AdminsSection {
     pub moderators: HashSet<String>,
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct IdentitySection {
+    #[serde(default)]
+    pub vouch_refresh: VouchRefreshPolicy,
+    // seconds after a proof during which it does not decay
+    #[serde(default)]
+    pub proof_grace_period: u64,
+    // higher voucher weights for vouches older than the step age, empty disables the bonus
+    #[serde(default)]
+    pub maturity_bonus: Vec<MaturityStep>,
+    // number of voucher hops a vouchee penalty propagates through, unlimited if not set
+    #[serde(default)]
+    pub max_penalty_depth: Option<usize>,
+    // seconds after a punishment during which vouchers can forget the punished user without
+    // taking over its penalty, 0 disables the waiver
+    #[serde(default)]
+    pub forget_grace_period: u64,
+    // scale proofs by the reputation of the moderator who issued them
+    #[serde(default)]
+    pub reputation_weighted_proofs: bool,
+    // proofs must be countersigned by the proven user
+    #[serde(default)]
+    pub require_proof_consent: bool,
+    // seconds without a signed action after which proofs decay faster, 0 disables the rule
+    #[serde(default)]
+    pub inactivity_period: u64,
+    // IDT per day decayed from the proof of an inactive user on top of the usual decay
+    #[serde(default)]
+    pub inactivity_decay: IdtAmount,
+    // vouch tree size above which balance computations are logged as warnings
+    #[serde(default)]
+    pub tree_size_warning: TreeSizeLimits,
+    // balance bounds and vouching permission by user category
+    #[serde(default)]
+    pub categories: HashMap<String, CategoryPolicy>,
+    // weight and decay speed of moderator penalties by reason code
+    #[serde(default)]
+    pub penalty_reasons: HashMap<String, PenaltyReasonPolicy>,
+    // slash a share of the voucher's proven balance when a vouchee is punished
+    #[serde(default)]
+    pub stake_slashing: StakeSlashingPolicy,
+    // rounding of voucher weights and vouchee penalty shares
+    #[serde(default)]
+    pub rounding: Rounding,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FederationSection {
+    // forward requests for users with a remote home server to that server
+    pub proxy: bool,
+    // seconds a server is fully trusted after its attestation, 0 disables the decay
+    pub attestation_interval: u64,
+    // seconds for the trust of an overdue server to decay to zero
+    pub attestation_decay: u64,
+    // external vouches are only accepted from servers that cross-signed this server
+    pub require_cross_signing: bool,
+    // balance changes of local users of at least this many IDT are pushed to the servers
+    // whose users vouched for them, 0 disables the pushes
+    pub balance_update_threshold: IdtAmount,
+}
+
+impl Default for FederationSection {
+    fn default() -> Self {
+        Self {
+            proxy: false,
+            attestation_interval: 0,
+            attestation_decay: 30 * 24 * 3600,
+            require_cross_signing: false,
+            balance_update_threshold: IdtAmount::ZERO,
+        }
+    }
+}
+
+// parameters of the PageRank balance strategy, see `scoring::pagerank`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PageRankSection {
+    // probability of following a vouch instead of jumping back to proven users
+    pub damping: f64,
+    pub max_iterations: u32,
+    // iteration stops when the total rank change is below this value
+    pub tolerance: f64,
+    // seconds between batch recomputations of the materialized balances
+    pub recompute_interval: u64,
+}
+
+impl Default for PageRankSection {
+    fn default() -> Self {
+        Self {
+            damping: 0.85,
+            max_iterations: 100,
+            tolerance: 1e-9,
+            recompute_interval: 3600,
+        }
+    }
+}
+
+// reminders about proofs that are about to decay to zero
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RemindersSection {
+    // url receiving reminders as JSON, reminders are only logged if missing
+    pub webhook: Option<String>,
+    pub days_before_expiry: u64,
+    // seconds between checks of expiring proofs
+    pub check_interval: u64,
+}
+
+impl Default for RemindersSection {
+    fn default() -> Self {
+        Self {
+            webhook: None,
+            days_before_expiry: 7,
+            check_interval: 3600,
+        }
+    }
+}
+
+// durable queue of outbound webhook pushes, see `outbox`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct OutboxSection {
+    // seconds between delivery runs
+    pub check_interval: u64,
+    // seconds before the first retry, doubled after every failed attempt
+    pub initial_backoff: u64,
+    pub max_backoff: u64,
+    // failed deliveries after which the item is only kept for inspection
+    pub max_attempts: u32,
+}
+
+impl Default for OutboxSection {
+    fn default() -> Self {
+        Self {
+            check_interval: 10,
+            initial_backoff: 30,
+            max_backoff: 6 * 60 * 60,
+            max_attempts: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SmtpSection {
+    pub host: String,
+    pub port: u16,
+    // sender address of notification emails
+    pub from: String,
+    // name this server introduces itself with
+    pub helo: String,
+}
+
+impl Default for SmtpSection {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 25,
+            from: "identity@localhost".to_string(),
+            helo: "localhost".to_string(),
+        }
+    }
+}
+
+// notifications are disabled unless at least one transport is configured
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct NotificationsSection {
+    pub smtp: Option<SmtpSection>,
+    pub web_push: bool,
+    // users are notified when their balance crosses any of these values
+    pub balance_thresholds: Vec<IdtAmount>,
+}
+
+// admin contact receiving scheduled summaries, `kind` is a contact kind of `POST /contact`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AdminContact {
+    pub kind: String,
+    pub address: String,
+}
+
+// periodic summaries of the identity graph, see `summaries` module
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SummariesSection {
+    pub enabled: bool,
+    // seconds covered by a summary
+    pub interval: u64,
+    // users with the greatest balance changes listed in a summary
+    pub top_movers: usize,
+    pub contacts: Vec<AdminContact>,
+}
+
+impl Default for SummariesSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: 7 * 24 * 60 * 60,
+            top_movers: 10,
+            contacts: vec![],
+        }
+    }
+}
+
+// balance strategy and weights of the trust score formula, see `scoring` module
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ScoringSection {
+    pub strategy: StrategyKind,
+    pub pagerank: PageRankSection,
+    pub local_weight: f64,
+    pub external_weight: f64,
+    pub penalty_weight: f64,
+    // weighted IDT amount that maps to the score of 100
+    pub full_score_idt: IdtAmount,
+}
+
+impl Default for ScoringSection {
+    fn default() -> Self {
+        Self {
+            strategy: StrategyKind::default(),
+            pagerank: PageRankSection::default(),
+            local_weight: 1.0,
+            external_weight: 0.5,
+            penalty_weight: 0.5,
+            full_score_idt: MAX_IDT_BY_PROOF,
+        }
+    }
+}
+
+// versions of the signed message format understood by this server, 0 is the unversioned
+// format. Servers accept the versions of `signatures.versions`, so the format can change
+// without breaking clients of older versions at once.
+pub const MESSAGE_VERSIONS: [u32; 2] = [0, LATEST_MESSAGE_VERSION];
+pub const LATEST_MESSAGE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SignaturesSection {
+    // seconds, signatures expiring later than this from now are rejected
+    pub max_age: u64,
+    // accept messages signed without the server address, only meant for migrating
+    // clients to domain separated signatures
+    pub allow_legacy: bool,
+    // with `allow_legacy`, accept admin messages signed without the server address in the
+    // domain or the message prefix. Only meant for migrating admin tools.
+    pub allow_unbound_admin: bool,
+    // seconds, client timestamps of vouches and forgets further than this from the
+    // server time are rejected
+    pub max_timestamp_skew: u64,
+    // accepted versions of the signed message format, 0 is the unversioned format
+    pub versions: Vec<u32>,
+}
+
+impl Default for SignaturesSection {
+    fn default() -> Self {
+        Self {
+            max_age: 3600,
+            allow_legacy: false,
+            allow_unbound_admin: false,
+            max_timestamp_skew: 300,
+            versions: MESSAGE_VERSIONS.to_vec(),
+        }
+    }
+}
+
+impl SignaturesSection {
+    // accepted versions known to this server, sorted
+    pub fn supported_versions(&self) -> Vec<u32> {
+        let mut versions: Vec<u32> = self
+            .versions
+            .iter()
+            .copied()
+            .filter(|v| MESSAGE_VERSIONS.contains(v))
+            .collect();
+        versions.sort();
+        versions.dedup();
+        versions
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ArchiveSection {
+    // users are only archived if enabled
+    pub enabled: bool,
+    // seconds the balance has to stay at zero before the user is archived
+    pub zero_balance_period: u64,
+    // seconds between archival runs
+    pub check_interval: u64,
+}
+
+impl Default for ArchiveSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            zero_balance_period: 90 * 24 * 60 * 60,
+            check_interval: 24 * 60 * 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TwoPhaseVouchSection {
+    // new vouches of users with a balance above it wait for a confirmation, disabled if not set
+    pub threshold: Option<IdtAmount>,
+    // seconds after the vouch before a confirmed vouch is activated
+    pub cooling_off: u64,
+    // seconds an unconfirmed vouch is kept
+    pub expiry: u64,
+    // seconds between runs of the activation job
+    pub check_interval: u64,
+}
+
+impl Default for TwoPhaseVouchSection {
+    fn default() -> Self {
+        Self {
+            threshold: None,
+            cooling_off: 24 * 60 * 60,
+            expiry: 7 * 24 * 60 * 60,
+            check_interval: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EscalationSection {
+    // penalties of moderators above it wait for an admin approval, disabled if not set
+    pub approval_threshold: Option<IdtAmount>,
+}
+
+// community pool offsetting penalties of users with approved appeals, see `restitution`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RestitutionSection {
+    // contributions, withdrawals and grants are rejected if disabled
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct IntegritySection {
+    // runs the consistency check periodically if enabled, admins can always run it with
+    // `POST /admin/check_integrity`
+    pub enabled: bool,
+    // seconds between scheduled checks
+    pub check_interval: u64,
+    // scheduled checks also fix the inconsistencies they find
+    pub repair: bool,
+}
+
+impl Default for IntegritySection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            check_interval: 24 * 60 * 60,
+            repair: false,
+        }
+    }
+}
+
+// detection of likely duplicate identities, see `duplicates`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DuplicatesSection {
+    // runs the analysis periodically and files the found pairs into the reports queue
+    pub enabled: bool,
+    // seconds between analyses
+    pub check_interval: u64,
+    // addresses with fewer vouch edges in both directions are not compared
+    pub min_edges: usize,
+    // edges shared by more addresses do not make candidate pairs, e.g. the vouches of a user
+    // vouching for everyone. They still count in the similarity of pairs found otherwise.
+    pub max_fan_out: usize,
+    // minimal Jaccard index of the vouch edges of a pair, from 0 to 1
+    pub similarity_threshold: f64,
+    // common edges created within this many seconds of each other are correlated
+    pub timing_window: u64,
+    // minimal share of correlated common edges, 0 ignores the timing
+    pub timing_threshold: f64,
+}
+
+impl Default for DuplicatesSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            check_interval: 24 * 60 * 60,
+            min_edges: 3,
+            max_fan_out: 100,
+            similarity_threshold: 0.8,
+            timing_window: 60 * 60,
+            timing_threshold: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EventsSection {
+    // number of events between projection snapshots of the event log, 0 disables them
+    pub snapshot_interval: u64,
+}
+
+impl Default for EventsSection {
+    fn default() -> Self {
+        Self {
+            snapshot_interval: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct StorageSection {
+    // backend selected by the url scheme, e.g. `sqlite://identity.db` or `memory://`.
+    // Falls back to the `STORAGE` and `MYSQL_*` variables if not set.
+    pub url: Option<String>,
+    // keep vouches, proofs and penalties in an append-only event log, SQL storage only
+    pub event_log: bool,
+    pub write_queue: WriteQueueSection,
+    pub chaos: ChaosSection,
+}
+
+// faults injected into vouch, proof and penalty storages, only applied by servers built with
+// the `chaos` feature. Never enable it in production.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct ChaosSection {
+    pub enabled: bool,
+    // milliseconds added to every storage call
+    pub latency_ms: u64,
+    // probability of a storage call to fail, from 0 to 1
+    pub error_rate: f64,
+    // faults are reproducible with a seed, random otherwise
+    pub seed: Option<u64>,
+}
+
+// batching of vouch and penalty writes, see `write_queue`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WriteQueueSection {
+    pub enabled: bool,
+    // writes applied in a single batch
+    pub max_batch: usize,
+    // milliseconds a batch waits for more writes after its first one
+    pub flush_interval_ms: u64,
+    // queued writes before new writes wait for a free slot
+    pub capacity: usize,
+}
+
+impl Default for WriteQueueSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_batch: 100,
+            flush_interval_ms: 5,
+            capacity: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ServerSection {
+    // milliseconds a read request may take before it is cancelled with 504, 0 disables the
+    // limit. Writes are not limited.
+    pub request_timeout_ms: u64,
+    // vouch tree nodes visited by a balance request, the balance of larger trees is
+    // approximate, 0 disables the limit
+    pub compute_budget: u64,
+    pub concurrency: ConcurrencySection,
+    // field names of JSON request and response bodies
+    pub json_naming: JsonNaming,
+    // request bodies are accepted in both namings
+    pub json_naming_compat: bool,
+}
+
+impl Default for ServerSection {
+    fn default() -> Self {
+        Self {
+            request_timeout_ms: 30_000,
+            compute_budget: 100_000,
+            concurrency: ConcurrencySection::default(),
+            json_naming: JsonNaming::default(),
+            json_naming_compat: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonNaming {
+    #[default]
+    SnakeCase,
+    CamelCase,
+}
+
+// requests served at once, so expensive routes cannot take every connection
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConcurrencySection {
+    // limit of all requests, 0 disables it
+    pub global: usize,
+    // limits by the first path segment, e.g. `idt` for `/idt/:user`
+    pub routes: HashMap<String, usize>,
+    // requests waiting for a free slot of a limit, further requests get 429
+    pub queue: usize,
+}
+
+impl Default for ConcurrencySection {
+    fn default() -> Self {
+        Self {
+            global: 0,
+            routes: HashMap::from([("idt".to_string(), 64), ("punish".to_string(), 16)]),
+            queue: 128,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AttestationsSection {
+    // addresses of verifier services whose signatures are accepted
+    pub verifiers: HashSet<UserAddress>,
+    // IDT given by an attestation of the provider, unlisted providers are rejected
+    pub providers: HashMap<String, IdtAmount>,
+    // seconds a challenge can be completed after it was started
+    pub challenge_ttl: u64,
+}
+
+impl Default for AttestationsSection {
+    fn default() -> Self {
+        Self {
+            verifiers: HashSet::new(),
+            providers: HashMap::new(),
+            challenge_ttl: 10 * 60,
+        }
+    }
+}
+
+// conditional GET support of `/idt/:user`, `/vouchers/:user` and `/servers`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CacheSection {
+    // send ETag and Last-Modified, answer matching conditional requests with 304
+    pub enabled: bool,
+    // seconds clients may reuse a response without revalidation, 0 requires revalidation
+    pub max_age: u64,
+    // seconds, balances change with time even without new vouches, so their validators
+    // also change every window. 0 is treated as 1.
+    pub balance_window: u64,
+}
+
+impl Default for CacheSection {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_age: 0,
+            balance_window: 60,
+        }
+    }
+}
+
+// helpers for developing clients, never enable them in production
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DevSection {
+    // serve `POST /dev/sign`, which signs requests with private keys sent to the server
+    pub sign_endpoint: bool,
+}
+
+// screening of addresses before they accrue reputation
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ScreeningSection {
+    // file with blocked addresses, one per line, nothing is blocked without it
+    pub denylist: Option<String>,
+}
+
+// ENS names of users, resolved through an Ethereum node
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EnsSection {
+    // JSON-RPC endpoint of an Ethereum node, names are not resolved without it
+    pub rpc_url: Option<String>,
+    // address of the ENS registry
+    pub registry: String,
+    // seconds resolved names and addresses are cached, including missing ones
+    pub cache_ttl: u64,
+}
+
+impl Default for EnsSection {
+    fn default() -> Self {
+        Self {
+            rpc_url: None,
+            registry: "0x00000000000c2e074ec69a0dfb2997ba6c7d2e1e".to_string(),
+            cache_ttl: 3600,
+        }
+    }
+}
+
+// registry of verified humans, e.g. Proof of Humanity or BrightID
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PersonhoodRegistry {
+    // `rpc` for a contract read through an Ethereum node, `api` for an HTTP API
+    pub kind: String,
+    // JSON-RPC endpoint for `rpc`, URL with an `{address}` placeholder for `api`
+    pub url: String,
+    // address of the registry contract, `rpc` only
+    pub contract: String,
+    // contract function taking the address, e.g. `isRegistered(address)`. A non-zero output
+    // means the address is verified.
+    pub function: String,
+    // JSON pointer of the field that is `true` for verified addresses, `api` only
+    pub pointer: String,
+    // IDT of the system proof given to verified addresses
+    pub amount: IdtAmount,
+}
+
+// system proofs of addresses verified by proof-of-personhood registries
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PersonhoodSection {
+    // key - registry name
+    pub registries: HashMap<String, PersonhoodRegistry>,
+}
+
+// noise of public aggregates for privacy-sensitive deployments
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PrivacySection {
+    // Laplace noise with scale 1 / epsilon is added to the counts of `GET /stats`, smaller
+    // values hide more. Counts are exact if not set.
+    pub epsilon: Option<f64>,
+    // seconds a noised count stays the same while the count does not change
+    pub noise_epoch: u64,
+    // `GET /penalties/recent` lists punished users by address instead of pseudonyms
+    pub public_penalties: bool,
+}
+
+impl Default for PrivacySection {
+    fn default() -> Self {
+        Self {
+            epsilon: None,
+            noise_epoch: 24 * 60 * 60,
+            public_penalties: false,
+        }
+    }
+}
+
+// retries of the storage connection at startup
+// genesis file published for all servers of a federation, downloaded to the local genesis
+// path and reused while its checksum matches
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct GenesisSection {
+    #[serde(default)]
+    pub url: Option<String>,
+    // hex SHA-256 of the published file, required with `url`
+    #[serde(default)]
+    pub sha256: Option<String>,
+    // applied on startup when the stored genesis differs from the loaded one
+    #[serde(default)]
+    pub drift_policy: GenesisDriftPolicy,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct StartupSection {
+    // attempts to create the storage before giving up, at least one is made
+    pub connect_attempts: u32,
+    // milliseconds before the first retry, doubled after every failed attempt
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for StartupSection {
+    fn default() -> Self {
+        Self {
+            connect_attempts: 5,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct Config {
     #[serde(default)]
     pub admins: AdminsSection,
+    #[serde(default)]
+    pub identity: IdentitySection,
+    #[serde(default)]
+    pub genesis: GenesisSection,
+    #[serde(default)]
+    pub federation: FederationSection,
+    #[serde(default)]
+    pub scoring: ScoringSection,
+    #[serde(default)]
+    pub reminders: RemindersSection,
+    #[serde(default)]
+    pub outbox: OutboxSection,
+    #[serde(default)]
+    pub notifications: NotificationsSection,
+    #[serde(default)]
+    pub summaries: SummariesSection,
+    #[serde(default)]
+    pub signatures: SignaturesSection,
+    #[serde(default)]
+    pub archive: ArchiveSection,
+    #[serde(default)]
+    pub integrity: IntegritySection,
+    #[serde(default)]
+    pub duplicates: DuplicatesSection,
+    #[serde(default)]
+    pub two_phase_vouch: TwoPhaseVouchSection,
+    #[serde(default)]
+    pub escalation: EscalationSection,
+    #[serde(default)]
+    pub restitution: RestitutionSection,
+    #[serde(default)]
+    pub events: EventsSection,
+    #[serde(default)]
+    pub storage: StorageSection,
+    #[serde(default)]
+    pub startup: StartupSection,
+    #[serde(default)]
+    pub server: ServerSection,
+    #[serde(default)]
+    pub attestations: AttestationsSection,
+    #[serde(default)]
+    pub cache: CacheSection,
+    #[serde(default)]
+    pub dev: DevSection,
+    #[serde(default)]
+    pub screening: ScreeningSection,
+    #[serde(default)]
+    pub privacy: PrivacySection,
+    #[serde(default)]
+    pub ens: EnsSection,
+    #[serde(default)]
+    pub personhood: PersonhoodSection,
 }
 
 pub async fn load_config(path: &str) -> Result<Config, io::Error> {
@@ -37,18 +787,99 @@ pub async fn load_config(path: &str) -> Result<Config, io::Error> {
     Ok(config)
 }
 
-pub async fn load_genesis(path: &str) -> Result<HashMap<UserAddress, IdtAmount>, io::Error> {
-    let content = match fs::read_to_string(path).await {
-        Ok(content) => content,
-        Err(err) => {
-            log::warn!("Failed to read {}: {}", path, err);
-            return Ok(HashMap::new());
+// reads genesis balances from `path`. With `genesis.url` the file at `path` is a cache of the
+// published genesis, it is downloaded again if missing or if its checksum does not match.
+pub async fn load_genesis(
+    path: &str,
+    section: &GenesisSection,
+) -> Result<HashMap<UserAddress, IdtAmount>, io::Error> {
+    let Some((_, sha256)) = remote_genesis(section)? else {
+        let content = match fs::read_to_string(path).await {
+            Ok(content) => content,
+            Err(err) => {
+                log::warn!("Failed to read {}: {}", path, err);
+                return Ok(HashMap::new());
+            }
+        };
+        return Ok(serde_json::from_str(&content)?);
+    };
+    match fs::read(path).await {
+        Ok(content) if verify_checksum(&content, sha256).is_ok() => {
+            return Ok(serde_json::from_slice(&content)?);
         }
+        Ok(_) => log::warn!("Cached genesis {} is outdated, downloading it again", path),
+        Err(_) => {}
+    }
+    refresh_genesis(path, section).await
+}
+
+// downloads the genesis from `genesis.url`, verifies its checksum and caches it at `path`
+pub async fn refresh_genesis(
+    path: &str,
+    section: &GenesisSection,
+) -> Result<HashMap<UserAddress, IdtAmount>, io::Error> {
+    let Some((url, sha256)) = remote_genesis(section)? else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "genesis.url is not set",
+        ));
     };
-    let genesis: HashMap<UserAddress, IdtAmount> = serde_json::from_str(&content)?;
+    let content = fetch_genesis(url).await?;
+    verify_checksum(&content, sha256)?;
+    let genesis = serde_json::from_slice(&content)?;
+    fs::write(path, &content).await?;
+    log::info!("Genesis downloaded from {} to {}", url, path);
     Ok(genesis)
 }
 
+// url and checksum of the published genesis, None if the local file is used
+fn remote_genesis(section: &GenesisSection) -> Result<Option<(&str, &str)>, io::Error> {
+    match (&section.url, &section.sha256) {
+        (None, _) => Ok(None),
+        (Some(url), Some(sha256)) => Ok(Some((url, sha256))),
+        (Some(_), None) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "genesis.sha256 is required with genesis.url",
+        )),
+    }
+}
+
+fn verify_checksum(content: &[u8], expected: &str) -> Result<(), io::Error> {
+    let actual = format!("{:x}", Sha256::digest(content));
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("genesis checksum mismatch: expected {expected}, got {actual}"),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "federation")]
+async fn fetch_genesis(url: &str) -> Result<Vec<u8>, io::Error> {
+    let mut response = surf::get(url)
+        .await
+        .map_err(|e| io::Error::other(format!("failed to download {url}: {e}")))?;
+    if !response.status().is_success() {
+        return Err(io::Error::other(format!(
+            "failed to download {url}: status {}",
+            response.status()
+        )));
+    }
+    response
+        .body_bytes()
+        .await
+        .map_err(|e| io::Error::other(format!("failed to download {url}: {e}")))
+}
+
+#[cfg(not(feature = "federation"))]
+async fn fetch_genesis(_url: &str) -> Result<Vec<u8>, io::Error> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "genesis.url requires the federation feature",
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
@@ -78,9 +909,57 @@ mod tests {
         let cfg: Config = serde_json::from_str(json).unwrap();
         assert!(cfg.admins.admins.is_empty());
         assert!(cfg.admins.moderators.is_empty());
+        assert_eq!(cfg.identity.vouch_refresh, VouchRefreshPolicy::Overwrite);
+        assert!(!cfg.federation.proxy);
+        assert_eq!(cfg.scoring.full_score_idt, MAX_IDT_BY_PROOF);
+        assert_eq!(cfg.genesis.drift_policy, GenesisDriftPolicy::Reconcile);
+        let json = r#"{"genesis": {"drift_policy": "fail"}}"#;
+        let cfg: Config = serde_json::from_str(json).unwrap();
+        assert_eq!(cfg.genesis.drift_policy, GenesisDriftPolicy::Fail);
         // no external server configuration
     }
 
+    #[test]
+    fn test_parse_scoring_partial() {
+        let json = r#"{"scoring": {"external_weight": 0.25}}"#;
+        let cfg: Config = serde_json::from_str(json).unwrap();
+        assert_eq!(cfg.scoring.external_weight, 0.25);
+        assert_eq!(cfg.scoring.local_weight, 1.0);
+        assert_eq!(cfg.scoring.strategy, StrategyKind::VouchTree);
+        let json = r#"{"scoring": {"strategy": "proof_only"}}"#;
+        let cfg: Config = serde_json::from_str(json).unwrap();
+        assert_eq!(cfg.scoring.strategy, StrategyKind::ProofOnly);
+        let json = r#"{"scoring": {"strategy": "page_rank", "pagerank": {"damping": 0.5}}}"#;
+        let cfg: Config = serde_json::from_str(json).unwrap();
+        assert_eq!(cfg.scoring.strategy, StrategyKind::PageRank);
+        assert_eq!(cfg.scoring.pagerank.damping, 0.5);
+        assert_eq!(cfg.scoring.pagerank.max_iterations, 100);
+    }
+
+    #[test]
+    fn test_parse_maturity_bonus() {
+        let json = r#"{"identity": {"maturity_bonus": [{"age": 7776000, "ratio": {"numerator": 12, "denominator": 100}}]}}"#;
+        let cfg: Config = serde_json::from_str(json).unwrap();
+        assert_eq!(cfg.identity.maturity_bonus.len(), 1);
+        assert_eq!(cfg.identity.maturity_bonus[0].age, 7776000);
+        assert_eq!(cfg.identity.maturity_bonus[0].ratio.to_float(), 0.12);
+    }
+
+    #[test]
+    fn test_parse_vouch_refresh_policy() {
+        let json = r#"{"identity": {"vouch_refresh": {"policy": "reject_within", "days": 7}}}"#;
+        let cfg: Config = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            cfg.identity.vouch_refresh,
+            VouchRefreshPolicy::RejectWithin { days: 7 }
+        );
+        let json = r#"{"identity": {"vouch_refresh": {"policy": "keep_earliest"}}}"#;
+        let cfg: Config = serde_json::from_str(json).unwrap();
+        assert_eq!(cfg.identity.vouch_refresh, VouchRefreshPolicy::KeepEarliest);
+        let json = r#"{"identity": {"vouch_refresh": {"policy": "unknown"}}}"#;
+        assert!(serde_json::from_str::<Config>(json).is_err());
+    }
+
     #[async_std::test]
     async fn test_load_config_nonexistent_file() {
         let temp_dir = TempDir::new("config").unwrap();
@@ -120,7 +999,9 @@ mod tests {
     async fn test_load_genesis_nonexistent_file() {
         let temp_dir = TempDir::new("config").unwrap();
         let path = temp_dir.path().join("nonexistent.json");
-        let balances = load_genesis(path.to_str().unwrap()).await.unwrap();
+        let balances = load_genesis(path.to_str().unwrap(), &GenesisSection::default())
+            .await
+            .unwrap();
         assert!(balances.is_empty());
     }
 
@@ -128,21 +1009,100 @@ mod tests {
     async fn test_load_genesis_valid() {
         let temp_dir = TempDir::new("config").unwrap();
         let path = create_test_genesis_config(&temp_dir, "{\"alice\":1}").await;
-        let balances = load_genesis(path.to_str().unwrap()).await.unwrap();
-        assert_eq!(balances["alice"], 1);
+        let balances = load_genesis(path.to_str().unwrap(), &GenesisSection::default())
+            .await
+            .unwrap();
+        assert_eq!(balances["alice"], IdtAmount::new(1));
     }
 
     #[async_std::test]
     async fn test_load_genesis_invalid() {
         let temp_dir = TempDir::new("config").unwrap();
         let path = create_test_genesis_config(&temp_dir, "{").await;
-        assert!(load_genesis(path.to_str().unwrap()).await.is_err());
+        assert!(
+            load_genesis(path.to_str().unwrap(), &GenesisSection::default())
+                .await
+                .is_err()
+        );
     }
 
     #[async_std::test]
     async fn test_load_genesis_empty() {
         let temp_dir = TempDir::new("config").unwrap();
         let path = create_test_genesis_config(&temp_dir, "").await;
-        assert!(load_genesis(path.to_str().unwrap()).await.is_err());
+        assert!(
+            load_genesis(path.to_str().unwrap(), &GenesisSection::default())
+                .await
+                .is_err()
+        );
+    }
+
+    fn remote_genesis(url: &str, content: &str) -> GenesisSection {
+        GenesisSection {
+            url: Some(url.to_string()),
+            sha256: Some(format!("{:x}", Sha256::digest(content.as_bytes()))),
+            ..Default::default()
+        }
+    }
+
+    #[async_std::test]
+    async fn test_load_genesis_cached() {
+        let temp_dir = TempDir::new("config").unwrap();
+        let content = "{\"alice\":1}";
+        let path = create_test_genesis_config(&temp_dir, content).await;
+        // the cache matches the checksum, nothing is downloaded
+        let section = remote_genesis("http://127.0.0.1:1/genesis.json", content);
+        let balances = load_genesis(path.to_str().unwrap(), &section)
+            .await
+            .unwrap();
+        assert_eq!(balances.get("alice"), Some(&IdtAmount::new(1)));
+
+        // outdated cache cannot be downloaded again
+        let section = remote_genesis("http://127.0.0.1:1/genesis.json", "{}");
+        assert!(
+            load_genesis(path.to_str().unwrap(), &section)
+                .await
+                .is_err()
+        );
+
+        let section = GenesisSection {
+            sha256: None,
+            ..section
+        };
+        let error = load_genesis(path.to_str().unwrap(), &section)
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(feature = "http-api")]
+    #[async_std::test]
+    async fn test_load_genesis_remote() {
+        use tide::listener::Listener;
+
+        let content = "{\"alice\":5}";
+        let mut app = tide::new();
+        app.at("/genesis.json")
+            .get(move |_| async move { Ok(content) });
+        let mut listener = app.bind("127.0.0.1:0").await.unwrap();
+        let url = format!("{}/genesis.json", listener.info()[0].connection());
+        async_std::task::spawn(async move { listener.accept().await });
+
+        let temp_dir = TempDir::new("config").unwrap();
+        let path = temp_dir.path().join("genesis.json");
+        let path = path.to_str().unwrap();
+        let mut section = remote_genesis(&url, content);
+        section.sha256 = Some("00".repeat(32));
+        let error = load_genesis(path, &section).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(fs::read(path).await.is_err());
+
+        let section = remote_genesis(&url, content);
+        let balances = load_genesis(path, &section).await.unwrap();
+        assert_eq!(balances.get("alice"), Some(&IdtAmount::new(5)));
+        assert_eq!(fs::read_to_string(path).await.unwrap(), content);
+
+        let balances = refresh_genesis(path, &section).await.unwrap();
+        assert_eq!(balances.get("alice"), Some(&IdtAmount::new(5)));
     }
 }
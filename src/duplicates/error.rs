@@ -0,0 +1,7 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Identity error: {0}")]
+    IdentityError(#[from] crate::identity::error::Error),
+    #[error("Report error: {0}")]
+    ReportError(#[from] crate::reports::error::Error),
+}
@@ -0,0 +1,344 @@
+// Detection of likely duplicate identities.
+//
+// Two addresses of one person tend to be vouched by the same users and to vouch for the same
+// users, often within minutes of each other. `find_duplicates` compares the vouch edges of
+// every pair of addresses sharing an edge that few others share: the similarity is the
+// Jaccard index of their edge sets, the timing is the share of common edges created within
+// `timing_window` seconds of each other. Pairs reaching both thresholds are filed by the
+// server into the reports queue, where moderators dismiss them or punish the duplicate.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::DuplicatesSection,
+    duplicates::error::Error,
+    identity::{IdentityService, UserAddress},
+    reports::{Report, ReportId, storage::ReportStorage},
+};
+
+pub mod error;
+
+// page size of the reports queue scanned for pairs that are already filed
+const REPORTS_PAGE: usize = 100;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Edge {
+    VouchedBy(UserAddress),
+    Vouched(UserAddress),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Duplicate {
+    // the address with the later first vouch, it is the one reported
+    pub user: UserAddress,
+    pub original: UserAddress,
+    pub similarity: f64,
+    pub timing: f64,
+}
+
+impl Duplicate {
+    fn reason(&self) -> String {
+        format!(
+            "{} (similarity {:.2}, timing {:.2})",
+            reason_prefix(&self.original),
+            self.similarity,
+            self.timing
+        )
+    }
+}
+
+fn reason_prefix(original: &UserAddress) -> String {
+    format!("possible duplicate of {original}")
+}
+
+// vouch edges of every address with at least `min_edges` of them
+async fn vouch_edges(
+    service: &IdentityService,
+    min_edges: usize,
+) -> Result<HashMap<UserAddress, HashMap<Edge, u64>>, Error> {
+    let mut edges: HashMap<UserAddress, HashMap<Edge, u64>> = HashMap::new();
+    for (voucher, vouchee, timestamp) in service.vouches.all_vouches().await? {
+        edges
+            .entry(voucher.clone())
+            .or_default()
+            .insert(Edge::Vouched(vouchee.clone()), timestamp);
+        edges
+            .entry(vouchee)
+            .or_default()
+            .insert(Edge::VouchedBy(voucher), timestamp);
+    }
+    edges.retain(|_, user_edges| user_edges.len() >= min_edges.max(1));
+    Ok(edges)
+}
+
+// pairs of addresses sharing an edge, only addresses sharing one can be similar. An edge
+// shared by more than `max_fan_out` addresses, like a vouch of a user vouching for everyone,
+// does not make pairs, so there are at most `max_fan_out` squared pairs per edge.
+fn candidate_pairs(
+    edges: &HashMap<UserAddress, HashMap<Edge, u64>>,
+    max_fan_out: usize,
+) -> BTreeSet<(&UserAddress, &UserAddress)> {
+    let mut sharing: HashMap<&Edge, Vec<&UserAddress>> = HashMap::new();
+    for (user, user_edges) in edges {
+        for edge in user_edges.keys() {
+            sharing.entry(edge).or_default().push(user);
+        }
+    }
+    let mut pairs = BTreeSet::new();
+    for users in sharing.values().filter(|users| users.len() <= max_fan_out) {
+        for (i, a) in users.iter().enumerate() {
+            for b in &users[i + 1..] {
+                pairs.insert(if a < b { (*a, *b) } else { (*b, *a) });
+            }
+        }
+    }
+    pairs
+}
+
+pub async fn find_duplicates(
+    service: &IdentityService,
+    config: &DuplicatesSection,
+) -> Result<Vec<Duplicate>, Error> {
+    let edges = vouch_edges(service, config.min_edges).await?;
+    let pairs = candidate_pairs(&edges, config.max_fan_out);
+
+    let mut duplicates = vec![];
+    for (a, b) in pairs {
+        let (edges_a, edges_b) = (&edges[a], &edges[b]);
+        let common: Vec<(u64, u64)> = edges_a
+            .iter()
+            .filter_map(|(edge, t)| edges_b.get(edge).map(|u| (*t, *u)))
+            .collect();
+        let union = edges_a.len() + edges_b.len() - common.len();
+        let similarity = common.len() as f64 / union as f64;
+        let close = common
+            .iter()
+            .filter(|(t, u)| t.abs_diff(*u) <= config.timing_window)
+            .count();
+        let timing = close as f64 / common.len() as f64;
+        if similarity < config.similarity_threshold || timing < config.timing_threshold {
+            continue;
+        }
+        let first = |user_edges: &HashMap<Edge, u64>| user_edges.values().min().copied();
+        let (user, original) = if first(edges_b) >= first(edges_a) {
+            (b, a)
+        } else {
+            (a, b)
+        };
+        duplicates.push(Duplicate {
+            user: user.clone(),
+            original: original.clone(),
+            similarity,
+            timing,
+        });
+    }
+    Ok(duplicates)
+}
+
+// files a report for every found pair, skipping the pairs in `flagged` and the ones with an
+// open report of `reporter`. Filed pairs are added to `flagged`.
+pub async fn flag_duplicates(
+    service: &IdentityService,
+    reports: &dyn ReportStorage,
+    reporter: &UserAddress,
+    config: &DuplicatesSection,
+    flagged: &mut HashSet<(UserAddress, UserAddress)>,
+) -> Result<Vec<Report>, Error> {
+    let duplicates = find_duplicates(service, config).await?;
+    let open = open_reports_of(reports, reporter).await?;
+    let mut filed = vec![];
+    for duplicate in duplicates {
+        let pair = (duplicate.user.clone(), duplicate.original.clone());
+        if flagged.contains(&pair) {
+            continue;
+        }
+        let prefix = reason_prefix(&duplicate.original);
+        let reported = open
+            .iter()
+            .any(|report| report.user == duplicate.user && report.reason.starts_with(&prefix));
+        if !reported {
+            let report = reports
+                .add_report(
+                    reporter.clone(),
+                    duplicate.user.clone(),
+                    duplicate.reason(),
+                    service.now(),
+                )
+                .await?;
+            filed.push(report);
+        }
+        flagged.insert(pair);
+    }
+    Ok(filed)
+}
+
+async fn open_reports_of(
+    reports: &dyn ReportStorage,
+    reporter: &UserAddress,
+) -> Result<Vec<Report>, Error> {
+    let mut found = vec![];
+    let mut after_id: ReportId = 0;
+    loop {
+        let page = reports.open_reports(after_id, REPORTS_PAGE).await?;
+        let Some(last) = page.last() else {
+            return Ok(found);
+        };
+        after_id = last.id;
+        found.extend(
+            page.into_iter()
+                .filter(|report| &report.reporter == reporter),
+        );
+    }
+}
+
+// analyses the graph forever. Dismissed pairs are not filed again until a restart.
+pub async fn flag_periodically(
+    service: IdentityService,
+    reports: Arc<dyn ReportStorage>,
+    reporter: UserAddress,
+    config: DuplicatesSection,
+) {
+    let interval = Duration::from_secs(config.check_interval.max(1));
+    let mut flagged = HashSet::new();
+    loop {
+        match flag_duplicates(&service, &*reports, &reporter, &config, &mut flagged).await {
+            Ok(filed) if !filed.is_empty() => {
+                log::warn!("Flagged {} likely duplicate identities", filed.len())
+            }
+            Ok(_) => {}
+            Err(e) => log::error!("Failed to look for duplicates: {:?}", e),
+        }
+        async_std::task::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reports::storage::InMemoryReportStorage;
+
+    const SERVER: &str = "server";
+
+    async fn vouch_at(service: &IdentityService, voucher: &str, vouchee: &str, timestamp: u64) {
+        service
+            .vouches
+            .vouch(voucher.into(), vouchee.into(), timestamp)
+            .await
+            .unwrap();
+    }
+
+    // `original` and `copy` are vouched by the same three users, `copy` a minute later
+    async fn setup(delay: u64) -> IdentityService {
+        let service = IdentityService::default();
+        for (i, voucher) in ["userA", "userB", "userC"].into_iter().enumerate() {
+            let timestamp = 1000 * (i as u64 + 1);
+            vouch_at(&service, voucher, "original", timestamp).await;
+            vouch_at(&service, voucher, "copy", timestamp + delay).await;
+        }
+        vouch_at(&service, "userA", "other", 1).await;
+        vouch_at(&service, "userD", "other", 2).await;
+        vouch_at(&service, "userE", "other", 3).await;
+        service
+    }
+
+    #[async_std::test]
+    async fn test_find_duplicates() {
+        let config = DuplicatesSection::default();
+        let service = setup(60).await;
+        let duplicates = find_duplicates(&service, &config).await.unwrap();
+        assert_eq!(
+            duplicates,
+            vec![Duplicate {
+                user: "copy".into(),
+                original: "original".into(),
+                similarity: 1.0,
+                timing: 1.0,
+            }]
+        );
+
+        // an extra vouch lowers the similarity to 3/4
+        vouch_at(&service, "copy", "userD", 5000).await;
+        assert_eq!(find_duplicates(&service, &config).await.unwrap(), vec![]);
+        let lenient = DuplicatesSection {
+            similarity_threshold: 0.75,
+            ..config.clone()
+        };
+        assert_eq!(find_duplicates(&service, &lenient).await.unwrap().len(), 1);
+
+        // vouches a day apart are not correlated
+        let service = setup(24 * 60 * 60).await;
+        assert_eq!(find_duplicates(&service, &config).await.unwrap(), vec![]);
+        let untimed = DuplicatesSection {
+            timing_threshold: 0.0,
+            ..config.clone()
+        };
+        assert_eq!(find_duplicates(&service, &untimed).await.unwrap().len(), 1);
+
+        // addresses with fewer than `min_edges` edges are not compared
+        let strict = DuplicatesSection {
+            min_edges: 4,
+            ..config
+        };
+        let service = setup(60).await;
+        assert_eq!(find_duplicates(&service, &strict).await.unwrap(), vec![]);
+    }
+
+    #[async_std::test]
+    async fn test_fan_out() {
+        let config = DuplicatesSection::default();
+        let service = setup(60).await;
+        // every vouchee of the hub has enough edges to be compared with all the others
+        for i in 0..1000 {
+            let vouchee = format!("vouchee{i}");
+            vouch_at(&service, "hub", &vouchee, 1).await;
+            vouch_at(&service, &format!("first{i}"), &vouchee, 2).await;
+            vouch_at(&service, &format!("second{i}"), &vouchee, 3).await;
+        }
+        let edges = vouch_edges(&service, config.min_edges).await.unwrap();
+        // only the pairs of `original`, `copy` and `other` are left
+        assert_eq!(candidate_pairs(&edges, config.max_fan_out).len(), 3);
+        assert_eq!(candidate_pairs(&edges, 1000).len(), 1000 * 999 / 2 + 3);
+        assert_eq!(find_duplicates(&service, &config).await.unwrap().len(), 1);
+    }
+
+    #[async_std::test]
+    async fn test_flag_duplicates() {
+        let config = DuplicatesSection::default();
+        let service = setup(60).await;
+        let reports = InMemoryReportStorage::default();
+        let mut flagged = HashSet::new();
+        let filed = flag_duplicates(&service, &reports, &SERVER.into(), &config, &mut flagged)
+            .await
+            .unwrap();
+        assert_eq!(filed.len(), 1);
+        assert_eq!(filed[0].reporter, SERVER);
+        assert_eq!(filed[0].user, "copy");
+        assert_eq!(
+            filed[0].reason,
+            "possible duplicate of original (similarity 1.00, timing 1.00)"
+        );
+
+        // the pair is filed once, also after a restart while the report is open
+        let filed = flag_duplicates(&service, &reports, &SERVER.into(), &config, &mut flagged)
+            .await
+            .unwrap();
+        assert!(filed.is_empty());
+        let filed = flag_duplicates(
+            &service,
+            &reports,
+            &SERVER.into(),
+            &config,
+            &mut HashSet::new(),
+        )
+        .await
+        .unwrap();
+        assert!(filed.is_empty());
+        assert_eq!(reports.open_reports(0, 10).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,11 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid encryption key: {0}")]
+    InvalidKey(String),
+    #[error("Unknown encryption key: {0}")]
+    UnknownKey(String),
+    #[error("Failed to decrypt value")]
+    DecryptionFailed,
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
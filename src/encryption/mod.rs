@@ -0,0 +1,249 @@
+// Field-level encryption of user addresses stored in the database.
+//
+// Values are encrypted with AES-256-GCM using a nonce derived from the key and the value,
+// so the same address always maps to the same ciphertext and can still be used in
+// `WHERE` clauses and primary keys. Equal addresses are therefore visible as equal in a
+// database dump, but the addresses themselves are not.
+//
+// Encrypted values look like `enc:<key id>:<hex>`. The first key of the keyring encrypts
+// new values, the rest are only used to decrypt values written before rotation. Tables
+// are re-encrypted with the current key on startup, see `rotate_column`.
+
+use std::{env, sync::Arc};
+
+use aes_gcm::{
+    Aes256Gcm, Key, KeyInit, Nonce,
+    aead::{Aead, Payload},
+};
+use ethers_core::utils::keccak256;
+use sqlx::Row;
+
+use crate::{encryption::error::Error, pools::MeteredPool};
+
+pub mod error;
+
+// comma separated list of `<key id>:<hex encoded 32 bytes key>`, current key goes first
+pub const ENCRYPTION_KEYS_ENV: &str = "DB_ENCRYPTION_KEYS";
+
+const PREFIX: &str = "enc";
+const NONCE_SIZE: usize = 12;
+
+// encrypts nothing if created without keys
+#[derive(Clone, Default)]
+pub struct FieldCipher {
+    keys: Arc<Vec<(String, [u8; 32])>>,
+}
+
+impl FieldCipher {
+    pub fn new(keys: Vec<(String, [u8; 32])>) -> Result<Self, Error> {
+        for (id, _) in &keys {
+            if id.is_empty() || id.contains(':') {
+                return Err(Error::InvalidKey(id.clone()));
+            }
+        }
+        Ok(Self {
+            keys: Arc::new(keys),
+        })
+    }
+
+    pub fn parse(keyring: &str) -> Result<Self, Error> {
+        let mut keys = vec![];
+        for entry in keyring.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (id, key) = entry
+                .split_once(':')
+                .ok_or_else(|| Error::InvalidKey(entry.to_string()))?;
+            let key: [u8; 32] = hex::decode(key.trim_start_matches("0x"))
+                .ok()
+                .and_then(|k| k.try_into().ok())
+                .ok_or_else(|| Error::InvalidKey(id.to_string()))?;
+            keys.push((id.to_string(), key));
+        }
+        Self::new(keys)
+    }
+
+    pub fn from_env() -> Result<Self, Error> {
+        Self::parse(&env::var(ENCRYPTION_KEYS_ENV).unwrap_or_default())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    pub fn encode(&self, value: &str) -> String {
+        let Some((id, key)) = self.keys.first() else {
+            return value.to_string();
+        };
+        let mut seed = key.to_vec();
+        seed.extend_from_slice(value.as_bytes());
+        let hash = keccak256(seed);
+        let nonce = &hash[..NONCE_SIZE];
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+        let payload = Payload {
+            msg: value.as_bytes(),
+            aad: id.as_bytes(),
+        };
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(nonce), payload)
+            .expect("AES-GCM encryption of short values does not fail");
+        format!(
+            "{PREFIX}:{id}:{}{}",
+            hex::encode(nonce),
+            hex::encode(ciphertext)
+        )
+    }
+
+    // values without the encryption prefix are returned as is
+    pub fn decode(&self, value: &str) -> Result<String, Error> {
+        let Some((id, data)) = value
+            .strip_prefix(PREFIX)
+            .and_then(|v| v.strip_prefix(':'))
+            .and_then(|v| v.split_once(':'))
+        else {
+            return Ok(value.to_string());
+        };
+        let (_, key) = self
+            .keys
+            .iter()
+            .find(|(key_id, _)| key_id == id)
+            .ok_or_else(|| Error::UnknownKey(id.to_string()))?;
+        let data = hex::decode(data).map_err(|_| Error::DecryptionFailed)?;
+        if data.len() < NONCE_SIZE {
+            return Err(Error::DecryptionFailed);
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_SIZE);
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+        let payload = Payload {
+            msg: ciphertext,
+            aad: id.as_bytes(),
+        };
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| Error::DecryptionFailed)?;
+        String::from_utf8(plaintext).map_err(|_| Error::DecryptionFailed)
+    }
+}
+
+// re-encrypts all values of the column with the current key, also encrypts plain values
+// and decrypts them if encryption is disabled. Returns the number of updated values.
+pub async fn rotate_column(
+    pool: &MeteredPool,
+    cipher: &FieldCipher,
+    table: &str,
+    column: &str,
+) -> Result<u64, Error> {
+    let rows = sqlx::query(&format!("SELECT DISTINCT {column} FROM {table}"))
+        .fetch_all(pool)
+        .await?;
+    let mut updated = 0;
+    for row in rows {
+        let stored: String = row.get(0);
+        let encoded = cipher.encode(&cipher.decode(&stored)?);
+        if encoded == stored {
+            continue;
+        }
+        sqlx::query(&format!(
+            "UPDATE {table} SET {column} = ? WHERE {column} = ?"
+        ))
+        .bind(&encoded)
+        .bind(&stored)
+        .execute(pool)
+        .await?;
+        updated += 1;
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pools;
+
+    fn test_cipher(id: &str, byte: u8) -> FieldCipher {
+        FieldCipher::new(vec![(id.to_string(), [byte; 32])]).unwrap()
+    }
+
+    #[test]
+    fn test_basic() {
+        let cipher = test_cipher("k1", 1);
+        let encoded = cipher.encode("0xuser");
+        assert!(encoded.starts_with("enc:k1:"));
+        assert!(!encoded.contains("user"));
+        // deterministic, so encoded values can be queried
+        assert_eq!(encoded, cipher.encode("0xuser"));
+        assert_ne!(encoded, cipher.encode("0xother"));
+        assert_eq!(cipher.decode(&encoded).unwrap(), "0xuser");
+        // plain values are passed through
+        assert_eq!(cipher.decode("0xuser").unwrap(), "0xuser");
+
+        let disabled = FieldCipher::default();
+        assert!(!disabled.is_enabled());
+        assert_eq!(disabled.encode("0xuser"), "0xuser");
+        assert!(matches!(
+            disabled.decode(&encoded),
+            Err(Error::UnknownKey(_))
+        ));
+        assert!(matches!(
+            test_cipher("k1", 2).decode(&encoded),
+            Err(Error::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn test_parse() {
+        let key1 = hex::encode([1u8; 32]);
+        let key2 = hex::encode([2u8; 32]);
+        let cipher = FieldCipher::parse(&format!("k2:{key2}, k1:0x{key1}")).unwrap();
+        assert!(cipher.encode("user").starts_with("enc:k2:"));
+        let old = test_cipher("k1", 1).encode("user");
+        assert_eq!(cipher.decode(&old).unwrap(), "user");
+
+        assert!(!FieldCipher::parse("").unwrap().is_enabled());
+        assert!(FieldCipher::parse("k1").is_err());
+        assert!(FieldCipher::parse("k1:1234").is_err());
+        assert!(FieldCipher::parse(&format!(":{key1}")).is_err());
+    }
+
+    #[async_std::test]
+    async fn test_rotate_column() {
+        let pool = pools::connect("users", "sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE users (user TEXT PRIMARY KEY)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO users (user) VALUES ('a'), ('b')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let values = || async {
+            let mut values: Vec<String> = sqlx::query("SELECT user FROM users")
+                .fetch_all(&pool)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|r| r.get(0))
+                .collect();
+            values.sort();
+            values
+        };
+
+        let k1 = test_cipher("k1", 1);
+        assert_eq!(rotate_column(&pool, &k1, "users", "user").await.unwrap(), 2);
+        assert_eq!(rotate_column(&pool, &k1, "users", "user").await.unwrap(), 0);
+        let mut expected = vec![k1.encode("a"), k1.encode("b")];
+        expected.sort();
+        assert_eq!(values().await, expected);
+
+        let k2 = FieldCipher::new(vec![
+            ("k2".to_string(), [2; 32]),
+            ("k1".to_string(), [1; 32]),
+        ])
+        .unwrap();
+        assert_eq!(rotate_column(&pool, &k2, "users", "user").await.unwrap(), 2);
+        let mut expected = vec![k2.encode("a"), k2.encode("b")];
+        expected.sort();
+        assert_eq!(values().await, expected);
+
+        // old key is gone
+        assert!(rotate_column(&pool, &k1, "users", "user").await.is_err());
+    }
+}
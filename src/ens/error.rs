@@ -0,0 +1,7 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Request to {0} failed: {1}")]
+    RequestError(String, String),
+    #[error("Unexpected response from {0}: {1}")]
+    ResponseError(String, String),
+}
@@ -0,0 +1,418 @@
+// ENS names of user addresses.
+//
+// With `ens.rpc_url` set, names are resolved with `eth_call`s of the ENS registry and the
+// resolver contracts through an Ethereum node. `GET /idt/by_name/:name` returns the balance
+// of the address a name points to, and `GET /idt/:user` includes the primary name of the
+// user. Answers are cached for `ens.cache_ttl` seconds, including missing names.
+
+use std::{collections::HashMap, sync::Arc};
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+use ethers_core::{
+    abi::{ParamType, Token, decode},
+    types::H160,
+    utils::{id, keccak256},
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    config::EnsSection,
+    ens::error::Error,
+    federation::cache::TtlCache,
+    identity::{UserAddress, clock::Clock},
+    verify::address_to_string,
+};
+
+pub mod error;
+
+// longest accepted name, as DNS names
+pub const MAX_NAME_LENGTH: usize = 255;
+
+#[async_trait]
+pub trait NameResolver: Send + Sync {
+    // address the name points to
+    async fn resolve(&self, name: &str) -> Result<Option<UserAddress>, Error>;
+    // primary name of the address, only if the name resolves back to the address
+    async fn lookup(&self, address: &UserAddress) -> Result<Option<String>, Error>;
+}
+
+// resolves nothing, used without `ens.rpc_url`
+#[derive(Default)]
+pub struct NoNameResolver;
+
+#[async_trait]
+impl NameResolver for NoNameResolver {
+    async fn resolve(&self, _name: &str) -> Result<Option<UserAddress>, Error> {
+        Ok(None)
+    }
+
+    async fn lookup(&self, _address: &UserAddress) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+}
+
+// names kept in memory, useful for tests
+#[derive(Default)]
+pub struct InMemoryNameResolver {
+    // key - name, value - address
+    names: RwLock<HashMap<String, UserAddress>>,
+    // key - address, value - primary name
+    primary: RwLock<HashMap<UserAddress, String>>,
+}
+
+impl InMemoryNameResolver {
+    pub async fn set_name(&self, name: &str, address: &UserAddress, primary: bool) {
+        self.names
+            .write()
+            .await
+            .insert(name.to_lowercase(), address.clone());
+        if primary {
+            self.primary
+                .write()
+                .await
+                .insert(address.clone(), name.to_lowercase());
+        }
+    }
+}
+
+#[async_trait]
+impl NameResolver for InMemoryNameResolver {
+    async fn resolve(&self, name: &str) -> Result<Option<UserAddress>, Error> {
+        Ok(self.names.read().await.get(&name.to_lowercase()).cloned())
+    }
+
+    async fn lookup(&self, address: &UserAddress) -> Result<Option<String>, Error> {
+        Ok(self.primary.read().await.get(address).cloned())
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    result: Option<String>,
+    error: Option<RpcError>,
+}
+
+// output of a read-only call of the contract at `to` through the node at `rpc_url`, empty if
+// the contract has no code
+pub async fn eth_call(rpc_url: &str, to: &str, data: &[u8]) -> Result<Vec<u8>, Error> {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "eth_call",
+        "params": [{"to": to, "data": format!("0x{}", hex::encode(data))}, "latest"],
+    });
+    let request_error = |e: surf::Error| Error::RequestError(rpc_url.to_string(), e.to_string());
+    let mut response = surf::post(rpc_url)
+        .body_json(&request)
+        .map_err(request_error)?
+        .await
+        .map_err(request_error)?;
+    if !response.status().is_success() {
+        return Err(Error::ResponseError(
+            rpc_url.to_string(),
+            format!("status {}", response.status()),
+        ));
+    }
+    let response_error = |e: String| Error::ResponseError(rpc_url.to_string(), e);
+    let response: RpcResponse = response
+        .body_json()
+        .await
+        .map_err(|e| response_error(e.to_string()))?;
+    if let Some(error) = response.error {
+        return Err(response_error(error.message));
+    }
+    let result = response
+        .result
+        .ok_or_else(|| response_error("no result".to_string()))?;
+    hex::decode(result.trim_start_matches("0x")).map_err(|e| response_error(e.to_string()))
+}
+
+// resolves names with JSON-RPC calls of an Ethereum node
+pub struct RpcNameResolver {
+    rpc_url: String,
+    registry: String,
+}
+
+impl RpcNameResolver {
+    pub fn new(rpc_url: String, registry: String) -> Self {
+        Self { rpc_url, registry }
+    }
+
+    async fn call(&self, to: &str, function: &str, node: [u8; 32]) -> Result<Vec<u8>, Error> {
+        let data = [id(function).as_slice(), &node].concat();
+        eth_call(&self.rpc_url, to, &data).await
+    }
+
+    // single ABI encoded value of the call output, `None` for empty output
+    async fn call_value(
+        &self,
+        to: &str,
+        function: &str,
+        node: [u8; 32],
+        kind: ParamType,
+    ) -> Result<Option<Token>, Error> {
+        let output = self.call(to, function, node).await?;
+        if output.is_empty() {
+            return Ok(None);
+        }
+        let mut tokens = decode(&[kind], &output)
+            .map_err(|e| Error::ResponseError(self.rpc_url.clone(), e.to_string()))?;
+        Ok(tokens.pop())
+    }
+
+    async fn call_address(
+        &self,
+        to: &str,
+        function: &str,
+        node: [u8; 32],
+    ) -> Result<Option<H160>, Error> {
+        match self
+            .call_value(to, function, node, ParamType::Address)
+            .await?
+        {
+            Some(Token::Address(address)) if !address.is_zero() => Ok(Some(address)),
+            _ => Ok(None),
+        }
+    }
+
+    async fn resolver(&self, node: [u8; 32]) -> Result<Option<String>, Error> {
+        let resolver = self
+            .call_address(&self.registry, "resolver(bytes32)", node)
+            .await?;
+        Ok(resolver.map(|resolver| address_to_string(&resolver)))
+    }
+}
+
+#[async_trait]
+impl NameResolver for RpcNameResolver {
+    async fn resolve(&self, name: &str) -> Result<Option<UserAddress>, Error> {
+        let node = namehash(name);
+        let Some(resolver) = self.resolver(node).await? else {
+            return Ok(None);
+        };
+        let address = self.call_address(&resolver, "addr(bytes32)", node).await?;
+        Ok(address.map(|address| address_to_string(&address)))
+    }
+
+    async fn lookup(&self, address: &UserAddress) -> Result<Option<String>, Error> {
+        // only Ethereum addresses have names
+        let Ok(parsed) = address.parse::<H160>() else {
+            return Ok(None);
+        };
+        let node = namehash(&format!("{}.addr.reverse", hex::encode(parsed)));
+        let Some(resolver) = self.resolver(node).await? else {
+            return Ok(None);
+        };
+        let name = match self
+            .call_value(&resolver, "name(bytes32)", node, ParamType::String)
+            .await?
+        {
+            Some(Token::String(name)) if valid_name(&name) => name,
+            _ => return Ok(None),
+        };
+        // anyone can claim any name in their reverse record
+        let forward = self.resolve(&name).await?;
+        Ok(forward
+            .filter(|forward| *forward == address_to_string(&parsed))
+            .map(|_| name))
+    }
+}
+
+// keeps the answers of another resolver for `ttl` seconds. Failed requests are not cached.
+pub struct CachedNameResolver {
+    inner: Arc<dyn NameResolver>,
+    clock: Arc<dyn Clock>,
+    addresses: TtlCache<Option<UserAddress>>,
+    names: TtlCache<Option<String>>,
+}
+
+impl CachedNameResolver {
+    pub fn new(inner: Arc<dyn NameResolver>, clock: Arc<dyn Clock>, ttl: u64) -> Self {
+        Self {
+            inner,
+            clock,
+            addresses: TtlCache::new(ttl),
+            names: TtlCache::new(ttl),
+        }
+    }
+}
+
+#[async_trait]
+impl NameResolver for CachedNameResolver {
+    async fn resolve(&self, name: &str) -> Result<Option<UserAddress>, Error> {
+        let key = name.to_lowercase();
+        let now = self.clock.now();
+        if let Some(address) = self.addresses.get(&key, now).await {
+            return Ok(address);
+        }
+        let address = self.inner.resolve(name).await?;
+        self.addresses.insert(key, address.clone(), now).await;
+        Ok(address)
+    }
+
+    async fn lookup(&self, address: &UserAddress) -> Result<Option<String>, Error> {
+        let key = address.to_lowercase();
+        let now = self.clock.now();
+        if let Some(name) = self.names.get(&key, now).await {
+            return Ok(name);
+        }
+        let name = self.inner.lookup(address).await?;
+        self.names.insert(key, name.clone(), now).await;
+        Ok(name)
+    }
+}
+
+pub fn name_resolver(config: &EnsSection, clock: Arc<dyn Clock>) -> Arc<dyn NameResolver> {
+    let Some(rpc_url) = &config.rpc_url else {
+        return Arc::new(NoNameResolver);
+    };
+    let resolver = RpcNameResolver::new(rpc_url.clone(), config.registry.clone());
+    Arc::new(CachedNameResolver::new(
+        Arc::new(resolver),
+        clock,
+        config.cache_ttl,
+    ))
+}
+
+// dot separated labels, e.g. `alice.eth`
+pub fn valid_name(name: &str) -> bool {
+    name.len() <= MAX_NAME_LENGTH
+        && name.contains('.')
+        && name.split('.').all(|label| !label.is_empty())
+}
+
+// EIP-137 node of the name. Labels are lowercased, other UTS-46 normalization is up to
+// clients.
+pub fn namehash(name: &str) -> [u8; 32] {
+    let mut node = [0u8; 32];
+    if name.is_empty() {
+        return node;
+    }
+    for label in name.rsplit('.') {
+        let label = keccak256(label.to_lowercase().as_bytes());
+        node = keccak256([node, label].concat());
+    }
+    node
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::clock::MockClock;
+    use ethers_core::abi::encode;
+    use tide::{Request, listener::Listener};
+
+    const ALICE: &str = "0x00000000000000000000000000000000000000a1";
+    const RESOLVER: &str = "0x00000000000000000000000000000000000000e5";
+
+    #[test]
+    fn test_namehash() {
+        assert_eq!(namehash(""), [0u8; 32]);
+        assert_eq!(
+            hex::encode(namehash("eth")),
+            "93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae"
+        );
+        assert_eq!(
+            hex::encode(namehash("foo.eth")),
+            "de9b09fd7c5f901e23a3f19fecc54828e9c848539801e86591bd9801b019f84f"
+        );
+        assert_eq!(namehash("Foo.ETH"), namehash("foo.eth"));
+        assert!(valid_name("alice.eth"));
+        assert!(!valid_name("alice"));
+        assert!(!valid_name("alice..eth"));
+    }
+
+    // node with a registry at `registry` and a resolver at `RESOLVER`. `alice.eth` points
+    // to `ALICE`, who claims `alice.eth` and `bob.eth` claims `alice.eth` too.
+    async fn mock_node() -> String {
+        async fn eth_call(mut req: Request<()>) -> tide::Result {
+            let body: serde_json::Value = req.body_json().await?;
+            let call = &body["params"][0];
+            let data = hex::decode(call["data"].as_str().unwrap().trim_start_matches("0x"))?;
+            let (selector, node) = data.split_at(4);
+            let node: [u8; 32] = node.try_into()?;
+            let alice = namehash("alice.eth");
+            let alice_reverse = namehash(&format!("{}.addr.reverse", &ALICE[2..]));
+            let bob_reverse = namehash(&format!("{}.addr.reverse", "b".repeat(40)));
+            let resolver: H160 = RESOLVER.parse()?;
+            let output = match (call["to"].as_str().unwrap(), selector) {
+                (RESOLVER, s) if s == id("addr(bytes32)") && node == alice => {
+                    encode(&[Token::Address(ALICE.parse()?)])
+                }
+                (RESOLVER, s)
+                    if s == id("name(bytes32)")
+                        && (node == alice_reverse || node == bob_reverse) =>
+                {
+                    encode(&[Token::String("alice.eth".into())])
+                }
+                (RESOLVER, _) => encode(&[Token::Address(H160::zero())]),
+                (_, s) if s == id("resolver(bytes32)") => encode(&[Token::Address(resolver)]),
+                _ => vec![],
+            };
+            Ok(
+                json!({"jsonrpc": "2.0", "id": 1, "result": format!("0x{}", hex::encode(output))})
+                    .into(),
+            )
+        }
+
+        let mut app = tide::new();
+        app.at("/").post(eth_call);
+        let mut listener = app.bind("127.0.0.1:0").await.unwrap();
+        let url = listener.info()[0].connection().to_string();
+        async_std::task::spawn(async move { listener.accept().await });
+        url
+    }
+
+    #[async_std::test]
+    async fn test_rpc() {
+        let url = mock_node().await;
+        let registry = address_to_string(&H160::repeat_byte(0xee));
+        let resolver = RpcNameResolver::new(url, registry);
+        assert_eq!(
+            resolver.resolve("Alice.eth").await.unwrap(),
+            Some(ALICE.to_string())
+        );
+        assert_eq!(resolver.resolve("bob.eth").await.unwrap(), None);
+        assert_eq!(
+            resolver.lookup(&ALICE.to_string()).await.unwrap(),
+            Some("alice.eth".to_string())
+        );
+        // the reverse record of bob does not resolve back to bob
+        let bob = format!("0x{}", "b".repeat(40));
+        assert_eq!(resolver.lookup(&bob).await.unwrap(), None);
+        assert_eq!(resolver.lookup(&"userA".to_string()).await.unwrap(), None);
+
+        let unreachable = RpcNameResolver::new("http://127.0.0.1:1".into(), RESOLVER.into());
+        assert!(matches!(
+            unreachable.resolve("alice.eth").await,
+            Err(Error::RequestError(_, _))
+        ));
+    }
+
+    #[async_std::test]
+    async fn test_cache() {
+        let inner = Arc::new(InMemoryNameResolver::default());
+        let clock = Arc::new(MockClock::default());
+        let cached = CachedNameResolver::new(inner.clone(), clock.clone(), 60);
+        assert_eq!(cached.resolve("alice.eth").await.unwrap(), None);
+        inner.set_name("alice.eth", &ALICE.to_string(), true).await;
+        // missing names are cached too
+        assert_eq!(cached.resolve("alice.eth").await.unwrap(), None);
+        clock.advance(60);
+        assert_eq!(
+            cached.resolve("ALICE.eth").await.unwrap(),
+            Some(ALICE.to_string())
+        );
+        assert_eq!(
+            cached.lookup(&ALICE.to_string()).await.unwrap(),
+            Some("alice.eth".to_string())
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    events::{EventLog, RecordedEvent, Snapshot},
+    identity::error::Error,
+    pools::{self, MeteredPool},
+};
+
+// events and snapshots are stored as JSON, encrypted as a whole since they contain
+// user addresses
+pub struct DatabaseEventLog {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabaseEventLog {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("events", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS events (seq INTEGER PRIMARY KEY, recorded_at INTEGER NOT NULL, data TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS snapshots (seq INTEGER PRIMARY KEY, recorded_at INTEGER NOT NULL, data TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "events", "data").await?;
+        rotate_column(&pool, &cipher, "snapshots", "data").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl EventLog for DatabaseEventLog {
+    async fn append(&self, event: &RecordedEvent) -> Result<(), Error> {
+        let data = serde_json::to_string(&event.event)?;
+        sqlx::query("INSERT INTO events (seq, recorded_at, data) VALUES (?, ?, ?)")
+            .bind(event.seq as i64)
+            .bind(event.recorded_at as i64)
+            .bind(self.cipher.encode(&data))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn events(&self, after_seq: u64, until: u64) -> Result<Vec<RecordedEvent>, Error> {
+        let rows = sqlx::query(
+            "SELECT seq, recorded_at, data FROM events WHERE seq > ? AND recorded_at <= ? ORDER BY seq",
+        )
+        .bind(after_seq as i64)
+        .bind(until.min(i64::MAX as u64) as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut events = vec![];
+        for row in rows {
+            let data = self.cipher.decode(&row.get::<String, _>(2))?;
+            events.push(RecordedEvent {
+                seq: row.get::<i64, _>(0) as u64,
+                recorded_at: row.get::<i64, _>(1) as u64,
+                event: serde_json::from_str(&data)?,
+            });
+        }
+        Ok(events)
+    }
+
+    async fn save_snapshot(&self, snapshot: &Snapshot) -> Result<(), Error> {
+        let data = serde_json::to_string(&snapshot.projection)?;
+        sqlx::query("REPLACE INTO snapshots (seq, recorded_at, data) VALUES (?, ?, ?)")
+            .bind(snapshot.seq as i64)
+            .bind(snapshot.recorded_at as i64)
+            .bind(self.cipher.encode(&data))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn snapshot(&self, until: u64) -> Result<Option<Snapshot>, Error> {
+        let row = sqlx::query(
+            "SELECT seq, recorded_at, data FROM snapshots WHERE recorded_at <= ? ORDER BY seq DESC LIMIT 1",
+        )
+        .bind(until.min(i64::MAX as u64) as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let data = self.cipher.decode(&row.get::<String, _>(2))?;
+        Ok(Some(Snapshot {
+            seq: row.get::<i64, _>(0) as u64,
+            recorded_at: row.get::<i64, _>(1) as u64,
+            projection: serde_json::from_str(&data)?,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{Event, projection::Projection};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let log = DatabaseEventLog::new("sqlite::memory:").await.unwrap();
+        assert!(log.events(0, u64::MAX).await.unwrap().is_empty());
+        assert!(log.snapshot(u64::MAX).await.unwrap().is_none());
+
+        let mut projection = Projection::default();
+        for seq in 1..=3 {
+            let event = RecordedEvent {
+                seq,
+                recorded_at: seq * 10,
+                event: Event::Vouch {
+                    voucher: "a".to_string(),
+                    vouchee: format!("user{seq}"),
+                    timestamp: seq,
+                },
+            };
+            log.append(&event).await.unwrap();
+            projection.apply(&event.event);
+            if seq == 2 {
+                log.save_snapshot(&Snapshot {
+                    seq,
+                    recorded_at: seq * 10,
+                    projection: projection.clone(),
+                })
+                .await
+                .unwrap();
+            }
+        }
+
+        let events = log.events(1, 20).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].seq, 2);
+        assert_eq!(log.events(0, u64::MAX).await.unwrap().len(), 3);
+
+        assert!(log.snapshot(19).await.unwrap().is_none());
+        let snapshot = log.snapshot(u64::MAX).await.unwrap().unwrap();
+        assert_eq!(snapshot.seq, 2);
+        assert_eq!(snapshot.projection.vouchees["a"].len(), 2);
+    }
+}
@@ -0,0 +1,150 @@
+// Event sourced persistence of vouches, proofs and penalties.
+//
+// Every mutation is appended to the event log and applied to an in-memory projection that
+// serves reads. The projection is saved as a snapshot every `events.snapshot_interval`
+// events, so startup only replays the events after the latest snapshot. Since the log is
+// never rewritten, the state at any past moment can be rebuilt, see
+// `EventSourcedStorage::service_at`.
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::projection::Projection,
+    identity::{ModeratorProof, SystemPenalty, UserAddress, error::Error},
+};
+
+pub mod db;
+pub mod projection;
+pub mod storage;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Vouch {
+        voucher: UserAddress,
+        vouchee: UserAddress,
+        timestamp: u64,
+    },
+    RemoveVouch {
+        voucher: UserAddress,
+        vouchee: UserAddress,
+    },
+    SetProof {
+        user: UserAddress,
+        proof: ModeratorProof,
+    },
+    RemoveProof {
+        user: UserAddress,
+    },
+    RevokeProofs {
+        moderator: UserAddress,
+    },
+    SetModeratorPenalty {
+        user: UserAddress,
+        penalty: ModeratorProof,
+    },
+    RemoveModeratorPenalty {
+        user: UserAddress,
+    },
+    SetForgottenPenalty {
+        user: UserAddress,
+        forgotten: UserAddress,
+        penalty: SystemPenalty,
+    },
+    RemoveForgottenPenalty {
+        user: UserAddress,
+        forgotten: UserAddress,
+    },
+}
+
+impl Event {
+    // users whose own vouches, proof or penalties are changed by the event
+    pub fn users(&self) -> Vec<&UserAddress> {
+        match self {
+            Event::Vouch {
+                voucher, vouchee, ..
+            }
+            | Event::RemoveVouch { voucher, vouchee } => vec![voucher, vouchee],
+            Event::SetProof { user, .. }
+            | Event::RemoveProof { user }
+            | Event::SetModeratorPenalty { user, .. }
+            | Event::RemoveModeratorPenalty { user } => vec![user],
+            // proofs of the revoked users are not listed in the event
+            Event::RevokeProofs { moderator } => vec![moderator],
+            Event::SetForgottenPenalty {
+                user, forgotten, ..
+            }
+            | Event::RemoveForgottenPenalty { user, forgotten } => vec![user, forgotten],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    // position in the log, starts from 1
+    pub seq: u64,
+    // server time when the event was appended
+    pub recorded_at: u64,
+    pub event: Event,
+}
+
+// projection after applying all events up to `seq`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub seq: u64,
+    pub recorded_at: u64,
+    pub projection: Projection,
+}
+
+#[async_trait]
+pub trait EventLog: Send + Sync {
+    async fn append(&self, event: &RecordedEvent) -> Result<(), Error>;
+    // events after `after_seq` recorded no later than `until`, ordered by seq
+    async fn events(&self, after_seq: u64, until: u64) -> Result<Vec<RecordedEvent>, Error>;
+    async fn save_snapshot(&self, snapshot: &Snapshot) -> Result<(), Error>;
+    // latest snapshot recorded no later than `until`
+    async fn snapshot(&self, until: u64) -> Result<Option<Snapshot>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryEventLog {
+    events: RwLock<Vec<RecordedEvent>>,
+    snapshots: RwLock<Vec<Snapshot>>,
+}
+
+#[async_trait]
+impl EventLog for InMemoryEventLog {
+    async fn append(&self, event: &RecordedEvent) -> Result<(), Error> {
+        self.events.write().await.push(event.clone());
+        Ok(())
+    }
+
+    async fn events(&self, after_seq: u64, until: u64) -> Result<Vec<RecordedEvent>, Error> {
+        Ok(self
+            .events
+            .read()
+            .await
+            .iter()
+            .filter(|e| e.seq > after_seq && e.recorded_at <= until)
+            .cloned()
+            .collect())
+    }
+
+    async fn save_snapshot(&self, snapshot: &Snapshot) -> Result<(), Error> {
+        self.snapshots.write().await.push(snapshot.clone());
+        Ok(())
+    }
+
+    async fn snapshot(&self, until: u64) -> Result<Option<Snapshot>, Error> {
+        Ok(self
+            .snapshots
+            .read()
+            .await
+            .iter()
+            .filter(|s| s.recorded_at <= until)
+            .max_by_key(|s| s.seq)
+            .cloned())
+    }
+}
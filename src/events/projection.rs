@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::Event,
+    identity::{ModeratorProof, SystemPenalty, UserAddress},
+};
+
+// current state rebuilt from the events, also stored in snapshots
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Projection {
+    // key - vouchee, value - (voucher, timestamp) map
+    pub vouchers: BTreeMap<UserAddress, BTreeMap<UserAddress, u64>>,
+    // key - voucher, value - (vouchee, timestamp) map
+    pub vouchees: BTreeMap<UserAddress, BTreeMap<UserAddress, u64>>,
+    pub proofs: BTreeMap<UserAddress, ModeratorProof>,
+    // proofs of moderators whose proofs were revoked
+    #[serde(default)]
+    pub revoked_proofs: BTreeMap<UserAddress, ModeratorProof>,
+    pub moderator_penalties: BTreeMap<UserAddress, ModeratorProof>,
+    // key - punished user, value - (forgotten user, penalty) map
+    pub forgotten_penalties: BTreeMap<UserAddress, BTreeMap<UserAddress, SystemPenalty>>,
+}
+
+impl Projection {
+    pub fn apply(&mut self, event: &Event) {
+        match event.clone() {
+            Event::Vouch {
+                voucher,
+                vouchee,
+                timestamp,
+            } => {
+                self.vouchers
+                    .entry(vouchee.clone())
+                    .or_default()
+                    .insert(voucher.clone(), timestamp);
+                self.vouchees
+                    .entry(voucher)
+                    .or_default()
+                    .insert(vouchee, timestamp);
+            }
+            Event::RemoveVouch { voucher, vouchee } => {
+                remove_nested(&mut self.vouchers, &vouchee, &voucher);
+                remove_nested(&mut self.vouchees, &voucher, &vouchee);
+            }
+            Event::SetProof { user, proof } => {
+                self.proofs.insert(user, proof);
+            }
+            Event::RemoveProof { user } => {
+                self.proofs.remove(&user);
+            }
+            Event::RevokeProofs { moderator } => {
+                let (revoked, proofs) = std::mem::take(&mut self.proofs)
+                    .into_iter()
+                    .partition(|(_, proof)| proof.moderator == moderator);
+                self.proofs = proofs;
+                self.revoked_proofs.extend(revoked);
+            }
+            Event::SetModeratorPenalty { user, penalty } => {
+                self.moderator_penalties.insert(user, penalty);
+            }
+            Event::RemoveModeratorPenalty { user } => {
+                self.moderator_penalties.remove(&user);
+            }
+            Event::SetForgottenPenalty {
+                user,
+                forgotten,
+                penalty,
+            } => {
+                self.forgotten_penalties
+                    .entry(user)
+                    .or_default()
+                    .insert(forgotten, penalty);
+            }
+            Event::RemoveForgottenPenalty { user, forgotten } => {
+                remove_nested(&mut self.forgotten_penalties, &user, &forgotten);
+            }
+        }
+    }
+}
+
+// removes the inner key and drops the outer entry once it is empty
+fn remove_nested<V>(
+    map: &mut BTreeMap<UserAddress, BTreeMap<UserAddress, V>>,
+    outer: &UserAddress,
+    inner: &UserAddress,
+) {
+    if let Some(values) = map.get_mut(outer) {
+        values.remove(inner);
+        if values.is_empty() {
+            map.remove(outer);
+        }
+    }
+}
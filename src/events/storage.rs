@@ -0,0 +1,544 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::{
+    events::{Event, EventLog, InMemoryEventLog, RecordedEvent, Snapshot, projection::Projection},
+    identity::{
+        IdentityService, IdtAmount, ModeratorProof, SystemPenalty, UserAddress,
+        balances::storage::InMemoryBalanceStorage,
+        clock::{Clock, FixedClock},
+        error::Error,
+        proof::storage::ProofStorage,
+        punish::storage::PenaltyStorage,
+        vouch::storage::VouchStorage,
+    },
+};
+
+struct EventState {
+    projection: Projection,
+    last_seq: u64,
+}
+
+// vouch, proof and penalty storage backed by an event log
+pub struct EventSourcedStorage {
+    log: Arc<dyn EventLog>,
+    clock: Arc<dyn Clock>,
+    // projection is saved every `snapshot_interval` events, 0 disables snapshots
+    snapshot_interval: u64,
+    state: RwLock<EventState>,
+    // genesis is loaded from the config on every start, so it is not an event
+    genesis: RwLock<HashMap<UserAddress, IdtAmount>>,
+}
+
+// rebuilds the projection from the latest snapshot and the events after it
+async fn replay(log: &dyn EventLog, until: u64) -> Result<EventState, Error> {
+    let (mut projection, mut last_seq) = match log.snapshot(until).await? {
+        Some(snapshot) => (snapshot.projection, snapshot.seq),
+        None => (Projection::default(), 0),
+    };
+    for recorded in log.events(last_seq, until).await? {
+        projection.apply(&recorded.event);
+        last_seq = recorded.seq;
+    }
+    Ok(EventState {
+        projection,
+        last_seq,
+    })
+}
+
+impl EventSourcedStorage {
+    pub async fn open(
+        log: Arc<dyn EventLog>,
+        clock: Arc<dyn Clock>,
+        snapshot_interval: u64,
+    ) -> Result<Self, Error> {
+        let state = replay(&*log, u64::MAX).await?;
+        Ok(Self {
+            log,
+            clock,
+            snapshot_interval,
+            state: RwLock::new(state),
+            genesis: RwLock::new(HashMap::new()),
+        })
+    }
+
+    async fn record(&self, event: Event) -> Result<(), Error> {
+        let mut state = self.state.write().await;
+        let recorded = RecordedEvent {
+            seq: state.last_seq + 1,
+            recorded_at: self.clock.now(),
+            event,
+        };
+        self.log.append(&recorded).await?;
+        state.projection.apply(&recorded.event);
+        state.last_seq = recorded.seq;
+        if self.snapshot_interval > 0 && recorded.seq % self.snapshot_interval == 0 {
+            let snapshot = Snapshot {
+                seq: recorded.seq,
+                recorded_at: recorded.recorded_at,
+                projection: state.projection.clone(),
+            };
+            // events are already stored, a missing snapshot only slows down the next replay
+            if let Err(e) = self.log.save_snapshot(&snapshot).await {
+                log::warn!("Failed to save snapshot {}: {}", recorded.seq, e);
+            }
+        }
+        Ok(())
+    }
+
+    // state as it was at `timestamp`
+    pub async fn projection_at(&self, timestamp: u64) -> Result<Projection, Error> {
+        Ok(replay(&*self.log, timestamp).await?.projection)
+    }
+
+    // copy of the service that sees the vouches, proofs and penalties recorded until
+    // `timestamp` and computes balances at that time. External vouches are not event
+    // sourced, so the current ones are used.
+    pub async fn service_at(
+        &self,
+        service: &IdentityService,
+        timestamp: u64,
+    ) -> Result<IdentityService, Error> {
+        let clock = Arc::new(FixedClock(timestamp));
+        let past = Arc::new(EventSourcedStorage {
+            log: Arc::new(InMemoryEventLog::default()),
+            clock: clock.clone(),
+            snapshot_interval: 0,
+            state: RwLock::new(replay(&*self.log, timestamp).await?),
+            genesis: RwLock::new(self.genesis.read().await.clone()),
+        });
+        Ok(IdentityService {
+            vouches: past.clone(),
+            proofs: past.clone(),
+            penalties: past,
+            clock,
+            // materialized balances are recomputed from the past graph
+            balances: Arc::new(InMemoryBalanceStorage::default()),
+            ..service.clone()
+        })
+    }
+}
+
+#[async_trait]
+impl VouchStorage for EventSourcedStorage {
+    async fn vouch(&self, from: UserAddress, to: UserAddress, timestamp: u64) -> Result<(), Error> {
+        self.record(Event::Vouch {
+            voucher: from,
+            vouchee: to,
+            timestamp,
+        })
+        .await
+    }
+
+    async fn vouchers_with_time(
+        &self,
+        user: &UserAddress,
+    ) -> Result<HashMap<UserAddress, u64>, Error> {
+        let state = self.state.read().await;
+        Ok(state
+            .projection
+            .vouchers
+            .get(user)
+            .map(|v| v.clone().into_iter().collect())
+            .unwrap_or_default())
+    }
+
+    async fn vouchees_with_time(
+        &self,
+        user: &UserAddress,
+    ) -> Result<HashMap<UserAddress, u64>, Error> {
+        let state = self.state.read().await;
+        Ok(state
+            .projection
+            .vouchees
+            .get(user)
+            .map(|v| v.clone().into_iter().collect())
+            .unwrap_or_default())
+    }
+
+    async fn remove_vouch(&self, voucher: UserAddress, vouchee: UserAddress) -> Result<(), Error> {
+        self.record(Event::RemoveVouch { voucher, vouchee }).await
+    }
+
+    async fn all_vouches(&self) -> Result<Vec<(UserAddress, UserAddress, u64)>, Error> {
+        let state = self.state.read().await;
+        Ok(state
+            .projection
+            .vouchees
+            .iter()
+            .flat_map(|(voucher, vouchees)| {
+                vouchees
+                    .iter()
+                    .map(move |(vouchee, timestamp)| (voucher.clone(), vouchee.clone(), *timestamp))
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl ProofStorage for EventSourcedStorage {
+    async fn set_genesis(&self, users: HashMap<UserAddress, IdtAmount>) -> Result<(), Error> {
+        *self.genesis.write().await = users;
+        Ok(())
+    }
+
+    async fn genesis_balance(&self, user: &UserAddress) -> Result<Option<IdtAmount>, Error> {
+        Ok(self.genesis.read().await.get(user).copied())
+    }
+
+    async fn genesis(&self) -> Result<HashMap<UserAddress, IdtAmount>, Error> {
+        Ok(self.genesis.read().await.clone())
+    }
+
+    async fn set_proof(&self, user: UserAddress, proof: ModeratorProof) -> Result<(), Error> {
+        self.record(Event::SetProof { user, proof }).await
+    }
+
+    async fn proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        Ok(self.state.read().await.projection.proofs.get(user).cloned())
+    }
+
+    async fn remove_proof(&self, user: &UserAddress) -> Result<(), Error> {
+        self.record(Event::RemoveProof { user: user.clone() }).await
+    }
+
+    async fn proven_users(&self) -> Result<HashSet<UserAddress>, Error> {
+        let mut users: HashSet<UserAddress> = self
+            .state
+            .read()
+            .await
+            .projection
+            .proofs
+            .keys()
+            .cloned()
+            .collect();
+        users.extend(self.genesis.read().await.keys().cloned());
+        Ok(users)
+    }
+
+    async fn revoke_proofs(&self, moderator: &UserAddress) -> Result<Vec<UserAddress>, Error> {
+        let users = self
+            .state
+            .read()
+            .await
+            .projection
+            .proofs
+            .iter()
+            .filter(|(_, proof)| &proof.moderator == moderator)
+            .map(|(user, _)| user.clone())
+            .collect();
+        self.record(Event::RevokeProofs {
+            moderator: moderator.clone(),
+        })
+        .await?;
+        Ok(users)
+    }
+
+    async fn revoked_proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        Ok(self
+            .state
+            .read()
+            .await
+            .projection
+            .revoked_proofs
+            .get(user)
+            .cloned())
+    }
+}
+
+#[async_trait]
+impl PenaltyStorage for EventSourcedStorage {
+    async fn set_moderator_penalty(
+        &self,
+        user: UserAddress,
+        proof: ModeratorProof,
+    ) -> Result<(), Error> {
+        self.record(Event::SetModeratorPenalty {
+            user,
+            penalty: proof,
+        })
+        .await
+    }
+
+    async fn set_forgotten_penalty(
+        &self,
+        user: UserAddress,
+        vouchee: UserAddress,
+        penalty: SystemPenalty,
+    ) -> Result<(), Error> {
+        self.record(Event::SetForgottenPenalty {
+            user,
+            forgotten: vouchee,
+            penalty,
+        })
+        .await
+    }
+
+    async fn remove_forgotten(
+        &self,
+        user: UserAddress,
+        forgotten: &UserAddress,
+    ) -> Result<(), Error> {
+        self.record(Event::RemoveForgottenPenalty {
+            user,
+            forgotten: forgotten.clone(),
+        })
+        .await
+    }
+
+    async fn moderator_penalty(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        Ok(self
+            .state
+            .read()
+            .await
+            .projection
+            .moderator_penalties
+            .get(user)
+            .cloned())
+    }
+
+    async fn remove_moderator_penalty(&self, user: &UserAddress) -> Result<(), Error> {
+        self.record(Event::RemoveModeratorPenalty { user: user.clone() })
+            .await
+    }
+
+    async fn forgotten_penalty(
+        &self,
+        user: &UserAddress,
+        forgotten: &UserAddress,
+    ) -> Result<Option<SystemPenalty>, Error> {
+        Ok(self
+            .state
+            .read()
+            .await
+            .projection
+            .forgotten_penalties
+            .get(user)
+            .and_then(|v| v.get(forgotten).cloned()))
+    }
+
+    async fn forgotten_users(&self, user: &UserAddress) -> Result<HashSet<UserAddress>, Error> {
+        Ok(self
+            .state
+            .read()
+            .await
+            .projection
+            .forgotten_penalties
+            .get(user)
+            .map(|v| v.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn all_moderator_penalties(&self) -> Result<Vec<(UserAddress, ModeratorProof)>, Error> {
+        Ok(self
+            .state
+            .read()
+            .await
+            .projection
+            .moderator_penalties
+            .iter()
+            .map(|(user, penalty)| (user.clone(), penalty.clone()))
+            .collect())
+    }
+
+    async fn all_forgotten_penalties(
+        &self,
+    ) -> Result<Vec<(UserAddress, UserAddress, SystemPenalty)>, Error> {
+        Ok(self
+            .state
+            .read()
+            .await
+            .projection
+            .forgotten_penalties
+            .iter()
+            .flat_map(|(user, penalties)| {
+                penalties.iter().map(move |(forgotten, penalty)| {
+                    (user.clone(), forgotten.clone(), penalty.clone())
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{
+        clock::MockClock,
+        forget::forget,
+        idt::balance,
+        proof::prove,
+        tests::{MODERATOR, PROOF_ID, START_TIMESTAMP, USER_A},
+        vouch::vouch,
+    };
+
+    async fn event_service(
+        log: Arc<dyn EventLog>,
+        clock: Arc<MockClock>,
+        snapshot_interval: u64,
+    ) -> (IdentityService, Arc<EventSourcedStorage>) {
+        let storage = Arc::new(
+            EventSourcedStorage::open(log, clock.clone(), snapshot_interval)
+                .await
+                .unwrap(),
+        );
+        let service = IdentityService {
+            vouches: storage.clone(),
+            proofs: storage.clone(),
+            penalties: storage.clone(),
+            clock,
+            ..Default::default()
+        };
+        (service, storage)
+    }
+
+    #[async_std::test]
+    async fn test_replay() {
+        let log: Arc<dyn EventLog> = Arc::new(InMemoryEventLog::default());
+        let clock = Arc::new(MockClock::new(START_TIMESTAMP));
+        let user_b = "userB".to_string();
+        let (service, _) = event_service(log.clone(), clock.clone(), 2).await;
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+        forget(&service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+        assert!(service.vouches.all_vouches().await.unwrap().is_empty());
+        assert!(log.snapshot(u64::MAX).await.unwrap().is_some());
+
+        // restarted storage sees the same state
+        let (restarted, _) = event_service(log.clone(), clock.clone(), 2).await;
+        assert_eq!(
+            restarted
+                .proof(&USER_A.to_string())
+                .await
+                .unwrap()
+                .unwrap()
+                .amount,
+            IdtAmount::new(100)
+        );
+        assert!(
+            restarted
+                .forgotten_users(&USER_A.to_string())
+                .await
+                .unwrap()
+                .contains(&user_b)
+        );
+        assert_eq!(
+            balance(&restarted, &USER_A.to_string()).await.unwrap(),
+            balance(&service, &USER_A.to_string()).await.unwrap()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_time_travel() {
+        let log: Arc<dyn EventLog> = Arc::new(InMemoryEventLog::default());
+        let clock = Arc::new(MockClock::new(START_TIMESTAMP));
+        let user_b = "userB".to_string();
+        let (service, storage) = event_service(log, clock.clone(), 0).await;
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        clock.advance(86400);
+        vouch(&service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+        let vouched_at = clock.now();
+        clock.advance(86400);
+        forget(&service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+
+        let before = storage
+            .service_at(&service, START_TIMESTAMP - 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            balance(&before, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+
+        let past = storage.service_at(&service, vouched_at).await.unwrap();
+        // one day of decay
+        assert_eq!(
+            balance(&past, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(99)
+        );
+        assert_eq!(
+            balance(&past, &user_b).await.unwrap(),
+            IdtAmount::from_milli(9_900)
+        );
+
+        assert_eq!(balance(&service, &user_b).await.unwrap(), IdtAmount::new(0));
+        assert!(
+            storage
+                .projection_at(vouched_at)
+                .await
+                .unwrap()
+                .vouchees
+                .contains_key(USER_A)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_revoke_proofs() {
+        let log: Arc<dyn EventLog> = Arc::new(InMemoryEventLog::default());
+        let clock = Arc::new(MockClock::new(START_TIMESTAMP));
+        let (service, _) = event_service(log.clone(), clock.clone(), 0).await;
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            service
+                .revoke_moderator_proofs(&MODERATOR.to_string())
+                .await
+                .unwrap(),
+            vec![USER_A.to_string()]
+        );
+
+        let (restarted, _) = event_service(log, clock, 0).await;
+        assert!(
+            restarted
+                .proof(&USER_A.to_string())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(
+            restarted
+                .revoked_proof(&USER_A.to_string())
+                .await
+                .unwrap()
+                .unwrap()
+                .amount,
+            IdtAmount::new(100)
+        );
+    }
+}
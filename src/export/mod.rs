@@ -0,0 +1,136 @@
+// Datasets for external consumers.
+//
+// Analytics export replaces user addresses with salted hashes, so researchers can study the
+// shape of the vouch graph without learning who vouched for whom. The salt is derived from
+// the server private key and never leaves the server, pseudonyms stay stable as long as
+// the key does not change.
+
+use std::collections::BTreeSet;
+
+use async_std::channel::{Receiver, Sender, bounded};
+use ethers_core::utils::keccak256;
+use futures::{AsyncBufRead, StreamExt, TryStreamExt};
+use serde::Serialize;
+
+use crate::identity::{IdentityService, UserAddress, error::Error, idt::balance};
+
+pub mod sync;
+
+// records buffered between the producer task and the response body
+const STREAM_BUFFER: usize = 64;
+const DAY: u64 = 60 * 60 * 24;
+
+#[derive(Clone)]
+pub struct Pseudonymizer {
+    salt: [u8; 32],
+}
+
+impl Pseudonymizer {
+    pub fn new(salt: [u8; 32]) -> Self {
+        Self { salt }
+    }
+
+    pub fn from_server_key(private_key: &str) -> Self {
+        Self::new(keccak256(format!("analytics/{private_key}")))
+    }
+
+    pub fn pseudonym(&self, user: &UserAddress) -> String {
+        let mut data = self.salt.to_vec();
+        data.extend_from_slice(user.as_bytes());
+        format!("0x{}", hex::encode(keccak256(data)))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnalyticsRecord {
+    // vouch time is truncated to days to make timing correlation harder
+    Edge { from: String, to: String, day: u64 },
+    Balance { user: String, idt: String },
+    Error { error: String },
+}
+
+async fn produce_analytics(
+    service: &IdentityService,
+    pseudonymizer: &Pseudonymizer,
+    sender: &Sender<AnalyticsRecord>,
+) -> Result<(), Error> {
+    let mut users = BTreeSet::new();
+    let mut edges = service.vouches.all_vouches().await?;
+    edges.sort();
+    for (from, to, timestamp) in edges {
+        let record = AnalyticsRecord::Edge {
+            from: pseudonymizer.pseudonym(&from),
+            to: pseudonymizer.pseudonym(&to),
+            day: timestamp / DAY,
+        };
+        users.insert(from);
+        users.insert(to);
+        // receiver is gone, nobody reads the export anymore
+        if sender.send(record).await.is_err() {
+            return Ok(());
+        }
+    }
+    users.extend(service.proofs.proven_users().await?);
+    for user in users {
+        let record = AnalyticsRecord::Balance {
+            user: pseudonymizer.pseudonym(&user),
+            idt: balance(service, &user).await?.to_string(),
+        };
+        if sender.send(record).await.is_err() {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+// NDJSON stream of vouch edges followed by balances, records are produced in the
+// background while the response is being sent
+pub fn analytics_stream(
+    service: IdentityService,
+    pseudonymizer: Pseudonymizer,
+) -> impl AsyncBufRead + Send + Sync + Unpin + 'static {
+    let (sender, receiver) = bounded(STREAM_BUFFER);
+    async_std::task::spawn(async move {
+        if let Err(e) = produce_analytics(&service, &pseudonymizer, &sender).await {
+            log::error!("Analytics export failed: {:?}", e);
+            let _ = sender
+                .send(AnalyticsRecord::Error {
+                    error: "export failed".to_string(),
+                })
+                .await;
+        }
+    });
+    ndjson(receiver)
+}
+
+// one JSON line per received record
+fn ndjson<T: Serialize + Send + 'static>(
+    receiver: Receiver<T>,
+) -> impl AsyncBufRead + Send + Sync + Unpin + 'static {
+    receiver
+        .map(|record: T| {
+            let mut line = serde_json::to_vec(&record).expect("records are serializable");
+            line.push(b'\n');
+            Ok::<_, std::io::Error>(line)
+        })
+        .into_async_read()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pseudonym() {
+        let p = Pseudonymizer::new([1; 32]);
+        let user = "0xuser".to_string();
+        assert_eq!(p.pseudonym(&user), p.pseudonym(&user));
+        assert_ne!(p.pseudonym(&user), p.pseudonym(&"0xother".to_string()));
+        assert_ne!(
+            p.pseudonym(&user),
+            Pseudonymizer::new([2; 32]).pseudonym(&user)
+        );
+        assert!(!p.pseudonym(&user).contains("user"));
+    }
+}
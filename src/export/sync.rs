@@ -0,0 +1,318 @@
+// Raw vouch and penalty exports for data pipelines.
+//
+// Rows are ordered by timestamp and then by their addresses, every row carries an opaque
+// cursor. A client that lost the connection or polls for new data passes the cursor of the
+// last processed row as `after` and receives only the rows that follow it. Removed records
+// are not reported, a full export is needed to notice them.
+
+use async_std::channel::{Sender, bounded};
+use futures::AsyncBufRead;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cursor::CursorKey,
+    export::{STREAM_BUFFER, ndjson},
+    identity::{IdentityService, IdtAmount, ProofId, UserAddress, error::Error},
+};
+
+// scopes of the signed cursors of the exports, see `cursor` module
+pub const VOUCH_CURSOR_SCOPE: &str = "export/vouches";
+pub const PENALTY_CURSOR_SCOPE: &str = "export/penalties";
+
+// position of a row in the export
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cursor {
+    timestamp: u64,
+    key: Vec<String>,
+}
+
+impl Cursor {
+    pub fn new(timestamp: u64, key: &[&str]) -> Self {
+        Self {
+            timestamp,
+            key: key.iter().map(|k| k.to_string()).collect(),
+        }
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn key(&self) -> &[String] {
+        &self.key
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VouchRecord {
+    Vouch {
+        voucher: UserAddress,
+        vouchee: UserAddress,
+        timestamp: u64,
+        cursor: String,
+    },
+    Error {
+        error: String,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PenaltyRecord {
+    ModeratorPenalty {
+        user: UserAddress,
+        moderator: UserAddress,
+        amount: IdtAmount,
+        proof_id: ProofId,
+        timestamp: u64,
+        cursor: String,
+    },
+    ForgottenPenalty {
+        user: UserAddress,
+        forgotten: UserAddress,
+        amount: IdtAmount,
+        timestamp: u64,
+        cursor: String,
+    },
+    Error {
+        error: String,
+    },
+}
+
+impl VouchRecord {
+    fn error() -> Self {
+        Self::Error {
+            error: "export failed".to_string(),
+        }
+    }
+
+    pub fn cursor(&self) -> Option<&str> {
+        match self {
+            Self::Vouch { cursor, .. } => Some(cursor),
+            Self::Error { .. } => None,
+        }
+    }
+}
+
+impl PenaltyRecord {
+    fn error() -> Self {
+        Self::Error {
+            error: "export failed".to_string(),
+        }
+    }
+
+    pub fn cursor(&self) -> Option<&str> {
+        match self {
+            Self::ModeratorPenalty { cursor, .. } | Self::ForgottenPenalty { cursor, .. } => {
+                Some(cursor)
+            }
+            Self::Error { .. } => None,
+        }
+    }
+}
+
+// vouch rows following `after`, ordered by their cursors
+pub async fn vouch_records(
+    service: &IdentityService,
+    cursors: &CursorKey,
+    after: Option<&Cursor>,
+) -> Result<Vec<VouchRecord>, Error> {
+    let mut rows: Vec<_> = service
+        .vouches
+        .all_vouches()
+        .await?
+        .into_iter()
+        .map(|(voucher, vouchee, timestamp)| {
+            let cursor = Cursor::new(timestamp, &[&voucher, &vouchee]);
+            (cursor, voucher, vouchee, timestamp)
+        })
+        .filter(|(cursor, ..)| after.is_none_or(|after| cursor > after))
+        .collect();
+    rows.sort();
+    Ok(rows
+        .into_iter()
+        .map(|(cursor, voucher, vouchee, timestamp)| VouchRecord::Vouch {
+            voucher,
+            vouchee,
+            timestamp,
+            cursor: cursors.encode(VOUCH_CURSOR_SCOPE, &cursor),
+        })
+        .collect())
+}
+
+// moderator and forgotten penalty rows following `after`, ordered by their cursors
+pub async fn penalty_records(
+    service: &IdentityService,
+    cursors: &CursorKey,
+    after: Option<&Cursor>,
+) -> Result<Vec<PenaltyRecord>, Error> {
+    let mut rows = vec![];
+    for (user, penalty) in service.penalties.all_moderator_penalties().await? {
+        let cursor = Cursor::new(penalty.timestamp, &["moderator", &user]);
+        rows.push((
+            cursor.clone(),
+            PenaltyRecord::ModeratorPenalty {
+                user,
+                moderator: penalty.moderator,
+                amount: penalty.amount,
+                proof_id: penalty.proof_id,
+                timestamp: penalty.timestamp,
+                cursor: cursors.encode(PENALTY_CURSOR_SCOPE, &cursor),
+            },
+        ));
+    }
+    for (user, forgotten, penalty) in service.penalties.all_forgotten_penalties().await? {
+        let cursor = Cursor::new(penalty.timestamp, &["forgotten", &user, &forgotten]);
+        rows.push((
+            cursor.clone(),
+            PenaltyRecord::ForgottenPenalty {
+                user,
+                forgotten,
+                amount: penalty.amount,
+                timestamp: penalty.timestamp,
+                cursor: cursors.encode(PENALTY_CURSOR_SCOPE, &cursor),
+            },
+        ));
+    }
+    rows.retain(|(cursor, _)| after.is_none_or(|after| cursor > after));
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(rows.into_iter().map(|(_, record)| record).collect())
+}
+
+async fn send_all<T>(sender: &Sender<T>, records: Vec<T>) {
+    for record in records {
+        // receiver is gone, nobody reads the export anymore
+        if sender.send(record).await.is_err() {
+            return;
+        }
+    }
+}
+
+pub fn vouch_stream(
+    service: IdentityService,
+    cursors: CursorKey,
+    after: Option<Cursor>,
+) -> impl AsyncBufRead + Send + Sync + Unpin + 'static {
+    let (sender, receiver) = bounded(STREAM_BUFFER);
+    async_std::task::spawn(async move {
+        match vouch_records(&service, &cursors, after.as_ref()).await {
+            Ok(records) => send_all(&sender, records).await,
+            Err(e) => {
+                log::error!("Vouch export failed: {:?}", e);
+                let _ = sender.send(VouchRecord::error()).await;
+            }
+        }
+    });
+    ndjson(receiver)
+}
+
+pub fn penalty_stream(
+    service: IdentityService,
+    cursors: CursorKey,
+    after: Option<Cursor>,
+) -> impl AsyncBufRead + Send + Sync + Unpin + 'static {
+    let (sender, receiver) = bounded(STREAM_BUFFER);
+    async_std::task::spawn(async move {
+        match penalty_records(&service, &cursors, after.as_ref()).await {
+            Ok(records) => send_all(&sender, records).await,
+            Err(e) => {
+                log::error!("Penalty export failed: {:?}", e);
+                let _ = sender.send(PenaltyRecord::error()).await;
+            }
+        }
+    });
+    ndjson(receiver)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{
+        forget::forget,
+        proof::prove,
+        punish::punish,
+        tests::{MODERATOR, PROOF_ID, USER_A},
+        vouch::vouch,
+    };
+
+    #[test]
+    fn test_cursor() {
+        let cursor = Cursor::new(5, &["a", "b"]);
+        assert!(cursor < Cursor::new(5, &["a", "c"]));
+        assert!(cursor < Cursor::new(6, &["a"]));
+    }
+
+    #[async_std::test]
+    async fn test_vouch_records() {
+        let service = IdentityService::default();
+        let (user_b, user_c) = ("userB".to_string(), "userC".to_string());
+        vouch(&service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+        vouch(&service, USER_A.to_string(), user_c.clone())
+            .await
+            .unwrap();
+        let cursors = CursorKey::new("secret");
+        let records = vouch_records(&service, &cursors, None).await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(matches!(&records[0], VouchRecord::Vouch { vouchee, .. } if vouchee == &user_b));
+        let after = cursors
+            .decode(VOUCH_CURSOR_SCOPE, records[0].cursor().unwrap())
+            .unwrap();
+        let rest = vouch_records(&service, &cursors, Some(&after))
+            .await
+            .unwrap();
+        assert_eq!(rest, records[1..]);
+    }
+
+    #[async_std::test]
+    async fn test_penalty_records() {
+        let service = IdentityService::default();
+        let user_b = "userB".to_string();
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+        punish(
+            &service,
+            user_b.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(10),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        forget(&service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+        let cursors = CursorKey::new("secret");
+        let records = penalty_records(&service, &cursors, None).await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(
+            records.iter().any(
+                |r| matches!(r, PenaltyRecord::ModeratorPenalty { user, .. } if user == &user_b)
+            )
+        );
+        assert!(records.iter().any(
+            |r| matches!(r, PenaltyRecord::ForgottenPenalty { forgotten, .. } if forgotten == &user_b)
+        ));
+        let after = cursors
+            .decode(PENALTY_CURSOR_SCOPE, records[1].cursor().unwrap())
+            .unwrap();
+        assert!(
+            penalty_records(&service, &cursors, Some(&after))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+
+pub const DEFAULT_CACHE_TTL: u64 = 60;
+
+// keeps values for `ttl` seconds, timestamps are supplied by the caller
+pub struct TtlCache<V> {
+    ttl: u64,
+    entries: RwLock<HashMap<String, (u64, V)>>,
+}
+
+impl<V: Clone> TtlCache<V> {
+    pub fn new(ttl: u64) -> Self {
+        Self {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get(&self, key: &str, now: u64) -> Option<V> {
+        let entries = self.entries.read().await;
+        let (stored_at, value) = entries.get(key)?;
+        if now.saturating_sub(*stored_at) >= self.ttl {
+            return None;
+        }
+        Some(value.clone())
+    }
+
+    pub async fn insert(&self, key: String, value: V, now: u64) {
+        let mut entries = self.entries.write().await;
+        // drop expired entries so the cache does not grow unbounded
+        entries.retain(|_, (stored_at, _)| now.saturating_sub(*stored_at) < self.ttl);
+        entries.insert(key, (now, value));
+    }
+
+    pub async fn remove(&self, key: &str) -> Option<V> {
+        self.entries
+            .write()
+            .await
+            .remove(key)
+            .map(|(_, value)| value)
+    }
+}
+
+impl<V: Clone> Default for TtlCache<V> {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_TTL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_expiry() {
+        let cache = TtlCache::new(10);
+        assert!(cache.get("key", 100).await.is_none());
+        cache.insert("key".to_string(), 1, 100).await;
+        assert_eq!(cache.get("key", 109).await, Some(1));
+        assert!(cache.get("key", 110).await.is_none());
+        assert_eq!(cache.remove("key").await, Some(1));
+        assert!(cache.remove("key").await.is_none());
+    }
+}
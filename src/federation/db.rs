@@ -0,0 +1,147 @@
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    federation::{
+        error::Error,
+        storage::{HomeClaim, HomeStorage},
+    },
+    identity::UserAddress,
+    pools::{self, MeteredPool},
+};
+
+pub struct DatabaseHomeStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabaseHomeStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("homes", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS homes (user TEXT PRIMARY KEY, server TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "homes", "user").await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS home_claims (user TEXT PRIMARY KEY, data TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "home_claims", "user").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl HomeStorage for DatabaseHomeStorage {
+    async fn set_home(&self, user: UserAddress, server: UserAddress) -> Result<(), Error> {
+        sqlx::query("REPLACE INTO homes (user, server) VALUES (?, ?)")
+            .bind(self.cipher.encode(&user))
+            .bind(server)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_home(&self, user: &UserAddress) -> Result<(), Error> {
+        sqlx::query("DELETE FROM homes WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn home(&self, user: &UserAddress) -> Result<Option<UserAddress>, Error> {
+        let row = sqlx::query("SELECT server FROM homes WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|r| r.get::<String, _>(0)))
+    }
+
+    async fn set_claim(&self, user: UserAddress, claim: HomeClaim) -> Result<(), Error> {
+        sqlx::query("REPLACE INTO home_claims (user, data) VALUES (?, ?)")
+            .bind(self.cipher.encode(&user))
+            .bind(serde_json::to_string(&claim)?)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_claim(&self, user: &UserAddress) -> Result<(), Error> {
+        sqlx::query("DELETE FROM home_claims WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn claim(&self, user: &UserAddress) -> Result<Option<HomeClaim>, Error> {
+        let row = sqlx::query("SELECT data FROM home_claims WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        Ok(Some(serde_json::from_str(&row.get::<String, _>(0))?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseHomeStorage::new("sqlite::memory:").await.unwrap();
+        let user = "user".to_string();
+        assert!(storage.home(&user).await.unwrap().is_none());
+        storage
+            .set_home(user.clone(), "server1".to_string())
+            .await
+            .unwrap();
+        storage
+            .set_home(user.clone(), "server2".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.home(&user).await.unwrap(),
+            Some("server2".to_string())
+        );
+        storage.remove_home(&user).await.unwrap();
+        assert!(storage.home(&user).await.unwrap().is_none());
+    }
+
+    #[async_std::test]
+    async fn test_claims() {
+        let storage = DatabaseHomeStorage::new("sqlite::memory:").await.unwrap();
+        let user = "user".to_string();
+        assert!(storage.claim(&user).await.unwrap().is_none());
+        let claim = HomeClaim {
+            signature: "0x01".to_string(),
+            nonce: 2,
+            expires_at: 3,
+            version: Some(1),
+            claimed_at: 4,
+        };
+        storage
+            .set_claim(user.clone(), claim.clone())
+            .await
+            .unwrap();
+        assert_eq!(storage.claim(&user).await.unwrap(), Some(claim));
+        storage.remove_claim(&user).await.unwrap();
+        assert!(storage.claim(&user).await.unwrap().is_none());
+    }
+}
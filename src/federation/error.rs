@@ -0,0 +1,18 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Request to {0} failed: {1}")]
+    RequestError(String, String),
+    #[error("Unexpected response from {0}: {1}")]
+    ResponseError(String, String),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[cfg(feature = "storage-sql")]
+    #[error("Encryption error: {0}")]
+    EncryptionError(#[from] crate::encryption::error::Error),
+    #[cfg(feature = "storage-sql")]
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[cfg(feature = "sled")]
+    #[error("Storage error: {0}")]
+    StorageError(#[from] crate::kv::error::Error),
+}
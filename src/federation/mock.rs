@@ -0,0 +1,201 @@
+// Fake external identity server for federation tests. It listens on a local port, answers
+// `/idt/:user` and `/vouchers/:user` with programmed users, signs handshakes with its own key
+// and records every request, e.g. the ones forwarded by the proxy mode.
+
+use std::{collections::HashMap, io, sync::Arc};
+
+use async_std::{sync::RwLock, task::JoinHandle};
+use serde_json::json;
+use tide::{Request, Response, http::mime, listener::Listener};
+
+use crate::{
+    federation::{ProxyRequest, RemoteUser},
+    identity::{UserAddress, next_timestamp},
+    servers::ServerIdentity,
+    verify::{
+        handshake::handshake_sign,
+        nonce::{InMemoryNonceManager, OutboundNonces},
+    },
+};
+
+// seconds the handshakes of the mock server stay valid
+const HANDSHAKE_VALIDITY: u64 = 60;
+
+#[derive(Default)]
+struct MockState {
+    identity: ServerIdentity,
+    nonce_manager: InMemoryNonceManager,
+    users: RwLock<HashMap<UserAddress, RemoteUser>>,
+    requests: RwLock<Vec<ProxyRequest>>,
+    // status every request is answered with instead of the programmed responses
+    failure: RwLock<Option<u16>>,
+}
+
+pub struct MockServer {
+    // base url to register the server with, e.g. `http://127.0.0.1:12345`
+    pub url: String,
+    state: Arc<MockState>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start() -> io::Result<Self> {
+        let state = Arc::new(MockState::default());
+        let mut app = tide::with_state(state.clone());
+        app.at("/").all(handle);
+        app.at("*").all(handle);
+        let mut listener = app.bind("127.0.0.1:0").await?;
+        let url = listener
+            .info()
+            .first()
+            .map(|info| info.connection().to_string())
+            .ok_or_else(|| io::Error::other("mock server is not listening"))?;
+        let task = async_std::task::spawn(async move {
+            if let Err(e) = listener.accept().await {
+                log::error!("Mock server failed: {:?}", e);
+            }
+        });
+        Ok(Self { url, state, task })
+    }
+
+    // address the mock server signs handshakes with
+    pub fn address(&self) -> UserAddress {
+        self.state.identity.address.clone()
+    }
+
+    pub async fn set_user(&self, user: UserAddress, remote: RemoteUser) {
+        self.state.users.write().await.insert(user, remote);
+    }
+
+    // unknown users are answered with 404
+    pub async fn remove_user(&self, user: &UserAddress) {
+        self.state.users.write().await.remove(user);
+    }
+
+    // answers every request with the status, `None` restores the programmed responses
+    pub async fn set_failure(&self, status: Option<u16>) {
+        *self.state.failure.write().await = status;
+    }
+
+    // requests received so far, in order
+    pub async fn requests(&self) -> Vec<ProxyRequest> {
+        self.state.requests.read().await.clone()
+    }
+
+    pub async fn stop(self) {
+        self.task.cancel().await;
+    }
+}
+
+fn json_response(status: u16, body: serde_json::Value) -> Response {
+    Response::builder(status)
+        .body(body)
+        .content_type(mime::JSON)
+        .build()
+}
+
+async fn handle(mut req: Request<Arc<MockState>>) -> tide::Result {
+    let path = match req.url().query() {
+        Some(query) => format!("{}?{}", req.url().path(), query),
+        None => req.url().path().to_string(),
+    };
+    let headers = req
+        .iter()
+        .map(|(name, values)| (name.to_string(), values.last().as_str().to_string()))
+        .collect();
+    let request = ProxyRequest {
+        method: req.method().to_string(),
+        path: path.clone(),
+        headers,
+        body: req.body_string().await?,
+    };
+    let state = req.state().clone();
+    state.requests.write().await.push(request);
+    if let Some(status) = *state.failure.read().await {
+        return Ok(json_response(status, json!({"error": "mock failure"})));
+    }
+
+    let segments: Vec<&str> = req
+        .url()
+        .path()
+        .trim_start_matches('/')
+        .split('/')
+        .collect();
+    let response = match segments.as_slice() {
+        ["idt", user] => match state.users.read().await.get(*user) {
+            Some(remote) => {
+                json_response(200, json!({"user": user, "idt": remote.idt.to_string()}))
+            }
+            None => json_response(404, json!({"error": "unknown user"})),
+        },
+        ["vouchers", user] => match state.users.read().await.get(*user) {
+            Some(remote) => json_response(200, json!({"user": user, "vouchers": remote.vouchers})),
+            None => json_response(404, json!({"error": "unknown user"})),
+        },
+        ["handshake", domain] => {
+            let handshake = handshake_sign(
+                &state.identity.private_key,
+                &domain.to_string(),
+                next_timestamp().saturating_add(HANDSHAKE_VALIDITY),
+                &OutboundNonces(&state.nonce_manager),
+            )
+            .await?;
+            json_response(200, serde_json::to_value(handshake)?)
+        }
+        // answers with the requested path like `InMemoryFederationClient`
+        _ => json_response(200, json!({"path": path})),
+    };
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::federation::{FederationClient, HttpFederationClient};
+    use crate::identity::IdtAmount;
+
+    #[async_std::test]
+    async fn test_http_client() {
+        let server = MockServer::start().await.unwrap();
+        let client = HttpFederationClient;
+        let user = "userA".to_string();
+        let remote = RemoteUser {
+            idt: IdtAmount::new(42),
+            vouchers: vec!["userB".to_string()],
+        };
+        server.set_user(user.clone(), remote.clone()).await;
+        assert_eq!(client.user(&server.url, &user).await.unwrap(), remote);
+        assert!(client.balance(&server.url, &"other".into()).await.is_err());
+
+        server.set_failure(Some(500)).await;
+        assert!(client.balance(&server.url, &user).await.is_err());
+        server.set_failure(None).await;
+
+        let response = client
+            .forward(
+                &server.url,
+                ProxyRequest {
+                    method: "POST".into(),
+                    path: "/vouch/userA?x=1".into(),
+                    headers: vec![("X-Test".into(), "1".into())],
+                    body: "{}".into(),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        let requests = server.requests().await;
+        assert_eq!(requests.len(), 5);
+        let forwarded = requests.last().unwrap();
+        assert_eq!(forwarded.method, "POST");
+        assert_eq!(forwarded.path, "/vouch/userA?x=1");
+        assert_eq!(forwarded.body, "{}");
+        assert!(
+            forwarded
+                .headers
+                .iter()
+                .any(|(name, value)| name.eq_ignore_ascii_case("x-test") && value == "1")
+        );
+        server.stop().await;
+    }
+}
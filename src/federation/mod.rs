@@ -0,0 +1,363 @@
+use std::{collections::HashMap, str::FromStr};
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::FederationSection,
+    federation::error::Error,
+    identity::{IdtAmount, UserAddress},
+    numbers::Rational,
+    servers::storage::ServerInfo,
+};
+
+pub mod cache;
+#[cfg(feature = "storage-sql")]
+pub mod db;
+pub mod error;
+#[cfg(any(all(test, feature = "http-api"), feature = "test-utils"))]
+pub mod mock;
+pub mod storage;
+
+// endpoint receiving balance updates of users vouched for by users of the receiving server
+pub const BALANCE_UPDATE_PATH: &str = "/federation/balance_update";
+
+// view of a user as reported by an external server, amounts are not scaled
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RemoteUser {
+    pub idt: IdtAmount,
+    pub vouchers: Vec<UserAddress>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProxyRequest {
+    pub method: String,
+    // path with query, e.g. `/idt/user?at=1`
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProxyResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+// queries other identity servers by their base url
+#[async_trait]
+pub trait FederationClient: Send + Sync {
+    async fn balance(&self, url: &str, user: &UserAddress) -> Result<IdtAmount, Error>;
+    async fn vouchers(&self, url: &str, user: &UserAddress) -> Result<Vec<UserAddress>, Error>;
+    async fn forward(&self, url: &str, request: ProxyRequest) -> Result<ProxyResponse, Error>;
+
+    async fn user(&self, url: &str, user: &UserAddress) -> Result<RemoteUser, Error> {
+        Ok(RemoteUser {
+            idt: self.balance(url, user).await?,
+            vouchers: self.vouchers(url, user).await?,
+        })
+    }
+
+    // posts a signed balance update as JSON to `BALANCE_UPDATE_PATH` of the server
+    async fn balance_update(&self, url: &str, body: String) -> Result<(), Error> {
+        let request = ProxyRequest {
+            method: "POST".to_string(),
+            path: BALANCE_UPDATE_PATH.to_string(),
+            headers: vec![],
+            body,
+        };
+        let response = self.forward(url, request).await?;
+        if !(200..300).contains(&response.status) {
+            return Err(Error::ResponseError(
+                url.to_string(),
+                format!("status {}", response.status),
+            ));
+        }
+        Ok(())
+    }
+}
+
+// answer of a single registered server about a user
+pub struct RemoteSource {
+    pub server: UserAddress,
+    pub info: ServerInfo,
+    // decay of the server trust since its last attestation
+    pub weight: Rational,
+    // share of the scale reached by the server schedule, see `ScaleSchedule`
+    pub schedule_weight: Rational,
+    pub result: Result<RemoteUser, Error>,
+}
+
+impl RemoteSource {
+    pub fn scaled_balance(&self) -> Option<IdtAmount> {
+        self.result.as_ref().ok().map(|remote| {
+            let scaled = self.schedule_weight.mul(self.info.scale.mul(remote.idt));
+            self.weight.mul(scaled)
+        })
+    }
+}
+
+// asks every server about the user, failures are kept per source
+pub async fn query_servers(
+    client: &dyn FederationClient,
+    servers: HashMap<UserAddress, ServerInfo>,
+    user: &UserAddress,
+    now: u64,
+    config: &FederationSection,
+) -> Vec<RemoteSource> {
+    let mut servers: Vec<_> = servers.into_iter().collect();
+    servers.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut sources = vec![];
+    for (server, info) in servers {
+        let result = client.user(&info.url, user).await;
+        if let Err(e) = &result {
+            log::warn!("Failed to resolve {} on {}: {}", user, info.url, e);
+        }
+        sources.push(RemoteSource {
+            server,
+            weight: info.attestation_weight(now, config),
+            schedule_weight: info.schedule_weight(now, config),
+            info,
+            result,
+        });
+    }
+    sources
+}
+
+// servers are not additive, so the best scaled balance is used
+pub fn best_scaled_balance(sources: &[RemoteSource]) -> IdtAmount {
+    sources
+        .iter()
+        .filter_map(RemoteSource::scaled_balance)
+        .max()
+        .unwrap_or_default()
+}
+
+#[derive(Default)]
+pub struct HttpFederationClient;
+
+#[derive(Deserialize)]
+struct IdtResponse {
+    idt: String,
+}
+
+#[derive(Deserialize)]
+struct VouchersResponse {
+    vouchers: Vec<UserAddress>,
+}
+
+impl HttpFederationClient {
+    async fn get<T: for<'de> Deserialize<'de>>(&self, url: &str, path: String) -> Result<T, Error> {
+        let endpoint = format!("{}{}", url.trim_end_matches('/'), path);
+        let mut response = surf::get(&endpoint)
+            .await
+            .map_err(|e| Error::RequestError(endpoint.clone(), e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(Error::ResponseError(
+                endpoint,
+                format!("status {}", response.status()),
+            ));
+        }
+        response
+            .body_json()
+            .await
+            .map_err(|e| Error::ResponseError(endpoint, e.to_string()))
+    }
+}
+
+#[async_trait]
+impl FederationClient for HttpFederationClient {
+    async fn balance(&self, url: &str, user: &UserAddress) -> Result<IdtAmount, Error> {
+        let response: IdtResponse = self.get(url, format!("/idt/{user}")).await?;
+        response
+            .idt
+            .parse()
+            .map_err(|_| Error::ResponseError(url.to_string(), "bad idt value".to_string()))
+    }
+
+    async fn vouchers(&self, url: &str, user: &UserAddress) -> Result<Vec<UserAddress>, Error> {
+        let response: VouchersResponse = self.get(url, format!("/vouchers/{user}")).await?;
+        Ok(response.vouchers)
+    }
+
+    async fn forward(&self, url: &str, request: ProxyRequest) -> Result<ProxyResponse, Error> {
+        let endpoint = format!("{}{}", url.trim_end_matches('/'), request.path);
+        let method = surf::http::Method::from_str(&request.method)
+            .map_err(|e| Error::RequestError(endpoint.clone(), e.to_string()))?;
+        let parsed_url = surf::Url::parse(&endpoint)
+            .map_err(|e| Error::RequestError(endpoint.clone(), e.to_string()))?;
+        let mut builder = surf::RequestBuilder::new(method, parsed_url);
+        for (name, value) in &request.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        if !request.body.is_empty() {
+            builder = builder
+                .body(request.body)
+                .content_type(surf::http::mime::JSON);
+        }
+        let mut response = builder
+            .await
+            .map_err(|e| Error::RequestError(endpoint.clone(), e.to_string()))?;
+        let body = response
+            .body_string()
+            .await
+            .map_err(|e| Error::ResponseError(endpoint, e.to_string()))?;
+        Ok(ProxyResponse {
+            status: response.status().into(),
+            body,
+        })
+    }
+}
+
+// serves predefined users and records forwarded requests, useful for tests
+#[derive(Default)]
+pub struct InMemoryFederationClient {
+    users: RwLock<HashMap<(String, UserAddress), RemoteUser>>,
+    forwarded: RwLock<Vec<(String, ProxyRequest)>>,
+}
+
+impl InMemoryFederationClient {
+    pub async fn set_user(&self, url: &str, user: UserAddress, remote: RemoteUser) {
+        self.users
+            .write()
+            .await
+            .insert((url.to_string(), user), remote);
+    }
+
+    async fn get(&self, url: &str, user: &UserAddress) -> Result<RemoteUser, Error> {
+        self.users
+            .read()
+            .await
+            .get(&(url.to_string(), user.clone()))
+            .cloned()
+            .ok_or_else(|| Error::ResponseError(url.to_string(), "status 404".to_string()))
+    }
+
+    pub async fn forwarded(&self) -> Vec<(String, ProxyRequest)> {
+        self.forwarded.read().await.clone()
+    }
+}
+
+#[async_trait]
+impl FederationClient for InMemoryFederationClient {
+    async fn balance(&self, url: &str, user: &UserAddress) -> Result<IdtAmount, Error> {
+        Ok(self.get(url, user).await?.idt)
+    }
+
+    async fn vouchers(&self, url: &str, user: &UserAddress) -> Result<Vec<UserAddress>, Error> {
+        Ok(self.get(url, user).await?.vouchers)
+    }
+
+    // answers with the requested path so callers can check what was forwarded
+    async fn forward(&self, url: &str, request: ProxyRequest) -> Result<ProxyResponse, Error> {
+        let body = serde_json::json!({ "path": request.path }).to_string();
+        self.forwarded
+            .write()
+            .await
+            .push((url.to_string(), request));
+        Ok(ProxyResponse { status: 200, body })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::servers::storage::ScaleSchedule;
+
+    #[async_std::test]
+    async fn test_in_memory_client() {
+        let client = InMemoryFederationClient::default();
+        let remote = RemoteUser {
+            idt: IdtAmount::new(100),
+            vouchers: vec!["voucher".to_string()],
+        };
+        client
+            .set_user("http://server1", "user".to_string(), remote.clone())
+            .await;
+        assert_eq!(
+            client
+                .user("http://server1", &"user".to_string())
+                .await
+                .unwrap(),
+            remote
+        );
+        assert!(
+            client
+                .user("http://server2", &"user".to_string())
+                .await
+                .is_err()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_overdue_server_decays() {
+        let client = InMemoryFederationClient::default();
+        let remote = RemoteUser {
+            idt: IdtAmount::new(100),
+            vouchers: vec![],
+        };
+        client
+            .set_user("http://server1", "user".to_string(), remote)
+            .await;
+        let servers = HashMap::from([(
+            "server1".to_string(),
+            ServerInfo {
+                url: "http://server1".to_string(),
+                scale: Rational::new(1, 2).unwrap(),
+                ..Default::default()
+            },
+        )]);
+        let config = FederationSection {
+            attestation_interval: 100,
+            attestation_decay: 100,
+            ..Default::default()
+        };
+        for (now, expected) in [(100, 50), (150, 25), (200, 0)] {
+            let sources =
+                query_servers(&client, servers.clone(), &"user".to_string(), now, &config).await;
+            assert_eq!(best_scaled_balance(&sources), IdtAmount::new(expected));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_scale_schedule() {
+        let client = InMemoryFederationClient::default();
+        let remote = RemoteUser {
+            idt: IdtAmount::new(100),
+            vouchers: vec![],
+        };
+        client
+            .set_user("http://server1", "user".to_string(), remote)
+            .await;
+        let servers = HashMap::from([(
+            "server1".to_string(),
+            ServerInfo {
+                url: "http://server1".to_string(),
+                scale: Rational::new(1, 2).unwrap(),
+                schedule: ScaleSchedule::Linear {
+                    initial: Rational::new(1, 5).unwrap(),
+                    duration: 100,
+                },
+                ..Default::default()
+            },
+        )]);
+        let config = FederationSection::default();
+        for (now, expected) in [(0, 10), (50, 30), (100, 50), (1000, 50)] {
+            let sources =
+                query_servers(&client, servers.clone(), &"user".to_string(), now, &config).await;
+            assert_eq!(best_scaled_balance(&sources), IdtAmount::new(expected));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_http_client_unreachable() {
+        let client = HttpFederationClient;
+        assert!(matches!(
+            client
+                .balance("http://127.0.0.1:1", &"user".to_string())
+                .await,
+            Err(Error::RequestError(_, _))
+        ));
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{federation::error::Error, identity::UserAddress};
+
+// declaration of a user that this server is their home, signed by the user over
+// `claim_home/<user>` for the domain of this server, see `verify::claim_home`
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HomeClaim {
+    pub signature: String,
+    pub nonce: u64,
+    pub expires_at: u64,
+    // format of the signed message, see `Freshness::version`
+    pub version: Option<u32>,
+    pub claimed_at: u64,
+}
+
+// maps users to the address of the server that owns their identity
+#[async_trait]
+pub trait HomeStorage: Send + Sync {
+    async fn set_home(&self, user: UserAddress, server: UserAddress) -> Result<(), Error>;
+    async fn remove_home(&self, user: &UserAddress) -> Result<(), Error>;
+    async fn home(&self, user: &UserAddress) -> Result<Option<UserAddress>, Error>;
+
+    // claims of users that this server is their home
+    async fn set_claim(&self, user: UserAddress, claim: HomeClaim) -> Result<(), Error>;
+    async fn remove_claim(&self, user: &UserAddress) -> Result<(), Error>;
+    async fn claim(&self, user: &UserAddress) -> Result<Option<HomeClaim>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryHomeStorage {
+    homes: RwLock<HashMap<UserAddress, UserAddress>>,
+    claims: RwLock<HashMap<UserAddress, HomeClaim>>,
+}
+
+#[async_trait]
+impl HomeStorage for InMemoryHomeStorage {
+    async fn set_home(&self, user: UserAddress, server: UserAddress) -> Result<(), Error> {
+        self.homes.write().await.insert(user, server);
+        Ok(())
+    }
+
+    async fn remove_home(&self, user: &UserAddress) -> Result<(), Error> {
+        self.homes.write().await.remove(user);
+        Ok(())
+    }
+
+    async fn home(&self, user: &UserAddress) -> Result<Option<UserAddress>, Error> {
+        Ok(self.homes.read().await.get(user).cloned())
+    }
+
+    async fn set_claim(&self, user: UserAddress, claim: HomeClaim) -> Result<(), Error> {
+        self.claims.write().await.insert(user, claim);
+        Ok(())
+    }
+
+    async fn remove_claim(&self, user: &UserAddress) -> Result<(), Error> {
+        self.claims.write().await.remove(user);
+        Ok(())
+    }
+
+    async fn claim(&self, user: &UserAddress) -> Result<Option<HomeClaim>, Error> {
+        Ok(self.claims.read().await.get(user).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryHomeStorage::default();
+        let user = "user".to_string();
+        assert!(storage.home(&user).await.unwrap().is_none());
+        storage
+            .set_home(user.clone(), "server1".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.home(&user).await.unwrap(),
+            Some("server1".to_string())
+        );
+        storage.remove_home(&user).await.unwrap();
+        assert!(storage.home(&user).await.unwrap().is_none());
+    }
+
+    #[async_std::test]
+    async fn test_claims() {
+        let storage = InMemoryHomeStorage::default();
+        let user = "user".to_string();
+        assert!(storage.claim(&user).await.unwrap().is_none());
+        let claim = HomeClaim {
+            signature: "0x01".to_string(),
+            claimed_at: 10,
+            ..Default::default()
+        };
+        storage
+            .set_claim(user.clone(), claim.clone())
+            .await
+            .unwrap();
+        assert_eq!(storage.claim(&user).await.unwrap(), Some(claim));
+        storage.remove_claim(&user).await.unwrap();
+        assert!(storage.claim(&user).await.unwrap().is_none());
+    }
+}
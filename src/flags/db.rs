@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::flags::{Flag, error::Error, storage::FlagStorage};
+use crate::pools::{self, MeteredPool};
+
+pub struct DatabaseFlagStorage {
+    pool: MeteredPool,
+}
+
+impl DatabaseFlagStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        let pool = pools::connect("flags", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS flags (name VARCHAR(64) PRIMARY KEY, enabled INTEGER NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        Ok(Self { pool })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl FlagStorage for DatabaseFlagStorage {
+    async fn set_flag(&self, flag: Flag, enabled: bool) -> Result<(), Error> {
+        sqlx::query("REPLACE INTO flags (name, enabled) VALUES (?, ?)")
+            .bind(flag.as_str())
+            .bind(i32::from(enabled))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn flags(&self) -> Result<BTreeMap<Flag, bool>, Error> {
+        let mut flags: BTreeMap<Flag, bool> =
+            Flag::ALL.into_iter().map(|flag| (flag, false)).collect();
+        let rows = sqlx::query("SELECT name, enabled FROM flags")
+            .fetch_all(&self.pool)
+            .await?;
+        for row in rows {
+            let name = row.get::<String, _>(0);
+            // flags removed from the server are ignored
+            if let Some(flag) = Flag::parse(&name) {
+                flags.insert(flag, row.get::<i32, _>(1) != 0);
+            }
+        }
+        Ok(flags)
+    }
+
+    async fn is_enabled(&self, flag: Flag) -> Result<bool, Error> {
+        let row = sqlx::query("SELECT enabled FROM flags WHERE name = ?")
+            .bind(flag.as_str())
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some_and(|r| r.get::<i32, _>(0) != 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseFlagStorage::new("sqlite::memory:").await.unwrap();
+        assert!(!storage.is_enabled(Flag::BanSelfVouch).await.unwrap());
+
+        storage.set_flag(Flag::BanSelfVouch, true).await.unwrap();
+        assert!(storage.is_enabled(Flag::BanSelfVouch).await.unwrap());
+        let flags = storage.flags().await.unwrap();
+        assert_eq!(flags.len(), Flag::ALL.len());
+        assert!(flags[&Flag::BanSelfVouch]);
+        assert!(!flags[&Flag::ReadOnly]);
+
+        storage.set_flag(Flag::BanSelfVouch, false).await.unwrap();
+        assert!(!storage.is_enabled(Flag::BanSelfVouch).await.unwrap());
+    }
+}
@@ -0,0 +1,8 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[cfg(feature = "sled")]
+    #[error("Storage error: {0}")]
+    StorageError(#[from] crate::kv::error::Error),
+}
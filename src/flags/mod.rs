@@ -0,0 +1,57 @@
+// Behavioral flags operators can flip at runtime with signed admin requests.
+//
+// Flags are off unless enabled with `POST /set_flag`, current values are listed by `GET /flags`.
+
+use serde::{Deserialize, Serialize};
+
+pub mod db;
+pub mod error;
+pub mod storage;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Flag {
+    // vouches must be countersigned by the vouchee
+    RequireVouchConsent,
+    // users cannot vouch for themselves
+    BanSelfVouch,
+    // all requests that change state are rejected, except setting flags
+    ReadOnly,
+}
+
+impl Flag {
+    pub const ALL: [Flag; 3] = [
+        Flag::RequireVouchConsent,
+        Flag::BanSelfVouch,
+        Flag::ReadOnly,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Flag::RequireVouchConsent => "require_vouch_consent",
+            Flag::BanSelfVouch => "ban_self_vouch",
+            Flag::ReadOnly => "read_only",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Flag::ALL.into_iter().find(|flag| flag.as_str() == value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        for flag in Flag::ALL {
+            assert_eq!(Flag::parse(flag.as_str()), Some(flag));
+            assert_eq!(
+                serde_json::to_value(flag).unwrap(),
+                serde_json::Value::from(flag.as_str())
+            );
+        }
+        assert_eq!(Flag::parse("unknown"), None);
+    }
+}
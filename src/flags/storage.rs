@@ -0,0 +1,60 @@
+use std::collections::BTreeMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::flags::{Flag, error::Error};
+
+#[async_trait]
+pub trait FlagStorage: Send + Sync {
+    async fn set_flag(&self, flag: Flag, enabled: bool) -> Result<(), Error>;
+
+    // every known flag with its value
+    async fn flags(&self) -> Result<BTreeMap<Flag, bool>, Error>;
+
+    async fn is_enabled(&self, flag: Flag) -> Result<bool, Error> {
+        Ok(self.flags().await?.get(&flag).copied().unwrap_or_default())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryFlagStorage {
+    enabled: RwLock<BTreeMap<Flag, bool>>,
+}
+
+#[async_trait]
+impl FlagStorage for InMemoryFlagStorage {
+    async fn set_flag(&self, flag: Flag, enabled: bool) -> Result<(), Error> {
+        self.enabled.write().await.insert(flag, enabled);
+        Ok(())
+    }
+
+    async fn flags(&self) -> Result<BTreeMap<Flag, bool>, Error> {
+        let enabled = self.enabled.read().await;
+        Ok(Flag::ALL
+            .into_iter()
+            .map(|flag| (flag, enabled.get(&flag).copied().unwrap_or_default()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryFlagStorage::default();
+        let flags = storage.flags().await.unwrap();
+        assert_eq!(flags.len(), Flag::ALL.len());
+        assert!(flags.values().all(|enabled| !enabled));
+
+        storage.set_flag(Flag::ReadOnly, true).await.unwrap();
+        assert!(storage.is_enabled(Flag::ReadOnly).await.unwrap());
+        assert!(!storage.is_enabled(Flag::BanSelfVouch).await.unwrap());
+        assert!(storage.flags().await.unwrap()[&Flag::ReadOnly]);
+
+        storage.set_flag(Flag::ReadOnly, false).await.unwrap();
+        assert!(!storage.is_enabled(Flag::ReadOnly).await.unwrap());
+    }
+}
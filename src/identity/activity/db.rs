@@ -0,0 +1,71 @@
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::{UserAddress, activity::storage::ActivityStorage, error::Error},
+    pools::{self, MeteredPool},
+};
+
+pub struct DatabaseActivityStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabaseActivityStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("activity", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS user_activity (user TEXT PRIMARY KEY, last_active INTEGER NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "user_activity", "user").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl ActivityStorage for DatabaseActivityStorage {
+    async fn set_last_active(&self, user: &UserAddress, timestamp: u64) -> Result<(), Error> {
+        sqlx::query("REPLACE INTO user_activity (user, last_active) VALUES (?, ?)")
+            .bind(self.cipher.encode(user))
+            .bind(timestamp as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn last_active(&self, user: &UserAddress) -> Result<Option<u64>, Error> {
+        let row = sqlx::query("SELECT last_active FROM user_activity WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| row.get::<i64, _>(0) as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseActivityStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        let user = "user".to_string();
+        assert_eq!(storage.last_active(&user).await.unwrap(), None);
+        storage.set_last_active(&user, 10).await.unwrap();
+        storage.set_last_active(&user, 20).await.unwrap();
+        assert_eq!(storage.last_active(&user).await.unwrap(), Some(20));
+    }
+}
@@ -0,0 +1,19 @@
+// Time of the last signed action of every address, recorded by
+// `verify::nonce::activity::ActivityNonceManager`. Users inactive for longer than
+// `identity.inactivity_period` have their proofs decayed faster, see `decay::inactivity_decay`.
+
+use crate::identity::{IdentityService, UserAddress, error::Error};
+
+#[cfg(feature = "storage-sql")]
+pub mod db;
+pub mod storage;
+
+impl IdentityService {
+    pub async fn record_activity(&self, user: &UserAddress) -> Result<(), Error> {
+        self.activity.set_last_active(user, self.now()).await
+    }
+
+    pub async fn last_active(&self, user: &UserAddress) -> Result<Option<u64>, Error> {
+        self.activity.last_active(user).await
+    }
+}
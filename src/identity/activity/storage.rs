@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::identity::{UserAddress, error::Error};
+
+#[async_trait]
+pub trait ActivityStorage: Send + Sync {
+    async fn set_last_active(&self, user: &UserAddress, timestamp: u64) -> Result<(), Error>;
+    async fn last_active(&self, user: &UserAddress) -> Result<Option<u64>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryActivityStorage {
+    last_active: RwLock<HashMap<UserAddress, u64>>,
+}
+
+#[async_trait]
+impl ActivityStorage for InMemoryActivityStorage {
+    async fn set_last_active(&self, user: &UserAddress, timestamp: u64) -> Result<(), Error> {
+        self.last_active
+            .write()
+            .await
+            .insert(user.clone(), timestamp);
+        Ok(())
+    }
+
+    async fn last_active(&self, user: &UserAddress) -> Result<Option<u64>, Error> {
+        Ok(self.last_active.read().await.get(user).copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryActivityStorage::default();
+        let user = "user".to_string();
+        assert_eq!(storage.last_active(&user).await.unwrap(), None);
+        storage.set_last_active(&user, 10).await.unwrap();
+        storage.set_last_active(&user, 20).await.unwrap();
+        assert_eq!(storage.last_active(&user).await.unwrap(), Some(20));
+    }
+}
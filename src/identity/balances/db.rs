@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use sqlx::{Acquire, Row};
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::{IdtAmount, UserAddress, balances::storage::BalanceStorage, error::Error},
+    numbers::rescale_column,
+    pools::{self, MeteredPool},
+};
+
+// name of the connection pool in `GET /metrics`
+const POOL_NAME: &str = "balances";
+
+pub struct DatabaseBalanceStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabaseBalanceStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect(POOL_NAME, url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS balances (user TEXT PRIMARY KEY, balance INTEGER NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS balances_meta (id INTEGER PRIMARY KEY, computed_at INTEGER NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "balances", "user").await?;
+        rescale_column(&pool, "balances", "balance").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl BalanceStorage for DatabaseBalanceStorage {
+    async fn set_balances(
+        &self,
+        balances: HashMap<UserAddress, IdtAmount>,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM balances")
+            .execute(tx.acquire().await?)
+            .await?;
+        for (user, balance) in balances {
+            sqlx::query("INSERT INTO balances (user, balance) VALUES (?, ?)")
+                .bind(self.cipher.encode(&user))
+                .bind(balance.milli() as i64)
+                .execute(tx.acquire().await?)
+                .await?;
+        }
+        sqlx::query("REPLACE INTO balances_meta (id, computed_at) VALUES (1, ?)")
+            .bind(timestamp as i64)
+            .execute(tx.acquire().await?)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn balance(&self, user: &UserAddress) -> Result<Option<IdtAmount>, Error> {
+        let row = sqlx::query("SELECT balance FROM balances WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|r| IdtAmount::from_milli(r.get::<i64, _>(0) as u64)))
+    }
+
+    async fn computed_at(&self) -> Result<Option<u64>, Error> {
+        let row = sqlx::query("SELECT computed_at FROM balances_meta WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|r| r.get::<i64, _>(0) as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseBalanceStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        assert!(storage.computed_at().await.unwrap().is_none());
+        storage
+            .set_balances(HashMap::from([("a".to_string(), IdtAmount::new(10))]), 100)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.balance(&"a".to_string()).await.unwrap(),
+            Some(IdtAmount::new(10))
+        );
+        assert_eq!(storage.computed_at().await.unwrap(), Some(100));
+        storage
+            .set_balances(HashMap::from([("b".to_string(), IdtAmount::new(20))]), 200)
+            .await
+            .unwrap();
+        assert!(storage.balance(&"a".to_string()).await.unwrap().is_none());
+        assert_eq!(
+            storage.balance(&"b".to_string()).await.unwrap(),
+            Some(IdtAmount::new(20))
+        );
+        assert_eq!(storage.computed_at().await.unwrap(), Some(200));
+    }
+}
@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+
+use crate::identity::{IdentityService, IdtAmount, UserAddress, error::Error};
+
+#[cfg(feature = "storage-sql")]
+pub mod db;
+pub mod storage;
+
+// balances computed in batch by strategies that cannot evaluate a single user
+impl IdentityService {
+    pub async fn set_materialized_balances(
+        &self,
+        balances: HashMap<UserAddress, IdtAmount>,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        self.balances.set_balances(balances, timestamp).await
+    }
+
+    pub async fn materialized_balance(
+        &self,
+        user: &UserAddress,
+    ) -> Result<Option<IdtAmount>, Error> {
+        self.balances.balance(user).await
+    }
+
+    pub async fn balances_computed_at(&self) -> Result<Option<u64>, Error> {
+        self.balances.computed_at().await
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::identity::{IdtAmount, UserAddress, error::Error};
+
+#[async_trait]
+pub trait BalanceStorage: Send + Sync {
+    // replaces all materialized balances at once
+    async fn set_balances(
+        &self,
+        balances: HashMap<UserAddress, IdtAmount>,
+        timestamp: u64,
+    ) -> Result<(), Error>;
+    async fn balance(&self, user: &UserAddress) -> Result<Option<IdtAmount>, Error>;
+    // timestamp of the last recomputation
+    async fn computed_at(&self) -> Result<Option<u64>, Error>;
+}
+
+#[derive(Default)]
+struct BalanceData {
+    balances: HashMap<UserAddress, IdtAmount>,
+    computed_at: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct InMemoryBalanceStorage {
+    data: RwLock<BalanceData>,
+}
+
+#[async_trait]
+impl BalanceStorage for InMemoryBalanceStorage {
+    async fn set_balances(
+        &self,
+        balances: HashMap<UserAddress, IdtAmount>,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        let mut lock = self.data.write().await;
+        lock.balances = balances;
+        lock.computed_at = Some(timestamp);
+        Ok(())
+    }
+
+    async fn balance(&self, user: &UserAddress) -> Result<Option<IdtAmount>, Error> {
+        Ok(self.data.read().await.balances.get(user).copied())
+    }
+
+    async fn computed_at(&self) -> Result<Option<u64>, Error> {
+        Ok(self.data.read().await.computed_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryBalanceStorage::default();
+        assert!(storage.computed_at().await.unwrap().is_none());
+        storage
+            .set_balances(HashMap::from([("a".to_string(), IdtAmount::new(10))]), 100)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.balance(&"a".to_string()).await.unwrap(),
+            Some(IdtAmount::new(10))
+        );
+        assert_eq!(storage.computed_at().await.unwrap(), Some(100));
+        storage
+            .set_balances(HashMap::from([("b".to_string(), IdtAmount::new(20))]), 200)
+            .await
+            .unwrap();
+        assert!(storage.balance(&"a".to_string()).await.unwrap().is_none());
+        assert_eq!(
+            storage.balance(&"b".to_string()).await.unwrap(),
+            Some(IdtAmount::new(20))
+        );
+        assert_eq!(storage.computed_at().await.unwrap(), Some(200));
+    }
+}
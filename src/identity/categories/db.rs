@@ -0,0 +1,96 @@
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::{UserAddress, categories::storage::CategoryStorage, error::Error},
+    pools::{self, MeteredPool},
+};
+
+pub struct DatabaseCategoryStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabaseCategoryStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("categories", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS user_categories (user TEXT PRIMARY KEY, category TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "user_categories", "user").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl CategoryStorage for DatabaseCategoryStorage {
+    async fn set_category(
+        &self,
+        user: &UserAddress,
+        category: Option<String>,
+    ) -> Result<(), Error> {
+        match category {
+            Some(category) => {
+                sqlx::query("REPLACE INTO user_categories (user, category) VALUES (?, ?)")
+                    .bind(self.cipher.encode(user))
+                    .bind(category)
+                    .execute(&self.pool)
+                    .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM user_categories WHERE user = ?")
+                    .bind(self.cipher.encode(user))
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn category(&self, user: &UserAddress) -> Result<Option<String>, Error> {
+        let row = sqlx::query("SELECT category FROM user_categories WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| row.get::<String, _>(0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseCategoryStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        let user = "user".to_string();
+        assert_eq!(storage.category(&user).await.unwrap(), None);
+        storage
+            .set_category(&user, Some("bot".into()))
+            .await
+            .unwrap();
+        storage
+            .set_category(&user, Some("service".into()))
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.category(&user).await.unwrap(),
+            Some("service".into())
+        );
+        storage.set_category(&user, None).await.unwrap();
+        assert_eq!(storage.category(&user).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,187 @@
+// Categories of users (e.g. "bot", "service", "human") assigned by moderators. The policy of
+// a category, configured in `identity.categories`, bounds the effective balance of its users
+// and may forbid them to vouch. Users without a category are not restricted.
+
+use serde::{Deserialize, Serialize};
+
+use crate::identity::{IdentityService, IdtAmount, UserAddress, error::Error};
+
+#[cfg(feature = "storage-sql")]
+pub mod db;
+pub mod storage;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CategoryPolicy {
+    pub min_balance: IdtAmount,
+    pub max_balance: Option<IdtAmount>,
+    pub can_vouch: bool,
+}
+
+impl Default for CategoryPolicy {
+    fn default() -> Self {
+        Self {
+            min_balance: IdtAmount::ZERO,
+            max_balance: None,
+            can_vouch: true,
+        }
+    }
+}
+
+impl CategoryPolicy {
+    // the floor wins over a misconfigured lower ceiling
+    pub fn clamp(&self, balance: IdtAmount) -> IdtAmount {
+        let balance = match self.max_balance {
+            Some(max) => balance.min(max),
+            None => balance,
+        };
+        balance.max(self.min_balance)
+    }
+}
+
+impl IdentityService {
+    // None removes the category of the user
+    pub async fn set_category(
+        &self,
+        user: &UserAddress,
+        category: Option<String>,
+    ) -> Result<(), Error> {
+        if let Some(category) = &category {
+            if !self.config.categories.contains_key(category) {
+                return Err(Error::UnknownCategory(category.clone()));
+            }
+        }
+        self.categories.set_category(user, category).await
+    }
+
+    pub async fn category(&self, user: &UserAddress) -> Result<Option<String>, Error> {
+        self.categories.category(user).await
+    }
+
+    // policy of the category of the user, categories removed from the config are ignored
+    pub async fn category_policy(
+        &self,
+        user: &UserAddress,
+    ) -> Result<Option<CategoryPolicy>, Error> {
+        // no lookups while categories are not configured
+        if self.config.categories.is_empty() {
+            return Ok(None);
+        }
+        Ok(self
+            .category(user)
+            .await?
+            .and_then(|category| self.config.categories.get(&category).cloned()))
+    }
+
+    pub async fn can_vouch(&self, user: &UserAddress) -> Result<bool, Error> {
+        Ok(self
+            .category_policy(user)
+            .await?
+            .is_none_or(|policy| policy.can_vouch))
+    }
+
+    pub async fn clamp_balance(
+        &self,
+        user: &UserAddress,
+        balance: IdtAmount,
+    ) -> Result<IdtAmount, Error> {
+        Ok(match self.category_policy(user).await? {
+            Some(policy) => policy.clamp(balance),
+            None => balance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        config::IdentitySection,
+        identity::{
+            idt::balance,
+            proof::prove,
+            tests::{MODERATOR, PROOF_ID, USER_A},
+            vouch::vouch,
+        },
+    };
+
+    #[async_std::test]
+    async fn test_category_policies() {
+        let service = IdentityService {
+            config: IdentitySection {
+                categories: HashMap::from([
+                    (
+                        "bot".to_string(),
+                        CategoryPolicy {
+                            max_balance: Some(IdtAmount::new(10)),
+                            can_vouch: false,
+                            ..Default::default()
+                        },
+                    ),
+                    (
+                        "service".to_string(),
+                        CategoryPolicy {
+                            min_balance: IdtAmount::new(50),
+                            ..Default::default()
+                        },
+                    ),
+                ]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, USER_A.to_string(), "userB".into())
+            .await
+            .unwrap();
+        let user_a = USER_A.to_string();
+        let user_b = "userB".to_string();
+        let balance_b = balance(&service, &user_b).await.unwrap();
+        assert!(balance_b > IdtAmount::new(0));
+
+        assert!(matches!(
+            service.set_category(&user_a, Some("human".into())).await,
+            Err(Error::UnknownCategory(_))
+        ));
+        service
+            .set_category(&user_a, Some("bot".into()))
+            .await
+            .unwrap();
+        assert_eq!(
+            balance(&service, &user_a).await.unwrap(),
+            IdtAmount::new(10)
+        );
+        assert!(!service.can_vouch(&user_a).await.unwrap());
+        // vouches of users who cannot vouch are not counted
+        assert_eq!(balance(&service, &user_b).await.unwrap(), IdtAmount::new(0));
+
+        service
+            .set_category(&user_b, Some("service".into()))
+            .await
+            .unwrap();
+        assert_eq!(
+            balance(&service, &user_b).await.unwrap(),
+            IdtAmount::new(50)
+        );
+
+        service.set_category(&user_a, None).await.unwrap();
+        assert_eq!(
+            balance(&service, &user_a).await.unwrap(),
+            IdtAmount::new(100)
+        );
+        assert_eq!(
+            balance(&service, &user_b).await.unwrap(),
+            balance_b.max(IdtAmount::new(50))
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::identity::{UserAddress, error::Error};
+
+#[async_trait]
+pub trait CategoryStorage: Send + Sync {
+    // None removes the category of the user
+    async fn set_category(&self, user: &UserAddress, category: Option<String>)
+    -> Result<(), Error>;
+    async fn category(&self, user: &UserAddress) -> Result<Option<String>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryCategoryStorage {
+    categories: RwLock<HashMap<UserAddress, String>>,
+}
+
+#[async_trait]
+impl CategoryStorage for InMemoryCategoryStorage {
+    async fn set_category(
+        &self,
+        user: &UserAddress,
+        category: Option<String>,
+    ) -> Result<(), Error> {
+        let mut categories = self.categories.write().await;
+        match category {
+            Some(category) => categories.insert(user.clone(), category),
+            None => categories.remove(user),
+        };
+        Ok(())
+    }
+
+    async fn category(&self, user: &UserAddress) -> Result<Option<String>, Error> {
+        Ok(self.categories.read().await.get(user).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryCategoryStorage::default();
+        let user = "user".to_string();
+        assert_eq!(storage.category(&user).await.unwrap(), None);
+        storage
+            .set_category(&user, Some("bot".into()))
+            .await
+            .unwrap();
+        assert_eq!(storage.category(&user).await.unwrap(), Some("bot".into()));
+        storage.set_category(&user, None).await.unwrap();
+        assert_eq!(storage.category(&user).await.unwrap(), None);
+    }
+}
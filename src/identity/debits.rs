@@ -0,0 +1,90 @@
+// IDT users gave up outside of the penalty system, e.g. pledges to the restitution pool.
+//
+// A debit lowers the balance of the user like a penalty, but it is not propagated to the
+// vouchers of the user, it is not theirs to pay. `NoDebits` is used unless restitution is
+// enabled.
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use crate::identity::{IdentityService, IdtAmount, UserAddress, error::Error};
+
+#[async_trait]
+pub trait Debits: Send + Sync {
+    async fn debit(&self, user: &UserAddress) -> Result<IdtAmount, Error>;
+}
+
+#[derive(Default)]
+pub struct NoDebits;
+
+#[async_trait]
+impl Debits for NoDebits {
+    async fn debit(&self, _user: &UserAddress) -> Result<IdtAmount, Error> {
+        Ok(IdtAmount::ZERO)
+    }
+}
+
+// debits known in advance, used to recompute balances of exported subgraphs
+#[derive(Default)]
+pub struct FixedDebits(pub HashMap<UserAddress, IdtAmount>);
+
+#[async_trait]
+impl Debits for FixedDebits {
+    async fn debit(&self, user: &UserAddress) -> Result<IdtAmount, Error> {
+        Ok(self.0.get(user).copied().unwrap_or_default())
+    }
+}
+
+impl IdentityService {
+    pub async fn debit(&self, user: &UserAddress) -> Result<IdtAmount, Error> {
+        self.debits.debit(user).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::identity::{
+        idt::balance,
+        proof::prove,
+        punish::penalty,
+        tests::{MODERATOR, PROOF_ID, USER_A},
+        vouch::vouch,
+    };
+
+    #[async_std::test]
+    async fn test_debit() {
+        let user_b = "userB".to_string();
+        let service = IdentityService {
+            debits: Arc::new(FixedDebits(HashMap::from([
+                (USER_A.to_string(), IdtAmount::new(40)),
+                (user_b.clone(), IdtAmount::new(1)),
+            ]))),
+            ..Default::default()
+        };
+        prove(
+            &service,
+            USER_A.into(),
+            MODERATOR.into(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, USER_A.into(), user_b.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            balance(&service, &USER_A.into()).await.unwrap(),
+            IdtAmount::new(60)
+        );
+        // the vouchee gets a share of the debited balance
+        assert_eq!(balance(&service, &user_b).await.unwrap(), IdtAmount::new(5));
+        // debits of the vouchee do not reach the voucher
+        assert!(penalty(&service, &USER_A.into()).await.unwrap().is_zero());
+    }
+}
@@ -1,27 +1,148 @@
-use crate::identity::{
-    IdentityService, IdtAmount, SystemPenalty, UserAddress, error::Error, next_timestamp,
-    vouch::voucher_timestamp,
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    identity::{
+        IdentityService, IdtAmount, SystemPenalty, UserAddress, error::Error,
+        vouch::voucher_timestamp,
+    },
+    numbers::MILLI_PER_IDT,
 };
 
-fn flat_one_idt_decay(event_timestamp: u64) -> IdtAmount {
-    let now = next_timestamp();
-    // future timestamp, should not happen
-    if now < event_timestamp {
-        return 0;
+pub const DAY: u64 = 60 * 60 * 24;
+
+// number of events seen with a timestamp ahead of the server time
+#[derive(Default)]
+pub struct FutureTimestamps(AtomicU64);
+
+impl FutureTimestamps {
+    pub fn count(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    // events from the future, e.g. signed by a client with a skewed clock, are treated as
+    // happening now, so they start to decay once the server time catches up
+    pub fn clamp(&self, now: u64, event_timestamp: u64) -> u64 {
+        if event_timestamp <= now {
+            return event_timestamp;
+        }
+        self.0.fetch_add(1, Ordering::Relaxed);
+        log::warn!(
+            "Event timestamp {} is {} seconds ahead of server time",
+            event_timestamp,
+            event_timestamp - now
+        );
+        now
     }
-    // decay is 1 IDT per day
-    (now - event_timestamp) / 60 / 60 / 24
+}
+
+// whole days since `start`
+fn elapsed_days(now: u64, start: u64) -> u64 {
+    now.saturating_sub(start) / DAY
+}
+
+// decay is 1 IDT per day, nothing is decayed before `decay_start`
+fn flat_one_idt_decay(now: u64, decay_start: u64) -> IdtAmount {
+    IdtAmount::new(elapsed_days(now, decay_start))
+}
+
+// timestamp when the proof starts to decay, fresh proofs are not decayed during the grace period
+pub async fn proof_grace_period_end(
+    service: &IdentityService,
+    user: &UserAddress,
+) -> Result<Option<u64>, Error> {
+    let grace_period = service.config.proof_grace_period;
+    Ok(service.proof(user).await?.map(|e| {
+        service
+            .future_timestamps
+            .clamp(service.now(), e.timestamp)
+            .saturating_add(grace_period)
+    }))
 }
 
 pub async fn proof_decay(
     service: &IdentityService,
     user: &UserAddress,
 ) -> Result<IdtAmount, Error> {
-    let (timestamp, _balance) = match service.proof(user).await? {
-        None => return Ok(0),
-        Some(e) => (e.timestamp, e.amount),
+    let decay_start = match proof_grace_period_end(service, user).await? {
+        None => return Ok(IdtAmount::ZERO),
+        Some(e) => e,
+    };
+    let decay = flat_one_idt_decay(service.now(), decay_start);
+    Ok(decay.saturating_add(inactivity_decay(service, user).await?))
+}
+
+// timestamp when the proof of an inactive user starts to decay faster. Users without
+// signed actions are active since their proof. None if the rule is disabled.
+pub async fn inactivity_start(
+    service: &IdentityService,
+    user: &UserAddress,
+) -> Result<Option<u64>, Error> {
+    let config = &service.config;
+    if config.inactivity_period == 0 || config.inactivity_decay.is_zero() {
+        return Ok(None);
+    }
+    let Some(proof) = service.proof(user).await? else {
+        return Ok(None);
+    };
+    let now = service.now();
+    let proven_at = service.future_timestamps.clamp(now, proof.timestamp);
+    let last_active = match service.last_active(user).await? {
+        Some(timestamp) => service
+            .future_timestamps
+            .clamp(now, timestamp)
+            .max(proven_at),
+        None => proven_at,
     };
-    Ok(flat_one_idt_decay(timestamp))
+    let grace_period_end = proven_at.saturating_add(config.proof_grace_period);
+    Ok(Some(
+        last_active
+            .saturating_add(config.inactivity_period)
+            .max(grace_period_end),
+    ))
+}
+
+// decay of the proof on top of the usual one, `identity.inactivity_decay` IDT per day
+// of inactivity
+pub async fn inactivity_decay(
+    service: &IdentityService,
+    user: &UserAddress,
+) -> Result<IdtAmount, Error> {
+    let Some(start) = inactivity_start(service, user).await? else {
+        return Ok(IdtAmount::ZERO);
+    };
+    let days = elapsed_days(service.now(), start);
+    Ok(service.config.inactivity_decay.saturating_mul(days))
+}
+
+// timestamp when the decayed proof balance reaches zero, assuming the user stays inactive
+pub async fn proof_expiry(
+    service: &IdentityService,
+    user: &UserAddress,
+) -> Result<Option<u64>, Error> {
+    let Some(proof) = service.proof(user).await? else {
+        return Ok(None);
+    };
+    let decay_start = service
+        .future_timestamps
+        .clamp(service.now(), proof.timestamp)
+        .saturating_add(service.config.proof_grace_period);
+    // whole days are decayed, so a fraction of IDT lasts one more day
+    let amount = proof.amount.milli();
+    let expiry = decay_start.saturating_add(amount.div_ceil(MILLI_PER_IDT).saturating_mul(DAY));
+    let Some(start) = inactivity_start(service, user).await? else {
+        return Ok(Some(expiry));
+    };
+    if expiry <= start {
+        return Ok(Some(expiry));
+    }
+    // both decays run after `start`:
+    // amount * DAY = MILLI_PER_IDT * (t - decay_start) + rate * (t - start)
+    let rate = service.config.inactivity_decay.milli();
+    let total = amount
+        .saturating_mul(DAY)
+        .saturating_add(decay_start.saturating_mul(MILLI_PER_IDT))
+        .saturating_add(rate.saturating_mul(start));
+    Ok(Some(total.div_ceil(rate.saturating_add(MILLI_PER_IDT))))
 }
 
 pub async fn moderator_penalty_decay(
@@ -29,10 +150,15 @@ pub async fn moderator_penalty_decay(
     user: &UserAddress,
 ) -> Result<IdtAmount, Error> {
     let (timestamp, _penalty) = match service.moderator_penalty(user).await? {
-        None => return Ok(0),
+        None => return Ok(IdtAmount::ZERO),
         Some(e) => (e.timestamp, e.amount),
     };
-    Ok(flat_one_idt_decay(timestamp))
+    let now = service.now();
+    let days = elapsed_days(now, service.future_timestamps.clamp(now, timestamp));
+    Ok(match service.penalty_reason_policy(user).await? {
+        Some(policy) => policy.decay_per_day.saturating_mul(days),
+        None => IdtAmount::new(days),
+    })
 }
 
 // vouchers decay twice,
@@ -44,14 +170,22 @@ pub async fn vouch_decay(
     voucher: &UserAddress,
 ) -> Result<IdtAmount, Error> {
     let timestamp = match voucher_timestamp(service, user, voucher).await? {
-        None => return Ok(0),
+        None => return Ok(IdtAmount::ZERO),
         Some(e) => e,
     };
-    Ok(flat_one_idt_decay(timestamp))
+    let now = service.now();
+    Ok(flat_one_idt_decay(
+        now,
+        service.future_timestamps.clamp(now, timestamp),
+    ))
 }
 
-pub fn system_penalty_decay(event: &SystemPenalty) -> IdtAmount {
-    flat_one_idt_decay(event.timestamp)
+pub fn system_penalty_decay(
+    service: &IdentityService,
+    event: &SystemPenalty,
+    now: u64,
+) -> IdtAmount {
+    flat_one_idt_decay(now, service.future_timestamps.clamp(now, event.timestamp))
 }
 
 // subtract decay from balance, ensuring it does not go below zero
@@ -61,7 +195,12 @@ pub fn balance_after_decay(balance: IdtAmount, decay: IdtAmount) -> IdtAmount {
 
 #[cfg(test)]
 mod tests {
-    use crate::identity::tests::{MODERATOR, PROOF_ID, USER_A};
+    use crate::config::IdentitySection;
+    use crate::identity::{
+        clock::Clock,
+        next_timestamp,
+        tests::{MODERATOR, PROOF_ID, START_TIMESTAMP, USER_A, service_with_mock_clock},
+    };
 
     use super::*;
 
@@ -69,45 +208,66 @@ mod tests {
     async fn test_basic_proof_decay() {
         let service = IdentityService::default();
         let ts = next_timestamp();
-        assert_eq!(proof_decay(&service, &USER_A.to_string()).await.unwrap(), 0);
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
         service
-            .prove_with_timestamp(USER_A.to_string(), MODERATOR.to_string(), 100, PROOF_ID, ts)
+            .prove_with_timestamp(
+                USER_A.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(100),
+                PROOF_ID,
+                ts,
+            )
             .await
             .unwrap();
-        assert_eq!(proof_decay(&service, &USER_A.to_string()).await.unwrap(), 0);
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
         service
             .prove_with_timestamp(
                 USER_A.to_string(),
                 MODERATOR.to_string(),
-                100,
+                IdtAmount::new(100),
                 PROOF_ID,
                 ts - 86400,
             )
             .await
             .unwrap();
-        assert_eq!(proof_decay(&service, &USER_A.to_string()).await.unwrap(), 1);
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1)
+        );
         service
             .prove_with_timestamp(
                 USER_A.to_string(),
                 MODERATOR.to_string(),
-                100,
+                IdtAmount::new(100),
                 PROOF_ID,
                 ts - 100000,
             )
             .await
             .unwrap();
-        assert_eq!(proof_decay(&service, &USER_A.to_string()).await.unwrap(), 1);
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1)
+        );
         service
             .prove_with_timestamp(
                 USER_A.to_string(),
                 MODERATOR.to_string(),
-                100,
+                IdtAmount::new(100),
                 PROOF_ID,
                 ts - 86400 * 2,
             )
             .await
             .unwrap();
-        assert_eq!(proof_decay(&service, &USER_A.to_string()).await.unwrap(), 2);
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(2)
+        );
     }
 
     #[async_std::test]
@@ -118,23 +278,29 @@ mod tests {
             moderator_penalty_decay(&service, &USER_A.to_string())
                 .await
                 .unwrap(),
-            0
+            IdtAmount::new(0)
         );
         service
-            .punish_with_timestamp(USER_A.to_string(), MODERATOR.to_string(), 100, PROOF_ID, ts)
+            .punish_with_timestamp(
+                USER_A.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(100),
+                PROOF_ID,
+                ts,
+            )
             .await
             .unwrap();
         assert_eq!(
             moderator_penalty_decay(&service, &USER_A.to_string())
                 .await
                 .unwrap(),
-            0
+            IdtAmount::new(0)
         );
         service
             .punish_with_timestamp(
                 USER_A.to_string(),
                 MODERATOR.to_string(),
-                100,
+                IdtAmount::new(100),
                 PROOF_ID,
                 ts - 86400,
             )
@@ -144,13 +310,13 @@ mod tests {
             moderator_penalty_decay(&service, &USER_A.to_string())
                 .await
                 .unwrap(),
-            1
+            IdtAmount::new(1)
         );
         service
             .punish_with_timestamp(
                 USER_A.to_string(),
                 MODERATOR.to_string(),
-                100,
+                IdtAmount::new(100),
                 PROOF_ID,
                 ts - 100000,
             )
@@ -160,13 +326,13 @@ mod tests {
             moderator_penalty_decay(&service, &USER_A.to_string())
                 .await
                 .unwrap(),
-            1
+            IdtAmount::new(1)
         );
         service
             .punish_with_timestamp(
                 USER_A.to_string(),
                 MODERATOR.to_string(),
-                100,
+                IdtAmount::new(100),
                 PROOF_ID,
                 ts - 86400 * 2,
             )
@@ -176,7 +342,7 @@ mod tests {
             moderator_penalty_decay(&service, &USER_A.to_string())
                 .await
                 .unwrap(),
-            2
+            IdtAmount::new(2)
         );
     }
 
@@ -189,7 +355,7 @@ mod tests {
             vouch_decay(&service, &USER_A.to_string(), &user_b.to_string())
                 .await
                 .unwrap(),
-            0
+            IdtAmount::new(0)
         );
         service
             .vouch_with_timestamp(user_b.to_string(), USER_A.to_string(), ts)
@@ -199,7 +365,7 @@ mod tests {
             vouch_decay(&service, &USER_A.to_string(), &user_b.to_string())
                 .await
                 .unwrap(),
-            0
+            IdtAmount::new(0)
         );
         service
             .vouch_with_timestamp(user_b.to_string(), USER_A.to_string(), ts - 86400)
@@ -209,7 +375,7 @@ mod tests {
             vouch_decay(&service, &USER_A.to_string(), &user_b.to_string())
                 .await
                 .unwrap(),
-            1
+            IdtAmount::new(1)
         );
         service
             .vouch_with_timestamp(user_b.to_string(), USER_A.to_string(), ts - 100000)
@@ -219,7 +385,7 @@ mod tests {
             vouch_decay(&service, &USER_A.to_string(), &user_b.to_string())
                 .await
                 .unwrap(),
-            1
+            IdtAmount::new(1)
         );
         service
             .vouch_with_timestamp(user_b.to_string(), USER_A.to_string(), ts - 86400 * 2)
@@ -229,33 +395,272 @@ mod tests {
             vouch_decay(&service, &USER_A.to_string(), &user_b.to_string())
                 .await
                 .unwrap(),
-            2
+            IdtAmount::new(2)
         );
     }
 
     #[test]
     fn test_system_penalty_decay() {
+        let service = IdentityService::default();
         let ts = next_timestamp();
         assert_eq!(
-            system_penalty_decay(&SystemPenalty {
-                amount: 10,
-                timestamp: ts
-            }),
-            0
+            system_penalty_decay(
+                &service,
+                &SystemPenalty {
+                    amount: IdtAmount::new(10),
+                    timestamp: ts
+                },
+                ts
+            ),
+            IdtAmount::new(0)
+        );
+        assert_eq!(
+            system_penalty_decay(
+                &service,
+                &SystemPenalty {
+                    amount: IdtAmount::new(10),
+                    timestamp: ts - 86400
+                },
+                ts
+            ),
+            IdtAmount::new(1)
+        );
+        assert_eq!(
+            system_penalty_decay(
+                &service,
+                &SystemPenalty {
+                    amount: IdtAmount::new(10),
+                    timestamp: ts - 86400 * 2
+                },
+                ts
+            ),
+            IdtAmount::new(2)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_proof_expiry() {
+        let (service, clock) = service_with_mock_clock();
+        assert_eq!(
+            proof_expiry(&service, &USER_A.to_string()).await.unwrap(),
+            None
+        );
+        service
+            .prove_with_timestamp(
+                USER_A.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(10),
+                PROOF_ID,
+                START_TIMESTAMP,
+            )
+            .await
+            .unwrap();
+        let expiry = proof_expiry(&service, &USER_A.to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(expiry, START_TIMESTAMP + 10 * 86400);
+        clock.set(expiry - 1);
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(9)
+        );
+        clock.set(expiry);
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(10)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_mock_clock_decay() {
+        let (service, clock) = service_with_mock_clock();
+        service
+            .prove_with_timestamp(
+                USER_A.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(100),
+                PROOF_ID,
+                START_TIMESTAMP,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+        clock.advance(86400 - 1);
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+        clock.advance(1);
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1)
+        );
+        clock.advance(86400 * 9);
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(10)
+        );
+        // events from the future do not decay
+        clock.set(START_TIMESTAMP - 86400);
+        assert_eq!(service.future_timestamps.count(), 0);
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+        assert_eq!(service.future_timestamps.count(), 1);
+        // and start to decay once the server time catches up
+        clock.set(START_TIMESTAMP + 86400);
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1)
+        );
+        assert_eq!(service.future_timestamps.count(), 1);
+    }
+
+    #[test]
+    fn test_clamp_timestamp() {
+        let service = IdentityService::default();
+        let future = &service.future_timestamps;
+        assert_eq!(future.clamp(100, 50), 50);
+        assert_eq!(future.clamp(100, 100), 100);
+        assert_eq!(future.count(), 0);
+        assert_eq!(future.clamp(100, 150), 100);
+        assert_eq!(future.count(), 1);
+        assert_eq!(
+            system_penalty_decay(
+                &service,
+                &SystemPenalty {
+                    amount: IdtAmount::new(10),
+                    timestamp: 100 + 86400 * 5,
+                },
+                100
+            ),
+            IdtAmount::new(0)
+        );
+        assert_eq!(future.count(), 2);
+    }
+
+    #[async_std::test]
+    async fn test_proof_grace_period() {
+        let (service, clock) = service_with_mock_clock();
+        let service = IdentityService {
+            config: IdentitySection {
+                proof_grace_period: 7 * 86400,
+                ..Default::default()
+            },
+            ..service
+        };
+        assert_eq!(
+            proof_grace_period_end(&service, &USER_A.to_string())
+                .await
+                .unwrap(),
+            None
+        );
+        service
+            .prove_with_timestamp(
+                USER_A.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(100),
+                PROOF_ID,
+                START_TIMESTAMP,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            proof_grace_period_end(&service, &USER_A.to_string())
+                .await
+                .unwrap(),
+            Some(START_TIMESTAMP + 7 * 86400)
+        );
+        clock.advance(7 * 86400);
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+        clock.advance(86400);
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1)
+        );
+
+        assert_eq!(
+            proof_expiry(&service, &USER_A.to_string()).await.unwrap(),
+            Some(START_TIMESTAMP + 107 * 86400)
+        );
+
+        // new proof restarts the grace period
+        service
+            .prove_with_timestamp(
+                USER_A.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(100),
+                PROOF_ID + 1,
+                clock.now(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            proof_decay(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_inactivity_decay() {
+        let (service, clock) = service_with_mock_clock();
+        let service = IdentityService {
+            config: IdentitySection {
+                inactivity_period: 10 * DAY,
+                inactivity_decay: IdtAmount::new(2),
+                ..Default::default()
+            },
+            ..service
+        };
+        let user = USER_A.to_string();
+        service
+            .prove_with_timestamp(
+                user.clone(),
+                MODERATOR.to_string(),
+                IdtAmount::new(100),
+                PROOF_ID,
+                START_TIMESTAMP,
+            )
+            .await
+            .unwrap();
+        // users are active since their proof
+        clock.advance(10 * DAY);
+        assert_eq!(
+            proof_decay(&service, &user).await.unwrap(),
+            IdtAmount::new(10)
+        );
+        clock.advance(DAY);
+        assert_eq!(
+            inactivity_decay(&service, &user).await.unwrap(),
+            IdtAmount::new(2)
+        );
+        assert_eq!(
+            proof_decay(&service, &user).await.unwrap(),
+            IdtAmount::new(13)
+        );
+
+        // a signed action stops the faster decay for another period
+        service.record_activity(&user).await.unwrap();
+        assert_eq!(
+            proof_decay(&service, &user).await.unwrap(),
+            IdtAmount::new(11)
         );
         assert_eq!(
-            system_penalty_decay(&SystemPenalty {
-                amount: 10,
-                timestamp: ts - 86400
-            }),
-            1
+            inactivity_start(&service, &user).await.unwrap(),
+            Some(START_TIMESTAMP + 21 * DAY)
         );
+        // 100 IDT are decayed after t days: t + 2 * (t - 21) = 100
         assert_eq!(
-            system_penalty_decay(&SystemPenalty {
-                amount: 10,
-                timestamp: ts - 86400 * 2
-            }),
-            2
+            proof_expiry(&service, &user).await.unwrap(),
+            Some(START_TIMESTAMP + 142 * DAY / 3)
         );
     }
 }
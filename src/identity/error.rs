@@ -1,7 +1,38 @@
+use crate::identity::{IdtAmount, UserAddress};
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Max balance from proof exceeded")]
     MaxBalanceExceeded,
+    #[error("Max balance from proof of the moderator exceeded, max is {0}")]
+    ModeratorLimitExceeded(IdtAmount),
+    #[error("Vouch refresh is not allowed before {0}")]
+    VouchRefreshTooEarly(u64),
+    #[error("Unknown user category {0}")]
+    UnknownCategory(String),
+    #[error("Unknown penalty reason {0}")]
+    UnknownPenaltyReason(String),
+    #[error("Address {0} is blocked by screening")]
+    AddressBlocked(UserAddress),
+    #[error("User {0} is proven more than once in the batch")]
+    DuplicateBatchUser(UserAddress),
+    #[error("{} entries of the proof batch were rejected", .0.len())]
+    ProofBatchRejected(Vec<(usize, Error)>),
+    #[error("Stored genesis differs from the loaded one: {0}")]
+    GenesisDrift(String),
+    #[error("Write queue is closed, the write was not applied")]
+    WriteQueueClosed,
+    #[error("Debit lookup failed: {0}")]
+    DebitError(String),
+    #[cfg(feature = "storage-sql")]
+    #[error("Encryption error: {0}")]
+    EncryptionError(#[from] crate::encryption::error::Error),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[cfg(feature = "storage-sql")]
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
+    #[cfg(feature = "sled")]
+    #[error("Storage error: {0}")]
+    StorageError(#[from] crate::kv::error::Error),
 }
@@ -1,6 +1,6 @@
 use std::collections::HashSet;
 
-use crate::identity::{IdentityService, UserAddress, error::Error, next_timestamp};
+use crate::identity::{IdentityService, UserAddress, error::Error};
 
 impl IdentityService {
     pub async fn forget_with_timestamp(
@@ -36,14 +36,16 @@ pub async fn forget(
     vouchee: UserAddress,
 ) -> Result<(), Error> {
     service
-        .forget_with_timestamp(user, vouchee, next_timestamp())
+        .forget_with_timestamp(user, vouchee, service.now())
         .await
 }
 
 #[cfg(test)]
 mod tests {
     use crate::identity::{
+        IdtAmount,
         idt::balance,
+        next_timestamp,
         proof::prove,
         punish::{penalty, punish},
         tests::{MODERATOR, PROOF_ID, USER_A},
@@ -60,7 +62,7 @@ mod tests {
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            10000,
+            IdtAmount::new(10000),
             PROOF_ID,
         )
         .await
@@ -68,14 +70,29 @@ mod tests {
         vouch(&service, USER_A.to_string(), user_b.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 10000);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 1000);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(10000)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(1000)
+        );
         forget(&service, USER_A.to_string(), user_b.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 9500);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 0);
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 500);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(9500)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(500)
+        );
     }
 
     #[async_std::test]
@@ -86,7 +103,7 @@ mod tests {
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            10000,
+            IdtAmount::new(10000),
             PROOF_ID,
         )
         .await
@@ -94,25 +111,46 @@ mod tests {
         vouch(&service, USER_A.to_string(), user_b.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 10000);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 1000);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(10000)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(1000)
+        );
         punish(
             &service,
             user_b.to_string(),
             MODERATOR.to_string(),
-            500,
+            IdtAmount::new(500),
             PROOF_ID,
         )
         .await
         .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 9950);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 495);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(9950)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(495)
+        );
         forget(&service, USER_A.to_string(), user_b.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 9450);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 0);
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 550);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(9450)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(550)
+        );
     }
 
     #[async_std::test]
@@ -124,7 +162,7 @@ mod tests {
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            10000,
+            IdtAmount::new(10000),
             PROOF_ID,
         )
         .await
@@ -135,21 +173,48 @@ mod tests {
         vouch(&service, USER_A.to_string(), user_c.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 10000);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 1000);
-        assert_eq!(balance(&service, &user_c.to_string()).await.unwrap(), 1000);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(10000)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(1000)
+        );
+        assert_eq!(
+            balance(&service, &user_c.to_string()).await.unwrap(),
+            IdtAmount::new(1000)
+        );
         forget(&service, USER_A.to_string(), user_b.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 9500);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 0);
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 500);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(9500)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(500)
+        );
         forget(&service, USER_A.to_string(), user_c.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 9000);
-        assert_eq!(balance(&service, &user_c.to_string()).await.unwrap(), 0);
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 1000);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(9000)
+        );
+        assert_eq!(
+            balance(&service, &user_c.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1000)
+        );
     }
 
     #[async_std::test]
@@ -162,7 +227,7 @@ mod tests {
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            10000,
+            IdtAmount::new(10000),
             PROOF_ID,
         )
         .await
@@ -173,24 +238,51 @@ mod tests {
         vouch(&service, USER_A.to_string(), user_c.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 10000);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 1000);
-        assert_eq!(balance(&service, &user_c.to_string()).await.unwrap(), 1000);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(10000)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(1000)
+        );
+        assert_eq!(
+            balance(&service, &user_c.to_string()).await.unwrap(),
+            IdtAmount::new(1000)
+        );
         // this is implementation of forget() but with overridden timestamp
         service
             .forget_with_timestamp(USER_A.to_string(), user_b.to_string(), ts - 86400 * 2)
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 9502);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 0);
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 498);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(9502)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(498)
+        );
         service
             .forget_with_timestamp(USER_A.to_string(), user_c.to_string(), ts - 86400)
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 9003);
-        assert_eq!(balance(&service, &user_c.to_string()).await.unwrap(), 0);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(9003)
+        );
+        assert_eq!(
+            balance(&service, &user_c.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
         // penalties from forget() decay simultaneously for all forgotten users
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 997);
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(997)
+        );
     }
 }
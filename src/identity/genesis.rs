@@ -1,10 +1,116 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
+
+use serde::{Deserialize, Serialize};
 
 use crate::identity::{IdentityService, IdtAmount, UserAddress, error::Error};
 
+// what `set_genesis` does when the stored genesis differs from the loaded one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GenesisDriftPolicy {
+    // refuses to start, the operator has to fix the genesis file or the storage
+    Fail,
+    // keeps the stored genesis and logs the drift
+    Warn,
+    // replaces the stored genesis and logs the drift
+    #[default]
+    Reconcile,
+}
+
+// differences of the stored genesis from the loaded one
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GenesisDrift {
+    // stored users missing from the loaded genesis
+    pub removed: HashMap<UserAddress, IdtAmount>,
+    // key - user, value - (stored, loaded) balance
+    pub changed: HashMap<UserAddress, (IdtAmount, IdtAmount)>,
+    // loaded users missing from the stored genesis
+    pub added: HashMap<UserAddress, IdtAmount>,
+}
+
+impl GenesisDrift {
+    pub fn new(
+        stored: &HashMap<UserAddress, IdtAmount>,
+        loaded: &HashMap<UserAddress, IdtAmount>,
+    ) -> Self {
+        let mut drift = Self::default();
+        for (user, stored_balance) in stored {
+            match loaded.get(user) {
+                None => {
+                    drift.removed.insert(user.clone(), *stored_balance);
+                }
+                Some(balance) if balance != stored_balance => {
+                    drift
+                        .changed
+                        .insert(user.clone(), (*stored_balance, *balance));
+                }
+                Some(_) => {}
+            }
+        }
+        for (user, balance) in loaded {
+            if !stored.contains_key(user) {
+                drift.added.insert(user.clone(), *balance);
+            }
+        }
+        drift
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.changed.is_empty() && self.added.is_empty()
+    }
+}
+
+impl fmt::Display for GenesisDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} removed, {} changed, {} added balances",
+            self.removed.len(),
+            self.changed.len(),
+            self.added.len()
+        )
+    }
+}
+
 impl IdentityService {
-    pub async fn set_genesis(&self, users: HashMap<UserAddress, IdtAmount>) -> Result<(), Error> {
-        self.proofs.set_genesis(users).await
+    // stores the genesis balances. A first start stores them as is, later starts compare them
+    // with the stored genesis and apply `policy` to the drift, which is returned.
+    pub async fn set_genesis(
+        &self,
+        users: HashMap<UserAddress, IdtAmount>,
+        policy: GenesisDriftPolicy,
+    ) -> Result<GenesisDrift, Error> {
+        let stored = self.proofs.genesis().await?;
+        if stored.is_empty() {
+            self.proofs.set_genesis(users).await?;
+            return Ok(GenesisDrift::default());
+        }
+        let drift = GenesisDrift::new(&stored, &users);
+        if drift.is_empty() {
+            return Ok(drift);
+        }
+        for (user, balance) in &drift.removed {
+            log::warn!("Genesis balance of {} ({} IDT) was removed", user, balance);
+        }
+        for (user, (stored, loaded)) in &drift.changed {
+            log::warn!(
+                "Genesis balance of {} changed from {} to {} IDT",
+                user,
+                stored,
+                loaded
+            );
+        }
+        match policy {
+            GenesisDriftPolicy::Fail => return Err(Error::GenesisDrift(drift.to_string())),
+            GenesisDriftPolicy::Warn => {
+                log::warn!("Genesis drift: {}, keeping the stored genesis", drift);
+            }
+            GenesisDriftPolicy::Reconcile => {
+                log::warn!("Genesis drift: {}, replacing the stored genesis", drift);
+                self.proofs.set_genesis(users).await?;
+            }
+        }
+        Ok(drift)
     }
 
     pub async fn genesis_balance(&self, user: &UserAddress) -> Result<Option<IdtAmount>, Error> {
@@ -20,18 +126,20 @@ mod tests {
         proof::prove,
         tests::{MODERATOR, PROOF_ID},
     };
-    use std::collections::HashMap;
 
     #[async_std::test]
     async fn test_genesis_balance() {
         let service = IdentityService::default();
         let genesis_user = "genesis_user".to_string();
-        let genesis_balance = 500;
+        let genesis_balance = IdtAmount::new(500);
 
         // set up a genesis balance for the user
         let mut balances = HashMap::new();
         balances.insert(genesis_user.clone(), genesis_balance);
-        service.set_genesis(balances).await.unwrap();
+        service
+            .set_genesis(balances, GenesisDriftPolicy::Fail)
+            .await
+            .unwrap();
 
         // check that the balance is recognized through the `balance` function
         assert_eq!(
@@ -45,7 +153,7 @@ mod tests {
             &service,
             genesis_user.clone(),
             MODERATOR.to_string(),
-            moderator_balance,
+            IdtAmount::new(moderator_balance),
             PROOF_ID,
         )
         .await
@@ -54,15 +162,69 @@ mod tests {
         // check that the balance is updated
         assert_eq!(
             balance(&service, &genesis_user).await.unwrap(),
-            moderator_balance
+            IdtAmount::new(moderator_balance)
         );
 
         // check that the proof is updated
         let proof = service.proof(&genesis_user).await.unwrap();
         assert!(proof.is_some());
         let proof = proof.unwrap();
-        assert_eq!(proof.amount, moderator_balance);
+        assert_eq!(proof.amount, IdtAmount::new(moderator_balance));
         assert_eq!(proof.moderator, MODERATOR);
         assert_eq!(proof.proof_id, PROOF_ID);
     }
+
+    fn genesis() -> HashMap<UserAddress, IdtAmount> {
+        HashMap::from([
+            ("a".to_string(), IdtAmount::new(100)),
+            ("b".to_string(), IdtAmount::new(200)),
+        ])
+    }
+
+    #[async_std::test]
+    async fn test_drift() {
+        let loaded = HashMap::from([
+            ("b".to_string(), IdtAmount::new(250)),
+            ("c".to_string(), IdtAmount::new(300)),
+        ]);
+        let drift = GenesisDrift::new(&genesis(), &loaded);
+        assert_eq!(
+            drift.removed,
+            HashMap::from([("a".to_string(), IdtAmount::new(100))])
+        );
+        assert_eq!(
+            drift.changed,
+            HashMap::from([("b".to_string(), (IdtAmount::new(200), IdtAmount::new(250)))])
+        );
+        assert_eq!(
+            drift.added,
+            HashMap::from([("c".to_string(), IdtAmount::new(300))])
+        );
+        assert_eq!(drift.to_string(), "1 removed, 1 changed, 1 added balances");
+        assert!(GenesisDrift::new(&genesis(), &genesis()).is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_drift_policies() {
+        let loaded = HashMap::from([("a".to_string(), IdtAmount::new(100))]);
+        for (policy, kept) in [
+            (GenesisDriftPolicy::Fail, true),
+            (GenesisDriftPolicy::Warn, true),
+            (GenesisDriftPolicy::Reconcile, false),
+        ] {
+            let service = IdentityService::default();
+            // the first start stores the genesis whatever the policy
+            let drift = service.set_genesis(genesis(), policy).await.unwrap();
+            assert!(drift.is_empty());
+
+            let result = service.set_genesis(loaded.clone(), policy).await;
+            if policy == GenesisDriftPolicy::Fail {
+                assert!(matches!(result, Err(Error::GenesisDrift(_))));
+            } else {
+                assert_eq!(result.unwrap().removed.len(), 1);
+            }
+            let stored = service.proofs.genesis().await.unwrap();
+            assert_eq!(stored == genesis(), kept);
+        }
+    }
 }
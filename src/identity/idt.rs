@@ -1,24 +1,53 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
+
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    config::IdentitySection,
     identity::{
         IdentityService, IdtAmount, UserAddress,
-        decay::{balance_after_decay, proof_decay, vouch_decay},
+        clock::FixedClock,
+        decay::{DAY, balance_after_decay, proof_decay, proof_grace_period_end, vouch_decay},
         error::Error,
         punish::penalty,
-        tree_walk::{ChildrenSelector, Visitor, walk_tree},
-        vouch::vouchers,
+        tree_walk::{ChildrenSelector, Visitor, walk_tree_with_budget, walk_tree_with_size},
+        vouch::{voucher_timestamp, vouchers},
     },
     numbers::Rational,
 };
 
 pub const TOP_VOUCHERS_SIZE: u16 = 5;
+// upper bound for the number of top vouchers requested by clients
+pub const MAX_TOP_VOUCHERS_SIZE: u16 = 20;
 // voucher's balance is multiplied to this coefficient before adding to vouchee balance,
 // stored as (numerator, denominator)
 pub const VOUCHER_WEIGHT_RATIO: (u32, u32) = (1, 10);
 
+// vouches at least `age` seconds old use `ratio` instead of VOUCHER_WEIGHT_RATIO
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MaturityStep {
+    pub age: u64,
+    pub ratio: Rational,
+}
+
+// coefficient applied to the voucher balance, the step with the greatest reached age wins
+pub fn vouch_weight(config: &IdentitySection, vouch_age: u64) -> Rational {
+    config
+        .maturity_bonus
+        .iter()
+        // zero denominator would panic on multiplication
+        .filter(|step| vouch_age >= step.age && step.ratio.denominator() != 0)
+        .max_by_key(|step| step.age)
+        .map(|step| step.ratio.clone())
+        .unwrap_or_else(|| {
+            Rational::new(VOUCHER_WEIGHT_RATIO.0, VOUCHER_WEIGHT_RATIO.1)
+                .expect("VOUCHER_WEIGHT_RATIO denominator must not be zero")
+        })
+}
+
 struct VouchTree<'a> {
     service: &'a IdentityService,
+    top_size: u16,
 }
 
 impl ChildrenSelector for VouchTree<'_> {
@@ -32,10 +61,11 @@ async fn top_vouchers(
     user: &UserAddress,
     visited: &im::HashSet<UserAddress>,
     balances: &HashMap<UserAddress, IdtAmount>,
+    top_size: u16,
 ) -> Result<Vec<(UserAddress, IdtAmount)>, Error> {
     let mut top_balances: Vec<(UserAddress, IdtAmount)> = vec![];
     for v in &vouchers(service, user).await? {
-        if visited.contains(v) {
+        if visited.contains(v) || !service.can_vouch(v).await? {
             continue;
         }
         // child balance could be missing due to cyclic dependency.
@@ -45,8 +75,10 @@ async fn top_vouchers(
         };
         top_balances.push((v.clone(), voucher_balance));
     }
-    top_balances.sort_by(|&(_, balance_a), &(_, balance_b)| balance_b.cmp(&balance_a));
-    top_balances.truncate(TOP_VOUCHERS_SIZE.into());
+    // equal balances are ordered by address, so every replica selects the same vouchers
+    top_balances
+        .sort_by(|(a, a_balance), (b, b_balance)| b_balance.cmp(a_balance).then_with(|| a.cmp(b)));
+    top_balances.truncate(top_size.into());
     Ok(top_balances)
 }
 
@@ -57,50 +89,153 @@ impl Visitor for VouchTree<'_> {
         visited_branch: &im::HashSet<UserAddress>,
         balances: &HashMap<UserAddress, IdtAmount>,
     ) -> Result<IdtAmount, Error> {
-        let voucher_scale = Rational::new(VOUCHER_WEIGHT_RATIO.0, VOUCHER_WEIGHT_RATIO.1)
-            .expect("VOUCHER_WEIGHT_RATIO denominator must not be zero");
-        let proven_balance = {
-            match self.service.proof(node).await? {
-                // fallback to genesis balance if proof is not found
-                // genesis balance does not decay but only lasts till the first proof
-                None => self
-                    .service
-                    .genesis_balance(node)
-                    .await?
-                    .unwrap_or_default(),
-                Some(e) => {
-                    let proven_balance_decay = proof_decay(self.service, node).await?;
-                    balance_after_decay(e.amount, proven_balance_decay)
-                }
-            }
-        };
+        let now = self.service.now();
+        let proven_balance = proven_balance(self.service, node).await?;
 
-        let top_vouchers = top_vouchers(self.service, node, visited_branch, balances).await?;
-        let mut balance_from_vouchers = 0;
+        let top_vouchers =
+            top_vouchers(self.service, node, visited_branch, balances, self.top_size).await?;
+        let mut balance_from_vouchers = IdtAmount::ZERO;
         for (user, balance) in &top_vouchers {
             let voucher_balance_decay = vouch_decay(self.service, node, user).await?;
-            let voucher_balance = voucher_scale.mul(*balance);
+            let vouch_age = voucher_timestamp(self.service, node, user)
+                .await?
+                .map(|timestamp| now.saturating_sub(timestamp))
+                .unwrap_or_default();
+            let voucher_scale = vouch_weight(&self.service.config, vouch_age);
+            let voucher_balance = voucher_scale.mul_rounded(*balance, self.service.config.rounding);
             balance_from_vouchers += balance_after_decay(voucher_balance, voucher_balance_decay);
         }
         let penalty = penalty(self.service, node).await?;
+        let debit = self.service.debit(node).await?;
         let positive_balance = proven_balance + balance_from_vouchers;
-        Ok(positive_balance.saturating_sub(penalty))
+        // bounded before it is propagated to the vouchees
+        self.service
+            .clamp_balance(node, positive_balance.saturating_sub(penalty + debit))
+            .await
+    }
+}
+
+// balance backed by the moderator proof, vouches and penalties are not included
+pub async fn proven_balance(
+    service: &IdentityService,
+    user: &UserAddress,
+) -> Result<IdtAmount, Error> {
+    match service.proof(user).await? {
+        // fallback to genesis balance if proof is not found
+        // genesis balance does not decay but only lasts till the first proof
+        None => Ok(service.genesis_balance(user).await?.unwrap_or_default()),
+        Some(e) => {
+            let proven_balance_decay = proof_decay(service, user).await?;
+            let balance = balance_after_decay(e.amount, proven_balance_decay);
+            if !service.config.reputation_weighted_proofs {
+                return Ok(balance);
+            }
+            let stats = service.moderator_stats(&e.moderator).await?;
+            Ok(stats.reputation().mul(balance))
+        }
     }
 }
 
+pub async fn vouch_tree_balance(
+    service: &IdentityService,
+    user: &UserAddress,
+) -> Result<IdtAmount, Error> {
+    vouch_tree_balance_with_top(service, user, TOP_VOUCHERS_SIZE).await
+}
+
+// vouch tree balance counting `top_size` vouchers of every user instead of TOP_VOUCHERS_SIZE
+pub async fn vouch_tree_balance_with_top(
+    service: &IdentityService,
+    user: &UserAddress,
+    top_size: u16,
+) -> Result<IdtAmount, Error> {
+    let tree = VouchTree { service, top_size };
+    let (balance, size) = walk_tree_with_size(&tree, user).await?;
+    service.record_tree_size(user, size);
+    Ok(balance)
+}
+
+// vouch tree balance visiting at most `budget` nodes of the tree, vouchers of further nodes
+// are not counted. Returns whether the budget was exceeded, the balance is then a lower bound
+// of the exact one.
+pub async fn vouch_tree_balance_with_budget(
+    service: &IdentityService,
+    user: &UserAddress,
+    top_size: u16,
+    budget: u64,
+) -> Result<(IdtAmount, bool), Error> {
+    let tree = VouchTree { service, top_size };
+    let (balance, size, approximate) = walk_tree_with_budget(&tree, user, Some(budget)).await?;
+    service.record_tree_size(user, size);
+    Ok((balance, approximate))
+}
+
+// parts of the balance that do not depend on vouches
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BalanceBreakdown {
+    // proven balance after decay
+    pub proven: IdtAmount,
+    pub proof_decay: IdtAmount,
+    // proof does not decay until this timestamp
+    pub grace_period_end: Option<u64>,
+    pub penalty: IdtAmount,
+}
+
+pub async fn balance_breakdown(
+    service: &IdentityService,
+    user: &UserAddress,
+) -> Result<BalanceBreakdown, Error> {
+    Ok(BalanceBreakdown {
+        proven: proven_balance(service, user).await?,
+        proof_decay: proof_decay(service, user).await?,
+        grace_period_end: proof_grace_period_end(service, user).await?,
+        penalty: penalty(service, user).await?,
+    })
+}
+
+// computes balance with the scoring strategy selected for the service, bounded by the
+// category of the user
 pub async fn balance(service: &IdentityService, user: &UserAddress) -> Result<IdtAmount, Error> {
-    let tree = VouchTree { service };
-    walk_tree(&tree, user).await
+    let balance = service.strategy.balance(service, user).await?;
+    service.clamp_balance(user, balance).await
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BalanceProjection {
+    pub timestamp: u64,
+    pub balance: IdtAmount,
+    pub breakdown: BalanceBreakdown,
+}
+
+// balance `days` days from now assuming no new proofs, vouches or penalties,
+// only decay changes it
+pub async fn balance_projection(
+    service: &IdentityService,
+    user: &UserAddress,
+    days: u64,
+) -> Result<BalanceProjection, Error> {
+    let timestamp = service.now().saturating_add(days.saturating_mul(DAY));
+    let future = IdentityService {
+        clock: Arc::new(FixedClock(timestamp)),
+        ..service.clone()
+    };
+    Ok(BalanceProjection {
+        timestamp,
+        balance: balance(&future, user).await?,
+        breakdown: balance_breakdown(&future, user).await?,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use crate::identity::{
+        genesis::GenesisDriftPolicy,
         next_timestamp,
         proof::prove,
-        tests::{MODERATOR, PROOF_ID, USER_A},
+        tests::{MODERATOR, PROOF_ID, USER_A, service_with_mock_clock},
         vouch::vouch,
     };
+    use crate::numbers::Rounding;
 
     use super::*;
     use std::collections::HashMap;
@@ -113,20 +248,32 @@ mod tests {
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            100,
+            IdtAmount::new(100),
             PROOF_ID,
         )
         .await
         .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 100);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 0);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(100)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
         vouch(&service, USER_A.to_string(), user_b.to_string())
             .await
             .unwrap();
         // IDT of A does not change after vouching
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 100);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(100)
+        );
         // IDT of B increased
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 10);
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(10)
+        );
     }
 
     #[async_std::test]
@@ -137,7 +284,7 @@ mod tests {
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            100,
+            IdtAmount::new(100),
             PROOF_ID,
         )
         .await
@@ -145,14 +292,26 @@ mod tests {
         vouch(&service, USER_A.to_string(), user_b.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 100);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 10);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(100)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(10)
+        );
         vouch(&service, user_b.to_string(), USER_A.to_string())
             .await
             .unwrap();
         // cyclic vouch does not change user A balance
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 100);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 10);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(100)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(10)
+        );
     }
 
     #[async_std::test]
@@ -163,7 +322,7 @@ mod tests {
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            100,
+            IdtAmount::new(100),
             PROOF_ID,
         )
         .await
@@ -172,26 +331,44 @@ mod tests {
             &service,
             user_b.to_string(),
             MODERATOR.to_string(),
-            200,
+            IdtAmount::new(200),
             PROOF_ID,
         )
         .await
         .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 100);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 200);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(100)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(200)
+        );
         vouch(&service, USER_A.to_string(), user_b.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 100);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(100)
+        );
         // 200 + 0.1 * 100
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 210);
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(210)
+        );
         vouch(&service, user_b.to_string(), USER_A.to_string())
             .await
             .unwrap();
         // 100 + 0.1 * 200
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 120);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(120)
+        );
         // not increased due to cyclic dependency
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 210);
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(210)
+        );
     }
 
     #[async_std::test]
@@ -204,7 +381,7 @@ mod tests {
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            10000,
+            IdtAmount::new(10000),
             PROOF_ID,
         )
         .await
@@ -213,7 +390,7 @@ mod tests {
             &service,
             user_b.to_string(),
             MODERATOR.to_string(),
-            20000,
+            IdtAmount::new(20000),
             PROOF_ID,
         )
         .await
@@ -222,35 +399,62 @@ mod tests {
             &service,
             user_c.to_string(),
             MODERATOR.to_string(),
-            30000,
+            IdtAmount::new(30000),
             PROOF_ID,
         )
         .await
         .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 10000);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 20000);
-        assert_eq!(balance(&service, &user_c.to_string()).await.unwrap(), 30000);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(10000)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(20000)
+        );
+        assert_eq!(
+            balance(&service, &user_c.to_string()).await.unwrap(),
+            IdtAmount::new(30000)
+        );
         vouch(&service, USER_A.to_string(), user_b.to_string())
             .await
             .unwrap();
         vouch(&service, USER_A.to_string(), user_c.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 21000);
-        assert_eq!(balance(&service, &user_c.to_string()).await.unwrap(), 31000);
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(21000)
+        );
+        assert_eq!(
+            balance(&service, &user_c.to_string()).await.unwrap(),
+            IdtAmount::new(31000)
+        );
         vouch(&service, user_b.to_string(), user_d.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &user_d.to_string()).await.unwrap(), 2100);
+        assert_eq!(
+            balance(&service, &user_d.to_string()).await.unwrap(),
+            IdtAmount::new(2100)
+        );
         vouch(&service, user_c.to_string(), user_d.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &user_d.to_string()).await.unwrap(), 5200);
+        assert_eq!(
+            balance(&service, &user_d.to_string()).await.unwrap(),
+            IdtAmount::new(5200)
+        );
         vouch(&service, user_b.to_string(), user_c.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &user_c.to_string()).await.unwrap(), 33100);
-        assert_eq!(balance(&service, &user_d.to_string()).await.unwrap(), 5410);
+        assert_eq!(
+            balance(&service, &user_c.to_string()).await.unwrap(),
+            IdtAmount::new(33100)
+        );
+        assert_eq!(
+            balance(&service, &user_d.to_string()).await.unwrap(),
+            IdtAmount::new(5410)
+        );
     }
 
     #[async_std::test]
@@ -266,7 +470,7 @@ mod tests {
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            1000,
+            IdtAmount::new(1000),
             PROOF_ID,
         )
         .await
@@ -275,7 +479,7 @@ mod tests {
             &service,
             user_b.to_string(),
             MODERATOR.to_string(),
-            2000,
+            IdtAmount::new(2000),
             PROOF_ID,
         )
         .await
@@ -284,7 +488,7 @@ mod tests {
             &service,
             user_c.to_string(),
             MODERATOR.to_string(),
-            3000,
+            IdtAmount::new(3000),
             PROOF_ID,
         )
         .await
@@ -293,7 +497,7 @@ mod tests {
             &service,
             user_d.to_string(),
             MODERATOR.to_string(),
-            4000,
+            IdtAmount::new(4000),
             PROOF_ID,
         )
         .await
@@ -302,7 +506,7 @@ mod tests {
             &service,
             user_e.to_string(),
             MODERATOR.to_string(),
-            5000,
+            IdtAmount::new(5000),
             PROOF_ID,
         )
         .await
@@ -311,7 +515,7 @@ mod tests {
             &service,
             user_f.to_string(),
             MODERATOR.to_string(),
-            6000,
+            IdtAmount::new(6000),
             PROOF_ID,
         )
         .await
@@ -320,49 +524,88 @@ mod tests {
             &service,
             user_g.to_string(),
             MODERATOR.to_string(),
-            7000,
+            IdtAmount::new(7000),
             PROOF_ID,
         )
         .await
         .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 1000);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 2000);
-        assert_eq!(balance(&service, &user_c.to_string()).await.unwrap(), 3000);
-        assert_eq!(balance(&service, &user_d.to_string()).await.unwrap(), 4000);
-        assert_eq!(balance(&service, &user_e.to_string()).await.unwrap(), 5000);
-        assert_eq!(balance(&service, &user_f.to_string()).await.unwrap(), 6000);
-        assert_eq!(balance(&service, &user_g.to_string()).await.unwrap(), 7000);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1000)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(2000)
+        );
+        assert_eq!(
+            balance(&service, &user_c.to_string()).await.unwrap(),
+            IdtAmount::new(3000)
+        );
+        assert_eq!(
+            balance(&service, &user_d.to_string()).await.unwrap(),
+            IdtAmount::new(4000)
+        );
+        assert_eq!(
+            balance(&service, &user_e.to_string()).await.unwrap(),
+            IdtAmount::new(5000)
+        );
+        assert_eq!(
+            balance(&service, &user_f.to_string()).await.unwrap(),
+            IdtAmount::new(6000)
+        );
+        assert_eq!(
+            balance(&service, &user_g.to_string()).await.unwrap(),
+            IdtAmount::new(7000)
+        );
         vouch(&service, user_b.to_string(), USER_A.to_string())
             .await
             .unwrap();
         // 1000 + 0.1 * 2000
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 1200);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1200)
+        );
         vouch(&service, user_c.to_string(), USER_A.to_string())
             .await
             .unwrap();
         // 1200 + 0.1 * 3000
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 1500);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1500)
+        );
         vouch(&service, user_d.to_string(), USER_A.to_string())
             .await
             .unwrap();
         // 1500 + 0.1 * 4000
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 1900);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1900)
+        );
         vouch(&service, user_e.to_string(), USER_A.to_string())
             .await
             .unwrap();
         // 1900 + 0.1 * 5000
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 2400);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(2400)
+        );
         vouch(&service, user_f.to_string(), USER_A.to_string())
             .await
             .unwrap();
         // 2400 + 0.1 * 6000
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 3000);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(3000)
+        );
         vouch(&service, user_g.to_string(), USER_A.to_string())
             .await
             .unwrap();
         // 3000 + 0.1 * 7000 - 0.1 * 2000
         // only 5 top vouchers are considered
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 3500);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(3500)
+        );
     }
 
     #[async_std::test]
@@ -383,17 +626,49 @@ mod tests {
             .unwrap();
 
         let mut balances = HashMap::new();
-        balances.insert(voucher_b.clone(), 5);
-        balances.insert(voucher_c.clone(), 10);
-        balances.insert(voucher_d.clone(), 8);
+        balances.insert(voucher_b.clone(), IdtAmount::new(5));
+        balances.insert(voucher_c.clone(), IdtAmount::new(10));
+        balances.insert(voucher_d.clone(), IdtAmount::new(8));
+
+        let top = top_vouchers(
+            &service,
+            &user_a,
+            &im::HashSet::new(),
+            &balances,
+            TOP_VOUCHERS_SIZE,
+        )
+        .await
+        .unwrap();
+        assert_eq!(top.len(), 3);
+        assert_eq!(top[0], (voucher_c, IdtAmount::new(10)));
+        assert_eq!(top[1], (voucher_d, IdtAmount::new(8)));
+        assert_eq!(top[2], (voucher_b, IdtAmount::new(5)));
+    }
+
+    #[async_std::test]
+    async fn test_voucher_tie_break() {
+        let user_a = USER_A.to_string();
+        let service = IdentityService::default();
+        let mut balances = HashMap::new();
+        for voucher in ["userE", "userC", "userD", "userB"] {
+            vouch(&service, voucher.to_string(), user_a.clone())
+                .await
+                .unwrap();
+            balances.insert(voucher.to_string(), IdtAmount::new(10));
+        }
+        balances.insert("userE".to_string(), IdtAmount::new(20));
 
-        let top = top_vouchers(&service, &user_a, &im::HashSet::new(), &balances)
+        let top = top_vouchers(&service, &user_a, &im::HashSet::new(), &balances, 3)
             .await
             .unwrap();
-        assert_eq!(top.len(), 3);
-        assert_eq!(top[0], (voucher_c, 10));
-        assert_eq!(top[1], (voucher_d, 8));
-        assert_eq!(top[2], (voucher_b, 5));
+        assert_eq!(
+            top,
+            vec![
+                ("userE".to_string(), IdtAmount::new(20)),
+                ("userB".to_string(), IdtAmount::new(10)),
+                ("userC".to_string(), IdtAmount::new(10)),
+            ]
+        );
     }
 
     #[async_std::test]
@@ -405,45 +680,54 @@ mod tests {
             .prove_with_timestamp(
                 USER_A.to_string(),
                 MODERATOR.to_string(),
-                1000,
+                IdtAmount::new(1000),
                 PROOF_ID,
                 ts,
             )
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 1000);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1000)
+        );
 
         service
             .prove_with_timestamp(
                 USER_A.to_string(),
                 MODERATOR.to_string(),
-                1000,
+                IdtAmount::new(1000),
                 PROOF_ID,
                 ts - 86400,
             )
             .await
             .unwrap();
         // decay after 1 day
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 999);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(999)
+        );
 
         service
             .prove_with_timestamp(
                 USER_A.to_string(),
                 MODERATOR.to_string(),
-                1,
+                IdtAmount::new(1),
                 PROOF_ID,
                 ts - 86400 * 10,
             )
             .await
             .unwrap();
         // cannot go lower than 0
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 0);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
 
         service
             .prove_with_timestamp(
                 user_b.to_string(),
                 MODERATOR.to_string(),
-                1000,
+                IdtAmount::new(1000),
                 PROOF_ID,
                 ts,
             )
@@ -455,25 +739,281 @@ mod tests {
             .unwrap();
 
         // only proven balance is affected so far
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 100);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(100)
+        );
 
         service
             .vouch_with_timestamp(user_b.to_string(), USER_A.to_string(), ts - 86400)
             .await
             .unwrap();
         // vouch balance also decays at 1 IDT per day rate
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 99);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(99)
+        );
 
         service
             .prove_with_timestamp(
                 user_b.to_string(),
                 MODERATOR.to_string(),
-                1000,
+                IdtAmount::new(1000),
                 PROOF_ID,
                 ts - 86400,
             )
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 98);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::from_milli(98_900)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_decay_time_travel() {
+        let user_b = "userB";
+        let (service, clock) = service_with_mock_clock();
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        prove(
+            &service,
+            user_b.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, user_b.to_string(), USER_A.to_string())
+            .await
+            .unwrap();
+        // 1000 + 0.1 * 1000
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1100)
+        );
+        clock.advance(86400 * 10);
+        // proof decays by 10, voucher balance decays by 10 and then
+        // 0.1 * 990 - 10 from the vouch decay
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1079)
+        );
+        clock.advance(86400 * 990);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_maturity_bonus() {
+        let user_b = "userB";
+        let (service, clock) = service_with_mock_clock();
+        let service = IdentityService {
+            config: IdentitySection {
+                maturity_bonus: vec![
+                    MaturityStep {
+                        age: 180 * 86400,
+                        ratio: Rational::new(15, 100).unwrap(),
+                    },
+                    MaturityStep {
+                        age: 90 * 86400,
+                        ratio: Rational::new(12, 100).unwrap(),
+                    },
+                ],
+                ..Default::default()
+            },
+            ..service
+        };
+        // genesis balance does not decay, so only the vouch weight and decay change
+        service
+            .set_genesis(
+                HashMap::from([(user_b.to_string(), IdtAmount::new(10000))]),
+                GenesisDriftPolicy::Fail,
+            )
+            .await
+            .unwrap();
+        vouch(&service, user_b.to_string(), USER_A.to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1000)
+        );
+        clock.advance(89 * 86400);
+        // 0.1 * 10000 - 89
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(911)
+        );
+        clock.advance(86400);
+        // 0.12 * 10000 - 90
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1110)
+        );
+        clock.advance(90 * 86400);
+        // 0.15 * 10000 - 180
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(1320)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_rounding() {
+        let user_b = "userB";
+        for (rounding, expected) in [(Rounding::Floor, 1), (Rounding::HalfEven, 2)] {
+            let service = IdentityService {
+                config: IdentitySection {
+                    rounding,
+                    ..Default::default()
+                },
+                ..IdentityService::default()
+            };
+            service
+                .set_genesis(
+                    HashMap::from([(user_b.to_string(), IdtAmount::from_milli(15))]),
+                    GenesisDriftPolicy::Fail,
+                )
+                .await
+                .unwrap();
+            vouch(&service, user_b.to_string(), USER_A.to_string())
+                .await
+                .unwrap();
+            // 0.1 * 0.015
+            assert_eq!(
+                balance(&service, &USER_A.to_string()).await.unwrap(),
+                IdtAmount::from_milli(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn test_vouch_weight() {
+        let config = IdentitySection {
+            maturity_bonus: vec![
+                MaturityStep {
+                    age: 100,
+                    ratio: Rational::new(12, 100).unwrap(),
+                },
+                MaturityStep {
+                    age: 50,
+                    ratio: Rational::new(11, 100).unwrap(),
+                },
+                // invalid steps from the config are ignored
+                serde_json::from_str(r#"{"age": 70, "ratio": {"numerator": 1, "denominator": 0}}"#)
+                    .unwrap(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            vouch_weight(&IdentitySection::default(), 1000).to_float(),
+            0.1
+        );
+        assert_eq!(vouch_weight(&config, 10).to_float(), 0.1);
+        assert_eq!(vouch_weight(&config, 50).to_float(), 0.11);
+        assert_eq!(vouch_weight(&config, 70).to_float(), 0.11);
+        assert_eq!(vouch_weight(&config, 100).to_float(), 0.12);
+    }
+
+    #[async_std::test]
+    async fn test_projection() {
+        let user_b = "userB";
+        let (service, clock) = service_with_mock_clock();
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        prove(
+            &service,
+            user_b.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, user_b.to_string(), USER_A.to_string())
+            .await
+            .unwrap();
+
+        let now = balance_projection(&service, &USER_A.to_string(), 0)
+            .await
+            .unwrap();
+        assert_eq!(now.balance, IdtAmount::new(1100));
+        assert_eq!(now.timestamp, service.now());
+
+        let projection = balance_projection(&service, &USER_A.to_string(), 10)
+            .await
+            .unwrap();
+        assert_eq!(projection.timestamp, service.now() + 86400 * 10);
+        assert_eq!(projection.balance, IdtAmount::new(1079));
+        assert_eq!(projection.breakdown.proven, IdtAmount::new(990));
+        assert_eq!(projection.breakdown.proof_decay, IdtAmount::new(10));
+
+        // projection matches the balance once the time comes
+        clock.advance(86400 * 10);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            projection.balance
+        );
+    }
+
+    #[async_std::test]
+    async fn test_budget() {
+        let service = IdentityService::default();
+        let (user_b, user_c) = ("userB".to_string(), "userC".to_string());
+        for user in [USER_A.to_string(), user_b.clone(), user_c.clone()] {
+            prove(
+                &service,
+                user,
+                MODERATOR.to_string(),
+                IdtAmount::new(1000),
+                PROOF_ID,
+            )
+            .await
+            .unwrap();
+        }
+        vouch(&service, user_b.clone(), USER_A.to_string())
+            .await
+            .unwrap();
+        vouch(&service, user_c, user_b).await.unwrap();
+
+        let user = USER_A.to_string();
+        assert_eq!(
+            vouch_tree_balance_with_budget(&service, &user, TOP_VOUCHERS_SIZE, 3)
+                .await
+                .unwrap(),
+            (IdtAmount::new(1110), false)
+        );
+        // userC is visited last and has no vouchers to skip
+        assert_eq!(
+            vouch_tree_balance_with_budget(&service, &user, TOP_VOUCHERS_SIZE, 2)
+                .await
+                .unwrap(),
+            (IdtAmount::new(1110), false)
+        );
+        // the vouchers of userB are beyond the budget
+        assert_eq!(
+            vouch_tree_balance_with_budget(&service, &user, TOP_VOUCHERS_SIZE, 1)
+                .await
+                .unwrap(),
+            (IdtAmount::new(1100), true)
+        );
     }
 }
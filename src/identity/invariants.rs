@@ -0,0 +1,242 @@
+// property-based checks of the trust math on randomly generated vouch graphs
+
+use async_std::task::block_on;
+use proptest::prelude::*;
+
+use crate::identity::{
+    IdentityService, IdtAmount, UserAddress,
+    idt::{TOP_VOUCHERS_SIZE, VOUCHER_WEIGHT_RATIO, balance},
+    proof::MAX_IDT_BY_PROOF,
+    punish::{MAX_VOUCHEE_PENALTY, PENALTY_VOUCHEE_WEIGHT_RATIO, penalty},
+    tests::{MODERATOR, PROOF_ID, START_TIMESTAMP, service_with_mock_clock},
+    vouch::vouchees,
+};
+
+// tree walk does not memoize branches, so graphs are kept small
+const MAX_USERS: usize = 6;
+const MAX_EDGES: usize = 12;
+const MAX_PENALTY: IdtAmount = IdtAmount::new(MAX_IDT_BY_PROOF.units() * 3);
+const DAY: u64 = 86400;
+
+#[derive(Debug, Clone)]
+struct Graph {
+    proofs: Vec<Option<IdtAmount>>,
+    penalties: Vec<Option<IdtAmount>>,
+    edges: Vec<(usize, usize)>,
+    forgets: Vec<(usize, usize)>,
+}
+
+impl Graph {
+    // forgetting also removes the vouch, and removing an edge can expose another
+    // voucher of a cycle, so penalties are compared against the graph without it
+    fn without_forgotten_edges(&self) -> Graph {
+        Graph {
+            edges: self
+                .edges
+                .iter()
+                .filter(|edge| !self.forgets.contains(edge))
+                .copied()
+                .collect(),
+            ..self.clone()
+        }
+    }
+}
+
+fn user(index: usize) -> UserAddress {
+    format!("user{index}")
+}
+
+fn amount_strategy(max: IdtAmount) -> impl Strategy<Value = IdtAmount> {
+    (0..=max.units()).prop_map(IdtAmount::new)
+}
+
+fn graph_strategy() -> impl Strategy<Value = Graph> {
+    (2..=MAX_USERS).prop_flat_map(|users| {
+        (
+            prop::collection::vec(prop::option::of(amount_strategy(MAX_IDT_BY_PROOF)), users),
+            prop::collection::vec(
+                prop::option::weighted(0.3, amount_strategy(MAX_PENALTY)),
+                users,
+            ),
+            prop::collection::vec((0..users, 0..users), 0..=MAX_EDGES),
+            prop::collection::vec((0..users, 0..users), 0..=2),
+        )
+            .prop_map(|(proofs, penalties, edges, forgets)| Graph {
+                proofs,
+                penalties,
+                edges,
+                forgets,
+            })
+    })
+}
+
+async fn build(graph: &Graph, with_penalties: bool) -> (IdentityService, Vec<UserAddress>) {
+    let (service, _clock) = service_with_mock_clock();
+    let users: Vec<UserAddress> = (0..graph.proofs.len()).map(user).collect();
+    for (i, proof) in graph.proofs.iter().enumerate() {
+        if let Some(amount) = proof {
+            service
+                .prove_with_timestamp(
+                    users[i].clone(),
+                    MODERATOR.to_string(),
+                    *amount,
+                    PROOF_ID,
+                    START_TIMESTAMP,
+                )
+                .await
+                .unwrap();
+        }
+    }
+    for (from, to) in &graph.edges {
+        service
+            .vouch_with_timestamp(users[*from].clone(), users[*to].clone(), START_TIMESTAMP)
+            .await
+            .unwrap();
+    }
+    if !with_penalties {
+        return (service, users);
+    }
+    for (i, penalty) in graph.penalties.iter().enumerate() {
+        if let Some(amount) = penalty {
+            service
+                .punish_with_timestamp(
+                    users[i].clone(),
+                    MODERATOR.to_string(),
+                    *amount,
+                    PROOF_ID,
+                    START_TIMESTAMP,
+                )
+                .await
+                .unwrap();
+        }
+    }
+    for (from, to) in &graph.forgets {
+        service
+            .forget_with_timestamp(users[*from].clone(), users[*to].clone(), START_TIMESTAMP)
+            .await
+            .unwrap();
+    }
+    (service, users)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    // every voucher contributes at most VOUCHER_WEIGHT_RATIO of its balance and only
+    // TOP_VOUCHERS_SIZE of them are counted, so balance B <= P + k * B, i.e.
+    // B <= P / (1 - k) no matter how many cycles the graph has
+    #[test]
+    fn test_cycles_do_not_amplify(graph in graph_strategy()) {
+        block_on(async {
+            let (service, users) = build(&graph, false).await;
+            let max_proof = graph.proofs.iter().flatten().copied().max().unwrap_or_default();
+            let k_numerator = VOUCHER_WEIGHT_RATIO.0 as u64 * TOP_VOUCHERS_SIZE as u64;
+            let k_denominator = VOUCHER_WEIGHT_RATIO.1 as u64;
+            assert!(k_numerator < k_denominator);
+            let bound = IdtAmount::from_milli(max_proof.milli() * k_denominator / (k_denominator - k_numerator));
+            for u in &users {
+                let b = balance(&service, u).await.unwrap();
+                prop_assert!(b <= bound, "balance {} of {} exceeds bound {}", b, u, bound);
+            }
+            Ok(())
+        })?;
+    }
+
+    #[test]
+    fn test_penalties_do_not_increase_balance(graph in graph_strategy()) {
+        block_on(async {
+            let (clean, users) = build(&graph.without_forgotten_edges(), false).await;
+            let (punished, _) = build(&graph, true).await;
+            for u in &users {
+                let clean_balance = balance(&clean, u).await.unwrap();
+                let punished_balance = balance(&punished, u).await.unwrap();
+                prop_assert!(punished_balance <= clean_balance);
+            }
+            Ok(())
+        })?;
+    }
+
+    #[test]
+    fn test_penalty_propagation_bounded(graph in graph_strategy()) {
+        block_on(async {
+            let (service, users) = build(&graph, true).await;
+            for u in &users {
+                let own_penalty = service
+                    .moderator_penalty(u)
+                    .await
+                    .unwrap()
+                    .map(|p| p.amount)
+                    .unwrap_or_default();
+                let forgotten = service.forgotten_users(u).await.unwrap();
+                let mut forget_penalty = IdtAmount::ZERO;
+                for f in &forgotten {
+                    forget_penalty += service
+                        .forgotten_penalty(u, f)
+                        .await
+                        .unwrap()
+                        .map(|p| p.amount)
+                        .unwrap_or_default();
+                }
+                let propagated = penalty(&service, u).await.unwrap() - own_penalty - forget_penalty;
+                let vouchees_count = vouchees(&service, u).await.unwrap().len() as u64;
+                let bound = IdtAmount::from_milli(
+                    vouchees_count * MAX_VOUCHEE_PENALTY.milli()
+                        * PENALTY_VOUCHEE_WEIGHT_RATIO.0 as u64
+                        / PENALTY_VOUCHEE_WEIGHT_RATIO.1 as u64,
+                );
+                prop_assert!(propagated <= bound, "propagated {} exceeds bound {}", propagated, bound);
+            }
+            Ok(())
+        })?;
+    }
+
+    #[test]
+    fn test_decay_monotonic(graph in graph_strategy(), days in prop::collection::vec(1..400u64, 1..4)) {
+        block_on(async {
+            let (service, users) = build(&graph, false).await;
+            let mut previous_balances = vec![];
+            for u in &users {
+                previous_balances.push(balance(&service, u).await.unwrap());
+            }
+            let mut now = START_TIMESTAMP;
+            for d in days {
+                now += d * DAY;
+                let service = IdentityService {
+                    clock: std::sync::Arc::new(crate::identity::clock::MockClock::new(now)),
+                    ..service.clone()
+                };
+                for (i, u) in users.iter().enumerate() {
+                    let b = balance(&service, u).await.unwrap();
+                    prop_assert!(b <= previous_balances[i], "balance of {} grew over time", u);
+                    previous_balances[i] = b;
+                }
+            }
+            Ok(())
+        })?;
+    }
+
+    #[test]
+    fn test_penalty_decay_monotonic(graph in graph_strategy(), days in prop::collection::vec(1..400u64, 1..4)) {
+        block_on(async {
+            let (service, users) = build(&graph, true).await;
+            let mut previous_penalties = vec![];
+            for u in &users {
+                previous_penalties.push(penalty(&service, u).await.unwrap());
+            }
+            let mut now = START_TIMESTAMP;
+            for d in days {
+                now += d * DAY;
+                let service = IdentityService {
+                    clock: std::sync::Arc::new(crate::identity::clock::MockClock::new(now)),
+                    ..service.clone()
+                };
+                for (i, u) in users.iter().enumerate() {
+                    let p = penalty(&service, u).await.unwrap();
+                    prop_assert!(p <= previous_penalties[i], "penalty of {} grew over time", u);
+                    previous_penalties[i] = p;
+                }
+            }
+            Ok(())
+        })?;
+    }
+}
@@ -1,28 +1,63 @@
-use std::{sync::Arc, time::SystemTime};
+use std::sync::Arc;
 
-use crate::identity::{
-    proof::storage::{InMemoryProofStorage, ProofStorage},
-    punish::storage::{InMemoryPenaltyStorage, PenaltyStorage},
-    vouch::storage::{InMemoryVouchStorage, VouchStorage},
-    vouch_external::storage::{ExternalVouchStorage, InMemoryExternalVouchStorage},
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::IdentitySection,
+    identity::{
+        activity::storage::{ActivityStorage, InMemoryActivityStorage},
+        balances::storage::{BalanceStorage, InMemoryBalanceStorage},
+        categories::storage::{CategoryStorage, InMemoryCategoryStorage},
+        clock::{Clock, SystemClock},
+        debits::{Debits, NoDebits},
+        decay::FutureTimestamps,
+        moderators::storage::{InMemoryModeratorStatsStorage, ModeratorStatsStorage},
+        penalty_reasons::storage::{InMemoryPenaltyReasonStorage, PenaltyReasonStorage},
+        proof::storage::{InMemoryProofStorage, ProofStorage},
+        proof_limits::storage::{InMemoryProofLimitStorage, ProofLimitStorage},
+        punish::storage::{InMemoryPenaltyStorage, PenaltyStorage},
+        screening::{NoScreening, Screening},
+        stakes::storage::{InMemoryStakeStorage, StakeStorage},
+        tree_size::TreeSizeStats,
+        vouch::storage::{InMemoryVouchStorage, VouchStorage},
+        vouch_external::storage::{ExternalVouchStorage, InMemoryExternalVouchStorage},
+    },
+    numbers::Idt,
+    scoring::strategy::{ScoringStrategy, VouchTreeStrategy},
 };
 
-mod decay;
+pub mod activity;
+pub mod balances;
+pub mod categories;
+pub mod clock;
+pub mod debits;
+pub mod decay;
 pub mod error;
 pub mod forget;
 pub mod genesis;
 pub mod idt;
+#[cfg(test)]
+mod invariants;
+pub mod moderators;
+pub mod neighborhood;
+pub mod penalty_reasons;
 pub mod proof;
+pub mod proof_limits;
 pub mod punish;
+pub mod screening;
+pub mod stakes;
+pub mod subgraph;
+pub mod tree_size;
 mod tree_walk;
 pub mod vouch;
 pub mod vouch_external;
 
 pub type UserAddress = String;
 pub type ProofId = u64;
-pub type IdtAmount = u64;
+// amounts of IDT, see `Idt`
+pub type IdtAmount = Idt;
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ModeratorProof {
     pub moderator: UserAddress,
     pub amount: IdtAmount,
@@ -31,7 +66,7 @@ pub struct ModeratorProof {
 }
 
 // system can generate own penalties, so proof_id and moderator are not required
-#[derive(Clone, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SystemPenalty {
     pub amount: IdtAmount,
     pub timestamp: u64,
@@ -43,6 +78,21 @@ pub struct IdentityService {
     pub external_vouches: Arc<dyn ExternalVouchStorage>,
     pub proofs: Arc<dyn ProofStorage>,
     pub penalties: Arc<dyn PenaltyStorage>,
+    pub clock: Arc<dyn Clock>,
+    pub config: IdentitySection,
+    pub strategy: Arc<dyn ScoringStrategy>,
+    pub balances: Arc<dyn BalanceStorage>,
+    pub moderator_stats: Arc<dyn ModeratorStatsStorage>,
+    pub tree_sizes: Arc<TreeSizeStats>,
+    // events with timestamps ahead of the server time, see `decay::FutureTimestamps`
+    pub future_timestamps: Arc<FutureTimestamps>,
+    pub categories: Arc<dyn CategoryStorage>,
+    pub activity: Arc<dyn ActivityStorage>,
+    pub proof_limits: Arc<dyn ProofLimitStorage>,
+    pub penalty_reasons: Arc<dyn PenaltyReasonStorage>,
+    pub screening: Arc<dyn Screening>,
+    pub stakes: Arc<dyn StakeStorage>,
+    pub debits: Arc<dyn Debits>,
 }
 
 impl Default for IdentityService {
@@ -52,22 +102,52 @@ impl Default for IdentityService {
             external_vouches: Arc::new(InMemoryExternalVouchStorage::default()),
             proofs: Arc::new(InMemoryProofStorage::default()),
             penalties: Arc::new(InMemoryPenaltyStorage::default()),
+            clock: Arc::new(SystemClock),
+            config: IdentitySection::default(),
+            strategy: Arc::new(VouchTreeStrategy),
+            balances: Arc::new(InMemoryBalanceStorage::default()),
+            moderator_stats: Arc::new(InMemoryModeratorStatsStorage::default()),
+            tree_sizes: Arc::new(TreeSizeStats::default()),
+            future_timestamps: Arc::new(FutureTimestamps::default()),
+            categories: Arc::new(InMemoryCategoryStorage::default()),
+            activity: Arc::new(InMemoryActivityStorage::default()),
+            proof_limits: Arc::new(InMemoryProofLimitStorage::default()),
+            penalty_reasons: Arc::new(InMemoryPenaltyReasonStorage::default()),
+            screening: Arc::new(NoScreening),
+            stakes: Arc::new(InMemoryStakeStorage::default()),
+            debits: Arc::new(NoDebits),
         }
     }
 }
 
+impl IdentityService {
+    pub fn now(&self) -> u64 {
+        self.clock.now()
+    }
+}
+
 pub fn next_timestamp() -> u64 {
-    SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .expect("Should be after the UNIX_EPOCH timestamp")
-        .as_secs()
+    SystemClock.now()
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::identity::ProofId;
+    use std::sync::Arc;
+
+    use crate::identity::{IdentityService, ProofId, clock::MockClock};
 
     pub const MODERATOR: &str = "moderator";
     pub const USER_A: &str = "userA";
     pub const PROOF_ID: ProofId = 1;
+    // arbitrary fixed point in time for tests using mock clock
+    pub const START_TIMESTAMP: u64 = 1_700_000_000;
+
+    pub fn service_with_mock_clock() -> (IdentityService, Arc<MockClock>) {
+        let clock = Arc::new(MockClock::new(START_TIMESTAMP));
+        let service = IdentityService {
+            clock: clock.clone(),
+            ..Default::default()
+        };
+        (service, clock)
+    }
 }
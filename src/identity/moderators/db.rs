@@ -0,0 +1,119 @@
+use async_trait::async_trait;
+use sqlx::{Acquire, Row};
+
+use crate::{
+    identity::{
+        UserAddress,
+        error::Error,
+        moderators::{ModeratorOutcome, ModeratorStats, storage::ModeratorStatsStorage},
+    },
+    pools::{self, MeteredPool},
+};
+
+// name of the connection pool in `GET /metrics`
+const POOL_NAME: &str = "moderator_stats";
+
+pub struct DatabaseModeratorStatsStorage {
+    pool: MeteredPool,
+}
+
+impl DatabaseModeratorStatsStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        let pool = pools::connect(POOL_NAME, url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS moderator_stats (moderator TEXT NOT NULL, outcome TEXT NOT NULL, count INTEGER NOT NULL, PRIMARY KEY(moderator, outcome))",
+        )
+        .execute(&pool)
+        .await?;
+        Ok(Self { pool })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl ModeratorStatsStorage for DatabaseModeratorStatsStorage {
+    async fn record(
+        &self,
+        moderator: &UserAddress,
+        outcome: ModeratorOutcome,
+        count: u64,
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        let updated = sqlx::query(
+            "UPDATE moderator_stats SET count = count + ? WHERE moderator = ? AND outcome = ?",
+        )
+        .bind(count as i64)
+        .bind(moderator)
+        .bind(outcome.as_str())
+        .execute(tx.acquire().await?)
+        .await?;
+        if updated.rows_affected() == 0 {
+            sqlx::query("INSERT INTO moderator_stats (moderator, outcome, count) VALUES (?, ?, ?)")
+                .bind(moderator)
+                .bind(outcome.as_str())
+                .bind(count as i64)
+                .execute(tx.acquire().await?)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn stats(&self, moderator: &UserAddress) -> Result<ModeratorStats, Error> {
+        let rows = sqlx::query("SELECT outcome, count FROM moderator_stats WHERE moderator = ?")
+            .bind(moderator)
+            .fetch_all(&self.pool)
+            .await?;
+        let mut stats = ModeratorStats::default();
+        for r in rows {
+            if let Some(outcome) = ModeratorOutcome::parse(&r.get::<String, _>(0)) {
+                stats.add(outcome, r.get::<i64, _>(1) as u64);
+            }
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseModeratorStatsStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        let moderator = "moderator".to_string();
+        assert_eq!(
+            storage.stats(&moderator).await.unwrap(),
+            ModeratorStats::default()
+        );
+        storage
+            .record(&moderator, ModeratorOutcome::Proof, 3)
+            .await
+            .unwrap();
+        storage
+            .record(&moderator, ModeratorOutcome::Proof, 2)
+            .await
+            .unwrap();
+        storage
+            .record(&moderator, ModeratorOutcome::RevokedProof, 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.stats(&moderator).await.unwrap(),
+            ModeratorStats {
+                proofs: 5,
+                penalties: 0,
+                revoked_proofs: 1,
+            }
+        );
+        assert_eq!(
+            storage.stats(&"other".to_string()).await.unwrap(),
+            ModeratorStats::default()
+        );
+    }
+}
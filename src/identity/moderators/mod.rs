@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    identity::{IdentityService, UserAddress, error::Error},
+    numbers::Rational,
+};
+
+#[cfg(feature = "storage-sql")]
+pub mod db;
+pub mod storage;
+
+// outcomes of moderator actions counted in the moderator stats
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModeratorOutcome {
+    Proof,
+    Penalty,
+    // proof invalidated by an admin, see `revoke_moderator_proofs`
+    RevokedProof,
+}
+
+impl ModeratorOutcome {
+    pub const ALL: [ModeratorOutcome; 3] = [
+        ModeratorOutcome::Proof,
+        ModeratorOutcome::Penalty,
+        ModeratorOutcome::RevokedProof,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModeratorOutcome::Proof => "proof",
+            ModeratorOutcome::Penalty => "penalty",
+            ModeratorOutcome::RevokedProof => "revoked_proof",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|o| o.as_str() == value)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModeratorStats {
+    pub proofs: u64,
+    pub penalties: u64,
+    pub revoked_proofs: u64,
+}
+
+impl ModeratorStats {
+    pub fn add(&mut self, outcome: ModeratorOutcome, count: u64) {
+        let counter = match outcome {
+            ModeratorOutcome::Proof => &mut self.proofs,
+            ModeratorOutcome::Penalty => &mut self.penalties,
+            ModeratorOutcome::RevokedProof => &mut self.revoked_proofs,
+        };
+        *counter = counter.saturating_add(count);
+    }
+
+    // share of the issued proofs that were not revoked, moderators without proofs
+    // have full reputation
+    pub fn reputation(&self) -> Rational {
+        if self.proofs == 0 {
+            return Rational::default();
+        }
+        let kept = self.proofs.saturating_sub(self.revoked_proofs);
+        Rational::fit(kept, self.proofs).expect("denominator is not zero")
+    }
+}
+
+impl IdentityService {
+    pub async fn record_moderator_outcome(
+        &self,
+        moderator: &UserAddress,
+        outcome: ModeratorOutcome,
+        count: u64,
+    ) -> Result<(), Error> {
+        if count == 0 {
+            return Ok(());
+        }
+        self.moderator_stats.record(moderator, outcome, count).await
+    }
+
+    pub async fn moderator_stats(&self, moderator: &UserAddress) -> Result<ModeratorStats, Error> {
+        self.moderator_stats.stats(moderator).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reputation() {
+        let mut stats = ModeratorStats::default();
+        assert_eq!(stats.reputation().to_float(), 1.0);
+        stats.add(ModeratorOutcome::Proof, 4);
+        stats.add(ModeratorOutcome::RevokedProof, 1);
+        assert_eq!(stats.reputation().to_float(), 0.75);
+        stats.add(ModeratorOutcome::RevokedProof, 10);
+        assert_eq!(stats.reputation().to_float(), 0.0);
+
+        let stats = ModeratorStats {
+            proofs: u64::MAX,
+            penalties: 0,
+            revoked_proofs: u64::MAX / 2,
+        };
+        assert!((stats.reputation().to_float() - 0.5).abs() < 1e-6);
+        for outcome in ModeratorOutcome::ALL {
+            assert_eq!(ModeratorOutcome::parse(outcome.as_str()), Some(outcome));
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::identity::{
+    UserAddress,
+    error::Error,
+    moderators::{ModeratorOutcome, ModeratorStats},
+};
+
+#[async_trait]
+pub trait ModeratorStatsStorage: Send + Sync {
+    // adds `count` to the outcome counter of the moderator
+    async fn record(
+        &self,
+        moderator: &UserAddress,
+        outcome: ModeratorOutcome,
+        count: u64,
+    ) -> Result<(), Error>;
+    async fn stats(&self, moderator: &UserAddress) -> Result<ModeratorStats, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryModeratorStatsStorage {
+    data: RwLock<HashMap<UserAddress, ModeratorStats>>,
+}
+
+#[async_trait]
+impl ModeratorStatsStorage for InMemoryModeratorStatsStorage {
+    async fn record(
+        &self,
+        moderator: &UserAddress,
+        outcome: ModeratorOutcome,
+        count: u64,
+    ) -> Result<(), Error> {
+        let mut data = self.data.write().await;
+        data.entry(moderator.clone())
+            .or_default()
+            .add(outcome, count);
+        Ok(())
+    }
+
+    async fn stats(&self, moderator: &UserAddress) -> Result<ModeratorStats, Error> {
+        Ok(self
+            .data
+            .read()
+            .await
+            .get(moderator)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryModeratorStatsStorage::default();
+        let moderator = "moderator".to_string();
+        assert_eq!(
+            storage.stats(&moderator).await.unwrap(),
+            ModeratorStats::default()
+        );
+        storage
+            .record(&moderator, ModeratorOutcome::Proof, 3)
+            .await
+            .unwrap();
+        storage
+            .record(&moderator, ModeratorOutcome::Penalty, 1)
+            .await
+            .unwrap();
+        storage
+            .record(&moderator, ModeratorOutcome::RevokedProof, 2)
+            .await
+            .unwrap();
+        storage
+            .record(&moderator, ModeratorOutcome::Proof, 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.stats(&moderator).await.unwrap(),
+            ModeratorStats {
+                proofs: 4,
+                penalties: 1,
+                revoked_proofs: 2,
+            }
+        );
+    }
+}
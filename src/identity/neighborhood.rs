@@ -0,0 +1,182 @@
+// Vouch graph around a user for visualizations. Nodes and links are shaped as the `nodes` and
+// `links` arrays consumed by D3 force layouts and vis.js networks.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::Serialize;
+
+use crate::identity::{IdentityService, IdtAmount, UserAddress, error::Error, idt::balance};
+
+// users returned by a single request, the search stops once reached
+pub const MAX_NEIGHBORHOOD_NODES: usize = 500;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GraphNode {
+    pub id: UserAddress,
+    // vouches between the user and the root
+    pub depth: usize,
+    pub idt: IdtAmount,
+    pub last_active: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GraphLink {
+    // voucher
+    pub source: UserAddress,
+    // vouchee
+    pub target: UserAddress,
+    pub timestamp: u64,
+    // seconds since the vouch
+    pub age: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Neighborhood {
+    pub nodes: Vec<GraphNode>,
+    pub links: Vec<GraphLink>,
+    // the search stopped at `MAX_NEIGHBORHOOD_NODES` before reaching `depth`
+    pub truncated: bool,
+}
+
+// users within `depth` vouches of the user in either direction and every vouch between them.
+// Users are visited once, so cycles in the vouch graph end the search.
+pub async fn neighborhood(
+    service: &IdentityService,
+    user: &UserAddress,
+    depth: usize,
+) -> Result<Neighborhood, Error> {
+    let mut depths = BTreeMap::from([(user.clone(), 0)]);
+    let mut queue = VecDeque::from([(user.clone(), 0)]);
+    let mut truncated = false;
+    'search: while let Some((current, distance)) = queue.pop_front() {
+        if distance == depth {
+            continue;
+        }
+        let mut next: Vec<_> = service
+            .vouchers_with_time(&current)
+            .await?
+            .into_keys()
+            .chain(service.vouchees_with_time(&current).await?.into_keys())
+            .collect();
+        next.sort();
+        for other in next {
+            if depths.contains_key(&other) {
+                continue;
+            }
+            if depths.len() == MAX_NEIGHBORHOOD_NODES {
+                truncated = true;
+                break 'search;
+            }
+            depths.insert(other.clone(), distance + 1);
+            queue.push_back((other, distance + 1));
+        }
+    }
+
+    let now = service.now();
+    let mut result = Neighborhood {
+        truncated,
+        ..Default::default()
+    };
+    for (id, depth) in &depths {
+        for (vouchee, timestamp) in service.vouchees_with_time(id).await? {
+            if depths.contains_key(&vouchee) {
+                result.links.push(GraphLink {
+                    source: id.clone(),
+                    target: vouchee,
+                    timestamp,
+                    age: now.saturating_sub(timestamp),
+                });
+            }
+        }
+        result.nodes.push(GraphNode {
+            id: id.clone(),
+            depth: *depth,
+            idt: balance(service, id).await?,
+            last_active: service.last_active(id).await?,
+        });
+    }
+    result
+        .nodes
+        .sort_by(|a, b| (a.depth, &a.id).cmp(&(b.depth, &b.id)));
+    result
+        .links
+        .sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{
+        proof::prove,
+        tests::{MODERATOR, PROOF_ID, USER_A},
+        vouch::vouch,
+    };
+
+    #[async_std::test]
+    async fn test_depth() {
+        let service = IdentityService::default();
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, USER_A.to_string(), "userB".to_string())
+            .await
+            .unwrap();
+        vouch(&service, "userB".to_string(), "userC".to_string())
+            .await
+            .unwrap();
+
+        let graph = neighborhood(&service, &"userB".to_string(), 1)
+            .await
+            .unwrap();
+        let ids: Vec<_> = graph
+            .nodes
+            .iter()
+            .map(|n| (n.id.as_str(), n.depth))
+            .collect();
+        assert_eq!(ids, vec![("userB", 0), (USER_A, 1), ("userC", 1)]);
+        assert_eq!(graph.nodes[1].idt, IdtAmount::new(100));
+        assert_eq!(graph.links.len(), 2);
+        assert!(!graph.truncated);
+
+        let graph = neighborhood(&service, &USER_A.to_string(), 1)
+            .await
+            .unwrap();
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.links.len(), 1);
+        assert_eq!(graph.links[0].source, USER_A);
+        assert_eq!(graph.links[0].target, "userB");
+
+        let graph = neighborhood(&service, &USER_A.to_string(), 0)
+            .await
+            .unwrap();
+        assert_eq!(graph.nodes.len(), 1);
+        assert!(graph.links.is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_cycle() {
+        let service = IdentityService::default();
+        vouch(&service, USER_A.to_string(), "userB".to_string())
+            .await
+            .unwrap();
+        vouch(&service, "userB".to_string(), "userC".to_string())
+            .await
+            .unwrap();
+        vouch(&service, "userC".to_string(), USER_A.to_string())
+            .await
+            .unwrap();
+
+        let graph = neighborhood(&service, &USER_A.to_string(), 10)
+            .await
+            .unwrap();
+        assert_eq!(graph.nodes.len(), 3);
+        assert_eq!(graph.links.len(), 3);
+    }
+}
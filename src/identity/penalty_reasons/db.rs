@@ -0,0 +1,89 @@
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::{UserAddress, error::Error, penalty_reasons::storage::PenaltyReasonStorage},
+    pools::{self, MeteredPool},
+};
+
+pub struct DatabasePenaltyReasonStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabasePenaltyReasonStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("penalty_reasons", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS penalty_reasons (user TEXT PRIMARY KEY, reason TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "penalty_reasons", "user").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl PenaltyReasonStorage for DatabasePenaltyReasonStorage {
+    async fn set_reason(&self, user: &UserAddress, reason: Option<String>) -> Result<(), Error> {
+        match reason {
+            Some(reason) => {
+                sqlx::query("REPLACE INTO penalty_reasons (user, reason) VALUES (?, ?)")
+                    .bind(self.cipher.encode(user))
+                    .bind(reason)
+                    .execute(&self.pool)
+                    .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM penalty_reasons WHERE user = ?")
+                    .bind(self.cipher.encode(user))
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn reason(&self, user: &UserAddress) -> Result<Option<String>, Error> {
+        let row = sqlx::query("SELECT reason FROM penalty_reasons WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| row.get::<String, _>(0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabasePenaltyReasonStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        let user = "user".to_string();
+        assert_eq!(storage.reason(&user).await.unwrap(), None);
+        storage
+            .set_reason(&user, Some("spam".into()))
+            .await
+            .unwrap();
+        storage
+            .set_reason(&user, Some("fraud".into()))
+            .await
+            .unwrap();
+        assert_eq!(storage.reason(&user).await.unwrap(), Some("fraud".into()));
+        storage.set_reason(&user, None).await.unwrap();
+        assert_eq!(storage.reason(&user).await.unwrap(), None);
+    }
+}
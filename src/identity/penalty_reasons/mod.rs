@@ -0,0 +1,195 @@
+// Reason codes of moderator penalties (e.g. "spam", "fraud", "impersonation"). The policy of
+// a reason, configured in `identity.penalty_reasons`, scales the penalty and sets how fast
+// it decays, so e.g. fraud can weigh more and last longer than spam. Penalties without a
+// reason keep the default weight and decay of 1 IDT per day.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    identity::{IdentityService, IdtAmount, UserAddress, error::Error},
+    numbers::Rational,
+};
+
+#[cfg(feature = "storage-sql")]
+pub mod db;
+pub mod storage;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PenaltyReasonPolicy {
+    // applied to the penalty amount before decay
+    pub multiplier: Rational,
+    // IDT decayed per day
+    pub decay_per_day: IdtAmount,
+}
+
+impl Default for PenaltyReasonPolicy {
+    fn default() -> Self {
+        Self {
+            multiplier: Rational::default(),
+            decay_per_day: IdtAmount::new(1),
+        }
+    }
+}
+
+impl PenaltyReasonPolicy {
+    pub fn scale(&self, amount: IdtAmount) -> IdtAmount {
+        // zero denominator would panic on multiplication
+        if self.multiplier.denominator() == 0 {
+            return amount;
+        }
+        self.multiplier.mul(amount)
+    }
+}
+
+impl IdentityService {
+    // reasons must be configured in `identity.penalty_reasons`
+    pub fn check_penalty_reason(&self, reason: Option<&String>) -> Result<(), Error> {
+        match reason {
+            Some(reason) if !self.config.penalty_reasons.contains_key(reason) => {
+                Err(Error::UnknownPenaltyReason(reason.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // None removes the reason of the user's penalty
+    pub async fn set_penalty_reason(
+        &self,
+        user: &UserAddress,
+        reason: Option<String>,
+    ) -> Result<(), Error> {
+        self.check_penalty_reason(reason.as_ref())?;
+        self.penalty_reasons.set_reason(user, reason).await
+    }
+
+    pub async fn penalty_reason(&self, user: &UserAddress) -> Result<Option<String>, Error> {
+        self.penalty_reasons.reason(user).await
+    }
+
+    // policy of the reason of the user's moderator penalty, reasons removed from the config
+    // are ignored
+    pub async fn penalty_reason_policy(
+        &self,
+        user: &UserAddress,
+    ) -> Result<Option<PenaltyReasonPolicy>, Error> {
+        // no lookups while reasons are not configured
+        if self.config.penalty_reasons.is_empty() {
+            return Ok(None);
+        }
+        Ok(self
+            .penalty_reason(user)
+            .await?
+            .and_then(|reason| self.config.penalty_reasons.get(&reason).cloned()))
+    }
+
+    // moderator penalty of the user scaled by the policy of its reason, before decay
+    pub async fn weighted_moderator_penalty(
+        &self,
+        user: &UserAddress,
+    ) -> Result<Option<IdtAmount>, Error> {
+        let Some(penalty) = self.moderator_penalty(user).await? else {
+            return Ok(None);
+        };
+        Ok(Some(match self.penalty_reason_policy(user).await? {
+            Some(policy) => policy.scale(penalty.amount),
+            None => penalty.amount,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        config::IdentitySection,
+        identity::{
+            decay::DAY,
+            punish::{penalty, punish_with_reason},
+            tests::{MODERATOR, PROOF_ID, USER_A, service_with_mock_clock},
+        },
+    };
+
+    #[async_std::test]
+    async fn test_penalty_reasons() {
+        let (mut service, clock) = service_with_mock_clock();
+        service.config = IdentitySection {
+            penalty_reasons: HashMap::from([
+                (
+                    "fraud".to_string(),
+                    PenaltyReasonPolicy {
+                        multiplier: Rational::new(2, 1).unwrap(),
+                        decay_per_day: IdtAmount::new(1),
+                    },
+                ),
+                (
+                    "spam".to_string(),
+                    PenaltyReasonPolicy {
+                        multiplier: Rational::new(1, 2).unwrap(),
+                        decay_per_day: IdtAmount::new(10),
+                    },
+                ),
+            ]),
+            ..Default::default()
+        };
+        let user = USER_A.to_string();
+
+        assert!(matches!(
+            punish_with_reason(
+                &service,
+                user.clone(),
+                MODERATOR.to_string(),
+                IdtAmount::new(100),
+                PROOF_ID,
+                Some("unknown".to_string())
+            )
+            .await,
+            Err(Error::UnknownPenaltyReason(_))
+        ));
+        assert!(service.moderator_penalty(&user).await.unwrap().is_none());
+
+        punish_with_reason(
+            &service,
+            user.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+            Some("fraud".to_string()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(penalty(&service, &user).await.unwrap(), IdtAmount::new(200));
+        clock.advance(5 * DAY);
+        assert_eq!(penalty(&service, &user).await.unwrap(), IdtAmount::new(195));
+
+        // the new penalty replaces the reason
+        punish_with_reason(
+            &service,
+            user.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+            Some("spam".to_string()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(penalty(&service, &user).await.unwrap(), IdtAmount::new(50));
+        clock.advance(2 * DAY);
+        assert_eq!(penalty(&service, &user).await.unwrap(), IdtAmount::new(30));
+
+        punish_with_reason(
+            &service,
+            user.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(service.penalty_reason(&user).await.unwrap(), None);
+        assert_eq!(penalty(&service, &user).await.unwrap(), IdtAmount::new(100));
+    }
+}
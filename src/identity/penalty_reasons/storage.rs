@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::identity::{UserAddress, error::Error};
+
+#[async_trait]
+pub trait PenaltyReasonStorage: Send + Sync {
+    // None removes the reason of the user's penalty
+    async fn set_reason(&self, user: &UserAddress, reason: Option<String>) -> Result<(), Error>;
+    async fn reason(&self, user: &UserAddress) -> Result<Option<String>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryPenaltyReasonStorage {
+    reasons: RwLock<HashMap<UserAddress, String>>,
+}
+
+#[async_trait]
+impl PenaltyReasonStorage for InMemoryPenaltyReasonStorage {
+    async fn set_reason(&self, user: &UserAddress, reason: Option<String>) -> Result<(), Error> {
+        let mut reasons = self.reasons.write().await;
+        match reason {
+            Some(reason) => reasons.insert(user.clone(), reason),
+            None => reasons.remove(user),
+        };
+        Ok(())
+    }
+
+    async fn reason(&self, user: &UserAddress) -> Result<Option<String>, Error> {
+        Ok(self.reasons.read().await.get(user).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryPenaltyReasonStorage::default();
+        let user = "user".to_string();
+        assert_eq!(storage.reason(&user).await.unwrap(), None);
+        storage
+            .set_reason(&user, Some("spam".into()))
+            .await
+            .unwrap();
+        assert_eq!(storage.reason(&user).await.unwrap(), Some("spam".into()));
+        storage.set_reason(&user, None).await.unwrap();
+        assert_eq!(storage.reason(&user).await.unwrap(), None);
+    }
+}
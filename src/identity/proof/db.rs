@@ -1,23 +1,32 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use async_trait::async_trait;
-use sqlx::{Acquire, AnyPool, Row, any::AnyPoolOptions};
+use sqlx::{Acquire, Row};
 
-use crate::identity::{
-    IdtAmount, ModeratorProof, ProofId, UserAddress, error::Error, proof::storage::ProofStorage,
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::{
+        IdtAmount, ModeratorProof, ProofId, UserAddress, error::Error, proof::storage::ProofStorage,
+    },
+    numbers::rescale_column,
+    pools::{self, MeteredPool},
 };
 
+// name of the connection pool in `GET /metrics`
+const POOL_NAME: &str = "proofs";
+
 pub struct DatabaseProofStorage {
-    pool: AnyPool,
+    pool: MeteredPool,
+    cipher: FieldCipher,
 }
 
 impl DatabaseProofStorage {
     pub async fn new(url: &str) -> Result<Self, Error> {
-        sqlx::any::install_default_drivers();
-        let pool = AnyPoolOptions::new()
-            .max_connections(1)
-            .connect(url)
-            .await?;
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect(POOL_NAME, url).await?;
         sqlx::query(
             "CREATE TABLE IF NOT EXISTS proofs (user TEXT PRIMARY KEY, moderator TEXT NOT NULL, amount INTEGER NOT NULL, proof_id INTEGER NOT NULL, timestamp INTEGER NOT NULL)"
         )
@@ -28,7 +37,22 @@ impl DatabaseProofStorage {
         )
         .execute(&pool)
         .await?;
-        Ok(Self { pool })
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS revoked_proofs (user TEXT PRIMARY KEY, moderator TEXT NOT NULL, amount INTEGER NOT NULL, proof_id INTEGER NOT NULL, timestamp INTEGER NOT NULL)"
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "proofs", "user").await?;
+        rotate_column(&pool, &cipher, "revoked_proofs", "user").await?;
+        rotate_column(&pool, &cipher, "genesis", "user").await?;
+        rescale_column(&pool, "proofs", "amount").await?;
+        rescale_column(&pool, "revoked_proofs", "amount").await?;
+        rescale_column(&pool, "genesis", "balance").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
     }
 }
 
@@ -41,8 +65,8 @@ impl ProofStorage for DatabaseProofStorage {
             .await?;
         for (user, bal) in users {
             sqlx::query("INSERT INTO genesis (user, balance) VALUES (?, ?)")
-                .bind(user)
-                .bind(bal as i64)
+                .bind(self.cipher.encode(&user))
+                .bind(bal.milli() as i64)
                 .execute(tx.acquire().await?)
                 .await?;
         }
@@ -52,33 +76,112 @@ impl ProofStorage for DatabaseProofStorage {
 
     async fn genesis_balance(&self, user: &UserAddress) -> Result<Option<IdtAmount>, Error> {
         let row = sqlx::query("SELECT balance FROM genesis WHERE user = ?")
-            .bind(user)
+            .bind(self.cipher.encode(user))
             .fetch_optional(&self.pool)
             .await?;
-        Ok(row.map(|r| r.get::<i64, _>(0) as IdtAmount))
+        Ok(row.map(|r| IdtAmount::from_milli(r.get::<i64, _>(0) as u64)))
     }
 
-    async fn set_proof(&self, user: UserAddress, proof: ModeratorProof) -> Result<(), Error> {
-        sqlx::query("REPLACE INTO proofs (user, moderator, amount, proof_id, timestamp) VALUES (?, ?, ?, ?, ?)")
-            .bind(&user)
-            .bind(&proof.moderator)
-            .bind(proof.amount as i64)
-            .bind(proof.proof_id as i64)
-            .bind(proof.timestamp as i64)
-            .execute(&self.pool)
+    async fn genesis(&self) -> Result<HashMap<UserAddress, IdtAmount>, Error> {
+        let rows = sqlx::query("SELECT user, balance FROM genesis")
+            .fetch_all(&self.pool)
             .await?;
+        let mut genesis = HashMap::new();
+        for r in rows {
+            genesis.insert(
+                self.cipher.decode(&r.get::<String, _>(0))?,
+                IdtAmount::from_milli(r.get::<i64, _>(1) as u64),
+            );
+        }
+        Ok(genesis)
+    }
+
+    async fn set_proof(&self, user: UserAddress, proof: ModeratorProof) -> Result<(), Error> {
+        self.set_proofs(vec![(user, proof)]).await
+    }
+
+    // a single transaction, so the whole batch is committed at once
+    async fn set_proofs(&self, proofs: Vec<(UserAddress, ModeratorProof)>) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        for (user, proof) in proofs {
+            sqlx::query("REPLACE INTO proofs (user, moderator, amount, proof_id, timestamp) VALUES (?, ?, ?, ?, ?)")
+                .bind(self.cipher.encode(&user))
+                .bind(&proof.moderator)
+                .bind(proof.amount.milli() as i64)
+                .bind(proof.proof_id as i64)
+                .bind(proof.timestamp as i64)
+                .execute(tx.acquire().await?)
+                .await?;
+        }
+        tx.commit().await?;
         Ok(())
     }
 
     async fn proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
         let row =
             sqlx::query("SELECT moderator, amount, proof_id, timestamp FROM proofs WHERE user = ?")
-                .bind(user)
+                .bind(self.cipher.encode(user))
                 .fetch_optional(&self.pool)
                 .await?;
         Ok(row.map(|r| ModeratorProof {
             moderator: r.get::<String, _>(0),
-            amount: r.get::<i64, _>(1) as IdtAmount,
+            amount: IdtAmount::from_milli(r.get::<i64, _>(1) as u64),
+            proof_id: r.get::<i64, _>(2) as ProofId,
+            timestamp: r.get::<i64, _>(3) as u64,
+        }))
+    }
+
+    async fn remove_proof(&self, user: &UserAddress) -> Result<(), Error> {
+        sqlx::query("DELETE FROM proofs WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn proven_users(&self) -> Result<HashSet<UserAddress>, Error> {
+        let rows = sqlx::query("SELECT user FROM proofs UNION SELECT user FROM genesis")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut users = HashSet::new();
+        for r in rows {
+            users.insert(self.cipher.decode(&r.get::<String, _>(0))?);
+        }
+        Ok(users)
+    }
+
+    async fn revoke_proofs(&self, moderator: &UserAddress) -> Result<Vec<UserAddress>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query("SELECT user FROM proofs WHERE moderator = ?")
+            .bind(moderator)
+            .fetch_all(tx.acquire().await?)
+            .await?;
+        sqlx::query("REPLACE INTO revoked_proofs (user, moderator, amount, proof_id, timestamp) SELECT user, moderator, amount, proof_id, timestamp FROM proofs WHERE moderator = ?")
+            .bind(moderator)
+            .execute(tx.acquire().await?)
+            .await?;
+        sqlx::query("DELETE FROM proofs WHERE moderator = ?")
+            .bind(moderator)
+            .execute(tx.acquire().await?)
+            .await?;
+        tx.commit().await?;
+        let mut users = vec![];
+        for r in rows {
+            users.push(self.cipher.decode(&r.get::<String, _>(0))?);
+        }
+        Ok(users)
+    }
+
+    async fn revoked_proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        let row = sqlx::query(
+            "SELECT moderator, amount, proof_id, timestamp FROM revoked_proofs WHERE user = ?",
+        )
+        .bind(self.cipher.encode(user))
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|r| ModeratorProof {
+            moderator: r.get::<String, _>(0),
+            amount: IdtAmount::from_milli(r.get::<i64, _>(1) as u64),
             proof_id: r.get::<i64, _>(2) as ProofId,
             timestamp: r.get::<i64, _>(3) as u64,
         }))
@@ -96,9 +199,13 @@ mod tests {
         let moderator = "moderator".to_string();
 
         let mut genesis = HashMap::<UserAddress, IdtAmount>::new();
-        genesis.insert(user.clone(), 100);
-        storage.set_genesis(genesis).await.unwrap();
-        assert_eq!(storage.genesis_balance(&user).await.unwrap().unwrap(), 100);
+        genesis.insert(user.clone(), IdtAmount::new(100));
+        storage.set_genesis(genesis.clone()).await.unwrap();
+        assert_eq!(
+            storage.genesis_balance(&user).await.unwrap().unwrap(),
+            IdtAmount::new(100)
+        );
+        assert_eq!(storage.genesis().await.unwrap(), genesis);
         assert!(
             storage
                 .genesis_balance(&"none".to_string())
@@ -109,7 +216,7 @@ mod tests {
 
         let proof1 = ModeratorProof {
             moderator: moderator.clone(),
-            amount: 10,
+            amount: IdtAmount::new(10),
             proof_id: 1,
             timestamp: 1,
         };
@@ -125,7 +232,7 @@ mod tests {
 
         let proof2 = ModeratorProof {
             moderator: "mod2".to_string(),
-            amount: 20,
+            amount: IdtAmount::new(20),
             proof_id: 2,
             timestamp: 2,
         };
@@ -141,4 +248,135 @@ mod tests {
 
         assert!(storage.proof(&"none".to_string()).await.unwrap().is_none());
     }
+
+    #[async_std::test]
+    async fn test_proven_users() {
+        let storage = DatabaseProofStorage::new("sqlite::memory:").await.unwrap();
+        storage
+            .set_genesis(HashMap::from([
+                ("a".to_string(), IdtAmount::new(10)),
+                ("b".to_string(), IdtAmount::new(10)),
+            ]))
+            .await
+            .unwrap();
+        let proof = ModeratorProof {
+            moderator: "moderator".to_string(),
+            amount: IdtAmount::new(10),
+            proof_id: 1,
+            timestamp: 1,
+        };
+        storage
+            .set_proof("b".to_string(), proof.clone())
+            .await
+            .unwrap();
+        storage
+            .set_proofs(vec![
+                ("c".to_string(), proof.clone()),
+                ("d".to_string(), proof),
+            ])
+            .await
+            .unwrap();
+        let users = storage.proven_users().await.unwrap();
+        assert_eq!(
+            users,
+            HashSet::from([
+                "a".to_string(),
+                "b".to_string(),
+                "c".to_string(),
+                "d".to_string()
+            ])
+        );
+    }
+
+    #[async_std::test]
+    async fn test_revoke_proofs() {
+        let storage = DatabaseProofStorage::new("sqlite::memory:").await.unwrap();
+        let proof = ModeratorProof {
+            moderator: "moderator".to_string(),
+            amount: IdtAmount::new(10),
+            proof_id: 1,
+            timestamp: 1,
+        };
+        storage
+            .set_proof("a".to_string(), proof.clone())
+            .await
+            .unwrap();
+        storage
+            .set_proof(
+                "b".to_string(),
+                ModeratorProof {
+                    moderator: "other".to_string(),
+                    ..proof.clone()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            storage
+                .revoke_proofs(&"moderator".to_string())
+                .await
+                .unwrap(),
+            vec!["a".to_string()]
+        );
+        assert!(storage.proof(&"a".to_string()).await.unwrap().is_none());
+        assert!(storage.proof(&"b".to_string()).await.unwrap().is_some());
+        assert_eq!(
+            storage.revoked_proof(&"a".to_string()).await.unwrap(),
+            Some(proof)
+        );
+        assert!(
+            storage
+                .revoked_proof(&"b".to_string())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_rescale_amounts() {
+        let dir = tempdir::TempDir::new("proofs").unwrap();
+        let url = format!(
+            "sqlite://{}?mode=rwc",
+            dir.path().join("proofs.db").display()
+        );
+        // stored in whole IDT before milli-IDT precision
+        let pool = sqlx::any::AnyPoolOptions::new()
+            .connect(&url)
+            .await
+            .unwrap();
+        sqlx::query(
+            "CREATE TABLE proofs (user TEXT PRIMARY KEY, moderator TEXT NOT NULL, amount INTEGER NOT NULL, proof_id INTEGER NOT NULL, timestamp INTEGER NOT NULL)"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO proofs VALUES ('user', 'moderator', 95, 1, 2)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let user = "user".to_string();
+        let storage = DatabaseProofStorage::new(&url).await.unwrap();
+        assert_eq!(
+            storage.proof(&user).await.unwrap().unwrap().amount,
+            IdtAmount::new(95)
+        );
+        let proof = ModeratorProof {
+            moderator: "moderator".to_string(),
+            amount: IdtAmount::from_milli(95_500),
+            proof_id: 1,
+            timestamp: 2,
+        };
+        storage
+            .set_proof(user.clone(), proof.clone())
+            .await
+            .unwrap();
+        storage.pool.close().await;
+
+        // rescaled only once
+        let storage = DatabaseProofStorage::new(&url).await.unwrap();
+        assert_eq!(storage.proof(&user).await.unwrap(), Some(proof));
+    }
 }
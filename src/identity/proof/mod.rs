@@ -1,13 +1,46 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
 use crate::identity::{
-    IdentityService, IdtAmount, ModeratorProof, ProofId, UserAddress, error::Error, next_timestamp,
+    IdentityService, IdtAmount, ModeratorProof, ProofId, UserAddress, error::Error,
+    moderators::ModeratorOutcome,
 };
 
+#[cfg(feature = "storage-sql")]
 pub mod db;
 pub mod storage;
 
-pub const MAX_IDT_BY_PROOF: IdtAmount = 50000;
+pub const MAX_IDT_BY_PROOF: IdtAmount = IdtAmount::new(50000);
+
+// proof of a single user in a batch, see `IdentityService::prove_batch_with_timestamp`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofBatchEntry {
+    pub user: UserAddress,
+    pub amount: IdtAmount,
+    pub proof_id: ProofId,
+}
 
 impl IdentityService {
+    // errors of a proof that the moderator cannot issue
+    async fn check_proof(
+        &self,
+        user: &UserAddress,
+        moderator: &UserAddress,
+        balance: IdtAmount,
+    ) -> Result<(), Error> {
+        if balance > MAX_IDT_BY_PROOF {
+            return Err(Error::MaxBalanceExceeded);
+        }
+        self.screen(user).await?;
+        if let Some(limit) = self.proof_limit(moderator).await? {
+            if balance > limit {
+                return Err(Error::ModeratorLimitExceeded(limit));
+            }
+        }
+        Ok(())
+    }
+
     pub async fn prove_with_timestamp(
         &self,
         user: UserAddress,
@@ -16,9 +49,23 @@ impl IdentityService {
         proof_id: ProofId,
         timestamp: u64,
     ) -> Result<(), Error> {
-        if balance > MAX_IDT_BY_PROOF {
-            return Err(Error::MaxBalanceExceeded);
-        }
+        self.prove_system_with_timestamp(user, moderator.clone(), balance, proof_id, timestamp)
+            .await?;
+        self.record_moderator_outcome(&moderator, ModeratorOutcome::Proof, 1)
+            .await
+    }
+
+    // proof issued by the server instead of a moderator, e.g. for a proof-of-personhood
+    // registry. It is not counted in the moderator statistics.
+    pub async fn prove_system_with_timestamp(
+        &self,
+        user: UserAddress,
+        moderator: UserAddress,
+        balance: IdtAmount,
+        proof_id: ProofId,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        self.check_proof(&user, &moderator, balance).await?;
         let event = ModeratorProof {
             moderator,
             amount: balance,
@@ -28,10 +75,120 @@ impl IdentityService {
         self.proofs.set_proof(user, event).await
     }
 
+    // proves every user of the batch or none of them. All entries are checked before any
+    // proof is stored, `ProofBatchRejected` lists the index and the error of each rejected
+    // entry. Storages with transactions store the checked proofs at once.
+    pub async fn prove_batch_with_timestamp(
+        &self,
+        moderator: UserAddress,
+        entries: Vec<ProofBatchEntry>,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        let mut users = HashSet::new();
+        let mut rejected = Vec::new();
+        for (i, entry) in entries.iter().enumerate() {
+            if !users.insert(&entry.user) {
+                rejected.push((i, Error::DuplicateBatchUser(entry.user.clone())));
+                continue;
+            }
+            match self
+                .check_proof(&entry.user, &moderator, entry.amount)
+                .await
+            {
+                Ok(()) => {}
+                Err(
+                    e @ (Error::MaxBalanceExceeded
+                    | Error::ModeratorLimitExceeded(_)
+                    | Error::AddressBlocked(_)),
+                ) => rejected.push((i, e)),
+                Err(e) => return Err(e),
+            }
+        }
+        if !rejected.is_empty() {
+            return Err(Error::ProofBatchRejected(rejected));
+        }
+
+        let count = entries.len() as u64;
+        let proofs = entries
+            .into_iter()
+            .map(|entry| {
+                let proof = ModeratorProof {
+                    moderator: moderator.clone(),
+                    amount: entry.amount,
+                    proof_id: entry.proof_id,
+                    timestamp,
+                };
+                (entry.user, proof)
+            })
+            .collect();
+        self.proofs.set_proofs(proofs).await?;
+        self.record_moderator_outcome(&moderator, ModeratorOutcome::Proof, count)
+            .await
+    }
+
     // TODO: avoid Option
     pub async fn proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
         self.proofs.proof(user).await
     }
+
+    // invalidates all proofs of a compromised moderator, returns the users who lost
+    // their proof
+    pub async fn revoke_moderator_proofs(
+        &self,
+        moderator: &UserAddress,
+    ) -> Result<Vec<UserAddress>, Error> {
+        let users = self.revoke_system_proofs(moderator).await?;
+        self.record_moderator_outcome(
+            moderator,
+            ModeratorOutcome::RevokedProof,
+            users.len() as u64,
+        )
+        .await?;
+        Ok(users)
+    }
+
+    // invalidates all system proofs of a registry, they are not counted in the moderator
+    // statistics
+    pub async fn revoke_system_proofs(
+        &self,
+        moderator: &UserAddress,
+    ) -> Result<Vec<UserAddress>, Error> {
+        let mut users = self.proofs.revoke_proofs(moderator).await?;
+        users.sort();
+        Ok(users)
+    }
+
+    // moves all proofs of a retiring moderator to their successor, keeping the amounts and
+    // timestamps, returns the users whose proofs were transferred
+    pub async fn transfer_moderator_proofs(
+        &self,
+        moderator: &UserAddress,
+        successor: &UserAddress,
+    ) -> Result<Vec<UserAddress>, Error> {
+        let mut users = Vec::new();
+        for user in self.proofs.proven_users().await? {
+            let Some(proof) = self.proofs.proof(&user).await? else {
+                continue;
+            };
+            if &proof.moderator != moderator {
+                continue;
+            }
+            let proof = ModeratorProof {
+                moderator: successor.clone(),
+                ..proof
+            };
+            self.proofs.set_proof(user.clone(), proof).await?;
+            users.push(user);
+        }
+        users.sort();
+        self.record_moderator_outcome(successor, ModeratorOutcome::Proof, users.len() as u64)
+            .await?;
+        Ok(users)
+    }
+
+    pub async fn revoked_proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        self.proofs.revoked_proof(user).await
+    }
 }
 
 pub async fn prove(
@@ -42,7 +199,17 @@ pub async fn prove(
     proof_id: ProofId,
 ) -> Result<(), Error> {
     service
-        .prove_with_timestamp(user, moderator, balance, proof_id, next_timestamp())
+        .prove_with_timestamp(user, moderator, balance, proof_id, service.now())
+        .await
+}
+
+pub async fn prove_batch(
+    service: &IdentityService,
+    moderator: UserAddress,
+    entries: Vec<ProofBatchEntry>,
+) -> Result<(), Error> {
+    service
+        .prove_batch_with_timestamp(moderator, entries, service.now())
         .await
 }
 
@@ -61,7 +228,7 @@ mod tests {
                 &service,
                 USER_A.to_string(),
                 MODERATOR.to_string(),
-                100,
+                IdtAmount::new(100),
                 PROOF_ID
             )
             .await
@@ -74,7 +241,7 @@ mod tests {
                 .unwrap()
                 .unwrap()
                 .amount,
-            100
+            IdtAmount::new(100)
         );
         assert_eq!(
             service
@@ -105,9 +272,15 @@ mod tests {
         );
 
         assert!(
-            prove(&service, USER_A.to_string(), MODERATOR.to_string(), 200, 2)
-                .await
-                .is_ok()
+            prove(
+                &service,
+                USER_A.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(200),
+                2
+            )
+            .await
+            .is_ok()
         );
         assert_eq!(
             service
@@ -116,7 +289,7 @@ mod tests {
                 .unwrap()
                 .unwrap()
                 .amount,
-            200
+            IdtAmount::new(200)
         );
         assert_eq!(
             service
@@ -149,6 +322,54 @@ mod tests {
         );
     }
 
+    #[async_std::test]
+    async fn test_transfer_moderator_proofs() {
+        let service = IdentityService::default();
+        let (user_b, successor) = ("userB".to_string(), "successor".to_string());
+        service
+            .prove_with_timestamp(
+                USER_A.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(100),
+                PROOF_ID,
+                10,
+            )
+            .await
+            .unwrap();
+        service
+            .prove_with_timestamp(
+                user_b.clone(),
+                "other".to_string(),
+                IdtAmount::new(200),
+                PROOF_ID,
+                20,
+            )
+            .await
+            .unwrap();
+
+        let users = service
+            .transfer_moderator_proofs(&MODERATOR.to_string(), &successor)
+            .await
+            .unwrap();
+        assert_eq!(users, vec![USER_A.to_string()]);
+        let proof = service.proof(&USER_A.to_string()).await.unwrap().unwrap();
+        assert_eq!(proof.moderator, successor);
+        assert_eq!(proof.amount, IdtAmount::new(100));
+        assert_eq!(proof.proof_id, PROOF_ID);
+        assert_eq!(proof.timestamp, 10);
+        assert_eq!(
+            service.proof(&user_b).await.unwrap().unwrap().moderator,
+            "other"
+        );
+
+        // nothing is left to transfer
+        let users = service
+            .transfer_moderator_proofs(&MODERATOR.to_string(), &successor)
+            .await
+            .unwrap();
+        assert!(users.is_empty());
+    }
+
     #[async_std::test]
     async fn test_max_balance() {
         let service = IdentityService::default();
@@ -158,7 +379,7 @@ mod tests {
                 &service,
                 USER_A.to_string(),
                 MODERATOR.to_string(),
-                40000,
+                IdtAmount::new(40000),
                 PROOF_ID
             )
             .await
@@ -171,14 +392,14 @@ mod tests {
                 .unwrap()
                 .unwrap()
                 .amount,
-            40000
+            IdtAmount::new(40000)
         );
         assert!(
             prove(
                 &service,
                 USER_A.to_string(),
                 MODERATOR.to_string(),
-                50001,
+                IdtAmount::new(50001),
                 PROOF_ID
             )
             .await
@@ -191,14 +412,14 @@ mod tests {
                 .unwrap()
                 .unwrap()
                 .amount,
-            40000
+            IdtAmount::new(40000)
         );
         assert!(
             prove(
                 &service,
                 USER_A.to_string(),
                 MODERATOR.to_string(),
-                60000,
+                IdtAmount::new(60000),
                 PROOF_ID
             )
             .await
@@ -211,7 +432,109 @@ mod tests {
                 .unwrap()
                 .unwrap()
                 .amount,
-            40000
+            IdtAmount::new(40000)
         );
     }
+
+    #[async_std::test]
+    async fn test_moderator_limit() {
+        let service = IdentityService::default();
+        service
+            .set_proof_limit(&MODERATOR.to_string(), Some(IdtAmount::new(1000)))
+            .await
+            .unwrap();
+        assert!(matches!(
+            prove(
+                &service,
+                USER_A.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(1001),
+                PROOF_ID
+            )
+            .await,
+            Err(Error::ModeratorLimitExceeded(limit)) if limit == IdtAmount::new(1000)
+        ));
+        assert!(service.proof(&USER_A.to_string()).await.unwrap().is_none());
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        // other moderators are not limited
+        prove(
+            &service,
+            USER_A.to_string(),
+            "other".to_string(),
+            IdtAmount::new(2000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        // the global maximum still applies to higher limits
+        service
+            .set_proof_limit(
+                &MODERATOR.to_string(),
+                Some(MAX_IDT_BY_PROOF + MAX_IDT_BY_PROOF),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            prove(
+                &service,
+                USER_A.to_string(),
+                MODERATOR.to_string(),
+                MAX_IDT_BY_PROOF + IdtAmount::new(1),
+                PROOF_ID
+            )
+            .await,
+            Err(Error::MaxBalanceExceeded)
+        ));
+    }
+
+    #[async_std::test]
+    async fn test_prove_batch() {
+        let service = IdentityService::default();
+        let entry = |user: &str, amount| ProofBatchEntry {
+            user: user.to_string(),
+            amount,
+            proof_id: PROOF_ID,
+        };
+        let rejected = prove_batch(
+            &service,
+            MODERATOR.to_string(),
+            vec![
+                entry(USER_A, IdtAmount::new(100)),
+                entry("userB", MAX_IDT_BY_PROOF + IdtAmount::new(1)),
+                entry(USER_A, IdtAmount::new(200)),
+            ],
+        )
+        .await;
+        let Err(Error::ProofBatchRejected(errors)) = rejected else {
+            panic!("batch should be rejected");
+        };
+        assert!(matches!(errors[0], (1, Error::MaxBalanceExceeded)));
+        assert!(matches!(&errors[1], (2, Error::DuplicateBatchUser(user)) if user == USER_A));
+        assert_eq!(errors.len(), 2);
+        // nothing is stored from a rejected batch
+        assert!(service.proof(&USER_A.to_string()).await.unwrap().is_none());
+
+        prove_batch(
+            &service,
+            MODERATOR.to_string(),
+            vec![
+                entry(USER_A, IdtAmount::new(100)),
+                entry("userB", IdtAmount::new(200)),
+            ],
+        )
+        .await
+        .unwrap();
+        let proof = service.proof(&"userB".to_string()).await.unwrap().unwrap();
+        assert_eq!(proof.amount, IdtAmount::new(200));
+        assert_eq!(proof.moderator, MODERATOR);
+        assert!(service.proof(&USER_A.to_string()).await.unwrap().is_some());
+    }
 }
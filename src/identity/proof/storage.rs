@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use async_std::sync::RwLock;
 use async_trait::async_trait;
@@ -9,8 +9,24 @@ use crate::identity::{IdtAmount, ModeratorProof, UserAddress, error::Error};
 pub trait ProofStorage: Send + Sync {
     async fn set_genesis(&self, users: HashMap<UserAddress, IdtAmount>) -> Result<(), Error>;
     async fn genesis_balance(&self, user: &UserAddress) -> Result<Option<IdtAmount>, Error>;
+    // every stored genesis balance
+    async fn genesis(&self) -> Result<HashMap<UserAddress, IdtAmount>, Error>;
     async fn set_proof(&self, user: UserAddress, proof: ModeratorProof) -> Result<(), Error>;
+    // stores the proofs in order. Storages with transactions store all or none of them,
+    // others may stop after a part of the proofs.
+    async fn set_proofs(&self, proofs: Vec<(UserAddress, ModeratorProof)>) -> Result<(), Error> {
+        for (user, proof) in proofs {
+            self.set_proof(user, proof).await?;
+        }
+        Ok(())
+    }
     async fn proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error>;
+    async fn remove_proof(&self, user: &UserAddress) -> Result<(), Error>;
+    // users with a proof or a genesis balance
+    async fn proven_users(&self) -> Result<HashSet<UserAddress>, Error>;
+    // moves all proofs issued by the moderator to revoked proofs, returns their users
+    async fn revoke_proofs(&self, moderator: &UserAddress) -> Result<Vec<UserAddress>, Error>;
+    async fn revoked_proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error>;
 }
 
 #[derive(Default)]
@@ -20,6 +36,7 @@ pub struct InMemoryProofStorage {
     // moderator should prove again and update proof manually.
     data: RwLock<HashMap<UserAddress, ModeratorProof>>,
     genesis: RwLock<HashMap<UserAddress, IdtAmount>>,
+    revoked: RwLock<HashMap<UserAddress, ModeratorProof>>,
 }
 
 #[async_trait]
@@ -34,6 +51,10 @@ impl ProofStorage for InMemoryProofStorage {
         Ok(self.genesis.read().await.get(user).cloned())
     }
 
+    async fn genesis(&self) -> Result<HashMap<UserAddress, IdtAmount>, Error> {
+        Ok(self.genesis.read().await.clone())
+    }
+
     async fn set_proof(&self, user: UserAddress, proof: ModeratorProof) -> Result<(), Error> {
         self.data.write().await.insert(user, proof);
         Ok(())
@@ -42,6 +63,37 @@ impl ProofStorage for InMemoryProofStorage {
     async fn proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
         Ok(self.data.read().await.get(user).cloned())
     }
+
+    async fn remove_proof(&self, user: &UserAddress) -> Result<(), Error> {
+        self.data.write().await.remove(user);
+        Ok(())
+    }
+
+    async fn proven_users(&self) -> Result<HashSet<UserAddress>, Error> {
+        let mut users: HashSet<UserAddress> = self.data.read().await.keys().cloned().collect();
+        users.extend(self.genesis.read().await.keys().cloned());
+        Ok(users)
+    }
+
+    async fn revoke_proofs(&self, moderator: &UserAddress) -> Result<Vec<UserAddress>, Error> {
+        let mut data = self.data.write().await;
+        let users: Vec<UserAddress> = data
+            .iter()
+            .filter(|(_, proof)| &proof.moderator == moderator)
+            .map(|(user, _)| user.clone())
+            .collect();
+        let mut revoked = self.revoked.write().await;
+        for user in &users {
+            if let Some(proof) = data.remove(user) {
+                revoked.insert(user.clone(), proof);
+            }
+        }
+        Ok(users)
+    }
+
+    async fn revoked_proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        Ok(self.revoked.read().await.get(user).cloned())
+    }
 }
 
 #[cfg(test)]
@@ -56,9 +108,13 @@ mod tests {
         let moderator = "moderator".to_string();
 
         let mut genesis = HashMap::<UserAddress, IdtAmount>::new();
-        genesis.insert(user.clone(), 100);
-        storage.set_genesis(genesis).await.unwrap();
-        assert_eq!(storage.genesis_balance(&user).await.unwrap().unwrap(), 100);
+        genesis.insert(user.clone(), IdtAmount::new(100));
+        storage.set_genesis(genesis.clone()).await.unwrap();
+        assert_eq!(
+            storage.genesis_balance(&user).await.unwrap().unwrap(),
+            IdtAmount::new(100)
+        );
+        assert_eq!(storage.genesis().await.unwrap(), genesis);
         assert!(
             storage
                 .genesis_balance(&"none".to_string())
@@ -69,7 +125,7 @@ mod tests {
 
         let proof1 = ModeratorProof {
             moderator: moderator.clone(),
-            amount: 10,
+            amount: IdtAmount::new(10),
             proof_id: 1,
             timestamp: 1,
         };
@@ -85,7 +141,7 @@ mod tests {
 
         let proof2 = ModeratorProof {
             moderator: "mod2".to_string(),
-            amount: 20,
+            amount: IdtAmount::new(20),
             proof_id: 2,
             timestamp: 2,
         };
@@ -101,4 +157,77 @@ mod tests {
 
         assert!(storage.proof(&"none".to_string()).await.unwrap().is_none());
     }
+
+    #[async_std::test]
+    async fn test_proven_users() {
+        let storage = InMemoryProofStorage::default();
+        storage
+            .set_genesis(HashMap::from([
+                ("a".to_string(), IdtAmount::new(10)),
+                ("b".to_string(), IdtAmount::new(10)),
+            ]))
+            .await
+            .unwrap();
+        let proof = ModeratorProof {
+            moderator: "moderator".to_string(),
+            amount: IdtAmount::new(10),
+            proof_id: 1,
+            timestamp: 1,
+        };
+        storage
+            .set_proof("b".to_string(), proof.clone())
+            .await
+            .unwrap();
+        storage.set_proof("c".to_string(), proof).await.unwrap();
+        let users = storage.proven_users().await.unwrap();
+        assert_eq!(
+            users,
+            HashSet::from(["a".to_string(), "b".to_string(), "c".to_string()])
+        );
+    }
+
+    #[async_std::test]
+    async fn test_revoke_proofs() {
+        let storage = InMemoryProofStorage::default();
+        let proof = ModeratorProof {
+            moderator: "moderator".to_string(),
+            amount: IdtAmount::new(10),
+            proof_id: 1,
+            timestamp: 1,
+        };
+        storage
+            .set_proof("a".to_string(), proof.clone())
+            .await
+            .unwrap();
+        storage
+            .set_proof(
+                "b".to_string(),
+                ModeratorProof {
+                    moderator: "other".to_string(),
+                    ..proof.clone()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            storage
+                .revoke_proofs(&"moderator".to_string())
+                .await
+                .unwrap(),
+            vec!["a".to_string()]
+        );
+        assert!(storage.proof(&"a".to_string()).await.unwrap().is_none());
+        assert!(storage.proof(&"b".to_string()).await.unwrap().is_some());
+        assert_eq!(
+            storage.revoked_proof(&"a".to_string()).await.unwrap(),
+            Some(proof)
+        );
+        assert!(
+            storage
+                .revoked_proof(&"b".to_string())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
 }
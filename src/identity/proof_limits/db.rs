@@ -0,0 +1,98 @@
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::{IdtAmount, UserAddress, error::Error, proof_limits::storage::ProofLimitStorage},
+    numbers::rescale_column,
+    pools::{self, MeteredPool},
+};
+
+pub struct DatabaseProofLimitStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabaseProofLimitStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("proof_limits", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS proof_limits (moderator TEXT PRIMARY KEY, amount INTEGER NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "proof_limits", "moderator").await?;
+        rescale_column(&pool, "proof_limits", "amount").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl ProofLimitStorage for DatabaseProofLimitStorage {
+    async fn set_limit(
+        &self,
+        moderator: &UserAddress,
+        limit: Option<IdtAmount>,
+    ) -> Result<(), Error> {
+        match limit {
+            Some(limit) => {
+                sqlx::query("REPLACE INTO proof_limits (moderator, amount) VALUES (?, ?)")
+                    .bind(self.cipher.encode(moderator))
+                    .bind(limit.milli() as i64)
+                    .execute(&self.pool)
+                    .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM proof_limits WHERE moderator = ?")
+                    .bind(self.cipher.encode(moderator))
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn limit(&self, moderator: &UserAddress) -> Result<Option<IdtAmount>, Error> {
+        let row = sqlx::query("SELECT amount FROM proof_limits WHERE moderator = ?")
+            .bind(self.cipher.encode(moderator))
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| IdtAmount::from_milli(row.get::<i64, _>(0) as u64)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseProofLimitStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        let moderator = "moderator".to_string();
+        assert_eq!(storage.limit(&moderator).await.unwrap(), None);
+        storage
+            .set_limit(&moderator, Some(IdtAmount::new(100)))
+            .await
+            .unwrap();
+        storage
+            .set_limit(&moderator, Some(IdtAmount::new(200)))
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.limit(&moderator).await.unwrap(),
+            Some(IdtAmount::new(200))
+        );
+        storage.set_limit(&moderator, None).await.unwrap();
+        assert_eq!(storage.limit(&moderator).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,23 @@
+// Maximum proof amounts of individual moderators set by admins, e.g. for moderators still
+// on probation. The limit applies in addition to `MAX_IDT_BY_PROOF`.
+
+use crate::identity::{IdentityService, IdtAmount, UserAddress, error::Error};
+
+#[cfg(feature = "storage-sql")]
+pub mod db;
+pub mod storage;
+
+impl IdentityService {
+    // None removes the limit of the moderator
+    pub async fn set_proof_limit(
+        &self,
+        moderator: &UserAddress,
+        limit: Option<IdtAmount>,
+    ) -> Result<(), Error> {
+        self.proof_limits.set_limit(moderator, limit).await
+    }
+
+    pub async fn proof_limit(&self, moderator: &UserAddress) -> Result<Option<IdtAmount>, Error> {
+        self.proof_limits.limit(moderator).await
+    }
+}
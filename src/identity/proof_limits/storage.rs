@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::identity::{IdtAmount, UserAddress, error::Error};
+
+#[async_trait]
+pub trait ProofLimitStorage: Send + Sync {
+    // None removes the limit of the moderator
+    async fn set_limit(
+        &self,
+        moderator: &UserAddress,
+        limit: Option<IdtAmount>,
+    ) -> Result<(), Error>;
+    async fn limit(&self, moderator: &UserAddress) -> Result<Option<IdtAmount>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryProofLimitStorage {
+    limits: RwLock<HashMap<UserAddress, IdtAmount>>,
+}
+
+#[async_trait]
+impl ProofLimitStorage for InMemoryProofLimitStorage {
+    async fn set_limit(
+        &self,
+        moderator: &UserAddress,
+        limit: Option<IdtAmount>,
+    ) -> Result<(), Error> {
+        let mut limits = self.limits.write().await;
+        match limit {
+            Some(limit) => limits.insert(moderator.clone(), limit),
+            None => limits.remove(moderator),
+        };
+        Ok(())
+    }
+
+    async fn limit(&self, moderator: &UserAddress) -> Result<Option<IdtAmount>, Error> {
+        Ok(self.limits.read().await.get(moderator).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryProofLimitStorage::default();
+        let moderator = "moderator".to_string();
+        assert_eq!(storage.limit(&moderator).await.unwrap(), None);
+        storage
+            .set_limit(&moderator, Some(IdtAmount::new(100)))
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.limit(&moderator).await.unwrap(),
+            Some(IdtAmount::new(100))
+        );
+        storage.set_limit(&moderator, None).await.unwrap();
+        assert_eq!(storage.limit(&moderator).await.unwrap(), None);
+    }
+}
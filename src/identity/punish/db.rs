@@ -1,22 +1,32 @@
 use async_trait::async_trait;
-use sqlx::{AnyPool, Row, any::AnyPoolOptions};
+use sqlx::{Acquire, Row, any::AnyRow};
 
-use crate::identity::{
-    IdtAmount, ModeratorProof, ProofId, SystemPenalty, UserAddress, error::Error,
-    punish::storage::PenaltyStorage,
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::{
+        IdtAmount, ModeratorProof, ProofId, SystemPenalty, UserAddress,
+        error::Error,
+        punish::storage::{PenaltyStorage, PenaltyWrite},
+    },
+    numbers::rescale_column,
+    pools::{self, MeteredPool},
 };
 
+// name of the connection pool in `GET /metrics`
+const POOL_NAME: &str = "penalties";
+
 pub struct DatabasePenaltyStorage {
-    pool: AnyPool,
+    pool: MeteredPool,
+    cipher: FieldCipher,
 }
 
 impl DatabasePenaltyStorage {
     pub async fn new(url: &str) -> Result<Self, Error> {
-        sqlx::any::install_default_drivers();
-        let pool = AnyPoolOptions::new()
-            .max_connections(1)
-            .connect(url)
-            .await?;
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect(POOL_NAME, url).await?;
         sqlx::query(
             "CREATE TABLE IF NOT EXISTS moderator_penalties (user TEXT PRIMARY KEY, moderator TEXT NOT NULL, amount INTEGER NOT NULL, proof_id INTEGER NOT NULL, timestamp INTEGER NOT NULL)"
         )
@@ -30,7 +40,41 @@ impl DatabasePenaltyStorage {
         sqlx::query("CREATE INDEX IF NOT EXISTS forget_penalties_idx ON forget_penalties(user)")
             .execute(&pool)
             .await?;
-        Ok(Self { pool })
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS moderator_penalties_time_idx ON moderator_penalties(timestamp)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "moderator_penalties", "user").await?;
+        rotate_column(&pool, &cipher, "forget_penalties", "user").await?;
+        rotate_column(&pool, &cipher, "forget_penalties", "forgotten").await?;
+        rescale_column(&pool, "moderator_penalties", "amount").await?;
+        rescale_column(&pool, "forget_penalties", "amount").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    // (user, moderator, amount, proof_id, timestamp) rows
+    fn moderator_penalties(
+        &self,
+        rows: Vec<AnyRow>,
+    ) -> Result<Vec<(UserAddress, ModeratorProof)>, Error> {
+        let mut penalties = vec![];
+        for r in rows {
+            penalties.push((
+                self.cipher.decode(&r.get::<String, _>(0))?,
+                ModeratorProof {
+                    moderator: r.get::<String, _>(1),
+                    amount: IdtAmount::from_milli(r.get::<i64, _>(2) as u64),
+                    proof_id: r.get::<i64, _>(3) as ProofId,
+                    timestamp: r.get::<i64, _>(4) as u64,
+                },
+            ));
+        }
+        Ok(penalties)
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
     }
 }
 
@@ -41,15 +85,8 @@ impl PenaltyStorage for DatabasePenaltyStorage {
         user: UserAddress,
         proof: ModeratorProof,
     ) -> Result<(), Error> {
-        sqlx::query("REPLACE INTO moderator_penalties (user, moderator, amount, proof_id, timestamp) VALUES (?, ?, ?, ?, ?)")
-            .bind(&user)
-            .bind(&proof.moderator)
-            .bind(proof.amount as i64)
-            .bind(proof.proof_id as i64)
-            .bind(proof.timestamp as i64)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+        self.write_batch(vec![PenaltyWrite::SetModeratorPenalty { user, proof }])
+            .await
     }
 
     async fn set_forgotten_penalty(
@@ -58,14 +95,12 @@ impl PenaltyStorage for DatabasePenaltyStorage {
         vouchee: UserAddress,
         penalty: SystemPenalty,
     ) -> Result<(), Error> {
-        sqlx::query("REPLACE INTO forget_penalties (user, forgotten, amount, timestamp) VALUES (?, ?, ?, ?)")
-            .bind(&user)
-            .bind(&vouchee)
-            .bind(penalty.amount as i64)
-            .bind(penalty.timestamp as i64)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+        self.write_batch(vec![PenaltyWrite::SetForgottenPenalty {
+            user,
+            vouchee,
+            penalty,
+        }])
+        .await
     }
 
     async fn remove_forgotten(
@@ -73,29 +108,35 @@ impl PenaltyStorage for DatabasePenaltyStorage {
         user: UserAddress,
         forgotten: &UserAddress,
     ) -> Result<(), Error> {
-        sqlx::query("DELETE FROM forget_penalties WHERE user = ? AND forgotten = ?")
-            .bind(&user)
-            .bind(forgotten)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+        self.write_batch(vec![PenaltyWrite::RemoveForgotten {
+            user,
+            forgotten: forgotten.clone(),
+        }])
+        .await
     }
 
     async fn moderator_penalty(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
         let row = sqlx::query(
             "SELECT moderator, amount, proof_id, timestamp FROM moderator_penalties WHERE user = ?",
         )
-        .bind(user)
+        .bind(self.cipher.encode(user))
         .fetch_optional(&self.pool)
         .await?;
         Ok(row.map(|r| ModeratorProof {
             moderator: r.get::<String, _>(0),
-            amount: r.get::<i64, _>(1) as IdtAmount,
+            amount: IdtAmount::from_milli(r.get::<i64, _>(1) as u64),
             proof_id: r.get::<i64, _>(2) as ProofId,
             timestamp: r.get::<i64, _>(3) as u64,
         }))
     }
 
+    async fn remove_moderator_penalty(&self, user: &UserAddress) -> Result<(), Error> {
+        self.write_batch(vec![PenaltyWrite::RemoveModeratorPenalty {
+            user: user.clone(),
+        }])
+        .await
+    }
+
     async fn forgotten_penalty(
         &self,
         user: &UserAddress,
@@ -104,12 +145,12 @@ impl PenaltyStorage for DatabasePenaltyStorage {
         let row = sqlx::query(
             "SELECT amount, timestamp FROM forget_penalties WHERE user = ? AND forgotten = ?",
         )
-        .bind(user)
-        .bind(forgotten)
+        .bind(self.cipher.encode(user))
+        .bind(self.cipher.encode(forgotten))
         .fetch_optional(&self.pool)
         .await?;
         Ok(row.map(|r| SystemPenalty {
-            amount: r.get::<i64, _>(0) as IdtAmount,
+            amount: IdtAmount::from_milli(r.get::<i64, _>(0) as u64),
             timestamp: r.get::<i64, _>(1) as u64,
         }))
     }
@@ -119,10 +160,103 @@ impl PenaltyStorage for DatabasePenaltyStorage {
         user: &UserAddress,
     ) -> Result<std::collections::HashSet<UserAddress>, Error> {
         let rows = sqlx::query("SELECT forgotten FROM forget_penalties WHERE user = ?")
-            .bind(user)
+            .bind(self.cipher.encode(user))
+            .fetch_all(&self.pool)
+            .await?;
+        let mut users = std::collections::HashSet::new();
+        for r in rows {
+            users.insert(self.cipher.decode(&r.get::<String, _>(0))?);
+        }
+        Ok(users)
+    }
+
+    async fn all_moderator_penalties(&self) -> Result<Vec<(UserAddress, ModeratorProof)>, Error> {
+        let rows = sqlx::query(
+            "SELECT user, moderator, amount, proof_id, timestamp FROM moderator_penalties",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        self.moderator_penalties(rows)
+    }
+
+    async fn recent_moderator_penalties(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<(UserAddress, ModeratorProof)>, Error> {
+        let rows = sqlx::query(
+            "SELECT user, moderator, amount, proof_id, timestamp FROM moderator_penalties ORDER BY timestamp DESC, proof_id DESC LIMIT ?",
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        self.moderator_penalties(rows)
+    }
+
+    async fn all_forgotten_penalties(
+        &self,
+    ) -> Result<Vec<(UserAddress, UserAddress, SystemPenalty)>, Error> {
+        let rows = sqlx::query("SELECT user, forgotten, amount, timestamp FROM forget_penalties")
             .fetch_all(&self.pool)
             .await?;
-        Ok(rows.into_iter().map(|r| r.get::<String, _>(0)).collect())
+        let mut penalties = vec![];
+        for r in rows {
+            penalties.push((
+                self.cipher.decode(&r.get::<String, _>(0))?,
+                self.cipher.decode(&r.get::<String, _>(1))?,
+                SystemPenalty {
+                    amount: IdtAmount::from_milli(r.get::<i64, _>(2) as u64),
+                    timestamp: r.get::<i64, _>(3) as u64,
+                },
+            ));
+        }
+        Ok(penalties)
+    }
+
+    // a single transaction, so the whole batch is committed at once
+    async fn write_batch(&self, writes: Vec<PenaltyWrite>) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        for write in writes {
+            match write {
+                PenaltyWrite::SetModeratorPenalty { user, proof } => {
+                    sqlx::query("REPLACE INTO moderator_penalties (user, moderator, amount, proof_id, timestamp) VALUES (?, ?, ?, ?, ?)")
+                        .bind(self.cipher.encode(&user))
+                        .bind(&proof.moderator)
+                        .bind(proof.amount.milli() as i64)
+                        .bind(proof.proof_id as i64)
+                        .bind(proof.timestamp as i64)
+                        .execute(tx.acquire().await?)
+                        .await?;
+                }
+                PenaltyWrite::RemoveModeratorPenalty { user } => {
+                    sqlx::query("DELETE FROM moderator_penalties WHERE user = ?")
+                        .bind(self.cipher.encode(&user))
+                        .execute(tx.acquire().await?)
+                        .await?;
+                }
+                PenaltyWrite::SetForgottenPenalty {
+                    user,
+                    vouchee,
+                    penalty,
+                } => {
+                    sqlx::query("REPLACE INTO forget_penalties (user, forgotten, amount, timestamp) VALUES (?, ?, ?, ?)")
+                        .bind(self.cipher.encode(&user))
+                        .bind(self.cipher.encode(&vouchee))
+                        .bind(penalty.amount.milli() as i64)
+                        .bind(penalty.timestamp as i64)
+                        .execute(tx.acquire().await?)
+                        .await?;
+                }
+                PenaltyWrite::RemoveForgotten { user, forgotten } => {
+                    sqlx::query("DELETE FROM forget_penalties WHERE user = ? AND forgotten = ?")
+                        .bind(self.cipher.encode(&user))
+                        .bind(self.cipher.encode(&forgotten))
+                        .execute(tx.acquire().await?)
+                        .await?;
+                }
+            }
+        }
+        tx.commit().await?;
+        Ok(())
     }
 }
 
@@ -140,7 +274,7 @@ mod tests {
 
         let proof1 = ModeratorProof {
             moderator: "mod".to_string(),
-            amount: 1,
+            amount: IdtAmount::new(1),
             proof_id: 1,
             timestamp: 2,
         };
@@ -156,7 +290,7 @@ mod tests {
 
         let proof2 = ModeratorProof {
             moderator: "mod2".to_string(),
-            amount: 3,
+            amount: IdtAmount::new(3),
             proof_id: 2,
             timestamp: 4,
         };
@@ -179,7 +313,7 @@ mod tests {
         );
 
         let penalty1 = SystemPenalty {
-            amount: 5,
+            amount: IdtAmount::new(5),
             timestamp: 6,
         };
         storage
@@ -202,7 +336,7 @@ mod tests {
         );
 
         let penalty2 = SystemPenalty {
-            amount: 7,
+            amount: IdtAmount::new(7),
             timestamp: 8,
         };
         storage
@@ -217,6 +351,15 @@ mod tests {
         assert_eq!(res.amount, penalty2.amount);
         assert_eq!(res.timestamp, penalty2.timestamp);
 
+        assert_eq!(
+            storage.all_moderator_penalties().await.unwrap(),
+            vec![(user.clone(), proof2)]
+        );
+        assert_eq!(
+            storage.all_forgotten_penalties().await.unwrap(),
+            vec![(user.clone(), vouchee.clone(), penalty2)]
+        );
+
         storage
             .remove_forgotten(user.clone(), &vouchee)
             .await
@@ -236,4 +379,31 @@ mod tests {
                 .contains(&vouchee)
         );
     }
+
+    #[async_std::test]
+    async fn test_recent() {
+        let storage = DatabasePenaltyStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        for (user, timestamp) in [("a", 3), ("b", 1), ("c", 2)] {
+            let proof = ModeratorProof {
+                moderator: "mod".to_string(),
+                amount: IdtAmount::new(1),
+                proof_id: 1,
+                timestamp,
+            };
+            storage
+                .set_moderator_penalty(user.to_string(), proof)
+                .await
+                .unwrap();
+        }
+        let recent: Vec<_> = storage
+            .recent_moderator_penalties(2)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(user, p)| (user, p.timestamp))
+            .collect();
+        assert_eq!(recent, vec![("a".to_string(), 3), ("c".to_string(), 2)]);
+    }
 }
@@ -5,7 +5,7 @@ use crate::{
         IdentityService, IdtAmount, ModeratorProof, ProofId, SystemPenalty, UserAddress,
         decay::{balance_after_decay, moderator_penalty_decay, system_penalty_decay},
         error::Error,
-        next_timestamp,
+        moderators::ModeratorOutcome,
         proof::MAX_IDT_BY_PROOF,
         tree_walk::{ChildrenSelector, Visitor, walk_tree},
         vouch::vouchees,
@@ -13,6 +13,7 @@ use crate::{
     numbers::Rational,
 };
 
+#[cfg(feature = "storage-sql")]
 pub mod db;
 pub mod storage;
 
@@ -20,11 +21,11 @@ pub mod storage;
 // IDT balance can eventually become positive.
 // It only limits vouchee penalty because we do not want to limit amount of penalties and their value
 // for a single user but we do not want to propagate it across the network indefinitely.
-pub const MAX_VOUCHEE_PENALTY: IdtAmount = MAX_IDT_BY_PROOF * 2;
+pub const MAX_VOUCHEE_PENALTY: IdtAmount = IdtAmount::new(MAX_IDT_BY_PROOF.units() * 2);
 // vouchee's penalty is multiplied to this coefficient before adding to voucher penalty
 // stored as (numerator, denominator)
 pub const PENALTY_VOUCHEE_WEIGHT_RATIO: (u32, u32) = (1, 10);
-pub const FORGET_PENALTY: IdtAmount = 500;
+pub const FORGET_PENALTY: IdtAmount = IdtAmount::new(500);
 
 struct PenaltyTree<'a> {
     service: &'a IdentityService,
@@ -34,6 +35,10 @@ impl ChildrenSelector for PenaltyTree<'_> {
     async fn children(&self, root: &UserAddress) -> Result<Vec<UserAddress>, Error> {
         vouchees(self.service, root).await
     }
+
+    fn max_depth(&self) -> Option<usize> {
+        self.service.config.max_penalty_depth
+    }
 }
 
 async fn penalty_from_vouchees(
@@ -42,7 +47,7 @@ async fn penalty_from_vouchees(
     visited: &im::HashSet<UserAddress>,
     penalties: &HashMap<UserAddress, IdtAmount>,
 ) -> Result<IdtAmount, Error> {
-    let mut penalty: IdtAmount = 0;
+    let mut penalty = IdtAmount::ZERO;
     for v in &vouchees(service, user).await? {
         if visited.contains(v) {
             continue;
@@ -64,7 +69,7 @@ async fn penalty_from_vouchees(
         PENALTY_VOUCHEE_WEIGHT_RATIO.1,
     )
     .expect("PENALTY_VOUCHEE_WEIGHT_RATIO denominator must not be zero");
-    Ok(penalty_scale.mul(penalty))
+    Ok(penalty_scale.mul_rounded(penalty, service.config.rounding))
 }
 
 async fn vouchee_penalty(
@@ -74,15 +79,15 @@ async fn vouchee_penalty(
 ) -> Result<IdtAmount, Error> {
     let vouchee_penalty_maybe = service.forgotten_penalty(user, vouchee).await?;
     let vouchee_penalty = match vouchee_penalty_maybe {
-        None => return Ok(0),
+        None => return Ok(IdtAmount::ZERO),
         Some(p) => p,
     };
-    let decay = system_penalty_decay(&vouchee_penalty);
+    let decay = system_penalty_decay(service, &vouchee_penalty, service.now());
     let result_penalty = balance_after_decay(vouchee_penalty.amount, decay);
     // cleanup outdated penalties
-    if result_penalty == 0 {
+    if result_penalty.is_zero() {
         service.delete_forgotten(user.clone(), vouchee).await?;
-        return Ok(0);
+        return Ok(IdtAmount::ZERO);
     }
     Ok(result_penalty)
 }
@@ -92,7 +97,7 @@ async fn forgotten_penalties_sum(
     user: &UserAddress,
     vouchees: &HashSet<UserAddress>,
 ) -> Result<IdtAmount, Error> {
-    let mut penalty = 0;
+    let mut penalty = IdtAmount::ZERO;
     for vouchee in vouchees {
         let vouchee_penalty = vouchee_penalty(service, user, vouchee).await?;
         penalty += vouchee_penalty;
@@ -108,17 +113,23 @@ impl Visitor for PenaltyTree<'_> {
         balances: &HashMap<UserAddress, IdtAmount>,
     ) -> Result<IdtAmount, Error> {
         let proven_penalty = {
-            let proven_penalty = match self.service.moderator_penalty(node).await? {
-                None => 0,
-                Some(e) => e.amount,
-            };
+            let proven_penalty = self
+                .service
+                .weighted_moderator_penalty(node)
+                .await?
+                .unwrap_or_default();
             let proven_penalty_decay = moderator_penalty_decay(self.service, node).await?;
             balance_after_decay(proven_penalty, proven_penalty_decay)
         };
         let vouchees = self.service.forgotten_users(node).await?;
-        let system_penalty = forgotten_penalties_sum(self.service, node, &vouchees).await?;
-        let vouchees_penalty =
-            penalty_from_vouchees(self.service, node, visited_branch, balances).await?;
+        let system_penalty = forgotten_penalties_sum(self.service, node, &vouchees).await?
+            + self.service.slashed_stakes_penalty(node).await?;
+        // vouchees of the deepest nodes may have penalties from other branches, they are
+        // not propagated either
+        let vouchees_penalty = match self.max_depth() {
+            Some(depth) if visited_branch.len() > depth => IdtAmount::ZERO,
+            _ => penalty_from_vouchees(self.service, node, visited_branch, balances).await?,
+        };
         Ok(proven_penalty + system_penalty + vouchees_penalty)
     }
 }
@@ -133,12 +144,18 @@ impl IdentityService {
         timestamp: u64,
     ) -> Result<(), Error> {
         let event = ModeratorProof {
-            moderator,
+            moderator: moderator.clone(),
             amount: balance,
             proof_id,
             timestamp,
         };
-        self.penalties.set_moderator_penalty(user, event).await
+        self.penalties
+            .set_moderator_penalty(user.clone(), event)
+            .await?;
+        self.record_moderator_outcome(&moderator, ModeratorOutcome::Penalty, 1)
+            .await?;
+        self.slash_stakes(&user, balance, timestamp).await?;
+        Ok(())
     }
 
     pub async fn punish_for_forgetting_with_timestamp(
@@ -154,7 +171,8 @@ impl IdentityService {
             )
             .expect("PENALTY_VOUCHEE_WEIGHT_RATIO denominator must not be zero");
             let vouchee_penalty = penalty(self, &vouchee).await?;
-            penalty_scale.mul(vouchee_penalty)
+            let waived = self.waived_penalty(&vouchee, timestamp).await?;
+            penalty_scale.mul_rounded(vouchee_penalty.saturating_sub(waived), self.config.rounding)
         };
         let event = SystemPenalty {
             amount: FORGET_PENALTY + vouchee_penalty,
@@ -165,6 +183,28 @@ impl IdentityService {
             .await
     }
 
+    // moderator penalty of the vouchee that is not passed to vouchers forgetting it within
+    // `forget_grace_period` after the punishment
+    async fn waived_penalty(
+        &self,
+        vouchee: &UserAddress,
+        timestamp: u64,
+    ) -> Result<IdtAmount, Error> {
+        let grace_period = self.config.forget_grace_period;
+        let Some(p) = self.moderator_penalty(vouchee).await? else {
+            return Ok(IdtAmount::ZERO);
+        };
+        if grace_period == 0 || timestamp > p.timestamp.saturating_add(grace_period) {
+            return Ok(IdtAmount::ZERO);
+        }
+        let amount = self
+            .weighted_moderator_penalty(vouchee)
+            .await?
+            .unwrap_or_default();
+        let decay = moderator_penalty_decay(self, vouchee).await?;
+        Ok(balance_after_decay(amount, decay))
+    }
+
     pub async fn moderator_penalty(
         &self,
         user: &UserAddress,
@@ -188,9 +228,24 @@ pub async fn punish(
     balance: IdtAmount,
     proof_id: ProofId,
 ) -> Result<(), Error> {
+    punish_with_reason(service, user, moderator, balance, proof_id, None).await
+}
+
+// `reason` is a code of `identity.penalty_reasons`, it replaces the reason of the previous
+// penalty of the user
+pub async fn punish_with_reason(
+    service: &IdentityService,
+    user: UserAddress,
+    moderator: UserAddress,
+    balance: IdtAmount,
+    proof_id: ProofId,
+    reason: Option<String>,
+) -> Result<(), Error> {
+    service.check_penalty_reason(reason.as_ref())?;
     service
-        .punish_with_timestamp(user, moderator, balance, proof_id, next_timestamp())
-        .await
+        .punish_with_timestamp(user.clone(), moderator, balance, proof_id, service.now())
+        .await?;
+    service.set_penalty_reason(&user, reason).await
 }
 
 pub async fn punish_for_forgetting(
@@ -199,7 +254,7 @@ pub async fn punish_for_forgetting(
     vouchee: UserAddress,
 ) -> Result<(), Error> {
     service
-        .punish_for_forgetting_with_timestamp(user, vouchee, next_timestamp())
+        .punish_for_forgetting_with_timestamp(user, vouchee, service.now())
         .await
 }
 
@@ -211,12 +266,13 @@ pub async fn penalty(service: &IdentityService, user: &UserAddress) -> Result<Id
 #[cfg(test)]
 mod tests {
     use crate::identity::{
-        IdentityService,
+        IdentityService, IdtAmount,
+        forget::forget,
         idt::balance,
         next_timestamp,
         proof::prove,
-        punish::{penalty, punish},
-        tests::{MODERATOR, PROOF_ID, USER_A},
+        punish::{FORGET_PENALTY, penalty, punish},
+        tests::{MODERATOR, PROOF_ID, USER_A, service_with_mock_clock},
         vouch::vouch,
     };
 
@@ -234,7 +290,7 @@ mod tests {
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            100,
+            IdtAmount::new(100),
             PROOF_ID,
         )
         .await
@@ -253,7 +309,7 @@ mod tests {
                 .unwrap()
                 .unwrap()
                 .amount,
-            100
+            IdtAmount::new(100)
         );
         assert_eq!(
             service
@@ -291,23 +347,32 @@ mod tests {
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            100,
+            IdtAmount::new(100),
             PROOF_ID,
         )
         .await
         .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 100);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(100)
+        );
         punish(
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            50,
+            IdtAmount::new(50),
             PROOF_ID,
         )
         .await
         .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 50);
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 50);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(50)
+        );
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(50)
+        );
     }
 
     #[async_std::test]
@@ -317,24 +382,33 @@ mod tests {
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            100,
+            IdtAmount::new(100),
             PROOF_ID,
         )
         .await
         .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 100);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(100)
+        );
         punish(
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            200,
+            IdtAmount::new(200),
             PROOF_ID,
         )
         .await
         .unwrap();
         // cannot go lower than 0
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 0);
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 200);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(200)
+        );
     }
 
     #[async_std::test]
@@ -345,7 +419,7 @@ mod tests {
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            100,
+            IdtAmount::new(100),
             PROOF_ID,
         )
         .await
@@ -354,7 +428,7 @@ mod tests {
             &service,
             user_b.to_string(),
             MODERATOR.to_string(),
-            200,
+            IdtAmount::new(200),
             PROOF_ID,
         )
         .await
@@ -362,26 +436,97 @@ mod tests {
         vouch(&service, USER_A.to_string(), user_b.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 100);
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 210);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(100)
+        );
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(210)
+        );
         punish(
             &service,
             user_b.to_string(),
             MODERATOR.to_string(),
-            50,
+            IdtAmount::new(50),
             PROOF_ID,
         )
         .await
         .unwrap();
         // 100 - 0.1 * 50
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 95);
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 5);
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(95)
+        );
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(5)
+        );
         // penalty affects vouchee twice:
         // first, from the direct punishment
         // second, from the voucher reduced balance from the vouchee penalty
         // 200 - 50 + 0.1 * 95
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 159);
-        assert_eq!(penalty(&service, &user_b.to_string()).await.unwrap(), 50);
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::from_milli(159_500)
+        );
+        assert_eq!(
+            penalty(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(50)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_max_penalty_depth() {
+        let (user_b, user_c) = ("userB", "userC");
+        let mut service = IdentityService::default();
+        vouch(&service, USER_A.to_string(), user_b.to_string())
+            .await
+            .unwrap();
+        vouch(&service, user_b.to_string(), user_c.to_string())
+            .await
+            .unwrap();
+        punish(
+            &service,
+            user_c.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            penalty(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(100)
+        );
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(10)
+        );
+
+        service.config.max_penalty_depth = Some(1);
+        assert_eq!(
+            penalty(&service, &user_c.to_string()).await.unwrap(),
+            IdtAmount::new(1000)
+        );
+        assert_eq!(
+            penalty(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(100)
+        );
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+
+        service.config.max_penalty_depth = Some(0);
+        assert_eq!(
+            penalty(&service, &user_c.to_string()).await.unwrap(),
+            IdtAmount::new(1000)
+        );
+        assert_eq!(
+            penalty(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
     }
 
     #[async_std::test]
@@ -392,7 +537,7 @@ mod tests {
             &service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            100,
+            IdtAmount::new(100),
             PROOF_ID,
         )
         .await
@@ -401,7 +546,7 @@ mod tests {
             &service,
             user_b.to_string(),
             MODERATOR.to_string(),
-            200,
+            IdtAmount::new(200),
             PROOF_ID,
         )
         .await
@@ -409,40 +554,58 @@ mod tests {
         vouch(&service, USER_A.to_string(), user_b.to_string())
             .await
             .unwrap();
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 210);
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(210)
+        );
         punish(
             &service,
             user_b.to_string(),
             MODERATOR.to_string(),
-            50000,
+            IdtAmount::new(50000),
             PROOF_ID,
         )
         .await
         .unwrap();
-        assert_eq!(penalty(&service, &user_b.to_string()).await.unwrap(), 50000);
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 5000);
+        assert_eq!(
+            penalty(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(50000)
+        );
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(5000)
+        );
         // balance is zero due to very high penalty
-        assert_eq!(balance(&service, &user_b.to_string()).await.unwrap(), 0);
-        assert_eq!(balance(&service, &USER_A.to_string()).await.unwrap(), 0);
+        assert_eq!(
+            balance(&service, &user_b.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+        assert_eq!(
+            balance(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
         punish(
             &service,
             user_b.to_string(),
             MODERATOR.to_string(),
-            100000,
+            IdtAmount::new(100000),
             PROOF_ID,
         )
         .await
         .unwrap();
         assert_eq!(
             penalty(&service, &user_b.to_string()).await.unwrap(),
-            100000
+            IdtAmount::new(100000)
+        );
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(10000)
         );
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 10000);
         punish(
             &service,
             user_b.to_string(),
             MODERATOR.to_string(),
-            150000,
+            IdtAmount::new(150000),
             PROOF_ID,
         )
         .await
@@ -451,9 +614,12 @@ mod tests {
         // penalty from vouchees is limited to 2 * MAX_IDT_BY_PROOF
         assert_eq!(
             penalty(&service, &user_b.to_string()).await.unwrap(),
-            150000
+            IdtAmount::new(150000)
+        );
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(10000)
         );
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 10000);
     }
 
     #[async_std::test]
@@ -461,12 +627,18 @@ mod tests {
         let service = IdentityService::default();
         let user_b = "userB";
         let ts = next_timestamp();
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 0);
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
         service
             .punish_for_forgetting_with_timestamp(USER_A.to_string(), user_b.to_string(), ts)
             .await
             .unwrap();
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 500);
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(500)
+        );
         service
             .punish_for_forgetting_with_timestamp(
                 USER_A.to_string(),
@@ -475,7 +647,10 @@ mod tests {
             )
             .await
             .unwrap();
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 499);
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(499)
+        );
         assert!(
             service
                 .forgotten_penalty(&USER_A.to_string(), &user_b.to_string())
@@ -491,7 +666,10 @@ mod tests {
             )
             .await
             .unwrap();
-        assert_eq!(penalty(&service, &USER_A.to_string()).await.unwrap(), 0);
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
         // forgotten penalty is cleaned up
         assert!(
             service
@@ -501,4 +679,50 @@ mod tests {
                 .is_none()
         );
     }
+
+    #[async_std::test]
+    async fn test_forget_grace_period() {
+        let (mut service, clock) = service_with_mock_clock();
+        service.config.forget_grace_period = 3600;
+        for vouchee in ["userB", "userC"] {
+            vouch(&service, USER_A.to_string(), vouchee.to_string())
+                .await
+                .unwrap();
+            punish(
+                &service,
+                vouchee.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(1000),
+                PROOF_ID,
+            )
+            .await
+            .unwrap();
+        }
+
+        // forgotten in time, only the forget penalty applies
+        forget(&service, USER_A.to_string(), "userB".to_string())
+            .await
+            .unwrap();
+        let forgotten = service
+            .forgotten_penalty(&USER_A.to_string(), &"userB".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(forgotten.amount, FORGET_PENALTY);
+
+        clock.advance(3601);
+        let vouchee_penalty = penalty(&service, &"userC".to_string()).await.unwrap();
+        forget(&service, USER_A.to_string(), "userC".to_string())
+            .await
+            .unwrap();
+        let forgotten = service
+            .forgotten_penalty(&USER_A.to_string(), &"userC".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            forgotten.amount,
+            FORGET_PENALTY + IdtAmount::new(vouchee_penalty.units() / 10)
+        );
+    }
 }
@@ -5,6 +5,27 @@ use async_trait::async_trait;
 
 use crate::identity::{ModeratorProof, SystemPenalty, UserAddress, error::Error};
 
+// mutation of penalties, see `PenaltyStorage::write_batch`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PenaltyWrite {
+    SetModeratorPenalty {
+        user: UserAddress,
+        proof: ModeratorProof,
+    },
+    RemoveModeratorPenalty {
+        user: UserAddress,
+    },
+    SetForgottenPenalty {
+        user: UserAddress,
+        vouchee: UserAddress,
+        penalty: SystemPenalty,
+    },
+    RemoveForgotten {
+        user: UserAddress,
+        forgotten: UserAddress,
+    },
+}
+
 #[async_trait]
 pub trait PenaltyStorage: Send + Sync {
     async fn set_moderator_penalty(
@@ -24,12 +45,54 @@ pub trait PenaltyStorage: Send + Sync {
         forgotten: &UserAddress,
     ) -> Result<(), Error>;
     async fn moderator_penalty(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error>;
+    async fn remove_moderator_penalty(&self, user: &UserAddress) -> Result<(), Error>;
     async fn forgotten_penalty(
         &self,
         user: &UserAddress,
         forgotten: &UserAddress,
     ) -> Result<Option<SystemPenalty>, Error>;
     async fn forgotten_users(&self, user: &UserAddress) -> Result<HashSet<UserAddress>, Error>;
+    async fn all_moderator_penalties(&self) -> Result<Vec<(UserAddress, ModeratorProof)>, Error>;
+    // at most `limit` moderator penalties, newest first
+    async fn recent_moderator_penalties(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<(UserAddress, ModeratorProof)>, Error> {
+        let mut penalties = self.all_moderator_penalties().await?;
+        penalties.sort_by(|a, b| {
+            (b.1.timestamp, b.1.proof_id, &b.0).cmp(&(a.1.timestamp, a.1.proof_id, &a.0))
+        });
+        penalties.truncate(limit);
+        Ok(penalties)
+    }
+    // (punished user, forgotten user, penalty)
+    async fn all_forgotten_penalties(
+        &self,
+    ) -> Result<Vec<(UserAddress, UserAddress, SystemPenalty)>, Error>;
+
+    // applies the writes in order. Storages with transactions apply all or none of them,
+    // others may stop after a part of the writes.
+    async fn write_batch(&self, writes: Vec<PenaltyWrite>) -> Result<(), Error> {
+        for write in writes {
+            match write {
+                PenaltyWrite::SetModeratorPenalty { user, proof } => {
+                    self.set_moderator_penalty(user, proof).await?
+                }
+                PenaltyWrite::RemoveModeratorPenalty { user } => {
+                    self.remove_moderator_penalty(&user).await?
+                }
+                PenaltyWrite::SetForgottenPenalty {
+                    user,
+                    vouchee,
+                    penalty,
+                } => self.set_forgotten_penalty(user, vouchee, penalty).await?,
+                PenaltyWrite::RemoveForgotten { user, forgotten } => {
+                    self.remove_forgotten(user, &forgotten).await?
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Default)]
@@ -90,6 +153,11 @@ impl PenaltyStorage for InMemoryPenaltyStorage {
         Ok(self.moderator_penalty.read().await.get(user).cloned())
     }
 
+    async fn remove_moderator_penalty(&self, user: &UserAddress) -> Result<(), Error> {
+        self.moderator_penalty.write().await.remove(user);
+        Ok(())
+    }
+
     async fn forgotten_penalty(
         &self,
         user: &UserAddress,
@@ -114,11 +182,38 @@ impl PenaltyStorage for InMemoryPenaltyStorage {
             .into_keys()
             .collect())
     }
+
+    async fn all_moderator_penalties(&self) -> Result<Vec<(UserAddress, ModeratorProof)>, Error> {
+        Ok(self
+            .moderator_penalty
+            .read()
+            .await
+            .iter()
+            .map(|(user, penalty)| (user.clone(), penalty.clone()))
+            .collect())
+    }
+
+    async fn all_forgotten_penalties(
+        &self,
+    ) -> Result<Vec<(UserAddress, UserAddress, SystemPenalty)>, Error> {
+        Ok(self
+            .forget_penalties
+            .read()
+            .await
+            .iter()
+            .flat_map(|(user, penalties)| {
+                penalties.iter().map(move |(forgotten, penalty)| {
+                    (user.clone(), forgotten.clone(), penalty.clone())
+                })
+            })
+            .collect())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::identity::IdtAmount;
 
     #[async_std::test]
     async fn test_basic() {
@@ -128,7 +223,7 @@ mod tests {
 
         let proof1 = ModeratorProof {
             moderator: "mod".to_string(),
-            amount: 1,
+            amount: IdtAmount::new(1),
             proof_id: 1,
             timestamp: 2,
         };
@@ -144,7 +239,7 @@ mod tests {
 
         let proof2 = ModeratorProof {
             moderator: "mod2".to_string(),
-            amount: 3,
+            amount: IdtAmount::new(3),
             proof_id: 2,
             timestamp: 4,
         };
@@ -167,7 +262,7 @@ mod tests {
         );
 
         let penalty1 = SystemPenalty {
-            amount: 5,
+            amount: IdtAmount::new(5),
             timestamp: 6,
         };
         storage
@@ -190,7 +285,7 @@ mod tests {
         );
 
         let penalty2 = SystemPenalty {
-            amount: 7,
+            amount: IdtAmount::new(7),
             timestamp: 8,
         };
         storage
@@ -224,4 +319,29 @@ mod tests {
                 .contains(&vouchee)
         );
     }
+
+    #[async_std::test]
+    async fn test_recent() {
+        let storage = InMemoryPenaltyStorage::default();
+        for (user, timestamp) in [("a", 3), ("b", 1), ("c", 2)] {
+            let proof = ModeratorProof {
+                moderator: "mod".to_string(),
+                amount: IdtAmount::new(1),
+                proof_id: 1,
+                timestamp,
+            };
+            storage
+                .set_moderator_penalty(user.to_string(), proof)
+                .await
+                .unwrap();
+        }
+        let recent: Vec<_> = storage
+            .recent_moderator_penalties(2)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(user, p)| (user, p.timestamp))
+            .collect();
+        assert_eq!(recent, vec![("a".to_string(), 3), ("c".to_string(), 2)]);
+    }
 }
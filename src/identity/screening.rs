@@ -0,0 +1,145 @@
+// Screening of addresses before they accrue reputation.
+//
+// Operators with regulatory obligations can block addresses from receiving vouches and
+// proofs. Blocked addresses keep the reputation they already have, but nothing new is
+// accepted for them. `NoScreening` is used unless `screening.denylist` is configured.
+
+use std::{collections::HashSet, io, sync::Arc};
+
+use async_std::fs;
+use async_trait::async_trait;
+
+use crate::{
+    config::ScreeningSection,
+    identity::{IdentityService, UserAddress, error::Error},
+};
+
+#[async_trait]
+pub trait Screening: Send + Sync {
+    async fn is_blocked(&self, address: &UserAddress) -> Result<bool, Error>;
+}
+
+#[derive(Default)]
+pub struct NoScreening;
+
+#[async_trait]
+impl Screening for NoScreening {
+    async fn is_blocked(&self, _address: &UserAddress) -> Result<bool, Error> {
+        Ok(false)
+    }
+}
+
+// blocks the addresses listed in a file, one per line. Empty lines and lines starting with
+// `#` are ignored, addresses are compared case-insensitively.
+#[derive(Default)]
+pub struct DenylistScreening {
+    addresses: HashSet<UserAddress>,
+}
+
+impl DenylistScreening {
+    pub fn parse(content: &str) -> Self {
+        let addresses = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_lowercase)
+            .collect();
+        Self { addresses }
+    }
+
+    pub async fn load(path: &str) -> Result<Self, io::Error> {
+        let content = fs::read_to_string(path).await?;
+        Ok(Self::parse(&content))
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+}
+
+#[async_trait]
+impl Screening for DenylistScreening {
+    async fn is_blocked(&self, address: &UserAddress) -> Result<bool, Error> {
+        Ok(self.addresses.contains(&address.to_lowercase()))
+    }
+}
+
+// screening selected by the configuration
+pub async fn screening(config: &ScreeningSection) -> Result<Arc<dyn Screening>, io::Error> {
+    let Some(path) = &config.denylist else {
+        return Ok(Arc::new(NoScreening));
+    };
+    let denylist = DenylistScreening::load(path).await?;
+    log::info!("Loaded {} blocked addresses from {}", denylist.len(), path);
+    Ok(Arc::new(denylist))
+}
+
+impl IdentityService {
+    // fails if the address may not accrue reputation
+    pub async fn screen(&self, address: &UserAddress) -> Result<(), Error> {
+        if self.screening.is_blocked(address).await? {
+            return Err(Error::AddressBlocked(address.clone()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{
+        IdtAmount,
+        proof::prove,
+        tests::{MODERATOR, PROOF_ID, USER_A, service_with_mock_clock},
+        vouch::vouch,
+    };
+
+    #[test]
+    fn test_parse() {
+        let denylist = DenylistScreening::parse("# sanctioned\n\n 0xABC \n0xdef\n");
+        assert_eq!(denylist.len(), 2);
+    }
+
+    #[async_std::test]
+    async fn test_blocked() {
+        let (service, _) = service_with_mock_clock();
+        let service = IdentityService {
+            screening: Arc::new(DenylistScreening::parse("userB\n")),
+            ..service
+        };
+        assert!(service.screening.is_blocked(&"USERB".into()).await.unwrap());
+
+        assert!(matches!(
+            vouch(&service, USER_A.into(), "userB".into()).await,
+            Err(Error::AddressBlocked(_))
+        ));
+        assert!(matches!(
+            prove(
+                &service,
+                "userB".into(),
+                MODERATOR.into(),
+                IdtAmount::new(100),
+                PROOF_ID
+            )
+            .await,
+            Err(Error::AddressBlocked(_))
+        ));
+        assert!(
+            service
+                .vouchers_with_time(&"userB".into())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(service.proof(&"userB".into()).await.unwrap().is_none());
+
+        // blocked addresses can still vouch for others
+        vouch(&service, "userB".into(), USER_A.into())
+            .await
+            .unwrap();
+    }
+}
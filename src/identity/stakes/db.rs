@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::{
+        IdtAmount, SystemPenalty, UserAddress, error::Error, stakes::storage::StakeStorage,
+    },
+    numbers::rescale_column,
+    pools::{self, MeteredPool},
+};
+
+pub struct DatabaseStakeStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabaseStakeStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("stakes", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS slashed_stakes (voucher TEXT NOT NULL, vouchee TEXT NOT NULL, amount INTEGER NOT NULL, timestamp INTEGER NOT NULL, PRIMARY KEY(voucher, vouchee))",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "slashed_stakes", "voucher").await?;
+        rotate_column(&pool, &cipher, "slashed_stakes", "vouchee").await?;
+        rescale_column(&pool, "slashed_stakes", "amount").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl StakeStorage for DatabaseStakeStorage {
+    async fn set_slashed_stake(
+        &self,
+        voucher: UserAddress,
+        vouchee: UserAddress,
+        penalty: SystemPenalty,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            "REPLACE INTO slashed_stakes (voucher, vouchee, amount, timestamp) VALUES (?, ?, ?, ?)",
+        )
+        .bind(self.cipher.encode(&voucher))
+        .bind(self.cipher.encode(&vouchee))
+        .bind(penalty.amount.milli() as i64)
+        .bind(penalty.timestamp as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn slashed_stake(
+        &self,
+        voucher: &UserAddress,
+        vouchee: &UserAddress,
+    ) -> Result<Option<SystemPenalty>, Error> {
+        let row = sqlx::query(
+            "SELECT amount, timestamp FROM slashed_stakes WHERE voucher = ? AND vouchee = ?",
+        )
+        .bind(self.cipher.encode(voucher))
+        .bind(self.cipher.encode(vouchee))
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|r| SystemPenalty {
+            amount: IdtAmount::from_milli(r.get::<i64, _>(0) as u64),
+            timestamp: r.get::<i64, _>(1) as u64,
+        }))
+    }
+
+    async fn slashed_stakes(
+        &self,
+        voucher: &UserAddress,
+    ) -> Result<HashMap<UserAddress, SystemPenalty>, Error> {
+        let rows =
+            sqlx::query("SELECT vouchee, amount, timestamp FROM slashed_stakes WHERE voucher = ?")
+                .bind(self.cipher.encode(voucher))
+                .fetch_all(&self.pool)
+                .await?;
+        let mut stakes = HashMap::new();
+        for r in rows {
+            stakes.insert(
+                self.cipher.decode(&r.get::<String, _>(0))?,
+                SystemPenalty {
+                    amount: IdtAmount::from_milli(r.get::<i64, _>(1) as u64),
+                    timestamp: r.get::<i64, _>(2) as u64,
+                },
+            );
+        }
+        Ok(stakes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseStakeStorage::new("sqlite::memory:").await.unwrap();
+        let (voucher, vouchee) = ("a".to_string(), "b".to_string());
+        let penalty = SystemPenalty {
+            amount: IdtAmount::new(100),
+            timestamp: 10,
+        };
+        assert_eq!(
+            storage.slashed_stake(&voucher, &vouchee).await.unwrap(),
+            None
+        );
+        storage
+            .set_slashed_stake(voucher.clone(), vouchee.clone(), penalty.clone())
+            .await
+            .unwrap();
+        let replaced = SystemPenalty {
+            amount: IdtAmount::new(200),
+            ..penalty
+        };
+        storage
+            .set_slashed_stake(voucher.clone(), vouchee.clone(), replaced.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.slashed_stake(&voucher, &vouchee).await.unwrap(),
+            Some(replaced.clone())
+        );
+        assert_eq!(
+            storage.slashed_stakes(&voucher).await.unwrap(),
+            HashMap::from([(vouchee, replaced)])
+        );
+    }
+}
@@ -0,0 +1,243 @@
+// Voucher stakes. With `identity.stake_slashing.enabled`, every vouch stakes a share of the
+// voucher's proven balance on the vouchee. Once the vouchee receives a moderator penalty of
+// at least `threshold` IDT, the stakes of its vouchers are slashed: each stake becomes a
+// system penalty of the voucher that decays like forget penalties. A stake is slashed once,
+// later penalties of the same vouchee do not slash it again. Slashed stakes are ignored
+// while the mode is disabled.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    identity::{
+        IdentityService, IdtAmount, SystemPenalty, UserAddress,
+        decay::{balance_after_decay, system_penalty_decay},
+        error::Error,
+        idt::proven_balance,
+        vouch::vouchers,
+    },
+    numbers::Rational,
+};
+
+#[cfg(feature = "storage-sql")]
+pub mod db;
+pub mod storage;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct StakeSlashingPolicy {
+    pub enabled: bool,
+    // share of the voucher's proven balance staked on every vouchee
+    pub stake: Rational,
+    // minimum moderator penalty of the vouchee that slashes the stakes
+    pub threshold: IdtAmount,
+}
+
+impl Default for StakeSlashingPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            stake: Rational::new(1, 10).expect("stake denominator must not be zero"),
+            threshold: IdtAmount::new(1000),
+        }
+    }
+}
+
+impl StakeSlashingPolicy {
+    pub fn stake_of(&self, balance: IdtAmount) -> IdtAmount {
+        // zero denominator would panic on multiplication
+        if self.stake.denominator() == 0 {
+            return IdtAmount::ZERO;
+        }
+        self.stake.mul(balance)
+    }
+}
+
+impl IdentityService {
+    // slashes the stakes of the vouchers of a user punished with `amount`, returns the
+    // vouchers whose stakes were slashed
+    pub async fn slash_stakes(
+        &self,
+        user: &UserAddress,
+        amount: IdtAmount,
+        timestamp: u64,
+    ) -> Result<Vec<UserAddress>, Error> {
+        let policy = &self.config.stake_slashing;
+        if !policy.enabled || amount < policy.threshold {
+            return Ok(vec![]);
+        }
+        let mut slashed = vec![];
+        for voucher in vouchers(self, user).await? {
+            if self.stakes.slashed_stake(&voucher, user).await?.is_some() {
+                continue;
+            }
+            let stake = policy.stake_of(proven_balance(self, &voucher).await?);
+            if stake.is_zero() {
+                continue;
+            }
+            let penalty = SystemPenalty {
+                amount: stake,
+                timestamp,
+            };
+            self.stakes
+                .set_slashed_stake(voucher.clone(), user.clone(), penalty)
+                .await?;
+            slashed.push(voucher);
+        }
+        Ok(slashed)
+    }
+
+    // slashed stakes of the voucher by vouchee, before decay
+    pub async fn slashed_stakes(
+        &self,
+        voucher: &UserAddress,
+    ) -> Result<Vec<(UserAddress, SystemPenalty)>, Error> {
+        // no lookups while the mode is disabled
+        if !self.config.stake_slashing.enabled {
+            return Ok(vec![]);
+        }
+        let mut stakes: Vec<_> = self
+            .stakes
+            .slashed_stakes(voucher)
+            .await?
+            .into_iter()
+            .collect();
+        stakes.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(stakes)
+    }
+
+    // penalty of the voucher from its slashed stakes, after decay
+    pub async fn slashed_stakes_penalty(&self, voucher: &UserAddress) -> Result<IdtAmount, Error> {
+        let now = self.now();
+        Ok(self
+            .slashed_stakes(voucher)
+            .await?
+            .iter()
+            .map(|(_, p)| balance_after_decay(p.amount, system_penalty_decay(self, p, now)))
+            .sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::IdentitySection,
+        identity::{
+            decay::DAY,
+            proof::prove,
+            punish::{penalty, punish},
+            tests::{MODERATOR, PROOF_ID, USER_A, service_with_mock_clock},
+            vouch::vouch,
+        },
+    };
+
+    #[async_std::test]
+    async fn test_slashing() {
+        let (mut service, clock) = service_with_mock_clock();
+        service.config = IdentitySection {
+            stake_slashing: StakeSlashingPolicy {
+                enabled: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (user_b, user_c) = ("userB".to_string(), "userC".to_string());
+        prove(
+            &service,
+            USER_A.into(),
+            MODERATOR.into(),
+            IdtAmount::new(5000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, USER_A.into(), user_b.clone())
+            .await
+            .unwrap();
+        vouch(&service, USER_A.into(), user_c.clone())
+            .await
+            .unwrap();
+
+        // penalties below the threshold keep the stakes
+        punish(
+            &service,
+            user_b.clone(),
+            MODERATOR.into(),
+            IdtAmount::new(999),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            penalty(&service, &USER_A.into()).await.unwrap(),
+            IdtAmount::from_milli(99_900)
+        );
+
+        punish(
+            &service,
+            user_b.clone(),
+            MODERATOR.into(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            service.slashed_stakes(&USER_A.into()).await.unwrap(),
+            vec![(
+                user_b.clone(),
+                SystemPenalty {
+                    amount: IdtAmount::new(500),
+                    timestamp: service.now(),
+                }
+            )]
+        );
+        // 10% of the vouchee penalty and the slashed stake
+        assert_eq!(
+            penalty(&service, &USER_A.into()).await.unwrap(),
+            IdtAmount::new(600)
+        );
+
+        // a stake is slashed once
+        clock.advance(10 * DAY);
+        punish(
+            &service,
+            user_b.clone(),
+            MODERATOR.into(),
+            IdtAmount::new(2000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            service
+                .slashed_stakes_penalty(&USER_A.into())
+                .await
+                .unwrap(),
+            IdtAmount::new(490)
+        );
+
+        punish(
+            &service,
+            user_c,
+            MODERATOR.into(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            service.slashed_stakes(&USER_A.into()).await.unwrap().len(),
+            2
+        );
+
+        service.config.stake_slashing.enabled = false;
+        assert_eq!(
+            service
+                .slashed_stakes_penalty(&USER_A.into())
+                .await
+                .unwrap(),
+            IdtAmount::new(0)
+        );
+    }
+}
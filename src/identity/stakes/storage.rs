@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::identity::{SystemPenalty, UserAddress, error::Error};
+
+#[async_trait]
+pub trait StakeStorage: Send + Sync {
+    // replaces the slashed stake of the voucher on the vouchee
+    async fn set_slashed_stake(
+        &self,
+        voucher: UserAddress,
+        vouchee: UserAddress,
+        penalty: SystemPenalty,
+    ) -> Result<(), Error>;
+    async fn slashed_stake(
+        &self,
+        voucher: &UserAddress,
+        vouchee: &UserAddress,
+    ) -> Result<Option<SystemPenalty>, Error>;
+    // slashed stakes of the voucher by vouchee
+    async fn slashed_stakes(
+        &self,
+        voucher: &UserAddress,
+    ) -> Result<HashMap<UserAddress, SystemPenalty>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryStakeStorage {
+    // voucher -> vouchee -> penalty
+    stakes: RwLock<HashMap<UserAddress, HashMap<UserAddress, SystemPenalty>>>,
+}
+
+#[async_trait]
+impl StakeStorage for InMemoryStakeStorage {
+    async fn set_slashed_stake(
+        &self,
+        voucher: UserAddress,
+        vouchee: UserAddress,
+        penalty: SystemPenalty,
+    ) -> Result<(), Error> {
+        self.stakes
+            .write()
+            .await
+            .entry(voucher)
+            .or_default()
+            .insert(vouchee, penalty);
+        Ok(())
+    }
+
+    async fn slashed_stake(
+        &self,
+        voucher: &UserAddress,
+        vouchee: &UserAddress,
+    ) -> Result<Option<SystemPenalty>, Error> {
+        Ok(self
+            .stakes
+            .read()
+            .await
+            .get(voucher)
+            .and_then(|stakes| stakes.get(vouchee))
+            .cloned())
+    }
+
+    async fn slashed_stakes(
+        &self,
+        voucher: &UserAddress,
+    ) -> Result<HashMap<UserAddress, SystemPenalty>, Error> {
+        Ok(self
+            .stakes
+            .read()
+            .await
+            .get(voucher)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::IdtAmount;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryStakeStorage::default();
+        let (voucher, vouchee) = ("a".to_string(), "b".to_string());
+        let penalty = SystemPenalty {
+            amount: IdtAmount::new(100),
+            timestamp: 10,
+        };
+        assert_eq!(
+            storage.slashed_stake(&voucher, &vouchee).await.unwrap(),
+            None
+        );
+        storage
+            .set_slashed_stake(voucher.clone(), vouchee.clone(), penalty.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.slashed_stake(&voucher, &vouchee).await.unwrap(),
+            Some(penalty.clone())
+        );
+        assert_eq!(
+            storage.slashed_stakes(&voucher).await.unwrap(),
+            HashMap::from([(vouchee, penalty)])
+        );
+        assert!(
+            storage
+                .slashed_stakes(&"b".into())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,318 @@
+// Part of the graph that a balance depends on, exported so clients can recompute the balance
+// without trusting the server. Balances depend on the vouchers of the user recursively and
+// penalties on the vouchees of every such voucher, so both closures are exported.
+// Only the vouch tree strategy can be recomputed, PageRank depends on the whole graph.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::IdentitySection,
+    identity::{
+        IdentityService, IdtAmount, ModeratorProof, SystemPenalty, UserAddress,
+        clock::FixedClock,
+        debits::FixedDebits,
+        error::Error,
+        idt::balance,
+        moderators::{ModeratorOutcome, ModeratorStats},
+        vouch::{vouchees, vouchers},
+    },
+    scoring::strategy::VouchTreeStrategy,
+};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Subgraph {
+    pub user: UserAddress,
+    // time the balance is computed at
+    pub timestamp: u64,
+    pub config: IdentitySection,
+    pub proofs: BTreeMap<UserAddress, ModeratorProof>,
+    pub genesis: BTreeMap<UserAddress, IdtAmount>,
+    // (voucher, vouchee, timestamp)
+    pub vouches: Vec<(UserAddress, UserAddress, u64)>,
+    pub moderator_penalties: BTreeMap<UserAddress, ModeratorProof>,
+    // (user, forgotten user, penalty)
+    pub forgotten_penalties: Vec<(UserAddress, UserAddress, SystemPenalty)>,
+    pub moderator_stats: BTreeMap<UserAddress, ModeratorStats>,
+    pub categories: BTreeMap<UserAddress, String>,
+    // only exported with `identity.inactivity_period` set
+    #[serde(default)]
+    pub last_active: BTreeMap<UserAddress, u64>,
+    // reason codes of moderator penalties
+    #[serde(default)]
+    pub penalty_reasons: BTreeMap<UserAddress, String>,
+    // (voucher, vouchee, penalty), only exported with `identity.stake_slashing` enabled
+    #[serde(default)]
+    pub slashed_stakes: Vec<(UserAddress, UserAddress, SystemPenalty)>,
+    // IDT given up by the users, e.g. pledges to the restitution pool
+    #[serde(default)]
+    pub debits: BTreeMap<UserAddress, IdtAmount>,
+}
+
+// users reachable from `roots` by `next`, roots included
+async fn closure<F, Fut>(
+    roots: BTreeSet<UserAddress>,
+    next: F,
+) -> Result<BTreeSet<UserAddress>, Error>
+where
+    F: Fn(UserAddress) -> Fut,
+    Fut: Future<Output = Result<Vec<UserAddress>, Error>>,
+{
+    let mut reached = roots.clone();
+    let mut queue: Vec<_> = roots.into_iter().collect();
+    while let Some(user) = queue.pop() {
+        for other in next(user).await? {
+            if reached.insert(other.clone()) {
+                queue.push(other);
+            }
+        }
+    }
+    Ok(reached)
+}
+
+// exports everything the balance of the user depends on at the current time
+pub async fn export_subgraph(
+    service: &IdentityService,
+    user: &UserAddress,
+) -> Result<Subgraph, Error> {
+    let balance_users = closure(BTreeSet::from([user.clone()]), |user| async move {
+        vouchers(service, &user).await
+    })
+    .await?;
+    let penalty_users = closure(balance_users.clone(), |user| async move {
+        vouchees(service, &user).await
+    })
+    .await?;
+
+    let mut subgraph = Subgraph {
+        user: user.clone(),
+        timestamp: service.now(),
+        config: service.config.clone(),
+        ..Default::default()
+    };
+    let mut moderators = BTreeSet::new();
+    for user in &balance_users {
+        for (voucher, timestamp) in service.vouchers_with_time(user).await? {
+            subgraph.vouches.push((voucher, user.clone(), timestamp));
+        }
+        if let Some(proof) = service.proof(user).await? {
+            moderators.insert(proof.moderator.clone());
+            subgraph.proofs.insert(user.clone(), proof);
+        }
+        if let Some(genesis) = service.genesis_balance(user).await? {
+            subgraph.genesis.insert(user.clone(), genesis);
+        }
+        if let Some(category) = service.category(user).await? {
+            subgraph.categories.insert(user.clone(), category);
+        }
+        if service.config.inactivity_period != 0 {
+            if let Some(last_active) = service.last_active(user).await? {
+                subgraph.last_active.insert(user.clone(), last_active);
+            }
+        }
+        let debit = service.debit(user).await?;
+        if !debit.is_zero() {
+            subgraph.debits.insert(user.clone(), debit);
+        }
+    }
+    for user in &penalty_users {
+        for (vouchee, timestamp) in service.vouchees_with_time(user).await? {
+            // vouches to users of the balance closure are exported with their vouchers
+            if !balance_users.contains(&vouchee) {
+                subgraph.vouches.push((user.clone(), vouchee, timestamp));
+            }
+        }
+        if let Some(penalty) = service.moderator_penalty(user).await? {
+            subgraph.moderator_penalties.insert(user.clone(), penalty);
+            if let Some(reason) = service.penalty_reason(user).await? {
+                subgraph.penalty_reasons.insert(user.clone(), reason);
+            }
+        }
+        for forgotten in service.forgotten_users(user).await? {
+            if let Some(penalty) = service.forgotten_penalty(user, &forgotten).await? {
+                subgraph
+                    .forgotten_penalties
+                    .push((user.clone(), forgotten, penalty));
+            }
+        }
+        for (vouchee, penalty) in service.slashed_stakes(user).await? {
+            subgraph
+                .slashed_stakes
+                .push((user.clone(), vouchee, penalty));
+        }
+    }
+    if service.config.reputation_weighted_proofs {
+        for moderator in moderators {
+            let stats = service.moderator_stats(&moderator).await?;
+            subgraph.moderator_stats.insert(moderator, stats);
+        }
+    }
+    subgraph.vouches.sort();
+    subgraph
+        .forgotten_penalties
+        .sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+    subgraph
+        .slashed_stakes
+        .sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+    Ok(subgraph)
+}
+
+impl Subgraph {
+    // in-memory service holding the subgraph with the clock stopped at its timestamp
+    pub async fn service(&self) -> Result<IdentityService, Error> {
+        let service = IdentityService {
+            clock: Arc::new(FixedClock(self.timestamp)),
+            config: self.config.clone(),
+            strategy: Arc::new(VouchTreeStrategy),
+            debits: Arc::new(FixedDebits(HashMap::from_iter(self.debits.clone()))),
+            ..Default::default()
+        };
+        service
+            .proofs
+            .set_genesis(HashMap::from_iter(self.genesis.clone()))
+            .await?;
+        for (user, proof) in &self.proofs {
+            service
+                .proofs
+                .set_proof(user.clone(), proof.clone())
+                .await?;
+        }
+        for (voucher, vouchee, timestamp) in &self.vouches {
+            service
+                .vouches
+                .vouch(voucher.clone(), vouchee.clone(), *timestamp)
+                .await?;
+        }
+        for (user, penalty) in &self.moderator_penalties {
+            service
+                .penalties
+                .set_moderator_penalty(user.clone(), penalty.clone())
+                .await?;
+        }
+        for (user, forgotten, penalty) in &self.forgotten_penalties {
+            service
+                .penalties
+                .set_forgotten_penalty(user.clone(), forgotten.clone(), penalty.clone())
+                .await?;
+        }
+        for (voucher, vouchee, penalty) in &self.slashed_stakes {
+            service
+                .stakes
+                .set_slashed_stake(voucher.clone(), vouchee.clone(), penalty.clone())
+                .await?;
+        }
+        for (moderator, stats) in &self.moderator_stats {
+            for (outcome, count) in [
+                (ModeratorOutcome::Proof, stats.proofs),
+                (ModeratorOutcome::Penalty, stats.penalties),
+                (ModeratorOutcome::RevokedProof, stats.revoked_proofs),
+            ] {
+                service
+                    .record_moderator_outcome(moderator, outcome, count)
+                    .await?;
+            }
+        }
+        for (user, category) in &self.categories {
+            service
+                .categories
+                .set_category(user, Some(category.clone()))
+                .await?;
+        }
+        for (user, last_active) in &self.last_active {
+            service.activity.set_last_active(user, *last_active).await?;
+        }
+        for (user, reason) in &self.penalty_reasons {
+            service
+                .penalty_reasons
+                .set_reason(user, Some(reason.clone()))
+                .await?;
+        }
+        Ok(service)
+    }
+
+    // balance of the exported user recomputed from the subgraph alone
+    pub async fn balance(&self) -> Result<IdtAmount, Error> {
+        balance(&self.service().await?, &self.user).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{
+        forget::forget,
+        proof::{MAX_IDT_BY_PROOF, prove},
+        punish::punish,
+        tests::{MODERATOR, PROOF_ID, USER_A, service_with_mock_clock},
+        vouch::vouch,
+    };
+
+    #[async_std::test]
+    async fn test_recompute_balance() {
+        let (service, clock) = service_with_mock_clock();
+        let service = IdentityService {
+            debits: Arc::new(FixedDebits(HashMap::from([(
+                USER_A.to_string(),
+                IdtAmount::new(100),
+            )]))),
+            ..service
+        };
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            MAX_IDT_BY_PROOF,
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        for (from, to) in [
+            (USER_A, "userB"),
+            ("userB", "userC"),
+            ("userB", "userD"),
+            ("userD", "userE"),
+            ("userX", "userY"),
+        ] {
+            vouch(&service, from.to_string(), to.to_string())
+                .await
+                .unwrap();
+        }
+        punish(
+            &service,
+            "userE".to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(30),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        forget(&service, "userB".to_string(), "userC".to_string())
+            .await
+            .unwrap();
+        clock.advance(3600 * 24 * 30);
+
+        let user = "userB".to_string();
+        let subgraph = export_subgraph(&service, &user).await.unwrap();
+        assert!(subgraph.proofs.contains_key(USER_A));
+        assert!(subgraph.moderator_penalties.contains_key("userE"));
+        assert_eq!(subgraph.forgotten_penalties.len(), 1);
+        assert_eq!(subgraph.debits[USER_A], IdtAmount::new(100));
+        // unrelated users are not exported
+        assert!(
+            !subgraph
+                .vouches
+                .iter()
+                .any(|(voucher, ..)| voucher == "userX")
+        );
+
+        let json = serde_json::to_string(&subgraph).unwrap();
+        let subgraph: Subgraph = serde_json::from_str(&json).unwrap();
+        let expected = balance(&service, &user).await.unwrap();
+        assert!(expected > IdtAmount::new(0));
+        assert_eq!(subgraph.balance().await.unwrap(), expected);
+    }
+}
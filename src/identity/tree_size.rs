@@ -0,0 +1,191 @@
+// Telemetry of the vouch trees walked to compute balances. The cost of a balance grows with
+// the tree of its vouchers, so the sizes are kept per user to spot hot spots of the graph
+// before their balance requests start to time out.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::identity::{IdentityService, UserAddress};
+
+// nodes and edges touched by a tree walk, a user reachable by several paths is counted
+// once per path
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TreeSize {
+    pub nodes: u64,
+    pub edges: u64,
+}
+
+impl TreeSize {
+    fn max(self, other: TreeSize) -> TreeSize {
+        TreeSize {
+            nodes: self.nodes.max(other.nodes),
+            edges: self.edges.max(other.edges),
+        }
+    }
+}
+
+// tree size above which a balance computation is logged as a warning, 0 disables the limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TreeSizeLimits {
+    pub nodes: u64,
+    pub edges: u64,
+}
+
+impl Default for TreeSizeLimits {
+    fn default() -> Self {
+        Self {
+            nodes: 10_000,
+            edges: 50_000,
+        }
+    }
+}
+
+impl TreeSizeLimits {
+    pub fn exceeded_by(&self, size: TreeSize) -> bool {
+        (self.nodes > 0 && size.nodes > self.nodes) || (self.edges > 0 && size.edges > self.edges)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct UserTreeSize {
+    pub last: TreeSize,
+    // largest nodes and edges seen since the server start
+    pub max: TreeSize,
+    pub computations: u64,
+    pub computed_at: u64,
+}
+
+// sizes since the server start, they are not persisted
+#[derive(Default)]
+pub struct TreeSizeStats {
+    users: Mutex<HashMap<UserAddress, UserTreeSize>>,
+    warnings: AtomicU64,
+}
+
+impl TreeSizeStats {
+    pub fn record(&self, user: &UserAddress, size: TreeSize, timestamp: u64) {
+        let mut users = self.users.lock().unwrap();
+        let entry = users.entry(user.clone()).or_default();
+        entry.last = size;
+        entry.max = entry.max.max(size);
+        entry.computations += 1;
+        entry.computed_at = timestamp;
+    }
+
+    pub fn user(&self, user: &UserAddress) -> Option<UserTreeSize> {
+        self.users.lock().unwrap().get(user).cloned()
+    }
+
+    pub fn tracked_users(&self) -> usize {
+        self.users.lock().unwrap().len()
+    }
+
+    // `count` users with the most nodes in their largest tree
+    pub fn largest(&self, count: usize) -> Vec<(UserAddress, UserTreeSize)> {
+        let mut users: Vec<_> = self
+            .users
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(user, size)| (user.clone(), size.clone()))
+            .collect();
+        users.sort_by(|(a, a_size), (b, b_size)| {
+            (b_size.max.nodes, b_size.max.edges, a).cmp(&(a_size.max.nodes, a_size.max.edges, b))
+        });
+        users.truncate(count);
+        users
+    }
+
+    // number of computations above the limits
+    pub fn warnings(&self) -> u64 {
+        self.warnings.load(Ordering::Relaxed)
+    }
+}
+
+impl IdentityService {
+    pub fn record_tree_size(&self, user: &UserAddress, size: TreeSize) {
+        self.tree_sizes.record(user, size, self.now());
+        if self.config.tree_size_warning.exceeded_by(size) {
+            self.tree_sizes.warnings.fetch_add(1, Ordering::Relaxed);
+            log::warn!(
+                "Vouch tree of {} has {} nodes and {} edges",
+                user,
+                size.nodes,
+                size.edges
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{
+        IdtAmount,
+        idt::balance,
+        proof::prove,
+        tests::{MODERATOR, PROOF_ID, USER_A},
+        vouch::vouch,
+    };
+
+    #[async_std::test]
+    async fn test_balance_records_size() {
+        let mut service = IdentityService::default();
+        service.config.tree_size_warning = TreeSizeLimits { nodes: 2, edges: 0 };
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, USER_A.to_string(), "userB".into())
+            .await
+            .unwrap();
+        vouch(&service, "userB".into(), "userC".into())
+            .await
+            .unwrap();
+
+        balance(&service, &"userB".to_string()).await.unwrap();
+        let size = service.tree_sizes.user(&"userB".to_string()).unwrap();
+        assert_eq!(size.last, TreeSize { nodes: 2, edges: 1 });
+        assert_eq!(size.computations, 1);
+        assert_eq!(service.tree_sizes.warnings(), 0);
+
+        balance(&service, &"userC".to_string()).await.unwrap();
+        let size = service.tree_sizes.user(&"userC".to_string()).unwrap();
+        assert_eq!(size.last, TreeSize { nodes: 3, edges: 2 });
+        assert_eq!(service.tree_sizes.warnings(), 1);
+
+        let largest = service.tree_sizes.largest(1);
+        assert_eq!(largest.len(), 1);
+        assert_eq!(largest[0].0, "userC");
+        assert_eq!(service.tree_sizes.tracked_users(), 2);
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = TreeSizeLimits {
+            nodes: 10,
+            edges: 0,
+        };
+        assert!(!limits.exceeded_by(TreeSize {
+            nodes: 10,
+            edges: 1000
+        }));
+        assert!(limits.exceeded_by(TreeSize {
+            nodes: 11,
+            edges: 0
+        }));
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::identity::{IdtAmount, UserAddress, error::Error};
+use crate::identity::{IdtAmount, UserAddress, error::Error, tree_size::TreeSize};
 
 pub trait Visitor {
     // called when all children of the node are processed
@@ -17,8 +17,16 @@ pub trait Visitor {
 
 pub trait ChildrenSelector {
     async fn children(&self, root: &UserAddress) -> Result<Vec<UserAddress>, Error>;
+
+    // children of nodes at this depth are not visited, the root has depth 0
+    fn max_depth(&self) -> Option<usize> {
+        None
+    }
 }
 
+// nodes processed between yields to the executor, so a long walk can be cancelled
+const YIELD_INTERVAL: usize = 1024;
+
 #[derive(Clone)]
 struct VisitNode {
     pub children_visited: bool,
@@ -30,6 +38,33 @@ pub async fn walk_tree<T>(tree: &T, root: &UserAddress) -> Result<IdtAmount, Err
 where
     T: ChildrenSelector + Visitor,
 {
+    Ok(walk_tree_with_size(tree, root).await?.0)
+}
+
+// also returns the number of nodes and edges touched by the walk
+pub async fn walk_tree_with_size<T>(
+    tree: &T,
+    root: &UserAddress,
+) -> Result<(IdtAmount, TreeSize), Error>
+where
+    T: ChildrenSelector + Visitor,
+{
+    let (balance, size, _) = walk_tree_with_budget(tree, root, None).await?;
+    Ok((balance, size))
+}
+
+// children of nodes visited after the first `budget` nodes are not visited, the returned flag
+// is set if any children were skipped this way
+pub async fn walk_tree_with_budget<T>(
+    tree: &T,
+    root: &UserAddress,
+    budget: Option<u64>,
+) -> Result<(IdtAmount, TreeSize, bool), Error>
+where
+    T: ChildrenSelector + Visitor,
+{
+    let mut size = TreeSize::default();
+    let mut truncated = false;
     // Stack used for depth-first traversal of the tree
     let mut stack = vec![];
     // balances may have different values for the same user but during branch
@@ -44,12 +79,21 @@ where
         },
     ));
 
+    let mut processed: usize = 0;
     loop {
+        processed += 1;
+        if processed % YIELD_INTERVAL == 0 {
+            async_std::task::yield_now().await;
+        }
         let (user, visit_node) = match stack.pop() {
-            None => return Ok(balances.get(root).cloned().unwrap_or_default()),
+            None => {
+                let balance = balances.get(root).cloned().unwrap_or_default();
+                return Ok((balance, size, truncated));
+            }
             Some(x) => x,
         };
         if !visit_node.children_visited {
+            size.nodes += 1;
             let mut visited_branch = visit_node.visited_branch;
             visited_branch.insert(user.clone());
             stack.push((
@@ -62,7 +106,20 @@ where
                     visited_branch: visited_branch.clone(),
                 },
             ));
-            for v in tree.children(&user).await? {
+            // visited branch contains the node and all its ancestors
+            if tree
+                .max_depth()
+                .is_some_and(|depth| visited_branch.len() > depth)
+            {
+                continue;
+            }
+            let children = tree.children(&user).await?;
+            if budget.is_some_and(|budget| size.nodes > budget) {
+                truncated |= children.iter().any(|v| !visited_branch.contains(v));
+                continue;
+            }
+            size.edges += children.len() as u64;
+            for v in children {
                 // Skip nodes that have already been visited to avoid cycles in the tree traversal
                 if visited_branch.contains(&v) {
                     continue;
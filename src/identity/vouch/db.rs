@@ -1,21 +1,33 @@
 use std::collections::HashMap;
 
 use async_trait::async_trait;
-use sqlx::{AnyPool, Row, any::AnyPoolOptions};
+use sqlx::{Acquire, Row};
 
-use crate::identity::{UserAddress, error::Error, vouch::storage::VouchStorage};
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::{
+        UserAddress,
+        error::Error,
+        vouch::storage::{VouchStorage, VouchWrite},
+    },
+    pools::{self, MeteredPool},
+};
+
+// name of the connection pool in `GET /metrics`
+const POOL_NAME: &str = "vouches";
 
 pub struct DatabaseVouchStorage {
-    pool: AnyPool,
+    pool: MeteredPool,
+    cipher: FieldCipher,
 }
 
 impl DatabaseVouchStorage {
     pub async fn new(url: &str) -> Result<Self, Error> {
-        sqlx::any::install_default_drivers();
-        let pool = AnyPoolOptions::new()
-            .max_connections(1)
-            .connect(url)
-            .await?;
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect(POOL_NAME, url).await?;
         sqlx::query(
             "CREATE TABLE IF NOT EXISTS vouches (voucher TEXT NOT NULL, vouchee TEXT NOT NULL, timestamp INTEGER NOT NULL, PRIMARY KEY(voucher, vouchee))"
         )
@@ -27,20 +39,25 @@ impl DatabaseVouchStorage {
         sqlx::query("CREATE INDEX IF NOT EXISTS vouchee_idx ON vouches(vouchee)")
             .execute(&pool)
             .await?;
-        Ok(Self { pool })
+        rotate_column(&pool, &cipher, "vouches", "voucher").await?;
+        rotate_column(&pool, &cipher, "vouches", "vouchee").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
     }
 }
 
 #[async_trait]
 impl VouchStorage for DatabaseVouchStorage {
     async fn vouch(&self, from: UserAddress, to: UserAddress, timestamp: u64) -> Result<(), Error> {
-        sqlx::query("REPLACE INTO vouches (voucher, vouchee, timestamp) VALUES (?, ?, ?)")
-            .bind(&from)
-            .bind(&to)
-            .bind(timestamp as i64)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+        self.write_batch(vec![VouchWrite::Vouch {
+            from,
+            to,
+            timestamp,
+        }])
+        .await
     }
 
     async fn vouchers_with_time(
@@ -48,13 +65,14 @@ impl VouchStorage for DatabaseVouchStorage {
         user: &UserAddress,
     ) -> Result<HashMap<UserAddress, u64>, Error> {
         let rows = sqlx::query("SELECT voucher, timestamp FROM vouches WHERE vouchee = ?")
-            .bind(user)
+            .bind(self.cipher.encode(user))
             .fetch_all(&self.pool)
             .await?;
-        let vouchers = rows
-            .into_iter()
-            .map(|r| (r.get::<String, _>(0), r.get::<i64, _>(1) as u64))
-            .collect();
+        let mut vouchers = HashMap::new();
+        for r in rows {
+            let voucher = self.cipher.decode(&r.get::<String, _>(0))?;
+            vouchers.insert(voucher, r.get::<i64, _>(1) as u64);
+        }
         Ok(vouchers)
     }
 
@@ -63,21 +81,66 @@ impl VouchStorage for DatabaseVouchStorage {
         user: &UserAddress,
     ) -> Result<HashMap<UserAddress, u64>, Error> {
         let rows = sqlx::query("SELECT vouchee, timestamp FROM vouches WHERE voucher = ?")
-            .bind(user)
+            .bind(self.cipher.encode(user))
             .fetch_all(&self.pool)
             .await?;
-        Ok(rows
-            .into_iter()
-            .map(|r| (r.get::<String, _>(0), r.get::<i64, _>(1) as u64))
-            .collect())
+        let mut vouchees = HashMap::new();
+        for r in rows {
+            let vouchee = self.cipher.decode(&r.get::<String, _>(0))?;
+            vouchees.insert(vouchee, r.get::<i64, _>(1) as u64);
+        }
+        Ok(vouchees)
     }
 
     async fn remove_vouch(&self, voucher: UserAddress, vouchee: UserAddress) -> Result<(), Error> {
-        sqlx::query("DELETE FROM vouches WHERE voucher = ? AND vouchee = ?")
-            .bind(voucher)
-            .bind(vouchee)
-            .execute(&self.pool)
+        self.write_batch(vec![VouchWrite::RemoveVouch { voucher, vouchee }])
+            .await
+    }
+
+    async fn all_vouches(&self) -> Result<Vec<(UserAddress, UserAddress, u64)>, Error> {
+        let rows = sqlx::query("SELECT voucher, vouchee, timestamp FROM vouches")
+            .fetch_all(&self.pool)
             .await?;
+        let mut vouches = vec![];
+        for r in rows {
+            vouches.push((
+                self.cipher.decode(&r.get::<String, _>(0))?,
+                self.cipher.decode(&r.get::<String, _>(1))?,
+                r.get::<i64, _>(2) as u64,
+            ));
+        }
+        Ok(vouches)
+    }
+
+    // a single transaction, so the whole batch is committed at once
+    async fn write_batch(&self, writes: Vec<VouchWrite>) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        for write in writes {
+            match write {
+                VouchWrite::Vouch {
+                    from,
+                    to,
+                    timestamp,
+                } => {
+                    sqlx::query(
+                        "REPLACE INTO vouches (voucher, vouchee, timestamp) VALUES (?, ?, ?)",
+                    )
+                    .bind(self.cipher.encode(&from))
+                    .bind(self.cipher.encode(&to))
+                    .bind(timestamp as i64)
+                    .execute(tx.acquire().await?)
+                    .await?;
+                }
+                VouchWrite::RemoveVouch { voucher, vouchee } => {
+                    sqlx::query("DELETE FROM vouches WHERE voucher = ? AND vouchee = ?")
+                        .bind(self.cipher.encode(&voucher))
+                        .bind(self.cipher.encode(&vouchee))
+                        .execute(tx.acquire().await?)
+                        .await?;
+                }
+            }
+        }
+        tx.commit().await?;
         Ok(())
     }
 }
@@ -188,4 +251,79 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[async_std::test]
+    async fn test_all_vouches() {
+        let storage = DatabaseVouchStorage::new("sqlite::memory:").await.unwrap();
+        assert!(storage.all_vouches().await.unwrap().is_empty());
+        storage
+            .vouch("a".to_string(), "b".to_string(), 1)
+            .await
+            .unwrap();
+        storage
+            .vouch("b".to_string(), "c".to_string(), 2)
+            .await
+            .unwrap();
+        storage
+            .remove_vouch("a".to_string(), "b".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.all_vouches().await.unwrap(),
+            vec![("b".to_string(), "c".to_string(), 2)]
+        );
+    }
+
+    #[async_std::test]
+    async fn test_encrypted() {
+        let dir = tempdir::TempDir::new("vouches").unwrap();
+        let url = format!(
+            "sqlite://{}?mode=rwc",
+            dir.path().join("vouches.db").display()
+        );
+        let k1 = FieldCipher::new(vec![("k1".to_string(), [1; 32])]).unwrap();
+        let storage = DatabaseVouchStorage::with_cipher(&url, k1.clone())
+            .await
+            .unwrap();
+        storage
+            .vouch("user_a".to_string(), "user_b".to_string(), 1)
+            .await
+            .unwrap();
+        let raw: (String, String) = sqlx::query_as("SELECT voucher, vouchee FROM vouches")
+            .fetch_one(&storage.pool)
+            .await
+            .unwrap();
+        assert_eq!(raw, (k1.encode("user_a"), k1.encode("user_b")));
+        assert!(
+            storage
+                .vouchers_with_time(&"user_b".to_string())
+                .await
+                .unwrap()
+                .contains_key("user_a")
+        );
+        storage.pool.close().await;
+
+        // rotate to a new key
+        let k2 = FieldCipher::new(vec![
+            ("k2".to_string(), [2; 32]),
+            ("k1".to_string(), [1; 32]),
+        ])
+        .unwrap();
+        let storage = DatabaseVouchStorage::with_cipher(&url, k2.clone())
+            .await
+            .unwrap();
+        let raw: (String, String) = sqlx::query_as("SELECT voucher, vouchee FROM vouches")
+            .fetch_one(&storage.pool)
+            .await
+            .unwrap();
+        assert_eq!(raw, (k2.encode("user_a"), k2.encode("user_b")));
+        assert_eq!(
+            storage.all_vouches().await.unwrap(),
+            vec![("user_a".to_string(), "user_b".to_string(), 1)]
+        );
+        storage.pool.close().await;
+
+        // encrypted values cannot be read without the key
+        assert!(DatabaseVouchStorage::new(&url).await.is_err());
+    }
 }
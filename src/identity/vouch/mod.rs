@@ -1,10 +1,30 @@
 use std::collections::HashMap;
 
-use crate::identity::{IdentityService, UserAddress, error::Error, next_timestamp};
+use serde::{Deserialize, Serialize};
 
+use crate::identity::{IdentityService, UserAddress, decay::DAY, error::Error};
+
+#[cfg(feature = "storage-sql")]
 pub mod db;
 pub mod storage;
 
+// defines what happens when a user vouches for the same vouchee again
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum VouchRefreshPolicy {
+    // vouch timestamp is reset, so the decay starts over
+    #[default]
+    Overwrite,
+    // repeated vouch is accepted but the original timestamp is preserved
+    KeepEarliest,
+    // refresh is rejected until the vouch is at least `days` old
+    RejectWithin {
+        days: u64,
+    },
+    // refresh is allowed but the voucher is penalized as if the vouch was forgotten
+    RefreshWithPenalty,
+}
+
 impl IdentityService {
     pub async fn vouch_with_timestamp(
         &self,
@@ -12,7 +32,27 @@ impl IdentityService {
         to: UserAddress,
         timestamp: u64,
     ) -> Result<(), Error> {
-        self.vouches.vouch(from, to, timestamp).await
+        self.screen(&to).await?;
+        let previous = self.vouchers_with_time(&to).await?.get(&from).copied();
+        let Some(previous) = previous else {
+            return self.vouches.vouch(from, to, timestamp).await;
+        };
+        match self.config.vouch_refresh {
+            VouchRefreshPolicy::Overwrite => self.vouches.vouch(from, to, timestamp).await,
+            VouchRefreshPolicy::KeepEarliest => Ok(()),
+            VouchRefreshPolicy::RejectWithin { days } => {
+                let allowed_at = previous.saturating_add(days.saturating_mul(DAY));
+                if timestamp < allowed_at {
+                    return Err(Error::VouchRefreshTooEarly(allowed_at));
+                }
+                self.vouches.vouch(from, to, timestamp).await
+            }
+            VouchRefreshPolicy::RefreshWithPenalty => {
+                self.punish_for_forgetting_with_timestamp(from.clone(), to.clone(), timestamp)
+                    .await?;
+                self.vouches.vouch(from, to, timestamp).await
+            }
+        }
     }
 
     pub async fn vouchers_with_time(
@@ -35,31 +75,34 @@ pub async fn vouch(
     from: UserAddress,
     to: UserAddress,
 ) -> Result<(), Error> {
-    service
-        .vouch_with_timestamp(from, to, next_timestamp())
-        .await
+    service.vouch_with_timestamp(from, to, service.now()).await
 }
 
+// sorted, so tree walks visit users in the same order regardless of storage
 pub async fn vouchers(
     service: &IdentityService,
     user: &UserAddress,
 ) -> Result<Vec<UserAddress>, Error> {
-    Ok(service
+    let mut users: Vec<UserAddress> = service
         .vouchers_with_time(user)
         .await?
         .into_keys()
-        .collect())
+        .collect();
+    users.sort();
+    Ok(users)
 }
 
 pub async fn vouchees(
     service: &IdentityService,
     user: &UserAddress,
 ) -> Result<Vec<UserAddress>, Error> {
-    Ok(service
+    let mut users: Vec<UserAddress> = service
         .vouchees_with_time(user)
         .await?
         .into_keys()
-        .collect())
+        .collect();
+    users.sort();
+    Ok(users)
 }
 
 pub async fn voucher_timestamp(
@@ -87,8 +130,16 @@ pub async fn vouchee_timestamp(
 }
 
 #[cfg(test)]
+#[allow(clippy::len_zero)]
 mod tests {
-    use crate::identity::tests::USER_A;
+    use crate::{
+        config::IdentitySection,
+        identity::{
+            IdtAmount,
+            punish::penalty,
+            tests::{START_TIMESTAMP, USER_A},
+        },
+    };
 
     use super::*;
 
@@ -164,4 +215,100 @@ mod tests {
         assert!(vouchees(&service, &user_b.to_string()).await.unwrap().len() == 1);
         assert!(vouchers(&service, &user_b.to_string()).await.unwrap().len() == 1);
     }
+
+    fn service_with_policy(policy: VouchRefreshPolicy) -> IdentityService {
+        IdentityService {
+            config: IdentitySection {
+                vouch_refresh: policy,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    async fn vouch_time(service: &IdentityService, from: &str, to: &str) -> u64 {
+        service.vouchers_with_time(&to.to_string()).await.unwrap()[from]
+    }
+
+    #[async_std::test]
+    async fn test_refresh_overwrite() {
+        let service = service_with_policy(VouchRefreshPolicy::Overwrite);
+        let user_b = "userB";
+        service
+            .vouch_with_timestamp(USER_A.to_string(), user_b.to_string(), START_TIMESTAMP)
+            .await
+            .unwrap();
+        service
+            .vouch_with_timestamp(USER_A.to_string(), user_b.to_string(), START_TIMESTAMP + 10)
+            .await
+            .unwrap();
+        assert_eq!(
+            vouch_time(&service, USER_A, user_b).await,
+            START_TIMESTAMP + 10
+        );
+    }
+
+    #[async_std::test]
+    async fn test_refresh_keep_earliest() {
+        let service = service_with_policy(VouchRefreshPolicy::KeepEarliest);
+        let user_b = "userB";
+        service
+            .vouch_with_timestamp(USER_A.to_string(), user_b.to_string(), START_TIMESTAMP)
+            .await
+            .unwrap();
+        service
+            .vouch_with_timestamp(USER_A.to_string(), user_b.to_string(), START_TIMESTAMP + 10)
+            .await
+            .unwrap();
+        assert_eq!(vouch_time(&service, USER_A, user_b).await, START_TIMESTAMP);
+    }
+
+    #[async_std::test]
+    async fn test_refresh_reject_within() {
+        let service = service_with_policy(VouchRefreshPolicy::RejectWithin { days: 2 });
+        let user_b = "userB";
+        service
+            .vouch_with_timestamp(USER_A.to_string(), user_b.to_string(), START_TIMESTAMP)
+            .await
+            .unwrap();
+        let allowed_at = START_TIMESTAMP + 2 * DAY;
+        assert!(matches!(
+            service
+                .vouch_with_timestamp(USER_A.to_string(), user_b.to_string(), allowed_at - 1)
+                .await,
+            Err(Error::VouchRefreshTooEarly(t)) if t == allowed_at
+        ));
+        assert_eq!(vouch_time(&service, USER_A, user_b).await, START_TIMESTAMP);
+        service
+            .vouch_with_timestamp(USER_A.to_string(), user_b.to_string(), allowed_at)
+            .await
+            .unwrap();
+        assert_eq!(vouch_time(&service, USER_A, user_b).await, allowed_at);
+        // first vouch for another user is not affected
+        service
+            .vouch_with_timestamp(USER_A.to_string(), "userC".to_string(), allowed_at)
+            .await
+            .unwrap();
+    }
+
+    #[async_std::test]
+    async fn test_refresh_with_penalty() {
+        let service = service_with_policy(VouchRefreshPolicy::RefreshWithPenalty);
+        let user_b = "userB";
+        let now = service.now();
+        service
+            .vouch_with_timestamp(USER_A.to_string(), user_b.to_string(), now)
+            .await
+            .unwrap();
+        assert_eq!(
+            penalty(&service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+        service
+            .vouch_with_timestamp(USER_A.to_string(), user_b.to_string(), now)
+            .await
+            .unwrap();
+        assert!(penalty(&service, &USER_A.to_string()).await.unwrap() > IdtAmount::new(0));
+        assert_eq!(vouch_time(&service, USER_A, user_b).await, now);
+    }
 }
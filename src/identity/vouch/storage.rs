@@ -5,6 +5,20 @@ use async_trait::async_trait;
 
 use crate::identity::{UserAddress, error::Error};
 
+// mutation of the vouch graph, see `VouchStorage::write_batch`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VouchWrite {
+    Vouch {
+        from: UserAddress,
+        to: UserAddress,
+        timestamp: u64,
+    },
+    RemoveVouch {
+        voucher: UserAddress,
+        vouchee: UserAddress,
+    },
+}
+
 #[async_trait]
 pub trait VouchStorage: Send + Sync {
     async fn vouch(&self, from: UserAddress, to: UserAddress, timestamp: u64) -> Result<(), Error>;
@@ -17,6 +31,26 @@ pub trait VouchStorage: Send + Sync {
         user: &UserAddress,
     ) -> Result<HashMap<UserAddress, u64>, Error>;
     async fn remove_vouch(&self, voucher: UserAddress, vouchee: UserAddress) -> Result<(), Error>;
+    // every (voucher, vouchee, timestamp) edge of the graph
+    async fn all_vouches(&self) -> Result<Vec<(UserAddress, UserAddress, u64)>, Error>;
+
+    // applies the writes in order. Storages with transactions apply all or none of them,
+    // others may stop after a part of the writes.
+    async fn write_batch(&self, writes: Vec<VouchWrite>) -> Result<(), Error> {
+        for write in writes {
+            match write {
+                VouchWrite::Vouch {
+                    from,
+                    to,
+                    timestamp,
+                } => self.vouch(from, to, timestamp).await?,
+                VouchWrite::RemoveVouch { voucher, vouchee } => {
+                    self.remove_vouch(voucher, vouchee).await?
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Default)]
@@ -100,6 +134,21 @@ impl VouchStorage for InMemoryVouchStorage {
         });
         Ok(())
     }
+
+    async fn all_vouches(&self) -> Result<Vec<(UserAddress, UserAddress, u64)>, Error> {
+        Ok(self
+            .data
+            .read()
+            .await
+            .vouchees
+            .iter()
+            .flat_map(|(voucher, vouchees)| {
+                vouchees
+                    .iter()
+                    .map(move |(vouchee, timestamp)| (voucher.clone(), vouchee.clone(), *timestamp))
+            })
+            .collect())
+    }
 }
 
 #[cfg(test)]
@@ -208,4 +257,26 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[async_std::test]
+    async fn test_all_vouches() {
+        let storage = InMemoryVouchStorage::default();
+        assert!(storage.all_vouches().await.unwrap().is_empty());
+        storage
+            .vouch("a".to_string(), "b".to_string(), 1)
+            .await
+            .unwrap();
+        storage
+            .vouch("b".to_string(), "c".to_string(), 2)
+            .await
+            .unwrap();
+        storage
+            .remove_vouch("a".to_string(), "b".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.all_vouches().await.unwrap(),
+            vec![("b".to_string(), "c".to_string(), 2)]
+        );
+    }
 }
@@ -1,21 +1,33 @@
 use async_trait::async_trait;
-use sqlx::{AnyPool, Row, any::AnyPoolOptions};
+use sqlx::Row;
 
 use super::storage::ExternalVouchStorage;
-use crate::identity::{UserAddress, error::Error, vouch_external::storage::ServerWithVoucher};
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::{
+        UserAddress,
+        error::Error,
+        vouch_external::{
+            ExternalVouch,
+            storage::{ExternalVouchFilter, ServerWithVoucher},
+        },
+    },
+    pools::{self, MeteredPool},
+};
 use std::collections::HashMap;
 
 pub struct DatabaseExternalVouchStorage {
-    pool: AnyPool,
+    pool: MeteredPool,
+    cipher: FieldCipher,
 }
 
 impl DatabaseExternalVouchStorage {
     pub async fn new(url: &str) -> Result<Self, Error> {
-        sqlx::any::install_default_drivers();
-        let pool = AnyPoolOptions::new()
-            .max_connections(1)
-            .connect(url)
-            .await?;
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("external_vouches", url).await?;
         sqlx::query(
             "CREATE TABLE IF NOT EXISTS external_vouches (server TEXT NOT NULL, voucher TEXT NOT NULL, vouchee TEXT NOT NULL, timestamp INTEGER NOT NULL, PRIMARY KEY(server, voucher, vouchee))",
         )
@@ -29,7 +41,24 @@ impl DatabaseExternalVouchStorage {
         sqlx::query("CREATE INDEX IF NOT EXISTS external_vouchee_idx ON external_vouches(vouchee)")
             .execute(&pool)
             .await?;
-        Ok(Self { pool })
+        // filtered queries of `find_vouchers`
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS external_vouchee_time_idx ON external_vouches(vouchee, timestamp)",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS external_vouchee_server_idx ON external_vouches(vouchee, server, timestamp)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "external_vouches", "voucher").await?;
+        rotate_column(&pool, &cipher, "external_vouches", "vouchee").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
     }
 }
 
@@ -46,8 +75,8 @@ impl ExternalVouchStorage for DatabaseExternalVouchStorage {
             "REPLACE INTO external_vouches (server, voucher, vouchee, timestamp) VALUES (?, ?, ?, ?)",
         )
         .bind(&server)
-        .bind(&from)
-        .bind(&to)
+        .bind(self.cipher.encode(&from))
+        .bind(self.cipher.encode(&to))
         .bind(timestamp as i64)
         .execute(&self.pool)
         .await?;
@@ -58,19 +87,60 @@ impl ExternalVouchStorage for DatabaseExternalVouchStorage {
         let rows = sqlx::query(
             "SELECT server, voucher, timestamp FROM external_vouches WHERE vouchee = ?",
         )
-        .bind(user)
+        .bind(self.cipher.encode(user))
         .fetch_all(&self.pool)
         .await?;
         let mut map: HashMap<UserAddress, HashMap<UserAddress, u64>> = HashMap::new();
         for r in rows {
             let server: String = r.get(0);
-            let voucher: String = r.get(1);
+            let voucher = self.cipher.decode(&r.get::<String, _>(1))?;
             let ts: i64 = r.get(2);
             map.entry(server).or_default().insert(voucher, ts as u64);
         }
         Ok(map)
     }
 
+    async fn find_vouchers(
+        &self,
+        user: &UserAddress,
+        filter: &ExternalVouchFilter,
+    ) -> Result<Vec<ExternalVouch>, Error> {
+        // server and time range are matched by the indexes. Vouchers are encrypted, so the
+        // order and the page are applied after decoding.
+        let mut query =
+            "SELECT server, voucher, timestamp FROM external_vouches WHERE vouchee = ?".to_string();
+        if filter.server.is_some() {
+            query.push_str(" AND server = ?");
+        }
+        let min_timestamp = filter.min_timestamp();
+        if min_timestamp.is_some() {
+            query.push_str(" AND timestamp >= ?");
+        }
+        if filter.until.is_some() {
+            query.push_str(" AND timestamp < ?");
+        }
+        let mut query = sqlx::query(&query).bind(self.cipher.encode(user));
+        if let Some(server) = &filter.server {
+            query = query.bind(server);
+        }
+        if let Some(timestamp) = min_timestamp {
+            query = query.bind(timestamp.min(i64::MAX as u64) as i64);
+        }
+        if let Some(until) = filter.until {
+            query = query.bind(until.min(i64::MAX as u64) as i64);
+        }
+        let rows = query.fetch_all(&self.pool).await?;
+        let mut vouches = vec![];
+        for r in rows {
+            vouches.push(ExternalVouch {
+                server: r.get(0),
+                voucher: self.cipher.decode(&r.get::<String, _>(1))?,
+                timestamp: r.get::<i64, _>(2) as u64,
+            });
+        }
+        Ok(filter.apply(vouches))
+    }
+
     async fn remove_vouch(
         &self,
         server: UserAddress,
@@ -81,17 +151,34 @@ impl ExternalVouchStorage for DatabaseExternalVouchStorage {
             "DELETE FROM external_vouches WHERE server = ? AND voucher = ? AND vouchee = ?",
         )
         .bind(&server)
-        .bind(&from)
-        .bind(&to)
+        .bind(self.cipher.encode(&from))
+        .bind(self.cipher.encode(&to))
         .execute(&self.pool)
         .await?;
         Ok(())
     }
+
+    async fn all_vouches(
+        &self,
+    ) -> Result<Vec<(UserAddress, UserAddress, UserAddress, u64)>, Error> {
+        let rows = sqlx::query("SELECT server, voucher, vouchee, timestamp FROM external_vouches")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut vouches = vec![];
+        for r in rows {
+            let voucher = self.cipher.decode(&r.get::<String, _>(1))?;
+            let vouchee = self.cipher.decode(&r.get::<String, _>(2))?;
+            let ts: i64 = r.get(3);
+            vouches.push((r.get(0), voucher, vouchee, ts as u64));
+        }
+        Ok(vouches)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::identity::vouch_external::storage::tests::check_find_vouchers;
 
     #[async_std::test]
     async fn test_basic() {
@@ -104,6 +191,10 @@ mod tests {
             .unwrap();
         let map = storage.vouchers_with_time(&"to".to_string()).await.unwrap();
         assert_eq!(map.get("server").unwrap().get("from").copied().unwrap(), 1);
+        assert_eq!(
+            storage.all_vouches().await.unwrap(),
+            vec![("server".into(), "from".into(), "to".into(), 1)]
+        );
     }
 
     #[async_std::test]
@@ -124,6 +215,14 @@ mod tests {
             .unwrap();
         // verify it no longer exists
         let map = storage.vouchers_with_time(&"to".into()).await.unwrap();
-        assert!(map.get("server").is_none());
+        assert!(!map.contains_key("server"));
+    }
+
+    #[async_std::test]
+    async fn test_find_vouchers() {
+        let storage = DatabaseExternalVouchStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        check_find_vouchers(&storage).await;
     }
 }
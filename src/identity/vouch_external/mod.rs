@@ -1,8 +1,12 @@
-use crate::identity::{IdentityService, UserAddress, error::Error, next_timestamp};
+use serde::Serialize;
 
+use crate::identity::{IdentityService, UserAddress, error::Error};
+
+#[cfg(feature = "storage-sql")]
 pub mod db;
 pub mod storage;
 
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct ExternalVouch {
     pub voucher: UserAddress,
     pub server: UserAddress,
@@ -17,6 +21,7 @@ impl IdentityService {
         to: UserAddress,
         timestamp: u64,
     ) -> Result<(), Error> {
+        self.screen(&to).await?;
         self.external_vouches
             .vouch(server, from, to, timestamp)
             .await
@@ -49,7 +54,7 @@ pub async fn vouch_external(
     to: UserAddress,
 ) -> Result<(), Error> {
     service
-        .vouch_external_with_timestamp(server, from, to, next_timestamp())
+        .vouch_external_with_timestamp(server, from, to, service.now())
         .await
 }
 
@@ -83,6 +88,6 @@ mod tests {
             .await
             .unwrap();
         assert_eq!(vouchers.len(), 1);
-        assert_eq!(vouchers.get(0).unwrap().voucher, USER_A.to_string());
+        assert_eq!(vouchers.first().unwrap().voucher, USER_A.to_string());
     }
 }
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use async_std::sync::RwLock;
 use async_trait::async_trait;
 
-use crate::identity::{UserAddress, error::Error};
+use crate::identity::{UserAddress, error::Error, vouch_external::ExternalVouch};
 
 // key - voucher
 pub type VoucherWithTime = HashMap<UserAddress, u64>;
@@ -12,6 +12,59 @@ pub type ServerWithVoucher = HashMap<UserAddress, VoucherWithTime>;
 // key - vouchee
 pub type VoucheeWithServer = HashMap<UserAddress, ServerWithVoucher>;
 
+// query of `ExternalVouchStorage::find_vouchers`, vouches are ordered by timestamp, server and
+// voucher
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExternalVouchFilter {
+    // only vouches relayed by this server
+    pub server: Option<UserAddress>,
+    // only vouches made at or after it
+    pub since: Option<u64>,
+    // only vouches made before it
+    pub until: Option<u64>,
+    // (timestamp, server, voucher) of the last vouch of the previous page
+    pub after: Option<(u64, UserAddress, UserAddress)>,
+    // at most this many vouches, all if not set
+    pub limit: Option<usize>,
+}
+
+impl ExternalVouchFilter {
+    // lowest timestamp a matching vouch can have, storages use it to narrow their scans
+    pub fn min_timestamp(&self) -> Option<u64> {
+        let after = self.after.as_ref().map(|(timestamp, ..)| *timestamp);
+        self.since.max(after)
+    }
+
+    // filters, orders and pages vouches of a single user, storages may pass a superset of
+    // the matching vouches
+    pub fn apply(&self, vouches: impl IntoIterator<Item = ExternalVouch>) -> Vec<ExternalVouch> {
+        let mut vouches: Vec<_> = vouches
+            .into_iter()
+            .filter(|v| {
+                self.server
+                    .as_ref()
+                    .is_none_or(|server| &v.server == server)
+            })
+            .filter(|v| self.since.is_none_or(|since| v.timestamp >= since))
+            .filter(|v| self.until.is_none_or(|until| v.timestamp < until))
+            .filter(|v| {
+                self.after
+                    .as_ref()
+                    .is_none_or(|(timestamp, server, voucher)| {
+                        (v.timestamp, &v.server, &v.voucher) > (*timestamp, server, voucher)
+                    })
+            })
+            .collect();
+        vouches.sort_by(|a, b| {
+            (a.timestamp, &a.server, &a.voucher).cmp(&(b.timestamp, &b.server, &b.voucher))
+        });
+        if let Some(limit) = self.limit {
+            vouches.truncate(limit);
+        }
+        vouches
+    }
+}
+
 #[async_trait]
 pub trait ExternalVouchStorage: Send + Sync {
     async fn vouch(
@@ -24,12 +77,23 @@ pub trait ExternalVouchStorage: Send + Sync {
 
     async fn vouchers_with_time(&self, user: &UserAddress) -> Result<ServerWithVoucher, Error>;
 
+    // external vouches for `user` matching `filter`
+    async fn find_vouchers(
+        &self,
+        user: &UserAddress,
+        filter: &ExternalVouchFilter,
+    ) -> Result<Vec<ExternalVouch>, Error>;
+
     async fn remove_vouch(
         &self,
         server: UserAddress,
         from: UserAddress,
         to: UserAddress,
     ) -> Result<(), Error>;
+
+    // every (server, voucher, vouchee, timestamp) external vouch
+    async fn all_vouches(&self)
+    -> Result<Vec<(UserAddress, UserAddress, UserAddress, u64)>, Error>;
 }
 
 #[derive(Default)]
@@ -65,6 +129,27 @@ impl ExternalVouchStorage for InMemoryExternalVouchStorage {
             .unwrap_or_default())
     }
 
+    async fn find_vouchers(
+        &self,
+        user: &UserAddress,
+        filter: &ExternalVouchFilter,
+    ) -> Result<Vec<ExternalVouch>, Error> {
+        let lock = self.data.read().await;
+        let Some(servers) = lock.get(user) else {
+            return Ok(vec![]);
+        };
+        let vouches = servers.iter().flat_map(|(server, vouchers)| {
+            vouchers
+                .iter()
+                .map(move |(voucher, timestamp)| ExternalVouch {
+                    voucher: voucher.clone(),
+                    server: server.clone(),
+                    timestamp: *timestamp,
+                })
+        });
+        Ok(filter.apply(vouches))
+    }
+
     async fn remove_vouch(
         &self,
         server: UserAddress,
@@ -83,10 +168,25 @@ impl ExternalVouchStorage for InMemoryExternalVouchStorage {
         vouchers.remove(&from);
         Ok(())
     }
+
+    async fn all_vouches(
+        &self,
+    ) -> Result<Vec<(UserAddress, UserAddress, UserAddress, u64)>, Error> {
+        let lock = self.data.read().await;
+        let mut vouches = vec![];
+        for (vouchee, servers) in lock.iter() {
+            for (server, vouchers) in servers {
+                for (voucher, timestamp) in vouchers {
+                    vouches.push((server.clone(), voucher.clone(), vouchee.clone(), *timestamp));
+                }
+            }
+        }
+        Ok(vouches)
+    }
 }
 
 #[cfg(test)]
-mod tests {
+pub mod tests {
     use super::*;
 
     #[async_std::test]
@@ -119,4 +219,58 @@ mod tests {
         let map = storage.vouchers_with_time(&"to".into()).await.unwrap();
         assert!(map.get("server").unwrap().get("from").is_none());
     }
+
+    async fn vouchers(
+        storage: &dyn ExternalVouchStorage,
+        filter: ExternalVouchFilter,
+    ) -> Vec<UserAddress> {
+        storage
+            .find_vouchers(&"to".to_string(), &filter)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|v| v.voucher)
+            .collect()
+    }
+
+    pub async fn check_find_vouchers(storage: &dyn ExternalVouchStorage) {
+        for (server, voucher, timestamp) in [("s1", "a", 30), ("s2", "b", 10), ("s1", "c", 10)] {
+            storage
+                .vouch(server.into(), voucher.into(), "to".into(), timestamp)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            vouchers(storage, ExternalVouchFilter::default()).await,
+            ["c", "b", "a"]
+        );
+        let filter = ExternalVouchFilter {
+            server: Some("s1".into()),
+            ..Default::default()
+        };
+        assert_eq!(vouchers(storage, filter).await, ["c", "a"]);
+        let filter = ExternalVouchFilter {
+            since: Some(10),
+            until: Some(30),
+            ..Default::default()
+        };
+        assert_eq!(vouchers(storage, filter).await, ["c", "b"]);
+        let filter = ExternalVouchFilter {
+            after: Some((10, "s1".into(), "c".into())),
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(vouchers(storage, filter).await, ["b"]);
+        let filter = ExternalVouchFilter {
+            server: Some("s2".into()),
+            since: Some(20),
+            ..Default::default()
+        };
+        assert!(vouchers(storage, filter).await.is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_find_vouchers() {
+        check_find_vouchers(&InMemoryExternalVouchStorage::default()).await;
+    }
 }
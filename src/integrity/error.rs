@@ -0,0 +1,7 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Identity error: {0}")]
+    IdentityError(#[from] crate::identity::error::Error),
+    #[error("Server storage error: {0}")]
+    ServerError(#[from] crate::servers::error::Error),
+}
@@ -0,0 +1,421 @@
+// Consistency checks of the stored identity data.
+//
+// Some storages keep the vouch graph indexed both by voucher and by vouchee, and records
+// reference users and servers stored elsewhere. An interrupted write or a manual edit of the
+// database can break these invariants. `check_integrity` reports every broken one and
+// optionally repairs it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::IntegritySection,
+    identity::{IdentityService, UserAddress},
+    integrity::error::Error,
+    servers::storage::ServerStorage,
+};
+
+pub mod error;
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Inconsistency {
+    // the edge is listed among the vouchees of the voucher, but missing from the vouchers of
+    // the vouchee or listed there with another timestamp
+    MissingVoucher {
+        voucher: UserAddress,
+        vouchee: UserAddress,
+        timestamp: u64,
+    },
+    // the edge is only listed among the vouchers of the vouchee
+    MissingVouchee {
+        voucher: UserAddress,
+        vouchee: UserAddress,
+        timestamp: u64,
+    },
+    // penalty of a user without a proof, a revoked proof, a genesis balance or vouches
+    DanglingModeratorPenalty {
+        user: UserAddress,
+    },
+    DanglingForgottenPenalty {
+        user: UserAddress,
+        forgotten: UserAddress,
+    },
+    // vouch imported from a server that is not registered
+    UnknownServer {
+        server: UserAddress,
+        voucher: UserAddress,
+        vouchee: UserAddress,
+    },
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct IntegrityReport {
+    pub checked_at: u64,
+    pub inconsistencies: Vec<Inconsistency>,
+    // number of fixed inconsistencies, always 0 unless a repair was requested
+    pub repaired: usize,
+}
+
+pub async fn check_integrity(
+    service: &IdentityService,
+    servers: &dyn ServerStorage,
+    repair: bool,
+) -> Result<IntegrityReport, Error> {
+    let mut inconsistencies = vec![];
+    let mut known: HashSet<UserAddress> = service.proofs.proven_users().await?;
+
+    // key - vouchee, value - (voucher, timestamp) map read from the voucher side
+    let mut vouchers: HashMap<UserAddress, HashMap<UserAddress, u64>> = HashMap::new();
+    for (voucher, vouchee, timestamp) in service.vouches.all_vouches().await? {
+        known.insert(voucher.clone());
+        known.insert(vouchee.clone());
+        vouchers
+            .entry(vouchee)
+            .or_default()
+            .insert(voucher, timestamp);
+    }
+    let external = service.external_vouches.all_vouches().await?;
+    known.extend(external.iter().map(|(_, _, vouchee, _)| vouchee.clone()));
+
+    // storages cannot list the vouchee side, so it is read for every user known from
+    // other records
+    let mut users: Vec<UserAddress> = known.iter().cloned().collect();
+    users.sort();
+    for vouchee in users {
+        let stored = service.vouches.vouchers_with_time(&vouchee).await?;
+        let expected = vouchers.remove(&vouchee).unwrap_or_default();
+        for (voucher, timestamp) in &expected {
+            if stored.get(voucher) != Some(timestamp) {
+                inconsistencies.push(Inconsistency::MissingVoucher {
+                    voucher: voucher.clone(),
+                    vouchee: vouchee.clone(),
+                    timestamp: *timestamp,
+                });
+            }
+        }
+        for (voucher, timestamp) in stored {
+            if !expected.contains_key(&voucher) {
+                known.insert(voucher.clone());
+                inconsistencies.push(Inconsistency::MissingVouchee {
+                    voucher,
+                    vouchee: vouchee.clone(),
+                    timestamp,
+                });
+            }
+        }
+    }
+
+    let registered = servers.servers().await?;
+    for (server, voucher, vouchee, _) in external {
+        if !registered.contains_key(&server) {
+            inconsistencies.push(Inconsistency::UnknownServer {
+                server,
+                voucher,
+                vouchee,
+            });
+        }
+    }
+
+    for (user, _) in service.penalties.all_moderator_penalties().await? {
+        if !is_known(service, &known, &user).await? {
+            inconsistencies.push(Inconsistency::DanglingModeratorPenalty { user });
+        }
+    }
+    for (user, forgotten, _) in service.penalties.all_forgotten_penalties().await? {
+        if !is_known(service, &known, &user).await? {
+            inconsistencies.push(Inconsistency::DanglingForgottenPenalty { user, forgotten });
+        }
+    }
+
+    inconsistencies.sort();
+    let mut repaired = 0;
+    if repair {
+        for inconsistency in &inconsistencies {
+            fix(service, inconsistency).await?;
+            repaired += 1;
+        }
+    }
+    Ok(IntegrityReport {
+        checked_at: service.now(),
+        inconsistencies,
+        repaired,
+    })
+}
+
+async fn is_known(
+    service: &IdentityService,
+    known: &HashSet<UserAddress>,
+    user: &UserAddress,
+) -> Result<bool, Error> {
+    Ok(known.contains(user) || service.revoked_proof(user).await?.is_some())
+}
+
+// the vouch is written again in both directions rather than removed, so a repair never
+// changes balances. Dangling penalties do not affect any balance either: the user has no
+// proof and nobody vouches for it. Penalties of archived users stay in the archive.
+async fn fix(service: &IdentityService, inconsistency: &Inconsistency) -> Result<(), Error> {
+    match inconsistency.clone() {
+        Inconsistency::MissingVoucher {
+            voucher,
+            vouchee,
+            timestamp,
+        }
+        | Inconsistency::MissingVouchee {
+            voucher,
+            vouchee,
+            timestamp,
+        } => service.vouches.vouch(voucher, vouchee, timestamp).await?,
+        Inconsistency::DanglingModeratorPenalty { user } => {
+            service.penalties.remove_moderator_penalty(&user).await?
+        }
+        Inconsistency::DanglingForgottenPenalty { user, forgotten } => {
+            service.penalties.remove_forgotten(user, &forgotten).await?
+        }
+        Inconsistency::UnknownServer {
+            server,
+            voucher,
+            vouchee,
+        } => {
+            service
+                .external_vouches
+                .remove_vouch(server, voucher, vouchee)
+                .await?
+        }
+    }
+    Ok(())
+}
+
+// checks the data forever
+pub async fn check_periodically(
+    service: IdentityService,
+    servers: Arc<dyn ServerStorage>,
+    config: IntegritySection,
+) {
+    let interval = Duration::from_secs(config.check_interval.max(1));
+    loop {
+        match check_integrity(&service, &*servers, config.repair).await {
+            Ok(report) if !report.inconsistencies.is_empty() => log::warn!(
+                "Found {} inconsistencies, repaired {}: {:?}",
+                report.inconsistencies.len(),
+                report.repaired,
+                report.inconsistencies
+            ),
+            Ok(_) => {}
+            Err(e) => log::error!("Failed to check integrity: {:?}", e),
+        }
+        async_std::task::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identity::{
+            IdtAmount,
+            error::Error as IdentityError,
+            proof::prove,
+            punish::{punish, punish_for_forgetting},
+            tests::{MODERATOR, PROOF_ID, USER_A},
+            vouch::{
+                storage::{InMemoryVouchStorage, VouchStorage},
+                vouch,
+            },
+        },
+        numbers::Rational,
+        servers::storage::{InMemoryServerStorage, ServerInfo},
+    };
+    use async_std::sync::RwLock;
+    use async_trait::async_trait;
+
+    // loses the vouchee side of chosen edges, like an interrupted write would
+    #[derive(Default)]
+    struct LossyStorage {
+        inner: InMemoryVouchStorage,
+        // (voucher, vouchee) edges missing from the vouchers of the vouchee
+        lost: RwLock<HashSet<(UserAddress, UserAddress)>>,
+    }
+
+    #[async_trait]
+    impl VouchStorage for LossyStorage {
+        async fn vouch(
+            &self,
+            from: UserAddress,
+            to: UserAddress,
+            timestamp: u64,
+        ) -> Result<(), IdentityError> {
+            self.lost.write().await.remove(&(from.clone(), to.clone()));
+            self.inner.vouch(from, to, timestamp).await
+        }
+
+        async fn vouchers_with_time(
+            &self,
+            user: &UserAddress,
+        ) -> Result<HashMap<UserAddress, u64>, IdentityError> {
+            let lost = self.lost.read().await;
+            let mut vouchers = self.inner.vouchers_with_time(user).await?;
+            vouchers.retain(|voucher, _| !lost.contains(&(voucher.clone(), user.clone())));
+            Ok(vouchers)
+        }
+
+        async fn vouchees_with_time(
+            &self,
+            user: &UserAddress,
+        ) -> Result<HashMap<UserAddress, u64>, IdentityError> {
+            self.inner.vouchees_with_time(user).await
+        }
+
+        async fn remove_vouch(
+            &self,
+            voucher: UserAddress,
+            vouchee: UserAddress,
+        ) -> Result<(), IdentityError> {
+            self.inner.remove_vouch(voucher, vouchee).await
+        }
+
+        async fn all_vouches(&self) -> Result<Vec<(UserAddress, UserAddress, u64)>, IdentityError> {
+            self.inner.all_vouches().await
+        }
+    }
+
+    #[async_std::test]
+    async fn test_consistent() {
+        let service = IdentityService::default();
+        let servers = InMemoryServerStorage::default();
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, USER_A.to_string(), "userB".into())
+            .await
+            .unwrap();
+        punish(
+            &service,
+            "userB".into(),
+            MODERATOR.to_string(),
+            IdtAmount::new(10),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        punish_for_forgetting(&service, USER_A.to_string(), "userB".into())
+            .await
+            .unwrap();
+        servers
+            .add_server(
+                "server".into(),
+                ServerInfo {
+                    url: "http://server".into(),
+                    scale: Rational::new(1, 1).unwrap(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        service
+            .external_vouches
+            .vouch("server".into(), "remote".into(), USER_A.to_string(), 1)
+            .await
+            .unwrap();
+
+        let report = check_integrity(&service, &servers, true).await.unwrap();
+        assert!(report.inconsistencies.is_empty());
+        assert_eq!(report.repaired, 0);
+    }
+
+    #[async_std::test]
+    async fn test_repair() {
+        let vouches = Arc::new(LossyStorage::default());
+        let service = IdentityService {
+            vouches: vouches.clone(),
+            ..Default::default()
+        };
+        let servers = InMemoryServerStorage::default();
+        vouch(&service, USER_A.to_string(), "userB".into())
+            .await
+            .unwrap();
+        vouches
+            .lost
+            .write()
+            .await
+            .insert((USER_A.to_string(), "userB".into()));
+        punish(
+            &service,
+            "stranger".into(),
+            MODERATOR.to_string(),
+            IdtAmount::new(10),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        service
+            .penalties
+            .set_forgotten_penalty("stranger".into(), USER_A.to_string(), Default::default())
+            .await
+            .unwrap();
+        service
+            .external_vouches
+            .vouch("removed".into(), "remote".into(), USER_A.to_string(), 1)
+            .await
+            .unwrap();
+
+        let report = check_integrity(&service, &servers, false).await.unwrap();
+        assert_eq!(
+            report.inconsistencies,
+            vec![
+                Inconsistency::MissingVoucher {
+                    voucher: USER_A.to_string(),
+                    vouchee: "userB".into(),
+                    timestamp: service
+                        .vouchees_with_time(&USER_A.to_string())
+                        .await
+                        .unwrap()["userB"],
+                },
+                Inconsistency::DanglingModeratorPenalty {
+                    user: "stranger".into()
+                },
+                Inconsistency::DanglingForgottenPenalty {
+                    user: "stranger".into(),
+                    forgotten: USER_A.to_string(),
+                },
+                Inconsistency::UnknownServer {
+                    server: "removed".into(),
+                    voucher: "remote".into(),
+                    vouchee: USER_A.to_string(),
+                },
+            ]
+        );
+        assert_eq!(report.repaired, 0);
+
+        let report = check_integrity(&service, &servers, true).await.unwrap();
+        assert_eq!(report.repaired, 4);
+        assert!(
+            service
+                .moderator_penalty(&"stranger".into())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            service
+                .external_vouches
+                .all_vouches()
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let report = check_integrity(&service, &servers, false).await.unwrap();
+        assert!(report.inconsistencies.is_empty());
+    }
+}
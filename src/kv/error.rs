@@ -0,0 +1,13 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Sled error: {0}")]
+    SledError(#[from] sled::Error),
+    #[error("Encoding error: {0}")]
+    EncodingError(#[from] bincode::Error),
+    #[error("Encryption error: {0}")]
+    EncryptionError(#[from] crate::encryption::error::Error),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
@@ -0,0 +1,976 @@
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use sled::{
+    Batch,
+    transaction::{TransactionError, Transactional},
+};
+
+use crate::{
+    changes::storage::{ChangeLog, Version},
+    events::{Event, RecordedEvent},
+    identity::{
+        IdtAmount, ModeratorProof, SystemPenalty, UserAddress,
+        activity::storage::ActivityStorage,
+        balances::storage::BalanceStorage,
+        categories::storage::CategoryStorage,
+        error::Error,
+        moderators::{ModeratorOutcome, ModeratorStats, storage::ModeratorStatsStorage},
+        penalty_reasons::storage::PenaltyReasonStorage,
+        proof::storage::ProofStorage,
+        proof_limits::storage::ProofLimitStorage,
+        punish::storage::PenaltyStorage,
+        stakes::storage::StakeStorage,
+        vouch::storage::VouchStorage,
+        vouch_external::{
+            ExternalVouch,
+            storage::{ExternalVouchFilter, ExternalVouchStorage, ServerWithVoucher},
+        },
+    },
+    kv::{
+        SEPARATOR, SledStorage, apply, batch_put, error::Error as KvError, get, key, put, remove,
+        scan, swap,
+    },
+};
+
+// key - (vouchee, voucher)
+const VOUCHERS: &str = "vouchers";
+// key - (voucher, vouchee)
+const VOUCHEES: &str = "vouchees";
+// key - user
+pub(super) const BALANCE: &str = "balance";
+pub(super) const COMPUTED_AT: &str = "computed_at";
+
+impl SledStorage {
+    // both directions are written in a single batch, so they never disagree
+    pub(super) fn insert_vouch(
+        &self,
+        voucher: &UserAddress,
+        vouchee: &UserAddress,
+        timestamp: u64,
+    ) -> Result<(), KvError> {
+        let mut batch = Batch::default();
+        batch_put(&mut batch, &[VOUCHERS, vouchee, voucher], &timestamp)?;
+        batch_put(&mut batch, &[VOUCHEES, voucher, vouchee], &timestamp)?;
+        apply(&self.vouches, batch)
+    }
+
+    // old balances are removed in the same batch, so readers never see a mix
+    pub(super) fn write_balances(
+        &self,
+        balances: HashMap<UserAddress, IdtAmount>,
+        timestamp: u64,
+    ) -> Result<(), KvError> {
+        let mut batch = Batch::default();
+        for (parts, _) in scan::<IdtAmount>(&self.balances, &[BALANCE])? {
+            batch.remove(key(&[BALANCE, &parts[0]]));
+        }
+        for (user, balance) in balances {
+            batch_put(&mut batch, &[BALANCE, &user], &balance)?;
+        }
+        batch_put(&mut batch, &[COMPUTED_AT], &timestamp)?;
+        apply(&self.balances, batch)
+    }
+}
+
+#[async_trait]
+impl VouchStorage for SledStorage {
+    async fn vouch(&self, from: UserAddress, to: UserAddress, timestamp: u64) -> Result<(), Error> {
+        Ok(self.insert_vouch(&from, &to, timestamp)?)
+    }
+
+    async fn vouchers_with_time(
+        &self,
+        user: &UserAddress,
+    ) -> Result<HashMap<UserAddress, u64>, Error> {
+        Ok(scan(&self.vouches, &[VOUCHERS, user])?
+            .into_iter()
+            .map(|(mut parts, timestamp)| (parts.remove(0), timestamp))
+            .collect())
+    }
+
+    async fn vouchees_with_time(
+        &self,
+        user: &UserAddress,
+    ) -> Result<HashMap<UserAddress, u64>, Error> {
+        Ok(scan(&self.vouches, &[VOUCHEES, user])?
+            .into_iter()
+            .map(|(mut parts, timestamp)| (parts.remove(0), timestamp))
+            .collect())
+    }
+
+    async fn remove_vouch(&self, voucher: UserAddress, vouchee: UserAddress) -> Result<(), Error> {
+        let mut batch = Batch::default();
+        batch.remove(key(&[VOUCHERS, &vouchee, &voucher]));
+        batch.remove(key(&[VOUCHEES, &voucher, &vouchee]));
+        Ok(apply(&self.vouches, batch)?)
+    }
+
+    async fn all_vouches(&self) -> Result<Vec<(UserAddress, UserAddress, u64)>, Error> {
+        Ok(scan(&self.vouches, &[VOUCHEES])?
+            .into_iter()
+            .map(|(mut parts, timestamp)| {
+                let vouchee = parts.remove(1);
+                (parts.remove(0), vouchee, timestamp)
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl ExternalVouchStorage for SledStorage {
+    async fn vouch(
+        &self,
+        server: UserAddress,
+        from: UserAddress,
+        to: UserAddress,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        Ok(put(
+            &self.external_vouches,
+            &[&to, &server, &from],
+            &timestamp,
+        )?)
+    }
+
+    async fn vouchers_with_time(&self, user: &UserAddress) -> Result<ServerWithVoucher, Error> {
+        let mut vouchers = ServerWithVoucher::new();
+        for (mut parts, timestamp) in scan(&self.external_vouches, &[user])? {
+            let voucher = parts.remove(1);
+            vouchers
+                .entry(parts.remove(0))
+                .or_default()
+                .insert(voucher, timestamp);
+        }
+        Ok(vouchers)
+    }
+
+    async fn find_vouchers(
+        &self,
+        user: &UserAddress,
+        filter: &ExternalVouchFilter,
+    ) -> Result<Vec<ExternalVouch>, Error> {
+        // keys start with the vouchee and the server, so only the server is matched by the scan
+        let vouches = match &filter.server {
+            Some(server) => scan(&self.external_vouches, &[user, server])?
+                .into_iter()
+                .map(|(mut parts, timestamp)| ExternalVouch {
+                    voucher: parts.remove(0),
+                    server: server.clone(),
+                    timestamp,
+                })
+                .collect::<Vec<_>>(),
+            None => scan(&self.external_vouches, &[user])?
+                .into_iter()
+                .map(|(mut parts, timestamp)| ExternalVouch {
+                    server: parts.remove(0),
+                    voucher: parts.remove(0),
+                    timestamp,
+                })
+                .collect(),
+        };
+        Ok(filter.apply(vouches))
+    }
+
+    async fn remove_vouch(
+        &self,
+        server: UserAddress,
+        from: UserAddress,
+        to: UserAddress,
+    ) -> Result<(), Error> {
+        Ok(remove(&self.external_vouches, &[&to, &server, &from])?)
+    }
+
+    async fn all_vouches(
+        &self,
+    ) -> Result<Vec<(UserAddress, UserAddress, UserAddress, u64)>, Error> {
+        Ok(scan(&self.external_vouches, &[])?
+            .into_iter()
+            .map(|(mut parts, timestamp)| {
+                let vouchee = parts.remove(0);
+                let server = parts.remove(0);
+                (server, parts.remove(0), vouchee, timestamp)
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl ProofStorage for SledStorage {
+    async fn set_genesis(&self, users: HashMap<UserAddress, IdtAmount>) -> Result<(), Error> {
+        let mut batch = Batch::default();
+        for (parts, _) in scan::<IdtAmount>(&self.genesis, &[])? {
+            batch.remove(key(&[&parts[0]]));
+        }
+        for (user, balance) in users {
+            batch_put(&mut batch, &[&user], &balance)?;
+        }
+        Ok(apply(&self.genesis, batch)?)
+    }
+
+    async fn genesis_balance(&self, user: &UserAddress) -> Result<Option<IdtAmount>, Error> {
+        Ok(get(&self.genesis, &[user])?)
+    }
+
+    async fn genesis(&self) -> Result<HashMap<UserAddress, IdtAmount>, Error> {
+        Ok(scan(&self.genesis, &[])?
+            .into_iter()
+            .map(|(mut parts, balance)| (parts.remove(0), balance))
+            .collect())
+    }
+
+    async fn set_proof(&self, user: UserAddress, proof: ModeratorProof) -> Result<(), Error> {
+        Ok(put(&self.proofs, &[&user], &proof)?)
+    }
+
+    // a single batch, so the proofs are stored at once
+    async fn set_proofs(&self, proofs: Vec<(UserAddress, ModeratorProof)>) -> Result<(), Error> {
+        let mut batch = Batch::default();
+        for (user, proof) in proofs {
+            batch_put(&mut batch, &[&user], &proof)?;
+        }
+        Ok(apply(&self.proofs, batch)?)
+    }
+
+    async fn proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        Ok(get(&self.proofs, &[user])?)
+    }
+
+    async fn remove_proof(&self, user: &UserAddress) -> Result<(), Error> {
+        Ok(remove(&self.proofs, &[user])?)
+    }
+
+    async fn proven_users(&self) -> Result<HashSet<UserAddress>, Error> {
+        let mut users = HashSet::new();
+        for (mut parts, _) in scan::<ModeratorProof>(&self.proofs, &[])? {
+            users.insert(parts.remove(0));
+        }
+        for (mut parts, _) in scan::<IdtAmount>(&self.genesis, &[])? {
+            users.insert(parts.remove(0));
+        }
+        Ok(users)
+    }
+
+    async fn revoke_proofs(&self, moderator: &UserAddress) -> Result<Vec<UserAddress>, Error> {
+        let mut users = vec![];
+        let mut proofs = Batch::default();
+        let mut revoked = Batch::default();
+        for (mut parts, proof) in scan::<ModeratorProof>(&self.proofs, &[])? {
+            if &proof.moderator != moderator {
+                continue;
+            }
+            let user = parts.remove(0);
+            proofs.remove(key(&[&user]));
+            batch_put(&mut revoked, &[&user], &proof)?;
+            users.push(user);
+        }
+        apply(&self.revoked_proofs, revoked)?;
+        apply(&self.proofs, proofs)?;
+        Ok(users)
+    }
+
+    async fn revoked_proof(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        Ok(get(&self.revoked_proofs, &[user])?)
+    }
+}
+
+#[async_trait]
+impl PenaltyStorage for SledStorage {
+    async fn set_moderator_penalty(
+        &self,
+        user: UserAddress,
+        proof: ModeratorProof,
+    ) -> Result<(), Error> {
+        Ok(put(&self.moderator_penalties, &[&user], &proof)?)
+    }
+
+    async fn set_forgotten_penalty(
+        &self,
+        user: UserAddress,
+        vouchee: UserAddress,
+        penalty: SystemPenalty,
+    ) -> Result<(), Error> {
+        Ok(put(
+            &self.forgotten_penalties,
+            &[&user, &vouchee],
+            &penalty,
+        )?)
+    }
+
+    async fn remove_forgotten(
+        &self,
+        user: UserAddress,
+        forgotten: &UserAddress,
+    ) -> Result<(), Error> {
+        Ok(remove(&self.forgotten_penalties, &[&user, forgotten])?)
+    }
+
+    async fn moderator_penalty(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        Ok(get(&self.moderator_penalties, &[user])?)
+    }
+
+    async fn remove_moderator_penalty(&self, user: &UserAddress) -> Result<(), Error> {
+        Ok(remove(&self.moderator_penalties, &[user])?)
+    }
+
+    async fn forgotten_penalty(
+        &self,
+        user: &UserAddress,
+        forgotten: &UserAddress,
+    ) -> Result<Option<SystemPenalty>, Error> {
+        Ok(get(&self.forgotten_penalties, &[user, forgotten])?)
+    }
+
+    async fn forgotten_users(&self, user: &UserAddress) -> Result<HashSet<UserAddress>, Error> {
+        Ok(scan::<SystemPenalty>(&self.forgotten_penalties, &[user])?
+            .into_iter()
+            .map(|(mut parts, _)| parts.remove(0))
+            .collect())
+    }
+
+    async fn all_moderator_penalties(&self) -> Result<Vec<(UserAddress, ModeratorProof)>, Error> {
+        Ok(scan::<ModeratorProof>(&self.moderator_penalties, &[])?
+            .into_iter()
+            .map(|(mut parts, penalty)| (parts.remove(0), penalty))
+            .collect())
+    }
+
+    async fn all_forgotten_penalties(
+        &self,
+    ) -> Result<Vec<(UserAddress, UserAddress, SystemPenalty)>, Error> {
+        Ok(scan::<SystemPenalty>(&self.forgotten_penalties, &[])?
+            .into_iter()
+            .map(|(mut parts, penalty)| {
+                let forgotten = parts.remove(1);
+                (parts.remove(0), forgotten, penalty)
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl BalanceStorage for SledStorage {
+    async fn set_balances(
+        &self,
+        balances: HashMap<UserAddress, IdtAmount>,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        Ok(self.write_balances(balances, timestamp)?)
+    }
+
+    async fn balance(&self, user: &UserAddress) -> Result<Option<IdtAmount>, Error> {
+        Ok(get(&self.balances, &[BALANCE, user])?)
+    }
+
+    async fn computed_at(&self) -> Result<Option<u64>, Error> {
+        Ok(get(&self.balances, &[COMPUTED_AT])?)
+    }
+}
+
+#[async_trait]
+impl ModeratorStatsStorage for SledStorage {
+    async fn record(
+        &self,
+        moderator: &UserAddress,
+        outcome: ModeratorOutcome,
+        count: u64,
+    ) -> Result<(), Error> {
+        let parts = [moderator.as_str(), outcome.as_str()];
+        loop {
+            let old: Option<u64> = get(&self.moderator_stats, &parts)?;
+            let new = old.unwrap_or_default().saturating_add(count);
+            if swap(&self.moderator_stats, &parts, old.as_ref(), &new)? {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn stats(&self, moderator: &UserAddress) -> Result<ModeratorStats, Error> {
+        let mut stats = ModeratorStats::default();
+        for (parts, count) in scan::<u64>(&self.moderator_stats, &[moderator])? {
+            if let Some(outcome) = ModeratorOutcome::parse(&parts[0]) {
+                stats.add(outcome, count);
+            }
+        }
+        Ok(stats)
+    }
+}
+
+#[async_trait]
+impl CategoryStorage for SledStorage {
+    async fn set_category(
+        &self,
+        user: &UserAddress,
+        category: Option<String>,
+    ) -> Result<(), Error> {
+        match category {
+            Some(category) => put(&self.categories, &[user], &category)?,
+            None => remove(&self.categories, &[user])?,
+        }
+        Ok(())
+    }
+
+    async fn category(&self, user: &UserAddress) -> Result<Option<String>, Error> {
+        Ok(get(&self.categories, &[user])?)
+    }
+}
+
+#[async_trait]
+impl ActivityStorage for SledStorage {
+    async fn set_last_active(&self, user: &UserAddress, timestamp: u64) -> Result<(), Error> {
+        Ok(put(&self.activity, &[user], &timestamp)?)
+    }
+
+    async fn last_active(&self, user: &UserAddress) -> Result<Option<u64>, Error> {
+        Ok(get(&self.activity, &[user])?)
+    }
+}
+
+#[async_trait]
+impl ProofLimitStorage for SledStorage {
+    async fn set_limit(
+        &self,
+        moderator: &UserAddress,
+        limit: Option<IdtAmount>,
+    ) -> Result<(), Error> {
+        match limit {
+            Some(limit) => put(&self.proof_limits, &[moderator], &limit)?,
+            None => remove(&self.proof_limits, &[moderator])?,
+        }
+        Ok(())
+    }
+
+    async fn limit(&self, moderator: &UserAddress) -> Result<Option<IdtAmount>, Error> {
+        Ok(get(&self.proof_limits, &[moderator])?)
+    }
+}
+
+#[async_trait]
+impl PenaltyReasonStorage for SledStorage {
+    async fn set_reason(&self, user: &UserAddress, reason: Option<String>) -> Result<(), Error> {
+        match reason {
+            Some(reason) => put(&self.penalty_reasons, &[user], &reason)?,
+            None => remove(&self.penalty_reasons, &[user])?,
+        }
+        Ok(())
+    }
+
+    async fn reason(&self, user: &UserAddress) -> Result<Option<String>, Error> {
+        Ok(get(&self.penalty_reasons, &[user])?)
+    }
+}
+
+#[async_trait]
+impl StakeStorage for SledStorage {
+    async fn set_slashed_stake(
+        &self,
+        voucher: UserAddress,
+        vouchee: UserAddress,
+        penalty: SystemPenalty,
+    ) -> Result<(), Error> {
+        Ok(put(&self.slashed_stakes, &[&voucher, &vouchee], &penalty)?)
+    }
+
+    async fn slashed_stake(
+        &self,
+        voucher: &UserAddress,
+        vouchee: &UserAddress,
+    ) -> Result<Option<SystemPenalty>, Error> {
+        Ok(get(&self.slashed_stakes, &[voucher, vouchee])?)
+    }
+
+    async fn slashed_stakes(
+        &self,
+        voucher: &UserAddress,
+    ) -> Result<HashMap<UserAddress, SystemPenalty>, Error> {
+        Ok(scan::<SystemPenalty>(&self.slashed_stakes, &[voucher])?
+            .into_iter()
+            .map(|(mut parts, penalty)| (parts.remove(0), penalty))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl ChangeLog for SledStorage {
+    async fn append(&self, change: Event, recorded_at: u64) -> Result<RecordedEvent, Error> {
+        let data = serde_json::to_vec(&(recorded_at, &change))?;
+        // counter, change and versions are written in one transaction, so seq follows the
+        // commit order
+        let seq = (&self.changes, &self.change_versions, &self.change_users)
+            .transaction(|(tx, versions, users)| {
+                let last = tx
+                    .get([])?
+                    .map(|v| u64::from_be_bytes(v.as_ref().try_into().unwrap_or_default()))
+                    .unwrap_or_default();
+                let seq = last + 1;
+                tx.insert(&[], &seq.to_be_bytes())?;
+                tx.insert(&seq.to_be_bytes(), data.as_slice())?;
+                let version = [seq.to_be_bytes(), recorded_at.to_be_bytes()].concat();
+                for user in change.users() {
+                    versions.insert(user.as_bytes(), version.as_slice())?;
+                    users.insert(
+                        [user_prefix(user), seq.to_be_bytes().to_vec()].concat(),
+                        &[],
+                    )?;
+                }
+                Ok(seq)
+            })
+            .map_err(|e: TransactionError<sled::Error>| match e {
+                TransactionError::Abort(e) | TransactionError::Storage(e) => KvError::from(e),
+            })?;
+        Ok(RecordedEvent {
+            seq,
+            recorded_at,
+            event: change,
+        })
+    }
+
+    async fn changes(&self, after_seq: u64, limit: usize) -> Result<Vec<RecordedEvent>, Error> {
+        let Some(start) = after_seq.checked_add(1) else {
+            return Ok(vec![]);
+        };
+        let mut changes = vec![];
+        for record in self.changes.range(start.to_be_bytes()..).take(limit) {
+            let (key, value) = record.map_err(KvError::from)?;
+            let (recorded_at, event) = serde_json::from_slice(&value)?;
+            changes.push(RecordedEvent {
+                seq: u64::from_be_bytes(key.as_ref().try_into().unwrap_or_default()),
+                recorded_at,
+                event,
+            });
+        }
+        Ok(changes)
+    }
+
+    async fn latest(&self) -> Result<Option<Version>, Error> {
+        // the counter under the empty key sorts first, so the last record is the latest change
+        let Some((key, value)) = self.changes.last().map_err(KvError::from)? else {
+            return Ok(None);
+        };
+        if key.is_empty() {
+            return Ok(None);
+        }
+        let (recorded_at, _): (u64, Event) = serde_json::from_slice(&value)?;
+        Ok(Some(Version {
+            seq: u64::from_be_bytes(key.as_ref().try_into().unwrap_or_default()),
+            recorded_at,
+        }))
+    }
+
+    async fn user_version(&self, user: &UserAddress) -> Result<Option<Version>, Error> {
+        let Some(value) = self
+            .change_versions
+            .get(user.as_bytes())
+            .map_err(KvError::from)?
+        else {
+            return Ok(None);
+        };
+        let (seq, recorded_at) = value.split_at(8);
+        Ok(Some(Version {
+            seq: u64::from_be_bytes(seq.try_into().unwrap_or_default()),
+            recorded_at: u64::from_be_bytes(recorded_at.try_into().unwrap_or_default()),
+        }))
+    }
+
+    async fn user_changes(
+        &self,
+        user: &UserAddress,
+        after_seq: u64,
+        limit: usize,
+    ) -> Result<Vec<RecordedEvent>, Error> {
+        let Some(start) = after_seq.checked_add(1) else {
+            return Ok(vec![]);
+        };
+        let prefix = user_prefix(user);
+        let mut changes = vec![];
+        let records = self
+            .change_users
+            .range([prefix.clone(), start.to_be_bytes().to_vec()].concat()..)
+            .take(limit);
+        for record in records {
+            let (key, _) = record.map_err(KvError::from)?;
+            let Some(seq) = key.strip_prefix(prefix.as_slice()) else {
+                break;
+            };
+            let Some(value) = self.changes.get(seq).map_err(KvError::from)? else {
+                continue;
+            };
+            let (recorded_at, event) = serde_json::from_slice(&value)?;
+            changes.push(RecordedEvent {
+                seq: u64::from_be_bytes(seq.try_into().unwrap_or_default()),
+                recorded_at,
+                event,
+            });
+        }
+        Ok(changes)
+    }
+}
+
+fn user_prefix(user: &UserAddress) -> Vec<u8> {
+    let mut prefix = key(&[user]);
+    prefix.push(SEPARATOR);
+    prefix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identity::vouch_external::storage::tests::check_find_vouchers, kv::tests::temporary_storage,
+    };
+
+    #[async_std::test]
+    async fn test_vouches() {
+        let storage = temporary_storage();
+        let (a, b, c) = ("a".to_string(), "b".to_string(), "c".to_string());
+        VouchStorage::vouch(&storage, a.clone(), b.clone(), 1)
+            .await
+            .unwrap();
+        VouchStorage::vouch(&storage, a.clone(), c.clone(), 2)
+            .await
+            .unwrap();
+        VouchStorage::vouch(&storage, c.clone(), b.clone(), 3)
+            .await
+            .unwrap();
+        assert_eq!(
+            VouchStorage::vouchers_with_time(&storage, &b)
+                .await
+                .unwrap(),
+            HashMap::from([(a.clone(), 1), (c.clone(), 3)])
+        );
+        assert_eq!(
+            storage.vouchees_with_time(&a).await.unwrap(),
+            HashMap::from([(b.clone(), 1), (c.clone(), 2)])
+        );
+        let mut vouches = VouchStorage::all_vouches(&storage).await.unwrap();
+        vouches.sort();
+        assert_eq!(
+            vouches,
+            vec![
+                (a.clone(), b.clone(), 1),
+                (a.clone(), c.clone(), 2),
+                (c.clone(), b.clone(), 3)
+            ]
+        );
+
+        VouchStorage::remove_vouch(&storage, a.clone(), b.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            VouchStorage::vouchers_with_time(&storage, &b)
+                .await
+                .unwrap(),
+            HashMap::from([(c.clone(), 3)])
+        );
+        assert_eq!(
+            storage.vouchees_with_time(&a).await.unwrap(),
+            HashMap::from([(c.clone(), 2)])
+        );
+    }
+
+    #[async_std::test]
+    async fn test_external_vouches() {
+        let storage = temporary_storage();
+        let (server, a, b) = ("server".to_string(), "a".to_string(), "b".to_string());
+        ExternalVouchStorage::vouch(&storage, server.clone(), a.clone(), b.clone(), 5)
+            .await
+            .unwrap();
+        let vouchers = ExternalVouchStorage::vouchers_with_time(&storage, &b)
+            .await
+            .unwrap();
+        assert_eq!(vouchers[&server][&a], 5);
+        assert_eq!(
+            ExternalVouchStorage::all_vouches(&storage).await.unwrap(),
+            vec![(server.clone(), a.clone(), b.clone(), 5)]
+        );
+        ExternalVouchStorage::remove_vouch(&storage, server, a, b.clone())
+            .await
+            .unwrap();
+        assert!(
+            ExternalVouchStorage::vouchers_with_time(&storage, &b)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_find_external_vouchers() {
+        check_find_vouchers(&temporary_storage()).await;
+    }
+
+    #[async_std::test]
+    async fn test_proofs_and_penalties() {
+        let storage = temporary_storage();
+        let (user, other) = ("user".to_string(), "other".to_string());
+        let proof = ModeratorProof {
+            moderator: "moderator".to_string(),
+            amount: IdtAmount::new(10),
+            proof_id: 1,
+            timestamp: 2,
+        };
+        storage
+            .set_genesis(HashMap::from([(other.clone(), IdtAmount::new(100))]))
+            .await
+            .unwrap();
+        storage
+            .set_proof(user.clone(), proof.clone())
+            .await
+            .unwrap();
+        assert_eq!(storage.proof(&user).await.unwrap(), Some(proof.clone()));
+        assert_eq!(
+            storage.genesis_balance(&other).await.unwrap(),
+            Some(IdtAmount::new(100))
+        );
+        assert_eq!(
+            storage.genesis().await.unwrap(),
+            HashMap::from([(other.clone(), IdtAmount::new(100))])
+        );
+        assert_eq!(
+            storage.proven_users().await.unwrap(),
+            HashSet::from([user.clone(), other.clone()])
+        );
+        storage.set_genesis(HashMap::new()).await.unwrap();
+        assert_eq!(storage.genesis_balance(&other).await.unwrap(), None);
+        assert_eq!(
+            storage.revoke_proofs(&proof.moderator).await.unwrap(),
+            vec![user.clone()]
+        );
+        assert_eq!(
+            storage.revoked_proof(&user).await.unwrap(),
+            Some(proof.clone())
+        );
+        assert!(storage.proven_users().await.unwrap().is_empty());
+        storage
+            .set_proof(user.clone(), proof.clone())
+            .await
+            .unwrap();
+        storage.remove_proof(&user).await.unwrap();
+        assert!(storage.proven_users().await.unwrap().is_empty());
+        storage
+            .set_proofs(vec![
+                (user.clone(), proof.clone()),
+                (other.clone(), proof.clone()),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(storage.proof(&other).await.unwrap(), Some(proof.clone()));
+        assert_eq!(
+            storage.proven_users().await.unwrap(),
+            HashSet::from([user.clone(), other.clone()])
+        );
+
+        storage
+            .set_moderator_penalty(user.clone(), proof.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.all_moderator_penalties().await.unwrap(),
+            vec![(user.clone(), proof.clone())]
+        );
+        assert_eq!(storage.moderator_penalty(&user).await.unwrap(), Some(proof));
+        storage.remove_moderator_penalty(&user).await.unwrap();
+        assert!(storage.moderator_penalty(&user).await.unwrap().is_none());
+
+        let penalty = SystemPenalty {
+            amount: IdtAmount::new(3),
+            timestamp: 4,
+        };
+        storage
+            .set_forgotten_penalty(user.clone(), other.clone(), penalty.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.all_forgotten_penalties().await.unwrap(),
+            vec![(user.clone(), other.clone(), penalty.clone())]
+        );
+        assert_eq!(
+            storage.forgotten_penalty(&user, &other).await.unwrap(),
+            Some(penalty)
+        );
+        assert_eq!(
+            storage.forgotten_users(&user).await.unwrap(),
+            HashSet::from([other.clone()])
+        );
+        storage
+            .remove_forgotten(user.clone(), &other)
+            .await
+            .unwrap();
+        assert!(storage.forgotten_users(&user).await.unwrap().is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_balances() {
+        let storage = temporary_storage();
+        let (a, b) = ("a".to_string(), "b".to_string());
+        assert!(storage.computed_at().await.unwrap().is_none());
+        storage
+            .set_balances(HashMap::from([(a.clone(), IdtAmount::new(10))]), 100)
+            .await
+            .unwrap();
+        assert_eq!(storage.balance(&a).await.unwrap(), Some(IdtAmount::new(10)));
+        assert_eq!(storage.computed_at().await.unwrap(), Some(100));
+        storage
+            .set_balances(HashMap::from([(b.clone(), IdtAmount::new(20))]), 200)
+            .await
+            .unwrap();
+        assert!(storage.balance(&a).await.unwrap().is_none());
+        assert_eq!(storage.balance(&b).await.unwrap(), Some(IdtAmount::new(20)));
+        assert_eq!(storage.computed_at().await.unwrap(), Some(200));
+    }
+
+    #[async_std::test]
+    async fn test_categories() {
+        let storage = temporary_storage();
+        let user = "a".to_string();
+        storage
+            .set_category(&user, Some("bot".into()))
+            .await
+            .unwrap();
+        assert_eq!(storage.category(&user).await.unwrap(), Some("bot".into()));
+        storage.set_category(&user, None).await.unwrap();
+        assert_eq!(storage.category(&user).await.unwrap(), None);
+    }
+
+    #[async_std::test]
+    async fn test_activity() {
+        let storage = temporary_storage();
+        let user = "a".to_string();
+        assert_eq!(storage.last_active(&user).await.unwrap(), None);
+        storage.set_last_active(&user, 10).await.unwrap();
+        assert_eq!(storage.last_active(&user).await.unwrap(), Some(10));
+    }
+
+    #[async_std::test]
+    async fn test_penalty_reasons() {
+        let storage = temporary_storage();
+        let user = "a".to_string();
+        storage
+            .set_reason(&user, Some("spam".into()))
+            .await
+            .unwrap();
+        assert_eq!(storage.reason(&user).await.unwrap(), Some("spam".into()));
+        storage.set_reason(&user, None).await.unwrap();
+        assert_eq!(storage.reason(&user).await.unwrap(), None);
+    }
+
+    #[async_std::test]
+    async fn test_slashed_stakes() {
+        let storage = temporary_storage();
+        let (voucher, vouchee) = ("a".to_string(), "b".to_string());
+        let penalty = SystemPenalty {
+            amount: IdtAmount::new(100),
+            timestamp: 10,
+        };
+        storage
+            .set_slashed_stake(voucher.clone(), vouchee.clone(), penalty.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.slashed_stake(&voucher, &vouchee).await.unwrap(),
+            Some(penalty.clone())
+        );
+        assert_eq!(
+            storage.slashed_stakes(&voucher).await.unwrap(),
+            HashMap::from([(vouchee, penalty)])
+        );
+        assert!(
+            storage
+                .slashed_stakes(&"ab".into())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_proof_limits() {
+        let storage = temporary_storage();
+        let moderator = "m".to_string();
+        assert_eq!(storage.limit(&moderator).await.unwrap(), None);
+        storage
+            .set_limit(&moderator, Some(IdtAmount::new(100)))
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.limit(&moderator).await.unwrap(),
+            Some(IdtAmount::new(100))
+        );
+        storage.set_limit(&moderator, None).await.unwrap();
+        assert_eq!(storage.limit(&moderator).await.unwrap(), None);
+    }
+
+    #[async_std::test]
+    async fn test_changes() {
+        let storage = temporary_storage();
+        assert!(storage.changes(0, 10).await.unwrap().is_empty());
+        assert_eq!(storage.latest().await.unwrap(), None);
+        for seq in 1..=3 {
+            let change = Event::RemoveProof {
+                user: format!("user{seq}"),
+            };
+            let recorded = storage.append(change, seq * 10).await.unwrap();
+            assert_eq!(recorded.seq, seq);
+        }
+        let changes = storage.changes(1, 1).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].seq, 2);
+        assert_eq!(changes[0].recorded_at, 20);
+        assert_eq!(
+            changes[0].event,
+            Event::RemoveProof {
+                user: "user2".to_string()
+            }
+        );
+        assert_eq!(storage.changes(0, 10).await.unwrap().len(), 3);
+        assert!(storage.changes(u64::MAX, 10).await.unwrap().is_empty());
+        assert_eq!(
+            storage.latest().await.unwrap(),
+            Some(Version {
+                seq: 3,
+                recorded_at: 30
+            })
+        );
+        assert_eq!(
+            storage.user_version(&"user2".to_string()).await.unwrap(),
+            Some(Version {
+                seq: 2,
+                recorded_at: 20
+            })
+        );
+        assert_eq!(storage.user_version(&"a".to_string()).await.unwrap(), None);
+
+        let changes = storage
+            .user_changes(&"user2".to_string(), 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].seq, 2);
+        assert_eq!(changes[0].recorded_at, 20);
+        assert!(
+            storage
+                .user_changes(&"user2".to_string(), 2, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        // "user1" is a prefix of "user10" but not of its key
+        storage
+            .append(
+                Event::RemoveProof {
+                    user: "user10".to_string(),
+                },
+                40,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            storage
+                .user_changes(&"user1".to_string(), 0, 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,932 @@
+// Copies the data of the SQL storage to sled.
+//
+// Tables are read directly, since storage traits cannot list every record. Encrypted
+// addresses are decrypted with the given keys, plain values are copied as is. Genesis is
+// not copied since it is loaded from the config on startup.
+
+use std::collections::{BTreeMap, HashMap};
+
+use sqlx::Row;
+
+use crate::{
+    archive::ArchivedUser,
+    attestations::Attestation,
+    encryption::FieldCipher,
+    federation::storage::HomeClaim,
+    flags::Flag,
+    identity::{IdtAmount, ModeratorProof, SystemPenalty},
+    kv::{
+        SledStorage,
+        error::Error,
+        key, put,
+        server::{APPEAL, CONTRIBUTED, CONTRIBUTION, DRAW, DRAWN, GIVEN},
+    },
+    notifications::{Contact, ContactKind},
+    numbers::{Rational, rescale_column},
+    outbox::OutboxItem,
+    pending_penalties::PendingPenalty,
+    pending_vouches::PendingVouch,
+    pools::{self, MeteredPool},
+    reports::Report,
+    restitution::Appeal,
+    servers::{
+        db::SELECT_SERVERS,
+        storage::{CrossSignature, ScaleSchedule, ServerInfo},
+    },
+    service_accounts::ServiceAccount,
+    summaries::StoredSummary,
+};
+
+// copies every table of the database at `url` and returns the number of copied rows by table
+pub async fn migrate(
+    url: &str,
+    cipher: &FieldCipher,
+    storage: &SledStorage,
+) -> Result<BTreeMap<&'static str, usize>, Error> {
+    let pool = pools::connect("migrate", url).await?;
+    // amounts of databases not opened since milli-IDT precision are still in whole IDT
+    for (table, column) in [
+        ("proofs", "amount"),
+        ("revoked_proofs", "amount"),
+        ("genesis", "balance"),
+        ("moderator_penalties", "amount"),
+        ("forget_penalties", "amount"),
+        ("balances", "balance"),
+        ("slashed_stakes", "amount"),
+        ("proof_limits", "amount"),
+    ] {
+        rescale_column(&pool, table, column).await?;
+    }
+    let mut copied = BTreeMap::new();
+
+    let rows = fetch(&pool, "SELECT voucher, vouchee, timestamp FROM vouches").await?;
+    copied.insert("vouches", rows.len());
+    for row in rows {
+        storage.insert_vouch(
+            &cipher.decode(&row.get::<String, _>(0))?,
+            &cipher.decode(&row.get::<String, _>(1))?,
+            row.get::<i64, _>(2) as u64,
+        )?;
+    }
+
+    let rows = fetch(
+        &pool,
+        "SELECT server, voucher, vouchee, timestamp FROM external_vouches",
+    )
+    .await?;
+    copied.insert("external_vouches", rows.len());
+    for row in rows {
+        put(
+            &storage.external_vouches,
+            &[
+                &cipher.decode(&row.get::<String, _>(2))?,
+                &cipher.decode(&row.get::<String, _>(0))?,
+                &cipher.decode(&row.get::<String, _>(1))?,
+            ],
+            &(row.get::<i64, _>(3) as u64),
+        )?;
+    }
+
+    for (table, tree) in [
+        ("proofs", &storage.proofs),
+        ("revoked_proofs", &storage.revoked_proofs),
+        ("moderator_penalties", &storage.moderator_penalties),
+    ] {
+        let rows = fetch(
+            &pool,
+            &format!("SELECT user, moderator, amount, proof_id, timestamp FROM {table}"),
+        )
+        .await?;
+        copied.insert(table, rows.len());
+        for row in rows {
+            let proof = ModeratorProof {
+                moderator: cipher.decode(&row.get::<String, _>(1))?,
+                amount: IdtAmount::from_milli(row.get::<i64, _>(2) as u64),
+                proof_id: row.get::<i64, _>(3) as u64,
+                timestamp: row.get::<i64, _>(4) as u64,
+            };
+            put(tree, &[&cipher.decode(&row.get::<String, _>(0))?], &proof)?;
+        }
+    }
+
+    let rows = fetch(
+        &pool,
+        "SELECT user, forgotten, amount, timestamp FROM forget_penalties",
+    )
+    .await?;
+    copied.insert("forget_penalties", rows.len());
+    for row in rows {
+        let penalty = SystemPenalty {
+            amount: IdtAmount::from_milli(row.get::<i64, _>(2) as u64),
+            timestamp: row.get::<i64, _>(3) as u64,
+        };
+        put(
+            &storage.forgotten_penalties,
+            &[
+                &cipher.decode(&row.get::<String, _>(0))?,
+                &cipher.decode(&row.get::<String, _>(1))?,
+            ],
+            &penalty,
+        )?;
+    }
+
+    let computed_at = sqlx::query("SELECT computed_at FROM balances_meta")
+        .fetch_optional(&pool)
+        .await?;
+    if let Some(computed_at) = computed_at {
+        let rows = fetch(&pool, "SELECT user, balance FROM balances").await?;
+        copied.insert("balances", rows.len());
+        let mut balances = HashMap::new();
+        for row in rows {
+            balances.insert(
+                cipher.decode(&row.get::<String, _>(0))?,
+                IdtAmount::from_milli(row.get::<i64, _>(1) as u64),
+            );
+        }
+        storage.write_balances(balances, computed_at.get::<i64, _>(0) as u64)?;
+    }
+
+    for (table, tree) in [
+        ("admins", &storage.admins),
+        ("moderators", &storage.moderators),
+    ] {
+        let rows = fetch(&pool, &format!("SELECT user FROM {table}")).await?;
+        copied.insert(table, rows.len());
+        for row in rows {
+            put(tree, &[&row.get::<String, _>(0)], &())?;
+        }
+    }
+
+    let rows = fetch(
+        &pool,
+        "SELECT moderator, successor FROM moderator_successors",
+    )
+    .await?;
+    copied.insert("moderator_successors", rows.len());
+    for row in rows {
+        put(
+            &storage.successors,
+            &[&row.get::<String, _>(0)],
+            &row.get::<String, _>(1),
+        )?;
+    }
+
+    let rows = fetch(
+        &pool,
+        "SELECT moderator, outcome, count FROM moderator_stats",
+    )
+    .await?;
+    copied.insert("moderator_stats", rows.len());
+    for row in rows {
+        put(
+            &storage.moderator_stats,
+            &[&row.get::<String, _>(0), &row.get::<String, _>(1)],
+            &(row.get::<i64, _>(2) as u64),
+        )?;
+    }
+
+    let rows = fetch(&pool, "SELECT user, category FROM user_categories").await?;
+    copied.insert("user_categories", rows.len());
+    for row in rows {
+        put(
+            &storage.categories,
+            &[&cipher.decode(&row.get::<String, _>(0))?],
+            &row.get::<String, _>(1),
+        )?;
+    }
+
+    let rows = fetch(&pool, "SELECT user, last_active FROM user_activity").await?;
+    copied.insert("user_activity", rows.len());
+    for row in rows {
+        put(
+            &storage.activity,
+            &[&cipher.decode(&row.get::<String, _>(0))?],
+            &(row.get::<i64, _>(1) as u64),
+        )?;
+    }
+
+    let rows = fetch(&pool, "SELECT user, reason FROM penalty_reasons").await?;
+    copied.insert("penalty_reasons", rows.len());
+    for row in rows {
+        put(
+            &storage.penalty_reasons,
+            &[&cipher.decode(&row.get::<String, _>(0))?],
+            &row.get::<String, _>(1),
+        )?;
+    }
+
+    let rows = fetch(
+        &pool,
+        "SELECT voucher, vouchee, amount, timestamp FROM slashed_stakes",
+    )
+    .await?;
+    copied.insert("slashed_stakes", rows.len());
+    for row in rows {
+        let penalty = SystemPenalty {
+            amount: IdtAmount::from_milli(row.get::<i64, _>(2) as u64),
+            timestamp: row.get::<i64, _>(3) as u64,
+        };
+        put(
+            &storage.slashed_stakes,
+            &[
+                &cipher.decode(&row.get::<String, _>(0))?,
+                &cipher.decode(&row.get::<String, _>(1))?,
+            ],
+            &penalty,
+        )?;
+    }
+
+    let rows = fetch(&pool, "SELECT moderator, amount FROM proof_limits").await?;
+    copied.insert("proof_limits", rows.len());
+    for row in rows {
+        put(
+            &storage.proof_limits,
+            &[&cipher.decode(&row.get::<String, _>(0))?],
+            &(row.get::<i64, _>(1) as u64),
+        )?;
+    }
+
+    let rows = fetch(&pool, "SELECT user, used_nonce FROM nonces").await?;
+    copied.insert("nonces", rows.len());
+    for row in rows {
+        put(
+            &storage.nonces,
+            &[&row.get::<String, _>(0)],
+            &(row.get::<i64, _>(1) as u64),
+        )?;
+    }
+
+    let rows = fetch(&pool, SELECT_SERVERS).await?;
+    copied.insert("servers", rows.len());
+    for row in rows {
+        let address = row.get::<String, _>(0);
+        let Some(scale) = Rational::new(row.get::<i64, _>(2) as u32, row.get::<i64, _>(3) as u32)
+        else {
+            log::warn!("Skipped server {} with zero scale denominator", address);
+            continue;
+        };
+        let schedule = match row.get::<Option<String>, _>(5) {
+            Some(schedule) => serde_json::from_str(&schedule)?,
+            None => ScaleSchedule::Static,
+        };
+        let info = ServerInfo {
+            url: row.get::<String, _>(1),
+            scale,
+            last_attested: row.get::<i64, _>(4) as u64,
+            schedule,
+            registered_at: row.get::<i64, _>(6) as u64,
+        };
+        put(&storage.servers, &[&address], &info)?;
+    }
+
+    let rows = fetch(&pool, "SELECT address, data FROM cross_signatures").await?;
+    copied.insert("cross_signatures", rows.len());
+    for row in rows {
+        let signature: CrossSignature = serde_json::from_str(&row.get::<String, _>(1))?;
+        put(
+            &storage.cross_signatures,
+            &[&row.get::<String, _>(0)],
+            &signature,
+        )?;
+    }
+
+    let rows = fetch(&pool, "SELECT user, server FROM homes").await?;
+    copied.insert("homes", rows.len());
+    for row in rows {
+        put(
+            &storage.homes,
+            &[&cipher.decode(&row.get::<String, _>(0))?],
+            &cipher.decode(&row.get::<String, _>(1))?,
+        )?;
+    }
+
+    let rows = fetch(&pool, "SELECT user, data FROM home_claims").await?;
+    copied.insert("home_claims", rows.len());
+    for row in rows {
+        let claim: HomeClaim = serde_json::from_str(&row.get::<String, _>(1))?;
+        put(
+            &storage.home_claims,
+            &[&cipher.decode(&row.get::<String, _>(0))?],
+            &claim,
+        )?;
+    }
+
+    let rows = fetch(&pool, "SELECT user, kind, address FROM contacts").await?;
+    copied.insert("contacts", rows.len());
+    for row in rows {
+        let kind = row.get::<String, _>(1);
+        let Some(kind) = ContactKind::parse(&kind) else {
+            log::warn!("Skipped contact of unknown kind {}", kind);
+            continue;
+        };
+        let contact = Contact {
+            kind,
+            address: cipher.decode(&row.get::<String, _>(2))?,
+        };
+        put(
+            &storage.contacts,
+            &[&cipher.decode(&row.get::<String, _>(0))?],
+            &contact,
+        )?;
+    }
+
+    let rows = fetch(&pool, "SELECT name, enabled FROM flags").await?;
+    copied.insert("flags", rows.len());
+    for row in rows {
+        let name = row.get::<String, _>(0);
+        let Some(flag) = Flag::parse(&name) else {
+            log::warn!("Skipped unknown flag {}", name);
+            continue;
+        };
+        put(
+            &storage.flags,
+            &[flag.as_str()],
+            &(row.get::<i64, _>(1) != 0),
+        )?;
+    }
+
+    let rows = fetch(&pool, "SELECT data FROM archived_users").await?;
+    copied.insert("archived_users", rows.len());
+    for row in rows {
+        let record: ArchivedUser = serde_json::from_str(&cipher.decode(&row.get::<String, _>(0))?)?;
+        put(&storage.archive, &[&record.user], &record)?;
+    }
+
+    // pending challenges are short lived and not copied
+    let rows = fetch(&pool, "SELECT data FROM attestations").await?;
+    copied.insert("attestations", rows.len());
+    for row in rows {
+        let attestation: Attestation =
+            serde_json::from_str(&cipher.decode(&row.get::<String, _>(0))?)?;
+        put(
+            &storage.attestations,
+            &[&attestation.provider, &attestation.account],
+            &attestation,
+        )?;
+    }
+
+    let rows = fetch(&pool, "SELECT data FROM reports").await?;
+    copied.insert("reports", rows.len());
+    for row in rows {
+        let report: Report = serde_json::from_str(&cipher.decode(&row.get::<String, _>(0))?)?;
+        storage
+            .reports
+            .insert(report.id.to_be_bytes(), serde_json::to_vec(&report)?)?;
+    }
+
+    let rows = fetch(
+        &pool,
+        "SELECT address, scopes, added_by, added_at FROM service_accounts",
+    )
+    .await?;
+    copied.insert("service_accounts", rows.len());
+    for row in rows {
+        let account = ServiceAccount {
+            address: row.get(0),
+            scopes: serde_json::from_str(&row.get::<String, _>(1))?,
+            added_by: row.get(2),
+            added_at: row.get::<i64, _>(3) as u64,
+        };
+        put(&storage.service_accounts, &[&account.address], &account)?;
+    }
+
+    let rows = fetch(&pool, "SELECT data FROM pending_vouches").await?;
+    copied.insert("pending_vouches", rows.len());
+    for row in rows {
+        let vouch: PendingVouch = serde_json::from_str(&cipher.decode(&row.get::<String, _>(0))?)?;
+        put(
+            &storage.pending_vouches,
+            &[&vouch.voucher, &vouch.vouchee],
+            &vouch,
+        )?;
+    }
+
+    let rows = fetch(&pool, "SELECT owner, address, name FROM petnames").await?;
+    copied.insert("petnames", rows.len());
+    for row in rows {
+        put(
+            &storage.petnames,
+            &[
+                &cipher.decode(&row.get::<String, _>(0))?,
+                &cipher.decode(&row.get::<String, _>(1))?,
+            ],
+            &cipher.decode(&row.get::<String, _>(2))?,
+        )?;
+    }
+
+    let rows = fetch(&pool, "SELECT data FROM outbox").await?;
+    copied.insert("outbox", rows.len());
+    for row in rows {
+        let item: OutboxItem = serde_json::from_str(&cipher.decode(&row.get::<String, _>(0))?)?;
+        put(&storage.outbox, &[&item.key], &item)?;
+    }
+
+    let rows = fetch(&pool, "SELECT data FROM summaries").await?;
+    copied.insert("summaries", rows.len());
+    for row in rows {
+        let summary: StoredSummary =
+            serde_json::from_str(&cipher.decode(&row.get::<String, _>(0))?)?;
+        put(&storage.summaries, &["latest"], &summary)?;
+    }
+
+    let rows = fetch(&pool, "SELECT data FROM pending_penalties").await?;
+    copied.insert("pending_penalties", rows.len());
+    for row in rows {
+        let penalty: PendingPenalty =
+            serde_json::from_str(&cipher.decode(&row.get::<String, _>(0))?)?;
+        storage
+            .pending_penalties
+            .insert(penalty.id.to_be_bytes(), serde_json::to_vec(&penalty)?)?;
+    }
+
+    let rows = fetch(&pool, "SELECT vouchee, voucher, note FROM vouch_notes").await?;
+    copied.insert("vouch_notes", rows.len());
+    for row in rows {
+        put(
+            &storage.vouch_notes,
+            &[
+                &cipher.decode(&row.get::<String, _>(0))?,
+                &cipher.decode(&row.get::<String, _>(1))?,
+            ],
+            &row.get::<String, _>(2),
+        )?;
+    }
+
+    // milli-IDT totals of the pool are kept next to the records, drawn contributions count
+    // as contributed
+    let mut totals = HashMap::new();
+    for (table, kind, total) in [
+        ("restitution_contributions", CONTRIBUTION, CONTRIBUTED),
+        ("restitution_given", GIVEN, CONTRIBUTED),
+        ("restitution_draws", DRAW, DRAWN),
+    ] {
+        let rows = fetch(&pool, &format!("SELECT user, amount FROM {table}")).await?;
+        copied.insert(table, rows.len());
+        let sum: &mut u64 = totals.entry(total).or_default();
+        for row in rows {
+            let amount = row.get::<i64, _>(1) as u64;
+            let user = cipher.decode(&row.get::<String, _>(0))?;
+            storage
+                .restitution
+                .insert(key(&[kind, &user]), &amount.to_be_bytes())?;
+            *sum = sum.saturating_add(amount);
+        }
+    }
+    for (total, sum) in totals {
+        storage.restitution.insert(total, &sum.to_be_bytes())?;
+    }
+
+    let rows = fetch(&pool, "SELECT user, data FROM restitution_appeals").await?;
+    copied.insert("restitution_appeals", rows.len());
+    for row in rows {
+        let appeal: Appeal = serde_json::from_str(&cipher.decode(&row.get::<String, _>(1))?)?;
+        put(&storage.restitution, &[APPEAL, &appeal.user], &appeal)?;
+    }
+
+    Ok(copied)
+}
+
+async fn fetch(pool: &MeteredPool, query: &str) -> Result<Vec<sqlx::any::AnyRow>, Error> {
+    Ok(sqlx::query(query).fetch_all(pool).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::{
+        admins::{AdminStorage, db::DatabaseAdminStorage},
+        archive::{db::DatabaseArchiveStorage, storage::ArchiveStorage},
+        attestations::{db::DatabaseAttestationStorage, storage::AttestationStorage},
+        federation::{db::DatabaseHomeStorage, storage::HomeStorage},
+        flags::{db::DatabaseFlagStorage, storage::FlagStorage},
+        identity::{
+            activity::{db::DatabaseActivityStorage, storage::ActivityStorage},
+            balances::{db::DatabaseBalanceStorage, storage::BalanceStorage},
+            categories::{db::DatabaseCategoryStorage, storage::CategoryStorage},
+            moderators::{
+                ModeratorOutcome, db::DatabaseModeratorStatsStorage, storage::ModeratorStatsStorage,
+            },
+            penalty_reasons::{db::DatabasePenaltyReasonStorage, storage::PenaltyReasonStorage},
+            proof::{db::DatabaseProofStorage, storage::ProofStorage},
+            proof_limits::{db::DatabaseProofLimitStorage, storage::ProofLimitStorage},
+            punish::{db::DatabasePenaltyStorage, storage::PenaltyStorage},
+            stakes::{db::DatabaseStakeStorage, storage::StakeStorage},
+            vouch::{db::DatabaseVouchStorage, storage::VouchStorage},
+            vouch_external::{db::DatabaseExternalVouchStorage, storage::ExternalVouchStorage},
+        },
+        notifications::{db::DatabaseContactStorage, storage::ContactStorage},
+        outbox::{db::DatabaseOutboxStorage, storage::OutboxStorage},
+        pending_penalties::{
+            db::DatabasePendingPenaltyStorage,
+            storage::{PendingPenaltyStorage, tests::penalty as pending_penalty},
+        },
+        pending_vouches::{db::DatabasePendingVouchStorage, storage::PendingVouchStorage},
+        petnames::{db::DatabasePetnameStorage, storage::PetnameStorage},
+        reports::{db::DatabaseReportStorage, storage::ReportStorage},
+        restitution::{
+            db::DatabaseRestitutionStorage,
+            storage::{Pool, RestitutionStorage, tests::pending_appeal},
+        },
+        servers::{db::DatabaseServerStorage, storage::ServerStorage},
+        service_accounts::{
+            ServiceAccount, db::DatabaseServiceAccountStorage, storage::ServiceAccountStorage,
+        },
+        summaries::{db::DatabaseSummaryStorage, storage::SummaryStorage},
+        verify::nonce::{NonceManager, db::DatabaseNonceManager},
+        vouch_notes::{db::DatabaseVouchNoteStorage, storage::VouchNoteStorage},
+    };
+
+    fn test_cipher() -> FieldCipher {
+        FieldCipher::new(vec![("k1".to_string(), [1u8; 32])]).unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_migrate() {
+        let dir = TempDir::new("migrate").unwrap();
+        let url = format!("sqlite://{}?mode=rwc", dir.path().join("db").display());
+        let cipher = test_cipher();
+        let (admin, user, other) = ("admin".to_string(), "user".to_string(), "other".to_string());
+        let proof = ModeratorProof {
+            moderator: "moderator".to_string(),
+            amount: IdtAmount::new(10),
+            proof_id: 1,
+            timestamp: 2,
+        };
+        let penalty = SystemPenalty {
+            amount: IdtAmount::new(3),
+            timestamp: 4,
+        };
+        let contact = Contact {
+            kind: ContactKind::Email,
+            address: "user@example.com".to_string(),
+        };
+        let record = ArchivedUser {
+            user: "archived".to_string(),
+            archived_at: 5,
+            proof: Some(proof.clone()),
+            moderator_penalty: None,
+            forgotten: BTreeMap::new(),
+        };
+
+        let vouches = DatabaseVouchStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        vouches.vouch(user.clone(), other.clone(), 7).await.unwrap();
+        let external = DatabaseExternalVouchStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        external
+            .vouch("server".to_string(), other.clone(), user.clone(), 8)
+            .await
+            .unwrap();
+        let proofs = DatabaseProofStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        proofs.set_proof(user.clone(), proof.clone()).await.unwrap();
+        let penalties = DatabasePenaltyStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        penalties
+            .set_moderator_penalty(other.clone(), proof.clone())
+            .await
+            .unwrap();
+        penalties
+            .set_forgotten_penalty(user.clone(), other.clone(), penalty.clone())
+            .await
+            .unwrap();
+        let balances = DatabaseBalanceStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        balances
+            .set_balances(HashMap::from([(user.clone(), IdtAmount::new(9))]), 100)
+            .await
+            .unwrap();
+        let admins = DatabaseAdminStorage::new(
+            &url,
+            HashSet::from([admin.clone()]),
+            HashSet::from(["moderator".to_string()]),
+        )
+        .await
+        .unwrap();
+        admins.add_moderator(&admin, user.clone()).await.unwrap();
+        admins
+            .set_successor(&admin, "moderator".to_string(), Some(user.clone()))
+            .await
+            .unwrap();
+        let stats = DatabaseModeratorStatsStorage::new(&url).await.unwrap();
+        stats
+            .record(&"moderator".to_string(), ModeratorOutcome::Proof, 2)
+            .await
+            .unwrap();
+        let categories = DatabaseCategoryStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        categories
+            .set_category(&user, Some("bot".into()))
+            .await
+            .unwrap();
+        let activity = DatabaseActivityStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        activity.set_last_active(&user, 7).await.unwrap();
+        let reasons = DatabasePenaltyReasonStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        reasons
+            .set_reason(&user, Some("spam".into()))
+            .await
+            .unwrap();
+        let stakes = DatabaseStakeStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        let stake = SystemPenalty {
+            amount: IdtAmount::new(50),
+            timestamp: 9,
+        };
+        stakes
+            .set_slashed_stake(user.clone(), other.clone(), stake.clone())
+            .await
+            .unwrap();
+        let limits = DatabaseProofLimitStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        limits
+            .set_limit(&"moderator".to_string(), Some(IdtAmount::new(500)))
+            .await
+            .unwrap();
+        let nonces = DatabaseNonceManager::new(&url).await.unwrap();
+        nonces.use_nonce(&user, 11).await.unwrap();
+        let servers = DatabaseServerStorage::new(&url).await.unwrap();
+        servers
+            .add_server(
+                "server".to_string(),
+                ServerInfo {
+                    url: "http://example.com".to_string(),
+                    scale: Rational::new(1, 2).unwrap(),
+                    last_attested: 42,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let cross_signature = CrossSignature {
+            signature: "0x01".to_string(),
+            nonce: 3,
+            expires_at: 50,
+            version: None,
+            received_at: 43,
+        };
+        servers
+            .set_cross_signature("server".to_string(), cross_signature.clone())
+            .await
+            .unwrap();
+        let homes = DatabaseHomeStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        homes
+            .set_home(other.clone(), "server".to_string())
+            .await
+            .unwrap();
+        let claim = HomeClaim {
+            signature: "0x02".to_string(),
+            claimed_at: 6,
+            ..Default::default()
+        };
+        homes.set_claim(user.clone(), claim.clone()).await.unwrap();
+        let contacts = DatabaseContactStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        contacts
+            .set_contact(user.clone(), contact.clone())
+            .await
+            .unwrap();
+        let flags = DatabaseFlagStorage::new(&url).await.unwrap();
+        flags.set_flag(Flag::BanSelfVouch, true).await.unwrap();
+        let archive = DatabaseArchiveStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        archive.archive(record.clone()).await.unwrap();
+        let attestations = DatabaseAttestationStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        let attestation = Attestation {
+            user: user.clone(),
+            provider: "github".to_string(),
+            account: "alice".to_string(),
+            verifier: "verifier".to_string(),
+            proof_id: 3,
+            attested_at: 4,
+        };
+        attestations.link(attestation.clone()).await.unwrap();
+        let reports = DatabaseReportStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        let report = reports
+            .add_report(other.clone(), user.clone(), "spam".to_string(), 5)
+            .await
+            .unwrap();
+        let service_accounts = DatabaseServiceAccountStorage::new(&url).await.unwrap();
+        let account = ServiceAccount {
+            address: other.clone(),
+            scopes: ["vouch".to_string()].into(),
+            added_by: admin.clone(),
+            added_at: 6,
+        };
+        service_accounts.add_account(account.clone()).await.unwrap();
+        let pending_vouches = DatabasePendingVouchStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        let pending = PendingVouch {
+            voucher: user.clone(),
+            vouchee: other.clone(),
+            created_at: 13,
+            confirmed_at: None,
+        };
+        pending_vouches.add_pending(pending.clone()).await.unwrap();
+        let petnames = DatabasePetnameStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        petnames
+            .set_petname(user.clone(), other.clone(), "Other".to_string())
+            .await
+            .unwrap();
+        let outbox = DatabaseOutboxStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        let item = OutboxItem::new(
+            format!("reminder/{user}"),
+            "http://example.com".to_string(),
+            "{}".to_string(),
+            14,
+        );
+        outbox.add_item(item.clone()).await.unwrap();
+        let summaries = DatabaseSummaryStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        let summary = StoredSummary {
+            last_seq: 9,
+            balances: BTreeMap::from([(user.clone(), IdtAmount::new(10))]),
+            ..Default::default()
+        };
+        summaries.set_latest(summary.clone()).await.unwrap();
+        let pending_penalties = DatabasePendingPenaltyStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        let pending_penalty = pending_penalties
+            .add_penalty(pending_penalty(&user, 300))
+            .await
+            .unwrap();
+
+        let vouch_notes = DatabaseVouchNoteStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        vouch_notes
+            .set_note(user.clone(), other.clone(), "0x01".to_string())
+            .await
+            .unwrap();
+
+        let restitution = DatabaseRestitutionStorage::with_cipher(&url, cipher.clone())
+            .await
+            .unwrap();
+        restitution
+            .contribute(other.clone(), IdtAmount::new(20))
+            .await
+            .unwrap();
+        restitution
+            .draw(user.clone(), IdtAmount::new(5))
+            .await
+            .unwrap();
+        let appeal = pending_appeal(&user);
+        restitution.set_appeal(appeal.clone(), None).await.unwrap();
+
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let storage = SledStorage::with_db(db, HashSet::new(), HashSet::new()).unwrap();
+        let copied = migrate(&url, &cipher, &storage).await.unwrap();
+        assert_eq!(copied["vouches"], 1);
+        assert_eq!(copied["moderators"], 2);
+        assert_eq!(copied["archived_users"], 1);
+        assert_eq!(copied["attestations"], 1);
+        assert_eq!(copied["reports"], 1);
+        assert_eq!(copied["service_accounts"], 1);
+        assert_eq!(copied["pending_vouches"], 1);
+        assert_eq!(copied["petnames"], 1);
+        assert_eq!(copied["outbox"], 1);
+        assert_eq!(copied["summaries"], 1);
+        assert_eq!(copied["pending_penalties"], 1);
+        assert_eq!(copied["vouch_notes"], 1);
+        assert_eq!(copied["restitution_contributions"], 1);
+        assert_eq!(copied["restitution_given"], 1);
+        assert_eq!(copied["restitution_draws"], 1);
+        assert_eq!(copied["restitution_appeals"], 1);
+
+        assert_eq!(
+            storage.vouchees_with_time(&user).await.unwrap(),
+            HashMap::from([(other.clone(), 7)])
+        );
+        assert_eq!(
+            ExternalVouchStorage::vouchers_with_time(&storage, &user)
+                .await
+                .unwrap()["server"][&other],
+            8
+        );
+        assert_eq!(storage.proof(&user).await.unwrap(), Some(proof.clone()));
+        assert_eq!(
+            storage.moderator_penalty(&other).await.unwrap(),
+            Some(proof)
+        );
+        assert_eq!(
+            storage.forgotten_penalty(&user, &other).await.unwrap(),
+            Some(penalty)
+        );
+        assert_eq!(
+            storage.balance(&user).await.unwrap(),
+            Some(IdtAmount::new(9))
+        );
+        assert_eq!(storage.computed_at().await.unwrap(), Some(100));
+        assert!(storage.check_admin(&admin).await.is_ok());
+        assert!(storage.check_moderator(&user).await.is_ok());
+        assert_eq!(
+            storage.successor(&"moderator".to_string()).await.unwrap(),
+            Some(user.clone())
+        );
+        assert_eq!(
+            storage
+                .stats(&"moderator".to_string())
+                .await
+                .unwrap()
+                .proofs,
+            2
+        );
+        assert_eq!(storage.category(&user).await.unwrap(), Some("bot".into()));
+        assert_eq!(storage.last_active(&user).await.unwrap(), Some(7));
+        assert_eq!(storage.reason(&user).await.unwrap(), Some("spam".into()));
+        assert_eq!(
+            storage.slashed_stake(&user, &other).await.unwrap(),
+            Some(stake)
+        );
+        assert_eq!(
+            storage.limit(&"moderator".to_string()).await.unwrap(),
+            Some(IdtAmount::new(500))
+        );
+        assert_eq!(storage.next_nonce(&user).await.unwrap(), 12);
+        assert_eq!(
+            storage.servers().await.unwrap()["server"]
+                .scale
+                .denominator(),
+            2
+        );
+        assert_eq!(storage.servers().await.unwrap()["server"].last_attested, 42);
+        assert_eq!(
+            storage.cross_signatures().await.unwrap()["server"],
+            cross_signature
+        );
+        assert_eq!(
+            storage.home(&other).await.unwrap(),
+            Some("server".to_string())
+        );
+        assert_eq!(copied["home_claims"], 1);
+        assert_eq!(storage.claim(&user).await.unwrap(), Some(claim));
+        assert_eq!(storage.contact(&user).await.unwrap(), Some(contact));
+        assert!(storage.is_enabled(Flag::BanSelfVouch).await.unwrap());
+        assert_eq!(storage.archived(&record.user).await.unwrap(), Some(record));
+        assert_eq!(
+            storage.attestations(&user).await.unwrap(),
+            vec![attestation]
+        );
+        assert_eq!(storage.report(report.id).await.unwrap(), Some(report));
+        assert_eq!(storage.accounts().await.unwrap(), vec![account]);
+        assert_eq!(storage.all_pending().await.unwrap(), vec![pending]);
+        assert_eq!(
+            storage.petnames(&user).await.unwrap(),
+            HashMap::from([(other.clone(), "Other".to_string())])
+        );
+        assert_eq!(storage.items().await.unwrap(), vec![item]);
+        assert_eq!(storage.latest().await.unwrap(), Some(summary));
+        assert_eq!(
+            storage.undecided(0, 10).await.unwrap(),
+            vec![pending_penalty]
+        );
+        assert_eq!(
+            storage.notes(&other).await.unwrap(),
+            HashMap::from([(user.clone(), "0x01".to_string())])
+        );
+        assert_eq!(
+            storage.contribution(&other).await.unwrap(),
+            IdtAmount::new(15)
+        );
+        assert_eq!(storage.given(&other).await.unwrap(), IdtAmount::new(5));
+        assert_eq!(storage.restitution(&user).await.unwrap(), IdtAmount::new(5));
+        assert_eq!(
+            storage.pool().await.unwrap(),
+            Pool {
+                contributed: IdtAmount::new(20),
+                drawn: IdtAmount::new(5),
+            }
+        );
+        assert_eq!(storage.appeal(&user).await.unwrap(), Some(appeal));
+    }
+}
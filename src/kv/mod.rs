@@ -0,0 +1,360 @@
+// Embedded key-value storage for single-binary deployments without SQL.
+//
+// All storage traits are implemented by `SledStorage` on top of a single sled database,
+// values are serialized with bincode. Composite keys join user addresses with `\0`, so
+// records of a user can be listed with a prefix scan. Addresses are stored as is,
+// `DB_ENCRYPTION_KEYS` only applies to SQL storage. Existing SQL data can be copied with
+// the `migrate-sled` binary, see `migrate::migrate`.
+
+use std::{collections::HashSet, path::Path};
+
+use serde::{Serialize, de::DeserializeOwned};
+use sled::{Batch, Db, Tree};
+
+use crate::{
+    archive::ArchivedUser,
+    identity::{IdtAmount, ModeratorProof, SystemPenalty, UserAddress},
+    kv::error::Error,
+    numbers::{IDT_DECIMALS, MILLI_PER_IDT},
+    summaries::StoredSummary,
+};
+
+pub mod error;
+pub mod identity;
+pub mod migrate;
+pub mod server;
+
+const SEPARATOR: u8 = 0;
+// key of the default tree, set once amounts are stored in milli-IDT
+const AMOUNT_DECIMALS: &str = "amount_decimals";
+
+pub struct SledStorage {
+    db: Db,
+    // both directions of every vouch under `vouchers` and `vouchees` prefixes
+    vouches: Tree,
+    // key - (vouchee, server, voucher)
+    external_vouches: Tree,
+    genesis: Tree,
+    proofs: Tree,
+    revoked_proofs: Tree,
+    moderator_penalties: Tree,
+    // key - (punished user, forgotten user)
+    forgotten_penalties: Tree,
+    // balances under `balance` prefix and the time of the last recomputation
+    balances: Tree,
+    admins: Tree,
+    moderators: Tree,
+    // retiring moderator -> successor
+    successors: Tree,
+    // key - (moderator, outcome)
+    moderator_stats: Tree,
+    // key - user, value - category
+    categories: Tree,
+    // key - user, value - timestamp of the last signed action
+    activity: Tree,
+    // key - moderator, value - maximum proof amount
+    proof_limits: Tree,
+    // key - punished user, value - reason code of the moderator penalty
+    penalty_reasons: Tree,
+    // key - voucher, vouchee, value - penalty of the slashed stake
+    slashed_stakes: Tree,
+    nonces: Tree,
+    servers: Tree,
+    // key - server, value - its attestation of this server
+    cross_signatures: Tree,
+    homes: Tree,
+    // key - user, value - their claim that this server is their home
+    home_claims: Tree,
+    contacts: Tree,
+    // key - flag name
+    flags: Tree,
+    archive: Tree,
+    // key - challenge
+    attestation_challenges: Tree,
+    // key - (provider, account)
+    attestations: Tree,
+    // key - big endian report id, reports are stored as JSON
+    reports: Tree,
+    // key - address
+    service_accounts: Tree,
+    // key - (voucher, vouchee)
+    pending_vouches: Tree,
+    // owner, address -> petname
+    petnames: Tree,
+    // key - outbox item key
+    outbox: Tree,
+    // the latest scheduled summary under `latest`
+    summaries: Tree,
+    // key - big endian pending penalty id, penalties are stored as JSON
+    pending_penalties: Tree,
+    // vouchee, voucher -> encrypted note
+    vouch_notes: Tree,
+    // (`contribution` | `draw`, user) and the pool totals, milli-IDT as big endian u64
+    restitution: Tree,
+    // key - big endian seq, the counter of the last seq is stored under the empty key
+    changes: Tree,
+    // key - user, value - big endian seq and recorded_at of the latest change of the user
+    change_versions: Tree,
+    // key - (user, big endian seq) of every change listing the user
+    change_users: Tree,
+}
+
+impl SledStorage {
+    pub fn open(
+        path: impl AsRef<Path>,
+        admins: HashSet<UserAddress>,
+        moderators: HashSet<UserAddress>,
+    ) -> Result<Self, Error> {
+        Self::with_db(sled::open(path)?, admins, moderators)
+    }
+
+    // admins and moderators from the config are added to the stored ones
+    pub fn with_db(
+        db: Db,
+        admins: HashSet<UserAddress>,
+        moderators: HashSet<UserAddress>,
+    ) -> Result<Self, Error> {
+        let storage = Self {
+            vouches: db.open_tree("vouches")?,
+            external_vouches: db.open_tree("external_vouches")?,
+            genesis: db.open_tree("genesis")?,
+            proofs: db.open_tree("proofs")?,
+            revoked_proofs: db.open_tree("revoked_proofs")?,
+            moderator_penalties: db.open_tree("moderator_penalties")?,
+            forgotten_penalties: db.open_tree("forgotten_penalties")?,
+            balances: db.open_tree("balances")?,
+            admins: db.open_tree("admins")?,
+            moderators: db.open_tree("moderators")?,
+            successors: db.open_tree("successors")?,
+            moderator_stats: db.open_tree("moderator_stats")?,
+            categories: db.open_tree("categories")?,
+            activity: db.open_tree("activity")?,
+            proof_limits: db.open_tree("proof_limits")?,
+            penalty_reasons: db.open_tree("penalty_reasons")?,
+            slashed_stakes: db.open_tree("slashed_stakes")?,
+            nonces: db.open_tree("nonces")?,
+            servers: db.open_tree("servers")?,
+            cross_signatures: db.open_tree("cross_signatures")?,
+            homes: db.open_tree("homes")?,
+            home_claims: db.open_tree("home_claims")?,
+            contacts: db.open_tree("contacts")?,
+            flags: db.open_tree("flags")?,
+            archive: db.open_tree("archive")?,
+            attestation_challenges: db.open_tree("attestation_challenges")?,
+            attestations: db.open_tree("attestations")?,
+            reports: db.open_tree("reports")?,
+            service_accounts: db.open_tree("service_accounts")?,
+            pending_vouches: db.open_tree("pending_vouches")?,
+            petnames: db.open_tree("petnames")?,
+            outbox: db.open_tree("outbox")?,
+            summaries: db.open_tree("summaries")?,
+            pending_penalties: db.open_tree("pending_penalties")?,
+            vouch_notes: db.open_tree("vouch_notes")?,
+            restitution: db.open_tree("restitution")?,
+            changes: db.open_tree("changes")?,
+            change_versions: db.open_tree("change_versions")?,
+            change_users: db.open_tree("change_users")?,
+            db,
+        };
+        for admin in admins {
+            put(&storage.admins, &[&admin], &())?;
+        }
+        for moderator in moderators {
+            put(&storage.moderators, &[&moderator], &())?;
+        }
+        storage.rescale_amounts()?;
+        Ok(storage)
+    }
+
+    // amounts were stored in whole IDT before milli-IDT precision, rescaled once on open
+    fn rescale_amounts(&self) -> Result<(), Error> {
+        if self.db.contains_key(AMOUNT_DECIMALS)? {
+            return Ok(());
+        }
+        let amount = |amount: &mut IdtAmount| *amount = amount.saturating_mul(MILLI_PER_IDT);
+        let proof = |proof: &mut ModeratorProof| amount(&mut proof.amount);
+        let penalty = |penalty: &mut SystemPenalty| amount(&mut penalty.amount);
+        rescale(&self.genesis, &[], amount)?;
+        rescale(&self.balances, &[identity::BALANCE], amount)?;
+        rescale(&self.proof_limits, &[], amount)?;
+        rescale(&self.proofs, &[], proof)?;
+        rescale(&self.revoked_proofs, &[], proof)?;
+        rescale(&self.moderator_penalties, &[], proof)?;
+        rescale(&self.forgotten_penalties, &[], penalty)?;
+        rescale(&self.slashed_stakes, &[], penalty)?;
+        rescale(&self.archive, &[], |record: &mut ArchivedUser| {
+            record.proof.iter_mut().for_each(proof);
+            record.moderator_penalty.iter_mut().for_each(proof);
+            record.forgotten.values_mut().for_each(penalty);
+        })?;
+        rescale(&self.summaries, &[], |stored: &mut StoredSummary| {
+            amount(&mut stored.summary.penalty_amount);
+            for mover in &mut stored.summary.top_movers {
+                amount(&mut mover.before);
+                amount(&mut mover.after);
+            }
+            stored.balances.values_mut().for_each(amount);
+        })?;
+        self.db
+            .insert(AMOUNT_DECIMALS, &IDT_DECIMALS.to_be_bytes())?;
+        Ok(())
+    }
+
+    // writes all pending changes to disk
+    pub async fn flush(&self) -> Result<(), Error> {
+        self.db.flush_async().await?;
+        Ok(())
+    }
+}
+
+fn key(parts: &[&str]) -> Vec<u8> {
+    let mut key = vec![];
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            key.push(SEPARATOR);
+        }
+        key.extend_from_slice(part.as_bytes());
+    }
+    key
+}
+
+fn get<T: DeserializeOwned>(tree: &Tree, parts: &[&str]) -> Result<Option<T>, Error> {
+    match tree.get(key(parts))? {
+        Some(value) => Ok(Some(bincode::deserialize(&value)?)),
+        None => Ok(None),
+    }
+}
+
+fn put<T: Serialize>(tree: &Tree, parts: &[&str], value: &T) -> Result<(), Error> {
+    tree.insert(key(parts), bincode::serialize(value)?)?;
+    Ok(())
+}
+
+fn remove(tree: &Tree, parts: &[&str]) -> Result<(), Error> {
+    tree.remove(key(parts))?;
+    Ok(())
+}
+
+// replaces `old` with `new`, returns false if the stored value is not `old`
+fn swap<T: Serialize>(
+    tree: &Tree,
+    parts: &[&str],
+    old: Option<&T>,
+    new: &T,
+) -> Result<bool, Error> {
+    let old = old.map(bincode::serialize).transpose()?;
+    let swapped = tree.compare_and_swap(key(parts), old, Some(bincode::serialize(new)?))?;
+    Ok(swapped.is_ok())
+}
+
+fn batch_put<T: Serialize>(batch: &mut Batch, parts: &[&str], value: &T) -> Result<(), Error> {
+    batch.insert(key(parts), bincode::serialize(value)?);
+    Ok(())
+}
+
+fn apply(tree: &Tree, batch: Batch) -> Result<(), Error> {
+    tree.apply_batch(batch)?;
+    Ok(())
+}
+
+// records whose key starts with `prefix`, returned with the remaining key parts
+fn scan<T: DeserializeOwned>(tree: &Tree, prefix: &[&str]) -> Result<Vec<(Vec<String>, T)>, Error> {
+    let mut start = key(prefix);
+    if !prefix.is_empty() {
+        start.push(SEPARATOR);
+    }
+    let mut records = vec![];
+    for record in tree.scan_prefix(&start) {
+        let (key, value) = record?;
+        let parts = String::from_utf8_lossy(&key[start.len()..])
+            .split(SEPARATOR as char)
+            .map(str::to_string)
+            .collect();
+        records.push((parts, bincode::deserialize(&value)?));
+    }
+    Ok(records)
+}
+
+// updates every record whose key starts with `prefix`
+fn rescale<T: Serialize + DeserializeOwned>(
+    tree: &Tree,
+    prefix: &[&str],
+    update: impl Fn(&mut T),
+) -> Result<(), Error> {
+    for (parts, mut value) in scan::<T>(tree, prefix)? {
+        update(&mut value);
+        let mut key = prefix.to_vec();
+        key.extend(parts.iter().map(String::as_str));
+        put(tree, &key, &value)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub fn temporary_storage() -> SledStorage {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        SledStorage::with_db(
+            db,
+            HashSet::from(["admin".to_string()]),
+            HashSet::from(["moderator".to_string()]),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_scan() {
+        let storage = temporary_storage();
+        put(&storage.vouches, &["a", "b"], &1u64).unwrap();
+        put(&storage.vouches, &["a", "c"], &2u64).unwrap();
+        put(&storage.vouches, &["ab", "d"], &3u64).unwrap();
+        assert_eq!(
+            scan::<u64>(&storage.vouches, &["a"]).unwrap(),
+            vec![(vec!["b".to_string()], 1), (vec!["c".to_string()], 2)]
+        );
+        assert_eq!(scan::<u64>(&storage.vouches, &[]).unwrap().len(), 3);
+        assert_eq!(get::<u64>(&storage.vouches, &["ab", "d"]).unwrap(), Some(3));
+        remove(&storage.vouches, &["ab", "d"]).unwrap();
+        assert_eq!(get::<u64>(&storage.vouches, &["ab", "d"]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_rescale_amounts() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        // stored in whole IDT before milli-IDT precision
+        let proofs = db.open_tree("proofs").unwrap();
+        let proof = ModeratorProof {
+            moderator: "moderator".to_string(),
+            amount: IdtAmount::from_milli(95),
+            proof_id: 1,
+            timestamp: 2,
+        };
+        put(&proofs, &["user"], &proof).unwrap();
+        let balances = db.open_tree("balances").unwrap();
+        put(&balances, &[identity::BALANCE, "user"], &10u64).unwrap();
+        put(&balances, &[identity::COMPUTED_AT], &5u64).unwrap();
+
+        let storage = SledStorage::with_db(db.clone(), HashSet::new(), HashSet::new()).unwrap();
+        let rescaled = get::<ModeratorProof>(&storage.proofs, &["user"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(rescaled.amount, IdtAmount::new(95));
+        assert_eq!(
+            get::<IdtAmount>(&storage.balances, &[identity::BALANCE, "user"]).unwrap(),
+            Some(IdtAmount::new(10))
+        );
+        assert_eq!(
+            get::<u64>(&storage.balances, &[identity::COMPUTED_AT]).unwrap(),
+            Some(5)
+        );
+
+        // rescaled only once
+        let storage = SledStorage::with_db(db, HashSet::new(), HashSet::new()).unwrap();
+        let stored = get::<ModeratorProof>(&storage.proofs, &["user"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.amount, IdtAmount::new(95));
+    }
+}
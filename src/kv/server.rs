@@ -0,0 +1,1233 @@
+use std::collections::{BTreeMap, HashMap};
+
+use async_trait::async_trait;
+use sled::{
+    IVec,
+    transaction::{ConflictableTransactionResult, TransactionError},
+};
+
+use crate::{
+    admins::{AdminStorage, error::Error as AdminError},
+    archive::{ArchivedUser, error::Error as ArchiveError, storage::ArchiveStorage},
+    attestations::{
+        Attestation, Challenge, error::Error as AttestationError, storage::AttestationStorage,
+    },
+    federation::{
+        error::Error as FederationError,
+        storage::{HomeClaim, HomeStorage},
+    },
+    flags::{Flag, error::Error as FlagError, storage::FlagStorage},
+    identity::{IdtAmount, UserAddress},
+    kv::{SledStorage, error::Error as KvError, get, key, put, remove, scan, swap},
+    notifications::{Contact, error::Error as NotificationError, storage::ContactStorage},
+    outbox::{OutboxItem, error::Error as OutboxError, storage::OutboxStorage},
+    pending_penalties::{
+        Decision, PendingPenalty, PendingPenaltyId, error::Error as PendingPenaltyError,
+        storage::PendingPenaltyStorage,
+    },
+    pending_vouches::{
+        PendingVouch, error::Error as PendingVouchError, storage::PendingVouchStorage,
+    },
+    petnames::{error::Error as PetnameError, storage::PetnameStorage},
+    reports::{Report, ReportId, Resolution, error::Error as ReportError, storage::ReportStorage},
+    restitution::{
+        Appeal, AppealStatus,
+        error::Error as RestitutionError,
+        storage::{Pool, RestitutionStorage, pro_rata_shares},
+    },
+    servers::{
+        error::Error as ServerError,
+        storage::{CrossSignature, ServerInfo, ServerStorage},
+    },
+    service_accounts::{
+        ServiceAccount, error::Error as ServiceAccountError, storage::ServiceAccountStorage,
+    },
+    summaries::{StoredSummary, error::Error as SummaryError, storage::SummaryStorage},
+    verify::nonce::{Nonce, NonceManager, error::Error as NonceError},
+    vouch_notes::{error::Error as VouchNoteError, storage::VouchNoteStorage},
+};
+
+#[async_trait]
+impl AdminStorage for SledStorage {
+    async fn check_admin(&self, user: &UserAddress) -> Result<(), AdminError> {
+        if get::<()>(&self.admins, &[user])?.is_some() {
+            return Ok(());
+        }
+        Err(AdminError::NoAdminPrivilege)
+    }
+
+    async fn check_moderator(&self, user: &UserAddress) -> Result<(), AdminError> {
+        if get::<()>(&self.moderators, &[user])?.is_some() {
+            return Ok(());
+        }
+        Err(AdminError::NoModeratorPrivilege)
+    }
+
+    async fn add_admin(
+        &self,
+        caller: &UserAddress,
+        new_admin: UserAddress,
+    ) -> Result<(), AdminError> {
+        self.check_admin(caller).await?;
+        Ok(put(&self.admins, &[&new_admin], &())?)
+    }
+
+    async fn remove_admin(
+        &self,
+        caller: &UserAddress,
+        admin: UserAddress,
+    ) -> Result<(), AdminError> {
+        self.check_admin(caller).await?;
+        Ok(remove(&self.admins, &[&admin])?)
+    }
+
+    async fn add_moderator(
+        &self,
+        caller: &UserAddress,
+        moderator: UserAddress,
+    ) -> Result<(), AdminError> {
+        self.check_admin(caller).await?;
+        Ok(put(&self.moderators, &[&moderator], &())?)
+    }
+
+    async fn remove_moderator(
+        &self,
+        caller: &UserAddress,
+        moderator: UserAddress,
+    ) -> Result<(), AdminError> {
+        self.check_admin(caller).await?;
+        Ok(remove(&self.moderators, &[&moderator])?)
+    }
+
+    async fn set_successor(
+        &self,
+        caller: &UserAddress,
+        moderator: UserAddress,
+        successor: Option<UserAddress>,
+    ) -> Result<(), AdminError> {
+        self.check_admin(caller).await?;
+        match successor {
+            Some(successor) => Ok(put(&self.successors, &[&moderator], &successor)?),
+            None => Ok(remove(&self.successors, &[&moderator])?),
+        }
+    }
+
+    async fn successor(&self, moderator: &UserAddress) -> Result<Option<UserAddress>, AdminError> {
+        Ok(get(&self.successors, &[moderator])?)
+    }
+}
+
+#[async_trait]
+impl NonceManager for SledStorage {
+    async fn use_nonce(&self, user: &UserAddress, nonce: Nonce) -> Result<(), NonceError> {
+        // compare-and-swap, so the same nonce cannot be accepted by two concurrent requests
+        loop {
+            let used: Option<Nonce> = get(&self.nonces, &[user])?;
+            if used.unwrap_or_default() >= nonce {
+                return Err(NonceError::NonceUsedError(nonce));
+            }
+            if swap(&self.nonces, &[user], used.as_ref(), &nonce)? {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn next_nonce(&self, user: &UserAddress) -> Result<Nonce, NonceError> {
+        get::<Nonce>(&self.nonces, &[user])?
+            .unwrap_or_default()
+            .checked_add(1)
+            .ok_or(NonceError::NonceOverflowError)
+    }
+}
+
+#[async_trait]
+impl ServerStorage for SledStorage {
+    async fn add_server(&self, address: UserAddress, info: ServerInfo) -> Result<(), ServerError> {
+        Ok(put(&self.servers, &[&address], &info)?)
+    }
+
+    async fn remove_server(&self, address: UserAddress) -> Result<(), ServerError> {
+        remove(&self.cross_signatures, &[&address])?;
+        Ok(remove(&self.servers, &[&address])?)
+    }
+
+    async fn servers(&self) -> Result<HashMap<UserAddress, ServerInfo>, ServerError> {
+        Ok(scan(&self.servers, &[])?
+            .into_iter()
+            .map(|(mut parts, info)| (parts.remove(0), info))
+            .collect())
+    }
+
+    async fn set_cross_signature(
+        &self,
+        address: UserAddress,
+        signature: CrossSignature,
+    ) -> Result<(), ServerError> {
+        Ok(put(&self.cross_signatures, &[&address], &signature)?)
+    }
+
+    async fn cross_signatures(&self) -> Result<HashMap<UserAddress, CrossSignature>, ServerError> {
+        Ok(scan(&self.cross_signatures, &[])?
+            .into_iter()
+            .map(|(mut parts, signature)| (parts.remove(0), signature))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl HomeStorage for SledStorage {
+    async fn set_home(
+        &self,
+        user: UserAddress,
+        server: UserAddress,
+    ) -> Result<(), FederationError> {
+        Ok(put(&self.homes, &[&user], &server)?)
+    }
+
+    async fn remove_home(&self, user: &UserAddress) -> Result<(), FederationError> {
+        Ok(remove(&self.homes, &[user])?)
+    }
+
+    async fn home(&self, user: &UserAddress) -> Result<Option<UserAddress>, FederationError> {
+        Ok(get(&self.homes, &[user])?)
+    }
+
+    async fn set_claim(&self, user: UserAddress, claim: HomeClaim) -> Result<(), FederationError> {
+        Ok(put(&self.home_claims, &[&user], &claim)?)
+    }
+
+    async fn remove_claim(&self, user: &UserAddress) -> Result<(), FederationError> {
+        Ok(remove(&self.home_claims, &[user])?)
+    }
+
+    async fn claim(&self, user: &UserAddress) -> Result<Option<HomeClaim>, FederationError> {
+        Ok(get(&self.home_claims, &[user])?)
+    }
+}
+
+#[async_trait]
+impl ContactStorage for SledStorage {
+    async fn set_contact(
+        &self,
+        user: UserAddress,
+        contact: Contact,
+    ) -> Result<(), NotificationError> {
+        Ok(put(&self.contacts, &[&user], &contact)?)
+    }
+
+    async fn remove_contact(&self, user: &UserAddress) -> Result<(), NotificationError> {
+        Ok(remove(&self.contacts, &[user])?)
+    }
+
+    async fn contact(&self, user: &UserAddress) -> Result<Option<Contact>, NotificationError> {
+        Ok(get(&self.contacts, &[user])?)
+    }
+}
+
+#[async_trait]
+impl FlagStorage for SledStorage {
+    async fn set_flag(&self, flag: Flag, enabled: bool) -> Result<(), FlagError> {
+        Ok(put(&self.flags, &[flag.as_str()], &enabled)?)
+    }
+
+    async fn flags(&self) -> Result<BTreeMap<Flag, bool>, FlagError> {
+        let mut flags = BTreeMap::new();
+        for flag in Flag::ALL {
+            let enabled = get(&self.flags, &[flag.as_str()])?;
+            flags.insert(flag, enabled.unwrap_or_default());
+        }
+        Ok(flags)
+    }
+}
+
+#[async_trait]
+impl ArchiveStorage for SledStorage {
+    async fn archive(&self, record: ArchivedUser) -> Result<(), ArchiveError> {
+        Ok(put(&self.archive, &[&record.user], &record)?)
+    }
+
+    async fn archived(&self, user: &UserAddress) -> Result<Option<ArchivedUser>, ArchiveError> {
+        Ok(get(&self.archive, &[user])?)
+    }
+
+    async fn remove(&self, user: &UserAddress) -> Result<(), ArchiveError> {
+        Ok(remove(&self.archive, &[user])?)
+    }
+}
+
+#[async_trait]
+impl AttestationStorage for SledStorage {
+    async fn add_challenge(&self, challenge: Challenge) -> Result<(), AttestationError> {
+        Ok(put(
+            &self.attestation_challenges,
+            &[&challenge.challenge],
+            &challenge,
+        )?)
+    }
+
+    async fn take_challenge(&self, challenge: &str) -> Result<Option<Challenge>, AttestationError> {
+        let removed = self
+            .attestation_challenges
+            .remove(key(&[challenge]))
+            .map_err(KvError::from)?;
+        match removed {
+            Some(value) => Ok(Some(bincode::deserialize(&value).map_err(KvError::from)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn remove_expired(&self, now: u64) -> Result<(), AttestationError> {
+        for (_, challenge) in scan::<Challenge>(&self.attestation_challenges, &[])? {
+            if challenge.expires_at < now {
+                remove(&self.attestation_challenges, &[&challenge.challenge])?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn link(&self, attestation: Attestation) -> Result<(), AttestationError> {
+        Ok(put(
+            &self.attestations,
+            &[&attestation.provider, &attestation.account],
+            &attestation,
+        )?)
+    }
+
+    async fn linked(
+        &self,
+        provider: &str,
+        account: &str,
+    ) -> Result<Option<Attestation>, AttestationError> {
+        Ok(get(&self.attestations, &[provider, account])?)
+    }
+
+    async fn attestations(&self, user: &UserAddress) -> Result<Vec<Attestation>, AttestationError> {
+        // keys are ordered by provider and account
+        Ok(scan::<Attestation>(&self.attestations, &[])?
+            .into_iter()
+            .map(|(_, attestation)| attestation)
+            .filter(|attestation| &attestation.user == user)
+            .collect())
+    }
+}
+
+// reports use internally tagged enums, which bincode cannot decode
+fn decode_report(value: &[u8]) -> Result<Report, ReportError> {
+    Ok(serde_json::from_slice(value)?)
+}
+
+#[async_trait]
+impl ReportStorage for SledStorage {
+    async fn add_report(
+        &self,
+        reporter: UserAddress,
+        user: UserAddress,
+        reason: String,
+        created_at: u64,
+    ) -> Result<Report, ReportError> {
+        loop {
+            let last = self.reports.last().map_err(KvError::from)?;
+            let id = match last {
+                Some((key, _)) => u64::from_be_bytes(key.as_ref().try_into().unwrap_or_default()),
+                None => 0,
+            } + 1;
+            let report = Report {
+                id,
+                reporter: reporter.clone(),
+                user: user.clone(),
+                reason: reason.clone(),
+                created_at,
+                resolution: None,
+            };
+            let value = serde_json::to_vec(&report)?;
+            // another report took the id, retry with the next one
+            let inserted = self
+                .reports
+                .compare_and_swap(id.to_be_bytes(), None::<&[u8]>, Some(value))
+                .map_err(KvError::from)?;
+            if inserted.is_ok() {
+                return Ok(report);
+            }
+        }
+    }
+
+    async fn report(&self, id: ReportId) -> Result<Option<Report>, ReportError> {
+        let value = self.reports.get(id.to_be_bytes()).map_err(KvError::from)?;
+        value.map(|value| decode_report(&value)).transpose()
+    }
+
+    async fn open_reports(
+        &self,
+        after_id: ReportId,
+        limit: usize,
+    ) -> Result<Vec<Report>, ReportError> {
+        let Some(start) = after_id.checked_add(1) else {
+            return Ok(vec![]);
+        };
+        let mut reports = vec![];
+        for record in self.reports.range(start.to_be_bytes()..) {
+            let (_, value) = record.map_err(KvError::from)?;
+            let report = decode_report(&value)?;
+            if report.resolution.is_none() {
+                reports.push(report);
+            }
+            if reports.len() >= limit {
+                break;
+            }
+        }
+        Ok(reports)
+    }
+
+    async fn resolve(&self, id: ReportId, resolution: Resolution) -> Result<bool, ReportError> {
+        let Some(old) = self.reports.get(id.to_be_bytes()).map_err(KvError::from)? else {
+            return Ok(false);
+        };
+        let report = decode_report(&old)?;
+        if report.resolution.is_some() {
+            return Ok(false);
+        }
+        let new = serde_json::to_vec(&Report {
+            resolution: Some(resolution),
+            ..report
+        })?;
+        // only the first resolution replaces the open report
+        let swapped = self
+            .reports
+            .compare_and_swap(id.to_be_bytes(), Some(old), Some(new))
+            .map_err(KvError::from)?;
+        Ok(swapped.is_ok())
+    }
+}
+
+#[async_trait]
+impl ServiceAccountStorage for SledStorage {
+    async fn add_account(&self, account: ServiceAccount) -> Result<(), ServiceAccountError> {
+        Ok(put(&self.service_accounts, &[&account.address], &account)?)
+    }
+
+    async fn remove_account(&self, address: &UserAddress) -> Result<bool, ServiceAccountError> {
+        let removed = self
+            .service_accounts
+            .remove(key(&[address]))
+            .map_err(KvError::from)?;
+        Ok(removed.is_some())
+    }
+
+    async fn account(
+        &self,
+        address: &UserAddress,
+    ) -> Result<Option<ServiceAccount>, ServiceAccountError> {
+        Ok(get(&self.service_accounts, &[address])?)
+    }
+
+    async fn accounts(&self) -> Result<Vec<ServiceAccount>, ServiceAccountError> {
+        let accounts = scan(&self.service_accounts, &[])?;
+        Ok(accounts.into_iter().map(|(_, account)| account).collect())
+    }
+}
+
+#[async_trait]
+impl PendingVouchStorage for SledStorage {
+    async fn add_pending(&self, vouch: PendingVouch) -> Result<(), PendingVouchError> {
+        Ok(put(
+            &self.pending_vouches,
+            &[&vouch.voucher, &vouch.vouchee],
+            &vouch,
+        )?)
+    }
+
+    async fn pending(
+        &self,
+        voucher: &UserAddress,
+        vouchee: &UserAddress,
+    ) -> Result<Option<PendingVouch>, PendingVouchError> {
+        Ok(get(&self.pending_vouches, &[voucher, vouchee])?)
+    }
+
+    async fn remove_pending(
+        &self,
+        voucher: &UserAddress,
+        vouchee: &UserAddress,
+    ) -> Result<bool, PendingVouchError> {
+        let removed = self
+            .pending_vouches
+            .remove(key(&[voucher, vouchee]))
+            .map_err(KvError::from)?;
+        Ok(removed.is_some())
+    }
+
+    async fn all_pending(&self) -> Result<Vec<PendingVouch>, PendingVouchError> {
+        let vouches = scan(&self.pending_vouches, &[])?;
+        Ok(vouches.into_iter().map(|(_, vouch)| vouch).collect())
+    }
+}
+
+#[async_trait]
+impl PetnameStorage for SledStorage {
+    async fn set_petname(
+        &self,
+        owner: UserAddress,
+        address: UserAddress,
+        name: String,
+    ) -> Result<(), PetnameError> {
+        Ok(put(&self.petnames, &[&owner, &address], &name)?)
+    }
+
+    async fn remove_petname(
+        &self,
+        owner: &UserAddress,
+        address: &UserAddress,
+    ) -> Result<(), PetnameError> {
+        Ok(remove(&self.petnames, &[owner, address])?)
+    }
+
+    async fn petnames(
+        &self,
+        owner: &UserAddress,
+    ) -> Result<HashMap<UserAddress, String>, PetnameError> {
+        Ok(scan(&self.petnames, &[owner])?
+            .into_iter()
+            .map(|(mut parts, name)| (parts.remove(0), name))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl VouchNoteStorage for SledStorage {
+    async fn set_note(
+        &self,
+        voucher: UserAddress,
+        vouchee: UserAddress,
+        note: String,
+    ) -> Result<(), VouchNoteError> {
+        Ok(put(&self.vouch_notes, &[&vouchee, &voucher], &note)?)
+    }
+
+    async fn notes(
+        &self,
+        vouchee: &UserAddress,
+    ) -> Result<HashMap<UserAddress, String>, VouchNoteError> {
+        Ok(scan(&self.vouch_notes, &[vouchee])?
+            .into_iter()
+            .map(|(mut parts, note)| (parts.remove(0), note))
+            .collect())
+    }
+}
+
+pub(super) const CONTRIBUTION: &str = "contribution";
+pub(super) const GIVEN: &str = "given";
+pub(super) const DRAW: &str = "draw";
+pub(super) const APPEAL: &str = "appeal";
+pub(super) const CONTRIBUTED: &[u8] = b"contributed";
+pub(super) const DRAWN: &[u8] = b"drawn";
+
+fn milli(value: Option<IVec>) -> IdtAmount {
+    let milli = value
+        .map(|v| u64::from_be_bytes(v.as_ref().try_into().unwrap_or_default()))
+        .unwrap_or_default();
+    IdtAmount::from_milli(milli)
+}
+
+// users of the records whose key starts with `kind`
+fn scan_users(tree: &sled::Tree, kind: &str) -> Result<Vec<UserAddress>, KvError> {
+    let prefix = key(&[kind, ""]);
+    let mut users = vec![];
+    for record in tree.scan_prefix(&prefix) {
+        let (key, _) = record?;
+        users.push(String::from_utf8_lossy(&key[prefix.len()..]).into_owned());
+    }
+    Ok(users)
+}
+
+fn transaction_error(e: TransactionError<sled::Error>) -> KvError {
+    match e {
+        TransactionError::Abort(e) | TransactionError::Storage(e) => KvError::from(e),
+    }
+}
+
+// records and pool totals are updated in one transaction, so the totals follow the records
+#[async_trait]
+impl RestitutionStorage for SledStorage {
+    async fn contribute(
+        &self,
+        user: UserAddress,
+        amount: IdtAmount,
+    ) -> Result<IdtAmount, RestitutionError> {
+        let record = key(&[CONTRIBUTION, &user]);
+        let contribution = self
+            .restitution
+            .transaction(|tx| -> ConflictableTransactionResult<_, sled::Error> {
+                let contribution = milli(tx.get(&record)?) + amount;
+                let contributed = milli(tx.get(CONTRIBUTED)?) + amount;
+                tx.insert(record.as_slice(), &contribution.milli().to_be_bytes())?;
+                tx.insert(CONTRIBUTED, &contributed.milli().to_be_bytes())?;
+                Ok(contribution)
+            })
+            .map_err(transaction_error)?;
+        Ok(contribution)
+    }
+
+    async fn withdraw(
+        &self,
+        user: &UserAddress,
+        amount: IdtAmount,
+    ) -> Result<bool, RestitutionError> {
+        let record = key(&[CONTRIBUTION, user]);
+        let withdrawn = self
+            .restitution
+            .transaction(|tx| -> ConflictableTransactionResult<_, sled::Error> {
+                let contribution = milli(tx.get(&record)?);
+                if contribution < amount {
+                    return Ok(false);
+                }
+                let contribution = contribution - amount;
+                match contribution.is_zero() {
+                    true => tx.remove(record.as_slice())?,
+                    false => tx.insert(record.as_slice(), &contribution.milli().to_be_bytes())?,
+                };
+                let contributed = milli(tx.get(CONTRIBUTED)?).saturating_sub(amount);
+                tx.insert(CONTRIBUTED, &contributed.milli().to_be_bytes())?;
+                Ok(true)
+            })
+            .map_err(transaction_error)?;
+        Ok(withdrawn)
+    }
+
+    async fn draw(
+        &self,
+        user: UserAddress,
+        amount: IdtAmount,
+    ) -> Result<IdtAmount, RestitutionError> {
+        // transactions can not scan, so the contributors are listed before and their records
+        // are read again inside. Contributors added in between are not drawn from.
+        let contributors = scan_users(&self.restitution, CONTRIBUTION)?;
+        let record = key(&[DRAW, &user]);
+        let drawn = self
+            .restitution
+            .transaction(|tx| -> ConflictableTransactionResult<_, sled::Error> {
+                let mut contributions = vec![];
+                for contributor in &contributors {
+                    contributions.push(milli(tx.get(key(&[CONTRIBUTION, contributor]))?));
+                }
+                let drawn = amount.min(contributions.iter().copied().sum());
+                if drawn.is_zero() {
+                    return Ok(drawn);
+                }
+                let shares = pro_rata_shares(&contributions, drawn);
+                for ((contributor, contribution), share) in
+                    contributors.iter().zip(&contributions).zip(shares)
+                {
+                    if share.is_zero() {
+                        continue;
+                    }
+                    let contribution_record = key(&[CONTRIBUTION, contributor]);
+                    let contribution = *contribution - share;
+                    match contribution.is_zero() {
+                        true => tx.remove(contribution_record.as_slice())?,
+                        false => tx.insert(
+                            contribution_record.as_slice(),
+                            &contribution.milli().to_be_bytes(),
+                        )?,
+                    };
+                    let given_record = key(&[GIVEN, contributor]);
+                    let given = milli(tx.get(&given_record)?) + share;
+                    tx.insert(given_record.as_slice(), &given.milli().to_be_bytes())?;
+                }
+                let restitution = milli(tx.get(&record)?) + drawn;
+                tx.insert(record.as_slice(), &restitution.milli().to_be_bytes())?;
+                let total = milli(tx.get(DRAWN)?) + drawn;
+                tx.insert(DRAWN, &total.milli().to_be_bytes())?;
+                Ok(drawn)
+            })
+            .map_err(transaction_error)?;
+        Ok(drawn)
+    }
+
+    async fn contribution(&self, user: &UserAddress) -> Result<IdtAmount, RestitutionError> {
+        let value = self
+            .restitution
+            .get(key(&[CONTRIBUTION, user]))
+            .map_err(KvError::from)?;
+        Ok(milli(value))
+    }
+
+    async fn given(&self, user: &UserAddress) -> Result<IdtAmount, RestitutionError> {
+        let value = self
+            .restitution
+            .get(key(&[GIVEN, user]))
+            .map_err(KvError::from)?;
+        Ok(milli(value))
+    }
+
+    async fn restitution(&self, user: &UserAddress) -> Result<IdtAmount, RestitutionError> {
+        let value = self
+            .restitution
+            .get(key(&[DRAW, user]))
+            .map_err(KvError::from)?;
+        Ok(milli(value))
+    }
+
+    async fn pool(&self) -> Result<Pool, RestitutionError> {
+        Ok(Pool {
+            contributed: milli(self.restitution.get(CONTRIBUTED).map_err(KvError::from)?),
+            drawn: milli(self.restitution.get(DRAWN).map_err(KvError::from)?),
+        })
+    }
+
+    async fn appeal(&self, user: &UserAddress) -> Result<Option<Appeal>, RestitutionError> {
+        Ok(get(&self.restitution, &[APPEAL, user])?)
+    }
+
+    async fn set_appeal(
+        &self,
+        appeal: Appeal,
+        expected: Option<AppealStatus>,
+    ) -> Result<bool, RestitutionError> {
+        let stored = get::<Appeal>(&self.restitution, &[APPEAL, &appeal.user])?;
+        if stored.as_ref().map(|a| a.status) != expected {
+            return Ok(false);
+        }
+        Ok(swap(
+            &self.restitution,
+            &[APPEAL, &appeal.user],
+            stored.as_ref(),
+            &appeal,
+        )?)
+    }
+}
+
+#[async_trait]
+impl OutboxStorage for SledStorage {
+    async fn add_item(&self, item: OutboxItem) -> Result<bool, OutboxError> {
+        Ok(swap(&self.outbox, &[&item.key], None, &item)?)
+    }
+
+    async fn update_item(&self, item: OutboxItem) -> Result<(), OutboxError> {
+        Ok(put(&self.outbox, &[&item.key], &item)?)
+    }
+
+    async fn remove_item(&self, item_key: &str) -> Result<bool, OutboxError> {
+        let removed = self
+            .outbox
+            .remove(key(&[item_key]))
+            .map_err(KvError::from)?;
+        Ok(removed.is_some())
+    }
+
+    async fn items(&self) -> Result<Vec<OutboxItem>, OutboxError> {
+        let mut items: Vec<OutboxItem> = scan(&self.outbox, &[])?
+            .into_iter()
+            .map(|(_, item)| item)
+            .collect();
+        items.sort_by_key(|item| item.created_at);
+        Ok(items)
+    }
+}
+
+#[async_trait]
+impl SummaryStorage for SledStorage {
+    async fn set_latest(&self, summary: StoredSummary) -> Result<(), SummaryError> {
+        Ok(put(&self.summaries, &["latest"], &summary)?)
+    }
+
+    async fn latest(&self) -> Result<Option<StoredSummary>, SummaryError> {
+        Ok(get(&self.summaries, &["latest"])?)
+    }
+}
+
+fn decode_penalty(value: &[u8]) -> Result<PendingPenalty, PendingPenaltyError> {
+    Ok(serde_json::from_slice(value)?)
+}
+
+#[async_trait]
+impl PendingPenaltyStorage for SledStorage {
+    async fn add_penalty(
+        &self,
+        penalty: PendingPenalty,
+    ) -> Result<PendingPenalty, PendingPenaltyError> {
+        loop {
+            let last = self.pending_penalties.last().map_err(KvError::from)?;
+            let id = match last {
+                Some((key, _)) => u64::from_be_bytes(key.as_ref().try_into().unwrap_or_default()),
+                None => 0,
+            } + 1;
+            let penalty = PendingPenalty {
+                id,
+                ..penalty.clone()
+            };
+            let value = serde_json::to_vec(&penalty)?;
+            // another penalty took the id, retry with the next one
+            let inserted = self
+                .pending_penalties
+                .compare_and_swap(id.to_be_bytes(), None::<&[u8]>, Some(value))
+                .map_err(KvError::from)?;
+            if inserted.is_ok() {
+                return Ok(penalty);
+            }
+        }
+    }
+
+    async fn penalty(
+        &self,
+        id: PendingPenaltyId,
+    ) -> Result<Option<PendingPenalty>, PendingPenaltyError> {
+        let value = self
+            .pending_penalties
+            .get(id.to_be_bytes())
+            .map_err(KvError::from)?;
+        value.map(|value| decode_penalty(&value)).transpose()
+    }
+
+    async fn undecided(
+        &self,
+        after_id: PendingPenaltyId,
+        limit: usize,
+    ) -> Result<Vec<PendingPenalty>, PendingPenaltyError> {
+        let Some(start) = after_id.checked_add(1) else {
+            return Ok(vec![]);
+        };
+        let mut penalties = vec![];
+        for record in self.pending_penalties.range(start.to_be_bytes()..) {
+            let (_, value) = record.map_err(KvError::from)?;
+            let penalty = decode_penalty(&value)?;
+            if penalty.decision.is_none() {
+                penalties.push(penalty);
+            }
+            if penalties.len() >= limit {
+                break;
+            }
+        }
+        Ok(penalties)
+    }
+
+    async fn decide(
+        &self,
+        id: PendingPenaltyId,
+        decision: Decision,
+    ) -> Result<bool, PendingPenaltyError> {
+        let Some(old) = self
+            .pending_penalties
+            .get(id.to_be_bytes())
+            .map_err(KvError::from)?
+        else {
+            return Ok(false);
+        };
+        let penalty = decode_penalty(&old)?;
+        if penalty.decision.is_some() {
+            return Ok(false);
+        }
+        let new = serde_json::to_vec(&PendingPenalty {
+            decision: Some(decision),
+            ..penalty
+        })?;
+        // only the first decision replaces the pending penalty
+        let swapped = self
+            .pending_penalties
+            .compare_and_swap(id.to_be_bytes(), Some(old), Some(new))
+            .map_err(KvError::from)?;
+        Ok(swapped.is_ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identity::SystemPenalty, kv::tests::temporary_storage, notifications::ContactKind,
+        numbers::Rational, pending_penalties::storage::tests::penalty as pending_penalty,
+        reports::ReportAction, restitution::storage::tests::check_storage as check_restitution,
+    };
+
+    #[async_std::test]
+    async fn test_admins() {
+        let storage = temporary_storage();
+        let (admin, moderator, user) = (
+            "admin".to_string(),
+            "moderator".to_string(),
+            "user".to_string(),
+        );
+        assert!(storage.check_admin(&admin).await.is_ok());
+        assert!(storage.check_admin(&user).await.is_err());
+        assert!(storage.check_moderator(&moderator).await.is_ok());
+        assert!(storage.add_moderator(&user, user.clone()).await.is_err());
+        storage.add_admin(&admin, user.clone()).await.unwrap();
+        storage.add_moderator(&user, user.clone()).await.unwrap();
+        assert!(storage.check_moderator(&user).await.is_ok());
+        storage.remove_admin(&user, admin.clone()).await.unwrap();
+        assert!(storage.check_admin(&admin).await.is_err());
+        storage
+            .remove_moderator(&user, moderator.clone())
+            .await
+            .unwrap();
+        assert!(storage.check_moderator(&moderator).await.is_err());
+
+        storage
+            .set_successor(&user, moderator.clone(), Some(user.clone()))
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.successor(&moderator).await.unwrap(),
+            Some(user.clone())
+        );
+        storage
+            .set_successor(&user, moderator.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(storage.successor(&moderator).await.unwrap(), None);
+    }
+
+    #[async_std::test]
+    async fn test_nonces() {
+        let storage = temporary_storage();
+        let user = "user".to_string();
+        assert_eq!(storage.next_nonce(&user).await.unwrap(), 1);
+        assert!(storage.use_nonce(&user, 0).await.is_err());
+        storage.use_nonce(&user, 5).await.unwrap();
+        assert!(storage.use_nonce(&user, 5).await.is_err());
+        assert!(storage.use_nonce(&user, 3).await.is_err());
+        assert_eq!(storage.next_nonce(&user).await.unwrap(), 6);
+        storage.use_nonce(&user, Nonce::MAX).await.unwrap();
+        assert!(storage.next_nonce(&user).await.is_err());
+    }
+
+    #[async_std::test]
+    async fn test_server_state() {
+        let storage = temporary_storage();
+        let (user, server) = ("user".to_string(), "server".to_string());
+        let info = ServerInfo {
+            url: "http://example.com".to_string(),
+            scale: Rational::new(1, 2).unwrap(),
+            ..Default::default()
+        };
+        storage.add_server(server.clone(), info).await.unwrap();
+        assert_eq!(
+            storage.servers().await.unwrap()[&server].url,
+            "http://example.com"
+        );
+        let signature = CrossSignature {
+            signature: "0x01".to_string(),
+            nonce: 1,
+            expires_at: 10,
+            version: None,
+            received_at: 5,
+        };
+        storage
+            .set_cross_signature(server.clone(), signature.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.cross_signatures().await.unwrap()[&server],
+            signature
+        );
+        storage.remove_server(server.clone()).await.unwrap();
+        assert!(storage.servers().await.unwrap().is_empty());
+        assert!(storage.cross_signatures().await.unwrap().is_empty());
+
+        storage
+            .set_home(user.clone(), server.clone())
+            .await
+            .unwrap();
+        assert_eq!(storage.home(&user).await.unwrap(), Some(server));
+        storage.remove_home(&user).await.unwrap();
+        assert!(storage.home(&user).await.unwrap().is_none());
+        let claim = HomeClaim {
+            signature: "0x01".to_string(),
+            version: Some(1),
+            claimed_at: 5,
+            ..Default::default()
+        };
+        storage
+            .set_claim(user.clone(), claim.clone())
+            .await
+            .unwrap();
+        assert_eq!(storage.claim(&user).await.unwrap(), Some(claim));
+        storage.remove_claim(&user).await.unwrap();
+        assert!(storage.claim(&user).await.unwrap().is_none());
+
+        let contact = Contact {
+            kind: ContactKind::WebPush,
+            address: "https://push.example.com".to_string(),
+        };
+        storage
+            .set_contact(user.clone(), contact.clone())
+            .await
+            .unwrap();
+        assert_eq!(storage.contact(&user).await.unwrap(), Some(contact));
+        storage.remove_contact(&user).await.unwrap();
+        assert!(storage.contact(&user).await.unwrap().is_none());
+
+        assert!(!storage.is_enabled(Flag::ReadOnly).await.unwrap());
+        storage.set_flag(Flag::ReadOnly, true).await.unwrap();
+        assert!(storage.is_enabled(Flag::ReadOnly).await.unwrap());
+        assert_eq!(storage.flags().await.unwrap().len(), Flag::ALL.len());
+    }
+
+    #[async_std::test]
+    async fn test_archive() {
+        let storage = temporary_storage();
+        let user = "user".to_string();
+        let record = ArchivedUser {
+            user: user.clone(),
+            archived_at: 10,
+            proof: None,
+            moderator_penalty: None,
+            forgotten: BTreeMap::from([(
+                "other".to_string(),
+                SystemPenalty {
+                    amount: IdtAmount::new(1),
+                    timestamp: 2,
+                },
+            )]),
+        };
+        storage.archive(record.clone()).await.unwrap();
+        assert_eq!(storage.archived(&user).await.unwrap(), Some(record));
+        ArchiveStorage::remove(&storage, &user).await.unwrap();
+        assert!(storage.archived(&user).await.unwrap().is_none());
+    }
+
+    #[async_std::test]
+    async fn test_attestations() {
+        let storage = temporary_storage();
+        let challenge = Challenge {
+            challenge: "abc".to_string(),
+            user: "user".to_string(),
+            provider: "github".to_string(),
+            expires_at: 10,
+        };
+        storage.add_challenge(challenge.clone()).await.unwrap();
+        assert_eq!(
+            storage.take_challenge("abc").await.unwrap(),
+            Some(challenge.clone())
+        );
+        assert!(storage.take_challenge("abc").await.unwrap().is_none());
+        storage.add_challenge(challenge).await.unwrap();
+        storage.remove_expired(11).await.unwrap();
+        assert!(storage.take_challenge("abc").await.unwrap().is_none());
+
+        let attestation = Attestation {
+            user: "user".to_string(),
+            provider: "github".to_string(),
+            account: "alice".to_string(),
+            verifier: "verifier".to_string(),
+            proof_id: 1,
+            attested_at: 5,
+        };
+        storage.link(attestation.clone()).await.unwrap();
+        assert_eq!(
+            storage.linked("github", "alice").await.unwrap(),
+            Some(attestation.clone())
+        );
+        assert_eq!(
+            storage.attestations(&"user".to_string()).await.unwrap(),
+            vec![attestation]
+        );
+    }
+
+    #[async_std::test]
+    async fn test_reports() {
+        let storage = temporary_storage();
+        for i in 1..=3 {
+            let report = storage
+                .add_report("a".into(), format!("user{i}"), "spam".into(), i * 10)
+                .await
+                .unwrap();
+            assert_eq!(report.id, i);
+            assert_eq!(storage.report(i).await.unwrap(), Some(report));
+        }
+        let resolution = Resolution {
+            moderator: "moderator".into(),
+            action: ReportAction::Punish {
+                amount: IdtAmount::new(100),
+                proof_id: 1,
+            },
+            resolved_at: 40,
+        };
+        assert!(storage.resolve(2, resolution.clone()).await.unwrap());
+        assert!(!storage.resolve(2, resolution.clone()).await.unwrap());
+        assert!(!storage.resolve(4, resolution.clone()).await.unwrap());
+        assert_eq!(
+            storage.report(2).await.unwrap().unwrap().resolution,
+            Some(resolution)
+        );
+        let open = storage.open_reports(0, 10).await.unwrap();
+        assert_eq!(open.iter().map(|r| r.id).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(storage.open_reports(1, 1).await.unwrap()[0].id, 3);
+        assert!(storage.open_reports(u64::MAX, 1).await.unwrap().is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_service_accounts() {
+        let storage = temporary_storage();
+        let account = ServiceAccount {
+            address: "service".into(),
+            scopes: ["idt".to_string(), "vouch".to_string()].into(),
+            added_by: "admin".into(),
+            added_at: 10,
+        };
+        let other = ServiceAccount {
+            address: "another".into(),
+            ..account.clone()
+        };
+        storage.add_account(account.clone()).await.unwrap();
+        storage.add_account(other.clone()).await.unwrap();
+        assert_eq!(
+            storage.account(&"service".into()).await.unwrap(),
+            Some(account.clone())
+        );
+        assert_eq!(storage.accounts().await.unwrap(), vec![other, account]);
+        assert!(storage.remove_account(&"service".into()).await.unwrap());
+        assert!(!storage.remove_account(&"service".into()).await.unwrap());
+        assert_eq!(storage.account(&"service".into()).await.unwrap(), None);
+    }
+
+    #[async_std::test]
+    async fn test_pending_vouches() {
+        let storage = temporary_storage();
+        let vouch = PendingVouch {
+            voucher: "b".into(),
+            vouchee: "c".into(),
+            created_at: 10,
+            confirmed_at: None,
+        };
+        let other = PendingVouch {
+            voucher: "a".into(),
+            confirmed_at: Some(20),
+            ..vouch.clone()
+        };
+        storage.add_pending(vouch.clone()).await.unwrap();
+        storage.add_pending(other.clone()).await.unwrap();
+        assert_eq!(
+            storage.pending(&"b".into(), &"c".into()).await.unwrap(),
+            Some(vouch.clone())
+        );
+        assert_eq!(storage.all_pending().await.unwrap(), vec![other, vouch]);
+        assert!(
+            storage
+                .remove_pending(&"b".into(), &"c".into())
+                .await
+                .unwrap()
+        );
+        assert!(
+            !storage
+                .remove_pending(&"b".into(), &"c".into())
+                .await
+                .unwrap()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_petnames() {
+        let storage = temporary_storage();
+        storage
+            .set_petname("a".into(), "b".into(), "Bob".into())
+            .await
+            .unwrap();
+        storage
+            .set_petname("a".into(), "c".into(), "Carol".into())
+            .await
+            .unwrap();
+        storage
+            .set_petname("ab".into(), "c".into(), "C".into())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.petnames(&"a".into()).await.unwrap(),
+            HashMap::from([("b".into(), "Bob".into()), ("c".into(), "Carol".into())])
+        );
+        storage
+            .remove_petname(&"a".into(), &"b".into())
+            .await
+            .unwrap();
+        assert_eq!(storage.petnames(&"a".into()).await.unwrap().len(), 1);
+    }
+
+    #[async_std::test]
+    async fn test_vouch_notes() {
+        let storage = temporary_storage();
+        storage
+            .set_note("b".into(), "a".into(), "0x01".into())
+            .await
+            .unwrap();
+        storage
+            .set_note("c".into(), "a".into(), "0x02".into())
+            .await
+            .unwrap();
+        storage
+            .set_note("c".into(), "ab".into(), "0x03".into())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.notes(&"a".into()).await.unwrap(),
+            HashMap::from([("b".into(), "0x01".into()), ("c".into(), "0x02".into())])
+        );
+    }
+
+    #[async_std::test]
+    async fn test_restitution() {
+        check_restitution(&temporary_storage()).await;
+    }
+
+    #[async_std::test]
+    async fn test_outbox() {
+        let storage = temporary_storage();
+        let item = OutboxItem::new("b".into(), "http://a.com".into(), "{}".into(), 20);
+        let older = OutboxItem::new("c".into(), "http://a.com".into(), "{}".into(), 10);
+        assert!(storage.add_item(item.clone()).await.unwrap());
+        assert!(storage.add_item(older.clone()).await.unwrap());
+        assert!(!storage.add_item(item.clone()).await.unwrap());
+        assert_eq!(
+            storage.items().await.unwrap(),
+            vec![older.clone(), item.clone()]
+        );
+        let retried = OutboxItem {
+            attempts: 1,
+            ..item
+        };
+        storage.update_item(retried.clone()).await.unwrap();
+        assert_eq!(storage.items().await.unwrap(), vec![older, retried]);
+        assert!(storage.remove_item("b").await.unwrap());
+        assert!(!storage.remove_item("b").await.unwrap());
+    }
+
+    #[async_std::test]
+    async fn test_summaries() {
+        let storage = temporary_storage();
+        assert_eq!(storage.latest().await.unwrap(), None);
+        let summary = StoredSummary {
+            last_seq: 3,
+            balances: BTreeMap::from([("a".to_string(), IdtAmount::new(10))]),
+            ..Default::default()
+        };
+        storage.set_latest(summary.clone()).await.unwrap();
+        assert_eq!(storage.latest().await.unwrap(), Some(summary));
+    }
+
+    #[async_std::test]
+    async fn test_pending_penalties() {
+        let storage = temporary_storage();
+        for i in 1..=3 {
+            let penalty = storage
+                .add_penalty(pending_penalty(&format!("user{i}"), i * 100))
+                .await
+                .unwrap();
+            assert_eq!(penalty.id, i);
+            assert_eq!(storage.penalty(i).await.unwrap(), Some(penalty));
+        }
+        let decision = Decision {
+            admin: "admin".into(),
+            approved: true,
+            decided_at: 40,
+        };
+        assert!(storage.decide(2, decision.clone()).await.unwrap());
+        assert!(!storage.decide(2, decision.clone()).await.unwrap());
+        assert!(!storage.decide(4, decision.clone()).await.unwrap());
+        assert_eq!(
+            storage.penalty(2).await.unwrap().unwrap().decision,
+            Some(decision)
+        );
+        let pending = storage.undecided(0, 10).await.unwrap();
+        assert_eq!(pending.iter().map(|p| p.id).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(storage.undecided(1, 1).await.unwrap()[0].id, 3);
+        assert!(storage.undecided(u64::MAX, 1).await.unwrap().is_empty());
+    }
+}
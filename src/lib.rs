@@ -1,8 +1,102 @@
+// Identity and reputation graph service.
+//
+// The crate is split into layers selected by Cargo features, so the trust computation can be
+// used without the server:
+// - `core` - identity graph, balances and scoring strategies with in-memory storage, always built
+// - `storage-sql` - SQL storage of the identity graph
+// - `federation` - registered servers and queries of other identity servers
+// - `http-api` - HTTP server with every application module, enabled by default
+// - `wasm` - JavaScript bindings to recompute balances from exported subgraphs
+//
+// The types needed to compute balances are re-exported at the crate root.
+
+#[cfg(feature = "http-api")]
 pub mod admins;
+#[cfg(feature = "http-api")]
+pub mod archive;
+#[cfg(feature = "http-api")]
+pub mod attestations;
+#[cfg(feature = "http-api")]
+pub mod changes;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+#[cfg(feature = "http-api")]
+pub mod check;
+#[cfg(feature = "http-api")]
+pub mod commitment;
 pub mod config;
+#[cfg(feature = "http-api")]
+pub mod cursor;
+#[cfg(feature = "http-api")]
+pub mod duplicates;
+#[cfg(feature = "storage-sql")]
+pub mod encryption;
+#[cfg(feature = "http-api")]
+pub mod ens;
+#[cfg(feature = "http-api")]
+pub mod events;
+#[cfg(feature = "http-api")]
+pub mod export;
+#[cfg(feature = "federation")]
+pub mod federation;
+#[cfg(feature = "http-api")]
+pub mod flags;
 pub mod identity;
+#[cfg(feature = "http-api")]
+pub mod integrity;
+#[cfg(feature = "sled")]
+pub mod kv;
+#[cfg(feature = "http-api")]
+pub mod notifications;
 pub mod numbers;
+#[cfg(feature = "http-api")]
+pub mod outbox;
+#[cfg(feature = "http-api")]
+pub mod pending_penalties;
+#[cfg(feature = "http-api")]
+pub mod pending_vouches;
+#[cfg(feature = "http-api")]
+pub mod personhood;
+#[cfg(feature = "http-api")]
+pub mod petnames;
+#[cfg(feature = "storage-sql")]
+pub mod pools;
+#[cfg(feature = "http-api")]
+pub mod reminders;
+#[cfg(feature = "http-api")]
+pub mod reports;
+#[cfg(feature = "http-api")]
+pub mod restitution;
+#[cfg(feature = "http-api")]
 pub mod routes;
+pub mod scoring;
+#[cfg(feature = "federation")]
 pub mod servers;
+#[cfg(feature = "http-api")]
+pub mod service_accounts;
+#[cfg(feature = "http-api")]
+pub mod startup;
+#[cfg(feature = "http-api")]
 pub mod storage;
+#[cfg(feature = "http-api")]
+pub mod summaries;
+#[cfg(feature = "http-api")]
 pub mod verify;
+#[cfg(feature = "http-api")]
+pub mod vouch_notes;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "http-api")]
+pub mod write_queue;
+
+pub use config::IdentitySection;
+pub use identity::{
+    IdentityService, IdtAmount, UserAddress,
+    error::Error,
+    idt::{balance, balance_breakdown},
+    proof::prove,
+    punish::punish,
+    vouch::vouch,
+};
+pub use numbers::Rational;
+pub use scoring::strategy::ScoringStrategy;
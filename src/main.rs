@@ -1,16 +1,34 @@
 use std::{
     env,
     io::{Error, Write},
+    process,
+    sync::Arc,
 };
 
 use identity_server::{
+    archive, check,
     config::{self, DEFAULT_CONFIG_PATH, DEFAULT_GENESIS_PATH},
-    identity::IdentityService,
+    duplicates, ens,
+    federation::{HttpFederationClient, cache::TtlCache},
+    identity::{IdentityService, clock::SystemClock, screening},
+    integrity,
+    notifications::NotificationDispatcher,
+    outbox::{self, HttpOutboxSender},
+    pending_vouches, reminders, restitution,
     routes::{self, State},
-    storage,
-    verify::{private_key_to_address, random_keypair},
+    scoring::{
+        pagerank,
+        strategy::{self, StrategyKind},
+    },
+    servers::ServerIdentity,
+    startup::{
+        self,
+        error::{EXIT_CONFIG, StartupError},
+        version::VersionInfo,
+    },
+    storage, summaries,
+    verify::{nonce::activity::ActivityNonceManager, private_key_to_address, random_keypair},
 };
-use tide::Server;
 
 pub const DEFAULT_PORT: u32 = 8080;
 pub const DEFAULT_HOST: &str = "localhost";
@@ -29,6 +47,45 @@ async fn main() {
         })
         .init();
 
+    // validate the configuration and exit without starting the server
+    if env::args().any(|arg| arg == "--check-config") {
+        let report = check::run(DEFAULT_CONFIG_PATH, DEFAULT_GENESIS_PATH).await;
+        print!("{report}");
+        process::exit(if report.has_errors() { 1 } else { 0 });
+    }
+
+    // download the published genesis again and exit
+    if env::args().any(|arg| arg == "--refresh-genesis") {
+        process::exit(refresh_genesis().await);
+    }
+
+    if let Err(e) = run().await {
+        log::error!("{}", e);
+        process::exit(e.exit_code());
+    }
+}
+
+async fn refresh_genesis() -> i32 {
+    let config = match config::load_config(DEFAULT_CONFIG_PATH).await {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("{}", StartupError::ConfigError(e));
+            return EXIT_CONFIG;
+        }
+    };
+    match config::refresh_genesis(DEFAULT_GENESIS_PATH, &config.genesis).await {
+        Ok(genesis) => {
+            log::info!("Genesis refreshed, {} users", genesis.len());
+            0
+        }
+        Err(e) => {
+            log::error!("{}", StartupError::GenesisError(e));
+            EXIT_CONFIG
+        }
+    }
+}
+
+async fn run() -> Result<(), StartupError> {
     let server_private_key = match env::var("SERVER_PRIVATE_KEY") {
         Ok(key) if !key.is_empty() => key,
         _ => {
@@ -37,68 +94,178 @@ async fn main() {
             key
         }
     };
-    let server_address = match private_key_to_address(&server_private_key) {
-        Ok(address) => address,
-        Err(e) => {
-            log::error!("Invalid SERVER_PRIVATE_KEY: {:?}", e);
-            panic!("Invalid SERVER_PRIVATE_KEY: {}", e);
-        }
-    };
+    let server_address = private_key_to_address(&server_private_key)?;
     log::info!("Server address: {}", server_address);
 
-    let config = match config::load_config(DEFAULT_CONFIG_PATH).await {
-        Ok(config) => config,
-        Err(e) => {
-            log::error!("Failed to load configuration: {:?}", e);
-            panic!("Failed to load configuration: {}", e);
-        }
-    };
-    let genesis = match config::load_genesis(DEFAULT_GENESIS_PATH).await {
-        Ok(genesis) => genesis,
-        Err(e) => {
-            log::error!("Failed to load genesis configuration: {:?}", e);
-            panic!("Failed to load genesis configuration: {}", e);
-        }
+    let config = config::load_config(DEFAULT_CONFIG_PATH)
+        .await
+        .map_err(StartupError::ConfigError)?;
+    let genesis = config::load_genesis(DEFAULT_GENESIS_PATH, &config.genesis)
+        .await
+        .map_err(StartupError::GenesisError)?;
+    let storage_url = config
+        .storage
+        .url
+        .clone()
+        .unwrap_or_else(storage::storage_url_from_env);
+    let options = storage::StorageOptions {
+        admins: config.admins.admins.clone(),
+        moderators: config.admins.moderators.clone(),
+        nonce_file: env::var("NONCE_FILE").ok().filter(|path| !path.is_empty()),
+        event_log: config.storage.event_log || env::var("STORAGE").is_ok_and(|s| s == "events"),
+        snapshot_interval: config.events.snapshot_interval,
+        write_queue: config.storage.write_queue.clone(),
+        chaos: config.storage.chaos.clone(),
     };
-    let storage = match storage::create_database_storage(
-        config.admins.admins,
-        config.admins.moderators,
+    let storage_info = storage::StorageInfo::new(&storage_url, &options);
+    let storage = startup::connect_storage(
+        &storage::StorageRegistry::default(),
+        &storage_url,
+        &options,
+        &config.startup,
     )
-    .await
-    {
-        Ok(storage) => storage,
-        Err(e) => {
-            log::error!("Failed to connect to database: {:?}", e);
-            panic!("Failed to connect to database: {}", e);
-        }
-    };
+    .await?;
 
     let identity_service = IdentityService {
         vouches: storage.vouch_storage,
         external_vouches: storage.external_vouch_storage,
         proofs: storage.proof_storage,
         penalties: storage.penalty_storage,
+        clock: Arc::new(SystemClock),
+        config: config.identity.clone(),
+        strategy: strategy::strategy(&config.scoring),
+        balances: storage.balance_storage,
+        moderator_stats: storage.moderator_stats_storage,
+        tree_sizes: Arc::default(),
+        future_timestamps: Arc::default(),
+        categories: storage.category_storage,
+        activity: storage.activity_storage,
+        proof_limits: storage.proof_limit_storage,
+        penalty_reasons: storage.penalty_reason_storage,
+        stakes: storage.stake_storage,
+        debits: restitution::debits(&config.restitution, storage.restitution_storage.clone()),
+        screening: screening::screening(&config.screening)
+            .await
+            .map_err(StartupError::DenylistError)?,
     };
     identity_service
-        .set_genesis(genesis)
-        .await
-        .unwrap_or_else(|e| {
-            log::error!("Failed to set genesis balances: {:?}", e);
-            panic!("Failed to set genesis balances: {}", e);
-        });
+        .set_genesis(genesis, config.genesis.drift_policy)
+        .await?;
+    let nonce_manager = Arc::new(ActivityNonceManager::new(
+        storage.nonce_manager,
+        identity_service.clone(),
+    ));
 
+    let names = ens::name_resolver(&config.ens, identity_service.clock.clone());
     let state = State {
         identity_service,
         admin_storage: storage.admin_storage,
-        nonce_manager: storage.nonce_manager,
+        nonce_manager,
         server_storage: storage.server_storage,
+        federation_client: Arc::new(HttpFederationClient),
+        resolve_cache: Arc::new(TtlCache::default()),
+        request_timeouts: Arc::new(routes::metrics::RouteCounters::default()),
+        request_rejections: Arc::new(routes::metrics::RouteCounters::default()),
+        pools: storage.pools,
+        names,
+        home_storage: storage.home_storage,
+        server_identity: ServerIdentity {
+            private_key: server_private_key,
+            address: server_address,
+        },
+        notifications: Arc::new(NotificationDispatcher::new(
+            storage.contact_storage,
+            &config.notifications,
+        )),
+        flags: storage.flag_storage,
+        archive_storage: storage.archive_storage,
+        attestations: storage.attestation_storage,
+        reports: storage.report_storage,
+        service_accounts: storage.service_account_storage,
+        pending_vouches: storage.pending_vouch_storage,
+        petnames: storage.petname_storage,
+        outbox: storage.outbox_storage,
+        summaries: storage.summary_storage,
+        pending_penalties: storage.pending_penalty_storage,
+        vouch_notes: storage.vouch_note_storage,
+        restitution: storage.restitution_storage,
+        pledge_locks: Arc::new(restitution::PledgeLocks::default()),
+        changes: storage.change_log,
+        history: storage.history,
+        storage_info,
+        config: Arc::new(config),
     };
 
-    log::info!("Starting identity server");
-    if let Err(err) = start_server(state).await {
-        log::error!("Failed to start server: {:?}", err);
-        panic!("Failed to start server: {}", err);
+    if state.config.scoring.strategy == StrategyKind::PageRank {
+        let params = state.config.scoring.pagerank.clone();
+        async_std::task::spawn(pagerank::recompute_periodically(
+            state.identity_service.clone(),
+            params,
+        ));
+    }
+
+    async_std::task::spawn(outbox::deliver_periodically(
+        state.identity_service.clone(),
+        state.outbox.clone(),
+        Arc::new(HttpOutboxSender),
+        state.config.outbox.clone(),
+    ));
+
+    async_std::task::spawn(reminders::remind_periodically(
+        state.identity_service.clone(),
+        state.outbox.clone(),
+        state.config.reminders.clone(),
+    ));
+
+    if state.config.summaries.enabled {
+        async_std::task::spawn(summaries::summarize_periodically(
+            state.identity_service.clone(),
+            state.changes.clone(),
+            state.summaries.clone(),
+            state.notifications.clone(),
+            state.config.summaries.clone(),
+        ));
+    }
+
+    if state.config.archive.enabled {
+        async_std::task::spawn(archive::archive_periodically(
+            state.identity_service.clone(),
+            state.archive_storage.clone(),
+            state.config.archive.clone(),
+        ));
+    }
+
+    if state.config.two_phase_vouch.threshold.is_some() {
+        async_std::task::spawn(pending_vouches::activate_periodically(
+            state.identity_service.clone(),
+            state.pending_vouches.clone(),
+            state.config.two_phase_vouch.clone(),
+        ));
+    }
+
+    if state.config.integrity.enabled {
+        async_std::task::spawn(integrity::check_periodically(
+            state.identity_service.clone(),
+            state.server_storage.clone(),
+            state.config.integrity.clone(),
+        ));
+    }
+
+    if state.config.duplicates.enabled {
+        async_std::task::spawn(duplicates::flag_periodically(
+            state.identity_service.clone(),
+            state.reports.clone(),
+            state.server_identity.address.clone(),
+            state.config.duplicates.clone(),
+        ));
     }
+
+    let info = VersionInfo::new(&state.config, &state.storage_info);
+    log::info!(
+        "Starting identity server {}",
+        serde_json::to_string(&info).unwrap_or_default()
+    );
+    start_server(state).await.map_err(StartupError::ServerError)
 }
 
 async fn start_server(state: State) -> Result<(), Error> {
@@ -111,40 +278,6 @@ async fn start_server(state: State) -> Result<(), Error> {
         host_str => host_str.to_string(),
     };
     let mut server = tide::with_state(state);
-    setup_routes(&mut server).await;
+    routes::setup_routes(&mut server);
     server.listen(format!("{host}:{port}")).await
 }
-
-async fn setup_routes(server: &mut Server<State>) {
-    server.at("/idt/:user").get(routes::idt::route);
-    server.at("/vouch/:user").post(routes::vouch::route);
-    server.at("/forget/:user").post(routes::forget::route);
-    server.at("/punish/:user").post(routes::punish::route);
-    server
-        .at("/is_admin/:user")
-        .get(routes::admins::is_admin::route);
-    server
-        .at("/add_admin/:user")
-        .post(routes::admins::add_admin::route);
-    server
-        .at("/remove_admin/:user")
-        .post(routes::admins::remove_admin::route);
-    server
-        .at("/is_moderator/:user")
-        .get(routes::admins::is_moderator::route);
-    server
-        .at("/add_moderator/:user")
-        .post(routes::admins::add_moderator::route);
-    server
-        .at("/remove_moderator/:user")
-        .post(routes::admins::remove_moderator::route);
-    server
-        .at("/servers")
-        .get(routes::servers::get_servers::route);
-    server
-        .at("/add_server")
-        .post(routes::servers::add_server::route);
-    server
-        .at("/remove_server")
-        .post(routes::servers::remove_server::route);
-}
@@ -0,0 +1,101 @@
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::UserAddress,
+    notifications::{Contact, ContactKind, error::Error, storage::ContactStorage},
+    pools::{self, MeteredPool},
+};
+
+pub struct DatabaseContactStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabaseContactStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("contacts", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS contacts (user TEXT PRIMARY KEY, kind TEXT NOT NULL, address TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "contacts", "user").await?;
+        rotate_column(&pool, &cipher, "contacts", "address").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl ContactStorage for DatabaseContactStorage {
+    async fn set_contact(&self, user: UserAddress, contact: Contact) -> Result<(), Error> {
+        sqlx::query("REPLACE INTO contacts (user, kind, address) VALUES (?, ?, ?)")
+            .bind(self.cipher.encode(&user))
+            .bind(contact.kind.as_str())
+            .bind(self.cipher.encode(&contact.address))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_contact(&self, user: &UserAddress) -> Result<(), Error> {
+        sqlx::query("DELETE FROM contacts WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn contact(&self, user: &UserAddress) -> Result<Option<Contact>, Error> {
+        let row = sqlx::query("SELECT kind, address FROM contacts WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let kind: String = row.get(0);
+        let Some(kind) = ContactKind::parse(&kind) else {
+            log::warn!("Unknown contact kind {} of {}", kind, user);
+            return Ok(None);
+        };
+        Ok(Some(Contact {
+            kind,
+            address: self.cipher.decode(&row.get::<String, _>(1))?,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseContactStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        let user = "user".to_string();
+        assert!(storage.contact(&user).await.unwrap().is_none());
+        let contact = Contact {
+            kind: ContactKind::WebPush,
+            address: "https://push.example.com/endpoint".to_string(),
+        };
+        storage
+            .set_contact(user.clone(), contact.clone())
+            .await
+            .unwrap();
+        assert_eq!(storage.contact(&user).await.unwrap(), Some(contact));
+        storage.remove_contact(&user).await.unwrap();
+        assert!(storage.contact(&user).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,14 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to deliver notification to {0}: {1}")]
+    TransportError(String, String),
+    #[error("No transport configured for {0} contacts")]
+    UnsupportedContact(String),
+    #[error("Encryption error: {0}")]
+    EncryptionError(#[from] crate::encryption::error::Error),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[cfg(feature = "sled")]
+    #[error("Storage error: {0}")]
+    StorageError(#[from] crate::kv::error::Error),
+}
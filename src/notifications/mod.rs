@@ -0,0 +1,382 @@
+// Optional notifications for users who registered a contact with `POST /contact`.
+//
+// Users are notified when they are punished, when a voucher forgets them and when their
+// balance crosses one of the configured thresholds. Admin contacts receive scheduled
+// summaries. Delivery is best effort: failures are logged and never fail the request that
+// caused the notification.
+
+use std::{collections::HashMap, sync::Arc};
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::NotificationsSection,
+    identity::{IdtAmount, ProofId, UserAddress},
+    notifications::{
+        error::Error,
+        push::PushNotifier,
+        smtp::SmtpNotifier,
+        storage::{ContactStorage, InMemoryContactStorage},
+    },
+    summaries::Summary,
+};
+
+pub mod db;
+pub mod error;
+pub mod push;
+pub mod smtp;
+pub mod storage;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContactKind {
+    Email,
+    WebPush,
+}
+
+impl ContactKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContactKind::Email => "email",
+            ContactKind::WebPush => "web_push",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "email" => Some(ContactKind::Email),
+            "web_push" => Some(ContactKind::WebPush),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contact {
+    pub kind: ContactKind,
+    // email address or push subscription endpoint
+    pub address: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Notification {
+    Punished {
+        moderator: UserAddress,
+        amount: IdtAmount,
+        proof_id: ProofId,
+    },
+    Forgotten {
+        voucher: UserAddress,
+    },
+    // balance went from one side of the threshold to the other
+    BalanceThreshold {
+        threshold: IdtAmount,
+        balance: IdtAmount,
+        above: bool,
+    },
+    Summary(Summary),
+    // sent to the moderator who proposed a penalty above `escalation.approval_threshold`
+    PenaltyRejected {
+        id: u64,
+        user: UserAddress,
+        amount: IdtAmount,
+        admin: UserAddress,
+    },
+}
+
+impl Notification {
+    pub fn subject(&self) -> &'static str {
+        match self {
+            Notification::Punished { .. } => "You have been punished",
+            Notification::Forgotten { .. } => "A voucher has forgotten you",
+            Notification::BalanceThreshold { .. } => "Your IDT balance has changed",
+            Notification::Summary(_) => "Identity server summary",
+            Notification::PenaltyRejected { .. } => "Your penalty has been rejected",
+        }
+    }
+
+    pub fn text(&self) -> String {
+        match self {
+            Notification::Punished {
+                moderator,
+                amount,
+                proof_id,
+            } => format!("Moderator {moderator} punished you for {amount} IDT, proof {proof_id}."),
+            Notification::Forgotten { voucher } => {
+                format!("{voucher} no longer vouches for you.")
+            }
+            Notification::BalanceThreshold {
+                threshold,
+                balance,
+                above,
+            } => {
+                let direction = if *above { "above" } else { "below" };
+                format!("Your balance is {balance} IDT, {direction} {threshold} IDT.")
+            }
+            Notification::Summary(summary) => summary.text(),
+            Notification::PenaltyRejected {
+                id,
+                user,
+                amount,
+                admin,
+            } => format!("Admin {admin} rejected your penalty {id} of {amount} IDT for {user}."),
+        }
+    }
+}
+
+// delivers notifications of a single contact kind
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(
+        &self,
+        user: &UserAddress,
+        contact: &Contact,
+        notification: &Notification,
+    ) -> Result<(), Error>;
+}
+
+// records notifications, useful for tests
+#[derive(Default)]
+pub struct InMemoryNotifier {
+    sent: RwLock<Vec<(UserAddress, Contact, Notification)>>,
+}
+
+impl InMemoryNotifier {
+    pub async fn sent(&self) -> Vec<(UserAddress, Contact, Notification)> {
+        self.sent.read().await.clone()
+    }
+}
+
+#[async_trait]
+impl Notifier for InMemoryNotifier {
+    async fn notify(
+        &self,
+        user: &UserAddress,
+        contact: &Contact,
+        notification: &Notification,
+    ) -> Result<(), Error> {
+        self.sent
+            .write()
+            .await
+            .push((user.clone(), contact.clone(), notification.clone()));
+        Ok(())
+    }
+}
+
+// thresholds crossed when the balance changes from `before` to `after`
+pub fn crossed_thresholds(
+    thresholds: &[IdtAmount],
+    before: IdtAmount,
+    after: IdtAmount,
+) -> Vec<Notification> {
+    let mut sorted = thresholds.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    sorted
+        .into_iter()
+        .filter(|t| (before >= *t) != (after >= *t))
+        .map(|threshold| Notification::BalanceThreshold {
+            threshold,
+            balance: after,
+            above: after >= threshold,
+        })
+        .collect()
+}
+
+pub struct NotificationDispatcher {
+    pub contacts: Arc<dyn ContactStorage>,
+    pub transports: HashMap<ContactKind, Arc<dyn Notifier>>,
+    pub balance_thresholds: Vec<IdtAmount>,
+}
+
+impl Default for NotificationDispatcher {
+    fn default() -> Self {
+        Self {
+            contacts: Arc::new(InMemoryContactStorage::default()),
+            transports: HashMap::new(),
+            balance_thresholds: vec![],
+        }
+    }
+}
+
+impl NotificationDispatcher {
+    pub fn new(contacts: Arc<dyn ContactStorage>, config: &NotificationsSection) -> Self {
+        let mut transports: HashMap<ContactKind, Arc<dyn Notifier>> = HashMap::new();
+        if let Some(smtp) = &config.smtp {
+            transports.insert(
+                ContactKind::Email,
+                Arc::new(SmtpNotifier::new(smtp.clone())),
+            );
+        }
+        if config.web_push {
+            transports.insert(ContactKind::WebPush, Arc::new(PushNotifier));
+        }
+        Self {
+            contacts,
+            transports,
+            balance_thresholds: config.balance_thresholds.clone(),
+        }
+    }
+
+    pub fn supports(&self, kind: ContactKind) -> bool {
+        self.transports.contains_key(&kind)
+    }
+
+    async fn try_notify(
+        &self,
+        user: &UserAddress,
+        notification: &Notification,
+    ) -> Result<(), Error> {
+        if self.transports.is_empty() {
+            return Ok(());
+        }
+        let Some(contact) = self.contacts.contact(user).await? else {
+            return Ok(());
+        };
+        self.send(user, &contact, notification).await
+    }
+
+    async fn send(
+        &self,
+        user: &UserAddress,
+        contact: &Contact,
+        notification: &Notification,
+    ) -> Result<(), Error> {
+        let transport = self
+            .transports
+            .get(&contact.kind)
+            .ok_or_else(|| Error::UnsupportedContact(contact.kind.as_str().to_string()))?;
+        transport.notify(user, contact, notification).await
+    }
+
+    // best effort delivery, errors are only logged
+    pub async fn notify(&self, user: &UserAddress, notification: Notification) {
+        if let Err(e) = self.try_notify(user, &notification).await {
+            log::warn!("Failed to notify {}: {}", user, e);
+        }
+    }
+
+    // best effort delivery to a contact that is not registered by a user, e.g. of an admin
+    pub async fn notify_contact(
+        &self,
+        user: &UserAddress,
+        contact: &Contact,
+        notification: &Notification,
+    ) {
+        if let Err(e) = self.send(user, contact, notification).await {
+            log::warn!("Failed to notify {}: {}", contact.address, e);
+        }
+    }
+
+    pub async fn balance_changed(&self, user: &UserAddress, before: IdtAmount, after: IdtAmount) {
+        for notification in crossed_thresholds(&self.balance_thresholds, before, after) {
+            self.notify(user, notification).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crossed_thresholds() {
+        let thresholds = [100, 10, 1000].map(IdtAmount::new);
+        assert!(crossed_thresholds(&thresholds, IdtAmount::new(50), IdtAmount::new(60)).is_empty());
+        assert_eq!(
+            crossed_thresholds(&thresholds, IdtAmount::new(150), IdtAmount::new(5)),
+            vec![
+                Notification::BalanceThreshold {
+                    threshold: IdtAmount::new(10),
+                    balance: IdtAmount::new(5),
+                    above: false
+                },
+                Notification::BalanceThreshold {
+                    threshold: IdtAmount::new(100),
+                    balance: IdtAmount::new(5),
+                    above: false
+                },
+            ]
+        );
+        assert_eq!(
+            crossed_thresholds(&thresholds, IdtAmount::new(99), IdtAmount::new(100)),
+            vec![Notification::BalanceThreshold {
+                threshold: IdtAmount::new(100),
+                balance: IdtAmount::new(100),
+                above: true
+            }]
+        );
+    }
+
+    #[async_std::test]
+    async fn test_dispatch() {
+        let notifier = Arc::new(InMemoryNotifier::default());
+        let dispatcher = NotificationDispatcher {
+            transports: HashMap::from([(
+                ContactKind::Email,
+                notifier.clone() as Arc<dyn Notifier>,
+            )]),
+            balance_thresholds: vec![IdtAmount::new(100)],
+            ..Default::default()
+        };
+        let user = "user".to_string();
+        let forgotten = Notification::Forgotten {
+            voucher: "voucher".to_string(),
+        };
+        // no contact registered
+        dispatcher.notify(&user, forgotten.clone()).await;
+        assert!(notifier.sent().await.is_empty());
+
+        let contact = Contact {
+            kind: ContactKind::Email,
+            address: "user@example.com".to_string(),
+        };
+        dispatcher
+            .contacts
+            .set_contact(user.clone(), contact.clone())
+            .await
+            .unwrap();
+        dispatcher.notify(&user, forgotten.clone()).await;
+        dispatcher
+            .balance_changed(&user, IdtAmount::new(150), IdtAmount::new(50))
+            .await;
+        assert_eq!(
+            notifier.sent().await,
+            vec![
+                (user.clone(), contact.clone(), forgotten),
+                (
+                    user.clone(),
+                    contact,
+                    Notification::BalanceThreshold {
+                        threshold: IdtAmount::new(100),
+                        balance: IdtAmount::new(50),
+                        above: false
+                    }
+                ),
+            ]
+        );
+
+        // no transport for web push contacts, delivery fails silently
+        let push = Contact {
+            kind: ContactKind::WebPush,
+            address: "https://push.example.com".to_string(),
+        };
+        dispatcher
+            .contacts
+            .set_contact(user.clone(), push)
+            .await
+            .unwrap();
+        dispatcher
+            .notify(
+                &user,
+                Notification::Forgotten {
+                    voucher: "voucher".to_string(),
+                },
+            )
+            .await;
+        assert_eq!(notifier.sent().await.len(), 2);
+    }
+}
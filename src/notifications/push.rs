@@ -0,0 +1,161 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use async_std::net::ToSocketAddrs;
+use async_trait::async_trait;
+use serde_json::json;
+use surf::{Url, http::url::Host};
+
+use crate::{
+    identity::UserAddress,
+    notifications::{Contact, Notification, Notifier, error::Error},
+};
+
+// seconds the push service keeps an undelivered message
+const PUSH_TTL: u32 = 24 * 60 * 60;
+
+// posts notifications as JSON to the push subscription endpoint of the user. Payloads are
+// not encrypted, so endpoints are expected to be push relays that accept plain messages.
+pub struct PushNotifier;
+
+fn is_public_ipv4(ip: &Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    // 100.64.0.0/10 is shared address space of carrier-grade NATs
+    let shared = a == 100 && (64..128).contains(&b);
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || shared)
+}
+
+fn is_public_ipv6(ip: &Ipv6Addr) -> bool {
+    if let Some(ipv4) = ip.to_ipv4_mapped() {
+        return is_public_ipv4(&ipv4);
+    }
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || ip.is_unique_local()
+        || ip.is_unicast_link_local())
+}
+
+// addresses the server may post to, endpoints must not reach the server host or its network
+pub fn is_public_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+// checks the endpoint without resolving it: an https URL whose host, if it is an IP address,
+// is public. Domain hosts are resolved and checked again before every delivery.
+pub fn is_valid_endpoint(endpoint: &str) -> bool {
+    let Ok(url) = Url::parse(endpoint) else {
+        return false;
+    };
+    if url.scheme() != "https" {
+        return false;
+    }
+    match url.host() {
+        Some(Host::Domain(domain)) => !domain.eq_ignore_ascii_case("localhost"),
+        Some(Host::Ipv4(ip)) => is_public_ipv4(&ip),
+        Some(Host::Ipv6(ip)) => is_public_ipv6(&ip),
+        None => false,
+    }
+}
+
+// resolves the endpoint host and fails if any of its addresses is not public
+async fn public_endpoint(endpoint: &str) -> Result<Url, Error> {
+    let rejected = |reason: &str| Error::TransportError(endpoint.to_string(), reason.to_string());
+    if !is_valid_endpoint(endpoint) {
+        return Err(rejected("endpoint is not a public https URL"));
+    }
+    let url = Url::parse(endpoint).map_err(|e| rejected(&e.to_string()))?;
+    let host = url.host_str().ok_or_else(|| rejected("missing host"))?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addresses: Vec<_> = (host, port)
+        .to_socket_addrs()
+        .await
+        .map_err(|e| rejected(&e.to_string()))?
+        .collect();
+    if addresses.is_empty() || addresses.iter().any(|a| !is_public_ip(&a.ip())) {
+        return Err(rejected("endpoint resolves to a non-public address"));
+    }
+    Ok(url)
+}
+
+#[async_trait]
+impl Notifier for PushNotifier {
+    async fn notify(
+        &self,
+        user: &UserAddress,
+        contact: &Contact,
+        notification: &Notification,
+    ) -> Result<(), Error> {
+        let body = json!({
+            "user": user,
+            "title": notification.subject(),
+            "body": notification.text(),
+            "data": notification,
+        });
+        let endpoint = &contact.address;
+        let url = public_endpoint(endpoint).await?;
+        let response = surf::post(url)
+            .header("TTL", PUSH_TTL.to_string())
+            .body(body)
+            .await
+            .map_err(|e| Error::TransportError(endpoint.clone(), e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(Error::TransportError(
+                endpoint.clone(),
+                format!("status {}", response.status()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::ContactKind;
+
+    #[test]
+    fn test_valid_endpoint() {
+        assert!(is_valid_endpoint("https://push.example.com/send/abc"));
+        assert!(is_valid_endpoint("https://8.8.8.8/send"));
+        assert!(!is_valid_endpoint("http://push.example.com/send"));
+        assert!(!is_valid_endpoint("http://127.0.0.1/send"));
+        assert!(!is_valid_endpoint("https://127.0.0.1/send"));
+        assert!(!is_valid_endpoint("https://localhost/send"));
+        assert!(!is_valid_endpoint(
+            "https://169.254.169.254/latest/meta-data"
+        ));
+        assert!(!is_valid_endpoint("https://10.0.0.1/send"));
+        assert!(!is_valid_endpoint("https://192.168.1.1/send"));
+        assert!(!is_valid_endpoint("https://0.0.0.0/send"));
+        assert!(!is_valid_endpoint("https://[::1]/send"));
+        assert!(!is_valid_endpoint("https://[fe80::1]/send"));
+        assert!(!is_valid_endpoint("https://[fd00::1]/send"));
+        assert!(!is_valid_endpoint("https://[::ffff:127.0.0.1]/send"));
+        assert!(!is_valid_endpoint("ftp://push.example.com"));
+        assert!(!is_valid_endpoint("not a url"));
+    }
+
+    #[async_std::test]
+    async fn test_notify_rejects_private_endpoint() {
+        let contact = Contact {
+            kind: ContactKind::WebPush,
+            address: "https://127.0.0.1:1/send".to_string(),
+        };
+        let notification = Notification::Forgotten {
+            voucher: "voucher".to_string(),
+        };
+        let result = PushNotifier
+            .notify(&"user".to_string(), &contact, &notification)
+            .await;
+        assert!(matches!(result, Err(Error::TransportError(..))));
+    }
+}
@@ -0,0 +1,221 @@
+use async_std::{
+    io::{BufReader, prelude::*},
+    net::TcpStream,
+};
+use async_trait::async_trait;
+
+use crate::{
+    config::SmtpSection,
+    identity::UserAddress,
+    notifications::{Contact, Notification, Notifier, error::Error},
+};
+
+// sends plain text emails through an SMTP relay without authentication or TLS,
+// the relay is expected to run next to the server
+pub struct SmtpNotifier {
+    config: SmtpSection,
+}
+
+impl SmtpNotifier {
+    pub fn new(config: SmtpSection) -> Self {
+        Self { config }
+    }
+
+    fn relay(&self) -> String {
+        format!("{}:{}", self.config.host, self.config.port)
+    }
+
+    fn error(&self, reason: impl ToString) -> Error {
+        Error::TransportError(self.relay(), reason.to_string())
+    }
+
+    // reads a possibly multiline reply and checks its code
+    async fn expect(&self, reader: &mut BufReader<&TcpStream>, code: &str) -> Result<(), Error> {
+        loop {
+            let mut line = String::new();
+            if reader
+                .read_line(&mut line)
+                .await
+                .map_err(|e| self.error(e))?
+                == 0
+            {
+                return Err(self.error("connection closed"));
+            }
+            if !line.starts_with(code) {
+                return Err(self.error(format!("unexpected reply {}", line.trim_end())));
+            }
+            // `250-` continues the reply, `250 ` ends it
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn command(
+        &self,
+        stream: &TcpStream,
+        reader: &mut BufReader<&TcpStream>,
+        command: &str,
+        code: &str,
+    ) -> Result<(), Error> {
+        let mut writer = stream;
+        writer
+            .write_all(format!("{command}\r\n").as_bytes())
+            .await
+            .map_err(|e| self.error(e))?;
+        self.expect(reader, code).await
+    }
+}
+
+fn is_valid_header(value: &str) -> bool {
+    !value.chars().any(|c| c.is_control())
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    async fn notify(
+        &self,
+        _user: &UserAddress,
+        contact: &Contact,
+        notification: &Notification,
+    ) -> Result<(), Error> {
+        let to = &contact.address;
+        if !is_valid_header(to) {
+            return Err(self.error("invalid recipient"));
+        }
+        let stream = TcpStream::connect(self.relay())
+            .await
+            .map_err(|e| self.error(e))?;
+        let mut reader = BufReader::new(&stream);
+        self.expect(&mut reader, "220").await?;
+        self.command(
+            &stream,
+            &mut reader,
+            &format!("HELO {}", self.config.helo),
+            "250",
+        )
+        .await?;
+        self.command(
+            &stream,
+            &mut reader,
+            &format!("MAIL FROM:<{}>", self.config.from),
+            "250",
+        )
+        .await?;
+        self.command(&stream, &mut reader, &format!("RCPT TO:<{to}>"), "250")
+            .await?;
+        self.command(&stream, &mut reader, "DATA", "354").await?;
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+            self.config.from,
+            to,
+            notification.subject()
+        );
+        for line in notification.text().lines() {
+            // lines starting with a dot are escaped, a single dot ends the message
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message.push('.');
+        self.command(&stream, &mut reader, &message, "250").await?;
+        self.command(&stream, &mut reader, "QUIT", "221").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_std::net::TcpListener;
+
+    use super::*;
+    use crate::notifications::ContactKind;
+
+    // accepts a single message and returns everything the client sent
+    async fn fake_relay(listener: TcpListener) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut reader = BufReader::new(&stream);
+        let mut writer = &stream;
+        let mut received = String::new();
+        writer.write_all(b"220 fake\r\n").await.unwrap();
+        let mut in_data = false;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await.unwrap() == 0 {
+                break;
+            }
+            received.push_str(&line);
+            let reply: &[u8] = if in_data {
+                if line != ".\r\n" {
+                    continue;
+                }
+                in_data = false;
+                b"250 queued\r\n"
+            } else if line.starts_with("DATA") {
+                in_data = true;
+                b"354 go ahead\r\n"
+            } else if line.starts_with("QUIT") {
+                writer.write_all(b"221 bye\r\n").await.unwrap();
+                break;
+            } else if line.starts_with("HELO") {
+                b"250-fake\r\n250 ok\r\n"
+            } else {
+                b"250 ok\r\n"
+            };
+            writer.write_all(reply).await.unwrap();
+        }
+        received
+    }
+
+    #[async_std::test]
+    async fn test_send() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let relay = async_std::task::spawn(fake_relay(listener));
+        let notifier = SmtpNotifier::new(SmtpSection {
+            host: "127.0.0.1".to_string(),
+            port,
+            ..Default::default()
+        });
+        let contact = Contact {
+            kind: ContactKind::Email,
+            address: "user@example.com".to_string(),
+        };
+        notifier
+            .notify(
+                &"user".to_string(),
+                &contact,
+                &Notification::Forgotten {
+                    voucher: "voucher".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        let received = relay.await;
+        assert!(received.contains("RCPT TO:<user@example.com>\r\n"));
+        assert!(received.contains("Subject: A voucher has forgotten you\r\n"));
+        assert!(received.contains("voucher no longer vouches for you.\r\n"));
+    }
+
+    #[async_std::test]
+    async fn test_invalid_recipient() {
+        let notifier = SmtpNotifier::new(SmtpSection::default());
+        let contact = Contact {
+            kind: ContactKind::Email,
+            address: "user@example.com\r\nBcc: other@example.com".to_string(),
+        };
+        assert!(
+            notifier
+                .notify(
+                    &"user".to_string(),
+                    &contact,
+                    &Notification::Forgotten {
+                        voucher: "voucher".to_string(),
+                    },
+                )
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::{
+    identity::UserAddress,
+    notifications::{Contact, error::Error},
+};
+
+// contacts registered by users to receive notifications
+#[async_trait]
+pub trait ContactStorage: Send + Sync {
+    async fn set_contact(&self, user: UserAddress, contact: Contact) -> Result<(), Error>;
+    async fn remove_contact(&self, user: &UserAddress) -> Result<(), Error>;
+    async fn contact(&self, user: &UserAddress) -> Result<Option<Contact>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryContactStorage {
+    contacts: RwLock<HashMap<UserAddress, Contact>>,
+}
+
+#[async_trait]
+impl ContactStorage for InMemoryContactStorage {
+    async fn set_contact(&self, user: UserAddress, contact: Contact) -> Result<(), Error> {
+        self.contacts.write().await.insert(user, contact);
+        Ok(())
+    }
+
+    async fn remove_contact(&self, user: &UserAddress) -> Result<(), Error> {
+        self.contacts.write().await.remove(user);
+        Ok(())
+    }
+
+    async fn contact(&self, user: &UserAddress) -> Result<Option<Contact>, Error> {
+        Ok(self.contacts.read().await.get(user).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::ContactKind;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryContactStorage::default();
+        let user = "user".to_string();
+        assert!(storage.contact(&user).await.unwrap().is_none());
+        let contact = Contact {
+            kind: ContactKind::Email,
+            address: "user@example.com".to_string(),
+        };
+        storage
+            .set_contact(user.clone(), contact.clone())
+            .await
+            .unwrap();
+        assert_eq!(storage.contact(&user).await.unwrap(), Some(contact));
+        storage.remove_contact(&user).await.unwrap();
+        assert!(storage.contact(&user).await.unwrap().is_none());
+    }
+}
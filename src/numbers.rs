@@ -1,4 +1,267 @@
-use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    fmt,
+    iter::Sum,
+    ops::{Add, AddAssign, Sub, SubAssign},
+    str::FromStr,
+};
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, Visitor},
+};
+
+// decimal places of IDT amounts
+pub const IDT_DECIMALS: u32 = 3;
+// smallest units in one IDT
+pub const MILLI_PER_IDT: u64 = 10u64.pow(IDT_DECIMALS);
+
+// amount of IDT with `IDT_DECIMALS` fixed decimal places, kept apart from counts, timestamps
+// and other integers. Methods follow those of u64. Human readable formats like JSON and
+// TOML use decimal numbers, e.g. `95.5`, so whole amounts look as before. Binary formats
+// and SQL columns store milli-IDT.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Idt(u64);
+
+impl Idt {
+    pub const ZERO: Idt = Idt(0);
+    pub const MAX: Idt = Idt(u64::MAX);
+
+    // whole IDT
+    pub const fn new(units: u64) -> Self {
+        Idt(units.saturating_mul(MILLI_PER_IDT))
+    }
+
+    pub const fn from_milli(milli: u64) -> Self {
+        Idt(milli)
+    }
+
+    // whole IDT units, the fraction is dropped
+    pub const fn units(self) -> u64 {
+        self.0 / MILLI_PER_IDT
+    }
+
+    pub const fn milli(self) -> u64 {
+        self.0
+    }
+
+    pub fn as_f64(self) -> f64 {
+        self.0 as f64 / MILLI_PER_IDT as f64
+    }
+
+    // nearest amount, None for negative or not finite values
+    pub fn from_f64(value: f64) -> Option<Self> {
+        let milli = (value * MILLI_PER_IDT as f64).round();
+        (milli.is_finite() && milli >= 0.0 && milli <= u64::MAX as f64).then_some(Idt(milli as u64))
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: Idt) -> Option<Idt> {
+        self.0.checked_add(other.0).map(Idt)
+    }
+
+    pub fn checked_sub(self, other: Idt) -> Option<Idt> {
+        self.0.checked_sub(other.0).map(Idt)
+    }
+
+    pub fn checked_mul(self, times: u64) -> Option<Idt> {
+        self.0.checked_mul(times).map(Idt)
+    }
+
+    pub fn saturating_add(self, other: Idt) -> Idt {
+        Idt(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Idt) -> Idt {
+        Idt(self.0.saturating_sub(other.0))
+    }
+
+    pub fn saturating_mul(self, times: u64) -> Idt {
+        Idt(self.0.saturating_mul(times))
+    }
+
+    pub fn abs_diff(self, other: Idt) -> Idt {
+        Idt(self.0.abs_diff(other.0))
+    }
+
+    // fraction digits without trailing zeros, empty for whole amounts
+    fn fraction(self) -> String {
+        let fraction = self.0 % MILLI_PER_IDT;
+        if fraction == 0 {
+            return String::new();
+        }
+        let digits = format!("{fraction:0width$}", width = IDT_DECIMALS as usize);
+        format!(".{}", digits.trim_end_matches('0'))
+    }
+
+    // digits of whole units in groups of three for people, e.g. `1,234,567.5`
+    pub fn grouped(self) -> String {
+        let digits = self.units().to_string();
+        let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+        grouped + &self.fraction()
+    }
+}
+
+// operators saturate at `Idt::ZERO` and `Idt::MAX` rather than panicking in debug builds and
+// wrapping in release ones. A balance cannot go below zero, and no real amount reaches
+// `Idt::MAX`. Use `checked_add` and `checked_sub` where an overflow must be reported.
+impl Add for Idt {
+    type Output = Idt;
+
+    fn add(self, other: Idt) -> Idt {
+        self.saturating_add(other)
+    }
+}
+
+impl AddAssign for Idt {
+    fn add_assign(&mut self, other: Idt) {
+        *self = self.saturating_add(other);
+    }
+}
+
+impl Sub for Idt {
+    type Output = Idt;
+
+    fn sub(self, other: Idt) -> Idt {
+        self.saturating_sub(other)
+    }
+}
+
+impl SubAssign for Idt {
+    fn sub_assign(&mut self, other: Idt) {
+        *self = self.saturating_sub(other);
+    }
+}
+
+impl Sum for Idt {
+    fn sum<I: Iterator<Item = Idt>>(iter: I) -> Idt {
+        iter.fold(Idt::ZERO, Add::add)
+    }
+}
+
+impl fmt::Display for Idt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.units(), self.fraction())
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid IDT amount {0:?}, expected at most {IDT_DECIMALS} decimal places")]
+pub struct ParseIdtError(String);
+
+impl FromStr for Idt {
+    type Err = ParseIdtError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseIdtError(s.to_string());
+        let (units, fraction) = s.split_once('.').unwrap_or((s, ""));
+        let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if units.is_empty()
+            || !digits(units)
+            || !digits(fraction)
+            || fraction.len() > IDT_DECIMALS as usize
+            || (s.contains('.') && fraction.is_empty())
+        {
+            return Err(error());
+        }
+        let units: u64 = units.parse().map_err(|_| error())?;
+        let fraction = format!("{fraction:0<width$}", width = IDT_DECIMALS as usize);
+        units
+            .checked_mul(MILLI_PER_IDT)
+            .and_then(|milli| milli.checked_add(fraction.parse().ok()?))
+            .map(Idt)
+            .ok_or_else(error)
+    }
+}
+
+impl Serialize for Idt {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            serializer.serialize_u64(self.0)
+        } else if self.0 % MILLI_PER_IDT == 0 {
+            serializer.serialize_u64(self.units())
+        } else {
+            serializer.serialize_f64(self.as_f64())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Idt {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return u64::deserialize(deserializer).map(Idt);
+        }
+        deserializer.deserialize_any(IdtVisitor)
+    }
+}
+
+// whole numbers are IDT units, strings and floats are parsed with `FromStr`
+struct IdtVisitor;
+
+impl Visitor<'_> for IdtVisitor {
+    type Value = Idt;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a non-negative IDT amount with at most {IDT_DECIMALS} decimal places"
+        )
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Idt, E> {
+        v.checked_mul(MILLI_PER_IDT)
+            .map(Idt)
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Idt, E> {
+        let v = u64::try_from(v).map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))?;
+        self.visit_u64(v)
+    }
+
+    // parsed from the shortest representation of the float, so more decimal places are
+    // rejected like in strings instead of being rounded
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Idt, E> {
+        v.to_string()
+            .parse()
+            .map_err(|_| E::invalid_value(de::Unexpected::Float(v), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Idt, E> {
+        v.parse()
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+}
+
+// how scaled amounts are rounded to whole milli-IDT
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    // always down, so scaled amounts never exceed the exact ones but lose up to a milli-IDT
+    // each time
+    #[default]
+    Floor,
+    // to the nearest, ties to the even amount, so errors cancel out on average
+    HalfEven,
+}
+
+impl fmt::Display for Rounding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rounding::Floor => write!(f, "floor"),
+            Rounding::HalfEven => write!(f, "half_even"),
+        }
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Rational {
@@ -17,6 +280,18 @@ impl Rational {
         })
     }
 
+    // ratio of two u64 values, both are scaled down to fit u32 keeping the ratio
+    pub fn fit(numerator: u64, denominator: u64) -> Option<Self> {
+        if denominator == 0 {
+            return None;
+        }
+        let shift = (64 - numerator.max(denominator).leading_zeros()).saturating_sub(32);
+        Rational::new(
+            (numerator >> shift) as u32,
+            ((denominator >> shift) as u32).max(1),
+        )
+    }
+
     pub fn numerator(&self) -> u32 {
         self.numerator
     }
@@ -29,11 +304,25 @@ impl Rational {
         self.numerator as f64 / self.denominator as f64
     }
 
-    // multiplies without overflowing
-    pub fn mul(&self, value: u64) -> u64 {
-        (self.numerator as u64)
-            .saturating_mul(value)
-            .saturating_div(self.denominator as u64)
+    // scales the amount without overflowing, rounded down
+    pub fn mul(&self, value: Idt) -> Idt {
+        self.mul_rounded(value, Rounding::Floor)
+    }
+
+    // scales the amount without overflowing, rounded to whole milli-IDT
+    pub fn mul_rounded(&self, value: Idt, rounding: Rounding) -> Idt {
+        let product = self.numerator as u128 * value.0 as u128;
+        let denominator = self.denominator as u128;
+        let (quotient, remainder) = (product / denominator, product % denominator);
+        let rounded = match rounding {
+            Rounding::Floor => quotient,
+            Rounding::HalfEven => match (remainder * 2).cmp(&denominator) {
+                Ordering::Greater => quotient + 1,
+                Ordering::Equal if quotient % 2 == 1 => quotient + 1,
+                _ => quotient,
+            },
+        };
+        Idt(u64::try_from(rounded).unwrap_or(u64::MAX))
     }
 }
 
@@ -46,8 +335,213 @@ impl Default for Rational {
     }
 }
 
-impl std::fmt::Display for Rational {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}/{}", self.numerator, self.denominator)
     }
 }
+
+// amounts were stored in whole IDT before milli-IDT precision. Multiplies the column by
+// `MILLI_PER_IDT` once and records it in `amount_precision`, so new tables are only recorded.
+// Returns whether the column was rescaled.
+#[cfg(feature = "storage-sql")]
+pub async fn rescale_column(
+    pool: &crate::pools::MeteredPool,
+    table: &str,
+    column: &str,
+) -> Result<bool, sqlx::Error> {
+    use sqlx::Acquire;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS amount_precision (name TEXT PRIMARY KEY, decimals INTEGER NOT NULL)",
+    )
+    .execute(pool)
+    .await?;
+    let name = format!("{table}.{column}");
+    let mut tx = pool.begin().await?;
+    let recorded = sqlx::query("SELECT decimals FROM amount_precision WHERE name = ?")
+        .bind(&name)
+        .fetch_optional(tx.acquire().await?)
+        .await?;
+    if recorded.is_some() {
+        return Ok(false);
+    }
+    sqlx::query(&format!(
+        "UPDATE {table} SET {column} = {column} * {MILLI_PER_IDT}"
+    ))
+    .execute(tx.acquire().await?)
+    .await?;
+    sqlx::query("INSERT INTO amount_precision (name, decimals) VALUES (?, ?)")
+        .bind(&name)
+        .bind(IDT_DECIMALS as i64)
+        .execute(tx.acquire().await?)
+        .await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_idt() {
+        let amount = Idt::new(1_234_567);
+        assert_eq!(amount.milli(), 1_234_567_000);
+        assert_eq!(amount.to_string(), "1234567");
+        assert_eq!(amount.grouped(), "1,234,567");
+        assert_eq!(Idt::new(123).grouped(), "123");
+        assert_eq!(Idt::new(1000).grouped(), "1,000");
+        assert_eq!(Idt::ZERO.grouped(), "0");
+        assert_eq!("1234567".parse::<Idt>().unwrap(), amount);
+        assert!("-1".parse::<Idt>().is_err());
+        assert_eq!(serde_json::to_string(&amount).unwrap(), "1234567");
+
+        assert_eq!(Idt::new(1).checked_sub(Idt::new(2)), None);
+        assert_eq!(Idt::MAX.checked_add(Idt::new(1)), None);
+        assert_eq!(Idt::MAX.checked_mul(2), None);
+        assert_eq!(Idt::new(1).saturating_sub(Idt::new(2)), Idt::ZERO);
+        assert_eq!(Idt::MAX.saturating_add(Idt::new(1)), Idt::MAX);
+        assert_eq!(Idt::MAX + Idt::new(1), Idt::MAX);
+        assert_eq!(Idt::new(1) - Idt::new(2), Idt::ZERO);
+        let mut amount = Idt::new(1);
+        amount -= Idt::new(2);
+        assert_eq!(amount, Idt::ZERO);
+        amount += Idt::MAX;
+        amount += Idt::MAX;
+        assert_eq!(amount, Idt::MAX);
+        assert_eq!(
+            [Idt::new(1), Idt::new(2)].into_iter().sum::<Idt>(),
+            Idt::new(3)
+        );
+        assert_eq!(
+            Rational::new(1, 3).unwrap().mul(Idt::new(100)),
+            Idt::from_milli(33_333)
+        );
+    }
+
+    #[test]
+    fn test_idt_fraction() {
+        let amount = Rational::new(1, 10).unwrap().mul(Idt::new(955));
+        assert_eq!(amount, Idt::from_milli(95_500));
+        assert_eq!(amount.units(), 95);
+        assert_eq!(amount.to_string(), "95.5");
+        assert_eq!(Idt::from_milli(1_234_567_001).grouped(), "1,234,567.001");
+        assert_eq!(Idt::from_milli(1).to_string(), "0.001");
+        assert!(Idt::from_milli(1) > Idt::new(0));
+        assert!(Idt::from_milli(999) < Idt::new(1));
+
+        for (text, milli) in [
+            ("95.5", 95_500),
+            ("0.001", 1),
+            ("7.250", 7_250),
+            ("3", 3_000),
+        ] {
+            assert_eq!(text.parse::<Idt>().unwrap(), Idt::from_milli(milli));
+        }
+        for text in [
+            "",
+            ".5",
+            "5.",
+            "1.2345",
+            "1e3",
+            "+1",
+            "1.-5",
+            "18446744073709552",
+        ] {
+            assert!(text.parse::<Idt>().is_err(), "{text}");
+        }
+
+        assert_eq!(serde_json::to_string(&amount).unwrap(), "95.5");
+        for json in ["95.5", "\"95.5\""] {
+            assert_eq!(serde_json::from_str::<Idt>(json).unwrap(), amount);
+        }
+        // amounts stored before milli-IDT precision are whole IDT
+        assert_eq!(serde_json::from_str::<Idt>("100").unwrap(), Idt::new(100));
+        assert!(serde_json::from_str::<Idt>("-1").is_err());
+        assert!(serde_json::from_str::<Idt>("-0.5").is_err());
+        assert_eq!(
+            serde_json::from_str::<Idt>("0.001").unwrap(),
+            Idt::from_milli(1)
+        );
+        assert_eq!(serde_json::from_str::<Idt>("1e3").unwrap(), Idt::new(1000));
+        for json in ["1.2345", "0.0005", "1e-4"] {
+            assert!(serde_json::from_str::<Idt>(json).is_err(), "{json}");
+        }
+    }
+
+    #[test]
+    fn test_rounding() {
+        let third = Rational::new(1, 3).unwrap();
+        let half = Rational::new(1, 2).unwrap();
+        for (rational, milli, floor, half_even) in [
+            (&third, 1, 0, 0),
+            (&third, 2, 0, 1),
+            (&half, 1, 0, 0),
+            (&half, 3, 1, 2),
+            (&half, 5, 2, 2),
+            (&half, 4, 2, 2),
+        ] {
+            let amount = Idt::from_milli(milli);
+            assert_eq!(rational.mul_rounded(amount, Rounding::Floor).milli(), floor);
+            assert_eq!(
+                rational.mul_rounded(amount, Rounding::HalfEven).milli(),
+                half_even
+            );
+        }
+        assert_eq!(Rational::new(3, 1).unwrap().mul(Idt::MAX), Idt::MAX);
+        assert_eq!(
+            serde_json::from_str::<Rounding>("\"half_even\"").unwrap(),
+            Rounding::HalfEven
+        );
+        assert_eq!(Rounding::HalfEven.to_string(), "half_even");
+    }
+
+    fn rounding_strategy() -> impl Strategy<Value = Rounding> {
+        prop_oneof![Just(Rounding::Floor), Just(Rounding::HalfEven)]
+    }
+
+    proptest! {
+        // floor is at most a milli-IDT below the exact amount, half even at most half of it away
+        #[test]
+        fn test_rounding_error(
+            numerator in 0u32..1000,
+            denominator in 1u32..1000,
+            milli in 0u64..u64::MAX / 1000,
+        ) {
+            let rational = Rational::new(numerator, denominator).unwrap();
+            let exact = numerator as u128 * milli as u128;
+            let denominator = denominator as u128;
+            let amount = Idt::from_milli(milli);
+            let floor = rational.mul_rounded(amount, Rounding::Floor).milli() as u128;
+            prop_assert!(floor * denominator <= exact && exact < (floor + 1) * denominator);
+            let half_even = rational.mul_rounded(amount, Rounding::HalfEven).milli() as u128;
+            prop_assert!((half_even * denominator * 2).abs_diff(exact * 2) <= denominator);
+        }
+
+        // scaling parts separately loses or gains less than a milli-IDT per part compared to
+        // scaling their sum, floor never gains
+        #[test]
+        fn test_rounding_conservation(
+            numerator in 0u32..1000,
+            denominator in 1u32..1000,
+            parts in prop::collection::vec(0u64..1_000_000_000, 1..20),
+            rounding in rounding_strategy(),
+        ) {
+            let rational = Rational::new(numerator, denominator).unwrap();
+            let total: Idt = parts.iter().copied().map(Idt::from_milli).sum();
+            let whole = rational.mul_rounded(total, rounding).milli();
+            let split: u64 = parts
+                .iter()
+                .map(|part| rational.mul_rounded(Idt::from_milli(*part), rounding).milli())
+                .sum();
+            let count = parts.len() as u64;
+            match rounding {
+                Rounding::Floor => prop_assert!(split <= whole && whole - split < count),
+                Rounding::HalfEven => prop_assert!(2 * whole.abs_diff(split) <= count + 1),
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    outbox::{OutboxItem, error::Error, storage::OutboxStorage},
+    pools::{self, MeteredPool},
+};
+
+// items are stored as JSON, encrypted as a whole since payloads contain user addresses
+pub struct DatabaseOutboxStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabaseOutboxStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("outbox", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS outbox (item_key TEXT PRIMARY KEY, data TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "outbox", "item_key").await?;
+        rotate_column(&pool, &cipher, "outbox", "data").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    fn decode(&self, data: &str) -> Result<OutboxItem, Error> {
+        Ok(serde_json::from_str(&self.cipher.decode(data)?)?)
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl OutboxStorage for DatabaseOutboxStorage {
+    async fn add_item(&self, item: OutboxItem) -> Result<bool, Error> {
+        let data = serde_json::to_string(&item)?;
+        let inserted = sqlx::query("INSERT INTO outbox (item_key, data) VALUES (?, ?)")
+            .bind(self.cipher.encode(&item.key))
+            .bind(self.cipher.encode(&data))
+            .execute(&self.pool)
+            .await;
+        match inserted {
+            Ok(_) => Ok(true),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn update_item(&self, item: OutboxItem) -> Result<(), Error> {
+        let data = serde_json::to_string(&item)?;
+        sqlx::query("REPLACE INTO outbox (item_key, data) VALUES (?, ?)")
+            .bind(self.cipher.encode(&item.key))
+            .bind(self.cipher.encode(&data))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_item(&self, key: &str) -> Result<bool, Error> {
+        let removed = sqlx::query("DELETE FROM outbox WHERE item_key = ?")
+            .bind(self.cipher.encode(key))
+            .execute(&self.pool)
+            .await?;
+        Ok(removed.rows_affected() > 0)
+    }
+
+    async fn items(&self) -> Result<Vec<OutboxItem>, Error> {
+        let rows = sqlx::query("SELECT data FROM outbox")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut items = rows
+            .iter()
+            .map(|row| self.decode(&row.get::<String, _>(0)))
+            .collect::<Result<Vec<_>, _>>()?;
+        items.sort_by(|a, b| (a.created_at, &a.key).cmp(&(b.created_at, &b.key)));
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseOutboxStorage::new("sqlite::memory:").await.unwrap();
+        let item = OutboxItem::new("b".into(), "http://a.com".into(), "{}".into(), 20);
+        let older = OutboxItem::new("c".into(), "http://a.com".into(), "{}".into(), 10);
+        assert!(storage.add_item(item.clone()).await.unwrap());
+        assert!(storage.add_item(older.clone()).await.unwrap());
+        assert!(!storage.add_item(item.clone()).await.unwrap());
+        assert_eq!(
+            storage.items().await.unwrap(),
+            vec![older.clone(), item.clone()]
+        );
+
+        let retried = OutboxItem {
+            attempts: 2,
+            next_attempt_at: 80,
+            last_error: Some("status 500".into()),
+            ..item
+        };
+        storage.update_item(retried.clone()).await.unwrap();
+        assert_eq!(storage.items().await.unwrap(), vec![older, retried]);
+        assert!(storage.remove_item("b").await.unwrap());
+        assert!(!storage.remove_item("b").await.unwrap());
+        assert_eq!(storage.items().await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,292 @@
+// Durable queue of outbound pushes to external servers.
+//
+// Webhook payloads are written to the outbox before they are sent, so they are not lost on
+// restarts. `deliver_periodically` posts due items, removes the delivered ones and retries
+// failures with exponential backoff. Items are deduplicated by key. Items that failed
+// `outbox.max_attempts` times stay queued without retries and are listed by
+// the signed admin request `POST /admin/outbox` for inspection.
+
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::OutboxSection,
+    identity::IdentityService,
+    outbox::{error::Error, storage::OutboxStorage},
+    startup::exponential_backoff,
+};
+
+pub mod db;
+pub mod error;
+pub mod storage;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxItem {
+    // an item is not queued again while an item with the same key is queued
+    pub key: String,
+    pub url: String,
+    // JSON posted to the url
+    pub body: String,
+    pub created_at: u64,
+    // failed deliveries so far
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+}
+
+impl OutboxItem {
+    pub fn new(key: String, url: String, body: String, now: u64) -> Self {
+        Self {
+            key,
+            url,
+            body,
+            created_at: now,
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: None,
+        }
+    }
+
+    // no more deliveries are attempted
+    pub fn is_failed(&self, config: &OutboxSection) -> bool {
+        self.attempts >= config.max_attempts.max(1)
+    }
+}
+
+// posts queued payloads
+#[async_trait]
+pub trait OutboxSender: Send + Sync {
+    async fn send(&self, url: &str, body: &str) -> Result<(), Error>;
+}
+
+pub struct HttpOutboxSender;
+
+#[async_trait]
+impl OutboxSender for HttpOutboxSender {
+    async fn send(&self, url: &str, body: &str) -> Result<(), Error> {
+        let response = surf::post(url)
+            .body(body)
+            .content_type(surf::http::mime::JSON)
+            .await
+            .map_err(|e| Error::DeliveryError(url.to_string(), e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(Error::DeliveryError(
+                url.to_string(),
+                format!("status {}", response.status()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+// records sent payloads and fails deliveries to selected urls, useful for tests
+#[derive(Default)]
+pub struct InMemoryOutboxSender {
+    sent: RwLock<Vec<(String, String)>>,
+    failing: RwLock<HashSet<String>>,
+}
+
+impl InMemoryOutboxSender {
+    pub async fn sent(&self) -> Vec<(String, String)> {
+        self.sent.read().await.clone()
+    }
+
+    pub async fn set_failing(&self, url: &str, failing: bool) {
+        let mut urls = self.failing.write().await;
+        if failing {
+            urls.insert(url.to_string());
+        } else {
+            urls.remove(url);
+        }
+    }
+}
+
+#[async_trait]
+impl OutboxSender for InMemoryOutboxSender {
+    async fn send(&self, url: &str, body: &str) -> Result<(), Error> {
+        if self.failing.read().await.contains(url) {
+            return Err(Error::DeliveryError(
+                url.to_string(),
+                "status 503".to_string(),
+            ));
+        }
+        self.sent
+            .write()
+            .await
+            .push((url.to_string(), body.to_string()));
+        Ok(())
+    }
+}
+
+// queues `body` for delivery to `url`, returns false if the key is queued already
+pub async fn enqueue<T: Serialize>(
+    storage: &dyn OutboxStorage,
+    key: String,
+    url: String,
+    body: &T,
+    now: u64,
+) -> Result<bool, Error> {
+    let body = serde_json::to_string(body)?;
+    storage.add_item(OutboxItem::new(key, url, body, now)).await
+}
+
+// seconds before the retry following `attempts` failed deliveries
+pub fn retry_delay(config: &OutboxSection, attempts: u32) -> u64 {
+    exponential_backoff(config.initial_backoff, config.max_backoff, attempts)
+}
+
+// sends the items that are due, returns the number of delivered ones
+pub async fn deliver_due(
+    storage: &dyn OutboxStorage,
+    sender: &dyn OutboxSender,
+    config: &OutboxSection,
+    now: u64,
+) -> Result<usize, Error> {
+    let mut delivered = 0;
+    for mut item in storage.items().await? {
+        if item.is_failed(config) || now < item.next_attempt_at {
+            continue;
+        }
+        let e = match sender.send(&item.url, &item.body).await {
+            Ok(()) => {
+                storage.remove_item(&item.key).await?;
+                delivered += 1;
+                continue;
+            }
+            Err(e) => e,
+        };
+        item.attempts = item.attempts.saturating_add(1);
+        item.next_attempt_at = now.saturating_add(retry_delay(config, item.attempts));
+        item.last_error = Some(e.to_string());
+        if item.is_failed(config) {
+            log::error!(
+                "Gave up delivering {} after {} attempts: {}",
+                item.key,
+                item.attempts,
+                e
+            );
+        } else {
+            log::warn!(
+                "Failed to deliver {} (attempt {}): {}, retrying at {}",
+                item.key,
+                item.attempts,
+                e,
+                item.next_attempt_at
+            );
+        }
+        storage.update_item(item).await?;
+    }
+    Ok(delivered)
+}
+
+// delivers queued items forever
+pub async fn deliver_periodically(
+    service: IdentityService,
+    storage: Arc<dyn OutboxStorage>,
+    sender: Arc<dyn OutboxSender>,
+    config: OutboxSection,
+) {
+    let interval = Duration::from_secs(config.check_interval.max(1));
+    loop {
+        if let Err(e) = deliver_due(&*storage, &*sender, &config, service.now()).await {
+            log::error!("Failed to deliver outbox items: {:?}", e);
+        }
+        async_std::task::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outbox::storage::InMemoryOutboxStorage;
+
+    const URL: &str = "http://example.com/hook";
+
+    #[async_std::test]
+    async fn test_deliver_due() {
+        let storage = InMemoryOutboxStorage::default();
+        let sender = InMemoryOutboxSender::default();
+        let config = OutboxSection {
+            initial_backoff: 10,
+            max_backoff: 15,
+            max_attempts: 3,
+            ..Default::default()
+        };
+        assert!(
+            enqueue(&storage, "a".into(), URL.into(), &[1], 100)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !enqueue(&storage, "a".into(), URL.into(), &[2], 100)
+                .await
+                .unwrap()
+        );
+
+        sender.set_failing(URL, true).await;
+        assert_eq!(
+            deliver_due(&storage, &sender, &config, 100).await.unwrap(),
+            0
+        );
+        let item = storage.items().await.unwrap().remove(0);
+        assert_eq!(item.attempts, 1);
+        assert_eq!(item.next_attempt_at, 110);
+        assert_eq!(
+            item.last_error.as_deref(),
+            Some("Delivery to http://example.com/hook failed: status 503")
+        );
+
+        // not due yet
+        assert_eq!(
+            deliver_due(&storage, &sender, &config, 109).await.unwrap(),
+            0
+        );
+        assert_eq!(storage.items().await.unwrap()[0].attempts, 1);
+        deliver_due(&storage, &sender, &config, 110).await.unwrap();
+        // backoff is capped
+        assert_eq!(storage.items().await.unwrap()[0].next_attempt_at, 125);
+
+        sender.set_failing(URL, false).await;
+        assert_eq!(
+            deliver_due(&storage, &sender, &config, 125).await.unwrap(),
+            1
+        );
+        assert!(storage.items().await.unwrap().is_empty());
+        assert_eq!(
+            sender.sent().await,
+            vec![(URL.to_string(), "[1]".to_string())]
+        );
+    }
+
+    #[async_std::test]
+    async fn test_max_attempts() {
+        let storage = InMemoryOutboxStorage::default();
+        let sender = InMemoryOutboxSender::default();
+        let config = OutboxSection {
+            initial_backoff: 0,
+            max_attempts: 2,
+            ..Default::default()
+        };
+        enqueue(&storage, "a".into(), URL.into(), &[1], 100)
+            .await
+            .unwrap();
+        sender.set_failing(URL, true).await;
+        for _ in 0..3 {
+            deliver_due(&storage, &sender, &config, 100).await.unwrap();
+        }
+        let item = storage.items().await.unwrap().remove(0);
+        assert_eq!(item.attempts, 2);
+        assert!(item.is_failed(&config));
+
+        // failed items are kept without retries
+        sender.set_failing(URL, false).await;
+        assert_eq!(
+            deliver_due(&storage, &sender, &config, 200).await.unwrap(),
+            0
+        );
+        assert!(sender.sent().await.is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::outbox::{OutboxItem, error::Error};
+
+#[async_trait]
+pub trait OutboxStorage: Send + Sync {
+    // returns false if an item with the same key is queued already
+    async fn add_item(&self, item: OutboxItem) -> Result<bool, Error>;
+    // replaces the queued item with the same key
+    async fn update_item(&self, item: OutboxItem) -> Result<(), Error>;
+    // returns false if no item is queued
+    async fn remove_item(&self, key: &str) -> Result<bool, Error>;
+    // every queued item, oldest first
+    async fn items(&self) -> Result<Vec<OutboxItem>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryOutboxStorage {
+    items: RwLock<BTreeMap<String, OutboxItem>>,
+}
+
+#[async_trait]
+impl OutboxStorage for InMemoryOutboxStorage {
+    async fn add_item(&self, item: OutboxItem) -> Result<bool, Error> {
+        let mut items = self.items.write().await;
+        if items.contains_key(&item.key) {
+            return Ok(false);
+        }
+        items.insert(item.key.clone(), item);
+        Ok(true)
+    }
+
+    async fn update_item(&self, item: OutboxItem) -> Result<(), Error> {
+        self.items.write().await.insert(item.key.clone(), item);
+        Ok(())
+    }
+
+    async fn remove_item(&self, key: &str) -> Result<bool, Error> {
+        Ok(self.items.write().await.remove(key).is_some())
+    }
+
+    async fn items(&self) -> Result<Vec<OutboxItem>, Error> {
+        let mut items: Vec<_> = self.items.read().await.values().cloned().collect();
+        items.sort_by_key(|item| item.created_at);
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryOutboxStorage::default();
+        let item = OutboxItem::new("b".into(), "http://a.com".into(), "{}".into(), 20);
+        let older = OutboxItem::new("c".into(), "http://a.com".into(), "{}".into(), 10);
+        assert!(storage.add_item(item.clone()).await.unwrap());
+        assert!(storage.add_item(older.clone()).await.unwrap());
+        // deduplicated by key
+        assert!(
+            !storage
+                .add_item(OutboxItem {
+                    body: "[]".into(),
+                    ..item.clone()
+                })
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            storage.items().await.unwrap(),
+            vec![older.clone(), item.clone()]
+        );
+
+        let retried = OutboxItem {
+            attempts: 1,
+            last_error: Some("status 500".into()),
+            ..item
+        };
+        storage.update_item(retried.clone()).await.unwrap();
+        assert_eq!(storage.items().await.unwrap(), vec![older, retried]);
+        assert!(storage.remove_item("b").await.unwrap());
+        assert!(!storage.remove_item("b").await.unwrap());
+        assert_eq!(storage.items().await.unwrap().len(), 1);
+    }
+}
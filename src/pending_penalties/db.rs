@@ -0,0 +1,156 @@
+use async_std::sync::Mutex;
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    pending_penalties::{
+        Decision, PendingPenalty, PendingPenaltyId, error::Error, storage::PendingPenaltyStorage,
+    },
+    pools::{self, MeteredPool},
+};
+
+// penalties are stored as JSON, encrypted as a whole since they contain user addresses
+pub struct DatabasePendingPenaltyStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+    // penalties are added one at a time, so ids follow the insertion order
+    last_id: Mutex<PendingPenaltyId>,
+}
+
+impl DatabasePendingPenaltyStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("pending_penalties", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS pending_penalties (id INTEGER PRIMARY KEY, decided INTEGER NOT NULL, data TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "pending_penalties", "data").await?;
+        let last_id = sqlx::query("SELECT id FROM pending_penalties ORDER BY id DESC LIMIT 1")
+            .fetch_optional(&pool)
+            .await?
+            .map(|row| row.get::<i64, _>(0) as PendingPenaltyId)
+            .unwrap_or_default();
+        Ok(Self {
+            pool,
+            cipher,
+            last_id: Mutex::new(last_id),
+        })
+    }
+
+    fn decode(&self, data: &str) -> Result<PendingPenalty, Error> {
+        Ok(serde_json::from_str(&self.cipher.decode(data)?)?)
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl PendingPenaltyStorage for DatabasePendingPenaltyStorage {
+    async fn add_penalty(&self, penalty: PendingPenalty) -> Result<PendingPenalty, Error> {
+        let mut last_id = self.last_id.lock().await;
+        let penalty = PendingPenalty {
+            id: *last_id + 1,
+            ..penalty
+        };
+        let data = serde_json::to_string(&penalty)?;
+        sqlx::query("INSERT INTO pending_penalties (id, decided, data) VALUES (?, ?, ?)")
+            .bind(penalty.id as i64)
+            .bind(penalty.decision.is_some() as i64)
+            .bind(self.cipher.encode(&data))
+            .execute(&self.pool)
+            .await?;
+        *last_id = penalty.id;
+        Ok(penalty)
+    }
+
+    async fn penalty(&self, id: PendingPenaltyId) -> Result<Option<PendingPenalty>, Error> {
+        let row = sqlx::query("SELECT data FROM pending_penalties WHERE id = ?")
+            .bind(id.min(i64::MAX as u64) as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|row| self.decode(&row.get::<String, _>(0)))
+            .transpose()
+    }
+
+    async fn undecided(
+        &self,
+        after_id: PendingPenaltyId,
+        limit: usize,
+    ) -> Result<Vec<PendingPenalty>, Error> {
+        let rows = sqlx::query(
+            "SELECT data FROM pending_penalties WHERE decided = 0 AND id > ? ORDER BY id LIMIT ?",
+        )
+        .bind(after_id.min(i64::MAX as u64) as i64)
+        .bind(limit.min(i64::MAX as usize) as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| self.decode(&row.get::<String, _>(0)))
+            .collect()
+    }
+
+    async fn decide(&self, id: PendingPenaltyId, decision: Decision) -> Result<bool, Error> {
+        let Some(penalty) = self.penalty(id).await? else {
+            return Ok(false);
+        };
+        let penalty = PendingPenalty {
+            decision: Some(decision),
+            ..penalty
+        };
+        let data = serde_json::to_string(&penalty)?;
+        // only the first decision updates the row
+        let updated = sqlx::query(
+            "UPDATE pending_penalties SET decided = 1, data = ? WHERE id = ? AND decided = 0",
+        )
+        .bind(self.cipher.encode(&data))
+        .bind(id as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(updated.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pending_penalties::storage::tests::penalty;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabasePendingPenaltyStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        for i in 1..=3 {
+            let added = storage
+                .add_penalty(penalty(&format!("user{i}"), i * 100))
+                .await
+                .unwrap();
+            assert_eq!(added.id, i);
+            assert_eq!(storage.penalty(i).await.unwrap(), Some(added));
+        }
+        assert_eq!(storage.penalty(4).await.unwrap(), None);
+        let decision = Decision {
+            admin: "admin".into(),
+            approved: false,
+            decided_at: 40,
+        };
+        assert!(storage.decide(2, decision.clone()).await.unwrap());
+        assert!(!storage.decide(2, decision.clone()).await.unwrap());
+        assert!(!storage.decide(4, decision.clone()).await.unwrap());
+        assert_eq!(
+            storage.penalty(2).await.unwrap().unwrap().decision,
+            Some(decision)
+        );
+        let pending = storage.undecided(0, 10).await.unwrap();
+        assert_eq!(pending.iter().map(|p| p.id).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(storage.undecided(1, 1).await.unwrap()[0].id, 3);
+    }
+}
@@ -0,0 +1,18 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Pending penalty {0} is not found")]
+    UnknownPenalty(u64),
+    #[error("Pending penalty {0} is already decided")]
+    AlreadyDecided(u64),
+    #[error("Identity error: {0}")]
+    IdentityError(#[from] crate::identity::error::Error),
+    #[error("Encryption error: {0}")]
+    EncryptionError(#[from] crate::encryption::error::Error),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[cfg(feature = "sled")]
+    #[error("Storage error: {0}")]
+    StorageError(#[from] crate::kv::error::Error),
+}
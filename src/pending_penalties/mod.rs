@@ -0,0 +1,208 @@
+// Penalties of moderators escalated to admins.
+//
+// With `escalation.approval_threshold` set, `POST /punish/:user` with a greater amount does not
+// punish the user. The penalty is stored as pending and listed by `GET /pending_penalties`
+// until an admin approves it with `POST /pending_penalties/:id/approve`, which applies it as
+// a penalty of the proposing moderator, or rejects it with `POST /pending_penalties/:id/reject`,
+// which notifies the moderator. Decided penalties are kept with the decision.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::EscalationSection,
+    identity::{IdentityService, IdtAmount, ProofId, UserAddress, punish::punish_with_reason},
+    pending_penalties::{error::Error, storage::PendingPenaltyStorage},
+};
+
+pub mod db;
+pub mod error;
+pub mod storage;
+
+pub type PendingPenaltyId = u64;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingPenalty {
+    // assigned by the storage, starts from 1
+    pub id: PendingPenaltyId,
+    pub user: UserAddress,
+    pub moderator: UserAddress,
+    pub amount: IdtAmount,
+    pub proof_id: ProofId,
+    // code of `identity.penalty_reasons`
+    pub reason: Option<String>,
+    pub created_at: u64,
+    // None while the penalty waits for an admin
+    pub decision: Option<Decision>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Decision {
+    pub admin: UserAddress,
+    pub approved: bool,
+    pub decided_at: u64,
+}
+
+// penalties above the threshold wait for an admin
+pub fn requires_approval(config: &EscalationSection, amount: IdtAmount) -> bool {
+    config
+        .approval_threshold
+        .is_some_and(|threshold| amount > threshold)
+}
+
+// stores the penalty proposed by the moderator. Unknown reasons are rejected at once, so
+// admins only see penalties that can be applied.
+pub async fn propose(
+    service: &IdentityService,
+    storage: &dyn PendingPenaltyStorage,
+    mut penalty: PendingPenalty,
+) -> Result<PendingPenalty, Error> {
+    service.check_penalty_reason(penalty.reason.as_ref())?;
+    penalty.created_at = service.now();
+    penalty.decision = None;
+    storage.add_penalty(penalty).await
+}
+
+// applies an approved penalty or drops a rejected one. The admin privilege is checked by
+// the caller. Punishing again with the same proof id replaces the penalty, so a decision
+// racing with another one cannot punish the user twice.
+pub async fn decide(
+    service: &IdentityService,
+    storage: &dyn PendingPenaltyStorage,
+    id: PendingPenaltyId,
+    admin: UserAddress,
+    approved: bool,
+) -> Result<PendingPenalty, Error> {
+    let Some(penalty) = storage.penalty(id).await? else {
+        return Err(Error::UnknownPenalty(id));
+    };
+    if penalty.decision.is_some() {
+        return Err(Error::AlreadyDecided(id));
+    }
+    if approved {
+        punish_with_reason(
+            service,
+            penalty.user.clone(),
+            penalty.moderator.clone(),
+            penalty.amount,
+            penalty.proof_id,
+            penalty.reason.clone(),
+        )
+        .await?;
+    }
+    let decision = Decision {
+        admin,
+        approved,
+        decided_at: service.now(),
+    };
+    if !storage.decide(id, decision.clone()).await? {
+        return Err(Error::AlreadyDecided(id));
+    }
+    Ok(PendingPenalty {
+        decision: Some(decision),
+        ..penalty
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identity::{
+            idt::balance,
+            proof::prove,
+            tests::{MODERATOR, PROOF_ID, START_TIMESTAMP, USER_A, service_with_mock_clock},
+        },
+        pending_penalties::storage::InMemoryPendingPenaltyStorage,
+    };
+
+    fn penalty(amount: IdtAmount) -> PendingPenalty {
+        PendingPenalty {
+            id: 0,
+            user: USER_A.into(),
+            moderator: MODERATOR.into(),
+            amount,
+            proof_id: PROOF_ID,
+            reason: None,
+            created_at: 0,
+            decision: None,
+        }
+    }
+
+    #[test]
+    fn test_requires_approval() {
+        let config = EscalationSection {
+            approval_threshold: Some(IdtAmount::new(100)),
+        };
+        assert!(!requires_approval(&config, IdtAmount::new(100)));
+        assert!(requires_approval(&config, IdtAmount::new(101)));
+        assert!(!requires_approval(
+            &EscalationSection::default(),
+            IdtAmount::new(1000)
+        ));
+    }
+
+    #[async_std::test]
+    async fn test_decide() {
+        let (service, _clock) = service_with_mock_clock();
+        let storage = InMemoryPendingPenaltyStorage::default();
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        let unknown = PendingPenalty {
+            reason: Some("unknown".into()),
+            ..penalty(IdtAmount::new(500))
+        };
+        assert!(matches!(
+            propose(&service, &storage, unknown).await,
+            Err(Error::IdentityError(_))
+        ));
+
+        let rejected = propose(&service, &storage, penalty(IdtAmount::new(500)))
+            .await
+            .unwrap();
+        let approved = propose(&service, &storage, penalty(IdtAmount::new(400)))
+            .await
+            .unwrap();
+        assert_eq!((rejected.id, approved.id), (1, 2));
+        assert_eq!(rejected.created_at, START_TIMESTAMP);
+
+        let decided = decide(&service, &storage, rejected.id, "admin".into(), false)
+            .await
+            .unwrap();
+        assert_eq!(
+            decided.decision,
+            Some(Decision {
+                admin: "admin".into(),
+                approved: false,
+                decided_at: START_TIMESTAMP,
+            })
+        );
+        assert_eq!(
+            balance(&service, &USER_A.into()).await.unwrap(),
+            IdtAmount::new(1000)
+        );
+
+        decide(&service, &storage, approved.id, "admin".into(), true)
+            .await
+            .unwrap();
+        assert_eq!(
+            balance(&service, &USER_A.into()).await.unwrap(),
+            IdtAmount::new(600)
+        );
+        assert!(matches!(
+            decide(&service, &storage, approved.id, "admin".into(), true).await,
+            Err(Error::AlreadyDecided(2))
+        ));
+        assert!(matches!(
+            decide(&service, &storage, 3, "admin".into(), true).await,
+            Err(Error::UnknownPenalty(3))
+        ));
+        assert!(storage.undecided(0, 10).await.unwrap().is_empty());
+    }
+}
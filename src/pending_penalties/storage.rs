@@ -0,0 +1,128 @@
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::pending_penalties::{Decision, PendingPenalty, PendingPenaltyId, error::Error};
+
+#[async_trait]
+pub trait PendingPenaltyStorage: Send + Sync {
+    // stores the penalty with the next id, the id of `penalty` is ignored
+    async fn add_penalty(&self, penalty: PendingPenalty) -> Result<PendingPenalty, Error>;
+    async fn penalty(&self, id: PendingPenaltyId) -> Result<Option<PendingPenalty>, Error>;
+    // at most `limit` undecided penalties after `after_id`, ordered by id
+    async fn undecided(
+        &self,
+        after_id: PendingPenaltyId,
+        limit: usize,
+    ) -> Result<Vec<PendingPenalty>, Error>;
+    // returns false if the penalty is missing or already decided
+    async fn decide(&self, id: PendingPenaltyId, decision: Decision) -> Result<bool, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryPendingPenaltyStorage {
+    // penalty with id `i` is stored at `i - 1`
+    penalties: RwLock<Vec<PendingPenalty>>,
+}
+
+#[async_trait]
+impl PendingPenaltyStorage for InMemoryPendingPenaltyStorage {
+    async fn add_penalty(&self, penalty: PendingPenalty) -> Result<PendingPenalty, Error> {
+        let mut penalties = self.penalties.write().await;
+        let penalty = PendingPenalty {
+            id: penalties.len() as PendingPenaltyId + 1,
+            ..penalty
+        };
+        penalties.push(penalty.clone());
+        Ok(penalty)
+    }
+
+    async fn penalty(&self, id: PendingPenaltyId) -> Result<Option<PendingPenalty>, Error> {
+        let penalties = self.penalties.read().await;
+        Ok(id
+            .checked_sub(1)
+            .and_then(|i| penalties.get(i as usize))
+            .cloned())
+    }
+
+    async fn undecided(
+        &self,
+        after_id: PendingPenaltyId,
+        limit: usize,
+    ) -> Result<Vec<PendingPenalty>, Error> {
+        Ok(self
+            .penalties
+            .read()
+            .await
+            .iter()
+            .filter(|p| p.id > after_id && p.decision.is_none())
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn decide(&self, id: PendingPenaltyId, decision: Decision) -> Result<bool, Error> {
+        let mut penalties = self.penalties.write().await;
+        let Some(penalty) = id
+            .checked_sub(1)
+            .and_then(|i| penalties.get_mut(i as usize))
+        else {
+            return Ok(false);
+        };
+        if penalty.decision.is_some() {
+            return Ok(false);
+        }
+        penalty.decision = Some(decision);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::identity::IdtAmount;
+
+    pub fn penalty(user: &str, amount: u64) -> PendingPenalty {
+        PendingPenalty {
+            id: 0,
+            user: user.into(),
+            moderator: "moderator".into(),
+            amount: IdtAmount::new(amount),
+            proof_id: 1,
+            reason: None,
+            created_at: amount,
+            decision: None,
+        }
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryPendingPenaltyStorage::default();
+        assert_eq!(storage.penalty(0).await.unwrap(), None);
+        for i in 1..=3 {
+            let added = storage
+                .add_penalty(penalty(&format!("user{i}"), i * 100))
+                .await
+                .unwrap();
+            assert_eq!(added.id, i);
+        }
+        let decision = Decision {
+            admin: "admin".into(),
+            approved: true,
+            decided_at: 40,
+        };
+        assert!(storage.decide(2, decision.clone()).await.unwrap());
+        assert!(!storage.decide(2, decision.clone()).await.unwrap());
+        assert!(!storage.decide(4, decision.clone()).await.unwrap());
+        assert_eq!(
+            storage.penalty(2).await.unwrap().unwrap().decision,
+            Some(decision)
+        );
+
+        let pending = storage.undecided(0, 10).await.unwrap();
+        assert_eq!(pending.iter().map(|p| p.id).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(pending[1].user, "user3");
+        assert_eq!(pending[1].created_at, 300);
+        assert_eq!(storage.undecided(1, 10).await.unwrap().len(), 1);
+        assert_eq!(storage.undecided(0, 1).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,142 @@
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::UserAddress,
+    pending_vouches::{PendingVouch, error::Error, storage::PendingVouchStorage},
+    pools::{self, MeteredPool},
+};
+
+// pending vouches are stored as JSON, encrypted as a whole since they contain user addresses
+pub struct DatabasePendingVouchStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabasePendingVouchStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("pending_vouches", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS pending_vouches (voucher TEXT NOT NULL, vouchee TEXT NOT NULL, data TEXT NOT NULL, PRIMARY KEY(voucher, vouchee))",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "pending_vouches", "voucher").await?;
+        rotate_column(&pool, &cipher, "pending_vouches", "vouchee").await?;
+        rotate_column(&pool, &cipher, "pending_vouches", "data").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    fn decode(&self, data: &str) -> Result<PendingVouch, Error> {
+        Ok(serde_json::from_str(&self.cipher.decode(data)?)?)
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl PendingVouchStorage for DatabasePendingVouchStorage {
+    async fn add_pending(&self, vouch: PendingVouch) -> Result<(), Error> {
+        let data = serde_json::to_string(&vouch)?;
+        sqlx::query("REPLACE INTO pending_vouches (voucher, vouchee, data) VALUES (?, ?, ?)")
+            .bind(self.cipher.encode(&vouch.voucher))
+            .bind(self.cipher.encode(&vouch.vouchee))
+            .bind(self.cipher.encode(&data))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn pending(
+        &self,
+        voucher: &UserAddress,
+        vouchee: &UserAddress,
+    ) -> Result<Option<PendingVouch>, Error> {
+        let row = sqlx::query("SELECT data FROM pending_vouches WHERE voucher = ? AND vouchee = ?")
+            .bind(self.cipher.encode(voucher))
+            .bind(self.cipher.encode(vouchee))
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|row| self.decode(&row.get::<String, _>(0)))
+            .transpose()
+    }
+
+    async fn remove_pending(
+        &self,
+        voucher: &UserAddress,
+        vouchee: &UserAddress,
+    ) -> Result<bool, Error> {
+        let removed = sqlx::query("DELETE FROM pending_vouches WHERE voucher = ? AND vouchee = ?")
+            .bind(self.cipher.encode(voucher))
+            .bind(self.cipher.encode(vouchee))
+            .execute(&self.pool)
+            .await?;
+        Ok(removed.rows_affected() > 0)
+    }
+
+    async fn all_pending(&self) -> Result<Vec<PendingVouch>, Error> {
+        let rows = sqlx::query("SELECT data FROM pending_vouches")
+            .fetch_all(&self.pool)
+            .await?;
+        // encrypted columns do not keep the order of addresses
+        let mut vouches = rows
+            .iter()
+            .map(|row| self.decode(&row.get::<String, _>(0)))
+            .collect::<Result<Vec<_>, _>>()?;
+        vouches.sort_by(|a, b| (&a.voucher, &a.vouchee).cmp(&(&b.voucher, &b.vouchee)));
+        Ok(vouches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabasePendingVouchStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        let vouch = PendingVouch {
+            voucher: "b".into(),
+            vouchee: "c".into(),
+            created_at: 10,
+            confirmed_at: None,
+        };
+        let other = PendingVouch {
+            voucher: "a".into(),
+            confirmed_at: Some(20),
+            ..vouch.clone()
+        };
+        storage.add_pending(vouch.clone()).await.unwrap();
+        storage.add_pending(other.clone()).await.unwrap();
+        assert_eq!(
+            storage.pending(&"b".into(), &"c".into()).await.unwrap(),
+            Some(vouch.clone())
+        );
+        assert_eq!(storage.all_pending().await.unwrap(), vec![other, vouch]);
+        assert!(
+            storage
+                .remove_pending(&"b".into(), &"c".into())
+                .await
+                .unwrap()
+        );
+        assert!(
+            !storage
+                .remove_pending(&"b".into(), &"c".into())
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            storage.pending(&"b".into(), &"c".into()).await.unwrap(),
+            None
+        );
+    }
+}
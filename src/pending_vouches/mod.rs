@@ -0,0 +1,197 @@
+// Two-phase vouches of high balance vouchers.
+//
+// A vouch of a user with a balance above `two_phase_vouch.threshold` is not written at once:
+// it waits as a pending vouch until the voucher confirms it with a second signature and the
+// cooling-off period is over, so a stolen key cannot move a large balance instantly.
+// Unconfirmed vouches are dropped after `two_phase_vouch.expiry` seconds.
+
+use std::{sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::TwoPhaseVouchSection,
+    identity::{IdentityService, UserAddress, idt::balance},
+    pending_vouches::{error::Error, storage::PendingVouchStorage},
+};
+
+pub mod db;
+pub mod error;
+pub mod storage;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingVouch {
+    pub voucher: UserAddress,
+    pub vouchee: UserAddress,
+    pub created_at: u64,
+    // time of the confirmation by the voucher
+    pub confirmed_at: Option<u64>,
+}
+
+impl PendingVouch {
+    // the vouch is not activated before this time even if confirmed
+    pub fn activates_at(&self, config: &TwoPhaseVouchSection) -> u64 {
+        self.created_at.saturating_add(config.cooling_off)
+    }
+
+    // unconfirmed vouch is dropped at this time
+    pub fn expires_at(&self, config: &TwoPhaseVouchSection) -> u64 {
+        self.created_at.saturating_add(config.expiry)
+    }
+}
+
+// new vouches of vouchers above the threshold are delayed, refreshes of active vouches are not
+pub async fn requires_confirmation(
+    service: &IdentityService,
+    config: &TwoPhaseVouchSection,
+    voucher: &UserAddress,
+    vouchee: &UserAddress,
+) -> Result<bool, Error> {
+    let Some(threshold) = config.threshold else {
+        return Ok(false);
+    };
+    if service
+        .vouchers_with_time(vouchee)
+        .await?
+        .contains_key(voucher)
+    {
+        return Ok(false);
+    }
+    Ok(balance(service, voucher).await? > threshold)
+}
+
+// writes the vouch if it is confirmed and the cooling-off period is over
+pub async fn try_activate(
+    service: &IdentityService,
+    storage: &dyn PendingVouchStorage,
+    config: &TwoPhaseVouchSection,
+    vouch: &PendingVouch,
+) -> Result<bool, Error> {
+    let now = service.now();
+    if vouch.confirmed_at.is_none() || now < vouch.activates_at(config) {
+        return Ok(false);
+    }
+    // removed first, so a vouch activated by a concurrent confirmation is not written twice
+    if !storage
+        .remove_pending(&vouch.voucher, &vouch.vouchee)
+        .await?
+    {
+        return Ok(false);
+    }
+    service
+        .vouch_with_timestamp(vouch.voucher.clone(), vouch.vouchee.clone(), now)
+        .await?;
+    Ok(true)
+}
+
+// activates confirmed vouches past their cooling-off period and drops expired ones.
+// Returns the activated vouches.
+pub async fn activate_pending(
+    service: &IdentityService,
+    storage: &dyn PendingVouchStorage,
+    config: &TwoPhaseVouchSection,
+) -> Result<Vec<PendingVouch>, Error> {
+    let now = service.now();
+    let mut activated = vec![];
+    for vouch in storage.all_pending().await? {
+        if try_activate(service, storage, config, &vouch).await? {
+            activated.push(vouch);
+        } else if vouch.confirmed_at.is_none() && now >= vouch.expires_at(config) {
+            storage
+                .remove_pending(&vouch.voucher, &vouch.vouchee)
+                .await?;
+            log::info!(
+                "Dropped unconfirmed vouch from {} to {}",
+                vouch.voucher,
+                vouch.vouchee
+            );
+        }
+    }
+    Ok(activated)
+}
+
+// activates pending vouches forever
+pub async fn activate_periodically(
+    service: IdentityService,
+    storage: Arc<dyn PendingVouchStorage>,
+    config: TwoPhaseVouchSection,
+) {
+    let interval = Duration::from_secs(config.check_interval.max(1));
+    loop {
+        match activate_pending(&service, &*storage, &config).await {
+            Ok(activated) if !activated.is_empty() => {
+                log::info!("Activated {} pending vouches", activated.len())
+            }
+            Ok(_) => {}
+            Err(e) => log::error!("Failed to activate pending vouches: {:?}", e),
+        }
+        async_std::task::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identity::{
+            IdtAmount,
+            tests::{USER_A, service_with_mock_clock},
+        },
+        pending_vouches::storage::InMemoryPendingVouchStorage,
+    };
+
+    const HOUR: u64 = 3600;
+
+    #[async_std::test]
+    async fn test_activate_pending() {
+        let (service, clock) = service_with_mock_clock();
+        let storage = InMemoryPendingVouchStorage::default();
+        let config = TwoPhaseVouchSection {
+            threshold: Some(IdtAmount::new(0)),
+            cooling_off: HOUR,
+            expiry: 2 * HOUR,
+            ..Default::default()
+        };
+        let now = service.now();
+        let confirmed = PendingVouch {
+            voucher: USER_A.to_string(),
+            vouchee: "userB".into(),
+            created_at: now,
+            confirmed_at: Some(now),
+        };
+        let unconfirmed = PendingVouch {
+            vouchee: "userC".into(),
+            confirmed_at: None,
+            ..confirmed.clone()
+        };
+        storage.add_pending(confirmed.clone()).await.unwrap();
+        storage.add_pending(unconfirmed.clone()).await.unwrap();
+
+        assert!(
+            activate_pending(&service, &storage, &config)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        clock.advance(HOUR);
+        let activated = activate_pending(&service, &storage, &config).await.unwrap();
+        assert_eq!(activated, vec![confirmed]);
+        assert_eq!(
+            service.vouchers_with_time(&"userB".into()).await.unwrap()[USER_A],
+            now + HOUR
+        );
+        assert_eq!(storage.all_pending().await.unwrap(), vec![unconfirmed]);
+
+        clock.advance(HOUR);
+        activate_pending(&service, &storage, &config).await.unwrap();
+        assert!(storage.all_pending().await.unwrap().is_empty());
+        assert!(
+            service
+                .vouchers_with_time(&"userC".into())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::{
+    identity::UserAddress,
+    pending_vouches::{PendingVouch, error::Error},
+};
+
+#[async_trait]
+pub trait PendingVouchStorage: Send + Sync {
+    // replaces the pending vouch of the same voucher and vouchee
+    async fn add_pending(&self, vouch: PendingVouch) -> Result<(), Error>;
+    async fn pending(
+        &self,
+        voucher: &UserAddress,
+        vouchee: &UserAddress,
+    ) -> Result<Option<PendingVouch>, Error>;
+    // returns false if no vouch is pending
+    async fn remove_pending(
+        &self,
+        voucher: &UserAddress,
+        vouchee: &UserAddress,
+    ) -> Result<bool, Error>;
+    // every pending vouch ordered by voucher and vouchee
+    async fn all_pending(&self) -> Result<Vec<PendingVouch>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryPendingVouchStorage {
+    // key - (voucher, vouchee)
+    vouches: RwLock<BTreeMap<(UserAddress, UserAddress), PendingVouch>>,
+}
+
+#[async_trait]
+impl PendingVouchStorage for InMemoryPendingVouchStorage {
+    async fn add_pending(&self, vouch: PendingVouch) -> Result<(), Error> {
+        self.vouches
+            .write()
+            .await
+            .insert((vouch.voucher.clone(), vouch.vouchee.clone()), vouch);
+        Ok(())
+    }
+
+    async fn pending(
+        &self,
+        voucher: &UserAddress,
+        vouchee: &UserAddress,
+    ) -> Result<Option<PendingVouch>, Error> {
+        Ok(self
+            .vouches
+            .read()
+            .await
+            .get(&(voucher.clone(), vouchee.clone()))
+            .cloned())
+    }
+
+    async fn remove_pending(
+        &self,
+        voucher: &UserAddress,
+        vouchee: &UserAddress,
+    ) -> Result<bool, Error> {
+        Ok(self
+            .vouches
+            .write()
+            .await
+            .remove(&(voucher.clone(), vouchee.clone()))
+            .is_some())
+    }
+
+    async fn all_pending(&self) -> Result<Vec<PendingVouch>, Error> {
+        Ok(self.vouches.read().await.values().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryPendingVouchStorage::default();
+        let vouch = PendingVouch {
+            voucher: "b".into(),
+            vouchee: "c".into(),
+            created_at: 10,
+            confirmed_at: None,
+        };
+        let other = PendingVouch {
+            voucher: "a".into(),
+            ..vouch.clone()
+        };
+        storage.add_pending(vouch.clone()).await.unwrap();
+        storage.add_pending(other.clone()).await.unwrap();
+        assert_eq!(
+            storage.pending(&"b".into(), &"c".into()).await.unwrap(),
+            Some(vouch.clone())
+        );
+        assert_eq!(storage.all_pending().await.unwrap(), vec![other, vouch]);
+        assert!(
+            storage
+                .remove_pending(&"b".into(), &"c".into())
+                .await
+                .unwrap()
+        );
+        assert!(
+            !storage
+                .remove_pending(&"b".into(), &"c".into())
+                .await
+                .unwrap()
+        );
+        assert_eq!(storage.all_pending().await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,9 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Unknown registry kind {0}")]
+    UnknownKind(String),
+    #[error("Registry {0} failed: {1}")]
+    RegistryError(String, String),
+    #[error("Identity error: {0}")]
+    IdentityError(#[from] crate::identity::error::Error),
+}
@@ -0,0 +1,381 @@
+// System proofs of addresses verified by proof-of-personhood registries.
+//
+// Each registry of `personhood.registries` is either a contract read through an Ethereum node
+// (e.g. `isRegistered(address)` of Proof of Humanity) or an HTTP API (e.g. BrightID
+// verifications). `POST /personhood/:user` checks the user in every registry and gives a
+// verified user a proof of the greatest amount configured for the registries that verified
+// them. The moderator of such a proof is `personhood/<registry>` instead of a moderator
+// address, so system proofs are told apart from moderator proofs and can be revoked per
+// registry like the proofs of a compromised moderator. System proofs are not counted in the
+// moderator statistics.
+
+use async_trait::async_trait;
+use ethers_core::{
+    abi::{Token, encode},
+    types::H160,
+    utils::{id, keccak256},
+};
+use serde_json::Value;
+
+use crate::{
+    config::{PersonhoodRegistry, PersonhoodSection},
+    ens::eth_call,
+    identity::{IdentityService, IdtAmount, ProofId, UserAddress},
+    personhood::error::Error,
+    verify::address_to_string,
+};
+
+pub mod error;
+
+// moderator of system proofs without the registry name
+pub const SYSTEM_MODERATOR_PREFIX: &str = "personhood/";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistryKind {
+    Rpc,
+    Api,
+}
+
+impl RegistryKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "rpc" => Some(RegistryKind::Rpc),
+            "api" => Some(RegistryKind::Api),
+            _ => None,
+        }
+    }
+}
+
+#[async_trait]
+pub trait Registry: Send + Sync {
+    async fn verified(&self, address: &H160) -> Result<bool, Error>;
+}
+
+// contract function of the address, a non-zero output means verified
+pub struct RpcRegistry {
+    rpc_url: String,
+    contract: String,
+    function: String,
+}
+
+impl RpcRegistry {
+    pub fn new(rpc_url: String, contract: String, function: String) -> Self {
+        Self {
+            rpc_url,
+            contract,
+            function,
+        }
+    }
+}
+
+#[async_trait]
+impl Registry for RpcRegistry {
+    async fn verified(&self, address: &H160) -> Result<bool, Error> {
+        let data = [
+            id(&self.function).as_slice(),
+            &encode(&[Token::Address(*address)]),
+        ]
+        .concat();
+        let output = eth_call(&self.rpc_url, &self.contract, &data)
+            .await
+            .map_err(|e| Error::RegistryError(self.rpc_url.clone(), e.to_string()))?;
+        Ok(output.iter().any(|byte| *byte != 0))
+    }
+}
+
+// GET of the URL with the address in place of `{address}`, the field at the JSON pointer is
+// `true` for verified addresses. Unknown addresses may be answered with 404.
+pub struct ApiRegistry {
+    url: String,
+    pointer: String,
+}
+
+impl ApiRegistry {
+    pub fn new(url: String, pointer: String) -> Self {
+        Self { url, pointer }
+    }
+}
+
+#[async_trait]
+impl Registry for ApiRegistry {
+    async fn verified(&self, address: &H160) -> Result<bool, Error> {
+        let url = self.url.replace("{address}", &address_to_string(address));
+        let registry_error = |e: String| Error::RegistryError(self.url.clone(), e);
+        let mut response = surf::get(&url)
+            .await
+            .map_err(|e| registry_error(e.to_string()))?;
+        if response.status() == 404 {
+            return Ok(false);
+        }
+        if !response.status().is_success() {
+            return Err(registry_error(format!("status {}", response.status())));
+        }
+        let body: Value = response
+            .body_json()
+            .await
+            .map_err(|e| registry_error(e.to_string()))?;
+        Ok(body.pointer(&self.pointer) == Some(&Value::Bool(true)))
+    }
+}
+
+pub fn registry(config: &PersonhoodRegistry) -> Result<Box<dyn Registry>, Error> {
+    match RegistryKind::parse(&config.kind) {
+        Some(RegistryKind::Rpc) => Ok(Box::new(RpcRegistry::new(
+            config.url.clone(),
+            config.contract.clone(),
+            config.function.clone(),
+        ))),
+        Some(RegistryKind::Api) => Ok(Box::new(ApiRegistry::new(
+            config.url.clone(),
+            config.pointer.clone(),
+        ))),
+        None => Err(Error::UnknownKind(config.kind.clone())),
+    }
+}
+
+pub fn system_moderator(registry: &str) -> UserAddress {
+    format!("{SYSTEM_MODERATOR_PREFIX}{registry}")
+}
+
+// name of the registry behind a system proof, `None` for moderator proofs
+pub fn system_registry(moderator: &str) -> Option<&str> {
+    moderator.strip_prefix(SYSTEM_MODERATOR_PREFIX)
+}
+
+// the same user always gets the same proof id from a registry
+pub fn personhood_proof_id(registry: &str, user: &str) -> ProofId {
+    let hash = keccak256(format!("personhood/{registry}/{user}"));
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash[..8]);
+    ProofId::from_be_bytes(bytes)
+}
+
+// checks the user in every registry and proves a verified user with the greatest amount of
+// the registries that verified them. An existing proof is only replaced if it gives less IDT
+// and revoked system proofs are not issued again by the same registry. Returns the names of
+// the registries that verified the user in name order.
+pub async fn verify(
+    service: &IdentityService,
+    config: &PersonhoodSection,
+    user: &UserAddress,
+) -> Result<Vec<String>, Error> {
+    // registries only know Ethereum addresses
+    let Ok(address) = user.parse::<H160>() else {
+        return Ok(Vec::new());
+    };
+    let revoked = service
+        .revoked_proof(user)
+        .await?
+        .map(|proof| proof.moderator);
+    let mut names: Vec<&String> = config.registries.keys().collect();
+    names.sort();
+    let mut verified = Vec::new();
+    let mut best: Option<(&String, IdtAmount)> = None;
+    for name in names {
+        let registry_config = &config.registries[name];
+        if !registry(registry_config)?.verified(&address).await? {
+            continue;
+        }
+        verified.push(name.clone());
+        if revoked.as_ref() == Some(&system_moderator(name)) {
+            continue;
+        }
+        if best.is_none_or(|(_, amount)| registry_config.amount > amount) {
+            best = Some((name, registry_config.amount));
+        }
+    }
+    let Some((name, amount)) = best else {
+        return Ok(verified);
+    };
+    let current = service.proof(user).await?;
+    if current.is_none_or(|proof| proof.amount < amount) {
+        service
+            .prove_system_with_timestamp(
+                user.clone(),
+                system_moderator(name),
+                amount,
+                personhood_proof_id(name, user),
+                service.now(),
+            )
+            .await?;
+    }
+    Ok(verified)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+    use tide::{Request, listener::Listener};
+
+    use super::*;
+    use crate::identity::tests::{MODERATOR, PROOF_ID, service_with_mock_clock};
+
+    pub const HUMAN: &str = "0x00000000000000000000000000000000000000a1";
+
+    // registry API verifying `HUMAN` and the RPC node of a registry contract verifying
+    // `HUMAN`, both at the returned URL
+    pub async fn mock_registry() -> String {
+        async fn api(req: Request<()>) -> tide::Result {
+            if req.param("address")? != HUMAN {
+                return Ok(tide::Response::new(404));
+            }
+            Ok(json!({"data": {"unique": true}}).into())
+        }
+
+        async fn eth_call(mut req: Request<()>) -> tide::Result {
+            let body: Value = req.body_json().await?;
+            let data = body["params"][0]["data"].as_str().unwrap();
+            let data = hex::decode(data.trim_start_matches("0x"))?;
+            let (selector, address) = data.split_at(4);
+            let verified = selector == id("isRegistered(address)")
+                && *address == *encode(&[Token::Address(HUMAN.parse()?)]);
+            let output = encode(&[Token::Bool(verified)]);
+            Ok(
+                json!({"jsonrpc": "2.0", "id": 1, "result": format!("0x{}", hex::encode(output))})
+                    .into(),
+            )
+        }
+
+        let mut app = tide::new();
+        app.at("/verifications/:address").get(api);
+        app.at("/").post(eth_call);
+        let mut listener = app.bind("127.0.0.1:0").await.unwrap();
+        let url = listener.info()[0].connection().to_string();
+        async_std::task::spawn(async move { listener.accept().await });
+        url
+    }
+
+    pub fn config(url: &str) -> PersonhoodSection {
+        PersonhoodSection {
+            registries: HashMap::from([
+                (
+                    "brightid".to_string(),
+                    PersonhoodRegistry {
+                        kind: "api".to_string(),
+                        url: format!("{url}/verifications/{{address}}"),
+                        pointer: "/data/unique".to_string(),
+                        amount: IdtAmount::new(100),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "poh".to_string(),
+                    PersonhoodRegistry {
+                        kind: "rpc".to_string(),
+                        url: url.to_string(),
+                        contract: address_to_string(&H160::repeat_byte(0xee)),
+                        function: "isRegistered(address)".to_string(),
+                        amount: IdtAmount::new(200),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+        }
+    }
+
+    #[async_std::test]
+    async fn test_registries() {
+        let url = mock_registry().await;
+        let config = config(&url);
+        let human: H160 = HUMAN.parse().unwrap();
+        let other = H160::repeat_byte(0xb0);
+        for name in ["brightid", "poh"] {
+            let registry = registry(&config.registries[name]).unwrap();
+            assert!(registry.verified(&human).await.unwrap());
+            assert!(!registry.verified(&other).await.unwrap());
+        }
+
+        let unknown = PersonhoodRegistry {
+            kind: "oracle".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(registry(&unknown), Err(Error::UnknownKind(_))));
+        let unreachable = RpcRegistry::new(
+            "http://127.0.0.1:1".to_string(),
+            HUMAN.to_string(),
+            "isRegistered(address)".to_string(),
+        );
+        assert!(matches!(
+            unreachable.verified(&human).await,
+            Err(Error::RegistryError(_, _))
+        ));
+    }
+
+    #[async_std::test]
+    async fn test_verify() {
+        let url = mock_registry().await;
+        let config = config(&url);
+        let (service, _) = service_with_mock_clock();
+        let human = HUMAN.to_string();
+
+        let other = address_to_string(&H160::repeat_byte(0xb0));
+        assert!(verify(&service, &config, &other).await.unwrap().is_empty());
+        assert!(service.proof(&other).await.unwrap().is_none());
+        // not an address
+        let user = "userA".to_string();
+        assert!(verify(&service, &config, &user).await.unwrap().is_empty());
+
+        // a greater moderator proof is kept
+        service
+            .prove_with_timestamp(
+                human.clone(),
+                MODERATOR.to_string(),
+                IdtAmount::new(300),
+                PROOF_ID,
+                1,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            verify(&service, &config, &human).await.unwrap(),
+            vec!["brightid", "poh"]
+        );
+        assert_eq!(
+            service.proof(&human).await.unwrap().unwrap().moderator,
+            MODERATOR
+        );
+
+        service
+            .prove_with_timestamp(
+                human.clone(),
+                MODERATOR.to_string(),
+                IdtAmount::new(50),
+                PROOF_ID,
+                1,
+            )
+            .await
+            .unwrap();
+        verify(&service, &config, &human).await.unwrap();
+        let proof = service.proof(&human).await.unwrap().unwrap();
+        assert_eq!(proof.amount, IdtAmount::new(200));
+        assert_eq!(proof.moderator, "personhood/poh");
+        assert_eq!(system_registry(&proof.moderator), Some("poh"));
+        assert_eq!(system_registry(MODERATOR), None);
+        assert_eq!(proof.proof_id, personhood_proof_id("poh", HUMAN));
+
+        // system proofs are revoked per registry
+        let revoked = service
+            .revoke_system_proofs(&system_moderator("poh"))
+            .await
+            .unwrap();
+        assert_eq!(revoked, vec![human.clone()]);
+        // registries are not moderators
+        assert_eq!(
+            service
+                .moderator_stats(&system_moderator("poh"))
+                .await
+                .unwrap(),
+            Default::default()
+        );
+        verify(&service, &config, &human).await.unwrap();
+        let proof = service.proof(&human).await.unwrap().unwrap();
+        assert_eq!(proof.moderator, "personhood/brightid");
+        assert_eq!(proof.amount, IdtAmount::new(100));
+        assert_eq!(
+            service.moderator_stats(&proof.moderator).await.unwrap(),
+            Default::default()
+        );
+    }
+}
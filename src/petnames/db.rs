@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::UserAddress,
+    petnames::{error::Error, storage::PetnameStorage},
+    pools::{self, MeteredPool},
+};
+
+pub struct DatabasePetnameStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabasePetnameStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("petnames", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS petnames (owner TEXT NOT NULL, address TEXT NOT NULL, name TEXT NOT NULL, PRIMARY KEY(owner, address))",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "petnames", "owner").await?;
+        rotate_column(&pool, &cipher, "petnames", "address").await?;
+        rotate_column(&pool, &cipher, "petnames", "name").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl PetnameStorage for DatabasePetnameStorage {
+    async fn set_petname(
+        &self,
+        owner: UserAddress,
+        address: UserAddress,
+        name: String,
+    ) -> Result<(), Error> {
+        sqlx::query("REPLACE INTO petnames (owner, address, name) VALUES (?, ?, ?)")
+            .bind(self.cipher.encode(&owner))
+            .bind(self.cipher.encode(&address))
+            .bind(self.cipher.encode(&name))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_petname(
+        &self,
+        owner: &UserAddress,
+        address: &UserAddress,
+    ) -> Result<(), Error> {
+        sqlx::query("DELETE FROM petnames WHERE owner = ? AND address = ?")
+            .bind(self.cipher.encode(owner))
+            .bind(self.cipher.encode(address))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn petnames(&self, owner: &UserAddress) -> Result<HashMap<UserAddress, String>, Error> {
+        let rows = sqlx::query("SELECT address, name FROM petnames WHERE owner = ?")
+            .bind(self.cipher.encode(owner))
+            .fetch_all(&self.pool)
+            .await?;
+        let mut petnames = HashMap::new();
+        for row in rows {
+            petnames.insert(
+                self.cipher.decode(&row.get::<String, _>(0))?,
+                self.cipher.decode(&row.get::<String, _>(1))?,
+            );
+        }
+        Ok(petnames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabasePetnameStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        let owner = "owner".to_string();
+        assert!(storage.petnames(&owner).await.unwrap().is_empty());
+        storage
+            .set_petname(owner.clone(), "a".to_string(), "Alice".to_string())
+            .await
+            .unwrap();
+        storage
+            .set_petname(owner.clone(), "a".to_string(), "Alice B.".to_string())
+            .await
+            .unwrap();
+        storage
+            .set_petname(owner.clone(), "b".to_string(), "Bob".to_string())
+            .await
+            .unwrap();
+        storage
+            .set_petname("other".to_string(), "a".to_string(), "Al".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.petnames(&owner).await.unwrap(),
+            HashMap::from([
+                ("a".to_string(), "Alice B.".to_string()),
+                ("b".to_string(), "Bob".to_string()),
+            ])
+        );
+        storage
+            .remove_petname(&owner, &"a".to_string())
+            .await
+            .unwrap();
+        assert_eq!(storage.petnames(&owner).await.unwrap().len(), 1);
+    }
+}
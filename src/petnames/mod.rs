@@ -0,0 +1,36 @@
+// Address book of users: names they give to the addresses they vouch for.
+//
+// Petnames are set with signed `POST /petname/<address>` requests of the voucher and are
+// returned with the vouchees of the user by `GET /vouchees/<user>`, so wallets can show
+// familiar names instead of raw addresses.
+
+pub mod db;
+pub mod error;
+pub mod storage;
+
+// longest petname in characters
+pub const MAX_PETNAME_LENGTH: usize = 64;
+// petnames a single user can store
+pub const MAX_PETNAMES: usize = 1000;
+
+pub fn is_valid_petname(name: &str) -> bool {
+    !name.trim().is_empty()
+        && name.chars().count() <= MAX_PETNAME_LENGTH
+        && !name.chars().any(char::is_control)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_petname() {
+        assert!(is_valid_petname("Alice"));
+        assert!(is_valid_petname("Алиса из банка"));
+        assert!(is_valid_petname(&"a".repeat(MAX_PETNAME_LENGTH)));
+        assert!(!is_valid_petname(&"a".repeat(MAX_PETNAME_LENGTH + 1)));
+        assert!(!is_valid_petname(""));
+        assert!(!is_valid_petname("  "));
+        assert!(!is_valid_petname("line\nbreak"));
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::{identity::UserAddress, petnames::error::Error};
+
+// names users give to other addresses
+#[async_trait]
+pub trait PetnameStorage: Send + Sync {
+    async fn set_petname(
+        &self,
+        owner: UserAddress,
+        address: UserAddress,
+        name: String,
+    ) -> Result<(), Error>;
+    async fn remove_petname(&self, owner: &UserAddress, address: &UserAddress)
+    -> Result<(), Error>;
+    // all petnames of the owner by address
+    async fn petnames(&self, owner: &UserAddress) -> Result<HashMap<UserAddress, String>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryPetnameStorage {
+    petnames: RwLock<HashMap<UserAddress, HashMap<UserAddress, String>>>,
+}
+
+#[async_trait]
+impl PetnameStorage for InMemoryPetnameStorage {
+    async fn set_petname(
+        &self,
+        owner: UserAddress,
+        address: UserAddress,
+        name: String,
+    ) -> Result<(), Error> {
+        self.petnames
+            .write()
+            .await
+            .entry(owner)
+            .or_default()
+            .insert(address, name);
+        Ok(())
+    }
+
+    async fn remove_petname(
+        &self,
+        owner: &UserAddress,
+        address: &UserAddress,
+    ) -> Result<(), Error> {
+        let mut petnames = self.petnames.write().await;
+        if let Some(names) = petnames.get_mut(owner) {
+            names.remove(address);
+            if names.is_empty() {
+                petnames.remove(owner);
+            }
+        }
+        Ok(())
+    }
+
+    async fn petnames(&self, owner: &UserAddress) -> Result<HashMap<UserAddress, String>, Error> {
+        Ok(self
+            .petnames
+            .read()
+            .await
+            .get(owner)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryPetnameStorage::default();
+        let (owner, other) = ("owner".to_string(), "other".to_string());
+        assert!(storage.petnames(&owner).await.unwrap().is_empty());
+        storage
+            .set_petname(owner.clone(), "a".to_string(), "Alice".to_string())
+            .await
+            .unwrap();
+        storage
+            .set_petname(owner.clone(), "a".to_string(), "Alice B.".to_string())
+            .await
+            .unwrap();
+        storage
+            .set_petname(other.clone(), "a".to_string(), "Al".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.petnames(&owner).await.unwrap(),
+            HashMap::from([("a".to_string(), "Alice B.".to_string())])
+        );
+        storage
+            .remove_petname(&owner, &"a".to_string())
+            .await
+            .unwrap();
+        assert!(storage.petnames(&owner).await.unwrap().is_empty());
+        assert_eq!(storage.petnames(&other).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,282 @@
+// Connection pools of the SQL storages. Every storage opens its pool with `connect` under its
+// name and runs its queries through the returned `MeteredPool`, which records how long each of
+// them waited for a connection. The pools of the opened storages are kept in `Pools`, so
+// `GET /metrics` can report their size, idle connections and waits.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::{
+    StreamExt, TryStreamExt,
+    future::BoxFuture,
+    stream::{self, BoxStream},
+};
+use serde::Serialize;
+use sqlx::{
+    Any, AnyPool, Describe, Either, Execute, Executor, Transaction,
+    any::{AnyPoolOptions, AnyQueryResult, AnyRow, AnyStatement, AnyTypeInfo},
+    pool::PoolConnection,
+};
+
+// waits longer than this mean the pool is saturated and are logged as warnings
+pub const SATURATION_WAIT: Duration = Duration::from_millis(100);
+// at most one saturation warning per pool in this period
+const WARNING_PERIOD: Duration = Duration::from_secs(60);
+const MAX_CONNECTIONS: u32 = 1;
+
+#[derive(Debug, Default)]
+struct Usage {
+    acquires: u64,
+    total_wait: Duration,
+    max_wait: Duration,
+    saturations: u64,
+    last_warning: Option<Instant>,
+}
+
+// pool of a storage recording every acquired connection. Queries executed on `&MeteredPool`
+// and transactions started with `begin` acquire their connection through `acquire`.
+#[derive(Clone, Debug)]
+pub struct MeteredPool {
+    name: Arc<str>,
+    pool: AnyPool,
+    usage: Arc<Mutex<Usage>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PoolStats {
+    pub size: u32,
+    pub idle: usize,
+    pub max_connections: u32,
+    // connections acquired since the pool was opened
+    pub acquires: u64,
+    pub avg_wait_ms: f64,
+    pub max_wait_ms: f64,
+    // acquires that waited longer than `SATURATION_WAIT`
+    pub saturations: u64,
+    // every connection is in use
+    pub saturated: bool,
+}
+
+pub async fn connect(name: &str, url: &str) -> Result<MeteredPool, sqlx::Error> {
+    sqlx::any::install_default_drivers();
+    let pool = AnyPoolOptions::new()
+        .max_connections(MAX_CONNECTIONS)
+        .connect(url)
+        .await?;
+    Ok(MeteredPool::new(name, pool))
+}
+
+impl MeteredPool {
+    pub fn new(name: &str, pool: AnyPool) -> Self {
+        Self {
+            name: name.into(),
+            pool,
+            usage: Arc::default(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub async fn acquire(&self) -> Result<PoolConnection<Any>, sqlx::Error> {
+        let started = Instant::now();
+        let conn = self.pool.acquire().await?;
+        self.record_wait(started.elapsed());
+        Ok(conn)
+    }
+
+    pub async fn begin(&self) -> Result<Transaction<'static, Any>, sqlx::Error> {
+        let conn = self.acquire().await?;
+        Transaction::begin(conn).await
+    }
+
+    pub async fn close(&self) {
+        self.pool.close().await
+    }
+
+    fn record_wait(&self, wait: Duration) {
+        let Ok(mut usage) = self.usage.lock() else {
+            return;
+        };
+        usage.acquires += 1;
+        usage.total_wait += wait;
+        usage.max_wait = usage.max_wait.max(wait);
+        if wait <= SATURATION_WAIT {
+            return;
+        }
+        usage.saturations += 1;
+        let now = Instant::now();
+        if usage
+            .last_warning
+            .is_none_or(|last| now.duration_since(last) >= WARNING_PERIOD)
+        {
+            usage.last_warning = Some(now);
+            log::warn!(
+                "Connection pool of {} is saturated: waited {} ms for a connection, {} saturated acquires",
+                self.name,
+                wait.as_millis(),
+                usage.saturations
+            );
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let size = self.pool.size();
+        let idle = self.pool.num_idle();
+        let max_connections = self.pool.options().get_max_connections();
+        let (acquires, avg_wait_ms, max_wait_ms, saturations) = match self.usage.lock() {
+            Ok(usage) => {
+                let avg_wait_nanos = match usage.acquires {
+                    0 => 0,
+                    n => usage.total_wait.as_nanos() / n as u128,
+                };
+                (
+                    usage.acquires,
+                    avg_wait_nanos as f64 / 1_000_000.0,
+                    usage.max_wait.as_secs_f64() * 1000.0,
+                    usage.saturations,
+                )
+            }
+            Err(_) => (0, 0.0, 0.0, 0),
+        };
+        PoolStats {
+            size,
+            idle,
+            max_connections,
+            acquires,
+            avg_wait_ms,
+            max_wait_ms,
+            saturations,
+            saturated: size == max_connections && idle == 0,
+        }
+    }
+}
+
+// same as the executor of `AnyPool`, but the connection is taken with `MeteredPool::acquire`
+impl<'p> Executor<'p> for &'_ MeteredPool {
+    type Database = Any;
+
+    fn fetch_many<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxStream<'e, Result<Either<AnyQueryResult, AnyRow>, sqlx::Error>>
+    where
+        E: 'q + Execute<'q, Any>,
+    {
+        let pool = self.clone();
+        stream::once(async move {
+            let mut conn = pool.acquire().await?;
+            conn.fetch_many(query).try_collect::<Vec<_>>().await
+        })
+        .map_ok(|results| stream::iter(results.into_iter().map(Ok)))
+        .try_flatten()
+        .boxed()
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'e, Result<Option<AnyRow>, sqlx::Error>>
+    where
+        E: 'q + Execute<'q, Any>,
+    {
+        let pool = self.clone();
+        Box::pin(async move { pool.acquire().await?.fetch_optional(query).await })
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [AnyTypeInfo],
+    ) -> BoxFuture<'e, Result<AnyStatement<'q>, sqlx::Error>> {
+        let pool = self.clone();
+        Box::pin(async move { pool.acquire().await?.prepare_with(sql, parameters).await })
+    }
+
+    fn describe<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+    ) -> BoxFuture<'e, Result<Describe<Any>, sqlx::Error>> {
+        let pool = self.clone();
+        Box::pin(async move { pool.acquire().await?.describe(sql).await })
+    }
+}
+
+// pools of the opened storages by name. Pools registered again under the same name replace the
+// previous one in the report.
+#[derive(Default)]
+pub struct Pools {
+    pools: Mutex<BTreeMap<String, MeteredPool>>,
+}
+
+impl Pools {
+    pub fn register(&self, pool: &MeteredPool) {
+        if let Ok(mut pools) = self.pools.lock() {
+            pools.insert(pool.name().to_string(), pool.clone());
+        }
+    }
+
+    // statistics of every registered pool by storage name
+    pub fn stats(&self) -> BTreeMap<String, PoolStats> {
+        let Ok(pools) = self.pools.lock() else {
+            return BTreeMap::new();
+        };
+        pools
+            .iter()
+            .map(|(name, pool)| (name.clone(), pool.stats()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_stats() {
+        let pool = connect("test_pools", "sqlite::memory:").await.unwrap();
+        let pools = Pools::default();
+        pools.register(&pool);
+        let stats = &pools.stats()["test_pools"];
+        assert_eq!(stats.acquires, 0);
+        assert_eq!(stats.avg_wait_ms, 0.0);
+        assert_eq!(stats.max_connections, MAX_CONNECTIONS);
+
+        let tx = pool.begin().await.unwrap();
+        let stats = &pools.stats()["test_pools"];
+        assert_eq!(stats.acquires, 1);
+        assert_eq!(stats.idle, 0);
+        assert!(stats.saturated);
+        tx.commit().await.unwrap();
+
+        // queries outside of transactions wait for a connection too
+        sqlx::query("SELECT 1").fetch_all(&pool).await.unwrap();
+        sqlx::query("SELECT 1").execute(&pool).await.unwrap();
+        sqlx::query("SELECT 1").fetch_optional(&pool).await.unwrap();
+        assert_eq!(pools.stats()["test_pools"].acquires, 4);
+
+        pool.record_wait(SATURATION_WAIT * 2);
+        let stats = &pools.stats()["test_pools"];
+        assert_eq!(stats.acquires, 5);
+        assert_eq!(stats.saturations, 1);
+        assert!(stats.max_wait_ms >= 200.0);
+        assert!(stats.avg_wait_ms >= 40.0);
+    }
+
+    #[async_std::test]
+    async fn test_registry() {
+        let pools = Pools::default();
+        assert!(pools.stats().is_empty());
+        let first = connect("test_registry", "sqlite::memory:").await.unwrap();
+        pools.register(&first);
+        first.acquire().await.unwrap();
+        let second = connect("test_registry", "sqlite::memory:").await.unwrap();
+        pools.register(&second);
+        assert_eq!(pools.stats().len(), 1);
+        assert_eq!(pools.stats()["test_registry"].acquires, 0);
+    }
+}
@@ -0,0 +1,7 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Outbox error: {0}")]
+    OutboxError(#[from] crate::outbox::error::Error),
+    #[error("Identity error: {0}")]
+    IdentityError(#[from] crate::identity::error::Error),
+}
@@ -0,0 +1,295 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::{
+    config::RemindersSection,
+    identity::{IdentityService, ProofId, UserAddress, clock::Clock, decay::proof_expiry},
+    outbox::{enqueue, storage::OutboxStorage},
+    reminders::error::Error,
+};
+
+pub mod error;
+
+const DAY: u64 = 60 * 60 * 24;
+
+// sent when a proof is about to decay to zero and the user should be verified again
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct ProofReminder {
+    pub event: String,
+    pub user: UserAddress,
+    pub moderator: UserAddress,
+    pub proof_id: ProofId,
+    pub expires_at: u64,
+}
+
+#[async_trait]
+pub trait ReminderSink: Send + Sync {
+    async fn remind(&self, reminder: &ProofReminder) -> Result<(), Error>;
+}
+
+// queues reminders in the outbox, which posts them as JSON to the configured url
+pub struct OutboxReminderSink {
+    pub url: String,
+    pub outbox: Arc<dyn OutboxStorage>,
+    pub clock: Arc<dyn Clock>,
+}
+
+#[async_trait]
+impl ReminderSink for OutboxReminderSink {
+    async fn remind(&self, reminder: &ProofReminder) -> Result<(), Error> {
+        let key = format!(
+            "reminder/{}/{}/{}",
+            reminder.user, reminder.proof_id, reminder.expires_at
+        );
+        enqueue(
+            &*self.outbox,
+            key,
+            self.url.clone(),
+            reminder,
+            self.clock.now(),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+// only writes reminders to the log, used when webhook is not configured
+pub struct LogReminderSink;
+
+#[async_trait]
+impl ReminderSink for LogReminderSink {
+    async fn remind(&self, reminder: &ProofReminder) -> Result<(), Error> {
+        log::info!(
+            "Proof {} of {} expires at {}",
+            reminder.proof_id,
+            reminder.user,
+            reminder.expires_at
+        );
+        Ok(())
+    }
+}
+
+// records reminders, useful for tests
+#[derive(Default)]
+pub struct InMemoryReminderSink {
+    reminders: RwLock<Vec<ProofReminder>>,
+}
+
+impl InMemoryReminderSink {
+    pub async fn reminders(&self) -> Vec<ProofReminder> {
+        self.reminders.read().await.clone()
+    }
+}
+
+#[async_trait]
+impl ReminderSink for InMemoryReminderSink {
+    async fn remind(&self, reminder: &ProofReminder) -> Result<(), Error> {
+        self.reminders.write().await.push(reminder.clone());
+        Ok(())
+    }
+}
+
+// timestamp when the reminder about the expiring proof is due
+pub fn remind_at(expires_at: u64, days_before_expiry: u64) -> u64 {
+    expires_at.saturating_sub(days_before_expiry.saturating_mul(DAY))
+}
+
+// proofs that expire within `days_before_expiry` days and have not expired yet
+pub async fn due_reminders(
+    service: &IdentityService,
+    days_before_expiry: u64,
+) -> Result<Vec<ProofReminder>, Error> {
+    let now = service.now();
+    let mut users: Vec<UserAddress> = service.proofs.proven_users().await?.into_iter().collect();
+    users.sort();
+    let mut reminders = vec![];
+    for user in users {
+        let (Some(proof), Some(expires_at)) = (
+            service.proof(&user).await?,
+            proof_expiry(service, &user).await?,
+        ) else {
+            continue;
+        };
+        if now < remind_at(expires_at, days_before_expiry) || now >= expires_at {
+            continue;
+        }
+        reminders.push(ProofReminder {
+            event: "proof_expiring".to_string(),
+            user,
+            moderator: proof.moderator,
+            proof_id: proof.proof_id,
+            expires_at,
+        });
+    }
+    Ok(reminders)
+}
+
+// sends due reminders once per proof, `sent` keeps reminders that were already delivered
+pub async fn send_reminders(
+    service: &IdentityService,
+    sink: &dyn ReminderSink,
+    days_before_expiry: u64,
+    sent: &mut HashSet<(UserAddress, ProofId, u64)>,
+) -> Result<usize, Error> {
+    let mut count = 0;
+    for reminder in due_reminders(service, days_before_expiry).await? {
+        let key = (
+            reminder.user.clone(),
+            reminder.proof_id,
+            reminder.expires_at,
+        );
+        if sent.contains(&key) {
+            continue;
+        }
+        match sink.remind(&reminder).await {
+            Ok(()) => {
+                sent.insert(key);
+                count += 1;
+            }
+            // retried on the next check
+            Err(e) => log::warn!("Failed to send reminder for {}: {}", reminder.user, e),
+        }
+    }
+    Ok(count)
+}
+
+pub fn sink(
+    config: &RemindersSection,
+    outbox: Arc<dyn OutboxStorage>,
+    clock: Arc<dyn Clock>,
+) -> Arc<dyn ReminderSink> {
+    match &config.webhook {
+        Some(url) => Arc::new(OutboxReminderSink {
+            url: url.clone(),
+            outbox,
+            clock,
+        }),
+        None => Arc::new(LogReminderSink),
+    }
+}
+
+// checks expiring proofs forever
+pub async fn remind_periodically(
+    service: IdentityService,
+    outbox: Arc<dyn OutboxStorage>,
+    config: RemindersSection,
+) {
+    let sink = sink(&config, outbox, service.clock.clone());
+    let interval = Duration::from_secs(config.check_interval.max(1));
+    let mut sent = HashSet::new();
+    loop {
+        if let Err(e) = send_reminders(&service, &*sink, config.days_before_expiry, &mut sent).await
+        {
+            log::error!("Failed to check expiring proofs: {:?}", e);
+        }
+        async_std::task::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identity::{
+            IdtAmount,
+            tests::{MODERATOR, PROOF_ID, START_TIMESTAMP, USER_A, service_with_mock_clock},
+        },
+        outbox::storage::InMemoryOutboxStorage,
+    };
+
+    #[async_std::test]
+    async fn test_send_reminders() {
+        let (service, clock) = service_with_mock_clock();
+        let sink = InMemoryReminderSink::default();
+        let mut sent = HashSet::new();
+        service
+            .prove_with_timestamp(
+                USER_A.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(10),
+                PROOF_ID,
+                START_TIMESTAMP,
+            )
+            .await
+            .unwrap();
+        let expires_at = START_TIMESTAMP + 10 * DAY;
+
+        // too early
+        assert_eq!(
+            send_reminders(&service, &sink, 3, &mut sent).await.unwrap(),
+            0
+        );
+        clock.set(remind_at(expires_at, 3));
+        assert_eq!(
+            send_reminders(&service, &sink, 3, &mut sent).await.unwrap(),
+            1
+        );
+        // sent only once
+        assert_eq!(
+            send_reminders(&service, &sink, 3, &mut sent).await.unwrap(),
+            0
+        );
+        assert_eq!(
+            sink.reminders().await,
+            vec![ProofReminder {
+                event: "proof_expiring".to_string(),
+                user: USER_A.to_string(),
+                moderator: MODERATOR.to_string(),
+                proof_id: PROOF_ID,
+                expires_at,
+            }]
+        );
+
+        // expired proofs are not reminded about
+        clock.set(expires_at);
+        assert!(due_reminders(&service, 3).await.unwrap().is_empty());
+
+        // re-proof is reminded about again
+        service
+            .prove_with_timestamp(
+                USER_A.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(2),
+                PROOF_ID + 1,
+                expires_at,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            send_reminders(&service, &sink, 3, &mut sent).await.unwrap(),
+            1
+        );
+    }
+
+    #[async_std::test]
+    async fn test_outbox_sink() {
+        let (service, _clock) = service_with_mock_clock();
+        let outbox = Arc::new(InMemoryOutboxStorage::default());
+        let config = RemindersSection {
+            webhook: Some("http://example.com/hook".to_string()),
+            ..Default::default()
+        };
+        let sink = sink(&config, outbox.clone(), service.clock.clone());
+        let reminder = ProofReminder {
+            event: "proof_expiring".to_string(),
+            user: USER_A.to_string(),
+            moderator: MODERATOR.to_string(),
+            proof_id: PROOF_ID,
+            expires_at: 100,
+        };
+        sink.remind(&reminder).await.unwrap();
+        // queued once
+        sink.remind(&reminder).await.unwrap();
+        let items = outbox.items().await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].url, "http://example.com/hook");
+        assert_eq!(items[0].created_at, service.now());
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&items[0].body).unwrap()["user"],
+            USER_A
+        );
+    }
+}
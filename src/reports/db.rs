@@ -0,0 +1,161 @@
+use async_std::sync::Mutex;
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::UserAddress,
+    pools::{self, MeteredPool},
+    reports::{Report, ReportId, Resolution, error::Error, storage::ReportStorage},
+};
+
+// reports are stored as JSON, encrypted as a whole since they contain user addresses
+pub struct DatabaseReportStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+    // reports are added one at a time, so ids follow the insertion order
+    last_id: Mutex<ReportId>,
+}
+
+impl DatabaseReportStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("reports", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS reports (id INTEGER PRIMARY KEY, resolved INTEGER NOT NULL, data TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "reports", "data").await?;
+        let last_id = sqlx::query("SELECT id FROM reports ORDER BY id DESC LIMIT 1")
+            .fetch_optional(&pool)
+            .await?
+            .map(|row| row.get::<i64, _>(0) as ReportId)
+            .unwrap_or_default();
+        Ok(Self {
+            pool,
+            cipher,
+            last_id: Mutex::new(last_id),
+        })
+    }
+
+    fn decode(&self, data: &str) -> Result<Report, Error> {
+        Ok(serde_json::from_str(&self.cipher.decode(data)?)?)
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl ReportStorage for DatabaseReportStorage {
+    async fn add_report(
+        &self,
+        reporter: UserAddress,
+        user: UserAddress,
+        reason: String,
+        created_at: u64,
+    ) -> Result<Report, Error> {
+        let mut last_id = self.last_id.lock().await;
+        let report = Report {
+            id: *last_id + 1,
+            reporter,
+            user,
+            reason,
+            created_at,
+            resolution: None,
+        };
+        let data = serde_json::to_string(&report)?;
+        sqlx::query("INSERT INTO reports (id, resolved, data) VALUES (?, 0, ?)")
+            .bind(report.id as i64)
+            .bind(self.cipher.encode(&data))
+            .execute(&self.pool)
+            .await?;
+        *last_id = report.id;
+        Ok(report)
+    }
+
+    async fn report(&self, id: ReportId) -> Result<Option<Report>, Error> {
+        let row = sqlx::query("SELECT data FROM reports WHERE id = ?")
+            .bind(id.min(i64::MAX as u64) as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|row| self.decode(&row.get::<String, _>(0)))
+            .transpose()
+    }
+
+    async fn open_reports(&self, after_id: ReportId, limit: usize) -> Result<Vec<Report>, Error> {
+        let rows = sqlx::query(
+            "SELECT data FROM reports WHERE resolved = 0 AND id > ? ORDER BY id LIMIT ?",
+        )
+        .bind(after_id.min(i64::MAX as u64) as i64)
+        .bind(limit.min(i64::MAX as usize) as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| self.decode(&row.get::<String, _>(0)))
+            .collect()
+    }
+
+    async fn resolve(&self, id: ReportId, resolution: Resolution) -> Result<bool, Error> {
+        let Some(report) = self.report(id).await? else {
+            return Ok(false);
+        };
+        let report = Report {
+            resolution: Some(resolution),
+            ..report
+        };
+        let data = serde_json::to_string(&report)?;
+        // only the first resolution updates the row
+        let updated =
+            sqlx::query("UPDATE reports SET resolved = 1, data = ? WHERE id = ? AND resolved = 0")
+                .bind(self.cipher.encode(&data))
+                .bind(id as i64)
+                .execute(&self.pool)
+                .await?;
+        Ok(updated.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::IdtAmount;
+    use crate::reports::ReportAction;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseReportStorage::new("sqlite::memory:").await.unwrap();
+        for i in 1..=3 {
+            let report = storage
+                .add_report("a".into(), format!("user{i}"), "spam".into(), i * 10)
+                .await
+                .unwrap();
+            assert_eq!(report.id, i);
+            assert_eq!(storage.report(i).await.unwrap(), Some(report));
+        }
+        assert_eq!(storage.report(4).await.unwrap(), None);
+        let resolution = Resolution {
+            moderator: "moderator".into(),
+            action: ReportAction::Punish {
+                amount: IdtAmount::new(100),
+                proof_id: 1,
+            },
+            resolved_at: 40,
+        };
+        assert!(storage.resolve(2, resolution.clone()).await.unwrap());
+        assert!(!storage.resolve(2, resolution.clone()).await.unwrap());
+        assert!(!storage.resolve(4, resolution.clone()).await.unwrap());
+        assert_eq!(
+            storage.report(2).await.unwrap().unwrap().resolution,
+            Some(resolution)
+        );
+        let open = storage.open_reports(0, 10).await.unwrap();
+        assert_eq!(open.iter().map(|r| r.id).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(storage.open_reports(1, 1).await.unwrap()[0].id, 3);
+    }
+}
@@ -0,0 +1,226 @@
+// Abuse reports submitted by users for moderators to triage.
+//
+// Any user can report another one with `POST /report/:user`, signing the reported user and
+// the reason. Open reports form a queue listed by `GET /reports`. A moderator resolves a
+// report with `POST /reports/:id/resolve`, either dismissing it or punishing the reported
+// user in the same request. Resolved reports are kept with the resolution.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    identity::{IdentityService, IdtAmount, ProofId, UserAddress, punish::punish},
+    reports::{error::Error, storage::ReportStorage},
+};
+
+pub mod db;
+pub mod error;
+pub mod storage;
+
+// longer reasons are rejected, details belong to the moderator conversation
+pub const MAX_REASON_LENGTH: usize = 1000;
+
+pub type ReportId = u64;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    // assigned by the storage, starts from 1
+    pub id: ReportId,
+    pub reporter: UserAddress,
+    pub user: UserAddress,
+    pub reason: String,
+    pub created_at: u64,
+    // None while the report is in the queue
+    pub resolution: Option<Resolution>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ReportAction {
+    Dismiss,
+    // the reported user is punished by the resolving moderator
+    Punish {
+        amount: IdtAmount,
+        proof_id: ProofId,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resolution {
+    pub moderator: UserAddress,
+    #[serde(flatten)]
+    pub action: ReportAction,
+    pub resolved_at: u64,
+}
+
+pub async fn submit(
+    storage: &dyn ReportStorage,
+    reporter: UserAddress,
+    user: UserAddress,
+    reason: String,
+    now: u64,
+) -> Result<Report, Error> {
+    if reporter == user {
+        return Err(Error::SelfReport);
+    }
+    if reason.trim().is_empty() || reason.len() > MAX_REASON_LENGTH {
+        return Err(Error::InvalidReason);
+    }
+    storage.add_report(reporter, user, reason, now).await
+}
+
+// applies the action and closes the report. The moderator privilege is checked by the
+// caller. Punishing again with the same proof id replaces the penalty, so a resolution
+// racing with another one cannot punish the user twice.
+pub async fn resolve(
+    service: &IdentityService,
+    storage: &dyn ReportStorage,
+    id: ReportId,
+    moderator: UserAddress,
+    action: ReportAction,
+) -> Result<Report, Error> {
+    let Some(report) = storage.report(id).await? else {
+        return Err(Error::UnknownReport(id));
+    };
+    if report.resolution.is_some() {
+        return Err(Error::AlreadyResolved(id));
+    }
+    if let ReportAction::Punish { amount, proof_id } = action {
+        punish(
+            service,
+            report.user.clone(),
+            moderator.clone(),
+            amount,
+            proof_id,
+        )
+        .await?;
+    }
+    let resolution = Resolution {
+        moderator,
+        action,
+        resolved_at: service.now(),
+    };
+    if !storage.resolve(id, resolution.clone()).await? {
+        return Err(Error::AlreadyResolved(id));
+    }
+    Ok(Report {
+        resolution: Some(resolution),
+        ..report
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identity::{
+            idt::balance,
+            proof::prove,
+            tests::{MODERATOR, PROOF_ID, START_TIMESTAMP, USER_A, service_with_mock_clock},
+        },
+        reports::storage::InMemoryReportStorage,
+    };
+
+    #[async_std::test]
+    async fn test_submit() {
+        let storage = InMemoryReportStorage::default();
+        let report = submit(
+            &storage,
+            "reporter".into(),
+            USER_A.into(),
+            "spam".into(),
+            START_TIMESTAMP,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.id, 1);
+        assert_eq!(report.resolution, None);
+        assert!(matches!(
+            submit(&storage, USER_A.into(), USER_A.into(), "spam".into(), 0).await,
+            Err(Error::SelfReport)
+        ));
+        for reason in [" ".to_string(), "a".repeat(MAX_REASON_LENGTH + 1)] {
+            assert!(matches!(
+                submit(&storage, "reporter".into(), USER_A.into(), reason, 0).await,
+                Err(Error::InvalidReason)
+            ));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_resolve() {
+        let (service, _clock) = service_with_mock_clock();
+        let storage = InMemoryReportStorage::default();
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        let dismissed = submit(&storage, "a".into(), USER_A.into(), "spam".into(), 0)
+            .await
+            .unwrap();
+        let punished = submit(&storage, "b".into(), USER_A.into(), "fake".into(), 0)
+            .await
+            .unwrap();
+
+        let report = resolve(
+            &service,
+            &storage,
+            dismissed.id,
+            MODERATOR.into(),
+            ReportAction::Dismiss,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            report.resolution,
+            Some(Resolution {
+                moderator: MODERATOR.into(),
+                action: ReportAction::Dismiss,
+                resolved_at: START_TIMESTAMP,
+            })
+        );
+        assert_eq!(
+            balance(&service, &USER_A.into()).await.unwrap(),
+            IdtAmount::new(1000)
+        );
+        assert!(matches!(
+            resolve(
+                &service,
+                &storage,
+                dismissed.id,
+                MODERATOR.into(),
+                ReportAction::Dismiss
+            )
+            .await,
+            Err(Error::AlreadyResolved(_))
+        ));
+
+        let action = ReportAction::Punish {
+            amount: IdtAmount::new(400),
+            proof_id: 7,
+        };
+        resolve(&service, &storage, punished.id, MODERATOR.into(), action)
+            .await
+            .unwrap();
+        assert_eq!(
+            balance(&service, &USER_A.into()).await.unwrap(),
+            IdtAmount::new(600)
+        );
+        assert!(storage.open_reports(0, 10).await.unwrap().is_empty());
+        assert!(matches!(
+            resolve(
+                &service,
+                &storage,
+                3,
+                MODERATOR.into(),
+                ReportAction::Dismiss
+            )
+            .await,
+            Err(Error::UnknownReport(3))
+        ));
+    }
+}
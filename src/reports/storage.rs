@@ -0,0 +1,123 @@
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::{
+    identity::UserAddress,
+    reports::{Report, ReportId, Resolution, error::Error},
+};
+
+#[async_trait]
+pub trait ReportStorage: Send + Sync {
+    // stores an open report with the next id
+    async fn add_report(
+        &self,
+        reporter: UserAddress,
+        user: UserAddress,
+        reason: String,
+        created_at: u64,
+    ) -> Result<Report, Error>;
+    async fn report(&self, id: ReportId) -> Result<Option<Report>, Error>;
+    // at most `limit` open reports after `after_id`, ordered by id
+    async fn open_reports(&self, after_id: ReportId, limit: usize) -> Result<Vec<Report>, Error>;
+    // returns false if the report is missing or already resolved
+    async fn resolve(&self, id: ReportId, resolution: Resolution) -> Result<bool, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryReportStorage {
+    // report with id `i` is stored at `i - 1`
+    reports: RwLock<Vec<Report>>,
+}
+
+#[async_trait]
+impl ReportStorage for InMemoryReportStorage {
+    async fn add_report(
+        &self,
+        reporter: UserAddress,
+        user: UserAddress,
+        reason: String,
+        created_at: u64,
+    ) -> Result<Report, Error> {
+        let mut reports = self.reports.write().await;
+        let report = Report {
+            id: reports.len() as ReportId + 1,
+            reporter,
+            user,
+            reason,
+            created_at,
+            resolution: None,
+        };
+        reports.push(report.clone());
+        Ok(report)
+    }
+
+    async fn report(&self, id: ReportId) -> Result<Option<Report>, Error> {
+        let reports = self.reports.read().await;
+        Ok(id
+            .checked_sub(1)
+            .and_then(|i| reports.get(i as usize))
+            .cloned())
+    }
+
+    async fn open_reports(&self, after_id: ReportId, limit: usize) -> Result<Vec<Report>, Error> {
+        Ok(self
+            .reports
+            .read()
+            .await
+            .iter()
+            .filter(|r| r.id > after_id && r.resolution.is_none())
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn resolve(&self, id: ReportId, resolution: Resolution) -> Result<bool, Error> {
+        let mut reports = self.reports.write().await;
+        let Some(report) = id.checked_sub(1).and_then(|i| reports.get_mut(i as usize)) else {
+            return Ok(false);
+        };
+        if report.resolution.is_some() {
+            return Ok(false);
+        }
+        report.resolution = Some(resolution);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reports::ReportAction;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryReportStorage::default();
+        assert_eq!(storage.report(0).await.unwrap(), None);
+        for i in 1..=3 {
+            let report = storage
+                .add_report("a".into(), format!("user{i}"), "spam".into(), i * 10)
+                .await
+                .unwrap();
+            assert_eq!(report.id, i);
+        }
+        let resolution = Resolution {
+            moderator: "moderator".into(),
+            action: ReportAction::Dismiss,
+            resolved_at: 40,
+        };
+        assert!(storage.resolve(2, resolution.clone()).await.unwrap());
+        assert!(!storage.resolve(2, resolution.clone()).await.unwrap());
+        assert!(!storage.resolve(4, resolution.clone()).await.unwrap());
+        assert_eq!(
+            storage.report(2).await.unwrap().unwrap().resolution,
+            Some(resolution)
+        );
+
+        let open = storage.open_reports(0, 10).await.unwrap();
+        assert_eq!(open.iter().map(|r| r.id).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(open[1].user, "user3");
+        assert_eq!(open[1].created_at, 30);
+        assert_eq!(storage.open_reports(1, 10).await.unwrap().len(), 1);
+        assert_eq!(storage.open_reports(0, 1).await.unwrap().len(), 1);
+    }
+}
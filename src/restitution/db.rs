@@ -0,0 +1,272 @@
+use async_trait::async_trait;
+use sqlx::{AnyConnection, Row};
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::{IdtAmount, UserAddress},
+    pools::{self, MeteredPool},
+    restitution::{
+        Appeal, AppealStatus,
+        error::Error,
+        storage::{Pool, RestitutionStorage, pro_rata_shares},
+    },
+};
+
+const POOL_NAME: &str = "restitution";
+
+pub struct DatabaseRestitutionStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabaseRestitutionStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect(POOL_NAME, url).await?;
+        for table in [
+            "restitution_contributions",
+            "restitution_given",
+            "restitution_draws",
+        ] {
+            sqlx::query(&format!(
+                "CREATE TABLE IF NOT EXISTS {table} (user TEXT PRIMARY KEY, amount INTEGER NOT NULL)"
+            ))
+            .execute(&pool)
+            .await?;
+            rotate_column(&pool, &cipher, table, "user").await?;
+        }
+        // appeals are stored as JSON, encrypted since they contain the reason
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS restitution_appeals (user TEXT PRIMARY KEY, data TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        for column in ["user", "data"] {
+            rotate_column(&pool, &cipher, "restitution_appeals", column).await?;
+        }
+        Ok(Self { pool, cipher })
+    }
+
+    async fn appeal_in(
+        &self,
+        conn: &mut AnyConnection,
+        user: &UserAddress,
+    ) -> Result<Option<Appeal>, Error> {
+        let row = sqlx::query("SELECT data FROM restitution_appeals WHERE user = ?")
+            .bind(self.cipher.encode(user))
+            .fetch_optional(&mut *conn)
+            .await?;
+        row.map(|row| {
+            let data = self.cipher.decode(&row.get::<String, _>(0))?;
+            Ok(serde_json::from_str(&data)?)
+        })
+        .transpose()
+    }
+
+    async fn amount(
+        &self,
+        conn: &mut AnyConnection,
+        table: &str,
+        user: &UserAddress,
+    ) -> Result<IdtAmount, Error> {
+        let row = sqlx::query(&format!("SELECT amount FROM {table} WHERE user = ?"))
+            .bind(self.cipher.encode(user))
+            .fetch_optional(&mut *conn)
+            .await?;
+        Ok(row
+            .map(|r| IdtAmount::from_milli(r.get::<i64, _>(0) as u64))
+            .unwrap_or_default())
+    }
+
+    async fn set_amount(
+        &self,
+        conn: &mut AnyConnection,
+        table: &str,
+        user: &UserAddress,
+        amount: IdtAmount,
+    ) -> Result<(), Error> {
+        if amount.is_zero() {
+            sqlx::query(&format!("DELETE FROM {table} WHERE user = ?"))
+                .bind(self.cipher.encode(user))
+                .execute(&mut *conn)
+                .await?;
+            return Ok(());
+        }
+        sqlx::query(&format!(
+            "REPLACE INTO {table} (user, amount) VALUES (?, ?)"
+        ))
+        .bind(self.cipher.encode(user))
+        .bind(amount.milli() as i64)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    async fn total(&self, conn: &mut AnyConnection, table: &str) -> Result<IdtAmount, Error> {
+        let rows = sqlx::query(&format!("SELECT amount FROM {table}"))
+            .fetch_all(&mut *conn)
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| IdtAmount::from_milli(row.get::<i64, _>(0) as u64))
+            .sum())
+    }
+
+    // drawn contributions count as contributed
+    async fn pool_in(&self, conn: &mut AnyConnection) -> Result<Pool, Error> {
+        Ok(Pool {
+            contributed: self.total(conn, "restitution_contributions").await?
+                + self.total(conn, "restitution_given").await?,
+            drawn: self.total(conn, "restitution_draws").await?,
+        })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl RestitutionStorage for DatabaseRestitutionStorage {
+    async fn contribute(&self, user: UserAddress, amount: IdtAmount) -> Result<IdtAmount, Error> {
+        let mut tx = self.pool.begin().await?;
+        let contribution = self
+            .amount(&mut tx, "restitution_contributions", &user)
+            .await?
+            + amount;
+        self.set_amount(&mut tx, "restitution_contributions", &user, contribution)
+            .await?;
+        tx.commit().await?;
+        Ok(contribution)
+    }
+
+    async fn withdraw(&self, user: &UserAddress, amount: IdtAmount) -> Result<bool, Error> {
+        let mut tx = self.pool.begin().await?;
+        let contribution = self
+            .amount(&mut tx, "restitution_contributions", user)
+            .await?;
+        if contribution < amount {
+            return Ok(false);
+        }
+        self.set_amount(
+            &mut tx,
+            "restitution_contributions",
+            user,
+            contribution - amount,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn draw(&self, user: UserAddress, amount: IdtAmount) -> Result<IdtAmount, Error> {
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query("SELECT user, amount FROM restitution_contributions")
+            .fetch_all(&mut *tx)
+            .await?;
+        let mut contributions = rows
+            .iter()
+            .map(|row| {
+                let contributor = self.cipher.decode(&row.get::<String, _>(0))?;
+                Ok((
+                    contributor,
+                    IdtAmount::from_milli(row.get::<i64, _>(1) as u64),
+                ))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        // sorted by the address, not by its ciphertext, like in the other storages
+        contributions.sort();
+        let amounts: Vec<_> = contributions.iter().map(|(_, c)| *c).collect();
+        let drawn = amount.min(amounts.iter().copied().sum());
+        if drawn.is_zero() {
+            return Ok(drawn);
+        }
+        for ((contributor, contribution), share) in
+            contributions.iter().zip(pro_rata_shares(&amounts, drawn))
+        {
+            if share.is_zero() {
+                continue;
+            }
+            self.set_amount(
+                &mut tx,
+                "restitution_contributions",
+                contributor,
+                *contribution - share,
+            )
+            .await?;
+            let given = self
+                .amount(&mut tx, "restitution_given", contributor)
+                .await?
+                + share;
+            self.set_amount(&mut tx, "restitution_given", contributor, given)
+                .await?;
+        }
+        let restitution = self.amount(&mut tx, "restitution_draws", &user).await? + drawn;
+        self.set_amount(&mut tx, "restitution_draws", &user, restitution)
+            .await?;
+        tx.commit().await?;
+        Ok(drawn)
+    }
+
+    async fn contribution(&self, user: &UserAddress) -> Result<IdtAmount, Error> {
+        let mut conn = self.pool.acquire().await?;
+        self.amount(&mut conn, "restitution_contributions", user)
+            .await
+    }
+
+    async fn given(&self, user: &UserAddress) -> Result<IdtAmount, Error> {
+        let mut conn = self.pool.acquire().await?;
+        self.amount(&mut conn, "restitution_given", user).await
+    }
+
+    async fn restitution(&self, user: &UserAddress) -> Result<IdtAmount, Error> {
+        let mut conn = self.pool.acquire().await?;
+        self.amount(&mut conn, "restitution_draws", user).await
+    }
+
+    async fn pool(&self) -> Result<Pool, Error> {
+        let mut conn = self.pool.acquire().await?;
+        self.pool_in(&mut conn).await
+    }
+
+    async fn appeal(&self, user: &UserAddress) -> Result<Option<Appeal>, Error> {
+        let mut conn = self.pool.acquire().await?;
+        self.appeal_in(&mut conn, user).await
+    }
+
+    async fn set_appeal(
+        &self,
+        appeal: Appeal,
+        expected: Option<AppealStatus>,
+    ) -> Result<bool, Error> {
+        let mut tx = self.pool.begin().await?;
+        let stored = self.appeal_in(&mut tx, &appeal.user).await?;
+        if stored.map(|a| a.status) != expected {
+            return Ok(false);
+        }
+        sqlx::query("REPLACE INTO restitution_appeals (user, data) VALUES (?, ?)")
+            .bind(self.cipher.encode(&appeal.user))
+            .bind(self.cipher.encode(&serde_json::to_string(&appeal)?))
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::restitution::storage::tests::check_storage;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseRestitutionStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        check_storage(&storage).await;
+    }
+}
@@ -0,0 +1,34 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Amount must be positive")]
+    ZeroAmount,
+    #[error("Contribution exceeds the balance of the user")]
+    InsufficientBalance,
+    #[error("Withdrawal exceeds the undrawn contribution of the user")]
+    InsufficientContribution,
+    #[error("User has no remaining moderator penalty")]
+    NoPenalty,
+    #[error("Restitution pool is empty")]
+    EmptyPool,
+    #[error("Appeal reason must not be empty or too long")]
+    InvalidReason,
+    #[error("User already has an open appeal")]
+    AppealOpen,
+    #[error("User has no appeal")]
+    NoAppeal,
+    #[error("Appeal is already decided")]
+    AppealDecided,
+    #[error("User has no approved appeal")]
+    NoApprovedAppeal,
+    #[error("Identity error: {0}")]
+    IdentityError(#[from] crate::identity::error::Error),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[error("Encryption error: {0}")]
+    EncryptionError(#[from] crate::encryption::error::Error),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[cfg(feature = "sled")]
+    #[error("Storage error: {0}")]
+    StorageError(#[from] crate::kv::error::Error),
+}
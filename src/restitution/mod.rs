@@ -0,0 +1,636 @@
+// Community restitution pool.
+//
+// With `restitution.enabled` users pledge part of their balance to a shared pool with signed
+// `POST /restitution/contribute` requests. Pledges are debited from the balance of the
+// contributor and can be taken back with `POST /restitution/withdraw` until they are drawn.
+// A punished user appeals with `POST /restitution/appeal` and an admin approves or rejects
+// the appeal. For an approved appeal an admin grants restitution with
+// `POST /restitution/:user/grant`: the grant is drawn from the pledges pro-rata and lowers the
+// remaining moderator penalty of the user by the drawn amount. Drawn pledges stay debited.
+
+use std::{collections::HashMap, sync::Arc};
+
+use async_std::sync::{Mutex, MutexGuardArc};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::RestitutionSection,
+    identity::{
+        IdentityService, IdtAmount, ModeratorProof, UserAddress,
+        debits::{Debits, NoDebits},
+        decay::{balance_after_decay, moderator_penalty_decay},
+        error::Error as IdentityError,
+        idt::balance,
+        penalty_reasons::PenaltyReasonPolicy,
+    },
+    restitution::{error::Error, storage::RestitutionStorage},
+};
+
+pub mod db;
+pub mod error;
+pub mod storage;
+
+pub const MAX_REASON_LENGTH: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppealStatus {
+    Pending,
+    Approved,
+    Rejected,
+    // restitution was granted for the approved appeal
+    Granted,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Appeal {
+    pub user: UserAddress,
+    pub reason: String,
+    pub status: AppealStatus,
+    pub created_at: u64,
+    // admin who approved or rejected the appeal and when
+    pub decided_by: Option<UserAddress>,
+    pub decided_at: Option<u64>,
+}
+
+impl Appeal {
+    // a user has at most one open appeal
+    pub fn is_open(&self) -> bool {
+        matches!(self.status, AppealStatus::Pending | AppealStatus::Approved)
+    }
+}
+
+// pledges are debited from the balance of the contributor, undrawn ones are locked in the
+// pool and drawn ones are given away
+pub struct PledgeDebits(pub Arc<dyn RestitutionStorage>);
+
+impl PledgeDebits {
+    async fn pledged(&self, user: &UserAddress) -> Result<IdtAmount, Error> {
+        Ok(self.0.contribution(user).await? + self.0.given(user).await?)
+    }
+}
+
+#[async_trait]
+impl Debits for PledgeDebits {
+    async fn debit(&self, user: &UserAddress) -> Result<IdtAmount, IdentityError> {
+        self.pledged(user)
+            .await
+            .map_err(|e| IdentityError::DebitError(e.to_string()))
+    }
+}
+
+// debits selected by the configuration, pledges are ignored while restitution is disabled
+pub fn debits(
+    config: &RestitutionSection,
+    storage: Arc<dyn RestitutionStorage>,
+) -> Arc<dyn Debits> {
+    match config.enabled {
+        true => Arc::new(PledgeDebits(storage)),
+        false => Arc::new(NoDebits),
+    }
+}
+
+// serializes the pledges of each contributor, so concurrent pledges cannot all pass the
+// balance check and together exceed the balance
+#[derive(Default)]
+pub struct PledgeLocks {
+    users: Mutex<HashMap<UserAddress, Arc<Mutex<()>>>>,
+}
+
+impl PledgeLocks {
+    async fn lock(&self, user: &UserAddress) -> PledgeGuard<'_> {
+        let lock = self
+            .users
+            .lock()
+            .await
+            .entry(user.clone())
+            .or_default()
+            .clone();
+        PledgeGuard {
+            _guard: lock.lock_arc().await,
+            locks: self,
+            user: user.clone(),
+        }
+    }
+}
+
+struct PledgeGuard<'a> {
+    _guard: MutexGuardArc<()>,
+    locks: &'a PledgeLocks,
+    user: UserAddress,
+}
+
+impl Drop for PledgeGuard<'_> {
+    fn drop(&mut self) {
+        // forgets the lock once no other pledge of the user holds or waits for it
+        if let Some(mut users) = self.locks.users.try_lock() {
+            let unused = users
+                .get(&self.user)
+                .is_some_and(|l| Arc::strong_count(l) <= 2);
+            if unused {
+                users.remove(&self.user);
+            }
+        }
+    }
+}
+
+// adds to the pledge of the user, returns the new pledge
+pub async fn contribute(
+    service: &IdentityService,
+    storage: &dyn RestitutionStorage,
+    locks: &PledgeLocks,
+    user: UserAddress,
+    amount: IdtAmount,
+) -> Result<IdtAmount, Error> {
+    if amount.is_zero() {
+        return Err(Error::ZeroAmount);
+    }
+    let _guard = locks.lock(&user).await;
+    // earlier pledges are already debited from the balance
+    if amount > balance(service, &user).await? {
+        return Err(Error::InsufficientBalance);
+    }
+    storage.contribute(user, amount).await
+}
+
+// takes back at most the undrawn pledge of the user
+pub async fn withdraw(
+    storage: &dyn RestitutionStorage,
+    user: &UserAddress,
+    amount: IdtAmount,
+) -> Result<(), Error> {
+    if amount.is_zero() {
+        return Err(Error::ZeroAmount);
+    }
+    if !storage.withdraw(user, amount).await? {
+        return Err(Error::InsufficientContribution);
+    }
+    Ok(())
+}
+
+// opens an appeal of a user with a remaining moderator penalty
+pub async fn appeal(
+    service: &IdentityService,
+    storage: &dyn RestitutionStorage,
+    user: UserAddress,
+    reason: String,
+) -> Result<Appeal, Error> {
+    if reason.trim().is_empty() || reason.len() > MAX_REASON_LENGTH {
+        return Err(Error::InvalidReason);
+    }
+    if remaining_penalty(service, &user).await?.is_zero() {
+        return Err(Error::NoPenalty);
+    }
+    let current = storage.appeal(&user).await?;
+    if current.as_ref().is_some_and(Appeal::is_open) {
+        return Err(Error::AppealOpen);
+    }
+    let appeal = Appeal {
+        user,
+        reason,
+        status: AppealStatus::Pending,
+        created_at: service.now(),
+        decided_by: None,
+        decided_at: None,
+    };
+    // another appeal was opened in the meantime
+    if !storage
+        .set_appeal(appeal.clone(), current.map(|a| a.status))
+        .await?
+    {
+        return Err(Error::AppealOpen);
+    }
+    Ok(appeal)
+}
+
+// approves or rejects the pending appeal of the user. The admin privilege is checked by the
+// caller.
+pub async fn decide_appeal(
+    service: &IdentityService,
+    storage: &dyn RestitutionStorage,
+    user: &UserAddress,
+    admin: UserAddress,
+    approved: bool,
+) -> Result<Appeal, Error> {
+    let Some(appeal) = storage.appeal(user).await? else {
+        return Err(Error::NoAppeal);
+    };
+    if appeal.status != AppealStatus::Pending {
+        return Err(Error::AppealDecided);
+    }
+    let decided = Appeal {
+        status: match approved {
+            true => AppealStatus::Approved,
+            false => AppealStatus::Rejected,
+        },
+        decided_by: Some(admin),
+        decided_at: Some(service.now()),
+        ..appeal
+    };
+    if !storage
+        .set_appeal(decided.clone(), Some(AppealStatus::Pending))
+        .await?
+    {
+        return Err(Error::AppealDecided);
+    }
+    Ok(decided)
+}
+
+// moderator penalty of the user after its reason weight and decay
+pub async fn remaining_penalty(
+    service: &IdentityService,
+    user: &UserAddress,
+) -> Result<IdtAmount, Error> {
+    let penalty = service
+        .weighted_moderator_penalty(user)
+        .await?
+        .unwrap_or_default();
+    let decay = moderator_penalty_decay(service, user).await?;
+    Ok(balance_after_decay(penalty, decay))
+}
+
+// stored penalties are scaled by the multiplier of their reason, so the stored amount is
+// lowered by the unscaled grant, rounded up
+fn unscaled(amount: IdtAmount, policy: Option<&PenaltyReasonPolicy>) -> IdtAmount {
+    let Some(multiplier) = policy
+        .map(|policy| &policy.multiplier)
+        .filter(|m| m.numerator() != 0 && m.denominator() != 0)
+    else {
+        return amount;
+    };
+    let milli = (amount.milli() as u128 * multiplier.denominator() as u128)
+        .div_ceil(multiplier.numerator() as u128);
+    IdtAmount::from_milli(milli.min(u64::MAX as u128) as u64)
+}
+
+// draws up to `amount` from the pool for the approved appeal of the user, at most the
+// remaining penalty of the user, and lowers the penalty by the drawn amount. The admin
+// privilege is checked by the caller. Returns the drawn amount.
+pub async fn grant(
+    service: &IdentityService,
+    storage: &dyn RestitutionStorage,
+    user: &UserAddress,
+    amount: IdtAmount,
+) -> Result<IdtAmount, Error> {
+    if amount.is_zero() {
+        return Err(Error::ZeroAmount);
+    }
+    let Some(penalty) = service.moderator_penalty(user).await? else {
+        return Err(Error::NoPenalty);
+    };
+    let remaining = remaining_penalty(service, user).await?;
+    if remaining.is_zero() {
+        return Err(Error::NoPenalty);
+    }
+    let Some(appeal) = storage
+        .appeal(user)
+        .await?
+        .filter(|a| a.status == AppealStatus::Approved)
+    else {
+        return Err(Error::NoApprovedAppeal);
+    };
+    // the appeal is claimed before drawing, so concurrent grants draw once
+    let granted = Appeal {
+        status: AppealStatus::Granted,
+        ..appeal.clone()
+    };
+    if !storage
+        .set_appeal(granted, Some(AppealStatus::Approved))
+        .await?
+    {
+        return Err(Error::NoApprovedAppeal);
+    }
+    let drawn = match storage.draw(user.clone(), amount.min(remaining)).await {
+        Ok(drawn) if !drawn.is_zero() => drawn,
+        result => {
+            // nothing was drawn, the appeal stays approved
+            storage
+                .set_appeal(appeal, Some(AppealStatus::Granted))
+                .await?;
+            result?;
+            return Err(Error::EmptyPool);
+        }
+    };
+    // the time of the penalty is kept, so it decays as before
+    let policy = service.penalty_reason_policy(user).await?;
+    let lowered = ModeratorProof {
+        amount: penalty
+            .amount
+            .saturating_sub(unscaled(drawn, policy.as_ref())),
+        ..penalty
+    };
+    service
+        .penalties
+        .set_moderator_penalty(user.clone(), lowered)
+        .await?;
+    Ok(drawn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identity::{
+            proof::prove,
+            punish::punish,
+            tests::{MODERATOR, PROOF_ID, USER_A},
+        },
+        numbers::Rational,
+        restitution::{db::DatabaseRestitutionStorage, storage::InMemoryRestitutionStorage},
+    };
+
+    const ADMIN: &str = "admin";
+
+    fn service_with_pledges() -> (IdentityService, Arc<InMemoryRestitutionStorage>) {
+        let storage = Arc::new(InMemoryRestitutionStorage::default());
+        let service = IdentityService {
+            debits: Arc::new(PledgeDebits(storage.clone())),
+            ..Default::default()
+        };
+        (service, storage)
+    }
+
+    #[async_std::test]
+    async fn test_contribute() {
+        let (service, storage) = service_with_pledges();
+        let storage = &*storage;
+        let locks = PledgeLocks::default();
+        let user = USER_A.to_string();
+        prove(
+            &service,
+            user.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            contribute(&service, storage, &locks, user.clone(), IdtAmount::new(60))
+                .await
+                .unwrap(),
+            IdtAmount::new(60)
+        );
+        // the pledge is debited from the balance
+        assert_eq!(balance(&service, &user).await.unwrap(), IdtAmount::new(40));
+        assert!(matches!(
+            contribute(&service, storage, &locks, user.clone(), IdtAmount::new(41)).await,
+            Err(Error::InsufficientBalance)
+        ));
+        assert!(matches!(
+            contribute(&service, storage, &locks, user.clone(), IdtAmount::ZERO).await,
+            Err(Error::ZeroAmount)
+        ));
+
+        withdraw(storage, &user, IdtAmount::new(60)).await.unwrap();
+        assert_eq!(balance(&service, &user).await.unwrap(), IdtAmount::new(100));
+        assert!(matches!(
+            withdraw(storage, &user, IdtAmount::new(1)).await,
+            Err(Error::InsufficientContribution)
+        ));
+    }
+
+    #[async_std::test]
+    async fn test_concurrent_contributions() {
+        // the database storage awaits its queries, so the two pledges interleave
+        let storage = Arc::new(
+            DatabaseRestitutionStorage::new("sqlite::memory:")
+                .await
+                .unwrap(),
+        );
+        let service = IdentityService {
+            debits: Arc::new(PledgeDebits(storage.clone())),
+            ..Default::default()
+        };
+        let storage = &*storage;
+        let locks = PledgeLocks::default();
+        let user = USER_A.to_string();
+        prove(
+            &service,
+            user.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+
+        // each pledge fits the balance, both together do not
+        let (first, second) = futures::join!(
+            contribute(&service, storage, &locks, user.clone(), IdtAmount::new(60)),
+            contribute(&service, storage, &locks, user.clone(), IdtAmount::new(60)),
+        );
+        assert_eq!(
+            [first.is_ok(), second.is_ok()]
+                .iter()
+                .filter(|ok| **ok)
+                .count(),
+            1
+        );
+        assert!(matches!(
+            first.err().or(second.err()),
+            Some(Error::InsufficientBalance)
+        ));
+        assert_eq!(
+            storage.contribution(&user).await.unwrap(),
+            IdtAmount::new(60)
+        );
+        assert_eq!(balance(&service, &user).await.unwrap(), IdtAmount::new(40));
+        assert!(locks.users.lock().await.is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_appeal() {
+        let (service, storage) = service_with_pledges();
+        let storage = &*storage;
+        let user = USER_A.to_string();
+        let reason = "the penalty was a mistake".to_string();
+        assert!(matches!(
+            appeal(&service, storage, user.clone(), reason.clone()).await,
+            Err(Error::NoPenalty)
+        ));
+        punish(
+            &service,
+            user.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(50),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        for invalid in [" ".to_string(), "a".repeat(MAX_REASON_LENGTH + 1)] {
+            assert!(matches!(
+                appeal(&service, storage, user.clone(), invalid).await,
+                Err(Error::InvalidReason)
+            ));
+        }
+        assert!(matches!(
+            decide_appeal(&service, storage, &user, ADMIN.into(), true).await,
+            Err(Error::NoAppeal)
+        ));
+
+        appeal(&service, storage, user.clone(), reason.clone())
+            .await
+            .unwrap();
+        assert!(matches!(
+            appeal(&service, storage, user.clone(), reason.clone()).await,
+            Err(Error::AppealOpen)
+        ));
+        let rejected = decide_appeal(&service, storage, &user, ADMIN.into(), false)
+            .await
+            .unwrap();
+        assert_eq!(rejected.status, AppealStatus::Rejected);
+        assert_eq!(rejected.decided_by.as_deref(), Some(ADMIN));
+        assert!(matches!(
+            decide_appeal(&service, storage, &user, ADMIN.into(), true).await,
+            Err(Error::AppealDecided)
+        ));
+
+        // a rejected appeal can be followed by a new one
+        appeal(&service, storage, user.clone(), reason)
+            .await
+            .unwrap();
+        decide_appeal(&service, storage, &user, ADMIN.into(), true)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.appeal(&user).await.unwrap().unwrap().status,
+            AppealStatus::Approved
+        );
+    }
+
+    #[async_std::test]
+    async fn test_grant() {
+        let (service, storage) = service_with_pledges();
+        let storage = &*storage;
+        let locks = PledgeLocks::default();
+        let user = USER_A.to_string();
+        let contributor = "contributor".to_string();
+        assert!(matches!(
+            grant(&service, storage, &user, IdtAmount::new(10)).await,
+            Err(Error::NoPenalty)
+        ));
+        punish(
+            &service,
+            user.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(50),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            grant(&service, storage, &user, IdtAmount::new(10)).await,
+            Err(Error::NoApprovedAppeal)
+        ));
+        appeal(&service, storage, user.clone(), "mistake".to_string())
+            .await
+            .unwrap();
+        assert!(matches!(
+            grant(&service, storage, &user, IdtAmount::new(10)).await,
+            Err(Error::NoApprovedAppeal)
+        ));
+        decide_appeal(&service, storage, &user, ADMIN.into(), true)
+            .await
+            .unwrap();
+        assert!(matches!(
+            grant(&service, storage, &user, IdtAmount::new(10)).await,
+            Err(Error::EmptyPool)
+        ));
+        // nothing was drawn, the appeal can still be granted
+        assert_eq!(
+            storage.appeal(&user).await.unwrap().unwrap().status,
+            AppealStatus::Approved
+        );
+
+        prove(
+            &service,
+            contributor.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        contribute(
+            &service,
+            storage,
+            &locks,
+            contributor.clone(),
+            IdtAmount::new(80),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            grant(&service, storage, &user, IdtAmount::new(20))
+                .await
+                .unwrap(),
+            IdtAmount::new(20)
+        );
+        assert_eq!(
+            remaining_penalty(&service, &user).await.unwrap(),
+            IdtAmount::new(30)
+        );
+        assert_eq!(
+            storage.appeal(&user).await.unwrap().unwrap().status,
+            AppealStatus::Granted
+        );
+        // every grant needs an approved appeal
+        assert!(matches!(
+            grant(&service, storage, &user, IdtAmount::new(10)).await,
+            Err(Error::NoApprovedAppeal)
+        ));
+        // the drawn pledge stays debited and can not be withdrawn
+        assert_eq!(
+            balance(&service, &contributor).await.unwrap(),
+            IdtAmount::new(20)
+        );
+        assert!(matches!(
+            withdraw(storage, &contributor, IdtAmount::new(61)).await,
+            Err(Error::InsufficientContribution)
+        ));
+
+        appeal(&service, storage, user.clone(), "mistake".to_string())
+            .await
+            .unwrap();
+        decide_appeal(&service, storage, &user, ADMIN.into(), true)
+            .await
+            .unwrap();
+        // at most the remaining penalty is drawn
+        assert_eq!(
+            grant(&service, storage, &user, IdtAmount::new(100))
+                .await
+                .unwrap(),
+            IdtAmount::new(30)
+        );
+        assert!(remaining_penalty(&service, &user).await.unwrap().is_zero());
+        assert_eq!(storage.pool().await.unwrap().balance(), IdtAmount::new(30));
+        assert_eq!(
+            storage.restitution(&user).await.unwrap(),
+            IdtAmount::new(50)
+        );
+        assert_eq!(
+            balance(&service, &contributor).await.unwrap(),
+            IdtAmount::new(20)
+        );
+    }
+
+    #[test]
+    fn test_unscaled() {
+        let policy = PenaltyReasonPolicy {
+            multiplier: Rational::new(3, 2).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            unscaled(IdtAmount::new(30), Some(&policy)),
+            IdtAmount::new(20)
+        );
+        assert_eq!(
+            unscaled(IdtAmount::from_milli(1), Some(&policy)),
+            IdtAmount::from_milli(1)
+        );
+        assert_eq!(unscaled(IdtAmount::new(30), None), IdtAmount::new(30));
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    identity::{IdtAmount, UserAddress},
+    restitution::{Appeal, AppealStatus, error::Error},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pool {
+    // contributions that were not withdrawn, drawn ones included
+    pub contributed: IdtAmount,
+    // granted to penalized users
+    pub drawn: IdtAmount,
+}
+
+impl Pool {
+    pub fn balance(&self) -> IdtAmount {
+        self.contributed.saturating_sub(self.drawn)
+    }
+}
+
+// splits `amount` over the contributions in proportion to them, the milli-IDT left by
+// rounding go to the first contributions that can take them. Shares never exceed their
+// contributions, the amount is capped by their sum.
+pub fn pro_rata_shares(contributions: &[IdtAmount], amount: IdtAmount) -> Vec<IdtAmount> {
+    let total: u128 = contributions.iter().map(|c| c.milli() as u128).sum();
+    let amount = (amount.milli() as u128).min(total);
+    let mut shares: Vec<u128> = contributions
+        .iter()
+        .map(|c| {
+            (c.milli() as u128 * amount)
+                .checked_div(total)
+                .unwrap_or_default()
+        })
+        .collect();
+    let mut remainder = amount - shares.iter().sum::<u128>();
+    for (share, contribution) in shares.iter_mut().zip(contributions) {
+        if remainder == 0 {
+            break;
+        }
+        if *share < contribution.milli() as u128 {
+            *share += 1;
+            remainder -= 1;
+        }
+    }
+    shares
+        .into_iter()
+        .map(|share| IdtAmount::from_milli(share as u64))
+        .collect()
+}
+
+// contributions to the restitution pool, the restitution drawn from it and the appeals of
+// penalized users
+#[async_trait]
+pub trait RestitutionStorage: Send + Sync {
+    // adds to the contribution of the user, returns the new contribution
+    async fn contribute(&self, user: UserAddress, amount: IdtAmount) -> Result<IdtAmount, Error>;
+    // false if the amount exceeds the undrawn contribution of the user
+    async fn withdraw(&self, user: &UserAddress, amount: IdtAmount) -> Result<bool, Error>;
+    // draws up to `amount` from the pool for the user, the contributions are lowered
+    // pro-rata. Returns the drawn amount.
+    async fn draw(&self, user: UserAddress, amount: IdtAmount) -> Result<IdtAmount, Error>;
+    // undrawn contribution of the user
+    async fn contribution(&self, user: &UserAddress) -> Result<IdtAmount, Error>;
+    // contributions of the user drawn so far
+    async fn given(&self, user: &UserAddress) -> Result<IdtAmount, Error>;
+    // restitution drawn for the user so far
+    async fn restitution(&self, user: &UserAddress) -> Result<IdtAmount, Error>;
+    async fn pool(&self) -> Result<Pool, Error>;
+    // latest appeal of the user
+    async fn appeal(&self, user: &UserAddress) -> Result<Option<Appeal>, Error>;
+    // replaces the appeal of the user if the stored one has the `expected` status, `None`
+    // if the user has no appeal. Returns false otherwise.
+    async fn set_appeal(
+        &self,
+        appeal: Appeal,
+        expected: Option<AppealStatus>,
+    ) -> Result<bool, Error>;
+}
+
+#[derive(Default)]
+struct Records {
+    contributions: HashMap<UserAddress, IdtAmount>,
+    given: HashMap<UserAddress, IdtAmount>,
+    draws: HashMap<UserAddress, IdtAmount>,
+    appeals: HashMap<UserAddress, Appeal>,
+    pool: Pool,
+}
+
+#[derive(Default)]
+pub struct InMemoryRestitutionStorage {
+    records: RwLock<Records>,
+}
+
+#[async_trait]
+impl RestitutionStorage for InMemoryRestitutionStorage {
+    async fn contribute(&self, user: UserAddress, amount: IdtAmount) -> Result<IdtAmount, Error> {
+        let mut records = self.records.write().await;
+        records.pool.contributed += amount;
+        let contribution = records.contributions.entry(user).or_default();
+        *contribution += amount;
+        Ok(*contribution)
+    }
+
+    async fn withdraw(&self, user: &UserAddress, amount: IdtAmount) -> Result<bool, Error> {
+        let mut records = self.records.write().await;
+        let Some(contribution) = records.contributions.get_mut(user) else {
+            return Ok(false);
+        };
+        if *contribution < amount {
+            return Ok(false);
+        }
+        *contribution -= amount;
+        if contribution.is_zero() {
+            records.contributions.remove(user);
+        }
+        records.pool.contributed -= amount;
+        Ok(true)
+    }
+
+    async fn draw(&self, user: UserAddress, amount: IdtAmount) -> Result<IdtAmount, Error> {
+        let mut records = self.records.write().await;
+        let drawn = amount.min(records.pool.balance());
+        if drawn.is_zero() {
+            return Ok(drawn);
+        }
+        let mut contributions: Vec<_> = records
+            .contributions
+            .iter()
+            .map(|(user, contribution)| (user.clone(), *contribution))
+            .collect();
+        contributions.sort();
+        let amounts: Vec<_> = contributions.iter().map(|(_, c)| *c).collect();
+        for ((contributor, contribution), share) in contributions
+            .into_iter()
+            .zip(pro_rata_shares(&amounts, drawn))
+        {
+            match contribution == share {
+                true => records.contributions.remove(&contributor),
+                false => records
+                    .contributions
+                    .insert(contributor.clone(), contribution - share),
+            };
+            if !share.is_zero() {
+                *records.given.entry(contributor).or_default() += share;
+            }
+        }
+        records.pool.drawn += drawn;
+        *records.draws.entry(user).or_default() += drawn;
+        Ok(drawn)
+    }
+
+    async fn contribution(&self, user: &UserAddress) -> Result<IdtAmount, Error> {
+        let records = self.records.read().await;
+        Ok(records.contributions.get(user).copied().unwrap_or_default())
+    }
+
+    async fn given(&self, user: &UserAddress) -> Result<IdtAmount, Error> {
+        let records = self.records.read().await;
+        Ok(records.given.get(user).copied().unwrap_or_default())
+    }
+
+    async fn restitution(&self, user: &UserAddress) -> Result<IdtAmount, Error> {
+        let records = self.records.read().await;
+        Ok(records.draws.get(user).copied().unwrap_or_default())
+    }
+
+    async fn pool(&self) -> Result<Pool, Error> {
+        Ok(self.records.read().await.pool)
+    }
+
+    async fn appeal(&self, user: &UserAddress) -> Result<Option<Appeal>, Error> {
+        Ok(self.records.read().await.appeals.get(user).cloned())
+    }
+
+    async fn set_appeal(
+        &self,
+        appeal: Appeal,
+        expected: Option<AppealStatus>,
+    ) -> Result<bool, Error> {
+        let mut records = self.records.write().await;
+        if records.appeals.get(&appeal.user).map(|a| a.status) != expected {
+            return Ok(false);
+        }
+        records.appeals.insert(appeal.user.clone(), appeal);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn pending_appeal(user: &str) -> Appeal {
+        Appeal {
+            user: user.to_string(),
+            reason: "the penalty was a mistake".to_string(),
+            status: AppealStatus::Pending,
+            created_at: 1,
+            decided_by: None,
+            decided_at: None,
+        }
+    }
+
+    // behavior shared by every storage
+    pub async fn check_storage(storage: &dyn RestitutionStorage) {
+        let (a, b, c) = ("a".to_string(), "b".to_string(), "c".to_string());
+        assert_eq!(storage.pool().await.unwrap(), Pool::default());
+        assert_eq!(
+            storage
+                .contribute(a.clone(), IdtAmount::new(10))
+                .await
+                .unwrap(),
+            IdtAmount::new(10)
+        );
+        assert_eq!(
+            storage
+                .contribute(a.clone(), IdtAmount::new(5))
+                .await
+                .unwrap(),
+            IdtAmount::new(15)
+        );
+        storage
+            .contribute(b.clone(), IdtAmount::new(5))
+            .await
+            .unwrap();
+        assert_eq!(storage.pool().await.unwrap().balance(), IdtAmount::new(20));
+
+        // the contributions are drawn in proportion to them
+        assert_eq!(
+            storage.draw(c.clone(), IdtAmount::new(8)).await.unwrap(),
+            IdtAmount::new(8)
+        );
+        assert_eq!(storage.restitution(&c).await.unwrap(), IdtAmount::new(8));
+        assert_eq!(storage.contribution(&a).await.unwrap(), IdtAmount::new(9));
+        assert_eq!(storage.given(&a).await.unwrap(), IdtAmount::new(6));
+        assert_eq!(storage.contribution(&b).await.unwrap(), IdtAmount::new(3));
+        assert_eq!(storage.given(&b).await.unwrap(), IdtAmount::new(2));
+
+        // only the undrawn share can be withdrawn
+        assert!(!storage.withdraw(&b, IdtAmount::new(4)).await.unwrap());
+        assert!(!storage.withdraw(&a, IdtAmount::new(10)).await.unwrap());
+        assert!(storage.withdraw(&a, IdtAmount::new(9)).await.unwrap());
+        assert!(storage.contribution(&a).await.unwrap().is_zero());
+        assert_eq!(
+            storage.pool().await.unwrap(),
+            Pool {
+                contributed: IdtAmount::new(11),
+                drawn: IdtAmount::new(8),
+            }
+        );
+
+        assert_eq!(
+            storage.draw(c.clone(), IdtAmount::new(5)).await.unwrap(),
+            IdtAmount::new(3)
+        );
+        assert!(storage.contribution(&b).await.unwrap().is_zero());
+        assert_eq!(storage.given(&b).await.unwrap(), IdtAmount::new(5));
+        // the pool is empty, nothing is drawn
+        assert_eq!(
+            storage.draw(c.clone(), IdtAmount::new(1)).await.unwrap(),
+            IdtAmount::ZERO
+        );
+        assert_eq!(storage.restitution(&c).await.unwrap(), IdtAmount::new(11));
+        assert!(!storage.withdraw(&b, IdtAmount::new(1)).await.unwrap());
+
+        assert_eq!(storage.appeal(&c).await.unwrap(), None);
+        let appeal = pending_appeal(&c);
+        assert!(storage.set_appeal(appeal.clone(), None).await.unwrap());
+        assert!(!storage.set_appeal(appeal.clone(), None).await.unwrap());
+        let approved = Appeal {
+            status: AppealStatus::Approved,
+            decided_by: Some(a.clone()),
+            decided_at: Some(2),
+            ..appeal
+        };
+        assert!(
+            !storage
+                .set_appeal(approved.clone(), Some(AppealStatus::Rejected))
+                .await
+                .unwrap()
+        );
+        assert!(
+            storage
+                .set_appeal(approved.clone(), Some(AppealStatus::Pending))
+                .await
+                .unwrap()
+        );
+        assert_eq!(storage.appeal(&c).await.unwrap(), Some(approved));
+    }
+
+    #[test]
+    fn test_pro_rata_shares() {
+        let shares = |contributions: &[u64], amount: u64| {
+            let contributions: Vec<_> = contributions
+                .iter()
+                .map(|c| IdtAmount::from_milli(*c))
+                .collect();
+            pro_rata_shares(&contributions, IdtAmount::from_milli(amount))
+                .into_iter()
+                .map(|share| share.milli())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(shares(&[2000, 1000], 1000), vec![667, 333]);
+        assert_eq!(shares(&[1, 1, 1], 2), vec![1, 1, 0]);
+        assert_eq!(shares(&[0, 5], 3), vec![0, 3]);
+        assert_eq!(shares(&[2, 3], 10), vec![2, 3]);
+        assert_eq!(shares(&[], 10), Vec::<u64>::new());
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        check_storage(&InMemoryRestitutionStorage::default()).await;
+    }
+}
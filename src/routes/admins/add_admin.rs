@@ -6,20 +6,31 @@ use tide::{Request, Response, http::mime};
 
 use crate::{
     identity::UserAddress,
-    routes::{State, verify_admin_action},
-    verify::{admins::admin_message_prefix, nonce::Nonce},
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        signed_body, verify_admin_action,
+    },
+    verify::{admins::admin_message_prefix, signature::Freshness},
 };
 
 #[derive(Deserialize)]
 struct AdminRequest {
     from: UserAddress,
     signature: String,
-    nonce: Nonce,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for AdminRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
 }
 
 pub async fn route(mut req: Request<State>) -> tide::Result {
     let recipient = req.param("user")?.to_string();
-    let body: AdminRequest = req.body_json().await?;
+    let body: AdminRequest = signed_body(&mut req).await?;
     let sender = body.from.clone();
     let message_prefix = admin_message_prefix(recipient.clone());
 
@@ -27,7 +38,7 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
         req.state(),
         &sender,
         body.signature,
-        body.nonce,
+        &body.freshness,
         &message_prefix,
     )
     .await
@@ -42,16 +53,13 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
         .await
         .is_err()
     {
-        return Ok(Response::builder(400)
-            .body(json!({"error": "failed to add admin"}))
-            .content_type(mime::JSON)
-            .build());
+        return Ok(ApiError::bad_request(ErrorCode::FailedToAddAdmin).into());
     }
 
     let response: HashMap<String, serde_json::Value> = HashMap::from([
         ("admin".into(), recipient.into()),
         ("from".into(), sender.into()),
-        ("nonce".into(), body.nonce.into()),
+        ("nonce".into(), body.freshness.nonce.into()),
     ]);
 
     let response = Response::builder(200)
@@ -68,7 +76,8 @@ mod tests {
 
     use crate::{
         admins::{AdminStorage, InMemoryAdminStorage},
-        verify::{random_keypair, sign_message},
+        config::{Config, SignaturesSection},
+        verify::{expires_in, random_keypair, sign_message, signature::generate},
     };
 
     use super::*;
@@ -90,14 +99,22 @@ mod tests {
 
         // sign the admin request
         let message_prefix = admin_message_prefix(new_admin.clone());
-        let signature = sign_message(&private_key, &message_prefix, &*state.nonce_manager)
-            .await
-            .expect("Should sign");
+        let signature = sign_message(
+            &private_key,
+            &state.server_identity.address,
+            &message_prefix,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
 
         let body = json!({
             "from": signature.signer,
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
         });
 
         let mut req = HttpRequest::new(
@@ -116,7 +133,7 @@ mod tests {
         let body: Value = response.body_json().await.unwrap();
         assert_eq!(body["admin"], new_admin.clone());
         assert_eq!(body["from"], admin_address);
-        assert_eq!(body["nonce"], signature.nonce);
+        assert_eq!(body["nonce"], signature.freshness.nonce);
 
         // verify the user is now an admin
         assert!(admin_storage.check_admin(&new_admin).await.is_ok());
@@ -124,9 +141,10 @@ mod tests {
 
     #[async_std::test]
     async fn test_no_privilege() {
-        let (private_key, _) = random_keypair();
+        let (private_key, moderator) = random_keypair();
         let admins = HashSet::from(["other_admin".to_string()]);
-        let admin_storage = Arc::new(InMemoryAdminStorage::new(admins, HashSet::new()));
+        let moderators = HashSet::from([moderator]);
+        let admin_storage = Arc::new(InMemoryAdminStorage::new(admins, moderators));
         let state = State {
             admin_storage: admin_storage.clone(),
             ..Default::default()
@@ -136,14 +154,22 @@ mod tests {
         let req_url = format!("/add_admin/{new_admin}");
 
         let message_prefix = admin_message_prefix(new_admin.clone());
-        let signature = sign_message(&private_key, &message_prefix, &*state.nonce_manager)
-            .await
-            .expect("Should sign");
+        let signature = sign_message(
+            &private_key,
+            &state.server_identity.address,
+            &message_prefix,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
 
         let body = json!({
             "from": signature.signer,
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
         });
 
         let mut req = HttpRequest::new(
@@ -156,8 +182,66 @@ mod tests {
         let mut server = tide::with_state(state);
         server.at("/add_admin/:user").post(route);
 
-        let response: Response = server.respond(req).await.unwrap();
+        let mut response: Response = server.respond(req).await.unwrap();
 
         assert_eq!(response.status(), 403);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "not admin");
+        assert_eq!(body["required_role"], "admin");
+        assert_eq!(body["roles"], json!(["moderator"]));
+        assert_eq!(body["message_prefix"], message_prefix);
+    }
+
+    #[async_std::test]
+    async fn test_legacy_signature() {
+        let (private_key, admin_address) = random_keypair();
+        let admins = HashSet::from([admin_address.clone()]);
+        let legacy_state = |allow_unbound_admin| State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(admins.clone(), HashSet::new())),
+            config: Arc::new(Config {
+                signatures: SignaturesSection {
+                    allow_legacy: true,
+                    allow_unbound_admin,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let add_admin = |state: State, message: String| {
+            let private_key = private_key.clone();
+            let admin_address = admin_address.clone();
+            async move {
+                let expires_at = expires_in(60);
+                let signature = generate(&private_key, format!("{message}/{expires_at}/1"))
+                    .await
+                    .unwrap();
+                let body = json!({
+                    "from": admin_address,
+                    "signature": signature,
+                    "nonce": 1,
+                    "expires_at": expires_at,
+                });
+                let mut req = HttpRequest::new(
+                    tide::http::Method::Post,
+                    Url::parse("http://example.com/add_admin/new_admin").unwrap(),
+                );
+                req.set_body(body);
+                req.set_content_type(mime::JSON);
+                let mut server = tide::with_state(state);
+                server.at("/add_admin/:user").post(route);
+                let response: Response = server.respond(req).await.unwrap();
+                response.status()
+            }
+        };
+
+        // signed for any server where the signer is an admin
+        let state = legacy_state(false);
+        let unbound = "admin/new_admin".to_string();
+        assert_eq!(add_admin(state.clone(), unbound.clone()).await, 400);
+        let bound = format!("{}/admin/new_admin", state.server_identity.address);
+        assert_eq!(add_admin(state, bound).await, 200);
+
+        assert_eq!(add_admin(legacy_state(true), unbound).await, 200);
     }
 }
@@ -6,20 +6,31 @@ use tide::{Request, Response, http::mime};
 
 use crate::{
     identity::UserAddress,
-    routes::{State, verify_admin_action},
-    verify::{admins::admin_set_moderator_message_prefix, nonce::Nonce},
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        signed_body, verify_admin_action,
+    },
+    verify::{admins::admin_set_moderator_message_prefix, signature::Freshness},
 };
 
 #[derive(Deserialize)]
 struct ModeratorRequest {
     from: UserAddress,
     signature: String,
-    nonce: Nonce,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for ModeratorRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
 }
 
 pub async fn route(mut req: Request<State>) -> tide::Result {
     let recipient = req.param("user")?.to_string();
-    let body: ModeratorRequest = req.body_json().await?;
+    let body: ModeratorRequest = signed_body(&mut req).await?;
     let sender = body.from.clone();
     let message_prefix = admin_set_moderator_message_prefix(recipient.clone());
 
@@ -27,7 +38,7 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
         req.state(),
         &sender,
         body.signature,
-        body.nonce,
+        &body.freshness,
         &message_prefix,
     )
     .await
@@ -42,16 +53,13 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
         .await
         .is_err()
     {
-        return Ok(Response::builder(400)
-            .body(json!({"error": "failed to add moderator"}))
-            .content_type(mime::JSON)
-            .build());
+        return Ok(ApiError::bad_request(ErrorCode::FailedToAddModerator).into());
     }
 
     let response: HashMap<String, serde_json::Value> = HashMap::from([
         ("moderator".into(), recipient.into()),
         ("from".into(), sender.into()),
-        ("nonce".into(), body.nonce.into()),
+        ("nonce".into(), body.freshness.nonce.into()),
     ]);
 
     let response = Response::builder(200)
@@ -68,7 +76,7 @@ mod tests {
 
     use crate::{
         admins::{AdminStorage, InMemoryAdminStorage},
-        verify::{random_keypair, sign_message},
+        verify::{expires_in, random_keypair, sign_message},
     };
 
     use super::*;
@@ -90,14 +98,22 @@ mod tests {
 
         // sign the moderator request
         let message_prefix = admin_set_moderator_message_prefix(new_moderator.clone());
-        let signature = sign_message(&private_key, &message_prefix, &*state.nonce_manager)
-            .await
-            .expect("Should sign");
+        let signature = sign_message(
+            &private_key,
+            &state.server_identity.address,
+            &message_prefix,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
 
         let body = json!({
             "from": signature.signer,
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
         });
 
         let mut req = HttpRequest::new(
@@ -116,7 +132,7 @@ mod tests {
         let body: Value = response.body_json().await.unwrap();
         assert_eq!(body["moderator"], new_moderator.clone());
         assert_eq!(body["from"], admin_address);
-        assert_eq!(body["nonce"], signature.nonce);
+        assert_eq!(body["nonce"], signature.freshness.nonce);
 
         // verify the user is now a moderator
         assert!(admin_storage.check_moderator(&new_moderator).await.is_ok());
@@ -136,14 +152,22 @@ mod tests {
         let req_url = format!("/add_moderator/{new_moderator}");
 
         let message_prefix = admin_set_moderator_message_prefix(new_moderator.clone());
-        let signature = sign_message(&private_key, &message_prefix, &*state.nonce_manager)
-            .await
-            .expect("Should sign");
+        let signature = sign_message(
+            &private_key,
+            &state.server_identity.address,
+            &message_prefix,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
 
         let body = json!({
             "from": signature.signer,
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
         });
 
         let mut req = HttpRequest::new(
@@ -0,0 +1,157 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::UserAddress,
+    integrity::check_integrity,
+    routes::{SignedRequest, State, signed_body, verify_admin_action},
+    verify::{admins::admin_check_integrity_message_prefix, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct CheckRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+    #[serde(default)]
+    repair: bool,
+}
+
+impl SignedRequest for CheckRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// reports inconsistencies of the stored data, and fixes them if `repair` is set
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let body: CheckRequest = signed_body(&mut req).await?;
+    let sender = body.from.clone();
+    let message_prefix = admin_check_integrity_message_prefix(body.repair);
+
+    if let Err(response) = verify_admin_action(
+        req.state(),
+        &sender,
+        body.signature,
+        &body.freshness,
+        &message_prefix,
+    )
+    .await
+    {
+        return Ok(response);
+    }
+
+    let state = req.state();
+    let report =
+        check_integrity(&state.identity_service, &*state.server_storage, body.repair).await?;
+    if report.repaired > 0 {
+        log::warn!("Repaired {} inconsistencies by {}", report.repaired, sender);
+    }
+
+    let response = Response::builder(200)
+        .body(json!({
+            "checked_at": report.checked_at,
+            "inconsistencies": report.inconsistencies,
+            "repaired": report.repaired,
+            "from": sender,
+            "nonce": body.freshness.nonce,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use super::*;
+    use crate::{
+        admins::InMemoryAdminStorage,
+        identity::{
+            IdtAmount,
+            punish::punish,
+            tests::{MODERATOR, PROOF_ID},
+        },
+        verify::{expires_in, random_keypair, sign_message},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn check(state: &State, private_key: &str, repair: bool) -> Response {
+        let signature = sign_message(
+            private_key,
+            &state.server_identity.address,
+            &admin_check_integrity_message_prefix(repair),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        let body = json!({
+            "from": signature.signer,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+            "repair": repair,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse("http://example.com/admin/check_integrity").unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/admin/check_integrity").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, admin) = random_keypair();
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::from([admin.clone()]),
+                HashSet::new(),
+            )),
+            ..Default::default()
+        };
+        punish(
+            &state.identity_service,
+            "stranger".into(),
+            MODERATOR.to_string(),
+            IdtAmount::new(10),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+
+        let mut response = check(&state, &private_key, false).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(
+            body["inconsistencies"],
+            json!([{"kind": "dangling_moderator_penalty", "user": "stranger"}])
+        );
+        assert_eq!(body["repaired"], 0);
+        assert_eq!(body["from"], admin);
+
+        let mut response = check(&state, &private_key, true).await;
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["repaired"], 1);
+        let mut response = check(&state, &private_key, false).await;
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["inconsistencies"], json!([]));
+    }
+
+    #[async_std::test]
+    async fn test_no_privilege() {
+        let (private_key, _) = random_keypair();
+        let state = State::default();
+        let response = check(&state, &private_key, true).await;
+        assert_eq!(response.status(), 403);
+    }
+}
@@ -1,6 +1,11 @@
 pub mod add_admin;
 pub mod add_moderator;
+pub mod check_integrity;
 pub mod is_admin;
 pub mod is_moderator;
 pub mod remove_admin;
 pub mod remove_moderator;
+pub mod restore_user;
+pub mod revoke_moderator_proofs;
+pub mod set_proof_limit;
+pub mod set_successor;
@@ -0,0 +1,164 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    archive::restore_user,
+    identity::UserAddress,
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        signed_body, verify_admin_action,
+    },
+    verify::{admins::admin_restore_user_message_prefix, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct RestoreRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for RestoreRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// moves an archived user back to the trust graph
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let body: RestoreRequest = signed_body(&mut req).await?;
+    let sender = body.from.clone();
+    let message_prefix = admin_restore_user_message_prefix(user.clone());
+
+    if let Err(response) = verify_admin_action(
+        req.state(),
+        &sender,
+        body.signature,
+        &body.freshness,
+        &message_prefix,
+    )
+    .await
+    {
+        return Ok(response);
+    }
+
+    let state = req.state();
+    let record = match restore_user(&state.identity_service, &*state.archive_storage, &user).await {
+        Ok(Some(record)) => record,
+        Ok(None) => {
+            return Ok(ApiError::new(404, ErrorCode::UserNotArchived).into());
+        }
+        Err(e) => {
+            log::error!("Failed to restore {}: {:?}", user, e);
+            return Ok(ApiError::bad_request(ErrorCode::FailedToRestoreUser).into());
+        }
+    };
+
+    let response = Response::builder(200)
+        .body(json!({
+            "restored": user,
+            "archived_at": record.archived_at,
+            "from": sender,
+            "nonce": body.freshness.nonce,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use super::*;
+    use crate::{
+        admins::InMemoryAdminStorage,
+        archive::archive_user,
+        identity::{
+            IdtAmount,
+            tests::{MODERATOR, PROOF_ID, USER_A},
+        },
+        verify::{expires_in, random_keypair, sign_message},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn restore(state: &State, private_key: &str, user: &str) -> Response {
+        let signature = sign_message(
+            private_key,
+            &state.server_identity.address,
+            &admin_restore_user_message_prefix(user.to_string()),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        let body = json!({
+            "from": signature.signer,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/restore_user/{user}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/restore_user/:user").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, admin) = random_keypair();
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::from([admin.clone()]),
+                HashSet::new(),
+            )),
+            ..Default::default()
+        };
+        let service = &state.identity_service;
+        let user = USER_A.to_string();
+
+        let response = restore(&state, &private_key, USER_A).await;
+        assert_eq!(response.status(), 404);
+
+        service
+            .prove_with_timestamp(
+                user.clone(),
+                MODERATOR.to_string(),
+                IdtAmount::new(5),
+                PROOF_ID,
+                1,
+            )
+            .await
+            .unwrap();
+        archive_user(service, &*state.archive_storage, &user)
+            .await
+            .unwrap();
+        assert!(service.proof(&user).await.unwrap().is_none());
+
+        let mut response = restore(&state, &private_key, USER_A).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["restored"], USER_A);
+        assert_eq!(body["from"], admin);
+        assert!(service.proof(&user).await.unwrap().is_some());
+    }
+
+    #[async_std::test]
+    async fn test_no_privilege() {
+        let (private_key, _) = random_keypair();
+        let state = State::default();
+        let response = restore(&state, &private_key, USER_A).await;
+        assert_eq!(response.status(), 403);
+    }
+}
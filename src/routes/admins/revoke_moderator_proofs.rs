@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::{UserAddress, idt::balance},
+    personhood::system_registry,
+    routes::{SignedRequest, State, signed_body, verify_admin_action},
+    verify::{admins::admin_revoke_moderator_proofs_message_prefix, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct RevokeRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for RevokeRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// invalidates all proofs issued by a compromised moderator
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let moderator = req.param("moderator")?.to_string();
+    let body: RevokeRequest = signed_body(&mut req).await?;
+    let sender = body.from.clone();
+    let message_prefix = admin_revoke_moderator_proofs_message_prefix(moderator.clone());
+
+    if let Err(response) = verify_admin_action(
+        req.state(),
+        &sender,
+        body.signature,
+        &body.freshness,
+        &message_prefix,
+    )
+    .await
+    {
+        return Ok(response);
+    }
+
+    let service = &req.state().identity_service;
+    let revoked = match system_registry(&moderator) {
+        Some(_) => service.revoke_system_proofs(&moderator).await?,
+        None => service.revoke_moderator_proofs(&moderator).await?,
+    };
+    log::warn!(
+        "Revoked {} proofs of moderator {} by {}",
+        revoked.len(),
+        moderator,
+        sender
+    );
+    let mut balances = BTreeMap::new();
+    for user in &revoked {
+        balances.insert(user.clone(), balance(service, user).await?.to_string());
+    }
+
+    let response = Response::builder(200)
+        .body(json!({
+            "moderator": moderator,
+            "revoked": revoked,
+            "balances": balances,
+            "from": sender,
+            "nonce": body.freshness.nonce,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use super::*;
+    use crate::{
+        admins::InMemoryAdminStorage,
+        identity::{
+            IdtAmount,
+            proof::prove,
+            tests::{MODERATOR, PROOF_ID, USER_A},
+            vouch::vouch,
+        },
+        verify::{expires_in, random_keypair, sign_message},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn revoke(state: &State, private_key: &str, moderator: &str) -> Response {
+        let signature = sign_message(
+            private_key,
+            &state.server_identity.address,
+            &admin_revoke_moderator_proofs_message_prefix(moderator.to_string()),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        let body = json!({
+            "from": signature.signer,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!(
+                "http://example.com/revoke_moderator_proofs/{moderator}"
+            ))
+            .unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/revoke_moderator_proofs/:moderator").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, admin) = random_keypair();
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::from([admin.clone()]),
+                HashSet::new(),
+            )),
+            ..Default::default()
+        };
+        let service = &state.identity_service;
+        let (user_b, user_c) = ("userB".to_string(), "userC".to_string());
+        prove(
+            service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        prove(
+            service,
+            user_b.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(200),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        prove(
+            service,
+            user_c.clone(),
+            "other".to_string(),
+            IdtAmount::new(300),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(service, user_c.clone(), USER_A.to_string())
+            .await
+            .unwrap();
+
+        let mut response = revoke(&state, &private_key, MODERATOR).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["moderator"], MODERATOR);
+        assert_eq!(body["revoked"], json!([USER_A, user_b]));
+        // only the vouch of the other proven user is left
+        assert_eq!(body["balances"][USER_A], "30");
+        assert_eq!(body["balances"][&user_b], "0");
+        assert_eq!(body["from"], admin);
+
+        assert!(service.proof(&user_b).await.unwrap().is_none());
+        assert_eq!(
+            service
+                .revoked_proof(&user_b)
+                .await
+                .unwrap()
+                .unwrap()
+                .amount,
+            IdtAmount::new(200)
+        );
+        assert!(service.proof(&user_c).await.unwrap().is_some());
+
+        let mut response = revoke(&state, &private_key, MODERATOR).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["revoked"], json!([]));
+    }
+
+    #[async_std::test]
+    async fn test_no_privilege() {
+        let (private_key, _) = random_keypair();
+        let state = State::default();
+        let response = revoke(&state, &private_key, MODERATOR).await;
+        assert_eq!(response.status(), 403);
+    }
+}
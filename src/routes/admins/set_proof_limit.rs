@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::{IdtAmount, UserAddress},
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        signed_body, verify_admin_action,
+    },
+    verify::{admins::admin_set_proof_limit_message_prefix, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct ProofLimitRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+    // maximum proof amount of the moderator, missing value removes the limit
+    #[serde(default)]
+    limit: Option<IdtAmount>,
+}
+
+impl SignedRequest for ProofLimitRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// limits the proofs of a moderator below `MAX_IDT_BY_PROOF`
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let moderator = req.param("user")?.to_string();
+    let body: ProofLimitRequest = signed_body(&mut req).await?;
+    let sender = body.from.clone();
+    let message_prefix = admin_set_proof_limit_message_prefix(moderator.clone(), body.limit);
+
+    if let Err(response) = verify_admin_action(
+        req.state(),
+        &sender,
+        body.signature,
+        &body.freshness,
+        &message_prefix,
+    )
+    .await
+    {
+        return Ok(response);
+    }
+
+    let state = req.state();
+    if body.limit.is_some()
+        && state
+            .admin_storage
+            .check_moderator(&moderator)
+            .await
+            .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::NotModerator).into());
+    }
+    state
+        .identity_service
+        .set_proof_limit(&moderator, body.limit)
+        .await?;
+
+    let response: HashMap<String, serde_json::Value> = HashMap::from([
+        ("moderator".into(), moderator.into()),
+        ("limit".into(), json!(body.limit)),
+        ("from".into(), sender.into()),
+        ("nonce".into(), body.freshness.nonce.into()),
+    ]);
+    let response = Response::builder(200)
+        .body(json!(response))
+        .content_type(mime::JSON)
+        .build();
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use super::*;
+    use crate::{
+        admins::InMemoryAdminStorage,
+        verify::{expires_in, random_keypair, sign_message},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn set_limit(
+        state: &State,
+        admin_priv: &str,
+        moderator: &str,
+        limit: Option<IdtAmount>,
+    ) -> Response {
+        let message_prefix = admin_set_proof_limit_message_prefix(moderator.to_string(), limit);
+        let signature = sign_message(
+            admin_priv,
+            &state.server_identity.address,
+            &message_prefix,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        let body = json!({
+            "from": signature.signer,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+            "limit": limit,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/moderators/{moderator}/limit")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/moderators/:user/limit").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (admin_priv, admin_addr) = random_keypair();
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::from([admin_addr]),
+                HashSet::from(["moderator".to_string()]),
+            )),
+            ..Default::default()
+        };
+
+        let mut response = set_limit(&state, &admin_priv, "user", Some(IdtAmount::new(100))).await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "user is not a moderator");
+
+        let mut response =
+            set_limit(&state, &admin_priv, "moderator", Some(IdtAmount::new(100))).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["limit"], 100);
+        let moderator = "moderator".to_string();
+        assert_eq!(
+            state
+                .identity_service
+                .proof_limit(&moderator)
+                .await
+                .unwrap(),
+            Some(IdtAmount::new(100))
+        );
+
+        let response = set_limit(&state, &admin_priv, "moderator", None).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            state
+                .identity_service
+                .proof_limit(&moderator)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[async_std::test]
+    async fn test_not_admin() {
+        let (priv_key, _) = random_keypair();
+        let state = State::default();
+        let response = set_limit(&state, &priv_key, "moderator", None).await;
+        assert_eq!(response.status(), 403);
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::UserAddress,
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        signed_body, verify_admin_action,
+    },
+    verify::{admins::admin_set_successor_message_prefix, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct SuccessorRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+    // moderator taking over the proofs, missing value removes the successor
+    #[serde(default)]
+    successor: Option<UserAddress>,
+}
+
+impl SignedRequest for SuccessorRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// designates a successor for a retiring moderator, see `proof::transfer_route`
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let moderator = req.param("moderator")?.to_string();
+    let body: SuccessorRequest = signed_body(&mut req).await?;
+    let sender = body.from.clone();
+    let message_prefix =
+        admin_set_successor_message_prefix(moderator.clone(), body.successor.clone());
+
+    if let Err(response) = verify_admin_action(
+        req.state(),
+        &sender,
+        body.signature,
+        &body.freshness,
+        &message_prefix,
+    )
+    .await
+    {
+        return Ok(response);
+    }
+
+    let admin_storage = &req.state().admin_storage;
+    if let Some(successor) = &body.successor {
+        if successor == &moderator || admin_storage.check_moderator(successor).await.is_err() {
+            return Ok(ApiError::bad_request(ErrorCode::SuccessorNotModerator).into());
+        }
+    }
+    if admin_storage
+        .set_successor(&sender, moderator.clone(), body.successor.clone())
+        .await
+        .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::FailedToSetSuccessor).into());
+    }
+
+    let response: HashMap<String, serde_json::Value> = HashMap::from([
+        ("moderator".into(), moderator.into()),
+        ("successor".into(), body.successor.into()),
+        ("from".into(), sender.into()),
+        ("nonce".into(), body.freshness.nonce.into()),
+    ]);
+    let response = Response::builder(200)
+        .body(json!(response))
+        .content_type(mime::JSON)
+        .build();
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use super::*;
+    use crate::{
+        admins::InMemoryAdminStorage,
+        verify::{expires_in, random_keypair, sign_message},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn set_successor(
+        state: &State,
+        admin_priv: &str,
+        moderator: &str,
+        successor: Option<&str>,
+    ) -> Response {
+        let message_prefix =
+            admin_set_successor_message_prefix(moderator.to_string(), successor.map(String::from));
+        let signature = sign_message(
+            admin_priv,
+            &state.server_identity.address,
+            &message_prefix,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        let body = json!({
+            "from": signature.signer,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+            "successor": successor,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/set_successor/{moderator}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/set_successor/:moderator").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (admin_priv, admin_addr) = random_keypair();
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::from([admin_addr]),
+                HashSet::from(["moderator".to_string(), "successor".to_string()]),
+            )),
+            ..Default::default()
+        };
+
+        let mut response = set_successor(&state, &admin_priv, "moderator", Some("user")).await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "successor is not a moderator");
+
+        let mut response = set_successor(&state, &admin_priv, "moderator", Some("successor")).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["successor"], "successor");
+        assert_eq!(
+            state
+                .admin_storage
+                .successor(&"moderator".to_string())
+                .await
+                .unwrap(),
+            Some("successor".to_string())
+        );
+
+        let response = set_successor(&state, &admin_priv, "moderator", None).await;
+        assert_eq!(response.status(), 200);
+        assert!(
+            state
+                .admin_storage
+                .successor(&"moderator".to_string())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_not_admin() {
+        let (priv_key, _) = random_keypair();
+        let state = State::default();
+        let response = set_successor(&state, &priv_key, "moderator", None).await;
+        assert_eq!(response.status(), 403);
+    }
+}
@@ -0,0 +1,164 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    attestations::{Claim, complete},
+    identity::UserAddress,
+    routes::{
+        State,
+        attestations::error_response,
+        error::{ApiError, ErrorCode},
+        naming::json_body,
+    },
+    verify::attestation::claim_verify,
+};
+
+#[derive(Deserialize)]
+struct CompleteRequest {
+    provider: String,
+    account: String,
+    challenge: String,
+    verifier: UserAddress,
+    // signature of the verifier over the claim
+    signature: String,
+}
+
+// links the account attested by a trusted verifier and proves the user
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let body: CompleteRequest = json_body(&mut req).await?;
+    let state = req.state();
+    let claim = Claim {
+        user,
+        provider: body.provider,
+        account: body.account,
+        challenge: body.challenge,
+        verifier: body.verifier,
+    };
+
+    if claim_verify(&body.signature, &state.server_identity.address, &claim).is_err() {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    let attestation = match complete(
+        &state.identity_service,
+        &*state.attestations,
+        &state.config.attestations,
+        claim,
+    )
+    .await
+    {
+        Ok(attestation) => attestation,
+        Err(e) => return error_response(e),
+    };
+
+    let response = Response::builder(200)
+        .body(json!({
+            "user": attestation.user,
+            "provider": attestation.provider,
+            "account": attestation.account,
+            "verifier": attestation.verifier,
+            "proof_id": attestation.proof_id.to_string(),
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        identity::IdtAmount,
+        routes::attestations::start::tests::{github_state, start_attestation},
+        verify::{attestation::claim_sign, random_keypair},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn complete_attestation(state: &State, verifier_key: &str, claim: &Claim) -> Response {
+        let signature = claim_sign(verifier_key, &state.server_identity.address, claim)
+            .await
+            .expect("Should sign");
+        let body = json!({
+            "provider": claim.provider,
+            "account": claim.account,
+            "challenge": claim.challenge,
+            "verifier": claim.verifier,
+            "signature": signature,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!(
+                "http://example.com/attestations/complete/{}",
+                claim.user
+            ))
+            .unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/attestations/complete/:user").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (verifier_key, verifier) = random_keypair();
+        let mut state = github_state();
+        let mut config = Config::clone(&state.config);
+        config.attestations.verifiers.insert(verifier.clone());
+        state.config = Arc::new(config);
+        let (user_key, user) = random_keypair();
+
+        let mut response = start_attestation(&state, &user_key, &user, "github").await;
+        let body: Value = response.body_json().await.unwrap();
+        let claim = Claim {
+            user: user.clone(),
+            provider: "github".to_string(),
+            account: "alice".to_string(),
+            challenge: body["challenge"].as_str().unwrap().to_string(),
+            verifier: verifier.clone(),
+        };
+
+        // signed by someone else than the verifier
+        let (other_key, _) = random_keypair();
+        let response = complete_attestation(&state, &other_key, &claim).await;
+        assert_eq!(response.status(), 400);
+
+        let mut response = complete_attestation(&state, &verifier_key, &claim).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["account"], "alice");
+        let proof = state.identity_service.proof(&user).await.unwrap().unwrap();
+        assert_eq!(proof.moderator, verifier);
+        assert_eq!(proof.amount, IdtAmount::new(100));
+
+        // challenge is already used
+        let response = complete_attestation(&state, &verifier_key, &claim).await;
+        assert_eq!(response.status(), 400);
+    }
+
+    #[async_std::test]
+    async fn test_untrusted_verifier() {
+        let state = github_state();
+        let (verifier_key, verifier) = random_keypair();
+        let (user_key, user) = random_keypair();
+        let mut response = start_attestation(&state, &user_key, &user, "github").await;
+        let body: Value = response.body_json().await.unwrap();
+        let claim = Claim {
+            user: user.clone(),
+            provider: "github".to_string(),
+            account: "alice".to_string(),
+            challenge: body["challenge"].as_str().unwrap().to_string(),
+            verifier,
+        };
+        let response = complete_attestation(&state, &verifier_key, &claim).await;
+        assert_eq!(response.status(), 403);
+        assert!(state.identity_service.proof(&user).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,52 @@
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::routes::State;
+
+// external accounts linked to the user
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let attestations = req.state().attestations.attestations(&user).await?;
+    let response = Response::builder(200)
+        .body(json!(attestations))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{attestations::Attestation, identity::tests::USER_A};
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        state
+            .attestations
+            .link(Attestation {
+                user: USER_A.to_string(),
+                provider: "github".to_string(),
+                account: "alice".to_string(),
+                verifier: "verifier".to_string(),
+                proof_id: 1,
+                attested_at: 5,
+            })
+            .await
+            .unwrap();
+
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/attestations/{USER_A}")).unwrap(),
+        );
+        let mut server = tide::with_state(state);
+        server.at("/attestations/:user").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body[0]["account"], "alice");
+        assert_eq!(body[0]["provider"], "github");
+    }
+}
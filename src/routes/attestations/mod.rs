@@ -0,0 +1,19 @@
+use crate::{
+    attestations::error::Error,
+    routes::error::{ApiError, ErrorCode},
+};
+
+pub mod complete;
+pub mod get_attestations;
+pub mod start;
+
+// client errors are reported, storage errors fail the request
+fn error_response(error: Error) -> tide::Result {
+    match error {
+        Error::UnknownProvider(_) => Ok(ApiError::bad_request(ErrorCode::UnknownProvider).into()),
+        Error::UnknownVerifier => Ok(ApiError::new(403, ErrorCode::NotVerifier).into()),
+        Error::InvalidChallenge => Ok(ApiError::bad_request(ErrorCode::InvalidChallenge).into()),
+        Error::AccountLinked => Ok(ApiError::new(409, ErrorCode::AccountLinked).into()),
+        e => Err(e.into()),
+    }
+}
@@ -0,0 +1,158 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    attestations::start,
+    routes::{
+        SignedRequest, State,
+        attestations::error_response,
+        error::{ApiError, ErrorCode},
+        freshness_error, signed_body,
+    },
+    verify::{attestation::attestation_start_verify, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct StartRequest {
+    provider: String,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for StartRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// issues a challenge the user passes to the verifier, signed by the user
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let body: StartRequest = signed_body(&mut req).await?;
+    let state = req.state();
+
+    if let Some(response) = freshness_error(state, &body.freshness) {
+        return Ok(response);
+    }
+
+    if attestation_start_verify(
+        body.signature,
+        &user,
+        &body.freshness,
+        &body.provider,
+        &*state.nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    let challenge = match start(
+        &*state.attestations,
+        &state.config.attestations,
+        user,
+        body.provider,
+        state.identity_service.now(),
+    )
+    .await
+    {
+        Ok(challenge) => challenge,
+        Err(e) => return error_response(e),
+    };
+
+    let response = Response::builder(200)
+        .body(json!({
+            "user": challenge.user,
+            "provider": challenge.provider,
+            "challenge": challenge.challenge,
+            "expires_at": challenge.expires_at,
+            "nonce": body.freshness.nonce,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        config::{AttestationsSection, Config},
+        identity::IdtAmount,
+        verify::{attestation::attestation_start_sign, expires_in, random_keypair},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    pub fn github_state() -> State {
+        let config = Config {
+            attestations: AttestationsSection {
+                providers: HashMap::from([("github".to_string(), IdtAmount::new(100))]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        State {
+            config: std::sync::Arc::new(config),
+            ..Default::default()
+        }
+    }
+
+    pub async fn start_attestation(
+        state: &State,
+        private_key: &str,
+        user: &str,
+        provider: &str,
+    ) -> Response {
+        let signature = attestation_start_sign(
+            private_key,
+            &state.server_identity.address,
+            provider,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        let body = json!({
+            "provider": provider,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/attestations/start/{user}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/attestations/start/:user").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = github_state();
+        let (private_key, user) = random_keypair();
+        let mut response = start_attestation(&state, &private_key, &user, "github").await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["user"], user);
+        assert_eq!(body["provider"], "github");
+        assert_eq!(body["challenge"].as_str().unwrap().len(), 64);
+
+        let response = start_attestation(&state, &private_key, &user, "twitter").await;
+        assert_eq!(response.status(), 400);
+
+        // signed by another user
+        let (other_key, _) = random_keypair();
+        let response = start_attestation(&state, &other_key, &user, "github").await;
+        assert_eq!(response.status(), 400);
+    }
+}
@@ -0,0 +1,238 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tide::{
+    Request, Response, StatusCode,
+    http::{
+        cache::{CacheControl, CacheDirective},
+        conditional::{ETag, IfModifiedSince, IfNoneMatch, LastModified},
+    },
+};
+
+use crate::{changes::storage::Version, config::CacheSection, routes::State};
+
+// ETag and Last-Modified of a response, derived from the change log so they can be checked
+// before the response is computed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: String,
+    // server time of the last change, unknown if nothing was changed yet
+    pub last_modified: Option<u64>,
+}
+
+impl Validators {
+    // changes only when the own vouches, proof or penalties of a user change
+    pub fn user(version: Option<Version>) -> Self {
+        let version = version.unwrap_or_default();
+        Self {
+            etag: format!("u{}", version.seq),
+            last_modified: (version.seq > 0).then_some(version.recorded_at),
+        }
+    }
+
+    // balances depend on the whole graph and decay with time, so any change and the end of
+    // every `window` seconds produce new validators
+    pub fn balance(latest: Option<Version>, now: u64, window: u64) -> Self {
+        let latest = latest.unwrap_or_default();
+        let window = window.max(1);
+        let window_start = now - now % window;
+        Self {
+            etag: format!("b{}-{}", latest.seq, window_start / window),
+            last_modified: Some(latest.recorded_at.max(window_start)),
+        }
+    }
+
+    // validators of data not recorded in the change log, the ETag is derived from the data
+    pub fn content(data: &[u8]) -> Self {
+        let hash = ethers_core::utils::keccak256(data);
+        Self {
+            etag: format!("c{}", hex::encode(&hash[..8])),
+            last_modified: None,
+        }
+    }
+
+    // distinguishes responses of the same data, e.g. with different query parameters
+    pub fn variant(mut self, variant: &str) -> Self {
+        self.etag = format!("{}-{variant}", self.etag);
+        self
+    }
+
+    fn matches(&self, req: &Request<State>) -> tide::Result<bool> {
+        // If-None-Match takes precedence over If-Modified-Since
+        if let Some(tags) = IfNoneMatch::from_headers(req)? {
+            return Ok(tags.wildcard()
+                || tags.iter().any(|tag| match tag {
+                    ETag::Strong(tag) | ETag::Weak(tag) => *tag == self.etag,
+                }));
+        }
+        match (IfModifiedSince::from_headers(req)?, self.last_modified) {
+            (Some(since), Some(modified)) => Ok(timestamp(modified) <= since.modified()),
+            _ => Ok(false),
+        }
+    }
+
+    // 304 response if the client already has the current version
+    pub fn not_modified(
+        &self,
+        req: &Request<State>,
+        config: &CacheSection,
+    ) -> tide::Result<Option<Response>> {
+        if !config.enabled || !self.matches(req)? {
+            return Ok(None);
+        }
+        let mut response = Response::new(StatusCode::NotModified);
+        self.apply(config, &mut response);
+        Ok(Some(response))
+    }
+
+    pub fn apply(&self, config: &CacheSection, response: &mut Response) {
+        if !config.enabled {
+            return;
+        }
+        ETag::new(self.etag.clone()).apply(&mut *response);
+        if let Some(modified) = self.last_modified {
+            LastModified::new(timestamp(modified)).apply(&mut *response);
+        }
+        let mut cache_control = CacheControl::new();
+        cache_control.push(match config.max_age {
+            0 => CacheDirective::NoCache,
+            max_age => CacheDirective::MaxAge(Duration::from_secs(max_age)),
+        });
+        cache_control.apply(response);
+    }
+}
+
+fn timestamp(seconds: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        identity::{tests::USER_A, vouch::vouch},
+        routes::{idt, servers::get_servers, tests::recorded_state, vouchers},
+    };
+    use tide::http::{Method, Request as HttpRequest, Response as HttpResponse, Url};
+
+    async fn get(state: &State, path: &str, headers: &[(&str, String)]) -> HttpResponse {
+        let mut req = HttpRequest::new(
+            Method::Get,
+            Url::parse(&format!("http://example.com{path}")).unwrap(),
+        );
+        for (name, value) in headers {
+            req.insert_header(*name, value.as_str());
+        }
+        let mut server = tide::with_state(state.clone());
+        server.at("/idt/:user").get(idt::route);
+        server.at("/vouchers/:user").get(vouchers::route);
+        server.at("/servers").get(get_servers::route);
+        server.respond(req).await.unwrap()
+    }
+
+    fn header(response: &HttpResponse, name: &str) -> String {
+        response.header(name).unwrap().as_str().to_string()
+    }
+
+    #[async_std::test]
+    async fn test_conditional_requests() {
+        let state = recorded_state();
+        let user_b = "userB".to_string();
+        vouch(&state.identity_service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+
+        for path in [
+            "/vouchers/userB",
+            "/idt/userB",
+            "/idt/userB?top=2",
+            "/servers",
+        ] {
+            let response = get(&state, path, &[]).await;
+            assert_eq!(response.status(), 200);
+            assert_eq!(header(&response, "Cache-Control"), "no-cache");
+            let etag = header(&response, "ETag");
+            let response = get(&state, path, &[("If-None-Match", etag.clone())]).await;
+            assert_eq!(response.status(), 304, "{path}");
+            assert_eq!(header(&response, "ETag"), etag);
+            let response = get(&state, path, &[("If-None-Match", "\"other\"".into())]).await;
+            assert_eq!(response.status(), 200);
+        }
+        let response = get(&state, "/vouchers/userB", &[]).await;
+        let etag = header(&response, "ETag");
+        let modified = header(&response, "Last-Modified");
+        let response = get(
+            &state,
+            "/vouchers/userB",
+            &[("If-Modified-Since", modified)],
+        )
+        .await;
+        assert_eq!(response.status(), 304);
+
+        // a change of other users does not touch the vouchers of userB
+        vouch(&state.identity_service, USER_A.to_string(), "userC".into())
+            .await
+            .unwrap();
+        let response = get(
+            &state,
+            "/vouchers/userB",
+            &[("If-None-Match", etag.clone())],
+        )
+        .await;
+        assert_eq!(response.status(), 304);
+        vouch(&state.identity_service, "userC".into(), user_b)
+            .await
+            .unwrap();
+        let response = get(&state, "/vouchers/userB", &[("If-None-Match", etag)]).await;
+        assert_eq!(response.status(), 200);
+    }
+
+    #[async_std::test]
+    async fn test_disabled() {
+        let mut state = recorded_state();
+        state.config = Arc::new(crate::config::Config {
+            cache: CacheSection {
+                enabled: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let response = get(&state, "/vouchers/userB", &[("If-None-Match", "*".into())]).await;
+        assert_eq!(response.status(), 200);
+        assert!(response.header("ETag").is_none());
+    }
+
+    #[test]
+    fn test_validators() {
+        assert_eq!(
+            Validators::user(None),
+            Validators {
+                etag: "u0".into(),
+                last_modified: None
+            }
+        );
+        let version = Version {
+            seq: 3,
+            recorded_at: 100,
+        };
+        assert_eq!(
+            Validators::user(Some(version)),
+            Validators {
+                etag: "u3".into(),
+                last_modified: Some(100)
+            }
+        );
+
+        let balance = Validators::balance(Some(version), 130, 60);
+        assert_eq!(balance.etag, "b3-2");
+        assert_eq!(balance.last_modified, Some(120));
+        assert_eq!(Validators::balance(Some(version), 179, 60), balance);
+        assert_ne!(Validators::balance(Some(version), 180, 60), balance);
+        assert_eq!(Validators::balance(None, 10, 0).last_modified, Some(10));
+        assert_eq!(balance.variant("top5").etag, "b3-2-top5");
+
+        assert_eq!(Validators::content(b"a"), Validators::content(b"a"));
+        assert_ne!(Validators::content(b"a"), Validators::content(b"b"));
+    }
+}
@@ -0,0 +1,228 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::{UserAddress, error::Error, idt::balance},
+    routes::{
+        Role, SignedRequest, State, check_role,
+        error::{ApiError, ErrorCode},
+        freshness_error, signed_body,
+    },
+    verify::{
+        category::{category_message_prefix, category_verify},
+        signature::Freshness,
+    },
+};
+
+#[derive(Deserialize)]
+struct CategoryRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+    // missing category removes the category of the user
+    #[serde(default)]
+    category: Option<String>,
+}
+
+impl SignedRequest for CategoryRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let service = &req.state().identity_service;
+    let category = service.category(&user).await?;
+    let policy = service.category_policy(&user).await?;
+    let response = Response::builder(200)
+        .body(json!({
+            "user": user,
+            "category": category,
+            "policy": policy,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+// assigns a category from `identity.categories` to the user. Signed by a moderator.
+pub async fn set_route(mut req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let body: CategoryRequest = signed_body(&mut req).await?;
+    let state = req.state();
+    let moderator = body.from;
+    let prefix = category_message_prefix(user.clone(), body.category.as_deref());
+    if let Err(response) = check_role(state, &moderator, Role::Moderator, &prefix).await {
+        return Ok(response);
+    }
+
+    if let Some(response) = freshness_error(state, &body.freshness) {
+        return Ok(response);
+    }
+
+    if category_verify(
+        body.signature,
+        &moderator,
+        &body.freshness,
+        user.clone(),
+        body.category.as_deref(),
+        &*state.nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    let service = &state.identity_service;
+    match service.set_category(&user, body.category.clone()).await {
+        Ok(()) => {}
+        Err(Error::UnknownCategory(_)) => {
+            return Ok(ApiError::bad_request(ErrorCode::UnknownCategory).into());
+        }
+        Err(e) => return Err(e.into()),
+    }
+    let response = Response::builder(200)
+        .body(json!({
+            "user": user,
+            "category": body.category,
+            "idt": balance(service, &user).await?.to_string(),
+            "nonce": body.freshness.nonce,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::Arc,
+    };
+
+    use super::*;
+    use crate::{
+        admins::InMemoryAdminStorage,
+        config::IdentitySection,
+        identity::{
+            IdentityService, IdtAmount,
+            categories::CategoryPolicy,
+            proof::prove,
+            tests::{MODERATOR, PROOF_ID},
+        },
+        routes::vouch,
+        verify::{category::category_sign, expires_in, random_keypair, vouch::vouch_sign},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_bot_category() {
+        let (moderator_priv, moderator) = random_keypair();
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::new(),
+                HashSet::from([moderator.clone()]),
+            )),
+            identity_service: IdentityService {
+                config: IdentitySection {
+                    categories: HashMap::from([(
+                        "bot".to_string(),
+                        CategoryPolicy {
+                            max_balance: Some(IdtAmount::new(10)),
+                            can_vouch: false,
+                            ..Default::default()
+                        },
+                    )]),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (user_priv, user) = random_keypair();
+        prove(
+            &state.identity_service,
+            user.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        let mut server = tide::with_state(state.clone());
+        server.at("/category/:user").get(route).post(set_route);
+        server.at("/vouch/:user").post(vouch::route);
+
+        for (category, expected_status) in [("human", 400), ("bot", 200)] {
+            let signature = category_sign(
+                &moderator_priv,
+                &state.server_identity.address,
+                user.clone(),
+                Some(category),
+                expires_in(60),
+                &*state.nonce_manager,
+            )
+            .await
+            .unwrap();
+            let body = json!({
+                "from": moderator,
+                "signature": signature.signature,
+                "nonce": signature.freshness.nonce,
+                "expires_at": signature.freshness.expires_at,
+                "domain": signature.freshness.domain,
+                "category": category,
+            });
+            let mut req = HttpRequest::new(
+                tide::http::Method::Post,
+                Url::parse(&format!("http://example.com/category/{user}")).unwrap(),
+            );
+            req.set_body(body);
+            req.set_content_type(mime::JSON);
+            let mut response: Response = server.respond(req).await.unwrap();
+            assert_eq!(response.status(), expected_status);
+            if expected_status == 200 {
+                let body: Value = response.body_json().await.unwrap();
+                assert_eq!(body["idt"], "10");
+            }
+        }
+
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/category/{user}")).unwrap(),
+        );
+        let mut response: Response = server.respond(req).await.unwrap();
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["category"], "bot");
+        assert_eq!(body["policy"]["can_vouch"], false);
+
+        let signature = vouch_sign(
+            &user_priv,
+            &state.server_identity.address,
+            "userB".to_string(),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .unwrap();
+        let body = json!({
+            "from": {"user": user},
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse("http://example.com/vouch/userB").unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 403);
+    }
+}
@@ -0,0 +1,217 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    changes::diff::diff,
+    routes::{
+        State,
+        error::{ApiError, ErrorCode},
+    },
+};
+
+// changes returned by a single request, clients poll again with the returned cursor
+pub const MAX_CHANGES: usize = 1000;
+
+// scope of the signed cursors, see `cursor` module
+const CURSOR_SCOPE: &str = "changes";
+
+#[derive(Deserialize)]
+struct ChangesQuery {
+    since: Option<String>,
+}
+
+// vouch, proof and penalty changes after `since` in the order they were applied. The
+// returned cursor points at the last change, or equals `since` if there are no new changes.
+pub async fn route(req: Request<State>) -> tide::Result {
+    let Ok(query) = req.query::<ChangesQuery>() else {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidQuery).into());
+    };
+    let cursors = req.state().cursors();
+    let since = match query
+        .since
+        .as_deref()
+        .map(|c| cursors.decode(CURSOR_SCOPE, c))
+    {
+        None => 0,
+        Some(Some(seq)) => seq,
+        Some(None) => return Ok(ApiError::bad_request(ErrorCode::InvalidSince).into()),
+    };
+    let changes = req.state().changes.changes(since, MAX_CHANGES).await?;
+    let cursor = changes.last().map(|c| c.seq).unwrap_or(since);
+    let changes: Vec<_> = changes
+        .into_iter()
+        .map(|c| {
+            json!({
+                "cursor": cursors.encode(CURSOR_SCOPE, &c.seq),
+                "recorded_at": c.recorded_at,
+                "change": c.event,
+            })
+        })
+        .collect();
+    let response = Response::builder(200)
+        .body(json!({
+            "changes": changes,
+            "cursor": cursors.encode(CURSOR_SCOPE, &cursor),
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    from_ts: u64,
+    // now if not set
+    to_ts: Option<u64>,
+}
+
+// edges and proofs added and removed between `from_ts` and `to_ts`, see `changes::diff`
+pub async fn diff_route(req: Request<State>) -> tide::Result {
+    let Ok(query) = req.query::<DiffQuery>() else {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidQuery).into());
+    };
+    let state = req.state();
+    let to_ts = query.to_ts.unwrap_or_else(|| state.identity_service.now());
+    if query.from_ts > to_ts {
+        return Ok(ApiError::bad_request(ErrorCode::FromAfterTo).into());
+    }
+    let diff = diff(&*state.changes, query.from_ts, to_ts).await?;
+    let response = Response::builder(200)
+        .body(json!({
+            "from_ts": query.from_ts,
+            "to_ts": to_ts,
+            "changes": diff.changes,
+            "edges": {
+                "added": diff.added_edges,
+                "removed": diff.removed_edges,
+            },
+            "proofs": {
+                "added": diff.added_proofs,
+                "removed": diff.removed_proofs,
+            },
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::Event,
+        identity::{tests::USER_A, vouch::vouch},
+        routes::tests::recorded_state,
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn changes(state: &State, query: &str) -> (u16, Value) {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/changes{query}")).unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/changes").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        let body = response.body_json().await.unwrap();
+        (response.status().into(), body)
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = recorded_state();
+        for user in ["userB", "userC"] {
+            vouch(
+                &state.identity_service,
+                USER_A.to_string(),
+                user.to_string(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let (status, body) = changes(&state, "").await;
+        assert_eq!(status, 200);
+        let list = body["changes"].as_array().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0]["change"]["type"], "vouch");
+        assert_eq!(list[0]["change"]["voucher"], USER_A);
+        assert_eq!(list[0]["change"]["vouchee"], "userB");
+        assert_eq!(body["cursor"], list[1]["cursor"]);
+
+        let cursor = list[0]["cursor"].as_str().unwrap();
+        let (_, body) = changes(&state, &format!("?since={cursor}")).await;
+        let list = body["changes"].as_array().unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0]["change"]["vouchee"], "userC");
+
+        // nothing new, the cursor stays the same
+        let cursor = body["cursor"].as_str().unwrap().to_string();
+        let (_, body) = changes(&state, &format!("?since={cursor}")).await;
+        assert!(body["changes"].as_array().unwrap().is_empty());
+        assert_eq!(body["cursor"], cursor);
+
+        let (status, _) = changes(&state, "?since=invalid").await;
+        assert_eq!(status, 400);
+    }
+
+    #[async_std::test]
+    async fn test_diff() {
+        let state = recorded_state();
+        let changes = &state.changes;
+        for (voucher, vouchee, recorded_at) in [(USER_A, "userB", 10), (USER_A, "userC", 20)] {
+            changes
+                .append(
+                    Event::Vouch {
+                        voucher: voucher.to_string(),
+                        vouchee: vouchee.to_string(),
+                        timestamp: recorded_at,
+                    },
+                    recorded_at,
+                )
+                .await
+                .unwrap();
+        }
+        changes
+            .append(
+                Event::RemoveVouch {
+                    voucher: USER_A.to_string(),
+                    vouchee: "userB".to_string(),
+                },
+                20,
+            )
+            .await
+            .unwrap();
+
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse("http://example.com/diff?from_ts=10&to_ts=20").unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/diff").get(diff_route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["changes"], 2);
+        assert_eq!(
+            body["edges"]["added"],
+            json!([{"voucher": USER_A, "vouchee": "userC", "timestamp": 20}])
+        );
+        assert_eq!(
+            body["edges"]["removed"],
+            json!([{"voucher": USER_A, "vouchee": "userB", "timestamp": 10}])
+        );
+        assert_eq!(body["proofs"]["added"], json!([]));
+
+        for query in ["?from_ts=20&to_ts=10", "?to_ts=10", "?from_ts=x"] {
+            let req = HttpRequest::new(
+                tide::http::Method::Get,
+                Url::parse(&format!("http://example.com/diff{query}")).unwrap(),
+            );
+            let response: Response = server.respond(req).await.unwrap();
+            assert_eq!(response.status(), 400);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    federation::storage::HomeClaim,
+    identity::UserAddress,
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        freshness_error, signed_body,
+    },
+    verify::{claim_home::claim_home_verify, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct ClaimHomeRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for ClaimHomeRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// home claim of the user with the address of this server, so other servers can verify the
+// signature against `server`. `None` if the user has not claimed this server.
+pub async fn home_claim_json(
+    state: &State,
+    user: &UserAddress,
+) -> tide::Result<Option<serde_json::Value>> {
+    let Some(claim) = state.home_storage.claim(user).await? else {
+        return Ok(None);
+    };
+    Ok(Some(json!({
+        "server": state.server_identity.address,
+        "signature": claim.signature,
+        "nonce": claim.nonce,
+        "expires_at": claim.expires_at,
+        "version": claim.version,
+        "claimed_at": claim.claimed_at,
+    })))
+}
+
+// the signing user declares this server as their home, so the data of this server is
+// authoritative for their address. Replaces the remote home set by admins.
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let body: ClaimHomeRequest = signed_body(&mut req).await?;
+    let state = req.state();
+    // stored claims are only verifiable with the domain
+    if body.freshness.domain.is_none() {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureDomainMissing).into());
+    }
+    if let Some(response) = freshness_error(state, &body.freshness) {
+        return Ok(response);
+    }
+    if claim_home_verify(
+        body.signature.clone(),
+        &body.from,
+        &body.freshness,
+        &*state.nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    let claim = HomeClaim {
+        signature: body.signature,
+        nonce: body.freshness.nonce,
+        expires_at: body.freshness.expires_at,
+        version: body.freshness.version,
+        claimed_at: state.identity_service.now(),
+    };
+    state.home_storage.remove_home(&body.from).await?;
+    state
+        .home_storage
+        .set_claim(body.from.clone(), claim)
+        .await?;
+    log::info!("User {} claimed this server as home", body.from);
+
+    Ok(Response::builder(200)
+        .body(json!({
+            "user": body.from,
+            "home": home_claim_json(state, &body.from).await?,
+        }))
+        .content_type(mime::JSON)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verify::{claim_home::claim_home_sign, expires_in, random_keypair};
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn claim(state: &State, body: Value) -> Response {
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse("http://example.com/claim_home").unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/claim_home").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    async fn signed_claim(state: &State, private_key: &str) -> Value {
+        let signature = claim_home_sign(
+            private_key,
+            &state.server_identity.address,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .unwrap();
+        json!({
+            "from": signature.signer,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        })
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        let (private_key, user) = random_keypair();
+        state
+            .home_storage
+            .set_home(user.clone(), "server1".to_string())
+            .await
+            .unwrap();
+        let body = signed_claim(&state, &private_key).await;
+
+        let mut response = claim(&state, body.clone()).await;
+        assert_eq!(response.status(), 200);
+        let response: Value = response.body_json().await.unwrap();
+        assert_eq!(response["user"], user);
+        assert_eq!(response["home"]["server"], state.server_identity.address);
+        assert_eq!(response["home"]["signature"], body["signature"]);
+        assert!(state.home_storage.home(&user).await.unwrap().is_none());
+        let stored = state.home_storage.claim(&user).await.unwrap().unwrap();
+        assert_eq!(stored.nonce, body["nonce"]);
+
+        // the nonce is accepted once
+        assert_eq!(claim(&state, body).await.status(), 400);
+    }
+
+    #[async_std::test]
+    async fn test_other_domain() {
+        let state = State::default();
+        let other = State::default();
+        let (private_key, user) = random_keypair();
+        let body = signed_claim(&other, &private_key).await;
+        let mut response = claim(&state, body).await;
+        assert_eq!(response.status(), 400);
+        let response: Value = response.body_json().await.unwrap();
+        assert_eq!(response["error"], "signature domain mismatch");
+        assert!(state.home_storage.claim(&user).await.unwrap().is_none());
+    }
+}
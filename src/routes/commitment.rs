@@ -0,0 +1,120 @@
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    commitment::{Hash, commit},
+    routes::State,
+    verify::commitment::commitment_sign,
+};
+
+fn hex_hash(hash: &Hash) -> String {
+    format!("0x{}", hex::encode(hash))
+}
+
+// commitment to the vouchers and balance of the user, signed by this server. The
+// blinding is returned only to let the user build zero knowledge proofs off-server
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let state = req.state();
+    let commitment = commit(
+        &state.identity_service,
+        &user,
+        &state.server_identity.private_key,
+    )
+    .await?;
+    let signature = commitment_sign(
+        &state.server_identity.private_key,
+        &user,
+        &commitment.voucher_root,
+        &commitment.balance_commitment,
+        commitment.timestamp,
+    )
+    .await?;
+    let vouchers: Vec<serde_json::Value> = commitment
+        .vouchers
+        .iter()
+        .map(|leaf| {
+            json!({
+                "voucher": leaf.voucher,
+                "timestamp": leaf.timestamp,
+                "leaf": hex_hash(&leaf.leaf),
+                "proof": leaf.proof.iter().map(hex_hash).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+    let response = Response::builder(200)
+        .body(json!({
+            "user": user,
+            "timestamp": commitment.timestamp,
+            "vouchers": vouchers,
+            "voucher_root": hex_hash(&commitment.voucher_root),
+            "balance": commitment.balance,
+            "blinding": hex_hash(&commitment.blinding),
+            "balance_commitment": hex_hash(&commitment.balance_commitment),
+            "server": state.server_identity.address,
+            "signature": signature,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identity::{tests::USER_A, vouch::vouch},
+        verify::commitment::commitment_verify,
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    fn parse_hash(value: &Value) -> Hash {
+        let bytes = hex::decode(value.as_str().unwrap().trim_start_matches("0x")).unwrap();
+        bytes.try_into().unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        let user_b = "userB";
+        vouch(
+            &state.identity_service,
+            USER_A.to_string(),
+            user_b.to_string(),
+        )
+        .await
+        .unwrap();
+
+        let mut server = tide::with_state(state.clone());
+        server.at("/commitment/:user").get(route);
+        let mut bodies = vec![];
+        for _ in 0..2 {
+            let req = HttpRequest::new(
+                tide::http::Method::Get,
+                Url::parse(&format!("http://example.com/commitment/{user_b}")).unwrap(),
+            );
+            let mut response: Response = server.respond(req).await.unwrap();
+            assert_eq!(response.status(), 200);
+            let body: Value = response.body_json().await.unwrap();
+            bodies.push(body);
+        }
+        let body = &bodies[0];
+        assert_eq!(body["voucher_root"], bodies[1]["voucher_root"]);
+        assert_eq!(body["balance_commitment"], bodies[1]["balance_commitment"]);
+        assert_eq!(body["vouchers"][0]["voucher"], USER_A);
+        // single leaf is the root
+        assert_eq!(body["vouchers"][0]["leaf"], body["voucher_root"]);
+        assert!(
+            commitment_verify(
+                body["signature"].as_str().unwrap(),
+                &state.server_identity.address,
+                &user_b.to_string(),
+                &parse_hash(&body["voucher_root"]),
+                &parse_hash(&body["balance_commitment"]),
+                body["timestamp"].as_u64().unwrap(),
+            )
+            .is_ok()
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use async_std::channel::{Receiver, Sender, bounded};
+use tide::{Middleware, Next, Request, Response};
+
+use crate::{
+    config::ConcurrencySection,
+    routes::{
+        State,
+        error::{ApiError, ErrorCode},
+        metrics::RouteCounters,
+    },
+    service_accounts::scope,
+};
+
+// semaphore whose permits are the slots of a bounded channel
+struct Limiter {
+    slots: Sender<()>,
+    taken: Receiver<()>,
+    waiting: AtomicUsize,
+    max_waiting: usize,
+}
+
+// frees the slot when the request is done
+struct Permit<'a>(&'a Limiter);
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.taken.try_recv();
+    }
+}
+
+impl Limiter {
+    fn new(limit: usize, max_waiting: usize) -> Self {
+        let (slots, taken) = bounded(limit.max(1));
+        Self {
+            slots,
+            taken,
+            waiting: AtomicUsize::new(0),
+            max_waiting,
+        }
+    }
+
+    // waits for a free slot, None if too many requests are already waiting
+    async fn acquire(&self) -> Option<Permit<'_>> {
+        if self.slots.try_send(()).is_ok() {
+            return Some(Permit(self));
+        }
+        if self.waiting.fetch_add(1, Ordering::SeqCst) >= self.max_waiting {
+            self.waiting.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        // the limiter owns both ends of the channel, so sending cannot fail
+        let _ = self.slots.send(()).await;
+        self.waiting.fetch_sub(1, Ordering::SeqCst);
+        Some(Permit(self))
+    }
+}
+
+// limits requests served at once by route and in total, see `server.concurrency`. A request
+// takes a slot of its route before a global one, so requests waiting for a busy route do
+// not hold global slots.
+pub struct ConcurrencyMiddleware {
+    global: Option<Limiter>,
+    routes: HashMap<String, Limiter>,
+}
+
+impl ConcurrencyMiddleware {
+    pub fn new(config: &ConcurrencySection) -> Self {
+        Self {
+            global: (config.global > 0).then(|| Limiter::new(config.global, config.queue)),
+            routes: config
+                .routes
+                .iter()
+                .filter(|(_, limit)| **limit > 0)
+                .map(|(route, limit)| (route.clone(), Limiter::new(*limit, config.queue)))
+                .collect(),
+        }
+    }
+}
+
+fn too_many_requests(rejections: &RouteCounters, route: &str) -> Response {
+    rejections.record(route);
+    let mut response: Response = ApiError::new(429, ErrorCode::TooManyRequests).into();
+    response.insert_header("Retry-After", "1");
+    response
+}
+
+#[tide::utils::async_trait]
+impl Middleware<State> for ConcurrencyMiddleware {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let route = scope(req.url().path()).to_string();
+        let rejections = req.state().request_rejections.clone();
+        let _route_permit = match self.routes.get(&route) {
+            Some(limiter) => match limiter.acquire().await {
+                Some(permit) => Some(permit),
+                None => return Ok(too_many_requests(&rejections, &route)),
+            },
+            None => None,
+        };
+        let _global_permit = match &self.global {
+            Some(limiter) => match limiter.acquire().await {
+                Some(permit) => Some(permit),
+                None => return Ok(too_many_requests(&rejections, &route)),
+            },
+            None => None,
+        };
+        Ok(next.run(req).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use futures::future::join_all;
+
+    use super::*;
+    use tide::http::{Method, Request as HttpRequest, Url};
+
+    fn server(config: &ConcurrencySection) -> Arc<tide::Server<State>> {
+        server_with_state(config, State::default())
+    }
+
+    fn server_with_state(config: &ConcurrencySection, state: State) -> Arc<tide::Server<State>> {
+        let mut server = tide::with_state(state);
+        server.with(ConcurrencyMiddleware::new(config));
+        server.at("/idt/:user").get(|_| async {
+            async_std::task::sleep(Duration::from_millis(50)).await;
+            Ok("balance")
+        });
+        server.at("/vouch/:user").post(|_| async { Ok("vouched") });
+        Arc::new(server)
+    }
+
+    async fn respond(server: &tide::Server<State>, method: Method, path: &str) -> u16 {
+        let req = HttpRequest::new(
+            method,
+            Url::parse(&format!("http://example.com{path}")).unwrap(),
+        );
+        let response: tide::http::Response = server.respond(req).await.unwrap();
+        response.status().into()
+    }
+
+    fn statuses(responses: &[u16], status: u16) -> usize {
+        responses.iter().filter(|s| **s == status).count()
+    }
+
+    #[async_std::test]
+    async fn test_route_limit() {
+        let state = State::default();
+        let limited = server_with_state(
+            &ConcurrencySection {
+                global: 0,
+                routes: HashMap::from([("idt".to_string(), 2)]),
+                queue: 1,
+            },
+            state.clone(),
+        );
+        // two requests are served, one waits and the rest are rejected
+        let responses =
+            join_all((0..5).map(|_| respond(&limited, Method::Get, "/idt/userA"))).await;
+        assert_eq!(statuses(&responses, 200), 3);
+        assert_eq!(statuses(&responses, 429), 2);
+        assert_eq!(state.request_rejections.by_route()["idt"], 2);
+        assert_eq!(state.request_rejections.total(), 2);
+
+        // other routes are not limited while `idt` is busy
+        let (idt, vouch) = futures::join!(
+            join_all((0..5).map(|_| respond(&limited, Method::Get, "/idt/userA"))),
+            respond(&limited, Method::Post, "/vouch/userA")
+        );
+        assert_eq!(statuses(&idt, 429), 2);
+        assert_eq!(vouch, 200);
+
+        // slots are freed after the requests
+        assert_eq!(respond(&limited, Method::Get, "/idt/userA").await, 200);
+    }
+
+    #[async_std::test]
+    async fn test_global_limit() {
+        let limited = server(&ConcurrencySection {
+            global: 1,
+            routes: HashMap::new(),
+            queue: 0,
+        });
+        let responses =
+            join_all((0..3).map(|_| respond(&limited, Method::Get, "/idt/userA"))).await;
+        assert_eq!(statuses(&responses, 200), 1);
+        assert_eq!(statuses(&responses, 429), 2);
+
+        let unlimited = server(&ConcurrencySection {
+            global: 0,
+            routes: HashMap::new(),
+            queue: 0,
+        });
+        let responses =
+            join_all((0..3).map(|_| respond(&unlimited, Method::Get, "/idt/userA"))).await;
+        assert_eq!(statuses(&responses, 200), 3);
+    }
+}
@@ -0,0 +1,239 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::UserAddress,
+    notifications::{Contact, ContactKind, push::is_valid_endpoint},
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        freshness_error, signed_body,
+    },
+    verify::{contact::contact_verify, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct ContactRequest {
+    from: UserAddress,
+    // missing contact unsubscribes the user
+    #[serde(default)]
+    contact: Option<Contact>,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for ContactRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+fn is_valid_contact(contact: &Contact) -> bool {
+    let address = &contact.address;
+    if address.is_empty() || address.chars().any(|c| c.is_control() || c.is_whitespace()) {
+        return false;
+    }
+    match contact.kind {
+        ContactKind::Email => address.contains('@'),
+        ContactKind::WebPush => is_valid_endpoint(address),
+    }
+}
+
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let body: ContactRequest = signed_body(&mut req).await?;
+    let state = req.state();
+    let notifications = &state.notifications;
+
+    if let Some(contact) = &body.contact {
+        if !is_valid_contact(contact) {
+            return Ok(ApiError::bad_request(ErrorCode::InvalidContact).into());
+        }
+        if !notifications.supports(contact.kind) {
+            return Ok(ApiError::bad_request(ErrorCode::UnsupportedContactKind).into());
+        }
+    }
+
+    if let Some(response) = freshness_error(state, &body.freshness) {
+        return Ok(response);
+    }
+
+    if contact_verify(
+        body.signature,
+        &body.from,
+        &body.freshness,
+        body.contact.as_ref(),
+        &*state.nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    let result = match body.contact.clone() {
+        Some(contact) => {
+            notifications
+                .contacts
+                .set_contact(body.from.clone(), contact)
+                .await
+        }
+        None => notifications.contacts.remove_contact(&body.from).await,
+    };
+    if result.is_err() {
+        return Ok(ApiError::bad_request(ErrorCode::FailedToSetContact).into());
+    }
+
+    let response = Response::builder(200)
+        .body(json!({
+            "from": body.from,
+            "contact": body.contact,
+            "nonce": body.freshness.nonce,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use super::*;
+    use crate::{
+        notifications::{InMemoryNotifier, NotificationDispatcher, Notifier},
+        verify::{contact::contact_sign, expires_in, random_keypair},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    fn email_state() -> State {
+        State {
+            notifications: Arc::new(NotificationDispatcher {
+                transports: HashMap::from([(
+                    ContactKind::Email,
+                    Arc::new(InMemoryNotifier::default()) as Arc<dyn Notifier>,
+                )]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    async fn set_contact(state: &State, private_key: &str, contact: Option<Contact>) -> Response {
+        let signature = contact_sign(
+            private_key,
+            &state.server_identity.address,
+            contact.as_ref(),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        let body = json!({
+            "from": signature.signer,
+            "contact": contact,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse("http://example.com/contact").unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/contact").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = email_state();
+        let (private_key, user) = random_keypair();
+        let contact = Contact {
+            kind: ContactKind::Email,
+            address: "user@example.com".to_string(),
+        };
+        let mut response = set_contact(&state, &private_key, Some(contact.clone())).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["from"], user);
+        assert_eq!(body["contact"]["kind"], "email");
+        assert_eq!(
+            state.notifications.contacts.contact(&user).await.unwrap(),
+            Some(contact)
+        );
+
+        let response = set_contact(&state, &private_key, None).await;
+        assert_eq!(response.status(), 200);
+        assert!(
+            state
+                .notifications
+                .contacts
+                .contact(&user)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_bad_contact() {
+        let state = email_state();
+        let (private_key, _) = random_keypair();
+        let mut response = set_contact(
+            &state,
+            &private_key,
+            Some(Contact {
+                kind: ContactKind::Email,
+                address: "not an email".to_string(),
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "invalid contact");
+
+        let mut response = set_contact(
+            &state,
+            &private_key,
+            Some(Contact {
+                kind: ContactKind::WebPush,
+                address: "https://push.example.com".to_string(),
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "unsupported contact kind");
+    }
+
+    #[async_std::test]
+    async fn test_private_push_endpoint() {
+        let state = email_state();
+        let (private_key, _) = random_keypair();
+        for address in [
+            "http://127.0.0.1/push",
+            "https://169.254.169.254/latest/meta-data",
+            "https://localhost/push",
+            "http://push.example.com",
+        ] {
+            let mut response = set_contact(
+                &state,
+                &private_key,
+                Some(Contact {
+                    kind: ContactKind::WebPush,
+                    address: address.to_string(),
+                }),
+            )
+            .await;
+            assert_eq!(response.status(), 400);
+            let body: Value = response.body_json().await.unwrap();
+            assert_eq!(body["error"], "invalid contact");
+        }
+    }
+}
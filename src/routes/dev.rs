@@ -0,0 +1,513 @@
+use serde::Deserialize;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::{IdtAmount, ProofId, UserAddress, proof::ProofBatchEntry},
+    pending_penalties::PendingPenaltyId,
+    reports::{ReportAction, ReportId},
+    routes::{
+        State,
+        error::{ApiError, ErrorCode},
+        naming::json_body,
+    },
+    verify::{
+        admins::{
+            admin_attest_server_message_prefix, admin_check_integrity_message_prefix,
+            admin_decide_appeal_message_prefix, admin_decide_penalty_message_prefix,
+            admin_grant_restitution_message_prefix, admin_message_prefix,
+            admin_outbox_message_prefix, admin_restore_user_message_prefix,
+            admin_revoke_moderator_proofs_message_prefix, admin_set_flag_message_prefix,
+            admin_set_home_message_prefix, admin_set_moderator_message_prefix,
+            admin_set_proof_limit_message_prefix, admin_set_server_message_prefix,
+            admin_set_successor_message_prefix,
+        },
+        attestation::attestation_start_sign,
+        category::category_sign,
+        error::Error,
+        forget::{forget_at_sign, forget_sign},
+        nonce::NonceManager,
+        petname::petname_sign,
+        proof::{proof_batch_sign, proof_consent_sign, proof_sign, transfer_proofs_sign},
+        punish::punish_message_prefix,
+        report::{report_sign, resolve_report_sign},
+        restitution::{appeal_sign, restitution_sign},
+        sign_message,
+        signature::Signature,
+        vouch::{
+            vouch_at_sign, vouch_confirm_sign, vouch_consent_sign, vouch_notes_sign, vouch_sign,
+        },
+    },
+};
+
+// seconds the signature stays valid if the request does not set `expires_in`
+const DEFAULT_EXPIRES_IN: u64 = 60;
+
+// actions with the parameters of their message prefixes
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum DevAction {
+    Vouch {
+        user: UserAddress,
+        timestamp: Option<u64>,
+    },
+    VouchConfirm {
+        user: UserAddress,
+    },
+    VouchConsent {
+        voucher: UserAddress,
+    },
+    Forget {
+        user: UserAddress,
+        timestamp: Option<u64>,
+    },
+    Proof {
+        user: UserAddress,
+        amount: IdtAmount,
+        proof_id: ProofId,
+    },
+    ProofConsent {
+        moderator: UserAddress,
+        amount: IdtAmount,
+        proof_id: ProofId,
+    },
+    TransferProofs {
+        moderator: UserAddress,
+    },
+    ProofBatch {
+        entries: Vec<ProofBatchEntry>,
+    },
+    Punish {
+        user: UserAddress,
+        amount: IdtAmount,
+        proof_id: ProofId,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    Category {
+        user: UserAddress,
+        category: Option<String>,
+    },
+    Report {
+        user: UserAddress,
+        reason: String,
+    },
+    ResolveReport {
+        id: ReportId,
+        resolution: ReportAction,
+    },
+    AttestationStart {
+        provider: String,
+    },
+    Petname {
+        address: UserAddress,
+        name: Option<String>,
+    },
+    // vouchee reading the notes of its vouchers
+    VouchNotes,
+    // contribution to the restitution pool, or withdrawal if `withdraw` is set
+    Restitution {
+        amount: IdtAmount,
+        #[serde(default)]
+        withdraw: bool,
+    },
+    // appeal of a penalized user for restitution
+    Appeal {
+        reason: String,
+    },
+    // add_admin and remove_admin
+    Admin {
+        user: UserAddress,
+    },
+    // add_moderator and remove_moderator
+    Moderator {
+        user: UserAddress,
+    },
+    // add_server and remove_server
+    Server {
+        address: UserAddress,
+    },
+    AttestServer {
+        address: UserAddress,
+    },
+    SetHome {
+        user: UserAddress,
+        server: Option<UserAddress>,
+    },
+    RestoreUser {
+        user: UserAddress,
+    },
+    RevokeModeratorProofs {
+        moderator: UserAddress,
+    },
+    SetSuccessor {
+        moderator: UserAddress,
+        successor: Option<UserAddress>,
+    },
+    SetProofLimit {
+        moderator: UserAddress,
+        limit: Option<IdtAmount>,
+    },
+    SetFlag {
+        flag: String,
+        enabled: bool,
+    },
+    CheckIntegrity {
+        repair: bool,
+    },
+    ListOutbox,
+    // approve and reject of a pending penalty
+    DecidePenalty {
+        id: PendingPenaltyId,
+        approved: bool,
+    },
+    // approve and reject of a restitution appeal
+    DecideAppeal {
+        user: UserAddress,
+        approved: bool,
+    },
+    GrantRestitution {
+        user: UserAddress,
+        amount: IdtAmount,
+    },
+}
+
+#[derive(Deserialize)]
+struct DevSignRequest {
+    private_key: String,
+    // server the message is signed for, this server if not set
+    domain: Option<UserAddress>,
+    expires_in: Option<u64>,
+    #[serde(flatten)]
+    action: DevAction,
+}
+
+async fn sign(
+    private_key: &str,
+    domain: &UserAddress,
+    action: DevAction,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    let prefix = match action {
+        DevAction::Vouch {
+            user,
+            timestamp: None,
+        } => return vouch_sign(private_key, domain, user, expires_at, nonce_manager).await,
+        DevAction::Vouch {
+            user,
+            timestamp: Some(timestamp),
+        } => {
+            return vouch_at_sign(
+                private_key,
+                domain,
+                user,
+                timestamp,
+                expires_at,
+                nonce_manager,
+            )
+            .await;
+        }
+        DevAction::VouchConfirm { user } => {
+            return vouch_confirm_sign(private_key, domain, user, expires_at, nonce_manager).await;
+        }
+        DevAction::VouchConsent { voucher } => {
+            return vouch_consent_sign(private_key, domain, voucher, expires_at, nonce_manager)
+                .await;
+        }
+        DevAction::Forget {
+            user,
+            timestamp: None,
+        } => return forget_sign(private_key, domain, user, expires_at, nonce_manager).await,
+        DevAction::Forget {
+            user,
+            timestamp: Some(timestamp),
+        } => {
+            return forget_at_sign(
+                private_key,
+                domain,
+                user,
+                timestamp,
+                expires_at,
+                nonce_manager,
+            )
+            .await;
+        }
+        DevAction::Proof {
+            user,
+            amount,
+            proof_id,
+        } => {
+            return proof_sign(
+                private_key,
+                domain,
+                user,
+                amount,
+                proof_id,
+                expires_at,
+                nonce_manager,
+            )
+            .await;
+        }
+        DevAction::ProofConsent {
+            moderator,
+            amount,
+            proof_id,
+        } => {
+            return proof_consent_sign(
+                private_key,
+                domain,
+                moderator,
+                amount,
+                proof_id,
+                expires_at,
+                nonce_manager,
+            )
+            .await;
+        }
+        DevAction::TransferProofs { moderator } => {
+            return transfer_proofs_sign(private_key, domain, moderator, expires_at, nonce_manager)
+                .await;
+        }
+        DevAction::ProofBatch { entries } => {
+            return proof_batch_sign(private_key, domain, &entries, expires_at, nonce_manager)
+                .await;
+        }
+        DevAction::Category { user, category } => {
+            return category_sign(
+                private_key,
+                domain,
+                user,
+                category.as_deref(),
+                expires_at,
+                nonce_manager,
+            )
+            .await;
+        }
+        DevAction::Report { user, reason } => {
+            return report_sign(
+                private_key,
+                domain,
+                &user,
+                &reason,
+                expires_at,
+                nonce_manager,
+            )
+            .await;
+        }
+        DevAction::ResolveReport { id, resolution } => {
+            return resolve_report_sign(
+                private_key,
+                domain,
+                id,
+                &resolution,
+                expires_at,
+                nonce_manager,
+            )
+            .await;
+        }
+        DevAction::AttestationStart { provider } => {
+            return attestation_start_sign(
+                private_key,
+                domain,
+                &provider,
+                expires_at,
+                nonce_manager,
+            )
+            .await;
+        }
+        DevAction::Petname { address, name } => {
+            return petname_sign(
+                private_key,
+                domain,
+                address,
+                name.as_deref(),
+                expires_at,
+                nonce_manager,
+            )
+            .await;
+        }
+        DevAction::VouchNotes => {
+            return vouch_notes_sign(private_key, domain, expires_at, nonce_manager).await;
+        }
+        DevAction::Restitution { amount, withdraw } => {
+            return restitution_sign(
+                private_key,
+                domain,
+                amount,
+                withdraw,
+                expires_at,
+                nonce_manager,
+            )
+            .await;
+        }
+        DevAction::Appeal { reason } => {
+            return appeal_sign(private_key, domain, &reason, expires_at, nonce_manager).await;
+        }
+        DevAction::Admin { user } => admin_message_prefix(user),
+        DevAction::Moderator { user } => admin_set_moderator_message_prefix(user),
+        DevAction::Server { address } => admin_set_server_message_prefix(address),
+        DevAction::AttestServer { address } => admin_attest_server_message_prefix(address),
+        DevAction::SetHome { user, server } => admin_set_home_message_prefix(user, server),
+        DevAction::RestoreUser { user } => admin_restore_user_message_prefix(user),
+        DevAction::RevokeModeratorProofs { moderator } => {
+            admin_revoke_moderator_proofs_message_prefix(moderator)
+        }
+        DevAction::SetSuccessor {
+            moderator,
+            successor,
+        } => admin_set_successor_message_prefix(moderator, successor),
+        DevAction::Punish {
+            user,
+            amount,
+            proof_id,
+            reason,
+        } => punish_message_prefix(user, amount, proof_id, reason),
+        DevAction::SetProofLimit { moderator, limit } => {
+            admin_set_proof_limit_message_prefix(moderator, limit)
+        }
+        DevAction::SetFlag { flag, enabled } => admin_set_flag_message_prefix(&flag, enabled),
+        DevAction::CheckIntegrity { repair } => admin_check_integrity_message_prefix(repair),
+        DevAction::ListOutbox => admin_outbox_message_prefix(),
+        DevAction::DecidePenalty { id, approved } => {
+            admin_decide_penalty_message_prefix(id, approved)
+        }
+        DevAction::DecideAppeal { user, approved } => {
+            admin_decide_appeal_message_prefix(user, approved)
+        }
+        DevAction::GrantRestitution { user, amount } => {
+            admin_grant_restitution_message_prefix(user, amount)
+        }
+    };
+    sign_message(private_key, domain, &prefix, expires_at, nonce_manager).await
+}
+
+// signs an action with the supplied private key and the exact message prefix of its route.
+// Only served with `dev.sign_endpoint`, the key is sent to the server.
+pub async fn sign_route(mut req: Request<State>) -> tide::Result {
+    let body: DevSignRequest = match json_body(&mut req).await {
+        Ok(body) => body,
+        Err(e) => {
+            return Ok(ApiError::bad_request(ErrorCode::BadRequest)
+                .with("reason", e.to_string())
+                .into());
+        }
+    };
+    let state = req.state();
+    let domain = body
+        .domain
+        .unwrap_or_else(|| state.server_identity.address.clone());
+    let expires_in = body
+        .expires_in
+        .unwrap_or(DEFAULT_EXPIRES_IN)
+        .min(state.config.signatures.max_age);
+    let expires_at = state.identity_service.now().saturating_add(expires_in);
+    let signature = match sign(
+        &body.private_key,
+        &domain,
+        body.action,
+        expires_at,
+        &*state.nonce_manager,
+    )
+    .await
+    {
+        Ok(signature) => signature,
+        Err(e) => {
+            return Ok(ApiError::bad_request(ErrorCode::SigningFailed)
+                .with("reason", e.to_string())
+                .into());
+        }
+    };
+    Ok(Response::builder(200)
+        .body(serde_json::to_value(signature)?)
+        .content_type(mime::JSON)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        config::{Config, DevSection},
+        identity::vouch::vouchers,
+        routes::setup_routes,
+        verify::random_keypair,
+    };
+    use serde_json::{Value, json};
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    fn post(path: &str, body: Value) -> HttpRequest {
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com{path}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        req
+    }
+
+    #[async_std::test]
+    async fn test_disabled_by_default() {
+        let mut server = tide::with_state(State::default());
+        setup_routes(&mut server);
+        let (private_key, _) = random_keypair();
+        let req = post(
+            "/dev/sign",
+            json!({"private_key": private_key, "action": "vouch", "user": "userB"}),
+        );
+        let response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 404);
+    }
+
+    #[async_std::test]
+    async fn test_signed_vouch() {
+        let state = State {
+            config: Arc::new(Config {
+                dev: DevSection {
+                    sign_endpoint: true,
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut server = tide::with_state(state.clone());
+        setup_routes(&mut server);
+        let (private_key, address) = random_keypair();
+
+        let req = post(
+            "/dev/sign",
+            json!({"private_key": private_key, "action": "unknown"}),
+        );
+        let response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 400);
+
+        let req = post(
+            "/dev/sign",
+            json!({"private_key": private_key, "action": "vouch", "user": "userB"}),
+        );
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let signature: Value = response.body_json().await.unwrap();
+        assert_eq!(signature["signer"], address);
+
+        let req = post(
+            "/vouch/userB",
+            json!({
+                "from": {"user": address},
+                "signature": signature["signature"],
+                "nonce": signature["nonce"],
+                "expires_at": signature["expires_at"],
+                "domain": signature["domain"],
+            }),
+        );
+        let response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            vouchers(&state.identity_service, &"userB".to_string())
+                .await
+                .unwrap(),
+            vec![address]
+        );
+    }
+}
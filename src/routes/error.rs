@@ -0,0 +1,259 @@
+// Error responses of the routes. Every error is created with a stable `ErrorCode`, the body is
+// `{"error": <English message>, "code": <code>}` plus the fields of the error. Messages are
+// looked up by code in `locales/`, `{name}` placeholders are filled with the fields, e.g.
+// `max` of `proof_batch_too_large`. `LocalizedErrorMiddleware` translates them by the code.
+
+use serde_json::{Map, Value};
+use tide::{Response, http::mime};
+
+use crate::routes::i18n::{DEFAULT_LANGUAGE, translate};
+
+// `ErrorCode` with the list of all codes and their catalog keys
+macro_rules! error_codes {
+    ($($variant:ident => $code:literal,)*) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ErrorCode {
+            $($variant,)*
+        }
+
+        impl ErrorCode {
+            pub const ALL: &[ErrorCode] = &[$(ErrorCode::$variant,)*];
+
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(ErrorCode::$variant => $code,)*
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    InvalidQuery => "invalid_query",
+    InvalidAfter => "invalid_after",
+    InvalidAt => "invalid_at",
+    InvalidTop => "invalid_top",
+    InvalidSince => "invalid_since",
+    InvalidDays => "invalid_days",
+    InvalidId => "invalid_id",
+    InvalidIdt => "invalid_idt",
+    InvalidName => "invalid_name",
+    InvalidPetname => "invalid_petname",
+    InvalidScope => "invalid_scope",
+    InvalidContact => "invalid_contact",
+    InvalidChallenge => "invalid_challenge",
+    InvalidReason => "invalid_reason",
+    BadRequest => "bad_request",
+    FromAfterTo => "from_after_to",
+    DepthTooLarge => "depth_too_large",
+    TopRequiresVouchTree => "top_requires_vouch_tree",
+    SignatureVerificationFailed => "signature_verification_failed",
+    SignatureExpired => "signature_expired",
+    SignatureExpiryTooFar => "signature_expiry_too_far",
+    SignatureDomainMismatch => "signature_domain_mismatch",
+    SignatureDomainMissing => "signature_domain_missing",
+    SignatureVersionUnsupported => "signature_version_unsupported",
+    SigningFailed => "signing_failed",
+    TimestampOutOfBounds => "timestamp_out_of_bounds",
+    TimestampOlderThanVouch => "timestamp_older_than_vouch",
+    AdminRequired => "admin_required",
+    ModeratorRequired => "moderator_required",
+    NotModerator => "not_moderator",
+    NotVerifier => "not_verifier",
+    NotSuccessor => "not_successor",
+    SuccessorNotModerator => "successor_not_moderator",
+    UnknownServer => "unknown_server",
+    UnknownCategory => "unknown_category",
+    UnknownPenaltyReason => "unknown_penalty_reason",
+    UnknownFlag => "unknown_flag",
+    UnknownFormat => "unknown_format",
+    UnknownProvider => "unknown_provider",
+    UnsupportedContactKind => "unsupported_contact_kind",
+    AddressBlocked => "address_blocked",
+    AddressNotVouchee => "address_not_vouchee",
+    AccountLinked => "account_linked",
+    TooManyPetnames => "too_many_petnames",
+    CategoryNotAllowedToVouch => "category_not_allowed_to_vouch",
+    ProofConsentRequired => "proof_consent_required",
+    VouchConsentRequired => "vouch_consent_required",
+    ConsentVerificationFailed => "consent_verification_failed",
+    SelfVouch => "self_vouch",
+    VouchAlreadyPending => "vouch_already_pending",
+    NoPendingVouch => "no_pending_vouch",
+    VouchRefreshTooEarly => "vouch_refresh_too_early",
+    InvalidVouchNote => "invalid_vouch_note",
+    VouchNoteRequiresLocalVoucher => "vouch_note_requires_local_voucher",
+    NoteSignatureVerificationFailed => "note_signature_verification_failed",
+    MaxBalanceExceeded => "max_balance_exceeded",
+    ModeratorLimitExceeded => "moderator_limit_exceeded",
+    UserProvenTwice => "user_proven_twice",
+    ProofRejected => "proof_rejected",
+    ProofBatchRejected => "proof_batch_rejected",
+    ProofBatchEmpty => "proof_batch_empty",
+    ProofBatchTooLarge => "proof_batch_too_large",
+    ProofNotFound => "proof_not_found",
+    PenaltyNotFound => "penalty_not_found",
+    PenaltyAlreadyDecided => "penalty_already_decided",
+    ReportNotFound => "report_not_found",
+    ReportAlreadyResolved => "report_already_resolved",
+    CannotReportYourself => "cannot_report_yourself",
+    NoSummary => "no_summary",
+    UserNotArchived => "user_not_archived",
+    BalanceNotComputed => "balance_not_computed",
+    NameNotFound => "name_not_found",
+    NameResolutionFailed => "name_resolution_failed",
+    RegistryRequestFailed => "registry_request_failed",
+    ServiceAccountNotFound => "service_account_not_found",
+    RouteOutsideScopes => "route_outside_scopes",
+    SignerMissing => "signer_missing",
+    ServerReadOnly => "server_read_only",
+    ServerNotRegistered => "server_not_registered",
+    ServerNotCrossSigned => "server_not_cross_signed",
+    ServerHandshakeFailed => "server_handshake_failed",
+    ServerSignatureVerificationFailed => "server_signature_verification_failed",
+    HomeServerUnavailable => "home_server_unavailable",
+    HistoryUnavailable => "history_unavailable",
+    TooManyRequests => "too_many_requests",
+    RequestTimedOut => "request_timed_out",
+    FailedToAddAdmin => "failed_to_add_admin",
+    FailedToRemoveAdmin => "failed_to_remove_admin",
+    FailedToAddModerator => "failed_to_add_moderator",
+    FailedToRemoveModerator => "failed_to_remove_moderator",
+    FailedToSetSuccessor => "failed_to_set_successor",
+    FailedToRestoreUser => "failed_to_restore_user",
+    FailedToAddServer => "failed_to_add_server",
+    FailedToRemoveServer => "failed_to_remove_server",
+    FailedToSetHome => "failed_to_set_home",
+    FailedToSetFlag => "failed_to_set_flag",
+    FailedToSetContact => "failed_to_set_contact",
+    RestitutionDisabled => "restitution_disabled",
+    AmountNotPositive => "amount_not_positive",
+    ContributionExceedsBalance => "contribution_exceeds_balance",
+    WithdrawalExceedsContribution => "withdrawal_exceeds_contribution",
+    NoRemainingPenalty => "no_remaining_penalty",
+    RestitutionPoolEmpty => "restitution_pool_empty",
+    AppealOpen => "appeal_open",
+    AppealNotFound => "appeal_not_found",
+    AppealAlreadyDecided => "appeal_already_decided",
+    AppealNotApproved => "appeal_not_approved",
+}
+
+// fills the `{name}` placeholders of the message with the fields
+pub fn interpolate(message: &str, fields: &Map<String, Value>) -> String {
+    fields
+        .iter()
+        .fold(message.to_string(), |message, (name, value)| {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            message.replace(&format!("{{{name}}}"), &value)
+        })
+}
+
+#[derive(Clone, Debug)]
+pub struct ApiError {
+    status: u16,
+    code: ErrorCode,
+    fields: Map<String, Value>,
+}
+
+impl ApiError {
+    pub fn new(status: u16, code: ErrorCode) -> Self {
+        Self {
+            status,
+            code,
+            fields: Map::new(),
+        }
+    }
+
+    pub fn bad_request(code: ErrorCode) -> Self {
+        Self::new(400, code)
+    }
+
+    // adds a field to the body, it also fills the placeholder of the same name
+    pub fn with(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.fields.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    // English message of the code
+    pub fn message(&self) -> String {
+        let message = translate(self.code.as_str(), DEFAULT_LANGUAGE).unwrap_or_default();
+        interpolate(message, &self.fields)
+    }
+
+    pub fn body(&self) -> Map<String, Value> {
+        let mut body = self.fields.clone();
+        body.insert("error".into(), self.message().into());
+        body.insert("code".into(), self.code.as_str().into());
+        body
+    }
+}
+
+impl From<ApiError> for Response {
+    fn from(error: ApiError) -> Self {
+        Response::builder(error.status)
+            .body(Value::Object(error.body()))
+            .content_type(mime::JSON)
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::i18n::CATALOGS;
+    use std::collections::BTreeSet;
+
+    fn placeholders(message: &str) -> BTreeSet<&str> {
+        message
+            .split('{')
+            .skip(1)
+            .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+            .collect()
+    }
+
+    #[test]
+    fn test_catalogs() {
+        let codes: BTreeSet<_> = ErrorCode::ALL.iter().map(|code| code.as_str()).collect();
+        assert_eq!(codes.len(), ErrorCode::ALL.len(), "codes must be unique");
+        for (language, catalog) in CATALOGS {
+            let catalog: Map<String, Value> = serde_json::from_str(catalog).unwrap();
+            let keys: BTreeSet<_> = catalog.keys().map(String::as_str).collect();
+            assert_eq!(
+                keys, codes,
+                "{language} must translate exactly the error codes"
+            );
+            for code in ErrorCode::ALL {
+                let english = translate(code.as_str(), DEFAULT_LANGUAGE).unwrap();
+                let message = catalog[code.as_str()].as_str().unwrap();
+                assert_eq!(
+                    placeholders(message),
+                    placeholders(english),
+                    "{language} {code:?} must have the placeholders of English"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_body() {
+        let error = ApiError::bad_request(ErrorCode::ProofBatchTooLarge).with("max", 1000);
+        assert_eq!(error.code(), ErrorCode::ProofBatchTooLarge);
+        assert_eq!(
+            Value::Object(error.body()),
+            serde_json::json!({
+                "error": "proof batch is too large, max is 1000 entries",
+                "code": "proof_batch_too_large",
+                "max": 1000,
+            })
+        );
+        let error = ApiError::bad_request(ErrorCode::BadRequest).with("reason", "missing field");
+        assert_eq!(error.message(), "bad request: missing field");
+    }
+}
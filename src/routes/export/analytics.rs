@@ -0,0 +1,88 @@
+use tide::{Body, Request, Response, http::Mime};
+
+use crate::{
+    export::{Pseudonymizer, analytics_stream},
+    routes::State,
+};
+
+pub const NDJSON_MIME: &str = "application/x-ndjson";
+
+pub async fn route(req: Request<State>) -> tide::Result {
+    let state = req.state();
+    let pseudonymizer = Pseudonymizer::from_server_key(&state.server_identity.private_key);
+    let reader = analytics_stream(state.identity_service.clone(), pseudonymizer);
+    let response = Response::builder(200)
+        .body(Body::from_reader(reader, None))
+        .content_type(NDJSON_MIME.parse::<Mime>()?)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{
+        IdtAmount,
+        proof::prove,
+        tests::{MODERATOR, PROOF_ID, USER_A},
+        vouch::vouch,
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        let user_b = "userB".to_string();
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&state.identity_service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+        let pseudonymizer = Pseudonymizer::from_server_key(&state.server_identity.private_key);
+
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse("http://example.com/export/analytics").unwrap(),
+        );
+        let mut server = tide::with_state(state);
+        server.at("/export/analytics").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.content_type().unwrap().essence(), NDJSON_MIME);
+        let body = response.body_string().await.unwrap();
+        assert!(!body.contains(USER_A));
+        assert!(!body.contains(&user_b));
+
+        let records: Vec<Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["type"], "edge");
+        assert_eq!(
+            records[0]["from"],
+            pseudonymizer.pseudonym(&USER_A.to_string())
+        );
+        assert_eq!(records[0]["to"], pseudonymizer.pseudonym(&user_b));
+        let balance = |user: &str| {
+            records
+                .iter()
+                .find(|r| r["type"] == "balance" && r["user"] == user)
+                .map(|r| r["idt"].clone())
+                .unwrap()
+        };
+        assert_eq!(
+            balance(&pseudonymizer.pseudonym(&USER_A.to_string())),
+            "100"
+        );
+        assert_eq!(balance(&pseudonymizer.pseudonym(&user_b)), "10");
+    }
+}
@@ -0,0 +1,35 @@
+use serde::Deserialize;
+use tide::Request;
+
+use crate::{
+    export::sync::Cursor,
+    routes::{
+        State,
+        error::{ApiError, ErrorCode},
+    },
+};
+
+pub mod analytics;
+pub mod penalties;
+pub mod vouches;
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    after: Option<String>,
+}
+
+// cursor of the last row the client has already processed, error if it is invalid
+fn after_cursor(req: &Request<State>, scope: &str) -> Result<Option<Cursor>, ApiError> {
+    let Ok(query) = req.query::<ExportQuery>() else {
+        return Err(ApiError::bad_request(ErrorCode::InvalidQuery));
+    };
+    match query.after {
+        None => Ok(None),
+        Some(token) => req
+            .state()
+            .cursors()
+            .decode(scope, &token)
+            .map(Some)
+            .ok_or(ApiError::bad_request(ErrorCode::InvalidAfter)),
+    }
+}
@@ -0,0 +1,89 @@
+use tide::{Body, Request, Response, http::Mime};
+
+use crate::{
+    export::sync::{PENALTY_CURSOR_SCOPE, penalty_stream},
+    routes::{
+        State,
+        export::{after_cursor, analytics::NDJSON_MIME},
+    },
+};
+
+pub async fn route(req: Request<State>) -> tide::Result {
+    let after = match after_cursor(&req, PENALTY_CURSOR_SCOPE) {
+        Ok(after) => after,
+        Err(error) => return Ok(error.into()),
+    };
+    let state = req.state();
+    let reader = penalty_stream(state.identity_service.clone(), state.cursors(), after);
+    let response = Response::builder(200)
+        .body(Body::from_reader(reader, None))
+        .content_type(NDJSON_MIME.parse::<Mime>()?)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{
+        IdtAmount,
+        proof::prove,
+        punish::punish,
+        tests::{MODERATOR, PROOF_ID, USER_A},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        punish(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(10),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse("http://example.com/export/penalties").unwrap(),
+        );
+        let mut server = tide::with_state(state);
+        server.at("/export/penalties").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body = response.body_string().await.unwrap();
+        let records: Vec<Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["type"], "moderator_penalty");
+        assert_eq!(records[0]["user"], USER_A);
+        assert_eq!(records[0]["amount"], 10);
+
+        let cursor = records[0]["cursor"].as_str().unwrap();
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!(
+                "http://example.com/export/penalties?after={cursor}"
+            ))
+            .unwrap(),
+        );
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(response.body_string().await.unwrap().is_empty());
+    }
+}
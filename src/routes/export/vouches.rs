@@ -0,0 +1,85 @@
+use tide::{Body, Request, Response, http::Mime};
+
+use crate::{
+    export::sync::{VOUCH_CURSOR_SCOPE, vouch_stream},
+    routes::{
+        State,
+        export::{after_cursor, analytics::NDJSON_MIME},
+    },
+};
+
+pub async fn route(req: Request<State>) -> tide::Result {
+    let after = match after_cursor(&req, VOUCH_CURSOR_SCOPE) {
+        Ok(after) => after,
+        Err(error) => return Ok(error.into()),
+    };
+    let state = req.state();
+    let reader = vouch_stream(state.identity_service.clone(), state.cursors(), after);
+    let response = Response::builder(200)
+        .body(Body::from_reader(reader, None))
+        .content_type(NDJSON_MIME.parse::<Mime>()?)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{tests::USER_A, vouch::vouch};
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn export(state: &State, query: &str) -> (u16, Vec<Value>) {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/export/vouches{query}")).unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/export/vouches").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        let body = response.body_string().await.unwrap();
+        if response.status() != 200 {
+            return (response.status().into(), vec![]);
+        }
+        assert_eq!(response.content_type().unwrap().essence(), NDJSON_MIME);
+        let records = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        (200, records)
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        for user in ["userB", "userC", "userD"] {
+            vouch(
+                &state.identity_service,
+                USER_A.to_string(),
+                user.to_string(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let (status, records) = export(&state, "").await;
+        assert_eq!(status, 200);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["type"], "vouch");
+        assert_eq!(records[0]["voucher"], USER_A);
+        assert_eq!(records[0]["vouchee"], "userB");
+
+        // resume after the first row
+        let cursor = records[0]["cursor"].as_str().unwrap();
+        let (status, rest) = export(&state, &format!("?after={cursor}")).await;
+        assert_eq!(status, 200);
+        assert_eq!(rest, records[1..]);
+
+        let cursor = records[2]["cursor"].as_str().unwrap();
+        let (_, rest) = export(&state, &format!("?after={cursor}")).await;
+        assert!(rest.is_empty());
+
+        let (status, _) = export(&state, "?after=invalid").await;
+        assert_eq!(status, 400);
+    }
+}
@@ -0,0 +1,168 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::{UserAddress, vouch_external::storage::ExternalVouchFilter},
+    routes::{
+        State,
+        error::{ApiError, ErrorCode},
+    },
+};
+
+// vouches returned by a single request, pass `next` as `after` to get the next page
+pub const EXTERNAL_VOUCHES_PAGE_SIZE: usize = 100;
+
+const CURSOR_SCOPE: &str = "external_vouches";
+
+#[derive(Deserialize)]
+struct ExternalVouchesQuery {
+    server: Option<UserAddress>,
+    since: Option<u64>,
+    until: Option<u64>,
+    after: Option<String>,
+}
+
+// vouches for the user relayed by other servers, ordered by timestamp, server and voucher
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let Ok(query) = req.query::<ExternalVouchesQuery>() else {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidQuery).into());
+    };
+    // timestamp, server and voucher of the last vouch of the previous page
+    let cursors = req.state().cursors();
+    let after = match query
+        .after
+        .as_deref()
+        .map(|c| cursors.decode(CURSOR_SCOPE, c))
+    {
+        None => None,
+        Some(Some(after)) => Some(after),
+        Some(None) => return Ok(ApiError::bad_request(ErrorCode::InvalidAfter).into()),
+    };
+    let filter = ExternalVouchFilter {
+        server: query.server,
+        since: query.since,
+        until: query.until,
+        after,
+        limit: Some(EXTERNAL_VOUCHES_PAGE_SIZE),
+    };
+    let vouches = req
+        .state()
+        .identity_service
+        .external_vouches
+        .find_vouchers(&user, &filter)
+        .await?;
+    // a full page may be followed by more vouches
+    let next = match vouches.last() {
+        Some(last) if vouches.len() == EXTERNAL_VOUCHES_PAGE_SIZE => {
+            let position = (last.timestamp, &last.server, &last.voucher);
+            Some(cursors.encode(CURSOR_SCOPE, &position))
+        }
+        _ => None,
+    };
+    Ok(Response::builder(200)
+        .body(json!({
+            "user": user,
+            "vouches": vouches,
+            "next": next,
+        }))
+        .content_type(mime::JSON)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::tests::USER_A;
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn external_vouches(state: &State, query: &str) -> (u16, Value) {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!(
+                "http://example.com/external_vouches/{USER_A}{query}"
+            ))
+            .unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/external_vouches/:user").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        (
+            response.status().into(),
+            response.body_json().await.unwrap(),
+        )
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        for (server, voucher, timestamp) in [("s1", "a", 30), ("s2", "b", 10), ("s1", "c", 20)] {
+            state
+                .identity_service
+                .vouch_external_with_timestamp(
+                    server.into(),
+                    voucher.into(),
+                    USER_A.into(),
+                    timestamp,
+                )
+                .await
+                .unwrap();
+        }
+
+        let (status, body) = external_vouches(&state, "").await;
+        assert_eq!(status, 200);
+        assert_eq!(body["user"], USER_A);
+        assert_eq!(
+            body["vouches"][0],
+            json!({"voucher": "b", "server": "s2", "timestamp": 10})
+        );
+        assert_eq!(body["vouches"].as_array().unwrap().len(), 3);
+        assert_eq!(body["next"], Value::Null);
+
+        let (_, body) = external_vouches(&state, "?server=s1&since=25").await;
+        let vouches = body["vouches"].as_array().unwrap();
+        assert_eq!(vouches.len(), 1);
+        assert_eq!(vouches[0]["voucher"], "a");
+        let (_, body) = external_vouches(&state, "?until=30").await;
+        assert_eq!(body["vouches"].as_array().unwrap().len(), 2);
+
+        let cursor = state.cursors().encode(CURSOR_SCOPE, &(10, "s2", "b"));
+        let (_, body) = external_vouches(&state, &format!("?after={cursor}")).await;
+        assert_eq!(body["vouches"][0]["voucher"], "c");
+
+        let (status, body) = external_vouches(&state, "?after=zz").await;
+        assert_eq!(status, 400);
+        assert_eq!(body["error"], "invalid after");
+    }
+
+    #[async_std::test]
+    async fn test_pages() {
+        let state = State::default();
+        for i in 0..EXTERNAL_VOUCHES_PAGE_SIZE as u64 + 1 {
+            state
+                .identity_service
+                .vouch_external_with_timestamp(
+                    "server".into(),
+                    format!("voucher{i}"),
+                    USER_A.into(),
+                    i,
+                )
+                .await
+                .unwrap();
+        }
+        let (_, body) = external_vouches(&state, "").await;
+        assert_eq!(
+            body["vouches"].as_array().unwrap().len(),
+            EXTERNAL_VOUCHES_PAGE_SIZE
+        );
+        let next = body["next"].as_str().unwrap();
+        let (_, body) = external_vouches(&state, &format!("?after={next}")).await;
+        assert_eq!(
+            body["vouches"],
+            json!([{"voucher": "voucher100", "server": "server", "timestamp": 100}])
+        );
+        assert_eq!(body["next"], Value::Null);
+    }
+}
@@ -0,0 +1,49 @@
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::routes::State;
+
+pub async fn route(req: Request<State>) -> tide::Result {
+    let flags = req.state().flags.flags().await?;
+    let response = Response::builder(200)
+        .body(json!(flags))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flags::Flag;
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        state
+            .flags
+            .set_flag(Flag::BanSelfVouch, true)
+            .await
+            .unwrap();
+
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse("http://example.com/flags").unwrap(),
+        );
+        let mut server = tide::with_state(state);
+        server.at("/flags").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "require_vouch_consent": false,
+                "ban_self_vouch": true,
+                "read_only": false,
+            })
+        );
+    }
+}
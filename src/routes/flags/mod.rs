@@ -0,0 +1,78 @@
+use tide::{Middleware, Next, Request};
+
+use crate::{
+    flags::Flag,
+    routes::{
+        State,
+        error::{ApiError, ErrorCode},
+    },
+};
+
+pub mod get_flags;
+pub mod set_flag;
+
+// route that stays writable in read only mode, so the mode can be turned off
+const SET_FLAG_PATH: &str = "/set_flag";
+// reads signed by the user, they only use a nonce
+const SIGNED_READ_PATHS: [&str; 1] = ["/vouch_notes"];
+
+// rejects requests that change state while the `read_only` flag is enabled
+pub struct ReadOnlyMiddleware;
+
+#[tide::utils::async_trait]
+impl Middleware<State> for ReadOnlyMiddleware {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let method = req.method();
+        if method == tide::http::Method::Get
+            || method == tide::http::Method::Head
+            || req.url().path() == SET_FLAG_PATH
+            || SIGNED_READ_PATHS.contains(&req.url().path())
+            || !req.state().flags.is_enabled(Flag::ReadOnly).await?
+        {
+            return Ok(next.run(req).await);
+        }
+        Ok(ApiError::new(503, ErrorCode::ServerReadOnly).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tide::http::{Method, Request as HttpRequest, Url};
+
+    async fn respond(state: &State, method: Method, path: &str) -> tide::http::Response {
+        let req = HttpRequest::new(
+            method,
+            Url::parse(&format!("http://example.com{path}")).unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.with(ReadOnlyMiddleware);
+        server.at("/vouch/:user").post(|_| async { Ok("vouched") });
+        server.at("/idt/:user").get(|_| async { Ok("balance") });
+        server.at(SET_FLAG_PATH).post(|_| async { Ok("flag set") });
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_read_only() {
+        let state = State::default();
+        assert_eq!(
+            respond(&state, Method::Post, "/vouch/userA").await.status(),
+            200
+        );
+
+        state.flags.set_flag(Flag::ReadOnly, true).await.unwrap();
+        let mut response = respond(&state, Method::Post, "/vouch/userA").await;
+        assert_eq!(response.status(), 503);
+        let body: serde_json::Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "server is read only");
+        assert_eq!(
+            respond(&state, Method::Get, "/idt/userA").await.status(),
+            200
+        );
+        assert_eq!(
+            respond(&state, Method::Post, SET_FLAG_PATH).await.status(),
+            200
+        );
+    }
+}
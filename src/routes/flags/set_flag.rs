@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    flags::Flag,
+    identity::UserAddress,
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        signed_body, verify_admin_action,
+    },
+    verify::{admins::admin_set_flag_message_prefix, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct SetFlagRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+    flag: String,
+    enabled: bool,
+}
+
+impl SignedRequest for SetFlagRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let body: SetFlagRequest = signed_body(&mut req).await?;
+    let sender = body.from.clone();
+    let Some(flag) = Flag::parse(&body.flag) else {
+        return Ok(ApiError::bad_request(ErrorCode::UnknownFlag).into());
+    };
+    let message_prefix = admin_set_flag_message_prefix(flag.as_str(), body.enabled);
+
+    if let Err(response) = verify_admin_action(
+        req.state(),
+        &sender,
+        body.signature,
+        &body.freshness,
+        &message_prefix,
+    )
+    .await
+    {
+        return Ok(response);
+    }
+
+    if req
+        .state()
+        .flags
+        .set_flag(flag, body.enabled)
+        .await
+        .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::FailedToSetFlag).into());
+    }
+    log::info!(
+        "Flag {} set to {} by {}",
+        flag.as_str(),
+        body.enabled,
+        sender
+    );
+
+    let response: HashMap<String, serde_json::Value> = HashMap::from([
+        ("flag".into(), flag.as_str().into()),
+        ("enabled".into(), body.enabled.into()),
+        ("from".into(), sender.into()),
+        ("nonce".into(), body.freshness.nonce.into()),
+    ]);
+    let response = Response::builder(200)
+        .body(json!(response))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use super::*;
+    use crate::{
+        admins::InMemoryAdminStorage,
+        verify::{expires_in, random_keypair, sign_message},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn set_flag(state: &State, private_key: &str, flag: &str, enabled: bool) -> Response {
+        let signature = sign_message(
+            private_key,
+            &state.server_identity.address,
+            &admin_set_flag_message_prefix(flag, enabled),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        let body = json!({
+            "from": signature.signer,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+            "flag": flag,
+            "enabled": enabled,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse("http://example.com/set_flag").unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/set_flag").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    fn admin_state(admin: &UserAddress) -> State {
+        State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::from([admin.clone()]),
+                HashSet::new(),
+            )),
+            ..Default::default()
+        }
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, admin) = random_keypair();
+        let state = admin_state(&admin);
+
+        let mut response = set_flag(&state, &private_key, "read_only", true).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["flag"], "read_only");
+        assert_eq!(body["enabled"], true);
+        assert!(state.flags.is_enabled(Flag::ReadOnly).await.unwrap());
+
+        let response = set_flag(&state, &private_key, "read_only", false).await;
+        assert_eq!(response.status(), 200);
+        assert!(!state.flags.is_enabled(Flag::ReadOnly).await.unwrap());
+    }
+
+    #[async_std::test]
+    async fn test_unknown_flag() {
+        let (private_key, admin) = random_keypair();
+        let state = admin_state(&admin);
+        let mut response = set_flag(&state, &private_key, "unknown", true).await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "unknown flag");
+    }
+
+    #[async_std::test]
+    async fn test_no_privilege() {
+        let (private_key, _) = random_keypair();
+        let state = admin_state(&"other_admin".to_string());
+        let response = set_flag(&state, &private_key, "read_only", true).await;
+        assert_eq!(response.status(), 403);
+        assert!(!state.flags.is_enabled(Flag::ReadOnly).await.unwrap());
+    }
+}
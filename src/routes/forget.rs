@@ -5,9 +5,17 @@ use serde_json::json;
 use tide::{Request, Response, http::mime};
 
 use crate::{
-    identity::{UserAddress, forget::forget, idt::balance},
-    routes::State,
-    verify::{forget::forget_verify, nonce::Nonce},
+    identity::{UserAddress, idt::balance},
+    notifications::Notification,
+    routes::{
+        SignedRequest, State, balance_changed,
+        error::{ApiError, ErrorCode},
+        freshness_error, signed_body, timestamp_error,
+    },
+    verify::{
+        forget::{forget_at_verify, forget_verify},
+        signature::Freshness,
+    },
 };
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -22,43 +30,98 @@ struct FromField {
 struct ForgetRequest {
     from: FromField,
     signature: String,
-    nonce: Nonce,
+    #[serde(flatten)]
+    freshness: Freshness,
+    // signed client time of the forget, stored instead of the server time
+    #[serde(default)]
+    timestamp: Option<u64>,
+}
+
+impl SignedRequest for ForgetRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
 }
 
 pub async fn route(mut req: Request<State>) -> tide::Result {
     let vouchee = req.param("user")?.to_string();
-    let body: ForgetRequest = req.body_json().await?;
+    let body: ForgetRequest = signed_body(&mut req).await?;
     let voucher = body.from;
     let voucher_user = voucher.user.clone();
 
-    if forget_verify(
-        body.signature,
-        &voucher_user,
-        body.nonce,
-        vouchee.clone(),
-        &*req.state().nonce_manager,
-    )
-    .await
-    .is_err()
-    {
-        return Ok(Response::builder(400)
-            .body(json!({"error": "signature verification failed"}))
-            .content_type(mime::JSON)
-            .build());
+    if let Some(response) = freshness_error(req.state(), &body.freshness) {
+        return Ok(response);
+    }
+    let service = &req.state().identity_service;
+    if let Some(timestamp) = body.timestamp {
+        if let Some(response) = timestamp_error(req.state(), timestamp) {
+            return Ok(response);
+        }
+        // cannot forget before the vouch was made
+        let vouched_at = service
+            .vouchers_with_time(&vouchee)
+            .await?
+            .get(&voucher_user)
+            .copied();
+        if vouched_at.is_some_and(|vouched_at| vouched_at > timestamp) {
+            return Ok(ApiError::bad_request(ErrorCode::TimestampOlderThanVouch).into());
+        }
+    }
+
+    let verified = match body.timestamp {
+        Some(timestamp) => {
+            forget_at_verify(
+                body.signature,
+                &voucher_user,
+                &body.freshness,
+                vouchee.clone(),
+                timestamp,
+                &*req.state().nonce_manager,
+            )
+            .await
+        }
+        None => {
+            forget_verify(
+                body.signature,
+                &voucher_user,
+                &body.freshness,
+                vouchee.clone(),
+                &*req.state().nonce_manager,
+            )
+            .await
+        }
+    };
+    if verified.is_err() {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
     }
 
-    forget(
-        &req.state().identity_service,
-        voucher_user.clone(),
-        vouchee.clone(),
-    )
-    .await?;
-    let voucher_balance = balance(&req.state().identity_service, &voucher_user).await?;
+    let timestamp = body.timestamp.unwrap_or_else(|| service.now());
+    let voucher_before = balance(service, &voucher_user).await?;
+    let vouchee_before = balance(service, &vouchee).await?;
+    service
+        .forget_with_timestamp(voucher_user.clone(), vouchee.clone(), timestamp)
+        .await?;
+    let voucher_balance = balance(service, &voucher_user).await?;
+    let vouchee_balance = balance(service, &vouchee).await?;
+
+    let state = req.state();
+    state
+        .notifications
+        .notify(
+            &vouchee,
+            Notification::Forgotten {
+                voucher: voucher_user.clone(),
+            },
+        )
+        .await;
+    balance_changed(state, &voucher_user, voucher_before, voucher_balance).await;
+    balance_changed(state, &vouchee, vouchee_before, vouchee_balance).await;
     let response: HashMap<String, serde_json::Value> = HashMap::from([
         ("from".into(), serde_json::to_value(&voucher)?),
         ("to".into(), vouchee.into()),
         ("idt".into(), voucher_balance.to_string().into()),
-        ("nonce".into(), body.nonce.into()),
+        ("nonce".into(), body.freshness.nonce.into()),
+        ("timestamp".into(), timestamp.into()),
     ]);
     let response = Response::builder(200)
         .body(json!(response))
@@ -72,11 +135,16 @@ mod tests {
     use super::*;
     use crate::{
         identity::{
+            IdtAmount,
             proof::prove,
             tests::{MODERATOR, PROOF_ID, USER_A},
             vouch::vouch,
         },
-        verify::{forget::forget_sign, random_keypair},
+        verify::{
+            expires_in,
+            forget::{forget_at_sign, forget_sign},
+            random_keypair,
+        },
     };
     use serde_json::Value;
     use tide::http::{Request as HttpRequest, Response, Url};
@@ -90,7 +158,7 @@ mod tests {
             &state.identity_service,
             user_address.clone(),
             MODERATOR.to_string(),
-            10000,
+            IdtAmount::new(10000),
             PROOF_ID,
         )
         .await
@@ -104,13 +172,21 @@ mod tests {
         .unwrap();
 
         let req_url = format!("/forget/{user_b}");
-        let signature = forget_sign(&private_key, user_b.to_string(), &*state.nonce_manager)
-            .await
-            .expect("Should sign successfully");
+        let signature = forget_sign(
+            &private_key,
+            &state.server_identity.address,
+            user_b.to_string(),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign successfully");
         let body = json!({
             "from": {"user": user_address},
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
         });
 
         let mut req = HttpRequest::new(
@@ -132,7 +208,65 @@ mod tests {
         assert_eq!(body["to"], user_b);
         // 500 IDT penalty for forgetting
         assert_eq!(body["idt"], "9500");
-        assert_eq!(body["nonce"], signature.nonce);
+        assert_eq!(body["nonce"], signature.freshness.nonce);
+    }
+
+    #[async_std::test]
+    async fn test_client_timestamp() {
+        let state = State::default();
+        let (private_key, user_address) = random_keypair();
+        let user_b = "userB";
+        let now = state.identity_service.now();
+        state
+            .identity_service
+            .vouch_with_timestamp(user_address.clone(), user_b.to_string(), now - 100)
+            .await
+            .unwrap();
+        let mut server = tide::with_state(state.clone());
+        server.at("/forget/:user").post(route);
+
+        for (timestamp, expected_status) in [(now - 200, 400), (now - 50, 200)] {
+            let signature = forget_at_sign(
+                &private_key,
+                &state.server_identity.address,
+                user_b.to_string(),
+                timestamp,
+                expires_in(60),
+                &*state.nonce_manager,
+            )
+            .await
+            .unwrap();
+            let body = json!({
+                "from": {"user": user_address},
+                "signature": signature.signature,
+                "nonce": signature.freshness.nonce,
+                "expires_at": signature.freshness.expires_at,
+                "domain": signature.freshness.domain,
+                "timestamp": timestamp,
+            });
+            let mut req = HttpRequest::new(
+                tide::http::Method::Post,
+                Url::parse(&format!("http://example.com/forget/{user_b}")).unwrap(),
+            );
+            req.set_body(body);
+            req.set_content_type(mime::JSON);
+            let mut response: Response = server.respond(req).await.unwrap();
+            assert_eq!(response.status(), expected_status);
+            let body: Value = response.body_json().await.unwrap();
+            if expected_status == 200 {
+                assert_eq!(body["timestamp"], timestamp);
+            } else {
+                assert_eq!(body["error"], "timestamp is older than the existing vouch");
+            }
+        }
+        // the penalty is dated by the client timestamp
+        let penalty = state
+            .identity_service
+            .forgotten_penalty(&user_address, &user_b.to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(penalty.timestamp, now - 50);
     }
 
     #[async_std::test]
@@ -172,7 +306,7 @@ mod tests {
             &state.identity_service,
             user_address.clone(),
             MODERATOR.to_string(),
-            10000,
+            IdtAmount::new(10000),
             PROOF_ID,
         )
         .await
@@ -186,13 +320,21 @@ mod tests {
         .unwrap();
 
         let req_url = format!("/forget/{user_b}");
-        let signature = forget_sign(&private_key, user_b.to_string(), &*state.nonce_manager)
-            .await
-            .expect("Should sign successfully");
+        let signature = forget_sign(
+            &private_key,
+            &state.server_identity.address,
+            user_b.to_string(),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign successfully");
         let body = json!({
             "from": {"user": user_address, "server": "server1"},
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
         });
 
         let mut req = HttpRequest::new(
@@ -0,0 +1,100 @@
+use serde::Deserialize;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::neighborhood::neighborhood,
+    routes::{
+        State,
+        error::{ApiError, ErrorCode},
+    },
+};
+
+const DEFAULT_DEPTH: usize = 2;
+// deeper neighborhoods cover most of the graph, clients should use the exports instead
+const MAX_DEPTH: usize = 4;
+
+#[derive(Deserialize)]
+struct GraphQuery {
+    depth: Option<usize>,
+}
+
+// users within `depth` vouches of the user with their balances and last activity, and the
+// vouches between them with their ages, as `nodes` and `links` arrays for graph views
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let Ok(query) = req.query::<GraphQuery>() else {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidQuery).into());
+    };
+    let depth = query.depth.unwrap_or(DEFAULT_DEPTH);
+    if depth > MAX_DEPTH {
+        return Ok(ApiError::bad_request(ErrorCode::DepthTooLarge).into());
+    }
+    let graph = neighborhood(&req.state().identity_service, &user, depth).await?;
+    let mut body = serde_json::to_value(&graph)?;
+    body["user"] = user.into();
+    body["depth"] = depth.into();
+    Ok(Response::builder(200)
+        .body(body)
+        .content_type(mime::JSON)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{
+        IdtAmount,
+        proof::prove,
+        tests::{MODERATOR, PROOF_ID, USER_A},
+        vouch::vouch,
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn get(state: State, url: &str) -> (u16, Value) {
+        let req = HttpRequest::new(tide::http::Method::Get, Url::parse(url).unwrap());
+        let mut server = tide::with_state(state);
+        server.at("/graph/:user").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        let status = response.status().into();
+        (status, response.body_json().await.unwrap())
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        let service = &state.identity_service;
+        prove(
+            service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(service, USER_A.to_string(), "userB".to_string())
+            .await
+            .unwrap();
+        vouch(service, "userB".to_string(), "userC".to_string())
+            .await
+            .unwrap();
+
+        let (status, body) = get(state.clone(), "http://example.com/graph/userA").await;
+        assert_eq!(status, 200);
+        assert_eq!(body["depth"], 2);
+        assert_eq!(body["nodes"].as_array().unwrap().len(), 3);
+        assert_eq!(body["nodes"][0]["id"], USER_A);
+        assert_eq!(body["nodes"][0]["idt"], 100);
+        assert_eq!(body["links"].as_array().unwrap().len(), 2);
+        assert_eq!(body["links"][0]["source"], USER_A);
+        assert_eq!(body["links"][0]["target"], "userB");
+        assert_eq!(body["links"][0]["age"], 0);
+
+        let (_, body) = get(state.clone(), "http://example.com/graph/userA?depth=1").await;
+        assert_eq!(body["nodes"].as_array().unwrap().len(), 2);
+
+        let (status, _) = get(state, "http://example.com/graph/userA?depth=5").await;
+        assert_eq!(status, 400);
+    }
+}
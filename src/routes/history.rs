@@ -0,0 +1,153 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::routes::{
+    State,
+    error::{ApiError, ErrorCode},
+};
+
+// changes returned by a single request, clients request the next page with the cursor
+pub const HISTORY_PAGE_SIZE: usize = 100;
+
+const CURSOR_SCOPE: &str = "history";
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    after: Option<String>,
+}
+
+// vouches given and received, proofs, penalties and forgets of the user in the order they
+// happened, for support and dispute resolution. The returned cursor points at the last
+// change of the page, or equals `after` if there are no more changes.
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let Ok(query) = req.query::<HistoryQuery>() else {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidQuery).into());
+    };
+    let cursors = req.state().cursors();
+    let after = match query
+        .after
+        .as_deref()
+        .map(|c| cursors.decode(CURSOR_SCOPE, c))
+    {
+        None => 0,
+        Some(Some(seq)) => seq,
+        Some(None) => return Ok(ApiError::bad_request(ErrorCode::InvalidAfter).into()),
+    };
+    let changes = req
+        .state()
+        .changes
+        .user_changes(&user, after, HISTORY_PAGE_SIZE)
+        .await?;
+    let cursor = changes.last().map(|c| c.seq).unwrap_or(after);
+    let history: Vec<_> = changes
+        .into_iter()
+        .map(|c| {
+            json!({
+                "cursor": cursors.encode(CURSOR_SCOPE, &c.seq),
+                "recorded_at": c.recorded_at,
+                "change": c.event,
+            })
+        })
+        .collect();
+    let response = Response::builder(200)
+        .body(json!({
+            "user": user,
+            "history": history,
+            "cursor": cursors.encode(CURSOR_SCOPE, &cursor),
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identity::{
+            IdtAmount,
+            forget::forget,
+            proof::prove,
+            tests::{MODERATOR, PROOF_ID, USER_A},
+            vouch::vouch,
+        },
+        routes::tests::recorded_state,
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn history(state: &State, path: &str) -> (u16, Value) {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/history/{path}")).unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/history/:user").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        let body = response.body_json().await.unwrap();
+        (response.status().into(), body)
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = recorded_state();
+        let service = &state.identity_service;
+        let (user_b, user_c) = ("userB".to_string(), "userC".to_string());
+        prove(
+            service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+        vouch(service, user_c.clone(), USER_A.to_string())
+            .await
+            .unwrap();
+        vouch(service, user_c.clone(), user_b.clone())
+            .await
+            .unwrap();
+        forget(service, USER_A.to_string(), user_b.clone())
+            .await
+            .unwrap();
+
+        let (status, body) = history(&state, USER_A).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["user"], USER_A);
+        let types: Vec<&str> = body["history"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["change"]["type"].as_str().unwrap())
+            .collect();
+        assert!(types.starts_with(&["set_proof", "vouch", "vouch"]));
+        assert!(types.contains(&"remove_vouch"));
+        assert!(types.contains(&"set_forgotten_penalty"));
+        assert_eq!(
+            body["cursor"],
+            body["history"].as_array().unwrap().last().unwrap()["cursor"]
+        );
+
+        // vouch of userC to userB is not in the history of userA
+        let (_, body) = history(&state, &user_b).await;
+        let list = body["history"].as_array().unwrap();
+        let cursor = list[0]["cursor"].as_str().unwrap();
+        let (_, page) = history(&state, &format!("{user_b}?after={cursor}")).await;
+        assert_eq!(page["history"].as_array().unwrap().len(), list.len() - 1);
+        let cursor = body["cursor"].as_str().unwrap();
+        let (_, page) = history(&state, &format!("{user_b}?after={cursor}")).await;
+        assert!(page["history"].as_array().unwrap().is_empty());
+        assert_eq!(page["cursor"], cursor);
+
+        let (_, body) = history(&state, "unknown").await;
+        assert!(body["history"].as_array().unwrap().is_empty());
+        let (status, _) = history(&state, &format!("{user_b}?after=invalid")).await;
+        assert_eq!(status, 400);
+    }
+}
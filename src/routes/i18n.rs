@@ -0,0 +1,209 @@
+// Translated error messages. Error responses keep their JSON shape, `error` is translated by
+// its `code`, see `routes::error`, to the first language of `Accept-Language` with a catalog.
+// Catalogs are keyed by code and bundled from `locales/`, messages missing from a catalog
+// fall back to English.
+
+use std::{collections::HashMap, sync::LazyLock};
+
+use serde_json::Value;
+use tide::{Middleware, Next, Request, http::mime};
+
+use crate::routes::{State, error::interpolate};
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+pub const CATALOGS: [(&str, &str); 3] = [
+    ("en", include_str!("../../locales/en.json")),
+    ("es", include_str!("../../locales/es.json")),
+    ("ru", include_str!("../../locales/ru.json")),
+];
+
+// language - code - message
+static MESSAGES: LazyLock<HashMap<&str, HashMap<String, String>>> = LazyLock::new(|| {
+    CATALOGS
+        .into_iter()
+        .map(|(language, catalog)| {
+            let catalog = serde_json::from_str(catalog).expect("locale catalogs must be valid");
+            (language, catalog)
+        })
+        .collect()
+});
+
+// message of the code in the language, English if the catalog of the language misses it
+pub fn translate(code: &str, language: &str) -> Option<&'static str> {
+    [language, DEFAULT_LANGUAGE]
+        .into_iter()
+        .find_map(|language| MESSAGES.get(language)?.get(code))
+        .map(String::as_str)
+}
+
+// first language of an `Accept-Language` header with a catalog by quality, e.g. `es` for
+// `fr;q=0.9, es-MX;q=0.8`
+pub fn negotiate(header: Option<&str>) -> &'static str {
+    let Some(header) = header else {
+        return DEFAULT_LANGUAGE;
+    };
+    let mut ranges: Vec<(&str, f32)> = header
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';').map(str::trim);
+            let tag = parts.next().filter(|tag| !tag.is_empty())?;
+            let quality = parts
+                .find_map(|p| p.strip_prefix("q="))
+                .map(|q| q.parse().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            Some((tag, quality))
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect();
+    // stable, so ranges of the same quality keep their order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranges
+        .into_iter()
+        .find_map(|(tag, _)| {
+            let primary = tag.split('-').next()?.to_ascii_lowercase();
+            CATALOGS
+                .iter()
+                .map(|(language, _)| *language)
+                .find(|language| *language == primary)
+        })
+        .unwrap_or(DEFAULT_LANGUAGE)
+}
+
+// translates the errors with a code in the value, also nested ones like the entries of a
+// rejected proof batch. Returns whether any error was translated.
+fn localize(value: &mut Value, language: &str) -> bool {
+    match value {
+        Value::Object(fields) => {
+            let mut localized = false;
+            let message = fields
+                .get("code")
+                .and_then(Value::as_str)
+                .filter(|_| fields.get("error").is_some_and(Value::is_string))
+                .and_then(|code| translate(code, language));
+            if let Some(message) = message {
+                let message = interpolate(message, fields);
+                fields.insert("error".into(), message.into());
+                localized = true;
+            }
+            for value in fields.values_mut() {
+                localized |= localize(value, language);
+            }
+            localized
+        }
+        Value::Array(values) => values.iter_mut().fold(false, |localized, value| {
+            localize(value, language) | localized
+        }),
+        _ => false,
+    }
+}
+
+// translates the `error` of JSON error responses, see the module comment
+pub struct LocalizedErrorMiddleware;
+
+#[tide::utils::async_trait]
+impl Middleware<State> for LocalizedErrorMiddleware {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let language = negotiate(req.header("Accept-Language").map(|h| h.as_str()));
+        let mut response = next.run(req).await;
+        if !(response.status().is_client_error() || response.status().is_server_error())
+            || response.content_type() != Some(mime::JSON)
+        {
+            return Ok(response);
+        }
+        let bytes = response.take_body().into_bytes().await?;
+        let mut body = match serde_json::from_slice::<Value>(&bytes) {
+            Ok(body) => body,
+            Err(_) => {
+                response.set_body(bytes);
+                response.set_content_type(mime::JSON);
+                return Ok(response);
+            }
+        };
+        if localize(&mut body, language) {
+            response.insert_header("Content-Language", language);
+        }
+        response.set_body(body);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::{
+        error::{ApiError, ErrorCode},
+        setup_routes,
+    };
+    use tide::http::{Method, Request as HttpRequest, Response, Url};
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(
+            translate("invalid_query", "ru"),
+            Some("недопустимый запрос")
+        );
+        assert_eq!(translate("invalid_query", "de"), Some("invalid query"));
+        assert_eq!(translate("unknown", "en"), None);
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(None), "en");
+        assert_eq!(negotiate(Some("ru")), "ru");
+        assert_eq!(negotiate(Some("es-MX,es;q=0.9")), "es");
+        assert_eq!(negotiate(Some("fr;q=0.9, ru;q=0.5, es;q=0.8")), "es");
+        assert_eq!(negotiate(Some("es;q=0, *")), "en");
+        assert_eq!(negotiate(Some("de")), "en");
+    }
+
+    #[test]
+    fn test_localize() {
+        let entry = ApiError::bad_request(ErrorCode::MaxBalanceExceeded).with("max", "1.5");
+        let mut body = Value::Object(
+            ApiError::bad_request(ErrorCode::ProofBatchRejected)
+                .with("results", vec![Value::Object(entry.body())])
+                .body(),
+        );
+        assert!(localize(&mut body, "es"));
+        assert_eq!(body["error"], "lote de pruebas rechazado");
+        assert_eq!(
+            body["results"][0]["error"],
+            "se superó el saldo máximo, el máximo es 1.5 IDT"
+        );
+        assert_eq!(body["results"][0]["code"], "max_balance_exceeded");
+
+        // errors without a code are left as they are
+        let mut body = serde_json::json!({"error": "remote failure"});
+        assert!(!localize(&mut body, "es"));
+        assert_eq!(body["error"], "remote failure");
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let mut server = tide::with_state(State::default());
+        setup_routes(&mut server);
+
+        let mut req = HttpRequest::new(
+            Method::Get,
+            Url::parse("http://example.com/history/userA?after=bad").unwrap(),
+        );
+        req.insert_header("Accept-Language", "es-ES,es;q=0.9,en;q=0.8");
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(response["Content-Language"].as_str(), "es");
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "valor de after no válido");
+        assert_eq!(body["code"], "invalid_after");
+
+        // English by default
+        let req = HttpRequest::new(
+            Method::Get,
+            Url::parse("http://example.com/history/userA?after=bad").unwrap(),
+        );
+        let mut response: Response = server.respond(req).await.unwrap();
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "invalid after");
+        assert_eq!(body["code"], "invalid_after");
+    }
+}
@@ -1,30 +1,228 @@
 use std::collections::HashMap;
 
+use serde::Deserialize;
 use serde_json::json;
 use tide::{Request, Response, http::mime};
 
-use crate::{identity::idt::balance, routes::State};
+use crate::{
+    ens::valid_name,
+    identity::{
+        UserAddress,
+        idt::{
+            BalanceBreakdown, MAX_TOP_VOUCHERS_SIZE, TOP_VOUCHERS_SIZE, balance, balance_breakdown,
+            balance_projection, vouch_tree_balance_with_budget, vouch_tree_balance_with_top,
+        },
+    },
+    routes::{
+        State,
+        cache::Validators,
+        error::{ApiError, ErrorCode},
+    },
+    scoring::strategy::StrategyKind,
+};
+
+// projections further than ten years are not useful and only cost tree walks
+pub const MAX_PROJECTION_DAYS: u64 = 3650;
+// set on balances computed with a part of the vouch tree, see `server.compute_budget`
+pub const APPROXIMATE_HEADER: &str = "X-Approximate";
+
+#[derive(Deserialize)]
+struct BalanceQuery {
+    // number of top vouchers counted for every user in the vouch tree
+    top: Option<u16>,
+    // past timestamp to compute the balance at, needs event sourced storage
+    at: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ProjectionQuery {
+    days: u64,
+}
+
+fn breakdown_json(breakdown: &BalanceBreakdown) -> serde_json::Value {
+    json!({
+        "proven": breakdown.proven.to_string(),
+        "proof_decay": breakdown.proof_decay.to_string(),
+        "grace_period_end": breakdown.grace_period_end,
+        "penalty": breakdown.penalty.to_string(),
+    })
+}
 
 pub async fn route(req: Request<State>) -> tide::Result {
-    let user = req.param("user")?;
-    let balance = balance(&req.state().identity_service, &user.to_string()).await?;
-    let response: HashMap<String, serde_json::Value> = HashMap::from([
+    let user = req.param("user")?.to_string();
+    user_balance(&req, user, None).await
+}
+
+// balance of the address the ENS name points to
+pub async fn by_name_route(req: Request<State>) -> tide::Result {
+    let name = req.param("name")?.to_lowercase();
+    if !valid_name(&name) {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidName).into());
+    }
+    let user = match req.state().names.resolve(&name).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return Ok(ApiError::new(404, ErrorCode::NameNotFound).into());
+        }
+        Err(e) => {
+            log::warn!("Failed to resolve {}: {}", name, e);
+            return Ok(ApiError::new(502, ErrorCode::NameResolutionFailed).into());
+        }
+    };
+    user_balance(&req, user, Some(name)).await
+}
+
+// primary ENS name of the user, failed lookups only leave the name out
+async fn primary_name(state: &State, user: &UserAddress) -> Option<String> {
+    match state.names.lookup(user).await {
+        Ok(name) => name,
+        Err(e) => {
+            log::warn!("Failed to look up the name of {}: {}", user, e);
+            None
+        }
+    }
+}
+
+// balance response of the user, `name` is looked up if not given
+async fn user_balance(
+    req: &Request<State>,
+    user: UserAddress,
+    name: Option<String>,
+) -> tide::Result {
+    let Ok(query) = req.query::<BalanceQuery>() else {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidQuery).into());
+    };
+    let state = req.state();
+    // balances in the past are not cached
+    let validators = match query.at {
+        Some(_) => None,
+        None => {
+            let validators = Validators::balance(
+                state.changes.latest().await?,
+                state.identity_service.now(),
+                state.config.cache.balance_window,
+            );
+            Some(match query.top {
+                Some(top) => validators.variant(&format!("top{top}")),
+                None => validators,
+            })
+        }
+    };
+    if let Some(validators) = &validators {
+        if let Some(response) = validators.not_modified(req, &state.config.cache)? {
+            return Ok(response);
+        }
+    }
+    let past_service = match (query.at, &state.history) {
+        (None, _) => None,
+        (Some(_), None) => return Ok(ApiError::bad_request(ErrorCode::HistoryUnavailable).into()),
+        (Some(at), Some(_)) if at > state.identity_service.now() => {
+            return Ok(ApiError::bad_request(ErrorCode::InvalidAt).into());
+        }
+        (Some(at), Some(history)) => Some(history.service_at(&state.identity_service, at).await?),
+    };
+    let service = past_service.as_ref().unwrap_or(&state.identity_service);
+    let vouch_tree = state.config.scoring.strategy == StrategyKind::VouchTree;
+    match query.top {
+        Some(top) if top == 0 || top > MAX_TOP_VOUCHERS_SIZE => {
+            return Ok(ApiError::bad_request(ErrorCode::InvalidTop).into());
+        }
+        Some(_) if !vouch_tree => {
+            return Ok(ApiError::bad_request(ErrorCode::TopRequiresVouchTree).into());
+        }
+        _ => {}
+    }
+    let budget = state.config.server.compute_budget;
+    let (balance, approximate) = match query.top {
+        _ if vouch_tree && budget > 0 => {
+            let top = query.top.unwrap_or(TOP_VOUCHERS_SIZE);
+            let (balance, approximate) =
+                vouch_tree_balance_with_budget(service, &user, top, budget).await?;
+            // only the default balance is bounded by the category, as with `balance`
+            let balance = match query.top {
+                Some(_) => balance,
+                None => service.clamp_balance(&user, balance).await?,
+            };
+            (balance, approximate)
+        }
+        None => (balance(service, &user).await?, false),
+        Some(top) => (
+            vouch_tree_balance_with_top(service, &user, top).await?,
+            false,
+        ),
+    };
+    let breakdown = balance_breakdown(service, &user).await?;
+    let name = match name {
+        Some(name) => Some(name),
+        None => primary_name(state, &user).await,
+    };
+    let mut response: HashMap<String, serde_json::Value> = HashMap::from([
         ("user".into(), user.into()),
         ("idt".into(), balance.to_string().into()),
+        ("breakdown".into(), breakdown_json(&breakdown)),
     ]);
-    let response = Response::builder(200)
+    if let Some(name) = name {
+        response.insert("name".into(), name.into());
+    }
+    if let Some(top) = query.top {
+        response.insert("top".into(), top.into());
+    }
+    if let Some(at) = query.at {
+        response.insert("at".into(), at.into());
+    }
+    if approximate {
+        response.insert("approximate".into(), true.into());
+    }
+    let mut response = Response::builder(200)
         .body(json!(response))
         .content_type(mime::JSON)
         .build();
+    if approximate {
+        response.insert_header(APPROXIMATE_HEADER, "compute-budget");
+    }
+    if let Some(validators) = validators {
+        validators.apply(&state.config.cache, &mut response);
+    }
     Ok(response)
 }
 
+// balance after `days` days assuming nothing but decay happens till then
+pub async fn projection_route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let days = match req.query::<ProjectionQuery>() {
+        Ok(query) if query.days <= MAX_PROJECTION_DAYS => query.days,
+        _ => return Ok(ApiError::bad_request(ErrorCode::InvalidDays).into()),
+    };
+    let service = &req.state().identity_service;
+    let projection = balance_projection(service, &user, days).await?;
+    let response = json!({
+        "user": user,
+        "days": days,
+        "timestamp": projection.timestamp,
+        "idt": projection.balance.to_string(),
+        "breakdown": breakdown_json(&projection.breakdown),
+    });
+    Ok(Response::builder(200)
+        .body(response)
+        .content_type(mime::JSON)
+        .build())
+}
+
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use super::*;
-    use crate::identity::{
-        proof::prove,
-        tests::{MODERATOR, PROOF_ID, USER_A},
+    use crate::{
+        config::{Config, IdentitySection},
+        ens::InMemoryNameResolver,
+        events::{InMemoryEventLog, storage::EventSourcedStorage},
+        identity::{
+            IdentityService, IdtAmount,
+            proof::prove,
+            tests::{MODERATOR, PROOF_ID, START_TIMESTAMP, USER_A, service_with_mock_clock},
+            vouch::vouch,
+        },
     };
     use serde_json::Value;
     use tide::http::{Request as HttpRequest, Response, Url};
@@ -37,7 +235,7 @@ mod tests {
             &state.identity_service,
             USER_A.to_string(),
             MODERATOR.to_string(),
-            100,
+            IdtAmount::new(100),
             PROOF_ID,
         )
         .await
@@ -56,12 +254,317 @@ mod tests {
         let body: Value = response.body_json().await.unwrap();
         assert_eq!(body["user"], USER_A);
         assert_eq!(body["idt"], "100");
+        assert_eq!(body["breakdown"]["proven"], "100");
+        assert_eq!(body["breakdown"]["proof_decay"], "0");
+        assert_eq!(body["breakdown"]["penalty"], "0");
+        assert!(body.get("name").is_none());
+    }
+
+    #[async_std::test]
+    async fn test_names() {
+        let names = Arc::new(InMemoryNameResolver::default());
+        names.set_name("alice.eth", &USER_A.to_string(), true).await;
+        names
+            .set_name("other.eth", &USER_A.to_string(), false)
+            .await;
+        let state = State {
+            names,
+            ..Default::default()
+        };
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        let mut server = tide::with_state(state);
+        server.at("/idt/:user").get(route);
+        server.at("/idt/by_name/:name").get(by_name_route);
+        let get = |path: &str| {
+            HttpRequest::new(
+                tide::http::Method::Get,
+                Url::parse(&format!("http://example.com{path}")).unwrap(),
+            )
+        };
+
+        let mut response: Response = server
+            .respond(get(&format!("/idt/{USER_A}")))
+            .await
+            .unwrap();
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["name"], "alice.eth");
+
+        let mut response: Response = server.respond(get("/idt/by_name/Other.eth")).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["user"], USER_A);
+        assert_eq!(body["name"], "other.eth");
+        assert_eq!(body["idt"], "100");
+
+        let response: Response = server.respond(get("/idt/by_name/bob.eth")).await.unwrap();
+        assert_eq!(response.status(), 404);
+        let response: Response = server.respond(get("/idt/by_name/bob")).await.unwrap();
+        assert_eq!(response.status(), 400);
+    }
+
+    #[async_std::test]
+    async fn test_top() {
+        let state = State::default();
+        let service = &state.identity_service;
+        for (i, voucher) in ["userB", "userC", "userD"].into_iter().enumerate() {
+            prove(
+                service,
+                voucher.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(100 * (i as u64 + 1)),
+                PROOF_ID,
+            )
+            .await
+            .unwrap();
+            vouch(service, voucher.to_string(), USER_A.to_string())
+                .await
+                .unwrap();
+        }
+        let mut server = tide::with_state(state);
+        server.at("/idt/:user").get(route);
+
+        let get = |query: &str| {
+            HttpRequest::new(
+                tide::http::Method::Get,
+                Url::parse(&format!("http://example.com/idt/{USER_A}{query}")).unwrap(),
+            )
+        };
+        let mut response: Response = server.respond(get("")).await.unwrap();
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["idt"], "60");
+        assert!(body.get("top").is_none());
+
+        // only userD and userC are counted
+        let mut response: Response = server.respond(get("?top=2")).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["idt"], "50");
+        assert_eq!(body["top"], 2);
+
+        for query in ["?top=0", "?top=21", "?top=abc"] {
+            let response: Response = server.respond(get(query)).await.unwrap();
+            assert_eq!(response.status(), 400);
+        }
+    }
+
+    #[async_std::test]
+    async fn test_compute_budget() {
+        let mut config = Config::default();
+        config.server.compute_budget = 1;
+        let state = State {
+            config: Arc::new(config),
+            ..Default::default()
+        };
+        let service = &state.identity_service;
+        for user in [USER_A, "userB", "userC"] {
+            prove(
+                service,
+                user.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(1000),
+                PROOF_ID,
+            )
+            .await
+            .unwrap();
+        }
+        vouch(service, "userB".to_string(), USER_A.to_string())
+            .await
+            .unwrap();
+        vouch(service, "userC".to_string(), "userB".to_string())
+            .await
+            .unwrap();
+        let mut server = tide::with_state(state);
+        server.at("/idt/:user").get(route);
+        let get = |user: &str| {
+            HttpRequest::new(
+                tide::http::Method::Get,
+                Url::parse(&format!("http://example.com/idt/{user}")).unwrap(),
+            )
+        };
+
+        // the vouchers of userB are beyond the budget, so userC is not counted
+        let mut response: Response = server.respond(get(USER_A)).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response[APPROXIMATE_HEADER], "compute-budget");
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["idt"], "1100");
+        assert_eq!(body["approximate"], true);
+
+        let mut response: Response = server.respond(get("userC")).await.unwrap();
+        assert!(response.header(APPROXIMATE_HEADER).is_none());
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["idt"], "1000");
+        assert!(body.get("approximate").is_none());
+    }
+
+    #[async_std::test]
+    async fn test_at() {
+        let get = |query: &str| {
+            HttpRequest::new(
+                tide::http::Method::Get,
+                Url::parse(&format!("http://example.com/idt/{USER_A}{query}")).unwrap(),
+            )
+        };
+        let mut server = tide::with_state(State::default());
+        server.at("/idt/:user").get(route);
+        let response: Response = server.respond(get("?at=1")).await.unwrap();
+        assert_eq!(response.status(), 400);
+
+        let (service, clock) = service_with_mock_clock();
+        let history = Arc::new(
+            EventSourcedStorage::open(Arc::new(InMemoryEventLog::default()), clock.clone(), 0)
+                .await
+                .unwrap(),
+        );
+        let state = State {
+            identity_service: IdentityService {
+                vouches: history.clone(),
+                proofs: history.clone(),
+                penalties: history.clone(),
+                ..service
+            },
+            history: Some(history),
+            ..Default::default()
+        };
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        clock.advance(86400);
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(500),
+            PROOF_ID + 1,
+        )
+        .await
+        .unwrap();
+
+        let mut server = tide::with_state(state);
+        server.at("/idt/:user").get(route);
+        let mut response: Response = server.respond(get("")).await.unwrap();
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["idt"], "500");
+
+        let query = format!("?at={}", START_TIMESTAMP + 86400 - 1);
+        let mut response: Response = server.respond(get(&query)).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["idt"], "100");
+        assert_eq!(body["at"], START_TIMESTAMP + 86400 - 1);
+
+        let query = format!("?at={}", START_TIMESTAMP + 2 * 86400);
+        let response: Response = server.respond(get(&query)).await.unwrap();
+        assert_eq!(response.status(), 400);
+    }
+
+    #[async_std::test]
+    async fn test_grace_period() {
+        let (service, clock) = service_with_mock_clock();
+        let state = State {
+            identity_service: IdentityService {
+                config: IdentitySection {
+                    proof_grace_period: 7 * 86400,
+                    ..Default::default()
+                },
+                ..service
+            },
+            ..Default::default()
+        };
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        clock.advance(8 * 86400);
+
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/idt/{USER_A}")).unwrap(),
+        );
+        let mut server = tide::with_state(state);
+        server.at("/idt/:user").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["idt"], "99");
+        assert_eq!(body["breakdown"]["proof_decay"], "1");
+        assert_eq!(
+            body["breakdown"]["grace_period_end"],
+            START_TIMESTAMP + 7 * 86400
+        );
+    }
+
+    #[async_std::test]
+    async fn test_projection() {
+        let (service, _clock) = service_with_mock_clock();
+        let state = State {
+            identity_service: service,
+            ..Default::default()
+        };
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+
+        let mut server = tide::with_state(state);
+        server.at("/idt/:user/projection").get(projection_route);
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!(
+                "http://example.com/idt/{USER_A}/projection?days=30"
+            ))
+            .unwrap(),
+        );
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["days"], 30);
+        assert_eq!(body["timestamp"], START_TIMESTAMP + 30 * 86400);
+        assert_eq!(body["idt"], "70");
+        assert_eq!(body["breakdown"]["proven"], "70");
+        assert_eq!(body["breakdown"]["proof_decay"], "30");
+
+        for query in ["", "?days=-1", "?days=abc", "?days=100000"] {
+            let req = HttpRequest::new(
+                tide::http::Method::Get,
+                Url::parse(&format!(
+                    "http://example.com/idt/{USER_A}/projection{query}"
+                ))
+                .unwrap(),
+            );
+            let response: Response = server.respond(req).await.unwrap();
+            assert_eq!(response.status(), 400);
+        }
     }
 
     #[async_std::test]
     async fn test_bad_route() {
         let state = State::default();
-        let req_url = format!("/idt");
+        let req_url = "/idt".to_string();
         let req = HttpRequest::new(
             tide::http::Method::Get,
             Url::parse(&format!("http://example.com{}", req_url)).unwrap(),
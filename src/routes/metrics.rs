@@ -0,0 +1,93 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::routes::State;
+
+// requests counted by the first path segment, e.g. `idt`, since the server start
+#[derive(Default)]
+pub struct RouteCounters {
+    by_route: Mutex<BTreeMap<String, u64>>,
+    total: AtomicU64,
+}
+
+impl RouteCounters {
+    pub fn record(&self, route: &str) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut by_route) = self.by_route.lock() {
+            *by_route.entry(route.to_string()).or_default() += 1;
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    pub fn by_route(&self) -> BTreeMap<String, u64> {
+        self.by_route
+            .lock()
+            .map(|by_route| by_route.clone())
+            .unwrap_or_default()
+    }
+}
+
+// counters of anomalies since the server start and connection pools of the SQL storages
+pub async fn route(req: Request<State>) -> tide::Result {
+    let state = req.state();
+    let (timeouts, rejections) = (&state.request_timeouts, &state.request_rejections);
+    let response = Response::builder(200)
+        .body(json!({
+            "request_timeouts": {
+                "total": timeouts.total(),
+                "by_route": timeouts.by_route(),
+            },
+            "rejected_requests": {
+                "total": rejections.total(),
+                "by_route": rejections.by_route(),
+            },
+            "future_timestamps": state.identity_service.future_timestamps.count(),
+            "pools": state.pools.stats(),
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pools;
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse("http://example.com/metrics").unwrap(),
+        );
+        let state = State::default();
+        state.request_timeouts.record("idt");
+        state.identity_service.future_timestamps.clamp(100, 150);
+        let pool = pools::connect("metrics", "sqlite::memory:").await.unwrap();
+        sqlx::query("SELECT 1").execute(&pool).await.unwrap();
+        state.pools.register(&pool);
+        let mut server = tide::with_state(state);
+        server.at("/metrics").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["request_timeouts"]["total"], 1);
+        assert_eq!(body["request_timeouts"]["by_route"]["idt"], 1);
+        assert!(body["rejected_requests"]["total"].is_u64());
+        assert_eq!(body["future_timestamps"], 1);
+        assert_eq!(body["pools"]["metrics"]["acquires"], 1);
+    }
+}
@@ -1,25 +1,108 @@
 use std::sync::Arc;
 
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::json;
-use tide::{Response, http::mime};
+use tide::{Request, Response, Server, StatusCode};
 
 use crate::{
     admins::{AdminStorage, InMemoryAdminStorage},
-    identity::{IdentityService, UserAddress},
-    servers::storage::{InMemoryServerStorage, ServerStorage},
+    archive::storage::{ArchiveStorage, InMemoryArchiveStorage},
+    attestations::storage::{AttestationStorage, InMemoryAttestationStorage},
+    changes::storage::{ChangeLog, InMemoryChangeLog},
+    config::Config,
+    cursor::CursorKey,
+    ens::{NameResolver, NoNameResolver},
+    events::storage::EventSourcedStorage,
+    federation::{
+        BALANCE_UPDATE_PATH, FederationClient, HttpFederationClient,
+        cache::TtlCache,
+        storage::{HomeStorage, InMemoryHomeStorage},
+    },
+    flags::storage::{FlagStorage, InMemoryFlagStorage},
+    identity::{IdentityService, IdtAmount, UserAddress},
+    notifications::NotificationDispatcher,
+    outbox::storage::{InMemoryOutboxStorage, OutboxStorage},
+    pending_penalties::storage::{InMemoryPendingPenaltyStorage, PendingPenaltyStorage},
+    pending_vouches::storage::{InMemoryPendingVouchStorage, PendingVouchStorage},
+    petnames::storage::{InMemoryPetnameStorage, PetnameStorage},
+    pools::Pools,
+    reports::storage::{InMemoryReportStorage, ReportStorage},
+    restitution::{
+        PledgeLocks,
+        storage::{InMemoryRestitutionStorage, RestitutionStorage},
+    },
+    routes::{
+        error::{ApiError, ErrorCode},
+        metrics::RouteCounters,
+    },
+    servers::{
+        ServerIdentity,
+        storage::{InMemoryServerStorage, ServerStorage},
+    },
+    service_accounts::storage::{InMemoryServiceAccountStorage, ServiceAccountStorage},
+    storage::StorageInfo,
+    summaries::storage::{InMemorySummaryStorage, SummaryStorage},
     verify::{
-        nonce::{InMemoryNonceManager, Nonce, NonceManager},
+        admins::admin_bound_message_prefix,
+        check_expiry,
+        error::Error,
+        nonce::{InMemoryNonceManager, NonceManager},
+        signature::{Freshness, canonical_body},
         verify_message,
     },
+    vouch_notes::storage::{InMemoryVouchNoteStorage, VouchNoteStorage},
 };
 
 pub mod admins;
+pub mod attestations;
+pub mod cache;
+pub mod categories;
+pub mod changes;
+pub mod claim_home;
+pub mod commitment;
+pub mod concurrency;
+pub mod contact;
+pub mod dev;
+pub mod error;
+pub mod export;
+pub mod external_vouches;
+pub mod flags;
 pub mod forget;
+pub mod graph;
+pub mod history;
+pub mod i18n;
 pub mod idt;
+pub mod metrics;
+pub mod moderator_reputation;
+pub mod naming;
+pub mod outbox;
+pub mod penalties;
+pub mod pending_penalties;
+pub mod pending_vouches;
+pub mod personhood;
+pub mod petname;
 pub mod proof;
+pub mod proof_status;
+pub mod proxy;
 pub mod punish;
+pub mod reports;
+pub mod resolve;
+pub mod restitution;
 pub mod servers;
+pub mod service_accounts;
+pub mod stats;
+pub mod subgraph;
+pub mod time;
+pub mod timeout;
+pub mod trust;
+#[cfg(feature = "ui")]
+pub mod ui;
+pub mod version;
 pub mod vouch;
+pub mod vouch_notes;
+pub mod vouchees;
+pub mod vouchers;
+pub mod well_known;
 
 #[derive(Clone)]
 pub struct State {
@@ -27,6 +110,44 @@ pub struct State {
     pub admin_storage: Arc<dyn AdminStorage>,
     pub nonce_manager: Arc<dyn NonceManager>,
     pub server_storage: Arc<dyn ServerStorage>,
+    pub federation_client: Arc<dyn FederationClient>,
+    pub resolve_cache: Arc<TtlCache<serde_json::Value>>,
+    // read requests cancelled by `TimeoutMiddleware`
+    pub request_timeouts: Arc<RouteCounters>,
+    // requests rejected by `ConcurrencyMiddleware`
+    pub request_rejections: Arc<RouteCounters>,
+    // connection pools of the SQL storages
+    pub pools: Arc<Pools>,
+    // ENS names of users, see `ens` module
+    pub names: Arc<dyn NameResolver>,
+    pub home_storage: Arc<dyn HomeStorage>,
+    pub server_identity: ServerIdentity,
+    pub notifications: Arc<NotificationDispatcher>,
+    pub flags: Arc<dyn FlagStorage>,
+    pub archive_storage: Arc<dyn ArchiveStorage>,
+    pub attestations: Arc<dyn AttestationStorage>,
+    pub reports: Arc<dyn ReportStorage>,
+    pub service_accounts: Arc<dyn ServiceAccountStorage>,
+    pub pending_vouches: Arc<dyn PendingVouchStorage>,
+    pub petnames: Arc<dyn PetnameStorage>,
+    // outbound webhook pushes waiting for delivery
+    pub outbox: Arc<dyn OutboxStorage>,
+    // latest scheduled summary, see `summaries` module
+    pub summaries: Arc<dyn SummaryStorage>,
+    // penalties of moderators waiting for an admin, see `pending_penalties` module
+    pub pending_penalties: Arc<dyn PendingPenaltyStorage>,
+    // encrypted notes of vouchers, see `vouch_notes` module
+    pub vouch_notes: Arc<dyn VouchNoteStorage>,
+    // community pool offsetting penalties, see `restitution` module
+    pub restitution: Arc<dyn RestitutionStorage>,
+    pub pledge_locks: Arc<PledgeLocks>,
+    // mutations recorded by the storages of `identity_service`
+    pub changes: Arc<dyn ChangeLog>,
+    // event sourced storage answering queries about the past, if enabled
+    pub history: Option<Arc<EventSourcedStorage>>,
+    // published by `GET /version`
+    pub storage_info: StorageInfo,
+    pub config: Arc<Config>,
 }
 
 impl Default for State {
@@ -36,39 +157,415 @@ impl Default for State {
             admin_storage: Arc::new(InMemoryAdminStorage::default()),
             nonce_manager: Arc::new(InMemoryNonceManager::default()),
             server_storage: Arc::new(InMemoryServerStorage::default()),
+            federation_client: Arc::new(HttpFederationClient),
+            resolve_cache: Arc::new(TtlCache::default()),
+            request_timeouts: Arc::new(RouteCounters::default()),
+            request_rejections: Arc::new(RouteCounters::default()),
+            pools: Arc::default(),
+            names: Arc::new(NoNameResolver),
+            home_storage: Arc::new(InMemoryHomeStorage::default()),
+            server_identity: ServerIdentity::default(),
+            notifications: Arc::new(NotificationDispatcher::default()),
+            flags: Arc::new(InMemoryFlagStorage::default()),
+            archive_storage: Arc::new(InMemoryArchiveStorage::default()),
+            attestations: Arc::new(InMemoryAttestationStorage::default()),
+            reports: Arc::new(InMemoryReportStorage::default()),
+            service_accounts: Arc::new(InMemoryServiceAccountStorage::default()),
+            pending_vouches: Arc::new(InMemoryPendingVouchStorage::default()),
+            petnames: Arc::new(InMemoryPetnameStorage::default()),
+            outbox: Arc::new(InMemoryOutboxStorage::default()),
+            summaries: Arc::new(InMemorySummaryStorage::default()),
+            pending_penalties: Arc::new(InMemoryPendingPenaltyStorage::default()),
+            vouch_notes: Arc::new(InMemoryVouchNoteStorage::default()),
+            restitution: Arc::new(InMemoryRestitutionStorage::default()),
+            pledge_locks: Arc::new(PledgeLocks::default()),
+            changes: Arc::new(InMemoryChangeLog::default()),
+            history: None,
+            storage_info: StorageInfo::default(),
+            config: Arc::new(Config::default()),
+        }
+    }
+}
+
+impl State {
+    // signs the pagination cursors of the routes, see `cursor` module
+    pub fn cursors(&self) -> CursorKey {
+        CursorKey::new(&self.server_identity.private_key)
+    }
+}
+
+pub fn setup_routes(server: &mut Server<State>) {
+    server.with(flags::ReadOnlyMiddleware);
+    server.with(service_accounts::ServiceAccountMiddleware);
+    server.with(proxy::ProxyMiddleware);
+    server.with(time::ServerTimeMiddleware);
+    server.with(naming::JsonNamingMiddleware);
+    server.with(i18n::LocalizedErrorMiddleware);
+    server.with(timeout::TimeoutMiddleware);
+    let concurrency = &server.state().config.server.concurrency;
+    server.with(concurrency::ConcurrencyMiddleware::new(concurrency));
+    server.at("/time").get(time::route);
+    if server.state().config.dev.sign_endpoint {
+        log::warn!("Serving /dev/sign, private keys are accepted by the server");
+        server.at("/dev/sign").post(dev::sign_route);
+    }
+    server
+        .at("/.well-known/identity-server")
+        .get(well_known::route);
+    server.at("/version").get(version::route);
+    server.at("/metrics").get(metrics::route);
+    server.at("/stats").get(stats::route);
+    server.at("/stats/:user").get(stats::user_route);
+    server.at("/idt/:user").get(idt::route);
+    server.at("/idt/by_name/:name").get(idt::by_name_route);
+    server
+        .at("/idt/:user/projection")
+        .get(idt::projection_route);
+    server.at("/vouch/:user").post(vouch::route);
+    server
+        .at("/vouch/:user/confirm")
+        .post(pending_vouches::confirm_route);
+    server
+        .at("/pending_vouches/:user")
+        .get(pending_vouches::route);
+    server.at("/forget/:user").post(forget::route);
+    server.at("/proof/:user").post(proof::route);
+    server.at("/proof_batch").post(proof::batch_route);
+    server.at("/proofs/transfer").post(proof::transfer_route);
+    server.at("/punish/:user").post(punish::route);
+    server
+        .at("/pending_penalties")
+        .get(pending_penalties::route);
+    server
+        .at("/pending_penalties/:id/approve")
+        .post(pending_penalties::approve_route);
+    server
+        .at("/pending_penalties/:id/reject")
+        .post(pending_penalties::reject_route);
+    server.at("/restitution").get(restitution::route);
+    server
+        .at("/restitution/contribute")
+        .post(restitution::contribute_route);
+    server
+        .at("/restitution/withdraw")
+        .post(restitution::withdraw_route);
+    server
+        .at("/restitution/appeal")
+        .post(restitution::appeal_route);
+    server.at("/restitution/:user").get(restitution::user_route);
+    server
+        .at("/restitution/:user/appeal/approve")
+        .post(restitution::approve_appeal_route);
+    server
+        .at("/restitution/:user/appeal/reject")
+        .post(restitution::reject_appeal_route);
+    server
+        .at("/restitution/:user/grant")
+        .post(restitution::grant_route);
+    server
+        .at("/category/:user")
+        .get(categories::route)
+        .post(categories::set_route);
+    server.at("/report/:user").post(reports::report::route);
+    server.at("/reports").get(reports::get_reports::route);
+    server.at("/reports/latest").get(reports::latest::route);
+    server
+        .at("/reports/:id/resolve")
+        .post(reports::resolve::route);
+    server.at("/vouchers/:user").get(vouchers::route);
+    server.at("/vouch_notes").post(vouch_notes::route);
+    server
+        .at("/external_vouches/:user")
+        .get(external_vouches::route);
+    server.at("/vouchees/:user").get(vouchees::route);
+    server.at("/petname/:address").post(petname::route);
+    server.at("/commitment/:user").get(commitment::route);
+    server.at("/resolve/:user").get(resolve::route);
+    server.at("/trust/:user").get(trust::route);
+    server.at("/proofs/:user/status").get(proof_status::route);
+    server.at("/penalties/recent").get(penalties::recent_route);
+    server.at("/penalties/:user").get(penalties::route);
+    server.at("/subgraph/:user").get(subgraph::route);
+    server.at("/graph/:user").get(graph::route);
+    server
+        .at("/moderators/:user/reputation")
+        .get(moderator_reputation::route);
+    server.at("/contact").post(contact::route);
+    server.at("/is_admin/:user").get(admins::is_admin::route);
+    server.at("/add_admin/:user").post(admins::add_admin::route);
+    server
+        .at("/remove_admin/:user")
+        .post(admins::remove_admin::route);
+    server
+        .at("/is_moderator/:user")
+        .get(admins::is_moderator::route);
+    server
+        .at("/add_moderator/:user")
+        .post(admins::add_moderator::route);
+    server
+        .at("/remove_moderator/:user")
+        .post(admins::remove_moderator::route);
+    server
+        .at("/restore_user/:user")
+        .post(admins::restore_user::route);
+    server
+        .at("/revoke_moderator_proofs/:moderator")
+        .post(admins::revoke_moderator_proofs::route);
+    server
+        .at("/set_successor/:moderator")
+        .post(admins::set_successor::route);
+    server
+        .at("/moderators/:user/limit")
+        .post(admins::set_proof_limit::route);
+    server
+        .at("/admin/check_integrity")
+        .post(admins::check_integrity::route);
+    server.at("/admin/outbox").post(outbox::route);
+    server.at("/export/analytics").get(export::analytics::route);
+    server.at("/export/vouches").get(export::vouches::route);
+    server.at("/export/penalties").get(export::penalties::route);
+    server.at("/changes").get(changes::route);
+    server.at("/diff").get(changes::diff_route);
+    server.at("/history/:user").get(history::route);
+    server
+        .at("/attestations/start/:user")
+        .post(attestations::start::route);
+    server
+        .at("/attestations/complete/:user")
+        .post(attestations::complete::route);
+    server
+        .at("/attestations/:user")
+        .get(attestations::get_attestations::route);
+    server.at("/personhood/:user").post(personhood::route);
+    server.at("/servers").get(servers::get_servers::route);
+    server.at("/add_server").post(servers::add_server::route);
+    server
+        .at("/handshake/:domain")
+        .get(servers::handshake::route);
+    server
+        .at("/federation/cross_sign")
+        .post(servers::cross_sign::submit_route);
+    server
+        .at("/federation/cross_sign/:server")
+        .get(servers::cross_sign::route);
+    server
+        .at("/federation/cross_signatures")
+        .get(servers::cross_sign::list_route);
+    server
+        .at(BALANCE_UPDATE_PATH)
+        .post(servers::balance_update::route);
+    server
+        .at("/attest_server")
+        .post(servers::attest_server::route);
+    server
+        .at("/remove_server")
+        .post(servers::remove_server::route);
+    server.at("/set_home/:user").post(servers::set_home::route);
+    server.at("/claim_home").post(claim_home::route);
+    server
+        .at("/service_accounts")
+        .get(service_accounts::get_service_accounts::route);
+    server
+        .at("/add_service_account/:address")
+        .post(service_accounts::add_service_account::route);
+    server
+        .at("/remove_service_account/:address")
+        .post(service_accounts::remove_service_account::route);
+    server.at("/flags").get(flags::get_flags::route);
+    server.at("/set_flag").post(flags::set_flag::route);
+    #[cfg(feature = "ui")]
+    ui::setup_routes(server);
+}
+
+// bodies of requests signed with a `Freshness`, see `signed_body`
+pub trait SignedRequest: DeserializeOwned {
+    fn freshness_mut(&mut self) -> &mut Freshness;
+}
+
+// parses the body of a signed request. For canonical signatures the canonical JSON of the
+// whole body is kept in the freshness, so the signature is checked against every field.
+pub async fn signed_body<T: SignedRequest>(req: &mut Request<State>) -> tide::Result<T> {
+    let mut value: serde_json::Value = req.body_json().await?;
+    // signatures cover the body as sent, before its fields are renamed
+    let canonical = canonical_body(&value);
+    naming::request_fields(&req.state().config.server, &mut value)?;
+    let mut body: T = serde_json::from_value(value)
+        .map_err(|e| tide::Error::new(StatusCode::UnprocessableEntity, e))?;
+    let freshness = body.freshness_mut();
+    if freshness.canonical {
+        freshness.body = Some(canonical);
+    }
+    Ok(body)
+}
+
+// error response for signatures of another server, expired signatures and
+// signatures valid for longer than configured
+pub fn freshness_error(state: &State, freshness: &Freshness) -> Option<Response> {
+    let config = &state.config.signatures;
+    let now = state.identity_service.now();
+    let code = match &freshness.domain {
+        Some(domain) if *domain != state.server_identity.address => {
+            ErrorCode::SignatureDomainMismatch
+        }
+        None if !config.allow_legacy => ErrorCode::SignatureDomainMissing,
+        _ if !config.versions.contains(&freshness.version.unwrap_or(0)) => {
+            ErrorCode::SignatureVersionUnsupported
         }
+        _ => match check_expiry(freshness.expires_at, now, config.max_age) {
+            Ok(()) => return None,
+            Err(Error::SignatureExpired(_)) => ErrorCode::SignatureExpired,
+            Err(_) => ErrorCode::SignatureExpiryTooFar,
+        },
+    };
+    Some(ApiError::bad_request(code).into())
+}
+
+// error response for client timestamps further than `signatures.max_timestamp_skew`
+// from the server time
+pub fn timestamp_error(state: &State, timestamp: u64) -> Option<Response> {
+    let now = state.identity_service.now();
+    if timestamp.abs_diff(now) <= state.config.signatures.max_timestamp_skew {
+        return None;
+    }
+    Some(
+        ApiError::bad_request(ErrorCode::TimestampOutOfBounds)
+            .with("now", now)
+            .into(),
+    )
+}
+
+// notifies the user and the servers of their external vouchers about a balance change
+pub async fn balance_changed(
+    state: &State,
+    user: &UserAddress,
+    before: IdtAmount,
+    after: IdtAmount,
+) {
+    state
+        .notifications
+        .balance_changed(user, before, after)
+        .await;
+    servers::balance_update::push_balance_update(state, user, before, after).await;
+}
+
+// counter-signature of the subject of a request, e.g. the vouchee of a vouch
+#[derive(Deserialize)]
+pub struct Consent {
+    pub signature: String,
+    #[serde(flatten)]
+    pub freshness: Freshness,
+}
+
+// privileged roles of the callers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Admin,
+    Moderator,
+}
+
+pub async fn roles(state: &State, user: &UserAddress) -> Vec<Role> {
+    let mut roles = vec![];
+    if state.admin_storage.check_admin(user).await.is_ok() {
+        roles.push(Role::Admin);
+    }
+    if state.admin_storage.check_moderator(user).await.is_ok() {
+        roles.push(Role::Moderator);
+    }
+    roles
+}
+
+// 403 response if `sender` does not have `role`. It states the required role, the roles
+// the sender has and the prefix of the message the request is signed over
+pub async fn check_role(
+    state: &State,
+    sender: &UserAddress,
+    role: Role,
+    message_prefix: &str,
+) -> Result<(), Response> {
+    let roles = roles(state, sender).await;
+    if roles.contains(&role) {
+        return Ok(());
     }
+    let code = match role {
+        Role::Admin => ErrorCode::AdminRequired,
+        Role::Moderator => ErrorCode::ModeratorRequired,
+    };
+    Err(ApiError::new(403, code)
+        .with("required_role", json!(role))
+        .with("roles", json!(roles))
+        .with("message_prefix", message_prefix)
+        .into())
 }
 
 pub async fn verify_admin_action(
     state: &State,
     sender: &UserAddress,
     signature: String,
-    nonce: Nonce,
+    freshness: &Freshness,
     message_prefix: &str,
 ) -> Result<(), Response> {
-    if state.admin_storage.check_admin(sender).await.is_err() {
-        return Err(Response::builder(403)
-            .body(json!({"error": "not admin"}))
-            .content_type(mime::JSON)
-            .build());
+    check_role(state, sender, Role::Admin, message_prefix).await?;
+
+    if let Some(response) = freshness_error(state, freshness) {
+        return Err(response);
     }
 
+    // legacy signatures without the domain still have to name this server
+    let message_prefix = match &freshness.domain {
+        None if !state.config.signatures.allow_unbound_admin => {
+            admin_bound_message_prefix(&state.server_identity.address, message_prefix)
+        }
+        _ => message_prefix.to_string(),
+    };
     if verify_message(
         signature,
         sender,
-        nonce,
-        message_prefix,
+        freshness,
+        &message_prefix,
         &*state.nonce_manager,
     )
     .await
     .is_err()
     {
-        return Err(Response::builder(400)
-            .body(json!({"error": "signature verification failed"}))
-            .content_type(mime::JSON)
-            .build());
+        return Err(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        changes::{recorder::ChangeRecorder, storage::InMemoryChangeLog},
+        identity::{
+            proof::storage::InMemoryProofStorage, punish::storage::InMemoryPenaltyStorage,
+            tests::service_with_mock_clock, vouch::storage::InMemoryVouchStorage,
+        },
+    };
+
+    // state whose vouches, proofs and penalties are recorded to `State::changes`. The clock
+    // is fixed, so time dependent responses do not change between requests.
+    pub fn recorded_state() -> State {
+        let log = Arc::new(InMemoryChangeLog::default());
+        let (service, _clock) = service_with_mock_clock();
+        let recorder = Arc::new(ChangeRecorder::new(
+            Arc::new(InMemoryVouchStorage::default()),
+            Arc::new(InMemoryProofStorage::default()),
+            Arc::new(InMemoryPenaltyStorage::default()),
+            log.clone(),
+            service.clock.clone(),
+        ));
+        State {
+            identity_service: IdentityService {
+                vouches: recorder.clone(),
+                proofs: recorder.clone(),
+                penalties: recorder,
+                ..service
+            },
+            changes: log,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::routes::State;
+
+pub async fn route(req: Request<State>) -> tide::Result {
+    let moderator = req.param("user")?.to_string();
+    let service = &req.state().identity_service;
+    let stats = service.moderator_stats(&moderator).await?;
+    let response = Response::builder(200)
+        .body(json!({
+            "moderator": moderator,
+            "proofs": stats.proofs,
+            "penalties": stats.penalties,
+            "revoked_proofs": stats.revoked_proofs,
+            "reputation": stats.reputation().to_float(),
+            // whether proofs of the moderator are scaled by the reputation
+            "weighted": service.config.reputation_weighted_proofs,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::IdentitySection,
+        identity::{
+            IdentityService, IdtAmount, ModeratorProof,
+            idt::balance,
+            proof::prove,
+            punish::punish,
+            tests::{MODERATOR, PROOF_ID, USER_A},
+        },
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn get_reputation(state: &State, moderator: &str) -> Value {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!(
+                "http://example.com/moderators/{moderator}/reputation"
+            ))
+            .unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/moderators/:user/reputation").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        response.body_json().await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State {
+            identity_service: IdentityService {
+                config: IdentitySection {
+                    reputation_weighted_proofs: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let service = &state.identity_service;
+        let body = get_reputation(&state, MODERATOR).await;
+        assert_eq!(body["proofs"], 0);
+        assert_eq!(body["reputation"], 1.0);
+        assert_eq!(body["weighted"], true);
+
+        let user_b = "userB".to_string();
+        for user in [USER_A.to_string(), user_b.clone(), "userC".to_string()] {
+            prove(
+                service,
+                user,
+                MODERATOR.to_string(),
+                IdtAmount::new(100),
+                PROOF_ID,
+            )
+            .await
+            .unwrap();
+        }
+        prove(
+            service,
+            "userD".to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        punish(
+            service,
+            user_b.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(10),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            balance(service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(100)
+        );
+
+        // revoked proofs of other moderators do not count
+        service
+            .proofs
+            .set_proof(
+                "userC".to_string(),
+                ModeratorProof {
+                    moderator: "other".to_string(),
+                    amount: IdtAmount::new(100),
+                    proof_id: PROOF_ID,
+                    timestamp: service.now(),
+                },
+            )
+            .await
+            .unwrap();
+        service
+            .revoke_moderator_proofs(&"other".to_string())
+            .await
+            .unwrap();
+        service
+            .revoke_moderator_proofs(&MODERATOR.to_string())
+            .await
+            .unwrap();
+        prove(
+            service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+
+        let body = get_reputation(&state, MODERATOR).await;
+        assert_eq!(body["moderator"], MODERATOR);
+        assert_eq!(body["proofs"], 5);
+        assert_eq!(body["penalties"], 1);
+        assert_eq!(body["revoked_proofs"], 3);
+        assert_eq!(body["reputation"], 0.4);
+        // the new proof is scaled by the reputation
+        assert_eq!(
+            balance(service, &USER_A.to_string()).await.unwrap(),
+            IdtAmount::new(40)
+        );
+    }
+}
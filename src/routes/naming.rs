@@ -0,0 +1,421 @@
+// Field naming of JSON bodies. Handlers and request structs use snake_case, bodies are renamed
+// at the edge: responses by `JsonNamingMiddleware` and requests by `request_fields`. Only the
+// declared fields of `FIELDS` are renamed, so map keys such as user addresses or flag names
+// stay as they are.
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tide::{Middleware, Next, Request, StatusCode, http::mime};
+
+use crate::{
+    config::{JsonNaming, ServerSection},
+    routes::State,
+};
+
+// snake_case fields of request and response bodies with more than one word, sorted. Fields
+// of a single word are the same in both namings. A new field of a body is added here.
+const FIELDS: &[&str] = &[
+    "activates_at",
+    "added_at",
+    "added_by",
+    "added_edges",
+    "added_proofs",
+    "allow_legacy",
+    "allow_unbound_admin",
+    "allowed_at",
+    "approval_threshold",
+    "archived_at",
+    "attestation_decay",
+    "attestation_interval",
+    "attestation_weight",
+    "attested_at",
+    "avg_wait_ms",
+    "balance_commitment",
+    "balance_thresholds",
+    "balance_update_threshold",
+    "balance_window",
+    "by_route",
+    "cache_ttl",
+    "can_vouch",
+    "challenge_ttl",
+    "check_interval",
+    "checked_at",
+    "claimed_at",
+    "compute_budget",
+    "computed_at",
+    "confirmed_at",
+    "connect_attempts",
+    "cooling_off",
+    "created_at",
+    "cross_signature",
+    "days_before_expiry",
+    "decay_per_day",
+    "decided_at",
+    "decided_by",
+    "drift_policy",
+    "error_rate",
+    "event_log",
+    "exceeds_limits",
+    "expires_at",
+    "expires_in",
+    "external_weight",
+    "flush_interval_ms",
+    "forget_grace_period",
+    "forgotten_penalties",
+    "from_ts",
+    "full_score_idt",
+    "future_timestamps",
+    "git_commit",
+    "grace_period_end",
+    "inactivity_decay",
+    "inactivity_period",
+    "initial_backoff",
+    "initial_backoff_ms",
+    "is_admin",
+    "is_moderator",
+    "json_naming",
+    "json_naming_compat",
+    "last_active",
+    "last_attested",
+    "last_error",
+    "last_seq",
+    "latency_ms",
+    "latest_message_version",
+    "local_weight",
+    "maturity_bonus",
+    "max_age",
+    "max_attempts",
+    "max_backoff",
+    "max_backoff_ms",
+    "max_balance",
+    "max_batch",
+    "max_connections",
+    "max_fan_out",
+    "max_iterations",
+    "max_ms",
+    "max_penalty_depth",
+    "max_timestamp_skew",
+    "max_wait_ms",
+    "mean_ms",
+    "message_prefix",
+    "message_versions",
+    "min_balance",
+    "min_edges",
+    "moderator_penalties",
+    "moderator_penalty",
+    "moderator_stats",
+    "new_users",
+    "next_attempt_at",
+    "noise_epoch",
+    "note_signature",
+    "p50_ms",
+    "p99_ms",
+    "penalty_amount",
+    "penalty_reasons",
+    "penalty_weight",
+    "period_end",
+    "period_start",
+    "private_key",
+    "proof_decay",
+    "proof_grace_period",
+    "proof_id",
+    "public_penalties",
+    "received_at",
+    "recompute_interval",
+    "recorded_at",
+    "registered_at",
+    "rejected_requests",
+    "remaining_penalty",
+    "remind_at",
+    "removed_edges",
+    "removed_proofs",
+    "removed_vouches",
+    "reputation_weighted_proofs",
+    "request_timeout_ms",
+    "request_timeouts",
+    "require_cross_signing",
+    "require_proof_consent",
+    "required_role",
+    "resolved_at",
+    "revoked_proofs",
+    "rpc_url",
+    "scaled_idt",
+    "schedule_weight",
+    "server_signature",
+    "service_accounts",
+    "setup_ms",
+    "sign_endpoint",
+    "similarity_threshold",
+    "slashed_stakes",
+    "snapshot_interval",
+    "stake_slashing",
+    "timeout_ms",
+    "timing_threshold",
+    "timing_window",
+    "to_ts",
+    "top_movers",
+    "tracked_users",
+    "tree_size",
+    "tree_size_warning",
+    "tree_sizes",
+    "two_phase_vouch",
+    "vouch_refresh",
+    "voucher_root",
+    "web_push",
+    "write_queue",
+    "zero_balance_period",
+];
+
+fn is_field(snake: &str) -> bool {
+    FIELDS.binary_search(&snake).is_ok()
+}
+
+fn is_camel_field(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_lowercase())
+        && key.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+pub fn to_camel_case(key: &str) -> String {
+    let mut result = String::with_capacity(key.len());
+    let mut upper = false;
+    for c in key.chars() {
+        match c {
+            '_' => upper = true,
+            c if upper => {
+                result.push(c.to_ascii_uppercase());
+                upper = false;
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+pub fn to_snake_case(key: &str) -> String {
+    let mut result = String::with_capacity(key.len() + 4);
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            result.push('_');
+            result.push(c.to_ascii_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+// renames the keys of every object in the value, `rename` returns `None` to keep a key
+fn rename_keys(value: &mut Value, rename: &impl Fn(&str) -> Option<String>) {
+    match value {
+        Value::Object(map) => {
+            let entries = std::mem::take(map);
+            for (key, mut field) in entries {
+                rename_keys(&mut field, rename);
+                map.insert(rename(&key).unwrap_or(key), field);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| rename_keys(item, rename)),
+        _ => {}
+    }
+}
+
+// first key of the value that is not a plain field name of the naming
+fn foreign_key(value: &Value, naming: JsonNaming) -> Option<String> {
+    match value {
+        Value::Object(map) => map.iter().find_map(|(key, field)| {
+            let foreign = match naming {
+                JsonNaming::SnakeCase => is_camel_field(key) && is_field(&to_snake_case(key)),
+                JsonNaming::CamelCase => is_field(key),
+            };
+            match foreign {
+                true => Some(key.clone()),
+                false => foreign_key(field, naming),
+            }
+        }),
+        Value::Array(items) => items.iter().find_map(|item| foreign_key(item, naming)),
+        _ => None,
+    }
+}
+
+// renames the fields of a request body to snake_case. Without `json_naming_compat` fields of
+// the other naming are rejected with 422. Bodies in snake_case are kept as they are, so
+// servers configured with the default naming read them as before.
+pub fn request_fields(config: &ServerSection, value: &mut Value) -> tide::Result<()> {
+    if !config.json_naming_compat && config.json_naming == JsonNaming::CamelCase {
+        if let Some(key) = foreign_key(value, config.json_naming) {
+            return Err(tide::Error::from_str(
+                StatusCode::UnprocessableEntity,
+                format!("field `{key}` is not in camelCase"),
+            ));
+        }
+    }
+    if config.json_naming_compat || config.json_naming == JsonNaming::CamelCase {
+        rename_keys(value, &|key| {
+            let snake = to_snake_case(key);
+            (is_camel_field(key) && snake != key && is_field(&snake)).then_some(snake)
+        });
+    }
+    Ok(())
+}
+
+// parses a JSON request body with the configured field naming
+pub async fn json_body<T: DeserializeOwned>(req: &mut Request<State>) -> tide::Result<T> {
+    let mut value: Value = req.body_json().await?;
+    request_fields(&req.state().config.server, &mut value)?;
+    serde_json::from_value(value).map_err(|e| tide::Error::new(StatusCode::UnprocessableEntity, e))
+}
+
+// renames the fields of JSON responses to `server.json_naming`
+pub struct JsonNamingMiddleware;
+
+#[tide::utils::async_trait]
+impl Middleware<State> for JsonNamingMiddleware {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let naming = req.state().config.server.json_naming;
+        let mut response = next.run(req).await;
+        if naming == JsonNaming::SnakeCase || response.content_type() != Some(mime::JSON) {
+            return Ok(response);
+        }
+        let bytes = response.take_body().into_bytes().await?;
+        match serde_json::from_slice::<Value>(&bytes) {
+            Ok(mut body) => {
+                rename_keys(&mut body, &|key| is_field(key).then(|| to_camel_case(key)));
+                response.set_body(body);
+            }
+            Err(_) => response.set_body(bytes),
+        }
+        response.set_content_type(mime::JSON);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{config::Config, flags::Flag, routes::setup_routes, verify::random_keypair};
+    use serde_json::json;
+    use tide::http::{Method, Request as HttpRequest, Response, Url};
+
+    fn config(json_naming: JsonNaming, json_naming_compat: bool) -> ServerSection {
+        ServerSection {
+            json_naming,
+            json_naming_compat,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_case() {
+        assert_eq!(to_camel_case("expires_at"), "expiresAt");
+        assert_eq!(to_camel_case("full_score_idt"), "fullScoreIdt");
+        assert_eq!(to_snake_case("expiresAt"), "expires_at");
+        assert_eq!(to_snake_case(&to_camel_case("proof_id")), "proof_id");
+    }
+
+    #[test]
+    fn test_request_fields() {
+        let body = json!({"expiresAt": 1, "proof_id": 2, "items": [{"maxAge": 3}], "0xAb": 4});
+
+        let mut value = body.clone();
+        request_fields(&config(JsonNaming::SnakeCase, false), &mut value).unwrap();
+        assert_eq!(value, body);
+
+        let mut value = body.clone();
+        assert!(request_fields(&config(JsonNaming::CamelCase, false), &mut value).is_err());
+
+        for naming in [JsonNaming::SnakeCase, JsonNaming::CamelCase] {
+            let mut value = body.clone();
+            request_fields(&config(naming, true), &mut value).unwrap();
+            assert_eq!(
+                value,
+                json!({"expires_at": 1, "proof_id": 2, "items": [{"max_age": 3}], "0xAb": 4})
+            );
+        }
+    }
+
+    #[test]
+    fn test_fields() {
+        assert!(FIELDS.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(FIELDS.iter().all(|field| field.contains('_')));
+        // flag names are map keys of `GET /flags`
+        assert!(Flag::ALL.iter().all(|flag| !is_field(flag.as_str())));
+    }
+
+    #[test]
+    fn test_map_keys() {
+        let body = json!({"expiresAt": 1, "notes": {"userA": "0x01", "user_b": "0x02"}});
+        let mut value = body.clone();
+        request_fields(&config(JsonNaming::CamelCase, true), &mut value).unwrap();
+        assert_eq!(
+            value,
+            json!({"expires_at": 1, "notes": {"userA": "0x01", "user_b": "0x02"}})
+        );
+    }
+
+    #[async_std::test]
+    async fn test_response() {
+        let state = State {
+            config: Arc::new(Config {
+                server: config(JsonNaming::CamelCase, false),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut server = tide::with_state(state);
+        setup_routes(&mut server);
+
+        let req = HttpRequest::new(Method::Get, Url::parse("http://example.com/time").unwrap());
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert!(body["futureTimestamps"].is_u64());
+        assert!(body.get("future_timestamps").is_none());
+
+        // flag names are data, not fields
+        let req = HttpRequest::new(Method::Get, Url::parse("http://example.com/flags").unwrap());
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["ban_self_vouch"], false);
+        assert!(body.get("banSelfVouch").is_none());
+    }
+
+    #[async_std::test]
+    async fn test_error_response() {
+        let state = State {
+            config: Arc::new(Config {
+                server: config(JsonNaming::CamelCase, false),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut server = tide::with_state(state);
+        setup_routes(&mut server);
+
+        // fields of errors are renamed after the message is translated
+        let (_key, user) = random_keypair();
+        let mut req = HttpRequest::new(
+            Method::Post,
+            Url::parse("http://example.com/admin/outbox").unwrap(),
+        );
+        req.insert_header("Accept-Language", "es");
+        req.set_body(json!({
+            "from": user,
+            "signature": "0x00",
+            "nonce": 1,
+            "expiresAt": u64::MAX,
+        }));
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 403);
+        assert_eq!(response["Content-Language"], "es");
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["code"], "admin_required");
+        assert_eq!(body["error"], "no es administrador");
+        assert_eq!(body["requiredRole"], "admin");
+        assert_eq!(body["messagePrefix"], "outbox");
+        assert!(body.get("required_role").is_none());
+        assert!(body.get("message_prefix").is_none());
+    }
+}
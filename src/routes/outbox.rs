@@ -0,0 +1,184 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::UserAddress,
+    outbox::OutboxItem,
+    routes::{SignedRequest, State, signed_body, verify_admin_action},
+    verify::{admins::admin_outbox_message_prefix, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct OutboxRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for OutboxRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// payloads are not listed since they may contain data of users
+fn item_json(state: &State, item: &OutboxItem) -> serde_json::Value {
+    let status = if item.is_failed(&state.config.outbox) {
+        "failed"
+    } else {
+        "pending"
+    };
+    json!({
+        "key": item.key,
+        "url": item.url,
+        "status": status,
+        "created_at": item.created_at,
+        "attempts": item.attempts,
+        "next_attempt_at": item.next_attempt_at,
+        "last_error": item.last_error,
+    })
+}
+
+// outbound pushes waiting for delivery and the ones given up on, oldest first. Signed by an
+// admin since the URLs and errors belong to contacts of users.
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let body: OutboxRequest = signed_body(&mut req).await?;
+    if let Err(response) = verify_admin_action(
+        req.state(),
+        &body.from,
+        body.signature,
+        &body.freshness,
+        &admin_outbox_message_prefix(),
+    )
+    .await
+    {
+        return Ok(response);
+    }
+
+    let state = req.state();
+    let items: Vec<_> = state
+        .outbox
+        .items()
+        .await?
+        .iter()
+        .map(|item| item_json(state, item))
+        .collect();
+    let response = Response::builder(200)
+        .body(json!({ "items": items }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use super::*;
+    use crate::{
+        admins::InMemoryAdminStorage,
+        config::{Config, OutboxSection},
+        verify::{expires_in, random_keypair, sign_message},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn list(state: &State, body: Value) -> Response {
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse("http://example.com/admin/outbox").unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/admin/outbox").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    async fn signed(state: &State, private_key: &str) -> Value {
+        let signature = sign_message(
+            private_key,
+            &state.server_identity.address,
+            &admin_outbox_message_prefix(),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        json!({
+            "from": signature.signer,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        })
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, admin) = random_keypair();
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::from([admin]),
+                HashSet::new(),
+            )),
+            config: Arc::new(Config {
+                outbox: OutboxSection {
+                    max_attempts: 2,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let item = OutboxItem::new("a".into(), "http://a.com".into(), "{}".into(), 10);
+        let failed = OutboxItem {
+            key: "b".into(),
+            attempts: 2,
+            last_error: Some("status 500".into()),
+            ..item.clone()
+        };
+        state.outbox.add_item(item).await.unwrap();
+        state.outbox.add_item(failed).await.unwrap();
+
+        let mut response = list(&state, signed(&state, &private_key).await).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        let items = body["items"].as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0]["key"], "a");
+        assert_eq!(items[0]["status"], "pending");
+        assert_eq!(items[1]["status"], "failed");
+        assert_eq!(items[1]["last_error"], "status 500");
+        assert!(items[1].get("body").is_none());
+    }
+
+    #[async_std::test]
+    async fn test_admin_required() {
+        let state = State::default();
+        state
+            .outbox
+            .add_item(OutboxItem::new(
+                "a".into(),
+                "http://a.com".into(),
+                "{}".into(),
+                10,
+            ))
+            .await
+            .unwrap();
+
+        let mut response = list(&state, json!({})).await;
+        assert!(response.status().is_client_error());
+        let body = response.body_string().await.unwrap();
+        assert!(!body.contains("http://a.com"));
+
+        // signed by a user who is not an admin
+        let (private_key, _) = random_keypair();
+        let mut response = list(&state, signed(&state, &private_key).await).await;
+        assert!(response.status().is_client_error());
+        let body = response.body_string().await.unwrap();
+        assert!(!body.contains("http://a.com"));
+    }
+}
@@ -0,0 +1,230 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    export::Pseudonymizer,
+    identity::{
+        IdentityService, SystemPenalty, UserAddress,
+        decay::{balance_after_decay, moderator_penalty_decay, system_penalty_decay},
+        punish::penalty,
+    },
+    routes::{
+        State,
+        error::{ApiError, ErrorCode},
+    },
+};
+
+// penalties returned by `GET /penalties/recent` without `limit`
+const DEFAULT_RECENT_LIMIT: usize = 50;
+const MAX_RECENT_LIMIT: usize = 500;
+
+#[derive(Deserialize)]
+struct RecentQuery {
+    limit: Option<usize>,
+}
+
+// system penalties by the related user that have not decayed yet
+fn system_penalties(
+    service: &IdentityService,
+    penalties: Vec<(UserAddress, SystemPenalty)>,
+    now: u64,
+) -> Vec<Value> {
+    penalties
+        .into_iter()
+        .filter_map(|(user, p)| {
+            let remaining = balance_after_decay(p.amount, system_penalty_decay(service, &p, now));
+            (!remaining.is_zero()).then(|| {
+                json!({
+                    "user": user,
+                    "amount": p.amount.to_string(),
+                    "remaining": remaining.to_string(),
+                    "timestamp": p.timestamp,
+                })
+            })
+        })
+        .collect()
+}
+
+// penalties of the user that have not decayed yet, `total` also includes penalties
+// propagated from vouchees
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let service = &req.state().identity_service;
+    let now = service.now();
+
+    let moderator = match service.moderator_penalty(&user).await? {
+        Some(p) => {
+            let weighted = service
+                .weighted_moderator_penalty(&user)
+                .await?
+                .unwrap_or(p.amount);
+            let decay = moderator_penalty_decay(service, &user).await?;
+            json!({
+                "moderator": p.moderator,
+                "amount": p.amount.to_string(),
+                "reason": service.penalty_reason(&user).await?,
+                "remaining": balance_after_decay(weighted, decay).to_string(),
+                "proof_id": p.proof_id,
+                "timestamp": p.timestamp,
+            })
+        }
+        None => serde_json::Value::Null,
+    };
+
+    let mut forgotten_users: Vec<_> = service.forgotten_users(&user).await?.into_iter().collect();
+    forgotten_users.sort();
+    let mut forgotten = vec![];
+    for forgotten_user in forgotten_users {
+        if let Some(p) = service.forgotten_penalty(&user, &forgotten_user).await? {
+            forgotten.push((forgotten_user, p));
+        }
+    }
+    let forgotten = system_penalties(service, forgotten, now);
+    // stakes slashed for punished vouchees
+    let slashed = system_penalties(service, service.slashed_stakes(&user).await?, now);
+
+    let response = json!({
+        "user": user,
+        "total": penalty(service, &user).await?.to_string(),
+        "moderator": moderator,
+        "forgotten": forgotten,
+        "slashed_stakes": slashed,
+    });
+    Ok(Response::builder(200)
+        .body(response)
+        .content_type(mime::JSON)
+        .build())
+}
+
+// latest moderator penalties with their moderators and reason codes, so communities can audit
+// moderation. Punished users are replaced by pseudonyms unless `privacy.public_penalties` is set.
+pub async fn recent_route(req: Request<State>) -> tide::Result {
+    let Ok(query) = req.query::<RecentQuery>() else {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidQuery).into());
+    };
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_RECENT_LIMIT)
+        .min(MAX_RECENT_LIMIT);
+    let state = req.state();
+    let service = &state.identity_service;
+    let public = state.config.privacy.public_penalties;
+    let pseudonymizer = Pseudonymizer::from_server_key(&state.server_identity.private_key);
+
+    let mut penalties = vec![];
+    for (user, p) in service.penalties.recent_moderator_penalties(limit).await? {
+        let reason = service.penalty_reason(&user).await?;
+        let user = match public {
+            true => user,
+            false => pseudonymizer.pseudonym(&user),
+        };
+        penalties.push(json!({
+            "user": user,
+            "moderator": p.moderator,
+            "amount": p.amount.to_string(),
+            "reason": reason,
+            "proof_id": p.proof_id,
+            "timestamp": p.timestamp,
+        }));
+    }
+    Ok(Response::builder(200)
+        .body(json!({ "anonymized": !public, "penalties": penalties }))
+        .content_type(mime::JSON)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{
+        IdtAmount,
+        forget::forget,
+        punish::punish,
+        tests::{MODERATOR, PROOF_ID, USER_A},
+        vouch::vouch,
+    };
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        let service = &state.identity_service;
+        punish(
+            service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(service, USER_A.to_string(), "userB".to_string())
+            .await
+            .unwrap();
+        forget(service, USER_A.to_string(), "userB".to_string())
+            .await
+            .unwrap();
+
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/penalties/{USER_A}")).unwrap(),
+        );
+        let mut server = tide::with_state(state);
+        server.at("/penalties/:user").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["moderator"]["amount"], "100");
+        assert_eq!(body["moderator"]["remaining"], "100");
+        assert_eq!(body["forgotten"][0]["user"], "userB");
+        assert_eq!(body["forgotten"][0]["amount"], "500");
+        assert_eq!(body["total"], "600");
+        assert_eq!(body["slashed_stakes"], json!([]));
+    }
+
+    #[async_std::test]
+    async fn test_recent() {
+        let mut state = State::default();
+        let service = &state.identity_service;
+        for (i, user) in [USER_A, "userB", "userC"].into_iter().enumerate() {
+            service
+                .punish_with_timestamp(
+                    user.to_string(),
+                    MODERATOR.to_string(),
+                    IdtAmount::new(10),
+                    PROOF_ID,
+                    i as u64,
+                )
+                .await
+                .unwrap();
+        }
+
+        let get = |state: State, url: &'static str| async move {
+            let req = HttpRequest::new(tide::http::Method::Get, Url::parse(url).unwrap());
+            let mut server = tide::with_state(state);
+            server.at("/penalties/recent").get(recent_route);
+            server.at("/penalties/:user").get(route);
+            let mut response: Response = server.respond(req).await.unwrap();
+            assert_eq!(response.status(), 200);
+            response.body_json::<Value>().await.unwrap()
+        };
+
+        let body = get(state.clone(), "http://example.com/penalties/recent?limit=2").await;
+        assert_eq!(body["anonymized"], true);
+        let penalties = body["penalties"].as_array().unwrap();
+        assert_eq!(penalties.len(), 2);
+        assert_eq!(penalties[0]["timestamp"], 2);
+        assert_eq!(penalties[0]["moderator"], MODERATOR);
+        assert_ne!(penalties[0]["user"], "userC");
+        assert_eq!(penalties[1]["timestamp"], 1);
+
+        let mut config = (*state.config).clone();
+        config.privacy.public_penalties = true;
+        state.config = std::sync::Arc::new(config);
+        let body = get(state, "http://example.com/penalties/recent").await;
+        assert_eq!(body["anonymized"], false);
+        assert_eq!(body["penalties"].as_array().unwrap().len(), 3);
+        assert_eq!(body["penalties"][0]["user"], "userC");
+    }
+}
@@ -0,0 +1,394 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::{UserAddress, idt::balance},
+    notifications::Notification,
+    pending_penalties::{PendingPenaltyId, decide, error::Error},
+    routes::{
+        SignedRequest, State, balance_changed,
+        error::{ApiError, ErrorCode},
+        signed_body, verify_admin_action,
+    },
+    verify::{admins::admin_decide_penalty_message_prefix, signature::Freshness},
+};
+
+// penalties returned by a single request, pass `cursor` as `after` to get the next page
+pub const PENDING_PENALTIES_PAGE_SIZE: usize = 100;
+
+const CURSOR_SCOPE: &str = "pending_penalties";
+
+#[derive(Deserialize)]
+struct PendingPenaltiesQuery {
+    after: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DecideRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for DecideRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// client errors are reported, storage errors fail the request
+fn error_response(e: Error) -> tide::Result {
+    match e {
+        Error::UnknownPenalty(_) => Ok(ApiError::new(404, ErrorCode::PenaltyNotFound).into()),
+        Error::AlreadyDecided(_) => Ok(ApiError::new(409, ErrorCode::PenaltyAlreadyDecided).into()),
+        e => Err(e.into()),
+    }
+}
+
+// penalties waiting for an admin, oldest first
+pub async fn route(req: Request<State>) -> tide::Result {
+    let Ok(query) = req.query::<PendingPenaltiesQuery>() else {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidQuery).into());
+    };
+    let cursors = req.state().cursors();
+    let after: PendingPenaltyId = match query
+        .after
+        .as_deref()
+        .map(|c| cursors.decode(CURSOR_SCOPE, c))
+    {
+        None => 0,
+        Some(Some(id)) => id,
+        Some(None) => return Ok(ApiError::bad_request(ErrorCode::InvalidAfter).into()),
+    };
+    let penalties = req
+        .state()
+        .pending_penalties
+        .undecided(after, PENDING_PENALTIES_PAGE_SIZE)
+        .await?;
+    let cursor = penalties.last().map(|p| p.id).unwrap_or(after);
+    Ok(Response::builder(200)
+        .body(json!({
+            "penalties": penalties,
+            "cursor": cursors.encode(CURSOR_SCOPE, &cursor),
+        }))
+        .content_type(mime::JSON)
+        .build())
+}
+
+pub async fn approve_route(req: Request<State>) -> tide::Result {
+    decide_route(req, true).await
+}
+
+pub async fn reject_route(req: Request<State>) -> tide::Result {
+    decide_route(req, false).await
+}
+
+// applies or drops the penalty, signed by an admin. The punished user is notified of an
+// approved penalty, the proposing moderator of a rejected one.
+async fn decide_route(mut req: Request<State>, approved: bool) -> tide::Result {
+    let Ok(id) = req.param("id")?.parse::<PendingPenaltyId>() else {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidId).into());
+    };
+    let body: DecideRequest = signed_body(&mut req).await?;
+    let state = req.state();
+    let admin = body.from;
+    let prefix = admin_decide_penalty_message_prefix(id, approved);
+    if let Err(response) =
+        verify_admin_action(state, &admin, body.signature, &body.freshness, &prefix).await
+    {
+        return Ok(response);
+    }
+
+    let Some(penalty) = state.pending_penalties.penalty(id).await? else {
+        return error_response(Error::UnknownPenalty(id));
+    };
+    let balance_before = balance(&state.identity_service, &penalty.user).await?;
+    let penalty = match decide(
+        &state.identity_service,
+        &*state.pending_penalties,
+        id,
+        admin.clone(),
+        approved,
+    )
+    .await
+    {
+        Ok(penalty) => penalty,
+        Err(e) => return error_response(e),
+    };
+
+    let user_balance = balance(&state.identity_service, &penalty.user).await?;
+    if approved {
+        state
+            .notifications
+            .notify(
+                &penalty.user,
+                Notification::Punished {
+                    moderator: penalty.moderator.clone(),
+                    amount: penalty.amount,
+                    proof_id: penalty.proof_id,
+                },
+            )
+            .await;
+        balance_changed(state, &penalty.user, balance_before, user_balance).await;
+    } else {
+        state
+            .notifications
+            .notify(
+                &penalty.moderator,
+                Notification::PenaltyRejected {
+                    id,
+                    user: penalty.user.clone(),
+                    amount: penalty.amount,
+                    admin,
+                },
+            )
+            .await;
+    }
+    Ok(Response::builder(200)
+        .body(json!({
+            "penalty": penalty,
+            "idt": user_balance.to_string(),
+            "nonce": body.freshness.nonce,
+        }))
+        .content_type(mime::JSON)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::Arc,
+    };
+
+    use super::*;
+    use crate::{
+        admins::InMemoryAdminStorage,
+        identity::{
+            IdtAmount,
+            proof::prove,
+            tests::{MODERATOR, PROOF_ID, USER_A},
+        },
+        notifications::{Contact, ContactKind, InMemoryNotifier, NotificationDispatcher, Notifier},
+        pending_penalties::PendingPenalty,
+        verify::{expires_in, random_keypair, sign_message},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn add_penalty(state: &State, amount: u64) -> PendingPenalty {
+        state
+            .pending_penalties
+            .add_penalty(PendingPenalty {
+                id: 0,
+                user: USER_A.into(),
+                moderator: MODERATOR.into(),
+                amount: IdtAmount::new(amount),
+                proof_id: PROOF_ID,
+                reason: None,
+                created_at: 10,
+                decision: None,
+            })
+            .await
+            .unwrap()
+    }
+
+    async fn send_decision(
+        state: &State,
+        private_key: &str,
+        admin: &str,
+        id: PendingPenaltyId,
+        approved: bool,
+    ) -> Response {
+        let signature = sign_message(
+            private_key,
+            &state.server_identity.address,
+            &admin_decide_penalty_message_prefix(id, approved),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .unwrap();
+        let body = json!({
+            "from": admin,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let decision = if approved { "approve" } else { "reject" };
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!(
+                "http://example.com/pending_penalties/{id}/{decision}"
+            ))
+            .unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server
+            .at("/pending_penalties/:id/approve")
+            .post(approve_route);
+        server
+            .at("/pending_penalties/:id/reject")
+            .post(reject_route);
+        server.respond(req).await.unwrap()
+    }
+
+    fn admin_state(admin: &UserAddress, notifier: Arc<InMemoryNotifier>) -> State {
+        State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::from([admin.clone()]),
+                HashSet::new(),
+            )),
+            notifications: Arc::new(NotificationDispatcher {
+                transports: HashMap::from([(ContactKind::Email, notifier as Arc<dyn Notifier>)]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    async fn set_contact(state: &State, user: &str) -> Contact {
+        let contact = Contact {
+            kind: ContactKind::Email,
+            address: format!("{user}@example.com"),
+        };
+        state
+            .notifications
+            .contacts
+            .set_contact(user.to_string(), contact.clone())
+            .await
+            .unwrap();
+        contact
+    }
+
+    async fn list(state: &State, query: &str) -> (u16, Value) {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/pending_penalties{query}")).unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/pending_penalties").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        let body = response.body_json().await.unwrap();
+        (response.status().into(), body)
+    }
+
+    #[async_std::test]
+    async fn test_list() {
+        let state = State::default();
+        for amount in [100, 200, 300] {
+            add_penalty(&state, amount).await;
+        }
+        decide(
+            &state.identity_service,
+            &*state.pending_penalties,
+            2,
+            "admin".into(),
+            false,
+        )
+        .await
+        .unwrap();
+
+        let (status, body) = list(&state, "").await;
+        assert_eq!(status, 200);
+        let cursor = body["cursor"].as_str().unwrap();
+        let (_, page) = list(&state, &format!("?after={cursor}")).await;
+        assert!(page["penalties"].as_array().unwrap().is_empty());
+        let (status, _) = list(&state, "?after=0").await;
+        assert_eq!(status, 400);
+        let list = body["penalties"].as_array().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0]["amount"], 100);
+        assert_eq!(list[1]["id"], 3);
+        assert_eq!(list[1]["moderator"], MODERATOR);
+    }
+
+    #[async_std::test]
+    async fn test_approve() {
+        let (private_key, admin) = random_keypair();
+        let notifier = Arc::new(InMemoryNotifier::default());
+        let state = admin_state(&admin, notifier.clone());
+        let contact = set_contact(&state, USER_A).await;
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        let penalty = add_penalty(&state, 400).await;
+
+        // only admins decide
+        let (other_key, other) = random_keypair();
+        let response = send_decision(&state, &other_key, &other, penalty.id, true).await;
+        assert_eq!(response.status(), 403);
+
+        let mut response = send_decision(&state, &private_key, &admin, penalty.id, true).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["idt"], "600");
+        assert_eq!(body["penalty"]["decision"]["approved"], true);
+        assert_eq!(body["penalty"]["decision"]["admin"], admin);
+        assert_eq!(
+            notifier.sent().await,
+            vec![(
+                USER_A.to_string(),
+                contact,
+                Notification::Punished {
+                    moderator: MODERATOR.into(),
+                    amount: IdtAmount::new(400),
+                    proof_id: PROOF_ID,
+                }
+            )]
+        );
+
+        let response = send_decision(&state, &private_key, &admin, penalty.id, false).await;
+        assert_eq!(response.status(), 409);
+        let response = send_decision(&state, &private_key, &admin, 2, true).await;
+        assert_eq!(response.status(), 404);
+    }
+
+    #[async_std::test]
+    async fn test_reject() {
+        let (private_key, admin) = random_keypair();
+        let notifier = Arc::new(InMemoryNotifier::default());
+        let state = admin_state(&admin, notifier.clone());
+        let contact = set_contact(&state, MODERATOR).await;
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        let penalty = add_penalty(&state, 400).await;
+
+        let mut response = send_decision(&state, &private_key, &admin, penalty.id, false).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["idt"], "1000");
+        assert_eq!(body["penalty"]["decision"]["approved"], false);
+        assert_eq!(
+            notifier.sent().await,
+            vec![(
+                MODERATOR.to_string(),
+                contact,
+                Notification::PenaltyRejected {
+                    id: penalty.id,
+                    user: USER_A.into(),
+                    amount: IdtAmount::new(400),
+                    admin,
+                }
+            )]
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::UserAddress,
+    pending_vouches::{PendingVouch, try_activate},
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        freshness_error, signed_body,
+    },
+    verify::{signature::Freshness, vouch::vouch_confirm_verify},
+};
+
+#[derive(Deserialize)]
+struct ConfirmRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for ConfirmRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+fn pending_json(state: &State, vouch: &PendingVouch) -> serde_json::Value {
+    let config = &state.config.two_phase_vouch;
+    json!({
+        "voucher": vouch.voucher,
+        "vouchee": vouch.vouchee,
+        "created_at": vouch.created_at,
+        "confirmed_at": vouch.confirmed_at,
+        "activates_at": vouch.activates_at(config),
+        "expires_at": vouch.expires_at(config),
+    })
+}
+
+// pending vouches from and to the user
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let state = req.state();
+    let vouches: Vec<_> = state
+        .pending_vouches
+        .all_pending()
+        .await?
+        .iter()
+        .filter(|vouch| vouch.voucher == user || vouch.vouchee == user)
+        .map(|vouch| pending_json(state, vouch))
+        .collect();
+    let response = Response::builder(200)
+        .body(json!({ "user": user, "pending": vouches }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+// second signature of the voucher, the vouch is activated once the cooling-off period is over
+pub async fn confirm_route(mut req: Request<State>) -> tide::Result {
+    let vouchee = req.param("user")?.to_string();
+    let body: ConfirmRequest = signed_body(&mut req).await?;
+    let voucher = body.from;
+    let state = req.state();
+
+    if let Some(response) = freshness_error(state, &body.freshness) {
+        return Ok(response);
+    }
+    let Some(mut pending) = state.pending_vouches.pending(&voucher, &vouchee).await? else {
+        return Ok(ApiError::new(404, ErrorCode::NoPendingVouch).into());
+    };
+    if vouch_confirm_verify(
+        body.signature,
+        &voucher,
+        &body.freshness,
+        vouchee.clone(),
+        &*state.nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    let service = &state.identity_service;
+    let config = &state.config.two_phase_vouch;
+    if pending.confirmed_at.is_none() {
+        pending.confirmed_at = Some(service.now());
+        state.pending_vouches.add_pending(pending.clone()).await?;
+    }
+    let active = try_activate(service, &*state.pending_vouches, config, &pending).await?;
+    let mut response = pending_json(state, &pending);
+    response["active"] = active.into();
+    response["nonce"] = body.freshness.nonce.into();
+    Ok(Response::builder(200)
+        .body(response)
+        .content_type(mime::JSON)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{Config, TwoPhaseVouchSection},
+        identity::{
+            IdtAmount,
+            proof::prove,
+            tests::{MODERATOR, PROOF_ID},
+        },
+        routes::vouch,
+        verify::{
+            expires_in, random_keypair,
+            vouch::{vouch_confirm_sign, vouch_sign},
+        },
+    };
+    use serde_json::Value;
+    use std::sync::Arc;
+    use tide::http::{Method, Request as HttpRequest, Response, Url};
+
+    async fn send(
+        server: &tide::Server<State>,
+        method: Method,
+        url: &str,
+        body: Value,
+    ) -> Response {
+        let mut req = HttpRequest::new(
+            method,
+            Url::parse(&format!("http://example.com{url}")).unwrap(),
+        );
+        if method == Method::Post {
+            req.set_body(body);
+            req.set_content_type(mime::JSON);
+        }
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_two_phase_vouch() {
+        let state = State {
+            config: Arc::new(Config {
+                two_phase_vouch: TwoPhaseVouchSection {
+                    threshold: Some(IdtAmount::new(50)),
+                    cooling_off: 0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let (private_key, user_address) = random_keypair();
+        prove(
+            &state.identity_service,
+            user_address.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        let mut server = tide::with_state(state.clone());
+        server.at("/vouch/:user").post(vouch::route);
+        server.at("/vouch/:user/confirm").post(confirm_route);
+        server.at("/pending_vouches/:user").get(route);
+
+        for expected_status in [202, 409] {
+            let signature = vouch_sign(
+                &private_key,
+                &state.server_identity.address,
+                "userB".to_string(),
+                expires_in(60),
+                &*state.nonce_manager,
+            )
+            .await
+            .unwrap();
+            let body = json!({
+                "from": {"user": user_address},
+                "signature": signature.signature,
+                "nonce": signature.freshness.nonce,
+                "expires_at": signature.freshness.expires_at,
+                "domain": signature.freshness.domain,
+            });
+            let response = send(&server, Method::Post, "/vouch/userB", body).await;
+            assert_eq!(response.status(), expected_status);
+        }
+        assert!(
+            state
+                .identity_service
+                .vouchers_with_time(&"userB".to_string())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let mut response = send(&server, Method::Get, "/pending_vouches/userB", json!({})).await;
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["pending"][0]["voucher"], user_address);
+        assert_eq!(body["pending"][0]["confirmed_at"], Value::Null);
+
+        // a signature for another vouchee does not confirm the vouch
+        for (vouchee, expected_status) in [("userC", 400), ("userB", 200)] {
+            let signature = vouch_confirm_sign(
+                &private_key,
+                &state.server_identity.address,
+                vouchee.to_string(),
+                expires_in(60),
+                &*state.nonce_manager,
+            )
+            .await
+            .unwrap();
+            let body = json!({
+                "from": user_address,
+                "signature": signature.signature,
+                "nonce": signature.freshness.nonce,
+                "expires_at": signature.freshness.expires_at,
+                "domain": signature.freshness.domain,
+            });
+            let mut response = send(&server, Method::Post, "/vouch/userB/confirm", body).await;
+            assert_eq!(response.status(), expected_status);
+            if expected_status == 200 {
+                let body: Value = response.body_json().await.unwrap();
+                assert_eq!(body["active"], true);
+            }
+        }
+        assert!(
+            state
+                .identity_service
+                .vouchers_with_time(&"userB".to_string())
+                .await
+                .unwrap()
+                .contains_key(&user_address)
+        );
+        assert!(
+            state
+                .pending_vouches
+                .all_pending()
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
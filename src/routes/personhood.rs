@@ -0,0 +1,109 @@
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::error::Error as IdentityError,
+    personhood::{error::Error, system_registry, verify},
+    routes::{
+        State,
+        error::{ApiError, ErrorCode},
+    },
+};
+
+// checks the user in the proof-of-personhood registries, a verified user gets a system proof
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let state = req.state();
+    let service = &state.identity_service;
+    let verified = match verify(service, &state.config.personhood, &user).await {
+        Ok(verified) => verified,
+        Err(Error::RegistryError(registry, e)) => {
+            log::warn!("Failed to check {} in {}: {}", user, registry, e);
+            return Ok(ApiError::new(502, ErrorCode::RegistryRequestFailed).into());
+        }
+        Err(Error::IdentityError(IdentityError::AddressBlocked(_))) => {
+            return Ok(ApiError::new(403, ErrorCode::AddressBlocked).into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let proof = service.proof(&user).await?.and_then(|proof| {
+        let registry = system_registry(&proof.moderator)?;
+        Some(json!({
+            "registry": registry,
+            "amount": proof.amount.to_string(),
+            "proof_id": proof.proof_id.to_string(),
+            "timestamp": proof.timestamp,
+        }))
+    });
+    let response = Response::builder(200)
+        .body(json!({
+            "user": user,
+            "verified": verified,
+            "proof": proof,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        identity::tests::USER_A,
+        personhood::tests::{HUMAN, config, mock_registry},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn check_user(state: &State, user: &str) -> Response {
+        let req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/personhood/{user}")).unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/personhood/:user").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    fn personhood_state(url: &str) -> State {
+        let config = Config {
+            personhood: config(url),
+            ..Default::default()
+        };
+        State {
+            config: Arc::new(config),
+            ..Default::default()
+        }
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let url = mock_registry().await;
+        let state = personhood_state(&url);
+
+        let mut response = check_user(&state, USER_A).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["verified"], json!([]));
+        assert!(body["proof"].is_null());
+
+        let mut response = check_user(&state, HUMAN).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["verified"], json!(["brightid", "poh"]));
+        assert_eq!(body["proof"]["registry"], "poh");
+        assert_eq!(body["proof"]["amount"], "200");
+    }
+
+    #[async_std::test]
+    async fn test_unreachable() {
+        let state = personhood_state("http://127.0.0.1:1");
+        let response = check_user(&state, HUMAN).await;
+        assert_eq!(response.status(), 502);
+    }
+}
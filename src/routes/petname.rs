@@ -0,0 +1,173 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::UserAddress,
+    petnames::{MAX_PETNAMES, is_valid_petname},
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        freshness_error, signed_body,
+    },
+    verify::{petname::petname_verify, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct PetnameRequest {
+    from: UserAddress,
+    // missing name removes the petname
+    #[serde(default)]
+    name: Option<String>,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for PetnameRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// names an address the sender vouches for, see `vouchees::route`
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let address = req.param("address")?.to_string();
+    let body: PetnameRequest = signed_body(&mut req).await?;
+    let state = req.state();
+
+    if body
+        .name
+        .as_deref()
+        .is_some_and(|name| !is_valid_petname(name))
+    {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidPetname).into());
+    }
+
+    if let Some(response) = freshness_error(state, &body.freshness) {
+        return Ok(response);
+    }
+
+    if petname_verify(
+        body.signature,
+        &body.from,
+        &body.freshness,
+        address.clone(),
+        body.name.as_deref(),
+        &*state.nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    let petnames = &state.petnames;
+    match body.name.clone() {
+        Some(name) => {
+            let vouchees = state
+                .identity_service
+                .vouchees_with_time(&body.from)
+                .await?;
+            if !vouchees.contains_key(&address) {
+                return Ok(ApiError::bad_request(ErrorCode::AddressNotVouchee).into());
+            }
+            let names = petnames.petnames(&body.from).await?;
+            if !names.contains_key(&address) && names.len() >= MAX_PETNAMES {
+                return Ok(ApiError::bad_request(ErrorCode::TooManyPetnames).into());
+            }
+            petnames
+                .set_petname(body.from.clone(), address.clone(), name)
+                .await?;
+        }
+        None => petnames.remove_petname(&body.from, &address).await?,
+    }
+
+    let response = Response::builder(200)
+        .body(json!({
+            "from": body.from,
+            "address": address,
+            "name": body.name,
+            "nonce": body.freshness.nonce,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identity::vouch::vouch,
+        petnames::MAX_PETNAME_LENGTH,
+        verify::{expires_in, petname::petname_sign, random_keypair},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn set_petname(
+        state: &State,
+        private_key: &str,
+        address: &str,
+        name: Option<&str>,
+    ) -> Response {
+        let signature = petname_sign(
+            private_key,
+            &state.server_identity.address,
+            address.to_string(),
+            name,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        let body = json!({
+            "from": signature.signer,
+            "name": name,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/petname/{address}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/petname/:address").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        let (private_key, owner) = random_keypair();
+        vouch(&state.identity_service, owner.clone(), "bob".to_string())
+            .await
+            .unwrap();
+
+        let mut response = set_petname(&state, &private_key, "carol", Some("Carol")).await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "address is not a vouchee");
+
+        let long_name = "b".repeat(MAX_PETNAME_LENGTH + 1);
+        let mut response = set_petname(&state, &private_key, "bob", Some(&long_name)).await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "invalid petname");
+
+        let mut response = set_petname(&state, &private_key, "bob", Some("Bob")).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["name"], "Bob");
+        assert_eq!(state.petnames.petnames(&owner).await.unwrap()["bob"], "Bob");
+
+        let response = set_petname(&state, &private_key, "bob", None).await;
+        assert_eq!(response.status(), 200);
+        assert!(state.petnames.petnames(&owner).await.unwrap().is_empty());
+    }
+}
@@ -1,13 +1,29 @@
 use std::collections::HashMap;
 
 use serde::Deserialize;
-use serde_json::json;
+use serde_json::{Map, Value, json};
 use tide::{Request, Response, http::mime};
 
 use crate::{
-    identity::{IdtAmount, ProofId, UserAddress, error::Error, idt::balance, proof::prove},
-    routes::State,
-    verify::{nonce::Nonce, proof::proof_verify},
+    identity::{
+        IdtAmount, ProofId, UserAddress,
+        error::Error,
+        idt::balance,
+        proof::{MAX_IDT_BY_PROOF, ProofBatchEntry, prove, prove_batch},
+    },
+    routes::{
+        Consent, Role, SignedRequest, State, check_role,
+        error::{ApiError, ErrorCode},
+        freshness_error, signed_body,
+    },
+    verify::{
+        proof::{
+            proof_batch_message_prefix, proof_batch_verify, proof_consent_verify,
+            proof_message_prefix, proof_verify, transfer_proofs_message_prefix,
+            transfer_proofs_verify,
+        },
+        signature::Freshness,
+    },
 };
 
 #[derive(Deserialize)]
@@ -16,32 +32,59 @@ struct ProofRequest {
     amount: IdtAmount,
     proof_id: ProofId,
     signature: String,
-    nonce: Nonce,
+    #[serde(flatten)]
+    freshness: Freshness,
+    // proven user signature, required with `identity.require_proof_consent`
+    #[serde(default)]
+    consent: Option<Consent>,
+}
+
+impl SignedRequest for ProofRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// error of a proof the moderator cannot issue, `None` for server errors
+fn proof_error(e: &Error) -> Option<ApiError> {
+    match e {
+        Error::MaxBalanceExceeded => Some(
+            ApiError::bad_request(ErrorCode::MaxBalanceExceeded)
+                .with("max", MAX_IDT_BY_PROOF.to_string()),
+        ),
+        Error::ModeratorLimitExceeded(limit) => Some(
+            ApiError::bad_request(ErrorCode::ModeratorLimitExceeded).with("max", limit.to_string()),
+        ),
+        Error::AddressBlocked(_) => Some(ApiError::new(403, ErrorCode::AddressBlocked)),
+        Error::DuplicateBatchUser(_) => Some(ApiError::bad_request(ErrorCode::UserProvenTwice)),
+        _ => None,
+    }
 }
 
 pub async fn route(mut req: Request<State>) -> tide::Result {
     let user = req.param("user")?.to_string();
-    let body: ProofRequest = req.body_json().await?;
+    let body: ProofRequest = signed_body(&mut req).await?;
     let moderator = body.from;
     let amount = body.amount;
     let proof_id = body.proof_id;
-    if req
-        .state()
-        .admin_storage
-        .check_moderator(&moderator)
-        .await
-        .is_err()
-    {
-        return Ok(Response::builder(403)
-            .body(json!({"error": "not moderator"}))
-            .content_type(mime::JSON)
-            .build());
+    let prefix = proof_message_prefix(user.clone(), amount, proof_id);
+    if let Err(response) = check_role(req.state(), &moderator, Role::Moderator, &prefix).await {
+        return Ok(response);
+    }
+
+    if let Some(response) = freshness_error(req.state(), &body.freshness) {
+        return Ok(response);
+    }
+
+    let require_consent = req.state().identity_service.config.require_proof_consent;
+    if require_consent && body.consent.is_none() {
+        return Ok(ApiError::bad_request(ErrorCode::ProofConsentRequired).into());
     }
 
     if proof_verify(
         body.signature,
         &moderator,
-        body.nonce,
+        &body.freshness,
         user.clone(),
         amount,
         proof_id,
@@ -50,10 +93,27 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
     .await
     .is_err()
     {
-        return Ok(Response::builder(400)
-            .body(json!({"error": "signature verification failed"}))
-            .content_type(mime::JSON)
-            .build());
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    if let Some(consent) = body.consent.filter(|_| require_consent) {
+        if let Some(response) = freshness_error(req.state(), &consent.freshness) {
+            return Ok(response);
+        }
+        if proof_consent_verify(
+            consent.signature,
+            &user,
+            &consent.freshness,
+            moderator.clone(),
+            amount,
+            proof_id,
+            &*req.state().nonce_manager,
+        )
+        .await
+        .is_err()
+        {
+            return Ok(ApiError::bad_request(ErrorCode::ConsentVerificationFailed).into());
+        }
     }
 
     let prove_result = prove(
@@ -65,13 +125,10 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
     )
     .await;
 
-    if matches!(prove_result, Err(Error::MaxBalanceExceeded)) {
-        return Ok(Response::builder(400)
-            .body(json!({
-                "error": format!("max balance exceeded, max is {} IDT", crate::identity::proof::MAX_IDT_BY_PROOF)
-            }))
-            .content_type(mime::JSON)
-            .build());
+    if let Err(e) = &prove_result {
+        if let Some(error) = proof_error(e) {
+            return Ok(error.into());
+        }
     }
 
     // handle other errors
@@ -83,7 +140,7 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
         ("from".into(), moderator.into()),
         ("idt".into(), user_balance.to_string().into()),
         ("proof_id".into(), proof_id.to_string().into()),
-        ("nonce".into(), body.nonce.into()),
+        ("nonce".into(), body.freshness.nonce.into()),
     ]);
     let response = Response::builder(200)
         .body(json!(response))
@@ -92,6 +149,197 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
     Ok(response)
 }
 
+// entries of a single batch at most
+pub const MAX_PROOF_BATCH: usize = 1000;
+
+#[derive(Deserialize)]
+struct ProofBatchRequest {
+    from: UserAddress,
+    entries: Vec<ProofBatchEntry>,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for ProofBatchRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// lists the error of each rejected entry, other entries have a `null` error
+fn rejected_batch_response(entries: &[ProofBatchEntry], rejected: Vec<(usize, Error)>) -> Response {
+    let mut errors: Vec<Option<ApiError>> = vec![None; entries.len()];
+    for (i, e) in rejected {
+        let error =
+            proof_error(&e).unwrap_or_else(|| ApiError::bad_request(ErrorCode::ProofRejected));
+        errors[i] = Some(error);
+    }
+    let results: Vec<_> = entries
+        .iter()
+        .zip(errors)
+        .map(|(entry, error)| {
+            let mut result = match error {
+                Some(error) => error.body(),
+                None => Map::from_iter([("error".to_string(), Value::Null)]),
+            };
+            result.insert("user".into(), entry.user.clone().into());
+            result.insert("proof_id".into(), entry.proof_id.to_string().into());
+            result
+        })
+        .collect();
+    ApiError::bad_request(ErrorCode::ProofBatchRejected)
+        .with("results", results)
+        .into()
+}
+
+// proves many users under a single moderator signature over the hash of the entries.
+// Every entry is stored or none of them, the response lists the result of each entry.
+pub async fn batch_route(mut req: Request<State>) -> tide::Result {
+    let body: ProofBatchRequest = signed_body(&mut req).await?;
+    let state = req.state();
+    let moderator = body.from;
+    let entries = body.entries;
+    let prefix = proof_batch_message_prefix(&entries);
+    if let Err(response) = check_role(state, &moderator, Role::Moderator, &prefix).await {
+        return Ok(response);
+    }
+
+    if let Some(response) = freshness_error(state, &body.freshness) {
+        return Ok(response);
+    }
+
+    if entries.is_empty() {
+        return Ok(ApiError::bad_request(ErrorCode::ProofBatchEmpty).into());
+    }
+    if entries.len() > MAX_PROOF_BATCH {
+        return Ok(ApiError::bad_request(ErrorCode::ProofBatchTooLarge)
+            .with("max", MAX_PROOF_BATCH)
+            .into());
+    }
+    // consents are signed per proof
+    if state.identity_service.config.require_proof_consent {
+        return Ok(ApiError::bad_request(ErrorCode::ProofConsentRequired).into());
+    }
+
+    if proof_batch_verify(
+        body.signature,
+        &moderator,
+        &body.freshness,
+        &entries,
+        &*state.nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    match prove_batch(&state.identity_service, moderator.clone(), entries.clone()).await {
+        Ok(()) => {}
+        Err(Error::ProofBatchRejected(rejected)) => {
+            return Ok(rejected_batch_response(&entries, rejected));
+        }
+        Err(e) => return Err(e.into()),
+    }
+    log::info!(
+        "Moderator {} proved {} users in a batch",
+        moderator,
+        entries.len()
+    );
+
+    let mut results = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let user_balance = balance(&state.identity_service, &entry.user).await?;
+        results.push(json!({
+            "user": entry.user,
+            "proof_id": entry.proof_id.to_string(),
+            "idt": user_balance.to_string(),
+        }));
+    }
+    Ok(Response::builder(200)
+        .body(json!({
+            "from": moderator,
+            "results": results,
+            "nonce": body.freshness.nonce,
+        }))
+        .content_type(mime::JSON)
+        .build())
+}
+
+#[derive(Deserialize)]
+struct TransferRequest {
+    from: UserAddress,
+    // retiring moderator
+    moderator: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for TransferRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// designated successor re-signs all proofs of a retiring moderator, proofs keep their
+// amounts and timestamps
+pub async fn transfer_route(mut req: Request<State>) -> tide::Result {
+    let body: TransferRequest = signed_body(&mut req).await?;
+    let successor = body.from;
+    let moderator = body.moderator;
+    let prefix = transfer_proofs_message_prefix(moderator.clone());
+    if let Err(response) = check_role(req.state(), &successor, Role::Moderator, &prefix).await {
+        return Ok(response);
+    }
+
+    if let Some(response) = freshness_error(req.state(), &body.freshness) {
+        return Ok(response);
+    }
+
+    if transfer_proofs_verify(
+        body.signature,
+        &successor,
+        &body.freshness,
+        moderator.clone(),
+        &*req.state().nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    let designated = req.state().admin_storage.successor(&moderator).await?;
+    if designated.as_ref() != Some(&successor) {
+        return Ok(ApiError::new(403, ErrorCode::NotSuccessor).into());
+    }
+
+    let transferred = req
+        .state()
+        .identity_service
+        .transfer_moderator_proofs(&moderator, &successor)
+        .await?;
+    log::info!(
+        "Transferred {} proofs of moderator {} to {}",
+        transferred.len(),
+        moderator,
+        successor
+    );
+
+    let response = Response::builder(200)
+        .body(json!({
+            "moderator": moderator,
+            "transferred": transferred,
+            "from": successor,
+            "nonce": body.freshness.nonce,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
 #[cfg(test)]
 mod tests {
     use std::{collections::HashSet, sync::Arc};
@@ -99,11 +347,17 @@ mod tests {
     use super::*;
     use crate::{
         admins::InMemoryAdminStorage,
+        config::IdentitySection,
+        identity::IdentityService,
         identity::{
-            proof::MAX_IDT_BY_PROOF,
+            screening::DenylistScreening,
             tests::{PROOF_ID, USER_A},
         },
-        verify::{proof::proof_sign, random_keypair},
+        verify::{
+            expires_in,
+            proof::{proof_batch_sign, proof_consent_sign, proof_sign, transfer_proofs_sign},
+            random_keypair,
+        },
     };
     use serde_json::Value;
     use tide::http::{Request as HttpRequest, Response, Url};
@@ -118,14 +372,16 @@ mod tests {
             ..Default::default()
         };
         let user_id = USER_A;
-        let amount = 5000;
+        let amount = IdtAmount::new(5000);
 
         let req_url = format!("/proof/{user_id}");
         let signature = proof_sign(
             &private_key,
+            &state.server_identity.address,
             user_id.to_string(),
             amount,
             PROOF_ID,
+            expires_in(60),
             &*state.nonce_manager,
         )
         .await
@@ -135,7 +391,9 @@ mod tests {
             "amount": amount,
             "proof_id": PROOF_ID,
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
         });
 
         let mut req = HttpRequest::new(
@@ -156,7 +414,7 @@ mod tests {
         assert_eq!(body["from"], moderator);
         assert_eq!(body["idt"], amount.to_string());
         assert_eq!(body["proof_id"], PROOF_ID.to_string());
-        assert_eq!(body["nonce"], signature.nonce);
+        assert_eq!(body["nonce"], signature.freshness.nonce);
     }
 
     #[async_std::test]
@@ -169,14 +427,16 @@ mod tests {
             ..Default::default()
         };
         let user_id = USER_A;
-        let amount = MAX_IDT_BY_PROOF + 1;
+        let amount = MAX_IDT_BY_PROOF + IdtAmount::new(1);
 
         let req_url = format!("/proof/{user_id}");
         let signature = proof_sign(
             &private_key,
+            &state.server_identity.address,
             user_id.to_string(),
             amount,
             PROOF_ID,
+            expires_in(60),
             &*state.nonce_manager,
         )
         .await
@@ -186,7 +446,9 @@ mod tests {
             "amount": amount,
             "proof_id": PROOF_ID,
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
         });
 
         let mut req = HttpRequest::new(
@@ -203,6 +465,61 @@ mod tests {
         assert_eq!(response.status(), 400);
     }
 
+    #[async_std::test]
+    async fn test_blocked_address() {
+        let (private_key, moderator) = random_keypair();
+        let moderators = HashSet::from([moderator.clone()]);
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(HashSet::new(), moderators)),
+            identity_service: IdentityService {
+                screening: Arc::new(DenylistScreening::parse(USER_A)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let signature = proof_sign(
+            &private_key,
+            &state.server_identity.address,
+            USER_A.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .unwrap();
+        let body = json!({
+            "from": moderator,
+            "amount": 100,
+            "proof_id": PROOF_ID,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/proof/{USER_A}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/proof/:user").post(route);
+
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 403);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "address is blocked");
+        assert!(
+            state
+                .identity_service
+                .proof(&USER_A.to_string())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
     #[async_std::test]
     async fn test_bad_request_format() {
         let state = State::default();
@@ -212,9 +529,11 @@ mod tests {
         let req_url = format!("/proof/{user_id}");
         let signature = proof_sign(
             &private_key,
+            &state.server_identity.address,
             user_id.to_string(),
-            5000,
+            IdtAmount::new(5000),
             PROOF_ID,
+            expires_in(60),
             &*state.nonce_manager,
         )
         .await
@@ -224,7 +543,9 @@ mod tests {
             "wrong_field": "wrong_value",
             "proof_id": PROOF_ID,
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
         });
         let mut req = HttpRequest::new(
             tide::http::Method::Post,
@@ -256,14 +577,16 @@ mod tests {
         };
 
         let target_user = "test_user".to_string();
-        let amount = 5000;
+        let amount = IdtAmount::new(5000);
         let req_url = format!("/proof/{target_user}");
 
         let signature = proof_sign(
             &private_key,
+            &state.server_identity.address,
             target_user.clone(),
             amount,
             PROOF_ID,
+            expires_in(60),
             &*state.nonce_manager,
         )
         .await
@@ -274,7 +597,9 @@ mod tests {
             "amount": amount,
             "proof_id": PROOF_ID,
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
         });
 
         let mut req = HttpRequest::new(
@@ -296,4 +621,280 @@ mod tests {
         let body: Value = response.body_json().await.unwrap();
         assert_eq!(body["error"], "not moderator");
     }
+
+    #[async_std::test]
+    async fn test_consent() {
+        let (private_key, moderator) = random_keypair();
+        let (user_key, user) = random_keypair();
+        let moderators = HashSet::from([moderator.clone()]);
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(HashSet::new(), moderators)),
+            identity_service: IdentityService {
+                config: IdentitySection {
+                    require_proof_consent: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let domain = state.server_identity.address.clone();
+        let nonce_manager = state.nonce_manager.clone();
+        let mut server = tide::with_state(state);
+        server.at("/proof/:user").post(route);
+        let post = |body: Value| {
+            let mut req = HttpRequest::new(
+                tide::http::Method::Post,
+                Url::parse(&format!("http://example.com/proof/{user}")).unwrap(),
+            );
+            req.set_body(body.to_string());
+            req.set_content_type(mime::JSON);
+            server.respond::<_, Response>(req)
+        };
+        let proof = |proof_id| {
+            let (private_key, user, domain, nonce_manager) =
+                (&private_key, &user, &domain, &nonce_manager);
+            async move {
+                let signature = proof_sign(
+                    private_key,
+                    domain,
+                    user.clone(),
+                    IdtAmount::new(100),
+                    proof_id,
+                    expires_in(60),
+                    &**nonce_manager,
+                )
+                .await
+                .unwrap();
+                json!({
+                    "from": signature.signer,
+                    "amount": 100,
+                    "proof_id": proof_id,
+                    "signature": signature.signature,
+                    "nonce": signature.freshness.nonce,
+                    "expires_at": signature.freshness.expires_at,
+                    "domain": signature.freshness.domain,
+                })
+            }
+        };
+
+        let mut response = post(proof(PROOF_ID).await).await.unwrap();
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "proof consent is required");
+
+        // consent for another proof id
+        let consent = proof_consent_sign(
+            &user_key,
+            &domain,
+            moderator.clone(),
+            IdtAmount::new(100),
+            PROOF_ID,
+            expires_in(60),
+            &*nonce_manager,
+        )
+        .await
+        .unwrap();
+        let mut body = proof(PROOF_ID + 1).await;
+        body["consent"] = json!({
+            "signature": consent.signature,
+            "nonce": consent.freshness.nonce,
+            "expires_at": consent.freshness.expires_at,
+            "domain": consent.freshness.domain,
+        });
+        let mut response = post(body).await.unwrap();
+        assert_eq!(response.status(), 400);
+        let error: Value = response.body_json().await.unwrap();
+        assert_eq!(error["error"], "consent verification failed");
+
+        let mut body = proof(PROOF_ID).await;
+        body["consent"] = json!({
+            "signature": consent.signature,
+            "nonce": consent.freshness.nonce,
+            "expires_at": consent.freshness.expires_at,
+            "domain": consent.freshness.domain,
+        });
+        let mut response = post(body).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["idt"], "100");
+    }
+
+    async fn transfer(state: &State, private_key: &str, moderator: &str) -> Response {
+        let signature = transfer_proofs_sign(
+            private_key,
+            &state.server_identity.address,
+            moderator.to_string(),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .unwrap();
+        let body = json!({
+            "from": signature.signer,
+            "moderator": moderator,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse("http://example.com/proofs/transfer").unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/proofs/transfer").post(transfer_route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_transfer() {
+        let (admin_key, admin) = random_keypair();
+        let (successor_key, successor) = random_keypair();
+        let (other_key, other) = random_keypair();
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::from([admin.clone()]),
+                HashSet::from(["retiring".to_string(), successor.clone(), other.clone()]),
+            )),
+            ..Default::default()
+        };
+        let service = &state.identity_service;
+        service
+            .prove_with_timestamp(
+                USER_A.to_string(),
+                "retiring".to_string(),
+                IdtAmount::new(100),
+                PROOF_ID,
+                10,
+            )
+            .await
+            .unwrap();
+
+        // no successor is designated yet
+        let mut response = transfer(&state, &successor_key, "retiring").await;
+        assert_eq!(response.status(), 403);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "not a successor of the moderator");
+
+        state
+            .admin_storage
+            .set_successor(&admin, "retiring".to_string(), Some(successor.clone()))
+            .await
+            .unwrap();
+        let response = transfer(&state, &other_key, "retiring").await;
+        assert_eq!(response.status(), 403);
+        let response = transfer(&state, &admin_key, "retiring").await;
+        assert_eq!(response.status(), 403);
+
+        let mut response = transfer(&state, &successor_key, "retiring").await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["transferred"], json!([USER_A]));
+        assert_eq!(body["from"], successor);
+        let proof = service.proof(&USER_A.to_string()).await.unwrap().unwrap();
+        assert_eq!(proof.moderator, successor);
+        assert_eq!(proof.timestamp, 10);
+    }
+
+    async fn post_batch(
+        state: &State,
+        private_key: &str,
+        entries: &[ProofBatchEntry],
+    ) -> (u16, Value) {
+        let signature = proof_batch_sign(
+            private_key,
+            &state.server_identity.address,
+            entries,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .unwrap();
+        let body = json!({
+            "from": signature.signer,
+            "entries": entries,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse("http://example.com/proof_batch").unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/proof_batch").post(batch_route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        (
+            response.status().into(),
+            response.body_json().await.unwrap(),
+        )
+    }
+
+    #[async_std::test]
+    async fn test_batch() {
+        let (private_key, moderator) = random_keypair();
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::new(),
+                HashSet::from([moderator]),
+            )),
+            ..Default::default()
+        };
+        let entry = |user: &str, amount| ProofBatchEntry {
+            user: user.to_string(),
+            amount,
+            proof_id: PROOF_ID,
+        };
+
+        let (other_key, _) = random_keypair();
+        let (status, _) =
+            post_batch(&state, &other_key, &[entry(USER_A, IdtAmount::new(100))]).await;
+        assert_eq!(status, 403);
+        let (status, body) = post_batch(&state, &private_key, &[]).await;
+        assert_eq!(status, 400);
+        assert_eq!(body["error"], "proof batch is empty");
+
+        let entries = [
+            entry(USER_A, IdtAmount::new(100)),
+            entry("userB", MAX_IDT_BY_PROOF + IdtAmount::new(1)),
+            entry(USER_A, IdtAmount::new(300)),
+        ];
+        let (status, body) = post_batch(&state, &private_key, &entries).await;
+        assert_eq!(status, 400);
+        assert_eq!(body["error"], "proof batch rejected");
+        assert_eq!(body["results"][0]["error"], Value::Null);
+        assert_eq!(
+            body["results"][1]["error"],
+            format!("max balance exceeded, max is {MAX_IDT_BY_PROOF} IDT")
+        );
+        assert_eq!(body["results"][2]["error"], "user is proven twice");
+        assert!(
+            state
+                .identity_service
+                .proof(&USER_A.to_string())
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let entries = [
+            entry(USER_A, IdtAmount::new(100)),
+            entry("userB", IdtAmount::new(200)),
+        ];
+        let (status, body) = post_batch(&state, &private_key, &entries).await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            body["results"],
+            json!([
+                {"user": USER_A, "proof_id": PROOF_ID.to_string(), "idt": "100"},
+                {"user": "userB", "proof_id": PROOF_ID.to_string(), "idt": "200"},
+            ])
+        );
+    }
 }
@@ -0,0 +1,115 @@
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::{
+        decay::{proof_expiry, proof_grace_period_end},
+        idt::proven_balance,
+    },
+    personhood::system_registry,
+    reminders::remind_at,
+    routes::{
+        State,
+        error::{ApiError, ErrorCode},
+    },
+};
+
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let state = req.state();
+    let service = &state.identity_service;
+
+    let (Some(proof), Some(expires_at)) = (
+        service.proof(&user).await?,
+        proof_expiry(service, &user).await?,
+    ) else {
+        return Ok(ApiError::new(404, ErrorCode::ProofNotFound).into());
+    };
+    let now = service.now();
+    let response = json!({
+        "user": user,
+        "registry": system_registry(&proof.moderator),
+        "moderator": proof.moderator,
+        "proof_id": proof.proof_id,
+        "amount": proof.amount.to_string(),
+        "timestamp": proof.timestamp,
+        "balance": proven_balance(service, &user).await?.to_string(),
+        "grace_period_end": proof_grace_period_end(service, &user).await?,
+        "expires_at": expires_at,
+        "remind_at": remind_at(expires_at, state.config.reminders.days_before_expiry),
+        "expired": now >= expires_at,
+    });
+    let response = Response::builder(200)
+        .body(response)
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{
+        IdtAmount,
+        tests::{MODERATOR, PROOF_ID, START_TIMESTAMP, USER_A, service_with_mock_clock},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn get_status(state: &State, user: &str) -> Response {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/proofs/{user}/status")).unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/proofs/:user/status").get(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (service, clock) = service_with_mock_clock();
+        let state = State {
+            identity_service: service,
+            ..Default::default()
+        };
+        state
+            .identity_service
+            .prove_with_timestamp(
+                USER_A.to_string(),
+                MODERATOR.to_string(),
+                IdtAmount::new(10),
+                PROOF_ID,
+                START_TIMESTAMP,
+            )
+            .await
+            .unwrap();
+        clock.advance(86400);
+
+        let mut response = get_status(&state, USER_A).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["amount"], "10");
+        // moderator proof
+        assert!(body["registry"].is_null());
+        assert_eq!(body["balance"], "9");
+        assert_eq!(body["expires_at"], START_TIMESTAMP + 10 * 86400);
+        assert_eq!(body["remind_at"], START_TIMESTAMP + 3 * 86400);
+        assert_eq!(body["expired"], false);
+
+        clock.advance(9 * 86400);
+        let mut response = get_status(&state, USER_A).await;
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["balance"], "0");
+        assert_eq!(body["expired"], true);
+    }
+
+    #[async_std::test]
+    async fn test_not_found() {
+        let state = State::default();
+        let mut response = get_status(&state, USER_A).await;
+        assert_eq!(response.status(), 404);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "proof not found");
+    }
+}
@@ -0,0 +1,450 @@
+use serde_json::json;
+use tide::{Middleware, Next, Request, Response, http::mime};
+
+use crate::{
+    federation::ProxyRequest,
+    identity::UserAddress,
+    routes::{
+        State,
+        error::{ApiError, ErrorCode},
+        timestamp_error,
+    },
+    verify::proxy::{proxy_sign, proxy_verify},
+};
+
+pub const PROXY_RELAY_HEADER: &str = "X-Proxy-Relay";
+pub const PROXY_TIMESTAMP_HEADER: &str = "X-Proxy-Timestamp";
+pub const PROXY_SIGNATURE_HEADER: &str = "X-Proxy-Signature";
+
+// routes in the form of `/<route>/:user` that act on behalf of a single user
+const PROXIED_ROUTES: [&str; 7] = [
+    "idt", "vouch", "forget", "punish", "proof", "vouchers", "history",
+];
+
+// forwards requests for users with a remote home server to that server, signing
+// them with the key of this server. Requests relayed by another server must carry a fresh
+// signature of a registered server.
+pub struct ProxyMiddleware;
+
+// checks the signature of a relayed request, the body is read and put back
+async fn relay_error(
+    state: &State,
+    req: &mut Request<State>,
+    relay: &UserAddress,
+) -> tide::Result<Option<Response>> {
+    if !state.server_storage.servers().await?.contains_key(relay) {
+        return Ok(Some(ApiError::bad_request(ErrorCode::UnknownServer).into()));
+    }
+    let header = |name: &str| req.header(name).map(|value| value.as_str().to_string());
+    let (Some(timestamp), Some(signature)) = (
+        header(PROXY_TIMESTAMP_HEADER).and_then(|t| t.parse::<u64>().ok()),
+        header(PROXY_SIGNATURE_HEADER),
+    ) else {
+        return Ok(Some(
+            ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into(),
+        ));
+    };
+    if let Some(response) = timestamp_error(state, timestamp) {
+        return Ok(Some(response));
+    }
+    let method = req.method().to_string();
+    let path = match req.url().query() {
+        Some(query) => format!("{}?{}", req.url().path(), query),
+        None => req.url().path().to_string(),
+    };
+    let body = req.body_string().await?;
+    let verified = proxy_verify(&signature, relay, &method, &path, &body, timestamp);
+    req.set_body(body);
+    Ok(verified
+        .is_err()
+        .then(|| ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into()))
+}
+
+fn target_user(path: &str) -> Option<UserAddress> {
+    let mut segments = path.trim_start_matches('/').split('/');
+    let route = segments.next()?;
+    let user = segments.next()?;
+    if segments.next().is_some() || user.is_empty() || !PROXIED_ROUTES.contains(&route) {
+        return None;
+    }
+    Some(user.to_string())
+}
+
+#[tide::utils::async_trait]
+impl Middleware<State> for ProxyMiddleware {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let state = req.state().clone();
+        // requests relayed by another server are always served locally to avoid loops
+        if let Some(relay) = req.header(PROXY_RELAY_HEADER) {
+            let relay = relay.as_str().to_string();
+            if let Some(response) = relay_error(&state, &mut req, &relay).await? {
+                return Ok(response);
+            }
+            return Ok(next.run(req).await);
+        }
+        if !state.config.federation.proxy {
+            return Ok(next.run(req).await);
+        }
+        let Some(user) = target_user(req.url().path()) else {
+            return Ok(next.run(req).await);
+        };
+        let home = match state.home_storage.home(&user).await? {
+            Some(home) if home != state.server_identity.address => home,
+            _ => return Ok(next.run(req).await),
+        };
+        let Some(info) = state.server_storage.servers().await?.remove(&home) else {
+            log::warn!("Home server {} of {} is not registered", home, user);
+            return Ok(next.run(req).await);
+        };
+
+        let method = req.method().to_string();
+        let path = match req.url().query() {
+            Some(query) => format!("{}?{}", req.url().path(), query),
+            None => req.url().path().to_string(),
+        };
+        let body = req.body_string().await?;
+        let timestamp = state.identity_service.now();
+        let signature = proxy_sign(
+            &state.server_identity.private_key,
+            &method,
+            &path,
+            &body,
+            timestamp,
+        )
+        .await?;
+        let request = ProxyRequest {
+            method,
+            path,
+            headers: vec![
+                (
+                    PROXY_RELAY_HEADER.to_string(),
+                    state.server_identity.address.clone(),
+                ),
+                (PROXY_TIMESTAMP_HEADER.to_string(), timestamp.to_string()),
+                (PROXY_SIGNATURE_HEADER.to_string(), signature),
+            ],
+            body,
+        };
+        let proxy = json!({
+            "home": home,
+            "url": info.url,
+            "relay": state.server_identity.address,
+        });
+
+        let remote = match state.federation_client.forward(&info.url, request).await {
+            Ok(remote) => remote,
+            Err(e) => {
+                log::warn!(
+                    "Failed to proxy request for {} to {}: {}",
+                    user,
+                    info.url,
+                    e
+                );
+                return Ok(ApiError::new(502, ErrorCode::HomeServerUnavailable)
+                    .with("proxy", proxy)
+                    .into());
+            }
+        };
+        let body = match serde_json::from_str::<serde_json::Value>(&remote.body) {
+            Ok(serde_json::Value::Object(mut object)) => {
+                object.insert("proxy".into(), proxy);
+                serde_json::Value::Object(object)
+            }
+            // not a JSON object, nowhere to put the metadata
+            _ => {
+                return Ok(Response::builder(remote.status).body(remote.body).build());
+            }
+        };
+        Ok(Response::builder(remote.status)
+            .body(body)
+            .content_type(mime::JSON)
+            .build())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        config::{Config, FederationSection},
+        federation::{
+            HttpFederationClient, InMemoryFederationClient, RemoteUser, mock::MockServer,
+        },
+        identity::{
+            IdtAmount,
+            proof::prove,
+            tests::{MODERATOR, PROOF_ID, USER_A},
+        },
+        numbers::Rational,
+        routes::setup_routes,
+        servers::storage::ServerInfo,
+        verify::random_keypair,
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response as HttpResponse, Url};
+
+    async fn proxy_state(enabled: bool) -> (State, Arc<InMemoryFederationClient>) {
+        let client = Arc::new(InMemoryFederationClient::default());
+        let state = State {
+            federation_client: client.clone(),
+            config: Arc::new(Config {
+                federation: FederationSection {
+                    proxy: enabled,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        state
+            .server_storage
+            .add_server(
+                "server1".to_string(),
+                ServerInfo {
+                    url: "http://server1".to_string(),
+                    scale: Rational::default(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        state
+            .home_storage
+            .set_home(USER_A.to_string(), "server1".to_string())
+            .await
+            .unwrap();
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        (state, client)
+    }
+
+    async fn get_idt_with(state: &State, headers: &[(&str, String)]) -> HttpResponse {
+        let mut req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/idt/{USER_A}")).unwrap(),
+        );
+        for (name, value) in headers {
+            req.insert_header(*name, value.as_str());
+        }
+        let mut server = tide::with_state(state.clone());
+        setup_routes(&mut server);
+        server.respond(req).await.unwrap()
+    }
+
+    async fn get_idt(state: &State, headers: &[(&str, String)]) -> Value {
+        let mut response = get_idt_with(state, headers).await;
+        assert_eq!(response.status(), 200);
+        response.body_json().await.unwrap()
+    }
+
+    // registers a relaying server and signs the request to `/idt/USER_A` with its key
+    async fn relay_headers(state: &State, timestamp: u64) -> Vec<(&'static str, String)> {
+        let (private_key, relay) = random_keypair();
+        state
+            .server_storage
+            .add_server(
+                relay.clone(),
+                ServerInfo {
+                    url: "http://relay".to_string(),
+                    scale: Rational::default(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let path = format!("/idt/{USER_A}");
+        let signature = proxy_sign(&private_key, "GET", &path, "", timestamp)
+            .await
+            .unwrap();
+        vec![
+            (PROXY_RELAY_HEADER, relay),
+            (PROXY_TIMESTAMP_HEADER, timestamp.to_string()),
+            (PROXY_SIGNATURE_HEADER, signature),
+        ]
+    }
+
+    #[test]
+    fn test_target_user() {
+        assert_eq!(target_user("/idt/user"), Some("user".to_string()));
+        assert_eq!(target_user("/vouch/user"), Some("user".to_string()));
+        assert_eq!(target_user("/is_admin/user"), None);
+        assert_eq!(target_user("/idt/"), None);
+        assert_eq!(target_user("/idt/user/extra"), None);
+        assert_eq!(target_user("/servers"), None);
+    }
+
+    #[async_std::test]
+    async fn test_forward() {
+        let (state, client) = proxy_state(true).await;
+        let body = get_idt(&state, &[]).await;
+        assert_eq!(body["path"], format!("/idt/{USER_A}"));
+        assert_eq!(body["proxy"]["home"], "server1");
+        assert_eq!(body["proxy"]["relay"], state.server_identity.address);
+
+        let forwarded = client.forwarded().await;
+        assert_eq!(forwarded.len(), 1);
+        let (url, request) = &forwarded[0];
+        assert_eq!(url, "http://server1");
+        let header = |name: &str| {
+            request
+                .headers
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.clone())
+                .unwrap()
+        };
+        assert!(
+            proxy_verify(
+                &header(PROXY_SIGNATURE_HEADER),
+                &header(PROXY_RELAY_HEADER),
+                "GET",
+                &request.path,
+                &request.body,
+                header(PROXY_TIMESTAMP_HEADER).parse().unwrap(),
+            )
+            .is_ok()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_served_locally() {
+        // proxy mode disabled
+        let (state, client) = proxy_state(false).await;
+        let body = get_idt(&state, &[]).await;
+        assert_eq!(body["idt"], "100");
+        assert!(client.forwarded().await.is_empty());
+
+        // request was already relayed by another server
+        let (state, client) = proxy_state(true).await;
+        let headers = relay_headers(&state, state.identity_service.now()).await;
+        let body = get_idt(&state, &headers).await;
+        assert_eq!(body["idt"], "100");
+        assert!(client.forwarded().await.is_empty());
+
+        // home server is this server
+        let (state, client) = proxy_state(true).await;
+        state
+            .home_storage
+            .set_home(USER_A.to_string(), state.server_identity.address.clone())
+            .await
+            .unwrap();
+        let body = get_idt(&state, &[]).await;
+        assert_eq!(body["idt"], "100");
+        assert!(client.forwarded().await.is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_forged_relay() {
+        let (state, client) = proxy_state(true).await;
+        let error = |mut response: HttpResponse| async move {
+            assert_eq!(response.status(), 400);
+            let body: Value = response.body_json().await.unwrap();
+            body["error"].as_str().unwrap().to_string()
+        };
+        let now = state.identity_service.now();
+
+        // relay header alone
+        let response = get_idt_with(&state, &[(PROXY_RELAY_HEADER, "0x01".into())]).await;
+        assert_eq!(error(response).await, "unknown server");
+
+        // registered relay without a signature
+        let mut headers = relay_headers(&state, now).await;
+        headers.truncate(1);
+        let response = get_idt_with(&state, &headers).await;
+        assert_eq!(error(response).await, "signature verification failed");
+
+        // signature of another request
+        let mut headers = relay_headers(&state, now).await;
+        headers[1].1 = (now + 1).to_string();
+        let response = get_idt_with(&state, &headers).await;
+        assert_eq!(error(response).await, "signature verification failed");
+
+        // stale signature
+        let skew = state.config.signatures.max_timestamp_skew;
+        let headers = relay_headers(&state, now - skew - 1).await;
+        let response = get_idt_with(&state, &headers).await;
+        assert_eq!(error(response).await, "timestamp is out of bounds");
+
+        // rejected relays are never forwarded
+        assert!(client.forwarded().await.is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_forward_over_http() {
+        let remote = MockServer::start().await.unwrap();
+        remote
+            .set_user(
+                USER_A.to_string(),
+                RemoteUser {
+                    idt: IdtAmount::new(7),
+                    vouchers: vec![],
+                },
+            )
+            .await;
+        let state = State {
+            federation_client: Arc::new(HttpFederationClient),
+            config: Arc::new(Config {
+                federation: FederationSection {
+                    proxy: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        state
+            .server_storage
+            .add_server(
+                remote.address(),
+                ServerInfo {
+                    url: remote.url.clone(),
+                    scale: Rational::default(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        state
+            .home_storage
+            .set_home(USER_A.to_string(), remote.address())
+            .await
+            .unwrap();
+
+        let body = get_idt(&state, &[]).await;
+        assert_eq!(body["idt"], "7");
+        assert_eq!(body["proxy"]["home"], remote.address());
+
+        let requests = remote.requests().await;
+        assert_eq!(requests.len(), 1);
+        let header = |name: &str| {
+            requests[0]
+                .headers
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.clone())
+                .unwrap()
+        };
+        assert_eq!(header(PROXY_RELAY_HEADER), state.server_identity.address);
+        assert!(
+            proxy_verify(
+                &header(PROXY_SIGNATURE_HEADER),
+                &header(PROXY_RELAY_HEADER),
+                "GET",
+                &requests[0].path,
+                &requests[0].body,
+                header(PROXY_TIMESTAMP_HEADER).parse().unwrap(),
+            )
+            .is_ok()
+        );
+        remote.stop().await;
+    }
+}
@@ -5,9 +5,17 @@ use serde_json::json;
 use tide::{Request, Response, http::mime};
 
 use crate::{
-    identity::{IdtAmount, ProofId, UserAddress, idt::balance, punish::punish},
-    routes::State,
-    verify::{nonce::Nonce, punish::punish_verify},
+    identity::{
+        IdtAmount, ProofId, UserAddress, error::Error, idt::balance, punish::punish_with_reason,
+    },
+    notifications::Notification,
+    pending_penalties::{self, PendingPenalty, requires_approval},
+    routes::{
+        Role, SignedRequest, State, balance_changed, check_role,
+        error::{ApiError, ErrorCode},
+        freshness_error, signed_body,
+    },
+    verify::{punish::punish_message_prefix, signature::Freshness, verify_message},
 };
 
 #[derive(Deserialize)]
@@ -15,63 +23,88 @@ struct PunishRequest {
     from: UserAddress,
     amount: IdtAmount,
     proof_id: ProofId,
+    // code of `identity.penalty_reasons`
+    #[serde(default)]
+    reason: Option<String>,
     signature: String,
-    nonce: Nonce,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for PunishRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
 }
 
 pub async fn route(mut req: Request<State>) -> tide::Result {
     let user = req.param("user")?.to_string();
-    let body: PunishRequest = req.body_json().await?;
+    let body: PunishRequest = signed_body(&mut req).await?;
     let moderator = body.from;
     let amount = body.amount;
     let proof_id = body.proof_id;
-    if req
-        .state()
-        .admin_storage
-        .check_moderator(&moderator)
-        .await
-        .is_err()
-    {
-        return Ok(Response::builder(403)
-            .body(json!({"error": "not moderator"}))
-            .content_type(mime::JSON)
-            .build());
+    let reason = body.reason;
+    let prefix = punish_message_prefix(user.clone(), amount, proof_id, reason.clone());
+    if let Err(response) = check_role(req.state(), &moderator, Role::Moderator, &prefix).await {
+        return Ok(response);
+    }
+
+    if let Some(response) = freshness_error(req.state(), &body.freshness) {
+        return Ok(response);
     }
 
-    if punish_verify(
+    if verify_message(
         body.signature,
         &moderator,
-        body.nonce,
-        user.clone(),
-        amount,
-        proof_id,
+        &body.freshness,
+        &prefix,
         &*req.state().nonce_manager,
     )
     .await
     .is_err()
     {
-        return Ok(Response::builder(400)
-            .body(json!({"error": "signature verification failed"}))
-            .content_type(mime::JSON)
-            .build());
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    if requires_approval(&req.state().config.escalation, amount) {
+        return propose(req.state(), user, moderator, amount, proof_id, reason).await;
     }
 
-    punish(
+    let balance_before = balance(&req.state().identity_service, &user).await?;
+    let punish_result = punish_with_reason(
         &req.state().identity_service,
         user.clone(),
         moderator.clone(),
         amount,
         proof_id,
+        reason.clone(),
     )
-    .await?;
+    .await;
+    if matches!(punish_result, Err(Error::UnknownPenaltyReason(_))) {
+        return Ok(unknown_reason());
+    }
+    punish_result?;
 
     let user_balance = balance(&req.state().identity_service, &user).await?;
+    req.state()
+        .notifications
+        .notify(
+            &user,
+            Notification::Punished {
+                moderator: moderator.clone(),
+                amount,
+                proof_id,
+            },
+        )
+        .await;
+    balance_changed(req.state(), &user, balance_before, user_balance).await;
     let response: HashMap<String, serde_json::Value> = HashMap::from([
         ("user".into(), user.into()),
         ("from".into(), moderator.into()),
         ("idt".into(), user_balance.to_string().into()),
         ("proof_id".into(), proof_id.to_string().into()),
-        ("nonce".into(), body.nonce.into()),
+        ("reason".into(), reason.into()),
+        ("nonce".into(), body.freshness.nonce.into()),
     ]);
     let response = Response::builder(200)
         .body(json!(response))
@@ -80,18 +113,78 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
     Ok(response)
 }
 
+fn unknown_reason() -> Response {
+    ApiError::bad_request(ErrorCode::UnknownPenaltyReason).into()
+}
+
+// penalties above `escalation.approval_threshold` wait for an admin, the user is not
+// punished yet
+async fn propose(
+    state: &State,
+    user: UserAddress,
+    moderator: UserAddress,
+    amount: IdtAmount,
+    proof_id: ProofId,
+    reason: Option<String>,
+) -> tide::Result {
+    let penalty = PendingPenalty {
+        id: 0,
+        user,
+        moderator,
+        amount,
+        proof_id,
+        reason,
+        created_at: 0,
+        decision: None,
+    };
+    let penalty = match pending_penalties::propose(
+        &state.identity_service,
+        &*state.pending_penalties,
+        penalty,
+    )
+    .await
+    {
+        Ok(penalty) => penalty,
+        Err(pending_penalties::error::Error::IdentityError(Error::UnknownPenaltyReason(_))) => {
+            return Ok(unknown_reason());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    log::info!(
+        "Penalty {} of {} by {} waits for an admin",
+        penalty.id,
+        penalty.user,
+        penalty.moderator
+    );
+    Ok(Response::builder(202)
+        .body(json!({
+            "pending": true,
+            "penalty": penalty,
+        }))
+        .content_type(mime::JSON)
+        .build())
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashSet, sync::Arc};
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::Arc,
+    };
 
     use super::*;
     use crate::{
         admins::InMemoryAdminStorage,
+        config::{Config, EscalationSection},
         identity::{
+            IdentityService,
+            penalty_reasons::PenaltyReasonPolicy,
             proof::prove,
             tests::{MODERATOR, PROOF_ID, USER_A},
         },
-        verify::{punish::punish_sign, random_keypair},
+        notifications::{Contact, ContactKind, InMemoryNotifier, NotificationDispatcher, Notifier},
+        numbers::Rational,
+        verify::{expires_in, punish::punish_sign, random_keypair, sign_message},
     };
     use serde_json::Value;
     use tide::http::{Request as HttpRequest, Response, Url};
@@ -106,13 +199,13 @@ mod tests {
             ..Default::default()
         };
         let user_id = USER_A;
-        let amount = 5000;
+        let amount = IdtAmount::new(5000);
 
         prove(
             &state.identity_service,
             USER_A.to_string(),
             moderator.clone(),
-            10000,
+            IdtAmount::new(10000),
             PROOF_ID,
         )
         .await
@@ -121,9 +214,11 @@ mod tests {
         let req_url = format!("/punish/{user_id}");
         let signature = punish_sign(
             &private_key,
+            &state.server_identity.address,
             user_id.to_string(),
             amount,
             PROOF_ID,
+            expires_in(60),
             &*state.nonce_manager,
         )
         .await
@@ -133,7 +228,9 @@ mod tests {
             "amount": amount,
             "proof_id": PROOF_ID,
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
         });
 
         let mut req = HttpRequest::new(
@@ -155,7 +252,7 @@ mod tests {
         // 10000 IDT minus 5000 IDT penalty
         assert_eq!(body["idt"], "5000");
         assert_eq!(body["proof_id"], PROOF_ID.to_string());
-        assert_eq!(body["nonce"], signature.nonce);
+        assert_eq!(body["nonce"], signature.freshness.nonce);
     }
 
     #[async_std::test]
@@ -199,14 +296,16 @@ mod tests {
         };
 
         let target_user = "test_user".to_string();
-        let amount = 5000;
+        let amount = IdtAmount::new(5000);
         let req_url = format!("/punish/{target_user}");
 
         let signature = punish_sign(
             &private_key,
+            &state.server_identity.address,
             target_user.clone(),
             amount,
             PROOF_ID,
+            expires_in(60),
             &*state.nonce_manager,
         )
         .await
@@ -217,7 +316,9 @@ mod tests {
             "amount": amount,
             "proof_id": PROOF_ID,
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
         });
 
         let mut req = HttpRequest::new(
@@ -238,5 +339,264 @@ mod tests {
         );
         let body: Value = response.body_json().await.unwrap();
         assert_eq!(body["error"], "not moderator");
+        assert_eq!(body["required_role"], "moderator");
+        assert_eq!(body["roles"], json!([]));
+        assert_eq!(
+            body["message_prefix"],
+            format!("punish/{target_user}/{amount}/{PROOF_ID}")
+        );
+    }
+
+    #[async_std::test]
+    async fn test_notifications() {
+        let (private_key, moderator) = random_keypair();
+        let moderators = HashSet::from([moderator.clone()]);
+        let notifier = Arc::new(InMemoryNotifier::default());
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(HashSet::new(), moderators)),
+            notifications: Arc::new(NotificationDispatcher {
+                transports: HashMap::from([(
+                    ContactKind::Email,
+                    notifier.clone() as Arc<dyn Notifier>,
+                )]),
+                balance_thresholds: vec![IdtAmount::new(5000)],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let contact = Contact {
+            kind: ContactKind::Email,
+            address: "user@example.com".to_string(),
+        };
+        state
+            .notifications
+            .contacts
+            .set_contact(USER_A.to_string(), contact.clone())
+            .await
+            .unwrap();
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            moderator.clone(),
+            IdtAmount::new(6000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+
+        let signature = punish_sign(
+            &private_key,
+            &state.server_identity.address,
+            USER_A.to_string(),
+            IdtAmount::new(2000),
+            PROOF_ID,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .unwrap();
+        let body = json!({
+            "from": moderator,
+            "amount": 2000,
+            "proof_id": PROOF_ID,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/punish/{USER_A}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state);
+        server.at("/punish/:user").post(route);
+        let response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+
+        let sent: Vec<Notification> = notifier
+            .sent()
+            .await
+            .into_iter()
+            .map(|(user, to, notification)| {
+                assert_eq!(user, USER_A);
+                assert_eq!(to, contact);
+                notification
+            })
+            .collect();
+        assert_eq!(
+            sent,
+            vec![
+                Notification::Punished {
+                    moderator,
+                    amount: IdtAmount::new(2000),
+                    proof_id: PROOF_ID,
+                },
+                Notification::BalanceThreshold {
+                    threshold: IdtAmount::new(5000),
+                    balance: IdtAmount::new(4000),
+                    above: false,
+                },
+            ]
+        );
+    }
+
+    #[async_std::test]
+    async fn test_reason() {
+        let (private_key, moderator) = random_keypair();
+        let moderators = HashSet::from([moderator.clone()]);
+        let mut service = IdentityService::default();
+        service.config.penalty_reasons = HashMap::from([(
+            "fraud".to_string(),
+            PenaltyReasonPolicy {
+                multiplier: Rational::new(2, 1).unwrap(),
+                decay_per_day: IdtAmount::new(1),
+            },
+        )]);
+        let state = State {
+            identity_service: service,
+            admin_storage: Arc::new(InMemoryAdminStorage::new(HashSet::new(), moderators)),
+            ..Default::default()
+        };
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            moderator.clone(),
+            IdtAmount::new(10000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        let mut server = tide::with_state(state.clone());
+        server.at("/punish/:user").post(route);
+
+        for (reason, status) in [("unknown", 400), ("fraud", 200)] {
+            let prefix = punish_message_prefix(
+                USER_A.to_string(),
+                IdtAmount::new(2000),
+                PROOF_ID,
+                Some(reason.to_string()),
+            );
+            let signature = sign_message(
+                &private_key,
+                &state.server_identity.address,
+                &prefix,
+                expires_in(60),
+                &*state.nonce_manager,
+            )
+            .await
+            .unwrap();
+            let body = json!({
+                "from": moderator,
+                "amount": 2000,
+                "proof_id": PROOF_ID,
+                "reason": reason,
+                "signature": signature.signature,
+                "nonce": signature.freshness.nonce,
+                "expires_at": signature.freshness.expires_at,
+                "domain": signature.freshness.domain,
+            });
+            let mut req = HttpRequest::new(
+                tide::http::Method::Post,
+                Url::parse(&format!("http://example.com/punish/{USER_A}")).unwrap(),
+            );
+            req.set_body(body);
+            req.set_content_type(mime::JSON);
+            let response: Response = server.respond(req).await.unwrap();
+            assert_eq!(response.status(), status);
+        }
+
+        // the fraud penalty weighs twice its amount
+        assert_eq!(
+            balance(&state.identity_service, &USER_A.to_string())
+                .await
+                .unwrap(),
+            IdtAmount::new(6000)
+        );
+        assert_eq!(
+            state
+                .identity_service
+                .penalty_reason(&USER_A.to_string())
+                .await
+                .unwrap(),
+            Some("fraud".to_string())
+        );
+    }
+
+    #[async_std::test]
+    async fn test_escalation() {
+        let (private_key, moderator) = random_keypair();
+        let moderators = HashSet::from([moderator.clone()]);
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(HashSet::new(), moderators)),
+            config: Arc::new(Config {
+                escalation: EscalationSection {
+                    approval_threshold: Some(IdtAmount::new(1000)),
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            moderator.clone(),
+            IdtAmount::new(10000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        let mut server = tide::with_state(state.clone());
+        server.at("/punish/:user").post(route);
+
+        for (amount, status) in [(1000, 200), (2000, 202)] {
+            let signature = punish_sign(
+                &private_key,
+                &state.server_identity.address,
+                USER_A.to_string(),
+                IdtAmount::new(amount),
+                PROOF_ID,
+                expires_in(60),
+                &*state.nonce_manager,
+            )
+            .await
+            .unwrap();
+            let body = json!({
+                "from": moderator,
+                "amount": amount,
+                "proof_id": PROOF_ID,
+                "signature": signature.signature,
+                "nonce": signature.freshness.nonce,
+                "expires_at": signature.freshness.expires_at,
+                "domain": signature.freshness.domain,
+            });
+            let mut req = HttpRequest::new(
+                tide::http::Method::Post,
+                Url::parse(&format!("http://example.com/punish/{USER_A}")).unwrap(),
+            );
+            req.set_body(body);
+            req.set_content_type(mime::JSON);
+            let mut response: Response = server.respond(req).await.unwrap();
+            assert_eq!(response.status(), status);
+            if status == 202 {
+                let body: Value = response.body_json().await.unwrap();
+                assert_eq!(body["pending"], true);
+                assert_eq!(body["penalty"]["id"], 1);
+                assert_eq!(body["penalty"]["moderator"], moderator);
+            }
+        }
+
+        // only the penalty below the threshold is applied
+        assert_eq!(
+            balance(&state.identity_service, &USER_A.to_string())
+                .await
+                .unwrap(),
+            IdtAmount::new(9000)
+        );
+        let pending = state.pending_penalties.undecided(0, 10).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].amount, IdtAmount::new(2000));
+        assert_eq!(pending[0].user, USER_A);
     }
 }
@@ -0,0 +1,106 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    reports::ReportId,
+    routes::{
+        State,
+        error::{ApiError, ErrorCode},
+    },
+};
+
+// reports returned by a single request, pass `cursor` as `after` to get the next page
+pub const REPORTS_PAGE_SIZE: usize = 100;
+
+const CURSOR_SCOPE: &str = "reports";
+
+#[derive(Deserialize)]
+struct ReportsQuery {
+    after: Option<String>,
+}
+
+// open reports waiting for a moderator, oldest first
+pub async fn route(req: Request<State>) -> tide::Result {
+    let Ok(query) = req.query::<ReportsQuery>() else {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidQuery).into());
+    };
+    let cursors = req.state().cursors();
+    let after: ReportId = match query
+        .after
+        .as_deref()
+        .map(|c| cursors.decode(CURSOR_SCOPE, c))
+    {
+        None => 0,
+        Some(Some(id)) => id,
+        Some(None) => return Ok(ApiError::bad_request(ErrorCode::InvalidAfter).into()),
+    };
+    let reports = req
+        .state()
+        .reports
+        .open_reports(after, REPORTS_PAGE_SIZE)
+        .await?;
+    let cursor = reports.last().map(|r| r.id).unwrap_or(after);
+    let response = Response::builder(200)
+        .body(json!({
+            "reports": reports,
+            "cursor": cursors.encode(CURSOR_SCOPE, &cursor),
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reports::{ReportAction, Resolution};
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn reports(state: &State, query: &str) -> Value {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/reports{query}")).unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/reports").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        response.body_json().await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        for user in ["userA", "userB", "userC"] {
+            state
+                .reports
+                .add_report("reporter".into(), user.into(), "spam".into(), 10)
+                .await
+                .unwrap();
+        }
+        let resolution = Resolution {
+            moderator: "moderator".into(),
+            action: ReportAction::Dismiss,
+            resolved_at: 20,
+        };
+        state.reports.resolve(2, resolution).await.unwrap();
+
+        let body = reports(&state, "").await;
+        let list = body["reports"].as_array().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0]["user"], "userA");
+        assert_eq!(list[0]["reason"], "spam");
+        assert_eq!(list[1]["id"], 3);
+        let cursor = state.cursors().encode(CURSOR_SCOPE, &1);
+        let body = reports(&state, &format!("?after={cursor}")).await;
+        assert_eq!(body["reports"][0]["user"], "userC");
+        let body = reports(
+            &state,
+            &format!("?after={}", body["cursor"].as_str().unwrap()),
+        )
+        .await;
+        assert!(body["reports"].as_array().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+use serde::Deserialize;
+use tide::{Request, Response, http::mime};
+
+use crate::routes::{
+    State,
+    error::{ApiError, ErrorCode},
+};
+
+#[derive(Deserialize)]
+struct LatestQuery {
+    // `json` or `html`
+    format: Option<String>,
+}
+
+// the latest scheduled summary, see `summaries` module
+pub async fn route(req: Request<State>) -> tide::Result {
+    let Ok(query) = req.query::<LatestQuery>() else {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidQuery).into());
+    };
+    let html = match query.format.as_deref() {
+        None | Some("json") => false,
+        Some("html") => true,
+        Some(_) => return Ok(ApiError::bad_request(ErrorCode::UnknownFormat).into()),
+    };
+    let Some(stored) = req.state().summaries.latest().await? else {
+        return Ok(ApiError::new(404, ErrorCode::NoSummary).into());
+    };
+    let response = if html {
+        Response::builder(200)
+            .body(stored.summary.html())
+            .content_type(mime::HTML)
+            .build()
+    } else {
+        Response::builder(200)
+            .body(serde_json::to_value(&stored.summary)?)
+            .content_type(mime::JSON)
+            .build()
+    };
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::identity::IdtAmount;
+    use crate::summaries::{Mover, StoredSummary, Summary};
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn latest(state: &State, query: &str) -> Response {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/reports/latest{query}")).unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/reports/latest").get(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        assert_eq!(latest(&state, "").await.status(), 404);
+
+        let summary = Summary {
+            period_start: 10,
+            period_end: 20,
+            new_users: 2,
+            top_movers: vec![Mover {
+                user: "<userA>".into(),
+                before: IdtAmount::new(0),
+                after: IdtAmount::new(100),
+            }],
+            ..Default::default()
+        };
+        state
+            .summaries
+            .set_latest(StoredSummary {
+                summary,
+                last_seq: 5,
+                balances: BTreeMap::from([("<userA>".to_string(), IdtAmount::new(100))]),
+            })
+            .await
+            .unwrap();
+
+        let mut response = latest(&state, "").await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["new_users"], 2);
+        assert_eq!(body["top_movers"][0]["after"], 100);
+        // the baseline of the next summary is not served
+        assert!(body.get("balances").is_none());
+
+        let mut response = latest(&state, "?format=html").await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.content_type(), Some(mime::HTML));
+        let body = response.body_string().await.unwrap();
+        assert!(body.contains("<td>&lt;userA&gt;</td>"));
+
+        assert_eq!(latest(&state, "?format=csv").await.status(), 400);
+    }
+}
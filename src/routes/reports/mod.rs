@@ -0,0 +1,22 @@
+use crate::{
+    reports::error::Error,
+    routes::error::{ApiError, ErrorCode},
+};
+
+pub mod get_reports;
+pub mod latest;
+pub mod report;
+pub mod resolve;
+
+// client errors are reported, storage errors fail the request
+fn error_response(error: Error) -> tide::Result {
+    match error {
+        Error::SelfReport => Ok(ApiError::bad_request(ErrorCode::CannotReportYourself).into()),
+        Error::InvalidReason => Ok(ApiError::bad_request(ErrorCode::InvalidReason).into()),
+        Error::UnknownReport(_) => Ok(ApiError::new(404, ErrorCode::ReportNotFound).into()),
+        Error::AlreadyResolved(_) => {
+            Ok(ApiError::new(409, ErrorCode::ReportAlreadyResolved).into())
+        }
+        e => Err(e.into()),
+    }
+}
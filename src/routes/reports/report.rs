@@ -0,0 +1,171 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::UserAddress,
+    reports::submit,
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        freshness_error,
+        reports::error_response,
+        signed_body,
+    },
+    verify::{report::report_verify, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct ReportRequest {
+    from: UserAddress,
+    reason: String,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for ReportRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// adds a report of the user to the moderator queue, signed by the reporter
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let body: ReportRequest = signed_body(&mut req).await?;
+    let state = req.state();
+
+    if let Some(response) = freshness_error(state, &body.freshness) {
+        return Ok(response);
+    }
+
+    if report_verify(
+        body.signature,
+        &body.from,
+        &body.freshness,
+        &user,
+        &body.reason,
+        &*state.nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    let report = match submit(
+        &*state.reports,
+        body.from,
+        user,
+        body.reason,
+        state.identity_service.now(),
+    )
+    .await
+    {
+        Ok(report) => report,
+        Err(e) => return error_response(e),
+    };
+
+    let response = Response::builder(200)
+        .body(json!({
+            "id": report.id,
+            "user": report.user,
+            "from": report.reporter,
+            "nonce": body.freshness.nonce,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::verify::{expires_in, random_keypair, report::report_sign};
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    pub async fn report_user(state: &State, user: &str, reason: &str) -> Response {
+        let (private_key, reporter) = random_keypair();
+        let signature = report_sign(
+            &private_key,
+            &state.server_identity.address,
+            &user.to_string(),
+            reason,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        let body = json!({
+            "from": reporter,
+            "reason": reason,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/report/{user}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/report/:user").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        let mut response = report_user(&state, "userA", "spam").await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["id"], 1);
+        assert_eq!(body["user"], "userA");
+        let report = state.reports.report(1).await.unwrap().unwrap();
+        assert_eq!(report.reason, "spam");
+        assert_eq!(report.reporter, body["from"]);
+
+        let response = report_user(&state, "userA", "").await;
+        assert_eq!(response.status(), 400);
+    }
+
+    #[async_std::test]
+    async fn test_bad_signature() {
+        let state = State::default();
+        let (private_key, _) = random_keypair();
+        let signature = report_sign(
+            &private_key,
+            &state.server_identity.address,
+            &"userA".to_string(),
+            "spam",
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .unwrap();
+        // signed by another user than `from`
+        let body = json!({
+            "from": "reporter",
+            "reason": "spam",
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse("http://example.com/report/userA").unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/report/:user").post(route);
+        let response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 400);
+        assert!(state.reports.report(1).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,235 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::{UserAddress, idt::balance},
+    notifications::Notification,
+    reports::{ReportAction, ReportId, resolve},
+    routes::{
+        Role, SignedRequest, State, balance_changed, check_role,
+        error::{ApiError, ErrorCode},
+        freshness_error,
+        reports::error_response,
+        signed_body,
+    },
+    verify::{
+        report::{resolve_report_message_prefix, resolve_report_verify},
+        signature::Freshness,
+    },
+};
+
+#[derive(Deserialize)]
+struct ResolveRequest {
+    from: UserAddress,
+    #[serde(flatten)]
+    action: ReportAction,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for ResolveRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// closes the report, punishing the reported user if requested. Signed by a moderator.
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let Ok(id) = req.param("id")?.parse::<ReportId>() else {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidId).into());
+    };
+    let body: ResolveRequest = signed_body(&mut req).await?;
+    let state = req.state();
+    let moderator = body.from;
+    let prefix = resolve_report_message_prefix(id, &body.action);
+    if let Err(response) = check_role(state, &moderator, Role::Moderator, &prefix).await {
+        return Ok(response);
+    }
+
+    if let Some(response) = freshness_error(state, &body.freshness) {
+        return Ok(response);
+    }
+
+    if resolve_report_verify(
+        body.signature,
+        &moderator,
+        &body.freshness,
+        id,
+        &body.action,
+        &*state.nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    let user = match state.reports.report(id).await? {
+        Some(report) => report.user,
+        None => return error_response(crate::reports::error::Error::UnknownReport(id)),
+    };
+    let balance_before = balance(&state.identity_service, &user).await?;
+    let report = match resolve(
+        &state.identity_service,
+        &*state.reports,
+        id,
+        moderator.clone(),
+        body.action.clone(),
+    )
+    .await
+    {
+        Ok(report) => report,
+        Err(e) => return error_response(e),
+    };
+
+    let user_balance = balance(&state.identity_service, &user).await?;
+    if let ReportAction::Punish { amount, proof_id } = body.action {
+        state
+            .notifications
+            .notify(
+                &user,
+                Notification::Punished {
+                    moderator,
+                    amount,
+                    proof_id,
+                },
+            )
+            .await;
+        balance_changed(state, &user, balance_before, user_balance).await;
+    }
+    let response = Response::builder(200)
+        .body(json!({
+            "report": report,
+            "idt": user_balance.to_string(),
+            "nonce": body.freshness.nonce,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use super::*;
+    use crate::{
+        admins::InMemoryAdminStorage,
+        identity::{
+            IdtAmount,
+            proof::prove,
+            tests::{PROOF_ID, USER_A},
+        },
+        verify::{expires_in, random_keypair, report::resolve_report_sign},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn resolve_report(
+        state: &State,
+        private_key: &str,
+        moderator: &str,
+        id: ReportId,
+        action: serde_json::Value,
+    ) -> Response {
+        let parsed: ReportAction = serde_json::from_value(action.clone()).unwrap();
+        let signature = resolve_report_sign(
+            private_key,
+            &state.server_identity.address,
+            id,
+            &parsed,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .unwrap();
+        let mut body = json!({
+            "from": moderator,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        for (key, value) in action.as_object().unwrap() {
+            body[key] = value.clone();
+        }
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/reports/{id}/resolve")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/reports/:id/resolve").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    fn moderator_state(moderator: &UserAddress) -> State {
+        State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::new(),
+                HashSet::from([moderator.clone()]),
+            )),
+            ..Default::default()
+        }
+    }
+
+    #[async_std::test]
+    async fn test_punish() {
+        let (private_key, moderator) = random_keypair();
+        let state = moderator_state(&moderator);
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            moderator.clone(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        state
+            .reports
+            .add_report("reporter".into(), USER_A.into(), "spam".into(), 10)
+            .await
+            .unwrap();
+
+        let action = json!({"action": "punish", "amount": 400, "proof_id": 9});
+        let mut response =
+            resolve_report(&state, &private_key, &moderator, 1, action.clone()).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["idt"], "600");
+        assert_eq!(body["report"]["resolution"]["action"], "punish");
+        assert_eq!(body["report"]["resolution"]["moderator"], moderator);
+        assert!(state.reports.open_reports(0, 10).await.unwrap().is_empty());
+
+        let response = resolve_report(&state, &private_key, &moderator, 1, action.clone()).await;
+        assert_eq!(response.status(), 409);
+        let response = resolve_report(&state, &private_key, &moderator, 2, action).await;
+        assert_eq!(response.status(), 404);
+    }
+
+    #[async_std::test]
+    async fn test_dismiss() {
+        let (private_key, moderator) = random_keypair();
+        let state = moderator_state(&moderator);
+        state
+            .reports
+            .add_report("reporter".into(), USER_A.into(), "spam".into(), 10)
+            .await
+            .unwrap();
+
+        // only moderators resolve reports
+        let (other_key, other) = random_keypair();
+        let dismiss = json!({"action": "dismiss"});
+        let response = resolve_report(&state, &other_key, &other, 1, dismiss.clone()).await;
+        assert_eq!(response.status(), 403);
+
+        let mut response = resolve_report(&state, &private_key, &moderator, 1, dismiss).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["report"]["resolution"]["action"], "dismiss");
+    }
+}
@@ -0,0 +1,231 @@
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    federation::{best_scaled_balance, query_servers},
+    identity::{IdentityService, UserAddress, error::Error, idt::balance, vouch::vouchers},
+    routes::{State, claim_home::home_claim_json},
+};
+
+async fn known_locally(service: &IdentityService, user: &UserAddress) -> Result<bool, Error> {
+    Ok(service.proof(user).await?.is_some()
+        || service.genesis_balance(user).await?.is_some()
+        || !vouchers(service, user).await?.is_empty()
+        || !service.vouchers_external(user).await?.is_empty())
+}
+
+async fn resolve_remote(state: &State, user: &UserAddress) -> tide::Result<serde_json::Value> {
+    let servers = state.server_storage.servers().await?;
+    let sources = query_servers(
+        &*state.federation_client,
+        servers,
+        user,
+        state.identity_service.now(),
+        &state.config.federation,
+    )
+    .await;
+    let idt = best_scaled_balance(&sources);
+    let sources: Vec<serde_json::Value> = sources
+        .into_iter()
+        .map(|source| match &source.result {
+            Ok(remote) => json!({
+                "server": source.server,
+                "url": source.info.url,
+                "scale": source.info.scale.to_string(),
+                "attestation_weight": source.weight.to_string(),
+                "schedule_weight": source.schedule_weight.to_string(),
+                "idt": remote.idt.to_string(),
+                "scaled_idt": source.scaled_balance().unwrap_or_default().to_string(),
+                "vouchers": remote.vouchers,
+            }),
+            Err(e) => json!({
+                "server": source.server,
+                "url": source.info.url,
+                "error": e.to_string(),
+            }),
+        })
+        .collect();
+    Ok(json!({
+        "user": user,
+        "source": "federation",
+        "idt": idt.to_string(),
+        "sources": sources,
+    }))
+}
+
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let state = req.state();
+    let service = &state.identity_service;
+
+    let home = home_claim_json(state, &user).await?;
+    let body = if home.is_some() || known_locally(service, &user).await? {
+        json!({
+            "user": user,
+            "source": "local",
+            "idt": balance(service, &user).await?.to_string(),
+            "home": home,
+        })
+    } else {
+        let now = service.now();
+        match state.resolve_cache.get(&user, now).await {
+            Some(cached) => cached,
+            None => {
+                let resolved = resolve_remote(state, &user).await?;
+                state
+                    .resolve_cache
+                    .insert(user.clone(), resolved.clone(), now)
+                    .await;
+                resolved
+            }
+        }
+    };
+    let response = Response::builder(200)
+        .body(body)
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        federation::{InMemoryFederationClient, RemoteUser, storage::HomeClaim},
+        identity::{
+            IdtAmount,
+            proof::prove,
+            tests::{MODERATOR, PROOF_ID, USER_A},
+        },
+        numbers::Rational,
+        servers::storage::ServerInfo,
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn resolve(state: &State, user: &str) -> Value {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/resolve/{user}")).unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/resolve/:user").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        response.body_json().await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_local_user() {
+        let state = State::default();
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        let body = resolve(&state, USER_A).await;
+        assert_eq!(body["source"], "local");
+        assert_eq!(body["idt"], "100");
+    }
+
+    #[async_std::test]
+    async fn test_remote_user() {
+        let client = Arc::new(InMemoryFederationClient::default());
+        let state = State {
+            federation_client: client.clone(),
+            ..Default::default()
+        };
+        for (address, url, scale) in [
+            ("server1", "http://server1", Rational::new(1, 2).unwrap()),
+            ("server2", "http://server2", Rational::default()),
+            ("server3", "http://server3", Rational::default()),
+        ] {
+            state
+                .server_storage
+                .add_server(
+                    address.to_string(),
+                    ServerInfo {
+                        url: url.to_string(),
+                        scale,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap();
+        }
+        client
+            .set_user(
+                "http://server1",
+                USER_A.to_string(),
+                RemoteUser {
+                    idt: IdtAmount::new(300),
+                    vouchers: vec!["voucher".to_string()],
+                },
+            )
+            .await;
+        client
+            .set_user(
+                "http://server2",
+                USER_A.to_string(),
+                RemoteUser {
+                    idt: IdtAmount::new(100),
+                    vouchers: vec![],
+                },
+            )
+            .await;
+
+        let body = resolve(&state, USER_A).await;
+        assert_eq!(body["source"], "federation");
+        assert_eq!(body["idt"], "150");
+        let sources = body["sources"].as_array().unwrap();
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources[0]["scaled_idt"], "150");
+        assert_eq!(sources[0]["vouchers"], json!(["voucher"]));
+        assert_eq!(sources[1]["scaled_idt"], "100");
+        assert!(sources[2]["error"].is_string());
+
+        // cached response is returned until the entry expires
+        client
+            .set_user(
+                "http://server2",
+                USER_A.to_string(),
+                RemoteUser {
+                    idt: IdtAmount::new(1000),
+                    vouchers: vec![],
+                },
+            )
+            .await;
+        let body = resolve(&state, USER_A).await;
+        assert_eq!(body["idt"], "150");
+    }
+
+    #[async_std::test]
+    async fn test_home_claim() {
+        let state = State::default();
+        let body = resolve(&state, USER_A).await;
+        assert_eq!(body["source"], "federation");
+
+        // users who claimed this server are resolved locally
+        let claim = HomeClaim {
+            signature: "0x01".to_string(),
+            claimed_at: 5,
+            ..Default::default()
+        };
+        state
+            .home_storage
+            .set_claim(USER_A.to_string(), claim)
+            .await
+            .unwrap();
+        let body = resolve(&state, USER_A).await;
+        assert_eq!(body["source"], "local");
+        assert_eq!(body["idt"], "0");
+        assert_eq!(body["home"]["server"], state.server_identity.address);
+        assert_eq!(body["home"]["claimed_at"], 5);
+    }
+}
@@ -0,0 +1,515 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::{IdtAmount, UserAddress, idt::balance},
+    restitution::{
+        appeal, contribute, decide_appeal, error::Error, grant, remaining_penalty, withdraw,
+    },
+    routes::{
+        SignedRequest, State, balance_changed,
+        error::{ApiError, ErrorCode},
+        freshness_error, signed_body, verify_admin_action,
+    },
+    verify::{
+        admins::{admin_decide_appeal_message_prefix, admin_grant_restitution_message_prefix},
+        restitution::{appeal_verify, restitution_verify},
+        signature::Freshness,
+    },
+};
+
+#[derive(Deserialize)]
+struct RestitutionRequest {
+    from: UserAddress,
+    amount: IdtAmount,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for RestitutionRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+#[derive(Deserialize)]
+struct AppealRequest {
+    from: UserAddress,
+    reason: String,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for AppealRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+#[derive(Deserialize)]
+struct DecideRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for DecideRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+fn disabled() -> Response {
+    ApiError::new(404, ErrorCode::RestitutionDisabled).into()
+}
+
+// client errors are reported, storage errors fail the request
+fn error_response(e: Error) -> tide::Result {
+    match e {
+        Error::ZeroAmount => Ok(ApiError::bad_request(ErrorCode::AmountNotPositive).into()),
+        Error::InsufficientBalance => {
+            Ok(ApiError::bad_request(ErrorCode::ContributionExceedsBalance).into())
+        }
+        Error::InsufficientContribution => {
+            Ok(ApiError::bad_request(ErrorCode::WithdrawalExceedsContribution).into())
+        }
+        Error::NoPenalty => Ok(ApiError::new(409, ErrorCode::NoRemainingPenalty).into()),
+        Error::EmptyPool => Ok(ApiError::new(409, ErrorCode::RestitutionPoolEmpty).into()),
+        Error::InvalidReason => Ok(ApiError::bad_request(ErrorCode::InvalidReason).into()),
+        Error::AppealOpen => Ok(ApiError::new(409, ErrorCode::AppealOpen).into()),
+        Error::NoAppeal => Ok(ApiError::new(404, ErrorCode::AppealNotFound).into()),
+        Error::AppealDecided => Ok(ApiError::new(409, ErrorCode::AppealAlreadyDecided).into()),
+        Error::NoApprovedAppeal => Ok(ApiError::new(409, ErrorCode::AppealNotApproved).into()),
+        e => Err(e.into()),
+    }
+}
+
+// balance of the pool
+pub async fn route(req: Request<State>) -> tide::Result {
+    let state = req.state();
+    if !state.config.restitution.enabled {
+        return Ok(disabled());
+    }
+    let pool = state.restitution.pool().await?;
+    Ok(Response::builder(200)
+        .body(json!({
+            "contributed": pool.contributed,
+            "drawn": pool.drawn,
+            "balance": pool.balance(),
+        }))
+        .content_type(mime::JSON)
+        .build())
+}
+
+// contribution of the user, the restitution granted to them, their remaining penalty and the
+// status of their latest appeal
+pub async fn user_route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let state = req.state();
+    if !state.config.restitution.enabled {
+        return Ok(disabled());
+    }
+    let remaining = match remaining_penalty(&state.identity_service, &user).await {
+        Ok(remaining) => remaining,
+        Err(e) => return error_response(e),
+    };
+    let appeal = state.restitution.appeal(&user).await?;
+    Ok(Response::builder(200)
+        .body(json!({
+            "user": user,
+            "contribution": state.restitution.contribution(&user).await?,
+            "given": state.restitution.given(&user).await?,
+            "restitution": state.restitution.restitution(&user).await?,
+            "remaining_penalty": remaining,
+            "appeal": appeal.map(|a| a.status),
+        }))
+        .content_type(mime::JSON)
+        .build())
+}
+
+pub async fn contribute_route(req: Request<State>) -> tide::Result {
+    pledge_route(req, false).await
+}
+
+pub async fn withdraw_route(req: Request<State>) -> tide::Result {
+    pledge_route(req, true).await
+}
+
+// adds to or takes back the contribution of the sender, signed by the sender
+async fn pledge_route(mut req: Request<State>, withdrawal: bool) -> tide::Result {
+    let body: RestitutionRequest = signed_body(&mut req).await?;
+    let state = req.state();
+    if !state.config.restitution.enabled {
+        return Ok(disabled());
+    }
+    if let Some(response) = freshness_error(state, &body.freshness) {
+        return Ok(response);
+    }
+    if restitution_verify(
+        body.signature,
+        &body.from,
+        &body.freshness,
+        body.amount,
+        withdrawal,
+        &*state.nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    let storage = &*state.restitution;
+    let service = &state.identity_service;
+    let balance_before = balance(service, &body.from).await?;
+    let result = match withdrawal {
+        true => withdraw(storage, &body.from, body.amount).await,
+        false => contribute(
+            &state.identity_service,
+            storage,
+            &state.pledge_locks,
+            body.from.clone(),
+            body.amount,
+        )
+        .await
+        .map(|_| ()),
+    };
+    if let Err(e) = result {
+        return error_response(e);
+    }
+    // pledges are debited from the balance of the sender
+    let user_balance = balance(service, &body.from).await?;
+    balance_changed(state, &body.from, balance_before, user_balance).await;
+    Ok(Response::builder(200)
+        .body(json!({
+            "user": body.from,
+            "contribution": storage.contribution(&body.from).await?,
+            "idt": user_balance,
+            "pool": storage.pool().await?.balance(),
+        }))
+        .content_type(mime::JSON)
+        .build())
+}
+
+// appeal of the sender against its remaining moderator penalty, signed by the sender
+pub async fn appeal_route(mut req: Request<State>) -> tide::Result {
+    let body: AppealRequest = signed_body(&mut req).await?;
+    let state = req.state();
+    if !state.config.restitution.enabled {
+        return Ok(disabled());
+    }
+    if let Some(response) = freshness_error(state, &body.freshness) {
+        return Ok(response);
+    }
+    if appeal_verify(
+        body.signature,
+        &body.from,
+        &body.freshness,
+        &body.reason,
+        &*state.nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    match appeal(
+        &state.identity_service,
+        &*state.restitution,
+        body.from,
+        body.reason,
+    )
+    .await
+    {
+        Ok(appeal) => Ok(Response::builder(200)
+            .body(json!({ "appeal": appeal }))
+            .content_type(mime::JSON)
+            .build()),
+        Err(e) => error_response(e),
+    }
+}
+
+pub async fn approve_appeal_route(req: Request<State>) -> tide::Result {
+    decide_appeal_route(req, true).await
+}
+
+pub async fn reject_appeal_route(req: Request<State>) -> tide::Result {
+    decide_appeal_route(req, false).await
+}
+
+// approves or rejects the pending appeal of the user, signed by an admin
+async fn decide_appeal_route(mut req: Request<State>, approved: bool) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let body: DecideRequest = signed_body(&mut req).await?;
+    let state = req.state();
+    if !state.config.restitution.enabled {
+        return Ok(disabled());
+    }
+    let admin = body.from;
+    let prefix = admin_decide_appeal_message_prefix(user.clone(), approved);
+    if let Err(response) =
+        verify_admin_action(state, &admin, body.signature, &body.freshness, &prefix).await
+    {
+        return Ok(response);
+    }
+
+    match decide_appeal(
+        &state.identity_service,
+        &*state.restitution,
+        &user,
+        admin,
+        approved,
+    )
+    .await
+    {
+        Ok(appeal) => Ok(Response::builder(200)
+            .body(json!({ "appeal": appeal }))
+            .content_type(mime::JSON)
+            .build()),
+        Err(e) => error_response(e),
+    }
+}
+
+// grants restitution to a user whose appeal is approved, signed by an admin. At most the
+// remaining penalty of the user is drawn from the pool, the appeal is then granted.
+pub async fn grant_route(mut req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let body: RestitutionRequest = signed_body(&mut req).await?;
+    let state = req.state();
+    if !state.config.restitution.enabled {
+        return Ok(disabled());
+    }
+    let admin = body.from;
+    let prefix = admin_grant_restitution_message_prefix(user.clone(), body.amount);
+    if let Err(response) =
+        verify_admin_action(state, &admin, body.signature, &body.freshness, &prefix).await
+    {
+        return Ok(response);
+    }
+
+    let service = &state.identity_service;
+    let balance_before = balance(service, &user).await?;
+    let drawn = match grant(service, &*state.restitution, &user, body.amount).await {
+        Ok(drawn) => drawn,
+        Err(e) => return error_response(e),
+    };
+    let user_balance = balance(service, &user).await?;
+    balance_changed(state, &user, balance_before, user_balance).await;
+    Ok(Response::builder(200)
+        .body(json!({
+            "user": user,
+            "drawn": drawn,
+            "remaining_penalty": remaining_penalty(service, &user).await?,
+            "idt": user_balance,
+            "pool": state.restitution.pool().await?.balance(),
+        }))
+        .content_type(mime::JSON)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use super::*;
+    use crate::{
+        admins::InMemoryAdminStorage,
+        config::{Config, RestitutionSection},
+        identity::{
+            proof::prove,
+            punish::punish,
+            tests::{MODERATOR, PROOF_ID},
+        },
+        restitution::PledgeDebits,
+        verify::{
+            expires_in, random_keypair,
+            restitution::{appeal_sign, restitution_sign},
+            sign_message,
+            signature::Signature,
+        },
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response as HttpResponse, Url};
+
+    async fn post(state: &State, url: &str, signature: Signature, fields: Value) -> (u16, Value) {
+        let mut body = json!({
+            "from": signature.signer,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        if let (Some(body), Value::Object(fields)) = (body.as_object_mut(), fields) {
+            body.extend(fields);
+        }
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com{url}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/restitution/contribute").post(contribute_route);
+        server.at("/restitution/withdraw").post(withdraw_route);
+        server.at("/restitution/appeal").post(appeal_route);
+        server
+            .at("/restitution/:user/appeal/approve")
+            .post(approve_appeal_route);
+        server
+            .at("/restitution/:user/appeal/reject")
+            .post(reject_appeal_route);
+        server.at("/restitution/:user/grant").post(grant_route);
+        let mut response: HttpResponse = server.respond(req).await.unwrap();
+        let status = response.status().into();
+        (status, response.body_json().await.unwrap())
+    }
+
+    async fn pledge(state: &State, private_key: &str, amount: u64, withdraw: bool) -> (u16, Value) {
+        let signature = restitution_sign(
+            private_key,
+            &state.server_identity.address,
+            IdtAmount::new(amount),
+            withdraw,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .unwrap();
+        let url = match withdraw {
+            true => "/restitution/withdraw",
+            false => "/restitution/contribute",
+        };
+        post(state, url, signature, json!({ "amount": amount })).await
+    }
+
+    async fn admin_post(
+        state: &State,
+        admin_key: &str,
+        prefix: &str,
+        url: &str,
+        amount: u64,
+    ) -> (u16, Value) {
+        let signature = sign_message(
+            admin_key,
+            &state.server_identity.address,
+            prefix,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .unwrap();
+        post(state, url, signature, json!({ "amount": amount })).await
+    }
+
+    async fn grant_to(state: &State, admin_key: &str, user: &str, amount: u64) -> (u16, Value) {
+        let prefix =
+            admin_grant_restitution_message_prefix(user.to_string(), IdtAmount::new(amount));
+        let url = format!("/restitution/{user}/grant");
+        admin_post(state, admin_key, &prefix, &url, amount).await
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (admin_key, admin) = random_keypair();
+        let (private_key, contributor) = random_keypair();
+        let (user_key, user) = random_keypair();
+        let mut state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::from([admin.clone()]),
+                HashSet::new(),
+            )),
+            ..Default::default()
+        };
+        let (status, _) = pledge(&state, &private_key, 10, false).await;
+        assert_eq!(status, 404);
+
+        state.config = Arc::new(Config {
+            restitution: RestitutionSection { enabled: true },
+            ..Default::default()
+        });
+        state.identity_service.debits = Arc::new(PledgeDebits(state.restitution.clone()));
+        let service = &state.identity_service;
+        prove(
+            service,
+            contributor.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        punish(
+            service,
+            user.clone(),
+            MODERATOR.to_string(),
+            IdtAmount::new(50),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+
+        let (status, body) = pledge(&state, &private_key, 80, false).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["contribution"], 80);
+        assert_eq!(body["idt"], 20);
+        assert_eq!(body["pool"], 80);
+        let (status, _) = pledge(&state, &private_key, 30, false).await;
+        assert_eq!(status, 400);
+
+        // restitution is only granted for an approved appeal
+        let (status, body) = grant_to(&state, &admin_key, &user, 20).await;
+        assert_eq!(status, 409);
+        assert_eq!(body["code"], "appeal_not_approved");
+        let reason = "the penalty was a mistake";
+        let signature = appeal_sign(
+            &user_key,
+            &state.server_identity.address,
+            reason,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .unwrap();
+        let (status, body) = post(
+            &state,
+            "/restitution/appeal",
+            signature,
+            json!({ "reason": reason }),
+        )
+        .await;
+        assert_eq!(status, 200);
+        assert_eq!(body["appeal"]["status"], "pending");
+        let prefix = admin_decide_appeal_message_prefix(user.clone(), true);
+        let url = format!("/restitution/{user}/appeal/approve");
+        let (status, body) = admin_post(&state, &admin_key, &prefix, &url, 0).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["appeal"]["status"], "approved");
+        assert_eq!(body["appeal"]["decided_by"], admin);
+        // the approval is signed for the decision
+        let url = format!("/restitution/{user}/appeal/reject");
+        let (status, _) = admin_post(&state, &admin_key, &prefix, &url, 0).await;
+        assert_eq!(status, 400);
+
+        let (status, body) = grant_to(&state, &admin_key, &user, 20).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["drawn"], 20);
+        assert_eq!(body["remaining_penalty"], 30);
+        assert_eq!(body["pool"], 60);
+        let (status, _) = grant_to(&state, &admin_key, &user, 20).await;
+        assert_eq!(status, 409);
+
+        // drawn contributions cannot be withdrawn and stay debited
+        let (status, _) = pledge(&state, &private_key, 80, true).await;
+        assert_eq!(status, 400);
+        let (status, body) = pledge(&state, &private_key, 60, true).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["contribution"], 0);
+        assert_eq!(body["idt"], 80);
+        assert_eq!(body["pool"], 0);
+    }
+}
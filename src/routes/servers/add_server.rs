@@ -7,23 +7,42 @@ use tide::{Request, Response, http::mime};
 use crate::{
     identity::UserAddress,
     numbers::Rational,
-    routes::{State, verify_admin_action},
-    servers::storage::ServerInfo,
-    verify::{admins::admin_set_server_message_prefix, nonce::Nonce},
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        freshness_error, signed_body, verify_admin_action,
+    },
+    servers::storage::{ScaleSchedule, ServerInfo},
+    verify::{
+        admins::admin_set_server_message_prefix,
+        handshake::handshake_verify,
+        signature::{Freshness, Signature},
+    },
 };
 
 #[derive(Deserialize)]
 struct ServerRequest {
     from: UserAddress,
     signature: String,
-    nonce: Nonce,
+    #[serde(flatten)]
+    freshness: Freshness,
     address: UserAddress,
     url: String,
     scale: Rational,
+    #[serde(default)]
+    schedule: ScaleSchedule,
+    // response of `/handshake/<this server>` of the added server
+    handshake: Signature,
+}
+
+impl SignedRequest for ServerRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
 }
 
 pub async fn route(mut req: Request<State>) -> tide::Result {
-    let body: ServerRequest = req.body_json().await?;
+    let body: ServerRequest = signed_body(&mut req).await?;
     let sender = body.from.clone();
     let message_prefix = admin_set_server_message_prefix(body.address.clone());
 
@@ -31,7 +50,7 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
         req.state(),
         &sender,
         body.signature,
-        body.nonce,
+        &body.freshness,
         &message_prefix,
     )
     .await
@@ -39,9 +58,28 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
         return Ok(response);
     }
 
+    let handshake = &body.handshake;
+    if handshake.signer != body.address
+        || freshness_error(req.state(), &handshake.freshness).is_some()
+        || handshake_verify(
+            handshake.signature.clone(),
+            &body.address,
+            &handshake.freshness,
+            &*req.state().nonce_manager,
+        )
+        .await
+        .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::ServerHandshakeFailed).into());
+    }
+
+    let now = req.state().identity_service.now();
     let info = ServerInfo {
         url: body.url.clone(),
         scale: body.scale.clone(),
+        last_attested: now,
+        schedule: body.schedule.clone(),
+        registered_at: now,
     };
     if req
         .state()
@@ -50,18 +88,16 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
         .await
         .is_err()
     {
-        return Ok(Response::builder(400)
-            .body(json!({"error": "failed to add server"}))
-            .content_type(mime::JSON)
-            .build());
+        return Ok(ApiError::bad_request(ErrorCode::FailedToAddServer).into());
     }
 
     let response: HashMap<String, serde_json::Value> = HashMap::from([
         ("server".into(), body.address.into()),
         ("from".into(), sender.into()),
-        ("nonce".into(), body.nonce.into()),
+        ("nonce".into(), body.freshness.nonce.into()),
         ("url".into(), body.url.into()),
         ("scale".into(), serde_json::to_value(body.scale)?),
+        ("schedule".into(), serde_json::to_value(body.schedule)?),
     ]);
 
     let response = Response::builder(200)
@@ -79,44 +115,81 @@ mod tests {
     use super::*;
     use crate::{
         admins::InMemoryAdminStorage,
+        federation::mock::MockServer,
         numbers::Rational,
-        verify::{random_keypair, sign_message},
+        verify::{
+            expires_in, handshake::handshake_sign, nonce::InMemoryNonceManager, random_keypair,
+            sign_message,
+        },
     };
     use serde_json::Value;
     use tide::http::{Request as HttpRequest, Response, Url};
 
-    #[async_std::test]
-    async fn test_basic() {
-        let (admin_priv, admin_addr) = random_keypair();
-        let admins = HashSet::from([admin_addr.clone()]);
-        let admin_storage = Arc::new(InMemoryAdminStorage::new(admins, HashSet::new()));
-        let state = State {
-            admin_storage: admin_storage.clone(),
-            ..Default::default()
-        };
+    const SERVER_URL: &str = "http://example.com";
 
-        let req_url = "/add_server";
-        let message_prefix = admin_set_server_message_prefix("server1".to_string());
-        let signature = sign_message(&admin_priv, &message_prefix, &*state.nonce_manager)
-            .await
-            .expect("Should sign");
-        let server_url = "http://example.com".to_string();
+    async fn add_server_request(
+        state: &State,
+        private_key: &str,
+        address: &UserAddress,
+        handshake: Signature,
+    ) -> (HttpRequest, Signature) {
+        let message_prefix = admin_set_server_message_prefix(address.clone());
+        let signature = sign_message(
+            private_key,
+            &state.server_identity.address,
+            &message_prefix,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
         let body = json!({
             "from": signature.signer,
             "signature": signature.signature,
-            "nonce": signature.nonce,
-            "address": "server1",
-            "url": server_url.clone(),
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+            "address": address,
+            "url": SERVER_URL,
             "scale": Rational::default(),
+            "handshake": handshake,
         });
 
         let mut req = HttpRequest::new(
             tide::http::Method::Post,
-            Url::parse(&format!("http://example.com{}", req_url)).unwrap(),
+            Url::parse("http://example.com/add_server").unwrap(),
         );
         req.set_body(serde_json::to_string(&body).unwrap());
         req.set_content_type(mime::JSON);
+        (req, signature)
+    }
 
+    // handshake of the server with the `private_key` for the domain of the state
+    async fn handshake(state: &State, private_key: &str) -> Signature {
+        handshake_sign(
+            private_key,
+            &state.server_identity.address,
+            expires_in(60),
+            &InMemoryNonceManager::default(),
+        )
+        .await
+        .expect("Should sign")
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (admin_priv, admin_addr) = random_keypair();
+        let (server_priv, server_addr) = random_keypair();
+        let admins = HashSet::from([admin_addr.clone()]);
+        let admin_storage = Arc::new(InMemoryAdminStorage::new(admins, HashSet::new()));
+        let state = State {
+            admin_storage: admin_storage.clone(),
+            ..Default::default()
+        };
+
+        let handshake = handshake(&state, &server_priv).await;
+        let (req, signature) =
+            add_server_request(&state, &admin_priv, &server_addr, handshake).await;
         let mut server = tide::with_state(state.clone());
         server.at("/add_server").post(route);
 
@@ -124,10 +197,10 @@ mod tests {
 
         assert_eq!(response.status(), 200);
         let body: Value = response.body_json().await.unwrap();
-        assert_eq!(body["server"], "server1");
+        assert_eq!(body["server"], server_addr);
         assert_eq!(body["from"], admin_addr);
-        assert_eq!(body["nonce"], signature.nonce);
-        assert_eq!(body["url"], server_url);
+        assert_eq!(body["nonce"], signature.freshness.nonce);
+        assert_eq!(body["url"], SERVER_URL);
         let scale: Rational =
             serde_json::from_value(body["scale"].clone()).expect("failed to deserialize scale");
         assert_eq!(scale, Rational::default());
@@ -137,14 +210,73 @@ mod tests {
                 .servers()
                 .await
                 .unwrap()
-                .contains_key("server1")
+                .contains_key(&server_addr)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_remote_handshake() {
+        let (admin_priv, admin_addr) = random_keypair();
+        let admins = HashSet::from([admin_addr]);
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(admins, HashSet::new())),
+            ..Default::default()
+        };
+        let remote = MockServer::start().await.unwrap();
+
+        let handshake: Signature = surf::get(format!(
+            "{}/handshake/{}",
+            remote.url, state.server_identity.address
+        ))
+        .recv_json()
+        .await
+        .unwrap();
+        let (req, _) = add_server_request(&state, &admin_priv, &remote.address(), handshake).await;
+        let mut server = tide::with_state(state.clone());
+        server.at("/add_server").post(route);
+
+        let response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(
+            state
+                .server_storage
+                .servers()
+                .await
+                .unwrap()
+                .contains_key(&remote.address())
         );
+        remote.stop().await;
+    }
+
+    #[async_std::test]
+    async fn test_foreign_handshake() {
+        let (admin_priv, admin_addr) = random_keypair();
+        let (_, server_addr) = random_keypair();
+        let (other_priv, _) = random_keypair();
+        let admins = HashSet::from([admin_addr]);
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(admins, HashSet::new())),
+            ..Default::default()
+        };
+
+        // address of the handshake does not match the registered one
+        let handshake = handshake(&state, &other_priv).await;
+        let (req, _) = add_server_request(&state, &admin_priv, &server_addr, handshake).await;
+        let mut server = tide::with_state(state.clone());
+        server.at("/add_server").post(route);
+
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "server handshake verification failed");
+        assert!(state.server_storage.servers().await.unwrap().is_empty());
     }
 
     #[async_std::test]
     async fn test_no_privilege() {
         // create a random keypair for a non-privileged user
         let (private_key, _) = random_keypair();
+        let (server_priv, server_addr) = random_keypair();
         let admins = HashSet::from(["other_admin".to_string()]);
         let admin_storage = Arc::new(InMemoryAdminStorage::new(admins, HashSet::new()));
         let state = State {
@@ -152,27 +284,8 @@ mod tests {
             ..Default::default()
         };
 
-        let req_url = "/add_server";
-        let message_prefix = admin_set_server_message_prefix("server1".to_string());
-        let signature = sign_message(&private_key, &message_prefix, &*state.nonce_manager)
-            .await
-            .expect("Should sign");
-        let body = json!({
-            "from": signature.signer,
-            "signature": signature.signature,
-            "nonce": signature.nonce,
-            "address": "server1",
-            "url": "http://example.com",
-            "scale": Rational::default(),
-        });
-
-        let mut req = HttpRequest::new(
-            tide::http::Method::Post,
-            Url::parse(&format!("http://example.com{}", req_url)).unwrap(),
-        );
-        req.set_body(serde_json::to_string(&body).unwrap());
-        req.set_content_type(mime::JSON);
-
+        let handshake = handshake(&state, &server_priv).await;
+        let (req, _) = add_server_request(&state, &private_key, &server_addr, handshake).await;
         let mut server = tide::with_state(state);
         server.at("/add_server").post(route);
 
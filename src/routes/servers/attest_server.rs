@@ -0,0 +1,148 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::UserAddress,
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        signed_body, verify_admin_action,
+    },
+    verify::{admins::admin_attest_server_message_prefix, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct AttestRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+    address: UserAddress,
+}
+
+impl SignedRequest for AttestRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// restores the full trust of a registered server, see `ServerInfo::attestation_weight`
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let body: AttestRequest = signed_body(&mut req).await?;
+    let sender = body.from.clone();
+    let message_prefix = admin_attest_server_message_prefix(body.address.clone());
+    let state = req.state();
+
+    if let Err(response) = verify_admin_action(
+        state,
+        &sender,
+        body.signature,
+        &body.freshness,
+        &message_prefix,
+    )
+    .await
+    {
+        return Ok(response);
+    }
+
+    let Some(mut info) = state.server_storage.servers().await?.remove(&body.address) else {
+        return Ok(ApiError::new(404, ErrorCode::ServerNotRegistered).into());
+    };
+    info.last_attested = state.identity_service.now();
+    state
+        .server_storage
+        .add_server(body.address.clone(), info.clone())
+        .await?;
+
+    let response = Response::builder(200)
+        .body(json!({
+            "server": body.address,
+            "from": sender,
+            "nonce": body.freshness.nonce,
+            "last_attested": info.last_attested,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        admins::InMemoryAdminStorage,
+        numbers::Rational,
+        servers::storage::ServerInfo,
+        verify::{expires_in, random_keypair, sign_message},
+    };
+    use serde_json::Value;
+    use std::{collections::HashSet, sync::Arc};
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (admin_priv, admin_addr) = random_keypair();
+        let admins = HashSet::from([admin_addr.clone()]);
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(admins, HashSet::new())),
+            ..Default::default()
+        };
+        state
+            .server_storage
+            .add_server(
+                "server1".to_string(),
+                ServerInfo {
+                    url: "http://e".into(),
+                    scale: Rational::default(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let mut server = tide::with_state(state.clone());
+        server.at("/attest_server").post(route);
+        let before = state.identity_service.now();
+
+        for (address, expected_status) in [("server2", 404), ("server1", 200)] {
+            let signature = sign_message(
+                &admin_priv,
+                &state.server_identity.address,
+                &admin_attest_server_message_prefix(address.to_string()),
+                expires_in(60),
+                &*state.nonce_manager,
+            )
+            .await
+            .expect("Should sign");
+            let body = json!({
+                "from": signature.signer,
+                "signature": signature.signature,
+                "nonce": signature.freshness.nonce,
+                "expires_at": signature.freshness.expires_at,
+                "domain": signature.freshness.domain,
+                "address": address,
+            });
+            let mut req = HttpRequest::new(
+                tide::http::Method::Post,
+                Url::parse("http://example.com/attest_server").unwrap(),
+            );
+            req.set_body(serde_json::to_string(&body).unwrap());
+            req.set_content_type(mime::JSON);
+            let mut response: Response = server.respond(req).await.unwrap();
+            assert_eq!(response.status(), expected_status);
+            if expected_status == 200 {
+                let body: Value = response.body_json().await.unwrap();
+                assert!(body["last_attested"].as_u64().unwrap() >= before);
+            }
+        }
+        assert!(state.server_storage.servers().await.unwrap()["server1"].last_attested >= before);
+        assert!(
+            !state
+                .server_storage
+                .servers()
+                .await
+                .unwrap()
+                .contains_key("server2")
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::{IdtAmount, UserAddress},
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        freshness_error,
+        servers::handshake::HANDSHAKE_VALIDITY,
+        signed_body,
+    },
+    verify::{
+        balance_update::{balance_update_sign, balance_update_verify},
+        nonce::OutboundNonces,
+        signature::{Freshness, Signature},
+    },
+};
+
+#[derive(Serialize, Deserialize)]
+struct BalanceUpdateRequest {
+    user: UserAddress,
+    idt: String,
+    #[serde(flatten)]
+    signature: Signature,
+}
+
+impl SignedRequest for BalanceUpdateRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.signature.freshness
+    }
+}
+
+// pushes the new balance of a local user to the registered servers whose users vouched for
+// them, if it changed by at least `federation.balance_update_threshold`. Best effort,
+// failures are only logged.
+pub async fn push_balance_update(
+    state: &State,
+    user: &UserAddress,
+    before: IdtAmount,
+    after: IdtAmount,
+) {
+    let threshold = state.config.federation.balance_update_threshold;
+    if threshold.is_zero() || before.abs_diff(after) < threshold {
+        return;
+    }
+    if let Err(e) = push(state, user, after).await {
+        log::warn!("Failed to push the balance of {}: {}", user, e);
+    }
+}
+
+async fn push(state: &State, user: &UserAddress, idt: IdtAmount) -> tide::Result<()> {
+    let vouchers = state
+        .identity_service
+        .external_vouches
+        .vouchers_with_time(user)
+        .await?;
+    if vouchers.is_empty() {
+        return Ok(());
+    }
+    let mut servers: Vec<_> = state
+        .server_storage
+        .servers()
+        .await?
+        .into_iter()
+        .filter(|(server, _)| vouchers.contains_key(server))
+        .collect();
+    servers.sort_by(|a, b| a.0.cmp(&b.0));
+    let validity = HANDSHAKE_VALIDITY.min(state.config.signatures.max_age);
+    for (server, info) in servers {
+        let signature = balance_update_sign(
+            &state.server_identity.private_key,
+            &server,
+            user,
+            idt,
+            state.identity_service.now().saturating_add(validity),
+            &OutboundNonces(&*state.nonce_manager),
+        )
+        .await?;
+        let body = serde_json::to_string(&BalanceUpdateRequest {
+            user: user.clone(),
+            idt: idt.to_string(),
+            signature,
+        })?;
+        if let Err(e) = state
+            .federation_client
+            .balance_update(&info.url, body)
+            .await
+        {
+            log::warn!(
+                "Failed to push the balance of {} to {}: {}",
+                user,
+                server,
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+// balance of a user of a registered server pushed by that server. The cached resolution of
+// the user is dropped, so the next `GET /resolve/:user` queries the servers again.
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let body: BalanceUpdateRequest = signed_body(&mut req).await?;
+    let state = req.state();
+    let Ok(idt) = body.idt.parse::<IdtAmount>() else {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidIdt).into());
+    };
+    let signer = &body.signature.signer;
+    if !state.server_storage.servers().await?.contains_key(signer) {
+        return Ok(ApiError::bad_request(ErrorCode::UnknownServer).into());
+    }
+    let freshness = &body.signature.freshness;
+    if let Some(response) = freshness_error(state, freshness) {
+        return Ok(response);
+    }
+    if balance_update_verify(
+        body.signature.signature.clone(),
+        signer,
+        freshness,
+        &body.user,
+        idt,
+        &*state.nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    let refreshed = state.resolve_cache.remove(&body.user).await.is_some();
+    log::info!("Server {} pushed the balance of {}", signer, body.user);
+    Ok(Response::builder(200)
+        .body(json!({
+            "server": signer,
+            "user": body.user,
+            "idt": idt.to_string(),
+            "refreshed": refreshed,
+        }))
+        .content_type(mime::JSON)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        config::{Config, FederationSection},
+        federation::{BALANCE_UPDATE_PATH, InMemoryFederationClient},
+        servers::storage::ServerInfo,
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response as HttpResponse, Url};
+
+    async fn register(state: &State, server: &UserAddress, url: &str) {
+        state
+            .server_storage
+            .add_server(
+                server.clone(),
+                ServerInfo {
+                    url: url.to_string(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    async fn submit(state: &State, body: &str) -> HttpResponse {
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com{BALANCE_UPDATE_PATH}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at(BALANCE_UPDATE_PATH).post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let client = Arc::new(InMemoryFederationClient::default());
+        let local = State {
+            federation_client: client.clone(),
+            config: Arc::new(Config {
+                federation: FederationSection {
+                    balance_update_threshold: IdtAmount::new(10),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let remote = State::default();
+        let local_address = local.server_identity.address.clone();
+        let remote_address = remote.server_identity.address.clone();
+        let user = "user".to_string();
+        register(&local, &remote_address, "http://remote.com").await;
+        register(&local, &"other".to_string(), "http://other.com").await;
+        register(&remote, &local_address, "http://local.com").await;
+
+        // nothing is pushed without external vouches for the user
+        push_balance_update(&local, &user, IdtAmount::new(100), IdtAmount::new(50)).await;
+        assert!(client.forwarded().await.is_empty());
+        local
+            .identity_service
+            .vouch_external_with_timestamp(
+                remote_address.clone(),
+                "voucher".into(),
+                user.clone(),
+                1,
+            )
+            .await
+            .unwrap();
+        // small changes are not pushed
+        push_balance_update(&local, &user, IdtAmount::new(100), IdtAmount::new(91)).await;
+        assert!(client.forwarded().await.is_empty());
+
+        push_balance_update(&local, &user, IdtAmount::new(100), IdtAmount::new(90)).await;
+        let forwarded = client.forwarded().await;
+        assert_eq!(forwarded.len(), 1);
+        let (url, request) = &forwarded[0];
+        assert_eq!(url, "http://remote.com");
+        assert_eq!(request.path, BALANCE_UPDATE_PATH);
+
+        remote
+            .resolve_cache
+            .insert(
+                user.clone(),
+                json!({ "idt": "100" }),
+                remote.identity_service.now(),
+            )
+            .await;
+        let mut response = submit(&remote, &request.body).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["server"], local_address);
+        assert_eq!(body["idt"], "90");
+        assert_eq!(body["refreshed"], true);
+        assert!(
+            remote
+                .resolve_cache
+                .get(&user, remote.identity_service.now())
+                .await
+                .is_none()
+        );
+        // the nonce is accepted once
+        assert_eq!(submit(&remote, &request.body).await.status(), 400);
+
+        // later pushes to the same server, for any user, carry new nonces
+        push_balance_update(&local, &user, IdtAmount::new(90), IdtAmount::new(50)).await;
+        local
+            .identity_service
+            .vouch_external_with_timestamp(
+                remote_address.clone(),
+                "voucher".into(),
+                "user2".into(),
+                1,
+            )
+            .await
+            .unwrap();
+        push_balance_update(
+            &local,
+            &"user2".to_string(),
+            IdtAmount::new(0),
+            IdtAmount::new(20),
+        )
+        .await;
+        let forwarded = client.forwarded().await;
+        assert_eq!(forwarded.len(), 3);
+        for (_, request) in &forwarded[1..] {
+            assert_eq!(submit(&remote, &request.body).await.status(), 200);
+        }
+
+        // updates are only accepted from registered servers
+        let mut response = submit(&State::default(), &request.body).await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "unknown server");
+    }
+
+    #[async_std::test]
+    async fn test_tampered_balance() {
+        let client = Arc::new(InMemoryFederationClient::default());
+        let local = State {
+            federation_client: client.clone(),
+            config: Arc::new(Config {
+                federation: FederationSection {
+                    balance_update_threshold: IdtAmount::new(1),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let remote = State::default();
+        let remote_address = remote.server_identity.address.clone();
+        register(&local, &remote_address, "http://remote.com").await;
+        register(&remote, &local.server_identity.address, "http://local.com").await;
+        local
+            .identity_service
+            .vouch_external_with_timestamp(remote_address, "voucher".into(), "user".into(), 1)
+            .await
+            .unwrap();
+        push_balance_update(
+            &local,
+            &"user".to_string(),
+            IdtAmount::new(0),
+            IdtAmount::new(10),
+        )
+        .await;
+
+        let (_, request) = client.forwarded().await.remove(0);
+        let mut body: Value = serde_json::from_str(&request.body).unwrap();
+        body["idt"] = "1000".into();
+        let mut response = submit(&remote, &body.to_string()).await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "signature verification failed");
+    }
+}
@@ -0,0 +1,270 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::UserAddress,
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        freshness_error,
+        servers::handshake::HANDSHAKE_VALIDITY,
+        signed_body,
+    },
+    servers::storage::CrossSignature,
+    verify::{
+        cross_sign::{cross_sign, cross_sign_verify},
+        nonce::OutboundNonces,
+        signature::Freshness,
+    },
+};
+
+#[derive(Deserialize)]
+struct CrossSignRequest {
+    signer: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for CrossSignRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// attestation of a registered server by this server. It is submitted to the attested
+// server with `POST /federation/cross_sign`.
+pub async fn route(req: Request<State>) -> tide::Result {
+    let server = req.param("server")?.to_string();
+    let state = req.state();
+    if !state.server_storage.servers().await?.contains_key(&server) {
+        return Ok(ApiError::new(404, ErrorCode::ServerNotRegistered).into());
+    }
+    let validity = HANDSHAKE_VALIDITY.min(state.config.signatures.max_age);
+    let signature = cross_sign(
+        &state.server_identity.private_key,
+        &server,
+        state.identity_service.now().saturating_add(validity),
+        &OutboundNonces(&*state.nonce_manager),
+    )
+    .await?;
+    Ok(Response::builder(200)
+        .body(serde_json::to_value(signature)?)
+        .content_type(mime::JSON)
+        .build())
+}
+
+// stores the attestation of this server by a registered server, so both servers trust
+// each other
+pub async fn submit_route(mut req: Request<State>) -> tide::Result {
+    let body: CrossSignRequest = signed_body(&mut req).await?;
+    let state = req.state();
+    if !state
+        .server_storage
+        .servers()
+        .await?
+        .contains_key(&body.signer)
+    {
+        return Ok(ApiError::bad_request(ErrorCode::UnknownServer).into());
+    }
+    // stored attestations are only verifiable with the domain
+    if body.freshness.domain.is_none() {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureDomainMissing).into());
+    }
+    if let Some(response) = freshness_error(state, &body.freshness) {
+        return Ok(response);
+    }
+    if cross_sign_verify(
+        body.signature.clone(),
+        &body.signer,
+        &body.freshness,
+        &state.server_identity.address,
+        &*state.nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    let signature = CrossSignature {
+        signature: body.signature,
+        nonce: body.freshness.nonce,
+        expires_at: body.freshness.expires_at,
+        version: body.freshness.version,
+        received_at: state.identity_service.now(),
+    };
+    state
+        .server_storage
+        .set_cross_signature(body.signer.clone(), signature.clone())
+        .await?;
+    log::info!("Server {} cross-signed this server", body.signer);
+
+    Ok(Response::builder(200)
+        .body(json!({
+            "server": body.signer,
+            "mutual": true,
+            "received_at": signature.received_at,
+        }))
+        .content_type(mime::JSON)
+        .build())
+}
+
+// registered servers and their attestations of this server. Servers are mutually trusted
+// once the registered server cross-signed this one.
+pub async fn list_route(req: Request<State>) -> tide::Result {
+    let state = req.state();
+    let mut signatures = state.server_storage.cross_signatures().await?;
+    let mut servers: Vec<_> = state.server_storage.servers().await?.into_iter().collect();
+    servers.sort_by(|a, b| a.0.cmp(&b.0));
+    let servers: Vec<_> = servers
+        .into_iter()
+        .map(|(server, info)| {
+            let signature = signatures.remove(&server);
+            json!({
+                "server": server,
+                "url": info.url,
+                "mutual": signature.is_some(),
+                "cross_signature": signature,
+            })
+        })
+        .collect();
+    Ok(Response::builder(200)
+        .body(json!({
+            "domain": state.server_identity.address,
+            "servers": servers,
+        }))
+        .content_type(mime::JSON)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{servers::storage::ServerInfo, verify::signature::Signature};
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn register(state: &State, server: &UserAddress) {
+        state
+            .server_storage
+            .add_server(
+                server.clone(),
+                ServerInfo {
+                    url: format!("http://{server}.com"),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    async fn get(state: &State, path: &str) -> Response {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com{path}")).unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/federation/cross_sign/:server").get(route);
+        server.at("/federation/cross_signatures").get(list_route);
+        server.respond(req).await.unwrap()
+    }
+
+    async fn submit(state: &State, body: Value) -> Response {
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse("http://example.com/federation/cross_sign").unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/federation/cross_sign").post(submit_route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (first, second) = (State::default(), State::default());
+        let first_address = first.server_identity.address.clone();
+        let second_address = second.server_identity.address.clone();
+
+        // only registered servers are attested
+        let response = get(&first, &format!("/federation/cross_sign/{second_address}")).await;
+        assert_eq!(response.status(), 404);
+        register(&first, &second_address).await;
+        register(&second, &first_address).await;
+
+        let mut response = get(&first, &format!("/federation/cross_sign/{second_address}")).await;
+        assert_eq!(response.status(), 200);
+        let attestation: Value = response.body_json().await.unwrap();
+        assert_eq!(attestation["signer"], first_address);
+
+        // attestations are only accepted by the attested server
+        let other = State::default();
+        register(&other, &first_address).await;
+        assert_eq!(submit(&other, attestation.clone()).await.status(), 400);
+
+        let mut response = get(&second, "/federation/cross_signatures").await;
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["servers"][0]["mutual"], false);
+
+        let mut response = submit(&second, attestation.clone()).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["server"], first_address);
+        // the nonce is accepted once
+        assert_eq!(submit(&second, attestation.clone()).await.status(), 400);
+
+        let mut response = get(&second, "/federation/cross_signatures").await;
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["domain"], second_address);
+        let listed = &body["servers"][0];
+        assert_eq!(listed["server"], first_address);
+        assert_eq!(listed["mutual"], true);
+        assert_eq!(
+            listed["cross_signature"]["signature"],
+            attestation["signature"]
+        );
+
+        // the stored attestation can be verified by clients
+        let stored: Signature = serde_json::from_value(json!({
+            "signer": listed["server"],
+            "signature": listed["cross_signature"]["signature"],
+            "nonce": listed["cross_signature"]["nonce"],
+            "expires_at": listed["cross_signature"]["expires_at"],
+            "domain": body["domain"],
+        }))
+        .unwrap();
+        cross_sign_verify(
+            stored.signature,
+            &stored.signer,
+            &stored.freshness,
+            &second_address,
+            &crate::verify::nonce::InMemoryNonceManager::default(),
+        )
+        .await
+        .unwrap();
+
+        // re-attestations carry new nonces
+        let mut response = get(&first, &format!("/federation/cross_sign/{second_address}")).await;
+        let attestation: Value = response.body_json().await.unwrap();
+        assert_eq!(submit(&second, attestation).await.status(), 200);
+    }
+
+    #[async_std::test]
+    async fn test_unknown_server() {
+        let (first, second) = (State::default(), State::default());
+        register(&first, &second.server_identity.address).await;
+        let mut response = get(
+            &first,
+            &format!("/federation/cross_sign/{}", second.server_identity.address),
+        )
+        .await;
+        let attestation: Value = response.body_json().await.unwrap();
+        let mut response = submit(&second, attestation).await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "unknown server");
+    }
+}
@@ -1,14 +1,21 @@
 use serde_json::json;
 use tide::{Request, Response, http::mime};
 
-use crate::routes::State;
+use crate::routes::{State, cache::Validators};
 
 pub async fn route(req: Request<State>) -> tide::Result {
-    let servers = req.state().server_storage.servers().await?;
-    let response = Response::builder(200)
-        .body(json!(servers))
+    let state = req.state();
+    // servers are not in the change log, the list is small enough to be hashed instead
+    let servers = json!(state.server_storage.servers().await?);
+    let validators = Validators::content(servers.to_string().as_bytes());
+    if let Some(response) = validators.not_modified(&req, &state.config.cache)? {
+        return Ok(response);
+    }
+    let mut response = Response::builder(200)
+        .body(servers)
         .content_type(mime::JSON)
         .build();
+    validators.apply(&state.config.cache, &mut response);
     Ok(response)
 }
 
@@ -29,6 +36,7 @@ mod tests {
                 ServerInfo {
                     url: "http://e".into(),
                     scale: Rational::default(),
+                    ..Default::default()
                 },
             )
             .await
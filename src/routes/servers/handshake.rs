@@ -0,0 +1,62 @@
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    routes::State,
+    verify::{handshake::handshake_sign, nonce::OutboundNonces},
+};
+
+// seconds the handshake stays valid, admins submit it right after requesting
+pub const HANDSHAKE_VALIDITY: u64 = 300;
+
+// proves to the `domain` server that this server controls its address. Admins of the domain
+// submit the response with `/add_server`.
+pub async fn route(req: Request<State>) -> tide::Result {
+    let domain = req.param("domain")?.to_string();
+    let state = req.state();
+    let validity = HANDSHAKE_VALIDITY.min(state.config.signatures.max_age);
+    let handshake = handshake_sign(
+        &state.server_identity.private_key,
+        &domain,
+        state.identity_service.now().saturating_add(validity),
+        &OutboundNonces(&*state.nonce_manager),
+    )
+    .await?;
+    Ok(Response::builder(200)
+        .body(serde_json::to_value(handshake)?)
+        .content_type(mime::JSON)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verify::{
+        handshake::handshake_verify, nonce::InMemoryNonceManager, signature::Signature,
+    };
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        let address = state.server_identity.address.clone();
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse("http://example.com/handshake/domain").unwrap(),
+        );
+        let mut server = tide::with_state(state);
+        server.at("/handshake/:domain").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let handshake: Signature = response.body_json().await.unwrap();
+        assert_eq!(handshake.signer, address);
+        assert_eq!(handshake.freshness.domain, Some("domain".to_string()));
+        handshake_verify(
+            handshake.signature,
+            &address,
+            &handshake.freshness,
+            &InMemoryNonceManager::default(),
+        )
+        .await
+        .unwrap();
+    }
+}
@@ -1,3 +1,8 @@
 pub mod add_server;
+pub mod attest_server;
+pub mod balance_update;
+pub mod cross_sign;
 pub mod get_servers;
+pub mod handshake;
 pub mod remove_server;
+pub mod set_home;
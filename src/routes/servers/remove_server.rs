@@ -6,20 +6,31 @@ use tide::{Request, Response, http::mime};
 
 use crate::{
     identity::UserAddress,
-    routes::{State, verify_admin_action},
-    verify::{admins::admin_set_server_message_prefix, nonce::Nonce},
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        signed_body, verify_admin_action,
+    },
+    verify::{admins::admin_set_server_message_prefix, signature::Freshness},
 };
 
 #[derive(Deserialize)]
 struct ServerRequest {
     from: UserAddress,
     signature: String,
-    nonce: Nonce,
+    #[serde(flatten)]
+    freshness: Freshness,
     address: UserAddress,
 }
 
+impl SignedRequest for ServerRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
 pub async fn route(mut req: Request<State>) -> tide::Result {
-    let body: ServerRequest = req.body_json().await?;
+    let body: ServerRequest = signed_body(&mut req).await?;
     let sender = body.from.clone();
     let message_prefix = admin_set_server_message_prefix(body.address.clone());
 
@@ -27,7 +38,7 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
         req.state(),
         &sender,
         body.signature,
-        body.nonce,
+        &body.freshness,
         &message_prefix,
     )
     .await
@@ -42,16 +53,13 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
         .await
         .is_err()
     {
-        return Ok(Response::builder(400)
-            .body(json!({"error": "failed to remove server"}))
-            .content_type(mime::JSON)
-            .build());
+        return Ok(ApiError::bad_request(ErrorCode::FailedToRemoveServer).into());
     }
 
     let response: HashMap<String, serde_json::Value> = HashMap::from([
         ("removed".into(), body.address.into()),
         ("from".into(), sender.into()),
-        ("nonce".into(), body.nonce.into()),
+        ("nonce".into(), body.freshness.nonce.into()),
     ]);
 
     let response = Response::builder(200)
@@ -69,7 +77,7 @@ mod tests {
         admins::InMemoryAdminStorage,
         numbers::Rational,
         servers::storage::ServerInfo,
-        verify::{random_keypair, sign_message},
+        verify::{expires_in, random_keypair, sign_message},
     };
     use serde_json::Value;
     use std::{collections::HashSet, sync::Arc};
@@ -91,19 +99,28 @@ mod tests {
                 ServerInfo {
                     url: "http://e".into(),
                     scale: Rational::default(),
+                    ..Default::default()
                 },
             )
             .await
             .unwrap();
 
         let message_prefix = admin_set_server_message_prefix("server1".to_string());
-        let signature = sign_message(&admin_priv, &message_prefix, &*state.nonce_manager)
-            .await
-            .expect("Should sign");
+        let signature = sign_message(
+            &admin_priv,
+            &state.server_identity.address,
+            &message_prefix,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
         let body = json!({
             "from": signature.signer,
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
             "address": "server1"
         });
 
@@ -123,7 +140,7 @@ mod tests {
         let body: Value = response.body_json().await.unwrap();
         assert_eq!(body["removed"], "server1");
         assert_eq!(body["from"], admin_addr);
-        assert_eq!(body["nonce"], signature.nonce);
+        assert_eq!(body["nonce"], signature.freshness.nonce);
         assert!(
             !state
                 .server_storage
@@ -147,13 +164,21 @@ mod tests {
 
         let req_url = "/remove_server";
         let message_prefix = admin_set_server_message_prefix("server1".to_string());
-        let signature = sign_message(&private_key, &message_prefix, &*state.nonce_manager)
-            .await
-            .expect("Should sign");
+        let signature = sign_message(
+            &private_key,
+            &state.server_identity.address,
+            &message_prefix,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
         let body = json!({
             "from": signature.signer,
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
             "address": "server1",
         });
 
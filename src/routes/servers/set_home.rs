@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::UserAddress,
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        signed_body, verify_admin_action,
+    },
+    verify::{admins::admin_set_home_message_prefix, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct HomeRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+    // home server address, missing value makes the user local again
+    #[serde(default)]
+    server: Option<UserAddress>,
+}
+
+impl SignedRequest for HomeRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let body: HomeRequest = signed_body(&mut req).await?;
+    let sender = body.from.clone();
+    let message_prefix = admin_set_home_message_prefix(user.clone(), body.server.clone());
+
+    if let Err(response) = verify_admin_action(
+        req.state(),
+        &sender,
+        body.signature,
+        &body.freshness,
+        &message_prefix,
+    )
+    .await
+    {
+        return Ok(response);
+    }
+
+    let home_storage = &req.state().home_storage;
+    let result = match body.server.clone() {
+        Some(server) => {
+            if !req
+                .state()
+                .server_storage
+                .servers()
+                .await?
+                .contains_key(&server)
+            {
+                return Ok(ApiError::bad_request(ErrorCode::UnknownServer).into());
+            }
+            // the user is no longer at home on this server
+            home_storage.remove_claim(&user).await?;
+            home_storage.set_home(user.clone(), server).await
+        }
+        None => home_storage.remove_home(&user).await,
+    };
+    if result.is_err() {
+        return Ok(ApiError::bad_request(ErrorCode::FailedToSetHome).into());
+    }
+
+    let response: HashMap<String, serde_json::Value> = HashMap::from([
+        ("user".into(), user.into()),
+        ("server".into(), body.server.into()),
+        ("from".into(), sender.into()),
+        ("nonce".into(), body.freshness.nonce.into()),
+    ]);
+
+    let response = Response::builder(200)
+        .body(json!(response))
+        .content_type(mime::JSON)
+        .build();
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use super::*;
+    use crate::{
+        admins::InMemoryAdminStorage,
+        numbers::Rational,
+        servers::storage::ServerInfo,
+        verify::{expires_in, random_keypair, sign_message},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn set_home(
+        state: &State,
+        admin_priv: &str,
+        user: &str,
+        server: Option<&str>,
+    ) -> Response {
+        let message_prefix =
+            admin_set_home_message_prefix(user.to_string(), server.map(String::from));
+        let signature = sign_message(
+            admin_priv,
+            &state.server_identity.address,
+            &message_prefix,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        let body = json!({
+            "from": signature.signer,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+            "server": server,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/set_home/{user}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/set_home/:user").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (admin_priv, admin_addr) = random_keypair();
+        let state = State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::from([admin_addr]),
+                HashSet::new(),
+            )),
+            ..Default::default()
+        };
+        state
+            .server_storage
+            .add_server(
+                "server1".to_string(),
+                ServerInfo {
+                    url: "http://server1".to_string(),
+                    scale: Rational::default(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let mut response = set_home(&state, &admin_priv, "user", Some("server2")).await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "unknown server");
+
+        let mut response = set_home(&state, &admin_priv, "user", Some("server1")).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["server"], "server1");
+        assert_eq!(
+            state.home_storage.home(&"user".to_string()).await.unwrap(),
+            Some("server1".to_string())
+        );
+
+        let response = set_home(&state, &admin_priv, "user", None).await;
+        assert_eq!(response.status(), 200);
+        assert!(
+            state
+                .home_storage
+                .home(&"user".to_string())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_not_admin() {
+        let (priv_key, _) = random_keypair();
+        let state = State::default();
+        let response = set_home(&state, &priv_key, "user", None).await;
+        assert_eq!(response.status(), 403);
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::BTreeSet;
+
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::UserAddress,
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        signed_body, verify_admin_action,
+    },
+    service_accounts::ServiceAccount,
+    verify::{admins::admin_add_service_account_message_prefix, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct AddServiceAccountRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+    // signed in the given order, joined with commas
+    scopes: Vec<String>,
+}
+
+impl SignedRequest for AddServiceAccountRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// registers the address as a service account, replacing the scopes if it is already registered
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let address = req.param("address")?.to_string();
+    let body: AddServiceAccountRequest = signed_body(&mut req).await?;
+    let sender = body.from.clone();
+    if body
+        .scopes
+        .iter()
+        .any(|scope| scope.is_empty() || scope.contains(['/', ',']))
+    {
+        return Ok(ApiError::bad_request(ErrorCode::InvalidScope).into());
+    }
+    let message_prefix = admin_add_service_account_message_prefix(address.clone(), &body.scopes);
+
+    if let Err(response) = verify_admin_action(
+        req.state(),
+        &sender,
+        body.signature,
+        &body.freshness,
+        &message_prefix,
+    )
+    .await
+    {
+        return Ok(response);
+    }
+
+    let account = ServiceAccount {
+        address,
+        scopes: body.scopes.into_iter().collect::<BTreeSet<_>>(),
+        added_by: sender,
+        added_at: req.state().identity_service.now(),
+    };
+    req.state()
+        .service_accounts
+        .add_account(account.clone())
+        .await?;
+    log::info!(
+        "Service account {} added by {} with scopes {:?}",
+        account.address,
+        account.added_by,
+        account.scopes
+    );
+
+    let response = Response::builder(200)
+        .body(json!({
+            "account": account,
+            "nonce": body.freshness.nonce,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use super::*;
+    use crate::{
+        admins::InMemoryAdminStorage,
+        verify::{expires_in, random_keypair, sign_message},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    pub fn admin_state(admin: &UserAddress) -> State {
+        State {
+            admin_storage: Arc::new(InMemoryAdminStorage::new(
+                HashSet::from([admin.clone()]),
+                HashSet::new(),
+            )),
+            ..Default::default()
+        }
+    }
+
+    async fn add(state: &State, private_key: &str, address: &str, scopes: &[&str]) -> Response {
+        let scopes: Vec<String> = scopes.iter().map(|scope| scope.to_string()).collect();
+        let signature = sign_message(
+            private_key,
+            &state.server_identity.address,
+            &admin_add_service_account_message_prefix(address.to_string(), &scopes),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        let body = json!({
+            "from": signature.signer,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+            "scopes": scopes,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/add_service_account/{address}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/add_service_account/:address").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, admin) = random_keypair();
+        let state = admin_state(&admin);
+
+        let mut response = add(&state, &private_key, "service", &["vouch", "idt"]).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["account"]["scopes"], json!(["idt", "vouch"]));
+        assert_eq!(body["account"]["added_by"], admin);
+        let account = state
+            .service_accounts
+            .account(&"service".into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(account.allows("/vouch/userA"));
+
+        let response = add(&state, &private_key, "service", &["vouch/userA"]).await;
+        assert_eq!(response.status(), 400);
+    }
+
+    #[async_std::test]
+    async fn test_no_privilege() {
+        let (private_key, _) = random_keypair();
+        let state = admin_state(&"other_admin".to_string());
+        let response = add(&state, &private_key, "service", &["vouch"]).await;
+        assert_eq!(response.status(), 403);
+        assert!(state.service_accounts.accounts().await.unwrap().is_empty());
+    }
+}
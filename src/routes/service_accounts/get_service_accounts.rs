@@ -0,0 +1,50 @@
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::routes::State;
+
+pub async fn route(req: Request<State>) -> tide::Result {
+    let accounts = req.state().service_accounts.accounts().await?;
+    let response = Response::builder(200)
+        .body(json!({ "service_accounts": accounts }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::service_accounts::ServiceAccount;
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        state
+            .service_accounts
+            .add_account(ServiceAccount {
+                address: "service".into(),
+                scopes: BTreeSet::from(["idt".to_string()]),
+                added_by: "admin".into(),
+                added_at: 10,
+            })
+            .await
+            .unwrap();
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse("http://example.com/service_accounts").unwrap(),
+        );
+        let mut server = tide::with_state(state);
+        server.at("/service_accounts").get(route);
+        let mut response: tide::http::Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["service_accounts"][0]["address"], "service");
+        assert_eq!(body["service_accounts"][0]["scopes"], json!(["idt"]));
+        assert_eq!(body["service_accounts"][0]["added_at"], 10);
+    }
+}
@@ -0,0 +1,203 @@
+use serde_json::Value;
+use tide::{Middleware, Next, Request};
+
+use crate::{
+    identity::UserAddress,
+    routes::{
+        State,
+        error::{ApiError, ErrorCode},
+    },
+};
+
+pub mod add_service_account;
+pub mod get_service_accounts;
+pub mod remove_service_account;
+
+// path of the route whose signer is the user of the path instead of a body field
+const START_ATTESTATION_PATH: &str = "/attestations/start/";
+
+// addresses that may sign the request, as each route reads them: `from` as an address or
+// as `{"user": ..}` for vouches and forgets, `verifier` of attestations, `signer` of servers
+// and the user of the path for started attestations
+fn signers(path: &str, body: &Value) -> Vec<UserAddress> {
+    let mut signers: Vec<UserAddress> = [
+        body.get("from"),
+        body.get("from").and_then(|from| from.get("user")),
+        body.get("verifier"),
+        body.get("signer"),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|value| value.as_str().map(str::to_string))
+    .collect();
+    if let Some(user) = path.strip_prefix(START_ATTESTATION_PATH) {
+        signers.push(user.to_string());
+    }
+    signers
+}
+
+// rejects signed requests of service accounts to routes outside of their scopes. Signatures
+// are still checked by the routes. Signed requests without a known signer are rejected,
+// requests without a signature pass through.
+pub struct ServiceAccountMiddleware;
+
+#[tide::utils::async_trait]
+impl Middleware<State> for ServiceAccountMiddleware {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let method = req.method();
+        if method == tide::http::Method::Get || method == tide::http::Method::Head {
+            return Ok(next.run(req).await);
+        }
+        let body = req.body_string().await?;
+        let value = serde_json::from_str::<Value>(&body).unwrap_or_default();
+        // the body is consumed by reading, give it back to the route
+        req.set_body(body);
+        if value.get("signature").is_none() {
+            return Ok(next.run(req).await);
+        }
+        let signers = signers(req.url().path(), &value);
+        if signers.is_empty() {
+            return Ok(ApiError::bad_request(ErrorCode::SignerMissing).into());
+        }
+        let accounts = &req.state().service_accounts;
+        for signer in &signers {
+            match accounts.account(signer).await? {
+                Some(account) if !account.allows(req.url().path()) => {
+                    return Ok(ApiError::new(403, ErrorCode::RouteOutsideScopes).into());
+                }
+                _ => {}
+            }
+        }
+        Ok(next.run(req).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::service_accounts::ServiceAccount;
+    use serde_json::json;
+    use tide::http::{Method, Request as HttpRequest, Url, mime};
+
+    async fn respond(state: &State, path: &str, body: serde_json::Value) -> tide::http::Response {
+        let mut req = HttpRequest::new(
+            Method::Post,
+            Url::parse(&format!("http://example.com{path}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.with(ServiceAccountMiddleware);
+        server
+            .at("/vouch/:user")
+            .post(|mut req: Request<State>| async move {
+                let body: serde_json::Value = req.body_json().await?;
+                Ok(body["from"].to_string())
+            });
+        server
+            .at("/punish/:user")
+            .post(|_| async { Ok("punished") });
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_scopes() {
+        let state = State::default();
+        state
+            .service_accounts
+            .add_account(ServiceAccount {
+                address: "service".into(),
+                scopes: BTreeSet::from(["vouch".to_string()]),
+                added_by: "admin".into(),
+                added_at: 0,
+            })
+            .await
+            .unwrap();
+
+        let mut response = respond(
+            &state,
+            "/vouch/userA",
+            json!({"from": "service", "signature": "0x"}),
+        )
+        .await;
+        assert_eq!(response.status(), 200);
+        // the route still reads the body
+        assert_eq!(response.body_string().await.unwrap(), "\"service\"");
+        let mut response = respond(
+            &state,
+            "/punish/userA",
+            json!({"from": "service", "signature": "0x"}),
+        )
+        .await;
+        assert_eq!(response.status(), 403);
+        let body: serde_json::Value = response.body_json().await.unwrap();
+        assert_eq!(
+            body["error"],
+            "route is outside of the service account scopes"
+        );
+
+        // other users are not restricted
+        let response = respond(
+            &state,
+            "/punish/userA",
+            json!({"from": "user", "signature": "0x"}),
+        )
+        .await;
+        assert_eq!(response.status(), 200);
+        let response = respond(&state, "/punish/userA", json!([])).await;
+        assert_eq!(response.status(), 200);
+    }
+
+    #[async_std::test]
+    async fn test_signers() {
+        let state = State::default();
+        state
+            .service_accounts
+            .add_account(ServiceAccount {
+                address: "service".into(),
+                scopes: BTreeSet::from(["idt".to_string()]),
+                added_by: "admin".into(),
+                added_at: 0,
+            })
+            .await
+            .unwrap();
+
+        for (path, body) in [
+            (
+                "/vouch/userA",
+                json!({"from": {"user": "service"}, "signature": "0x"}),
+            ),
+            (
+                "/forget/userA",
+                json!({"from": {"user": "service", "server": "other"}, "signature": "0x"}),
+            ),
+            (
+                "/attestations/complete/userA",
+                json!({"verifier": "service", "signature": "0x"}),
+            ),
+            (
+                "/federation/cross_sign",
+                json!({"signer": "service", "signature": "0x"}),
+            ),
+            ("/attestations/start/service", json!({"signature": "0x"})),
+        ] {
+            let response = respond(&state, path, body).await;
+            assert_eq!(response.status(), 403, "{path}");
+        }
+
+        // signed requests name their signer
+        let mut response = respond(&state, "/punish/userA", json!({"signature": "0x"})).await;
+        assert_eq!(response.status(), 400);
+        let body: serde_json::Value = response.body_json().await.unwrap();
+        assert_eq!(body["code"], "signer_missing");
+        let response = respond(
+            &state,
+            "/vouch/userA",
+            json!({"from": {"user": "user"}, "signature": "0x"}),
+        )
+        .await;
+        assert_eq!(response.status(), 200);
+    }
+}
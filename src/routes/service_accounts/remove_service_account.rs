@@ -0,0 +1,133 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::UserAddress,
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        signed_body, verify_admin_action,
+    },
+    verify::{admins::admin_remove_service_account_message_prefix, signature::Freshness},
+};
+
+#[derive(Deserialize)]
+struct RemoveServiceAccountRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for RemoveServiceAccountRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// the address becomes a regular user again
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let address = req.param("address")?.to_string();
+    let body: RemoveServiceAccountRequest = signed_body(&mut req).await?;
+    let sender = body.from.clone();
+    let message_prefix = admin_remove_service_account_message_prefix(address.clone());
+
+    if let Err(response) = verify_admin_action(
+        req.state(),
+        &sender,
+        body.signature,
+        &body.freshness,
+        &message_prefix,
+    )
+    .await
+    {
+        return Ok(response);
+    }
+
+    if !req
+        .state()
+        .service_accounts
+        .remove_account(&address)
+        .await?
+    {
+        return Ok(ApiError::new(404, ErrorCode::ServiceAccountNotFound).into());
+    }
+    log::info!("Service account {} removed by {}", address, sender);
+
+    let response = Response::builder(200)
+        .body(json!({
+            "address": address,
+            "from": sender,
+            "nonce": body.freshness.nonce,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::{
+        routes::service_accounts::add_service_account::tests::admin_state,
+        service_accounts::ServiceAccount,
+        verify::{expires_in, random_keypair, sign_message},
+    };
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn remove(state: &State, private_key: &str, address: &str) -> Response {
+        let signature = sign_message(
+            private_key,
+            &state.server_identity.address,
+            &admin_remove_service_account_message_prefix(address.to_string()),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        let body = json!({
+            "from": signature.signer,
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!(
+                "http://example.com/remove_service_account/{address}"
+            ))
+            .unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/remove_service_account/:address").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, admin) = random_keypair();
+        let state = admin_state(&admin);
+        state
+            .service_accounts
+            .add_account(ServiceAccount {
+                address: "service".into(),
+                scopes: BTreeSet::from(["vouch".to_string()]),
+                added_by: admin.clone(),
+                added_at: 0,
+            })
+            .await
+            .unwrap();
+
+        let response = remove(&state, &private_key, "service").await;
+        assert_eq!(response.status(), 200);
+        assert!(state.service_accounts.accounts().await.unwrap().is_empty());
+        let response = remove(&state, &private_key, "service").await;
+        assert_eq!(response.status(), 404);
+    }
+}
@@ -0,0 +1,282 @@
+use ethers_core::utils::keccak256;
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::{
+        UserAddress,
+        tree_size::{TreeSize, UserTreeSize},
+    },
+    routes::{
+        State,
+        error::{ApiError, ErrorCode},
+    },
+};
+
+// number of users listed by default and at most
+const DEFAULT_TOP: usize = 10;
+const MAX_TOP: usize = 100;
+
+#[derive(Deserialize)]
+struct StatsQuery {
+    top: Option<usize>,
+}
+
+// adds Laplace noise with scale 1 / epsilon to the count, `u` is uniform in (-0.5, 0.5)
+fn noisy_count(count: u64, epsilon: f64, u: f64) -> u64 {
+    // inverse of the Laplace distribution function
+    let noise = -u.signum() * (1.0 - 2.0 * u.abs()).ln() / epsilon;
+    (count as f64 + noise).round().max(0.0) as u64
+}
+
+// noise of the counts with `privacy.epsilon`, exact without it. The noise is derived from
+// a secret of the server, the counted value and the current `privacy.noise_epoch`, so
+// repeated queries get the same answer and averaging them reveals nothing new.
+struct Noise {
+    salt: [u8; 32],
+    epsilon: Option<f64>,
+    epoch: u64,
+}
+
+impl Noise {
+    fn new(state: &State) -> Self {
+        let privacy = &state.config.privacy;
+        Self {
+            salt: keccak256(format!("stats/{}", state.server_identity.private_key)),
+            epsilon: privacy.epsilon,
+            epoch: state.identity_service.now() / privacy.noise_epoch.max(1),
+        }
+    }
+
+    fn count(&self, label: &str, count: u64) -> u64 {
+        let Some(epsilon) = self.epsilon else {
+            return count;
+        };
+        let mut data = self.salt.to_vec();
+        data.extend_from_slice(format!("{label}/{count}/{}", self.epoch).as_bytes());
+        let hash = keccak256(data);
+        let bits = u64::from_be_bytes(hash[..8].try_into().expect("hash has 32 bytes")) >> 11;
+        // 53 bits fit into f64 exactly, the result never reaches -0.5 or 0.5
+        let u = (bits as f64 + 0.5) / (1u64 << 53) as f64 - 0.5;
+        noisy_count(count, epsilon, u)
+    }
+
+    fn tree_size(&self, label: &str, size: TreeSize) -> TreeSize {
+        TreeSize {
+            nodes: self.count(&format!("{label}/nodes"), size.nodes),
+            edges: self.count(&format!("{label}/edges"), size.edges),
+        }
+    }
+
+    fn user_tree_size(&self, user: &UserAddress, size: UserTreeSize) -> UserTreeSize {
+        UserTreeSize {
+            last: self.tree_size(&format!("{user}/last"), size.last),
+            max: self.tree_size(&format!("{user}/max"), size.max),
+            computations: self.count(&format!("{user}/computations"), size.computations),
+            computed_at: size.computed_at,
+        }
+    }
+}
+
+// users with the largest vouch trees walked since the server start. Counts are noised with
+// `privacy.epsilon` and users are ranked by the noised sizes.
+pub async fn route(req: Request<State>) -> tide::Result {
+    let top = req
+        .query::<StatsQuery>()
+        .ok()
+        .and_then(|query| query.top)
+        .unwrap_or(DEFAULT_TOP)
+        .min(MAX_TOP);
+    let state = req.state();
+    let service = &state.identity_service;
+    let noise = Noise::new(state);
+    let ranked = match noise.epsilon {
+        Some(_) => usize::MAX,
+        None => top,
+    };
+    let mut largest: Vec<_> = service
+        .tree_sizes
+        .largest(ranked)
+        .into_iter()
+        .map(|(user, size)| {
+            let size = noise.user_tree_size(&user, size);
+            (user, size)
+        })
+        .collect();
+    largest.sort_by(|(a, a_size), (b, b_size)| {
+        (b_size.max.nodes, b_size.max.edges, a).cmp(&(a_size.max.nodes, a_size.max.edges, b))
+    });
+    largest.truncate(top);
+    let largest: Vec<_> = largest
+        .into_iter()
+        .map(|(user, size)| json!({"user": user, "tree_size": size}))
+        .collect();
+    let tracked_users = service.tree_sizes.tracked_users() as u64;
+    let response = Response::builder(200)
+        .body(json!({
+            "tree_sizes": {
+                "tracked_users": noise.count("tracked_users", tracked_users),
+                "warnings": noise.count("warnings", service.tree_sizes.warnings()),
+                "limits": service.config.tree_size_warning,
+                "largest": largest,
+            },
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+pub async fn user_route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let state = req.state();
+    let service = &state.identity_service;
+    let Some(size) = service.tree_sizes.user(&user) else {
+        return Ok(ApiError::new(404, ErrorCode::BalanceNotComputed).into());
+    };
+    let size = Noise::new(state).user_tree_size(&user, size);
+    let response = Response::builder(200)
+        .body(json!({
+            "user": user,
+            "tree_size": size,
+            "exceeds_limits": service.config.tree_size_warning.exceeded_by(size.max),
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        config::{Config, PrivacySection},
+        identity::{
+            IdtAmount,
+            idt::balance,
+            proof::prove,
+            tests::{MODERATOR, PROOF_ID, USER_A, service_with_mock_clock},
+            vouch::vouch,
+        },
+    };
+    use ethers_core::rand::{Rng, SeedableRng, rngs::StdRng};
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn get(state: &State, path: &str) -> Response {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com{path}")).unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/stats").get(route);
+        server.at("/stats/:user").get(user_route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        let service = &state.identity_service;
+        prove(
+            service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(service, USER_A.to_string(), "userB".into())
+            .await
+            .unwrap();
+        assert_eq!(get(&state, "/stats/userB").await.status(), 404);
+
+        balance(service, &USER_A.to_string()).await.unwrap();
+        balance(service, &"userB".to_string()).await.unwrap();
+
+        let mut response = get(&state, "/stats/userB").await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["tree_size"]["last"], json!({"nodes": 2, "edges": 1}));
+        assert_eq!(body["tree_size"]["computations"], 1);
+        assert_eq!(body["exceeds_limits"], false);
+
+        let mut response = get(&state, "/stats?top=1").await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["tree_sizes"]["tracked_users"], 2);
+        assert_eq!(body["tree_sizes"]["warnings"], 0);
+        assert_eq!(body["tree_sizes"]["largest"].as_array().unwrap().len(), 1);
+        assert_eq!(body["tree_sizes"]["largest"][0]["user"], "userB");
+    }
+
+    #[test]
+    fn test_noisy_count() {
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(noisy_count(100, 0.5, 0.0), 100);
+        let samples: Vec<u64> = (0..10000)
+            .map(|_| noisy_count(100, 0.5, rng.gen_range(-0.5..0.5)))
+            .collect();
+        assert!(samples.iter().any(|&count| count != 100));
+        // the noise has zero mean and variance 2 / epsilon^2
+        let mean = samples.iter().sum::<u64>() as f64 / samples.len() as f64;
+        assert!((mean - 100.0).abs() < 0.5, "{mean}");
+    }
+
+    #[async_std::test]
+    async fn test_noise() {
+        let (service, clock) = service_with_mock_clock();
+        let state = State {
+            identity_service: service,
+            config: Arc::new(Config {
+                privacy: PrivacySection {
+                    epsilon: Some(0.1),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let service = &state.identity_service;
+        for i in 0..20 {
+            balance(service, &format!("user{i}")).await.unwrap();
+        }
+        let query = |path: &'static str| {
+            let state = state.clone();
+            async move {
+                let mut response = get(&state, path).await;
+                assert_eq!(response.status(), 200);
+                response.body_json::<Value>().await.unwrap()
+            }
+        };
+
+        // repeated queries within an epoch cannot be averaged
+        let first = query("/stats?top=20").await;
+        let tracked = |body: &Value| body["tree_sizes"]["tracked_users"].as_u64().unwrap();
+        for _ in 0..5 {
+            assert_eq!(query("/stats?top=20").await, first);
+        }
+        let user = query("/stats/user0").await;
+        assert_eq!(query("/stats/user0").await, user);
+
+        // users are ranked by the noised sizes
+        let largest = first["tree_sizes"]["largest"].as_array().unwrap().clone();
+        assert_eq!(largest.len(), 20);
+        let sizes: Vec<u64> = largest
+            .iter()
+            .map(|entry| entry["tree_size"]["max"]["nodes"].as_u64().unwrap())
+            .collect();
+        assert!(sizes.windows(2).all(|pair| pair[0] >= pair[1]));
+
+        // the counts are noised again in every epoch
+        let mut counts = vec![tracked(&first)];
+        for _ in 0..5 {
+            clock.advance(state.config.privacy.noise_epoch);
+            counts.push(tracked(&query("/stats").await));
+        }
+        assert!(counts.iter().any(|&count| count != counts[0]));
+    }
+}
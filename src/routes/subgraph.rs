@@ -0,0 +1,66 @@
+use tide::{Request, Response, http::mime};
+
+use crate::{identity::subgraph::export_subgraph, routes::State};
+
+// everything the balance of the user depends on, so clients can recompute it with the
+// `wasm` build of the library. `idt` is the balance recomputed from the subgraph.
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let subgraph = export_subgraph(&req.state().identity_service, &user).await?;
+    let idt = subgraph.balance().await?;
+    let mut body = serde_json::to_value(&subgraph)?;
+    body["idt"] = idt.to_string().into();
+    Ok(Response::builder(200)
+        .body(body)
+        .content_type(mime::JSON)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{
+        IdtAmount,
+        idt::balance,
+        proof::prove,
+        subgraph::Subgraph,
+        tests::{MODERATOR, PROOF_ID, USER_A},
+        vouch::vouch,
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        let service = &state.identity_service;
+        prove(
+            service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(service, USER_A.to_string(), "userB".to_string())
+            .await
+            .unwrap();
+        let expected = balance(service, &"userB".to_string()).await.unwrap();
+
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse("http://example.com/subgraph/userB").unwrap(),
+        );
+        let mut server = tide::with_state(state);
+        server.at("/subgraph/:user").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["idt"], expected.to_string());
+        assert_eq!(body["proofs"][USER_A]["amount"], 100);
+
+        let subgraph: Subgraph = serde_json::from_value(body).unwrap();
+        assert_eq!(subgraph.balance().await.unwrap(), expected);
+    }
+}
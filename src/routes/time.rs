@@ -0,0 +1,69 @@
+use serde_json::json;
+use tide::{Middleware, Next, Request, Response, http::mime};
+
+use crate::routes::State;
+
+pub const SERVER_TIME_HEADER: &str = "X-Server-Time";
+
+// lets clients detect the skew of their clocks before signing timestamps
+pub async fn route(req: Request<State>) -> tide::Result {
+    let service = &req.state().identity_service;
+    let response = Response::builder(200)
+        .body(json!({
+            "time": service.now(),
+            "future_timestamps": service.future_timestamps.count(),
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+// adds the server time to every response
+pub struct ServerTimeMiddleware;
+
+#[tide::utils::async_trait]
+impl Middleware<State> for ServerTimeMiddleware {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let now = req.state().identity_service.now();
+        let mut response = next.run(req).await;
+        response.insert_header(SERVER_TIME_HEADER, now.to_string());
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::setup_routes;
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        let now = state.identity_service.now();
+        let mut server = tide::with_state(state);
+        setup_routes(&mut server);
+
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse("http://example.com/time").unwrap(),
+        );
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let header: u64 = response[SERVER_TIME_HEADER].as_str().parse().unwrap();
+        assert!(header >= now);
+        let body: Value = response.body_json().await.unwrap();
+        assert!(body["time"].as_u64().unwrap() >= now);
+        assert_eq!(body["future_timestamps"], 0);
+
+        // the header is added to other routes, including errors
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse("http://example.com/unknown").unwrap(),
+        );
+        let response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 404);
+        assert!(response.header(SERVER_TIME_HEADER).is_some());
+    }
+}
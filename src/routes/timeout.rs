@@ -0,0 +1,142 @@
+use std::time::Duration;
+
+use tide::{Middleware, Next, Request, http::Method};
+
+use crate::routes::{
+    State,
+    error::{ApiError, ErrorCode},
+};
+
+// hint for clients on how to get a result that fits into the limit
+fn partial_result_hint(route: &str) -> &'static str {
+    match route {
+        "idt" => {
+            "retry with a smaller `top` or use `GET /proofs/<user>/status` for the proven balance"
+        }
+        "trust" => "use `GET /idt/<user>?top=1` for a cheaper local balance",
+        _ => "retry later",
+    }
+}
+
+// cancels read requests running longer than `server.request_timeout_ms`, dropping the
+// handler future stops the computation at its next await point. Writes are never cancelled:
+// a write stopped between two storage calls would leave e.g. a forgotten vouch without its
+// penalty while the nonce of the request is already used.
+pub struct TimeoutMiddleware;
+
+#[tide::utils::async_trait]
+impl Middleware<State> for TimeoutMiddleware {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let state = req.state().clone();
+        let timeout_ms = state.config.server.request_timeout_ms;
+        let read = matches!(req.method(), Method::Get | Method::Head);
+        if timeout_ms == 0 || !read {
+            return Ok(next.run(req).await);
+        }
+        let route = req
+            .url()
+            .path()
+            .trim_start_matches('/')
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let timeout = Duration::from_millis(timeout_ms);
+        match async_std::future::timeout(timeout, next.run(req)).await {
+            Ok(response) => Ok(response),
+            Err(_) => {
+                state.request_timeouts.record(&route);
+                log::warn!("Request to /{} timed out after {:?}", route, timeout);
+                Ok(ApiError::new(504, ErrorCode::RequestTimedOut)
+                    .with("timeout_ms", timeout_ms)
+                    .with("hint", partial_result_hint(&route))
+                    .into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::config::{Config, ServerSection};
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn slow(_req: Request<State>) -> tide::Result {
+        async_std::task::sleep(Duration::from_secs(5)).await;
+        Ok("done".into())
+    }
+
+    async fn get(server: &tide::Server<State>, path: &str) -> Response {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com{path}")).unwrap(),
+        );
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_timeout() {
+        let state = State {
+            config: Arc::new(Config {
+                server: ServerSection {
+                    request_timeout_ms: 20,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut server = tide::with_state(state.clone());
+        server.with(TimeoutMiddleware);
+        server.at("/slow").get(slow);
+        server.at("/fast").get(|_| async { Ok("done") });
+        server.at("/write").post(|_| async {
+            async_std::task::sleep(Duration::from_millis(100)).await;
+            Ok("done")
+        });
+
+        let mut response = get(&server, "/slow").await;
+        assert_eq!(response.status(), 504);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "request timed out");
+        assert_eq!(body["timeout_ms"], 20);
+        assert_eq!(state.request_timeouts.by_route()["slow"], 1);
+        assert_eq!(state.request_timeouts.total(), 1);
+
+        assert_eq!(get(&server, "/fast").await.status(), 200);
+        // timeouts of other servers are not counted
+        assert_eq!(State::default().request_timeouts.total(), 0);
+
+        // writes run to the end
+        let req = HttpRequest::new(
+            Method::Post,
+            Url::parse("http://example.com/write").unwrap(),
+        );
+        assert_eq!(
+            server.respond::<_, Response>(req).await.unwrap().status(),
+            200
+        );
+    }
+
+    #[async_std::test]
+    async fn test_disabled() {
+        let state = State {
+            config: Arc::new(Config {
+                server: ServerSection {
+                    request_timeout_ms: 0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut server = tide::with_state(state);
+        server.with(TimeoutMiddleware);
+        server.at("/fast").get(|_| async { Ok("done") });
+        assert_eq!(get(&server, "/fast").await.status(), 200);
+    }
+}
@@ -0,0 +1,146 @@
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    federation::{best_scaled_balance, query_servers},
+    identity::{idt::balance, punish::penalty},
+    routes::State,
+    scoring::{TrustInputs, trust_score},
+};
+
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let state = req.state();
+    let service = &state.identity_service;
+
+    let servers = state.server_storage.servers().await?;
+    let sources = query_servers(
+        &*state.federation_client,
+        servers,
+        &user,
+        service.now(),
+        &state.config.federation,
+    )
+    .await;
+    let inputs = TrustInputs {
+        local: balance(service, &user).await?,
+        external: best_scaled_balance(&sources),
+        penalty: penalty(service, &user).await?,
+    };
+    let weights = &state.config.scoring;
+    let score = trust_score(&inputs, weights);
+    let response = json!({
+        "user": user,
+        "score": score,
+        "local": inputs.local.to_string(),
+        "external": inputs.external.to_string(),
+        "penalty": inputs.penalty.to_string(),
+        "weights": {
+            "local": weights.local_weight,
+            "external": weights.external_weight,
+            "penalty": weights.penalty_weight,
+            "full_score_idt": weights.full_score_idt.to_string(),
+        },
+    });
+    let response = Response::builder(200)
+        .body(response)
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        config::{Config, ScoringSection},
+        federation::{InMemoryFederationClient, RemoteUser},
+        identity::{
+            IdtAmount,
+            proof::prove,
+            punish::punish,
+            tests::{MODERATOR, PROOF_ID, USER_A},
+        },
+        numbers::Rational,
+        servers::storage::ServerInfo,
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let client = Arc::new(InMemoryFederationClient::default());
+        let state = State {
+            federation_client: client.clone(),
+            config: Arc::new(Config {
+                scoring: ScoringSection {
+                    strategy: Default::default(),
+                    pagerank: Default::default(),
+                    local_weight: 1.0,
+                    external_weight: 0.5,
+                    penalty_weight: 1.0,
+                    full_score_idt: IdtAmount::new(1000),
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        state
+            .server_storage
+            .add_server(
+                "server1".to_string(),
+                ServerInfo {
+                    url: "http://server1".to_string(),
+                    scale: Rational::new(1, 2).unwrap(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        client
+            .set_user(
+                "http://server1",
+                USER_A.to_string(),
+                RemoteUser {
+                    idt: IdtAmount::new(800),
+                    vouchers: vec![],
+                },
+            )
+            .await;
+        prove(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(500),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        punish(
+            &state.identity_service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/trust/{USER_A}")).unwrap(),
+        );
+        let mut server = tide::with_state(state);
+        server.at("/trust/:user").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["local"], "400");
+        assert_eq!(body["external"], "400");
+        assert_eq!(body["penalty"], "100");
+        // 400 + 0.5 * 400 - 100 = 500 out of 1000
+        assert_eq!(body["score"], 50.0);
+    }
+}
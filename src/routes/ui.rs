@@ -0,0 +1,95 @@
+// Admin dashboard served from `/ui`, enabled with the `ui` feature.
+//
+// Assets are embedded into the binary. The dashboard only reads the public JSON
+// endpoints and the server config from `/ui/config`.
+
+use serde_json::json;
+use tide::{
+    Request, Response, Server,
+    http::{Mime, mime},
+};
+
+use crate::routes::State;
+
+const INDEX_HTML: &str = include_str!("../../ui/index.html");
+const APP_JS: &str = include_str!("../../ui/app.js");
+const STYLE_CSS: &str = include_str!("../../ui/style.css");
+
+fn asset(body: &'static str, content_type: Mime) -> tide::Result {
+    Ok(Response::builder(200)
+        .body(body)
+        .content_type(content_type)
+        .build())
+}
+
+async fn index(_req: Request<State>) -> tide::Result {
+    asset(INDEX_HTML, mime::HTML)
+}
+
+async fn app_js(_req: Request<State>) -> tide::Result {
+    asset(APP_JS, mime::JAVASCRIPT)
+}
+
+async fn style_css(_req: Request<State>) -> tide::Result {
+    asset(STYLE_CSS, mime::CSS)
+}
+
+async fn config(req: Request<State>) -> tide::Result {
+    Ok(Response::builder(200)
+        .body(json!(*req.state().config))
+        .content_type(mime::JSON)
+        .build())
+}
+
+pub fn setup_routes(server: &mut Server<State>) {
+    server.at("/ui").get(index);
+    server.at("/ui/").get(index);
+    server.at("/ui/app.js").get(app_js);
+    server.at("/ui/style.css").get(style_css);
+    server.at("/ui/config").get(config);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn get(path: &str) -> Response {
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com{path}")).unwrap(),
+        );
+        let mut server = tide::with_state(State::default());
+        setup_routes(&mut server);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_assets() {
+        let mut response = get("/ui").await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.content_type().unwrap().essence(), "text/html");
+        assert!(response.body_string().await.unwrap().contains("/ui/app.js"));
+
+        let response = get("/ui/app.js").await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.content_type().unwrap().essence(),
+            "application/javascript"
+        );
+
+        let response = get("/ui/style.css").await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.content_type().unwrap().essence(), "text/css");
+    }
+
+    #[async_std::test]
+    async fn test_config() {
+        let mut response = get("/ui/config").await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["scoring"]["strategy"], "vouch_tree");
+        assert!(body["identity"].is_object());
+    }
+}
@@ -0,0 +1,61 @@
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{routes::State, startup::version::VersionInfo};
+
+// build of the server, enabled features and storage backend
+pub async fn route(req: Request<State>) -> tide::Result {
+    let state = req.state();
+    let info = VersionInfo::new(&state.config, &state.storage_info);
+    let response = Response::builder(200)
+        .body(json!(info))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::LATEST_MESSAGE_VERSION,
+        startup::version::{GIT_COMMIT, VERSION},
+        storage::StorageInfo,
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State {
+            storage_info: StorageInfo {
+                backend: "sqlite".to_string(),
+                event_log: true,
+                write_queue: false,
+            },
+            ..Default::default()
+        };
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse("http://example.com/version").unwrap(),
+        );
+        let mut server = tide::with_state(state);
+        server.at("/version").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["version"], VERSION);
+        assert_eq!(body["git_commit"], GIT_COMMIT);
+        assert!(
+            body["features"]
+                .as_array()
+                .unwrap()
+                .contains(&json!("http-api"))
+        );
+        assert_eq!(body["storage"]["backend"], "sqlite");
+        assert_eq!(body["storage"]["event_log"], true);
+        assert_eq!(body["message_versions"], json!([0, 1]));
+        assert_eq!(body["latest_message_version"], LATEST_MESSAGE_VERSION);
+        assert_eq!(body["rounding"], "floor");
+    }
+}
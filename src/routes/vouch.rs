@@ -5,9 +5,23 @@ use serde_json::json;
 use tide::{Request, Response, http::mime};
 
 use crate::{
-    identity::{UserAddress, idt::balance, vouch::vouch, vouch_external::vouch_external},
-    routes::State,
-    verify::{nonce::Nonce, vouch::vouch_verify},
+    flags::Flag,
+    identity::{UserAddress, error::Error, idt::balance},
+    pending_vouches::{PendingVouch, requires_confirmation},
+    routes::{
+        Consent, SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        freshness_error, signed_body, timestamp_error,
+    },
+    verify::{
+        nonce::Nonce,
+        signature::Freshness,
+        vouch::{
+            external_vouch_verify, vouch_at_verify, vouch_consent_verify, vouch_note_verify,
+            vouch_verify,
+        },
+    },
+    vouch_notes::is_valid_note,
 };
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -22,52 +36,256 @@ struct FromField {
 struct VouchRequest {
     from: FromField,
     signature: String,
-    nonce: Nonce,
+    #[serde(flatten)]
+    freshness: Freshness,
+    // required when vouching on behalf of an external server user
+    #[serde(default)]
+    server_signature: Option<String>,
+    // vouchee signature, required when the `require_vouch_consent` flag is enabled
+    #[serde(default)]
+    consent: Option<Consent>,
+    // signed client time of the vouch, stored instead of the server time
+    #[serde(default)]
+    timestamp: Option<u64>,
+    // note encrypted to the vouchee, see `vouch_notes`
+    #[serde(default)]
+    note: Option<String>,
+    // voucher signature of the note, required with `note`
+    #[serde(default)]
+    note_signature: Option<String>,
+}
+
+impl SignedRequest for VouchRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
 }
 
 pub async fn route(mut req: Request<State>) -> tide::Result {
     let vouchee = req.param("user")?.to_string();
-    let body: VouchRequest = req.body_json().await?;
+    let body: VouchRequest = signed_body(&mut req).await?;
     let voucher = body.from;
     let voucher_user = voucher.user.clone();
+    let flags = &req.state().flags;
 
-    if vouch_verify(
-        body.signature,
-        &voucher_user,
-        body.nonce,
-        vouchee.clone(),
-        &*req.state().nonce_manager,
-    )
-    .await
-    .is_err()
+    if voucher.server.is_none()
+        && voucher_user == vouchee
+        && flags.is_enabled(Flag::BanSelfVouch).await?
     {
-        return Ok(Response::builder(400)
-            .body(json!({"error": "signature verification failed"}))
-            .content_type(mime::JSON)
-            .build());
+        return Ok(ApiError::bad_request(ErrorCode::SelfVouch).into());
     }
-    if let Some(server) = voucher.server.clone() {
-        vouch_external(
-            &req.state().identity_service,
-            server,
+    if voucher.server.is_none()
+        && !req
+            .state()
+            .identity_service
+            .can_vouch(&voucher_user)
+            .await?
+    {
+        return Ok(ApiError::new(403, ErrorCode::CategoryNotAllowedToVouch).into());
+    }
+    let require_consent = flags.is_enabled(Flag::RequireVouchConsent).await?;
+    if require_consent && body.consent.is_none() {
+        return Ok(ApiError::bad_request(ErrorCode::VouchConsentRequired).into());
+    }
+    if let Some(note) = &body.note {
+        if voucher.server.is_some() {
+            return Ok(ApiError::bad_request(ErrorCode::VouchNoteRequiresLocalVoucher).into());
+        }
+        if !is_valid_note(note) {
+            return Ok(ApiError::bad_request(ErrorCode::InvalidVouchNote).into());
+        }
+    }
+
+    if let Some(server) = &voucher.server {
+        if !req
+            .state()
+            .server_storage
+            .servers()
+            .await?
+            .contains_key(server)
+        {
+            return Ok(ApiError::bad_request(ErrorCode::UnknownServer).into());
+        }
+        if req.state().config.federation.require_cross_signing
+            && !req
+                .state()
+                .server_storage
+                .cross_signatures()
+                .await?
+                .contains_key(server)
+        {
+            return Ok(ApiError::new(403, ErrorCode::ServerNotCrossSigned).into());
+        }
+        let verified = body.server_signature.as_deref().is_some_and(|signature| {
+            external_vouch_verify(
+                signature,
+                server,
+                voucher_user.clone(),
+                vouchee.clone(),
+                body.freshness.nonce,
+            )
+            .is_ok()
+        });
+        if !verified {
+            return Ok(ApiError::bad_request(ErrorCode::ServerSignatureVerificationFailed).into());
+        }
+    }
+
+    if let Some(response) = freshness_error(req.state(), &body.freshness) {
+        return Ok(response);
+    }
+    let service = &req.state().identity_service;
+    if let Some(timestamp) = body.timestamp {
+        if let Some(response) = timestamp_error(req.state(), timestamp) {
+            return Ok(response);
+        }
+        // the vouch cannot be moved back in time
+        let previous = match &voucher.server {
+            Some(server) => service
+                .external_vouches
+                .vouchers_with_time(&vouchee)
+                .await?
+                .get(server)
+                .and_then(|vouchers| vouchers.get(&voucher_user).copied()),
+            None => service
+                .vouchers_with_time(&vouchee)
+                .await?
+                .get(&voucher_user)
+                .copied(),
+        };
+        if previous.is_some_and(|previous| previous > timestamp) {
+            return Ok(ApiError::bad_request(ErrorCode::TimestampOlderThanVouch).into());
+        }
+    }
+
+    let verified = match body.timestamp {
+        Some(timestamp) => {
+            vouch_at_verify(
+                body.signature,
+                &voucher_user,
+                &body.freshness,
+                vouchee.clone(),
+                timestamp,
+                &*req.state().nonce_manager,
+            )
+            .await
+        }
+        None => {
+            vouch_verify(
+                body.signature,
+                &voucher_user,
+                &body.freshness,
+                vouchee.clone(),
+                &*req.state().nonce_manager,
+            )
+            .await
+        }
+    };
+    if verified.is_err() {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+    if let Some(note) = &body.note {
+        let verified = body.note_signature.as_deref().is_some_and(|signature| {
+            vouch_note_verify(
+                signature,
+                &voucher_user,
+                vouchee.clone(),
+                note,
+                body.freshness.nonce,
+            )
+            .is_ok()
+        });
+        if !verified {
+            return Ok(ApiError::bad_request(ErrorCode::NoteSignatureVerificationFailed).into());
+        }
+    }
+    if let Some(consent) = body.consent.filter(|_| require_consent) {
+        if let Some(response) = freshness_error(req.state(), &consent.freshness) {
+            return Ok(response);
+        }
+        if vouch_consent_verify(
+            consent.signature,
+            &vouchee,
+            &consent.freshness,
             voucher_user.clone(),
-            vouchee.clone(),
+            &*req.state().nonce_manager,
         )
-        .await?;
-    } else {
-        vouch(
-            &req.state().identity_service,
+        .await
+        .is_err()
+        {
+            return Ok(ApiError::bad_request(ErrorCode::ConsentVerificationFailed).into());
+        }
+    }
+    if let Err(e) = service.screen(&vouchee).await {
+        if let Error::AddressBlocked(_) = e {
+            return Ok(ApiError::new(403, ErrorCode::AddressBlocked).into());
+        }
+        return Err(e.into());
+    }
+    if voucher.server.is_none()
+        && requires_confirmation(
+            service,
+            &req.state().config.two_phase_vouch,
+            &voucher_user,
+            &vouchee,
+        )
+        .await?
+    {
+        let response = delay_vouch(
+            req.state(),
             voucher_user.clone(),
             vouchee.clone(),
+            body.freshness.nonce,
         )
         .await?;
+        // shown once the vouch is confirmed, listings only include notes of current vouchers
+        if let Some(note) = body.note.filter(|_| response.status().is_success()) {
+            req.state()
+                .vouch_notes
+                .set_note(voucher_user, vouchee, note)
+                .await?;
+        }
+        return Ok(response);
+    }
+    let timestamp = body.timestamp.unwrap_or_else(|| service.now());
+    if let Some(server) = voucher.server.clone() {
+        service
+            .vouch_external_with_timestamp(server, voucher_user.clone(), vouchee.clone(), timestamp)
+            .await?;
+    } else if let Err(e) = service
+        .vouch_with_timestamp(voucher_user.clone(), vouchee.clone(), timestamp)
+        .await
+    {
+        if let Error::VouchRefreshTooEarly(allowed_at) = e {
+            return Ok(ApiError::bad_request(ErrorCode::VouchRefreshTooEarly)
+                .with("allowed_at", allowed_at)
+                .into());
+        }
+        return Err(e.into());
     }
-    let voucher_balance = balance(&req.state().identity_service, &voucher_user).await?;
+    // refresh policy may keep the earlier timestamp of a local vouch
+    let timestamp = match &voucher.server {
+        Some(_) => timestamp,
+        None => service
+            .vouchers_with_time(&vouchee)
+            .await?
+            .get(&voucher_user)
+            .copied()
+            .unwrap_or(timestamp),
+    };
+    if let Some(note) = body.note {
+        req.state()
+            .vouch_notes
+            .set_note(voucher_user.clone(), vouchee.clone(), note)
+            .await?;
+    }
+    let voucher_balance = balance(service, &voucher_user).await?;
     let response: HashMap<String, serde_json::Value> = HashMap::from([
         ("from".into(), serde_json::to_value(&voucher)?),
         ("to".into(), vouchee.into()),
         ("idt".into(), voucher_balance.to_string().into()),
-        ("nonce".into(), body.nonce.into()),
+        ("nonce".into(), body.freshness.nonce.into()),
+        ("timestamp".into(), timestamp.into()),
     ]);
     let response = Response::builder(200)
         .body(json!(response))
@@ -76,17 +294,62 @@ pub async fn route(mut req: Request<State>) -> tide::Result {
     Ok(response)
 }
 
+// stores the vouch of a high balance voucher until it is confirmed
+async fn delay_vouch(
+    state: &State,
+    voucher: UserAddress,
+    vouchee: UserAddress,
+    nonce: Nonce,
+) -> tide::Result {
+    let config = &state.config.two_phase_vouch;
+    if let Some(pending) = state.pending_vouches.pending(&voucher, &vouchee).await? {
+        return Ok(ApiError::new(409, ErrorCode::VouchAlreadyPending)
+            .with("activates_at", pending.activates_at(config))
+            .into());
+    }
+    let pending = PendingVouch {
+        voucher,
+        vouchee,
+        created_at: state.identity_service.now(),
+        confirmed_at: None,
+    };
+    state.pending_vouches.add_pending(pending.clone()).await?;
+    Ok(Response::builder(202)
+        .body(json!({
+            "from": {"user": pending.voucher},
+            "to": pending.vouchee,
+            "nonce": nonce,
+            "pending": true,
+            "activates_at": pending.activates_at(config),
+            "expires_at": pending.expires_at(config),
+        }))
+        .content_type(mime::JSON)
+        .build())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
+        config::{Config, FederationSection, IdentitySection, SignaturesSection},
         identity::{
+            IdentityService, IdtAmount,
             proof::prove,
             tests::{MODERATOR, PROOF_ID, USER_A},
+            vouch::{VouchRefreshPolicy, vouchers},
+        },
+        numbers::Rational,
+        servers::storage::{CrossSignature, ServerInfo},
+        verify::{
+            expires_in, random_keypair, sign_body,
+            signature::generate,
+            vouch::{
+                external_vouch_sign, vouch_at_sign, vouch_consent_sign, vouch_note_sign, vouch_sign,
+            },
         },
-        verify::{random_keypair, vouch::vouch_sign},
     };
     use serde_json::Value;
+    use std::sync::Arc;
     use tide::http::{Request as HttpRequest, Response, Url};
 
     //TODO: add test that vouch from external server affects vouchee balance,
@@ -101,20 +364,28 @@ mod tests {
             &state.identity_service,
             user_address.clone(),
             MODERATOR.to_string(),
-            100,
+            IdtAmount::new(100),
             PROOF_ID,
         )
         .await
         .unwrap();
 
         let req_url = format!("/vouch/{user_b}");
-        let signature = vouch_sign(&private_key, user_b.to_string(), &*state.nonce_manager)
-            .await
-            .expect("Should sign successfully");
+        let signature = vouch_sign(
+            &private_key,
+            &state.server_identity.address,
+            user_b.to_string(),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign successfully");
         let body = json!({
             "from": {"user": user_address},
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
         });
 
         let mut req = HttpRequest::new(
@@ -136,7 +407,282 @@ mod tests {
         assert_eq!(body["to"], user_b);
         // user A balance
         assert_eq!(body["idt"], "100");
-        assert_eq!(body["nonce"], signature.nonce);
+        assert_eq!(body["nonce"], signature.freshness.nonce);
+    }
+
+    #[async_std::test]
+    async fn test_signature_expiry() {
+        let state = State::default();
+        let (private_key, user_address) = random_keypair();
+        let now = state.identity_service.now();
+        let max_age = state.config.signatures.max_age;
+
+        for (expires_at, error) in [
+            (now - 1, "signature expired"),
+            (now + max_age + 60, "signature expiry is too far"),
+        ] {
+            let signature = vouch_sign(
+                &private_key,
+                &state.server_identity.address,
+                "userB".to_string(),
+                expires_at,
+                &*state.nonce_manager,
+            )
+            .await
+            .expect("Should sign successfully");
+            let body = json!({
+                "from": {"user": user_address},
+                "signature": signature.signature,
+                "nonce": signature.freshness.nonce,
+                "expires_at": signature.freshness.expires_at,
+                "domain": signature.freshness.domain,
+            });
+            let mut req = HttpRequest::new(
+                tide::http::Method::Post,
+                Url::parse("http://example.com/vouch/userB").unwrap(),
+            );
+            req.set_body(body);
+            req.set_content_type(mime::JSON);
+
+            let mut server = tide::with_state(state.clone());
+            server.at("/vouch/:user").post(route);
+            let mut response: Response = server.respond(req).await.unwrap();
+            assert_eq!(response.status(), 400);
+            let body: Value = response.body_json().await.unwrap();
+            assert_eq!(body["error"], error);
+        }
+        // nonce is not consumed by rejected signatures
+        assert_eq!(
+            state.nonce_manager.next_nonce(&user_address).await.unwrap(),
+            1
+        );
+    }
+
+    async fn post_vouch(state: &State, body: Value) -> Response {
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse("http://example.com/vouch/userB").unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/vouch/:user").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    async fn signed_vouch(state: &State, private_key: &str, vouchee: &str) -> Value {
+        let signature = vouch_sign(
+            private_key,
+            &state.server_identity.address,
+            vouchee.to_string(),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign successfully");
+        json!({
+            "from": {"user": signature.signer},
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        })
+    }
+
+    #[async_std::test]
+    async fn test_self_vouch_flag() {
+        let state = State::default();
+        let (private_key, user) = random_keypair();
+        let body = signed_vouch(&state, &private_key, &user).await;
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/vouch/{user}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        state
+            .flags
+            .set_flag(Flag::BanSelfVouch, true)
+            .await
+            .unwrap();
+
+        let mut server = tide::with_state(state.clone());
+        server.at("/vouch/:user").post(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "self vouch is not allowed");
+    }
+
+    #[async_std::test]
+    async fn test_consent_flag() {
+        let state = State::default();
+        state
+            .flags
+            .set_flag(Flag::RequireVouchConsent, true)
+            .await
+            .unwrap();
+        let (voucher_key, voucher) = random_keypair();
+        let (vouchee_key, vouchee) = random_keypair();
+
+        // vouchee has not consented
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/vouch/{vouchee}")).unwrap(),
+        );
+        req.set_body(signed_vouch(&state, &voucher_key, &vouchee).await);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/vouch/:user").post(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "vouch consent is required");
+
+        let consent = vouch_consent_sign(
+            &vouchee_key,
+            &state.server_identity.address,
+            voucher.clone(),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .unwrap();
+        let mut body = signed_vouch(&state, &voucher_key, &vouchee).await;
+        body["consent"] = json!({
+            "signature": consent.signature,
+            "nonce": consent.freshness.nonce,
+            "expires_at": consent.freshness.expires_at,
+            "domain": consent.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/vouch/{vouchee}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            vouchers(&state.identity_service, &vouchee).await.unwrap(),
+            vec![voucher]
+        );
+    }
+
+    #[async_std::test]
+    async fn test_signature_domain() {
+        let state = State::default();
+        let (private_key, user_address) = random_keypair();
+
+        // signed for another server
+        let (_, other_server) = random_keypair();
+        let signature = vouch_sign(
+            &private_key,
+            &other_server,
+            "userB".to_string(),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign successfully");
+        let mut response = post_vouch(
+            &state,
+            json!({
+                "from": {"user": user_address},
+                "signature": signature.signature,
+                "nonce": signature.freshness.nonce,
+                "expires_at": signature.freshness.expires_at,
+                "domain": signature.freshness.domain,
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "signature domain mismatch");
+
+        // signed before domain separation
+        let expires_at = expires_in(60);
+        let legacy_signature = generate(&private_key, format!("vouch/userB/{expires_at}/1"))
+            .await
+            .unwrap();
+        let legacy_body = json!({
+            "from": {"user": user_address},
+            "signature": legacy_signature,
+            "nonce": 1,
+            "expires_at": expires_at,
+        });
+        let mut response = post_vouch(&state, legacy_body.clone()).await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "signature domain is missing");
+
+        let legacy_state = State {
+            config: Arc::new(Config {
+                signatures: SignaturesSection {
+                    allow_legacy: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..state
+        };
+        let response = post_vouch(&legacy_state, legacy_body).await;
+        assert_eq!(response.status(), 200);
+    }
+
+    #[async_std::test]
+    async fn test_message_versions() {
+        let state = State {
+            config: Arc::new(Config {
+                signatures: SignaturesSection {
+                    versions: vec![1],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let (private_key, user_address) = random_keypair();
+        let domain = state.server_identity.address.clone();
+        let expires_at = expires_in(60);
+
+        let signature = generate(&private_key, format!("{domain}/vouch/userB/{expires_at}/1"))
+            .await
+            .unwrap();
+        let mut response = post_vouch(
+            &state,
+            json!({
+                "from": {"user": user_address},
+                "signature": signature,
+                "nonce": 1,
+                "expires_at": expires_at,
+                "domain": domain,
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "signature version is not supported");
+
+        let signature = generate(
+            &private_key,
+            format!("v1|{domain}|vouch/userB|{expires_at}|1"),
+        )
+        .await
+        .unwrap();
+        let response = post_vouch(
+            &state,
+            json!({
+                "from": {"user": user_address},
+                "signature": signature,
+                "nonce": 1,
+                "expires_at": expires_at,
+                "domain": domain,
+                "version": 1,
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), 200);
     }
 
     #[async_std::test]
@@ -174,9 +720,15 @@ mod tests {
         let user_b = "userB";
 
         // generate valid signature
-        let mut signature = vouch_sign(&private_key, user_b.to_string(), &*state.nonce_manager)
-            .await
-            .expect("Should sign successfully");
+        let mut signature = vouch_sign(
+            &private_key,
+            &state.server_identity.address,
+            user_b.to_string(),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign successfully");
 
         // tamper with the signature
         signature.signature.push_str("bad");
@@ -184,7 +736,9 @@ mod tests {
         let body = json!({
             "from": {"user": user_address},
             "signature": signature.signature,
-            "nonce": signature.nonce,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
         });
 
         let req_url = format!("/vouch/{user_b}");
@@ -205,46 +759,446 @@ mod tests {
         );
     }
 
+    async fn external_vouch(
+        state: &State,
+        private_key: &str,
+        user_address: &UserAddress,
+        server_key: &str,
+        server_address: &UserAddress,
+        vouchee: &str,
+    ) -> Response {
+        let signature = vouch_sign(
+            private_key,
+            &state.server_identity.address,
+            vouchee.to_string(),
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign successfully");
+        let server_signature = external_vouch_sign(
+            server_key,
+            user_address.clone(),
+            vouchee.to_string(),
+            signature.freshness.nonce,
+        )
+        .await
+        .expect("Should sign successfully");
+        let body = json!({
+            "from": {"user": user_address, "server": server_address},
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+            "server_signature": server_signature,
+        });
+
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse(&format!("http://example.com/vouch/{vouchee}")).unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+
+        let mut server = tide::with_state(state.clone());
+        server.at("/vouch/:user").post(route);
+        server.respond(req).await.unwrap()
+    }
+
     #[async_std::test]
     async fn test_external_server() {
         let state = State::default();
         let (private_key, user_address) = random_keypair();
+        let (server_key, server_address) = random_keypair();
         let user_b = "userB";
         prove(
             &state.identity_service,
             user_address.clone(),
             MODERATOR.to_string(),
-            100,
+            IdtAmount::new(100),
             PROOF_ID,
         )
         .await
         .unwrap();
+        state
+            .server_storage
+            .add_server(
+                server_address.clone(),
+                ServerInfo {
+                    url: "http://server1".to_string(),
+                    scale: Rational::default(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
 
-        let req_url = format!("/vouch/{user_b}");
-        let signature = vouch_sign(&private_key, user_b.to_string(), &*state.nonce_manager)
+        let mut response = external_vouch(
+            &state,
+            &private_key,
+            &user_address,
+            &server_key,
+            &server_address,
+            user_b,
+        )
+        .await;
+
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["from"]["user"], user_address);
+        assert_eq!(body["from"]["server"], server_address);
+    }
+
+    #[async_std::test]
+    async fn test_external_server_cross_signing() {
+        let state = State {
+            config: Arc::new(Config {
+                federation: FederationSection {
+                    require_cross_signing: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let (private_key, user_address) = random_keypair();
+        let (server_key, server_address) = random_keypair();
+        state
+            .server_storage
+            .add_server(server_address.clone(), ServerInfo::default())
             .await
-            .expect("Should sign successfully");
-        let body = json!({
-            "from": {"user": user_address, "server": "server1"},
-            "signature": signature.signature,
-            "nonce": signature.nonce,
-        });
+            .unwrap();
 
-        let mut req = HttpRequest::new(
-            tide::http::Method::Post,
-            Url::parse(&format!("http://example.com{}", req_url)).unwrap(),
+        let mut response = external_vouch(
+            &state,
+            &private_key,
+            &user_address,
+            &server_key,
+            &server_address,
+            "userB",
+        )
+        .await;
+        assert_eq!(response.status(), 403);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "server is not cross-signed");
+
+        state
+            .server_storage
+            .set_cross_signature(server_address.clone(), CrossSignature::default())
+            .await
+            .unwrap();
+        let response = external_vouch(
+            &state,
+            &private_key,
+            &user_address,
+            &server_key,
+            &server_address,
+            "userB",
+        )
+        .await;
+        assert_eq!(response.status(), 200);
+    }
+
+    #[async_std::test]
+    async fn test_external_server_rejected() {
+        let state = State::default();
+        let (private_key, user_address) = random_keypair();
+        let (_, server_address) = random_keypair();
+        let (unknown_key, unknown_address) = random_keypair();
+        let user_b = "userB";
+        state
+            .server_storage
+            .add_server(
+                server_address.clone(),
+                ServerInfo {
+                    url: "http://server1".to_string(),
+                    scale: Rational::default(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let mut server = tide::with_state(state.clone());
+        server.at("/vouch/:user").post(route);
+
+        // unregistered server, missing counter-signature, counter-signature of another key
+        let cases = [
+            (
+                unknown_address.clone(),
+                Some(unknown_key.clone()),
+                "unknown server",
+            ),
+            (
+                server_address.clone(),
+                None,
+                "server signature verification failed",
+            ),
+            (
+                server_address.clone(),
+                Some(unknown_key.clone()),
+                "server signature verification failed",
+            ),
+        ];
+        for (claimed_server, signing_key, expected_error) in cases {
+            let signature = vouch_sign(
+                &private_key,
+                &state.server_identity.address,
+                user_b.to_string(),
+                expires_in(60),
+                &*state.nonce_manager,
+            )
+            .await
+            .unwrap();
+            let server_signature = match signing_key {
+                Some(key) => Some(
+                    external_vouch_sign(
+                        &key,
+                        user_address.clone(),
+                        user_b.to_string(),
+                        signature.freshness.nonce,
+                    )
+                    .await
+                    .unwrap(),
+                ),
+                None => None,
+            };
+            let body = json!({
+                "from": {"user": user_address, "server": claimed_server},
+                "signature": signature.signature,
+                "nonce": signature.freshness.nonce,
+                "expires_at": signature.freshness.expires_at,
+                "domain": signature.freshness.domain,
+                "server_signature": server_signature,
+            });
+            let mut req = HttpRequest::new(
+                tide::http::Method::Post,
+                Url::parse(&format!("http://example.com/vouch/{user_b}")).unwrap(),
+            );
+            req.set_body(body);
+            req.set_content_type(mime::JSON);
+            let mut response: Response = server.respond(req).await.unwrap();
+            assert_eq!(response.status(), 400);
+            let body: Value = response.body_json().await.unwrap();
+            assert_eq!(body["error"], expected_error);
+        }
+        assert!(
+            state
+                .identity_service
+                .vouchers_external(&user_b.to_string())
+                .await
+                .unwrap()
+                .is_empty()
         );
-        req.set_body(body);
-        req.set_content_type(mime::JSON);
+    }
 
-        let mut server = tide::with_state(state);
+    #[async_std::test]
+    async fn test_refresh_too_early() {
+        let state = State {
+            identity_service: IdentityService {
+                config: IdentitySection {
+                    vouch_refresh: VouchRefreshPolicy::RejectWithin { days: 1 },
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (private_key, user_address) = random_keypair();
+        let user_b = "userB";
+        let mut server = tide::with_state(state.clone());
         server.at("/vouch/:user").post(route);
 
-        let mut response: Response = server.respond(req).await.unwrap();
+        for expected_status in [200, 400] {
+            let signature = vouch_sign(
+                &private_key,
+                &state.server_identity.address,
+                user_b.to_string(),
+                expires_in(60),
+                &*state.nonce_manager,
+            )
+            .await
+            .unwrap();
+            let body = json!({
+                "from": {"user": user_address},
+                "signature": signature.signature,
+                "nonce": signature.freshness.nonce,
+                "expires_at": signature.freshness.expires_at,
+                "domain": signature.freshness.domain,
+            });
+            let mut req = HttpRequest::new(
+                tide::http::Method::Post,
+                Url::parse(&format!("http://example.com/vouch/{user_b}")).unwrap(),
+            );
+            req.set_body(body);
+            req.set_content_type(mime::JSON);
+            let mut response: Response = server.respond(req).await.unwrap();
+            assert_eq!(response.status(), expected_status);
+            if expected_status == 400 {
+                let body: Value = response.body_json().await.unwrap();
+                assert_eq!(body["error"], "vouch refresh is too early");
+                assert!(body["allowed_at"].as_u64().is_some());
+            }
+        }
+    }
 
+    #[async_std::test]
+    async fn test_client_timestamp() {
+        let state = State::default();
+        let (private_key, user_address) = random_keypair();
+        let user_b = "userB";
+        let mut server = tide::with_state(state.clone());
+        server.at("/vouch/:user").post(route);
+        let now = state.identity_service.now();
+
+        // (signed timestamp, sent timestamp, expected error)
+        let cases = [
+            (now - 100, now - 100, None),
+            (now - 50, now - 49, Some("signature verification failed")),
+            (now - 1000, now - 1000, Some("timestamp is out of bounds")),
+            (now + 1000, now + 1000, Some("timestamp is out of bounds")),
+            (
+                now - 200,
+                now - 200,
+                Some("timestamp is older than the existing vouch"),
+            ),
+        ];
+        for (signed, sent, expected_error) in cases {
+            let signature = vouch_at_sign(
+                &private_key,
+                &state.server_identity.address,
+                user_b.to_string(),
+                signed,
+                expires_in(60),
+                &*state.nonce_manager,
+            )
+            .await
+            .unwrap();
+            let body = json!({
+                "from": {"user": user_address},
+                "signature": signature.signature,
+                "nonce": signature.freshness.nonce,
+                "expires_at": signature.freshness.expires_at,
+                "domain": signature.freshness.domain,
+                "timestamp": sent,
+            });
+            let mut req = HttpRequest::new(
+                tide::http::Method::Post,
+                Url::parse(&format!("http://example.com/vouch/{user_b}")).unwrap(),
+            );
+            req.set_body(body);
+            req.set_content_type(mime::JSON);
+            let mut response: Response = server.respond(req).await.unwrap();
+            let body: Value = response.body_json().await.unwrap();
+            match expected_error {
+                None => {
+                    assert_eq!(response.status(), 200);
+                    assert_eq!(body["timestamp"], now - 100);
+                }
+                Some(error) => {
+                    assert_eq!(response.status(), 400);
+                    assert_eq!(body["error"], error);
+                }
+            }
+        }
+        assert_eq!(
+            vouchers(&state.identity_service, &user_b.to_string())
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            state
+                .identity_service
+                .vouchers_with_time(&user_b.to_string())
+                .await
+                .unwrap()[&user_address],
+            now - 100
+        );
+    }
+
+    #[async_std::test]
+    async fn test_canonical_body() {
+        let state = State::default();
+        let (private_key, user_address) = random_keypair();
+        let sign = |body: Value| {
+            let state = state.clone();
+            let private_key = private_key.clone();
+            async move {
+                sign_body(
+                    &private_key,
+                    &state.server_identity.address,
+                    "vouch/userB",
+                    body,
+                    expires_in(60),
+                    &*state.nonce_manager,
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        let body = sign(json!({"from": {"user": user_address}, "memo": "hello"})).await;
+        assert_eq!(body["canonical"], true);
+        let response = post_vouch(&state, body).await;
         assert_eq!(response.status(), 200);
-        let body: Value = response.body_json().await.unwrap();
-        assert_eq!(body["from"]["user"], user_address);
-        assert_eq!(body["from"]["server"], "server1");
+
+        // every field of the body is covered by the signature
+        let mut body = sign(json!({"from": {"user": user_address}, "memo": "hello"})).await;
+        body["memo"] = "changed".into();
+        let response = post_vouch(&state, body).await;
+        assert_eq!(response.status(), 400);
+
+        // a canonical signature does not verify as a prefix-only one
+        let mut body = sign(json!({"from": {"user": user_address}})).await;
+        body.as_object_mut().unwrap().remove("canonical");
+        let response = post_vouch(&state, body).await;
+        assert_eq!(response.status(), 400);
+    }
+
+    #[async_std::test]
+    async fn test_note() {
+        let state = State::default();
+        let (private_key, user_address) = random_keypair();
+        let note = "0x04abcd";
+
+        let mut body = signed_vouch(&state, &private_key, "userB").await;
+        let nonce = body["nonce"].as_u64().unwrap();
+        body["note"] = note.into();
+        body["note_signature"] =
+            vouch_note_sign(&private_key, "userB".to_string(), "0x04ff", nonce)
+                .await
+                .unwrap()
+                .into();
+        let response = post_vouch(&state, body).await;
+        assert_eq!(response.status(), 400);
+
+        let mut body = signed_vouch(&state, &private_key, "userB").await;
+        body["note"] = "hello".into();
+        let response = post_vouch(&state, body).await;
+        assert_eq!(response.status(), 400);
+
+        let mut body = signed_vouch(&state, &private_key, "userB").await;
+        let nonce = body["nonce"].as_u64().unwrap();
+        body["note"] = note.into();
+        body["note_signature"] = vouch_note_sign(&private_key, "userB".to_string(), note, nonce)
+            .await
+            .unwrap()
+            .into();
+        let response = post_vouch(&state, body).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            state.vouch_notes.notes(&"userB".to_string()).await.unwrap(),
+            HashMap::from([(user_address.clone(), note.to_string())])
+        );
+
+        // vouching again without a note keeps it
+        let body = signed_vouch(&state, &private_key, "userB").await;
+        let response = post_vouch(&state, body).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            state.vouch_notes.notes(&"userB".to_string()).await.unwrap()[&user_address],
+            note
+        );
     }
 }
@@ -0,0 +1,125 @@
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::{UserAddress, vouch::vouchers},
+    routes::{
+        SignedRequest, State,
+        error::{ApiError, ErrorCode},
+        freshness_error, signed_body,
+    },
+    verify::{signature::Freshness, vouch::vouch_notes_verify},
+};
+
+#[derive(Deserialize)]
+struct VouchNotesRequest {
+    from: UserAddress,
+    signature: String,
+    #[serde(flatten)]
+    freshness: Freshness,
+}
+
+impl SignedRequest for VouchNotesRequest {
+    fn freshness_mut(&mut self) -> &mut Freshness {
+        &mut self.freshness
+    }
+}
+
+// notes of the current vouchers of the signer, only the vouchee can list them
+pub async fn route(mut req: Request<State>) -> tide::Result {
+    let body: VouchNotesRequest = signed_body(&mut req).await?;
+    let state = req.state();
+    if let Some(response) = freshness_error(state, &body.freshness) {
+        return Ok(response);
+    }
+    if vouch_notes_verify(
+        body.signature,
+        &body.from,
+        &body.freshness,
+        &*state.nonce_manager,
+    )
+    .await
+    .is_err()
+    {
+        return Ok(ApiError::bad_request(ErrorCode::SignatureVerificationFailed).into());
+    }
+
+    let vouchers = vouchers(&state.identity_service, &body.from).await?;
+    let mut notes = state.vouch_notes.notes(&body.from).await?;
+    notes.retain(|voucher, _| vouchers.contains(voucher));
+    Ok(Response::builder(200)
+        .body(json!({"user": body.from, "notes": notes}))
+        .content_type(mime::JSON)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identity::{tests::USER_A, vouch::vouch},
+        verify::{expires_in, random_keypair, vouch::vouch_notes_sign},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    async fn read_notes(state: &State, private_key: &str, from: Option<&str>) -> Response {
+        let signature = vouch_notes_sign(
+            private_key,
+            &state.server_identity.address,
+            expires_in(60),
+            &*state.nonce_manager,
+        )
+        .await
+        .expect("Should sign");
+        let body = json!({
+            "from": from.unwrap_or(&signature.signer),
+            "signature": signature.signature,
+            "nonce": signature.freshness.nonce,
+            "expires_at": signature.freshness.expires_at,
+            "domain": signature.freshness.domain,
+        });
+        let mut req = HttpRequest::new(
+            tide::http::Method::Post,
+            Url::parse("http://example.com/vouch_notes").unwrap(),
+        );
+        req.set_body(body);
+        req.set_content_type(mime::JSON);
+        let mut server = tide::with_state(state.clone());
+        server.at("/vouch_notes").post(route);
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        let (private_key, user) = random_keypair();
+        vouch(&state.identity_service, USER_A.to_string(), user.clone())
+            .await
+            .unwrap();
+        let notes = &state.vouch_notes;
+        notes
+            .set_note(USER_A.to_string(), user.clone(), "0x01".into())
+            .await
+            .unwrap();
+        // the vouch of `userC` was forgotten
+        notes
+            .set_note("userC".into(), user.clone(), "0x02".into())
+            .await
+            .unwrap();
+
+        let mut response = read_notes(&state, &private_key, None).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["user"], user);
+        assert_eq!(body["notes"], json!({USER_A: "0x01"}));
+
+        // another user cannot read them
+        let (other_key, _) = random_keypair();
+        let mut response = read_notes(&state, &other_key, Some(&user)).await;
+        assert_eq!(response.status(), 400);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["error"], "signature verification failed");
+    }
+}
@@ -0,0 +1,75 @@
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::routes::State;
+
+// lists users the user vouches for, with the petnames the user gave them
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?.to_string();
+    let state = req.state();
+    let mut vouchees: Vec<_> = state
+        .identity_service
+        .vouchees_with_time(&user)
+        .await?
+        .into_keys()
+        .collect();
+    vouchees.sort();
+    let mut petnames = state.petnames.petnames(&user).await?;
+    let vouchees: Vec<_> = vouchees
+        .into_iter()
+        .map(|vouchee| {
+            let petname = petnames.remove(&vouchee);
+            json!({"user": vouchee, "petname": petname})
+        })
+        .collect();
+    let response = Response::builder(200)
+        .body(json!({"user": user, "vouchees": vouchees}))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{tests::USER_A, vouch::vouch};
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        for vouchee in ["userC", "userB"] {
+            vouch(
+                &state.identity_service,
+                USER_A.to_string(),
+                vouchee.to_string(),
+            )
+            .await
+            .unwrap();
+        }
+        state
+            .petnames
+            .set_petname(USER_A.to_string(), "userB".to_string(), "Bob".to_string())
+            .await
+            .unwrap();
+
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/vouchees/{USER_A}")).unwrap(),
+        );
+        let mut server = tide::with_state(state);
+        server.at("/vouchees/:user").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["user"], USER_A);
+        assert_eq!(
+            body["vouchees"],
+            json!([
+                {"user": "userB", "petname": "Bob"},
+                {"user": "userC", "petname": null},
+            ])
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{
+    identity::vouch::vouchers,
+    routes::{State, cache::Validators, claim_home::home_claim_json},
+};
+
+// lists local vouchers of the user, used by other servers to resolve users
+pub async fn route(req: Request<State>) -> tide::Result {
+    let user = req.param("user")?;
+    let state = req.state();
+    let home = home_claim_json(state, &user.to_string()).await?;
+    let mut validators = Validators::user(state.changes.user_version(&user.to_string()).await?);
+    // claims are not recorded in the change log
+    if let Some(home) = &home {
+        validators = validators.variant(&format!("home{}", home["claimed_at"]));
+    }
+    if let Some(response) = validators.not_modified(&req, &state.config.cache)? {
+        return Ok(response);
+    }
+    let vouchers = vouchers(&state.identity_service, &user.to_string()).await?;
+    let response: HashMap<String, serde_json::Value> = HashMap::from([
+        ("user".into(), user.into()),
+        ("vouchers".into(), vouchers.into()),
+        ("home".into(), home.into()),
+    ]);
+    let mut response = Response::builder(200)
+        .body(json!(response))
+        .content_type(mime::JSON)
+        .build();
+    validators.apply(&state.config.cache, &mut response);
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        federation::storage::HomeClaim,
+        identity::{tests::USER_A, vouch::vouch},
+    };
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State::default();
+        let user_b = "userB";
+        vouch(
+            &state.identity_service,
+            USER_A.to_string(),
+            user_b.to_string(),
+        )
+        .await
+        .unwrap();
+
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse(&format!("http://example.com/vouchers/{user_b}")).unwrap(),
+        );
+        let mut server = tide::with_state(state.clone());
+        server.at("/vouchers/:user").get(route);
+        let mut response: Response = server.respond(req.clone()).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["user"], user_b);
+        assert_eq!(body["vouchers"], json!([USER_A]));
+        assert!(body["home"].is_null());
+
+        state
+            .home_storage
+            .set_claim(
+                user_b.to_string(),
+                HomeClaim {
+                    claimed_at: 5,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let mut response: Response = server.respond(req).await.unwrap();
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["home"]["server"], state.server_identity.address);
+    }
+}
@@ -0,0 +1,60 @@
+use serde_json::json;
+use tide::{Request, Response, http::mime};
+
+use crate::{config::LATEST_MESSAGE_VERSION, routes::State};
+
+// parameters clients need before signing requests for this server
+pub async fn route(req: Request<State>) -> tide::Result {
+    let state = req.state();
+    let signatures = &state.config.signatures;
+    let response = Response::builder(200)
+        .body(json!({
+            "address": state.server_identity.address,
+            "message_versions": signatures.supported_versions(),
+            "latest_message_version": LATEST_MESSAGE_VERSION,
+            "max_age": signatures.max_age,
+            "allow_legacy": signatures.allow_legacy,
+            "allow_unbound_admin": signatures.allow_unbound_admin,
+        }))
+        .content_type(mime::JSON)
+        .build();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::config::{Config, SignaturesSection};
+    use serde_json::Value;
+    use tide::http::{Request as HttpRequest, Response, Url};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let state = State {
+            config: Arc::new(Config {
+                signatures: SignaturesSection {
+                    // unknown versions are not advertised
+                    versions: vec![7, 1, 1],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let address = state.server_identity.address.clone();
+        let req = HttpRequest::new(
+            tide::http::Method::Get,
+            Url::parse("http://example.com/.well-known/identity-server").unwrap(),
+        );
+        let mut server = tide::with_state(state);
+        server.at("/.well-known/identity-server").get(route);
+        let mut response: Response = server.respond(req).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.body_json().await.unwrap();
+        assert_eq!(body["address"], address);
+        assert_eq!(body["message_versions"], json!([1]));
+        assert_eq!(body["latest_message_version"], LATEST_MESSAGE_VERSION);
+    }
+}
@@ -0,0 +1,86 @@
+// Trust score in range [0, 100] built from local and federated data:
+//
+//   weighted = local_weight * local + external_weight * external - penalty_weight * penalty
+//   score = 100 * clamp(weighted / full_score_idt, 0, 1)
+//
+// local is the IDT balance on this server (penalties are already subtracted from it),
+// external is the best scaled balance reported by registered servers and penalty is
+// the current penalty of the user. Extra penalty weight makes punished users score lower
+// than users with the same balance and a clean history.
+
+use serde::Serialize;
+
+use crate::{config::ScoringSection, identity::IdtAmount};
+
+pub mod pagerank;
+pub mod strategy;
+
+pub const MAX_SCORE: f64 = 100.0;
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TrustInputs {
+    pub local: IdtAmount,
+    pub external: IdtAmount,
+    pub penalty: IdtAmount,
+}
+
+pub fn trust_score(inputs: &TrustInputs, weights: &ScoringSection) -> f64 {
+    if weights.full_score_idt.is_zero() {
+        return 0.0;
+    }
+    let weighted = weights.local_weight * inputs.local.as_f64()
+        + weights.external_weight * inputs.external.as_f64()
+        - weights.penalty_weight * inputs.penalty.as_f64();
+    let ratio = (weighted / weights.full_score_idt.as_f64()).clamp(0.0, 1.0);
+    MAX_SCORE * ratio
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weights() -> ScoringSection {
+        ScoringSection {
+            strategy: Default::default(),
+            pagerank: Default::default(),
+            local_weight: 1.0,
+            external_weight: 0.5,
+            penalty_weight: 0.5,
+            full_score_idt: IdtAmount::new(1000),
+        }
+    }
+
+    #[test]
+    fn test_basic() {
+        let inputs = TrustInputs {
+            local: IdtAmount::new(400),
+            external: IdtAmount::new(200),
+            penalty: IdtAmount::new(0),
+        };
+        assert_eq!(trust_score(&inputs, &weights()), 50.0);
+        let inputs = TrustInputs {
+            penalty: IdtAmount::new(200),
+            ..inputs
+        };
+        assert_eq!(trust_score(&inputs, &weights()), 40.0);
+    }
+
+    #[test]
+    fn test_bounds() {
+        let inputs = TrustInputs {
+            local: IdtAmount::new(5000),
+            ..Default::default()
+        };
+        assert_eq!(trust_score(&inputs, &weights()), MAX_SCORE);
+        let inputs = TrustInputs {
+            penalty: IdtAmount::new(5000),
+            ..Default::default()
+        };
+        assert_eq!(trust_score(&inputs, &weights()), 0.0);
+        let zero = ScoringSection {
+            full_score_idt: IdtAmount::new(0),
+            ..weights()
+        };
+        assert_eq!(trust_score(&TrustInputs::default(), &zero), 0.0);
+    }
+}
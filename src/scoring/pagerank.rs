@@ -0,0 +1,268 @@
+// EigenTrust-like PageRank over the vouch graph.
+//
+// Proven users (moderator proof or genesis) form the pre-trust vector, weighted by their
+// proven balance. On every iteration a user passes `damping` of its rank equally to its
+// vouchees and the rest of the rank teleports back to proven users:
+//
+//   t' = damping * C^T t + (1 - damping) * p
+//
+// Users without vouchees give their whole rank back to the pre-trust vector. Ranks sum up
+// to 1, so the balance of a user is its rank multiplied by the total proven IDT. Penalties
+// are subtracted when the balance is read, vouch decay is not applied.
+
+use std::{collections::HashMap, time::Duration};
+
+use async_trait::async_trait;
+
+use crate::{
+    config::PageRankSection,
+    identity::{
+        IdentityService, IdtAmount, UserAddress, error::Error, idt::proven_balance, punish::penalty,
+    },
+    scoring::strategy::ScoringStrategy,
+};
+
+pub fn page_rank(
+    edges: &[(UserAddress, UserAddress)],
+    pre_trust: &HashMap<UserAddress, f64>,
+    params: &PageRankSection,
+) -> HashMap<UserAddress, f64> {
+    // index users in sorted order so results do not depend on map ordering
+    let mut users: Vec<&UserAddress> = edges
+        .iter()
+        .flat_map(|(from, to)| [from, to])
+        .chain(pre_trust.keys())
+        .collect();
+    users.sort();
+    users.dedup();
+    let index: HashMap<&UserAddress, usize> =
+        users.iter().enumerate().map(|(i, u)| (*u, i)).collect();
+    let n = users.len();
+
+    let total_pre_trust: f64 = pre_trust.values().filter(|v| **v > 0.0).sum();
+    if n == 0 || total_pre_trust <= 0.0 {
+        return users.into_iter().map(|u| (u.clone(), 0.0)).collect();
+    }
+    let mut p = vec![0.0; n];
+    for (user, value) in pre_trust {
+        if *value > 0.0 {
+            p[index[user]] = value / total_pre_trust;
+        }
+    }
+
+    let mut outgoing: Vec<Vec<usize>> = vec![vec![]; n];
+    for (from, to) in edges {
+        // self vouches do not move any trust
+        if from != to {
+            outgoing[index[from]].push(index[to]);
+        }
+    }
+    for targets in &mut outgoing {
+        targets.sort_unstable();
+        targets.dedup();
+    }
+
+    let damping = params.damping.clamp(0.0, 1.0);
+    let mut ranks = p.clone();
+    for _ in 0..params.max_iterations {
+        let mut next: Vec<f64> = p.iter().map(|v| (1.0 - damping) * v).collect();
+        let mut dangling = 0.0;
+        for (i, targets) in outgoing.iter().enumerate() {
+            if targets.is_empty() {
+                dangling += ranks[i];
+                continue;
+            }
+            let share = damping * ranks[i] / targets.len() as f64;
+            for &t in targets {
+                next[t] += share;
+            }
+        }
+        for (i, value) in next.iter_mut().enumerate() {
+            *value += damping * dangling * p[i];
+        }
+        let change: f64 = next.iter().zip(&ranks).map(|(a, b)| (a - b).abs()).sum();
+        ranks = next;
+        if change < params.tolerance {
+            break;
+        }
+    }
+    users
+        .into_iter()
+        .zip(ranks)
+        .map(|(u, r)| (u.clone(), r))
+        .collect()
+}
+
+// recomputes balances of all users and stores them in the materialized balances table
+pub async fn recompute(
+    service: &IdentityService,
+    params: &PageRankSection,
+) -> Result<HashMap<UserAddress, IdtAmount>, Error> {
+    let edges: Vec<(UserAddress, UserAddress)> = service
+        .vouches
+        .all_vouches()
+        .await?
+        .into_iter()
+        .map(|(from, to, _)| (from, to))
+        .collect();
+    let mut pre_trust = HashMap::new();
+    let mut total = IdtAmount::ZERO;
+    for user in service.proofs.proven_users().await? {
+        let proven = proven_balance(service, &user).await?;
+        total = total.saturating_add(proven);
+        pre_trust.insert(user, proven.as_f64());
+    }
+    let balances: HashMap<UserAddress, IdtAmount> = page_rank(&edges, &pre_trust, params)
+        .into_iter()
+        .map(|(user, rank)| {
+            (
+                user,
+                IdtAmount::from_f64(rank * total.as_f64()).unwrap_or_default(),
+            )
+        })
+        .collect();
+    service
+        .set_materialized_balances(balances.clone(), service.now())
+        .await?;
+    Ok(balances)
+}
+
+// keeps materialized balances up to date, never returns
+pub async fn recompute_periodically(service: IdentityService, params: PageRankSection) {
+    let interval = Duration::from_secs(params.recompute_interval.max(1));
+    loop {
+        match recompute(&service, &params).await {
+            Ok(balances) => log::info!("Recomputed PageRank balances of {} users", balances.len()),
+            Err(e) => log::error!("Failed to recompute PageRank balances: {:?}", e),
+        }
+        async_std::task::sleep(interval).await;
+    }
+}
+
+pub struct PageRankStrategy {
+    pub params: PageRankSection,
+}
+
+#[async_trait]
+impl ScoringStrategy for PageRankStrategy {
+    async fn balance(
+        &self,
+        service: &IdentityService,
+        user: &UserAddress,
+    ) -> Result<IdtAmount, Error> {
+        // compute on demand until the first batch recomputation
+        if service.balances_computed_at().await?.is_none() {
+            recompute(service, &self.params).await?;
+        }
+        let balance = service
+            .materialized_balance(user)
+            .await?
+            .unwrap_or_default();
+        let debit = service.debit(user).await?;
+        Ok(balance.saturating_sub(penalty(service, user).await? + debit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::identity::{
+        idt::balance,
+        proof::prove,
+        tests::{MODERATOR, PROOF_ID},
+        vouch::vouch,
+    };
+
+    fn edges(list: &[(&str, &str)]) -> Vec<(UserAddress, UserAddress)> {
+        list.iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_ranks_sum_to_one() {
+        let edges = edges(&[("a", "b"), ("b", "c"), ("c", "a"), ("a", "d")]);
+        let pre_trust = HashMap::from([("a".to_string(), 1.0)]);
+        let ranks = page_rank(&edges, &pre_trust, &PageRankSection::default());
+        let total: f64 = ranks.values().sum();
+        assert!((total - 1.0).abs() < 1e-6);
+        // vouched users receive trust, proven user keeps the most
+        assert!(ranks["a"] > ranks["b"]);
+        assert!(ranks["b"] > 0.0);
+        assert!(ranks["d"] > 0.0);
+    }
+
+    #[test]
+    fn test_no_pre_trust() {
+        let edges = edges(&[("a", "b")]);
+        let ranks = page_rank(&edges, &HashMap::new(), &PageRankSection::default());
+        assert!(ranks.values().all(|r| *r == 0.0));
+    }
+
+    #[test]
+    fn test_sybil_cycle_gets_nothing() {
+        // unproven users vouching for each other cannot create trust
+        let edges = edges(&[("x", "y"), ("y", "x"), ("a", "b")]);
+        let pre_trust = HashMap::from([("a".to_string(), 1.0)]);
+        let ranks = page_rank(&edges, &pre_trust, &PageRankSection::default());
+        assert_eq!(ranks["x"], 0.0);
+        assert_eq!(ranks["y"], 0.0);
+    }
+
+    #[test]
+    fn test_no_damping() {
+        let edges = edges(&[("a", "b")]);
+        let pre_trust = HashMap::from([("a".to_string(), 1.0)]);
+        let params = PageRankSection {
+            damping: 0.0,
+            ..Default::default()
+        };
+        let ranks = page_rank(&edges, &pre_trust, &params);
+        assert_eq!(ranks["a"], 1.0);
+        assert_eq!(ranks["b"], 0.0);
+    }
+
+    #[async_std::test]
+    async fn test_strategy() {
+        let service = IdentityService {
+            strategy: Arc::new(PageRankStrategy {
+                params: PageRankSection::default(),
+            }),
+            ..Default::default()
+        };
+        prove(
+            &service,
+            "a".to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, "a".to_string(), "b".to_string())
+            .await
+            .unwrap();
+        let a = balance(&service, &"a".to_string()).await.unwrap();
+        let b = balance(&service, &"b".to_string()).await.unwrap();
+        assert!(b > IdtAmount::new(0));
+        assert!(a > b);
+        // total proven IDT is redistributed, rounding may lose a unit
+        assert!((999..=1001).contains(&(a + b).units()));
+        assert!(service.balances_computed_at().await.unwrap().is_some());
+
+        // new vouches are visible only after recomputation
+        vouch(&service, "a".to_string(), "c".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            balance(&service, &"c".to_string()).await.unwrap(),
+            IdtAmount::new(0)
+        );
+        recompute(&service, &PageRankSection::default())
+            .await
+            .unwrap();
+        assert!(balance(&service, &"c".to_string()).await.unwrap() > IdtAmount::new(0));
+    }
+}
@@ -0,0 +1,152 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::ScoringSection,
+    identity::{
+        IdentityService, IdtAmount, UserAddress,
+        error::Error,
+        idt::{proven_balance, vouch_tree_balance},
+        punish::penalty,
+    },
+    scoring::pagerank::PageRankStrategy,
+};
+
+// algorithm used to compute IDT balance of a user
+#[async_trait]
+pub trait ScoringStrategy: Send + Sync {
+    async fn balance(
+        &self,
+        service: &IdentityService,
+        user: &UserAddress,
+    ) -> Result<IdtAmount, Error>;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StrategyKind {
+    #[default]
+    VouchTree,
+    ProofOnly,
+    PageRank,
+}
+
+// proven balance plus the weighted balances of the top vouchers, minus penalties
+#[derive(Default)]
+pub struct VouchTreeStrategy;
+
+#[async_trait]
+impl ScoringStrategy for VouchTreeStrategy {
+    async fn balance(
+        &self,
+        service: &IdentityService,
+        user: &UserAddress,
+    ) -> Result<IdtAmount, Error> {
+        vouch_tree_balance(service, user).await
+    }
+}
+
+// only the user's own proof counts, vouches are ignored
+#[derive(Default)]
+pub struct ProofOnlyStrategy;
+
+#[async_trait]
+impl ScoringStrategy for ProofOnlyStrategy {
+    async fn balance(
+        &self,
+        service: &IdentityService,
+        user: &UserAddress,
+    ) -> Result<IdtAmount, Error> {
+        let proven = proven_balance(service, user).await?;
+        let debit = service.debit(user).await?;
+        Ok(proven.saturating_sub(penalty(service, user).await? + debit))
+    }
+}
+
+pub fn strategy(config: &ScoringSection) -> Arc<dyn ScoringStrategy> {
+    match config.strategy {
+        StrategyKind::VouchTree => Arc::new(VouchTreeStrategy),
+        StrategyKind::ProofOnly => Arc::new(ProofOnlyStrategy),
+        StrategyKind::PageRank => Arc::new(PageRankStrategy {
+            params: config.pagerank.clone(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{
+        idt::balance,
+        proof::prove,
+        punish::punish,
+        tests::{MODERATOR, PROOF_ID, USER_A},
+        vouch::vouch,
+    };
+
+    async fn setup(kind: StrategyKind) -> IdentityService {
+        let config = ScoringSection {
+            strategy: kind,
+            ..Default::default()
+        };
+        let service = IdentityService {
+            strategy: strategy(&config),
+            ..Default::default()
+        };
+        prove(
+            &service,
+            USER_A.to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        prove(
+            &service,
+            "userB".to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(100),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, USER_A.to_string(), "userB".to_string())
+            .await
+            .unwrap();
+        service
+    }
+
+    #[async_std::test]
+    async fn test_vouch_tree() {
+        let service = setup(StrategyKind::VouchTree).await;
+        assert_eq!(
+            balance(&service, &"userB".to_string()).await.unwrap(),
+            IdtAmount::new(200)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_proof_only() {
+        let service = setup(StrategyKind::ProofOnly).await;
+        assert_eq!(
+            balance(&service, &"userB".to_string()).await.unwrap(),
+            IdtAmount::new(100)
+        );
+        punish(
+            &service,
+            "userB".to_string(),
+            MODERATOR.to_string(),
+            IdtAmount::new(30),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            balance(&service, &"userB".to_string()).await.unwrap(),
+            IdtAmount::new(70)
+        );
+    }
+}
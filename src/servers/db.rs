@@ -1,50 +1,90 @@
 use std::collections::HashMap;
 
 use async_trait::async_trait;
-use sqlx::{AnyPool, Row, any::AnyPoolOptions};
+use sqlx::Row;
 
 use crate::{
     identity::UserAddress,
     numbers::Rational,
+    pools::{self, MeteredPool},
     servers::{
         error::Error,
-        storage::{ServerInfo, ServerStorage},
+        storage::{CrossSignature, ScaleSchedule, ServerInfo, ServerStorage},
     },
 };
 
+// servers without an attestation row were registered before attestations were tracked,
+// servers without a schedule row have the static scale
+pub const SELECT_SERVERS: &str = "SELECT s.address, s.url, s.scale_numerator, s.scale_denominator, COALESCE(a.last_attested, 0), c.schedule, COALESCE(c.registered_at, 0) FROM servers s LEFT JOIN server_attestations a ON s.address = a.address LEFT JOIN server_schedules c ON s.address = c.address";
+
 pub struct DatabaseServerStorage {
-    pool: AnyPool,
+    pool: MeteredPool,
 }
 
 impl DatabaseServerStorage {
     pub async fn new(url: &str) -> Result<Self, Error> {
-        sqlx::any::install_default_drivers();
-        let pool = AnyPoolOptions::new()
-            .max_connections(1)
-            .connect(url)
-            .await?;
+        let pool = pools::connect("servers", url).await?;
         sqlx::query("CREATE TABLE IF NOT EXISTS servers (address TEXT PRIMARY KEY, url TEXT NOT NULL, scale_numerator INTEGER NOT NULL, scale_denominator INTEGER NOT NULL)")
             .execute(&pool)
             .await?;
+        // kept apart from `servers` so tables created before attestations keep working
+        sqlx::query("CREATE TABLE IF NOT EXISTS server_attestations (address TEXT PRIMARY KEY, last_attested INTEGER NOT NULL)")
+            .execute(&pool)
+            .await?;
+        sqlx::query("CREATE TABLE IF NOT EXISTS server_schedules (address TEXT PRIMARY KEY, schedule TEXT NOT NULL, registered_at INTEGER NOT NULL)")
+            .execute(&pool)
+            .await?;
+        sqlx::query("CREATE TABLE IF NOT EXISTS cross_signatures (address TEXT PRIMARY KEY, data TEXT NOT NULL)")
+            .execute(&pool)
+            .await?;
         Ok(Self { pool })
     }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
 }
 
 #[async_trait]
 impl ServerStorage for DatabaseServerStorage {
     async fn add_server(&self, address: UserAddress, info: ServerInfo) -> Result<(), Error> {
         sqlx::query("REPLACE INTO servers (address, url, scale_numerator, scale_denominator) VALUES (?, ?, ?, ?)")
-            .bind(address)
+            .bind(address.clone())
             .bind(info.url)
             .bind(info.scale.numerator() as i32)
             .bind(info.scale.denominator() as i32)
             .execute(&self.pool)
             .await?;
+        sqlx::query("REPLACE INTO server_attestations (address, last_attested) VALUES (?, ?)")
+            .bind(address.clone())
+            .bind(info.last_attested as i64)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            "REPLACE INTO server_schedules (address, schedule, registered_at) VALUES (?, ?, ?)",
+        )
+        .bind(address)
+        .bind(serde_json::to_string(&info.schedule)?)
+        .bind(info.registered_at as i64)
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
 
     async fn remove_server(&self, address: UserAddress) -> Result<(), Error> {
         sqlx::query("DELETE FROM servers WHERE address = ?")
+            .bind(address.clone())
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DELETE FROM server_attestations WHERE address = ?")
+            .bind(address.clone())
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DELETE FROM server_schedules WHERE address = ?")
+            .bind(address.clone())
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DELETE FROM cross_signatures WHERE address = ?")
             .bind(address)
             .execute(&self.pool)
             .await?;
@@ -52,20 +92,58 @@ impl ServerStorage for DatabaseServerStorage {
     }
 
     async fn servers(&self) -> Result<HashMap<UserAddress, ServerInfo>, Error> {
-        let rows =
-            sqlx::query("SELECT address, url, scale_numerator, scale_denominator FROM servers")
-                .fetch_all(&self.pool)
-                .await?;
-        Ok(rows
-            .into_iter()
-            .map(|r| {
-                let key = r.get::<String, _>(0);
-                let url = r.get::<String, _>(1);
-                let scale = Rational::new(r.get::<i32, _>(2) as u32, r.get::<i32, _>(3) as u32)
-                    .expect("Scale factor denominator must not be zero");
-                (key, ServerInfo { url, scale })
-            })
-            .collect())
+        let rows = sqlx::query(SELECT_SERVERS).fetch_all(&self.pool).await?;
+        let mut servers = HashMap::new();
+        for r in rows {
+            let key = r.get::<String, _>(0);
+            let url = r.get::<String, _>(1);
+            let scale = Rational::new(r.get::<i32, _>(2) as u32, r.get::<i32, _>(3) as u32)
+                .expect("Scale factor denominator must not be zero");
+            let last_attested = r.get::<i64, _>(4) as u64;
+            let schedule = match r.get::<Option<String>, _>(5) {
+                Some(schedule) => serde_json::from_str(&schedule)?,
+                None => ScaleSchedule::Static,
+            };
+            let registered_at = r.get::<i64, _>(6) as u64;
+            servers.insert(
+                key,
+                ServerInfo {
+                    url,
+                    scale,
+                    last_attested,
+                    schedule,
+                    registered_at,
+                },
+            );
+        }
+        Ok(servers)
+    }
+
+    async fn set_cross_signature(
+        &self,
+        address: UserAddress,
+        signature: CrossSignature,
+    ) -> Result<(), Error> {
+        sqlx::query("REPLACE INTO cross_signatures (address, data) VALUES (?, ?)")
+            .bind(address)
+            .bind(serde_json::to_string(&signature)?)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn cross_signatures(&self) -> Result<HashMap<UserAddress, CrossSignature>, Error> {
+        let rows = sqlx::query("SELECT address, data FROM cross_signatures")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut signatures = HashMap::new();
+        for row in rows {
+            signatures.insert(
+                row.get::<String, _>(0),
+                serde_json::from_str(&row.get::<String, _>(1))?,
+            );
+        }
+        Ok(signatures)
     }
 }
 
@@ -87,6 +165,7 @@ mod tests {
         let info1 = ServerInfo {
             url: "http://example1.com".to_string(),
             scale: Rational::default(),
+            ..Default::default()
         };
         storage
             .add_server(server1.clone(), info1.clone())
@@ -105,6 +184,7 @@ mod tests {
         let info2 = ServerInfo {
             url: "http://example2.com".to_string(),
             scale: Rational::new(2, 1).unwrap(),
+            ..Default::default()
         };
         storage
             .add_server(server2.clone(), info2.clone())
@@ -121,6 +201,12 @@ mod tests {
         let updated_info = ServerInfo {
             url: "http://updated.com".to_string(),
             scale: Rational::new(3, 1).unwrap(),
+            schedule: ScaleSchedule::Linear {
+                initial: Rational::new(1, 5).unwrap(),
+                duration: 100,
+            },
+            registered_at: 42,
+            ..Default::default()
         };
         storage
             .add_server(server1.clone(), updated_info.clone())
@@ -133,6 +219,8 @@ mod tests {
         let retrieved_info = &servers[&server1];
         assert_eq!(retrieved_info.url, updated_info.url);
         assert_eq!(retrieved_info.scale, updated_info.scale);
+        assert_eq!(retrieved_info.schedule, updated_info.schedule);
+        assert_eq!(retrieved_info.registered_at, 42);
 
         // remove a server
         storage.remove_server(server1.clone()).await.unwrap();
@@ -149,4 +237,27 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[async_std::test]
+    async fn test_cross_signatures() {
+        let storage = DatabaseServerStorage::new("sqlite::memory:").await.unwrap();
+        let server = "server1".to_string();
+        let signature = CrossSignature {
+            signature: "0x01".to_string(),
+            nonce: 1,
+            expires_at: 10,
+            version: Some(1),
+            received_at: 5,
+        };
+        storage
+            .set_cross_signature(server.clone(), signature.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.cross_signatures().await.unwrap(),
+            HashMap::from([(server.clone(), signature)])
+        );
+        storage.remove_server(server).await.unwrap();
+        assert!(storage.cross_signatures().await.unwrap().is_empty());
+    }
 }
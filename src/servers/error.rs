@@ -1,5 +1,11 @@
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[cfg(feature = "storage-sql")]
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
+    #[cfg(feature = "sled")]
+    #[error("Storage error: {0}")]
+    StorageError(#[from] crate::kv::error::Error),
 }
@@ -1,3 +1,26 @@
+#[cfg(feature = "http-api")]
+use crate::{identity::UserAddress, verify::random_keypair};
+
+#[cfg(feature = "storage-sql")]
 pub mod db;
 pub mod error;
 pub mod storage;
+
+// keypair this server uses to sign requests it relays to other servers
+#[cfg(feature = "http-api")]
+#[derive(Clone)]
+pub struct ServerIdentity {
+    pub private_key: String,
+    pub address: UserAddress,
+}
+
+#[cfg(feature = "http-api")]
+impl Default for ServerIdentity {
+    fn default() -> Self {
+        let (private_key, address) = random_keypair();
+        Self {
+            private_key,
+            address,
+        }
+    }
+}
@@ -4,12 +4,95 @@ use async_std::sync::RwLock;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use crate::{identity::UserAddress, numbers::Rational};
+use crate::{config::FederationSection, identity::UserAddress, numbers::Rational};
+
+// how the scale of a server grows after its registration
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleSchedule {
+    // the full scale is applied from the registration
+    #[default]
+    Static,
+    // starts at `initial` of the scale and grows linearly to the full scale over
+    // `duration` seconds of good standing
+    Linear {
+        initial: Rational,
+        duration: u64,
+    },
+}
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct ServerInfo {
     pub url: String,
     pub scale: Rational,
+    // time of the registration or of the latest re-attestation by an admin
+    #[serde(default)]
+    pub last_attested: u64,
+    #[serde(default)]
+    pub schedule: ScaleSchedule,
+    #[serde(default)]
+    pub registered_at: u64,
+}
+
+// attestation of this server signed by a registered server over `cross_sign/<this server>`
+// for the domain of this server, see `verify::cross_sign`
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CrossSignature {
+    pub signature: String,
+    pub nonce: u64,
+    pub expires_at: u64,
+    // format of the signed message, see `Freshness::version`
+    pub version: Option<u32>,
+    pub received_at: u64,
+}
+
+impl ServerInfo {
+    // weight of the balances reported by the server. It is full for `attestation_interval`
+    // seconds after the attestation and decays linearly to zero over `attestation_decay`
+    pub fn attestation_weight(&self, now: u64, config: &FederationSection) -> Rational {
+        if config.attestation_interval == 0 {
+            return Rational::default();
+        }
+        let overdue = now
+            .saturating_sub(self.last_attested)
+            .saturating_sub(config.attestation_interval);
+        if overdue == 0 {
+            return Rational::default();
+        }
+        let remaining = config.attestation_decay.saturating_sub(overdue);
+        Rational::fit(remaining, config.attestation_decay.max(1)).expect("denominator is not zero")
+    }
+
+    // share of the scale reached by the `schedule`. The server is in good standing since
+    // its registration until its attestation becomes overdue. Like the scale, it only weighs
+    // balances reported by the server, see `RemoteSource::scaled_balance`. Local balances
+    // count local vouches only, so external vouches and the schedule do not change them.
+    pub fn schedule_weight(&self, now: u64, config: &FederationSection) -> Rational {
+        let ScaleSchedule::Linear { initial, duration } = &self.schedule else {
+            return Rational::default();
+        };
+        let mut standing_until = now;
+        if config.attestation_interval != 0 {
+            standing_until = standing_until.min(
+                self.last_attested
+                    .saturating_add(config.attestation_interval),
+            );
+        }
+        let elapsed = standing_until.saturating_sub(self.registered_at);
+        if elapsed >= *duration {
+            return Rational::default();
+        }
+        // initial + (1 - initial) * elapsed / duration
+        let numerator = initial.numerator() as u128;
+        let denominator = initial.denominator() as u128;
+        let grown =
+            numerator * *duration as u128 + denominator.saturating_sub(numerator) * elapsed as u128;
+        let total = denominator * *duration as u128;
+        // both fit u64 after dropping the same low bits
+        let shift = (128 - grown.max(total).leading_zeros()).saturating_sub(64);
+        Rational::fit((grown >> shift) as u64, ((total >> shift) as u64).max(1))
+            .expect("denominator is not zero")
+    }
 }
 
 #[async_trait]
@@ -26,11 +109,23 @@ pub trait ServerStorage: Send + Sync {
     async fn servers(
         &self,
     ) -> Result<HashMap<UserAddress, ServerInfo>, crate::servers::error::Error>;
+
+    // replaces the cross signature of the server, it is dropped with the server
+    async fn set_cross_signature(
+        &self,
+        address: UserAddress,
+        signature: CrossSignature,
+    ) -> Result<(), crate::servers::error::Error>;
+
+    async fn cross_signatures(
+        &self,
+    ) -> Result<HashMap<UserAddress, CrossSignature>, crate::servers::error::Error>;
 }
 
 #[derive(Default)]
 pub struct InMemoryServerStorage {
     servers: RwLock<HashMap<UserAddress, ServerInfo>>,
+    cross_signatures: RwLock<HashMap<UserAddress, CrossSignature>>,
 }
 
 #[async_trait]
@@ -49,6 +144,7 @@ impl ServerStorage for InMemoryServerStorage {
         address: UserAddress,
     ) -> Result<(), crate::servers::error::Error> {
         self.servers.write().await.remove(&address);
+        self.cross_signatures.write().await.remove(&address);
         Ok(())
     }
 
@@ -57,11 +153,30 @@ impl ServerStorage for InMemoryServerStorage {
     ) -> Result<HashMap<UserAddress, ServerInfo>, crate::servers::error::Error> {
         Ok(self.servers.read().await.clone())
     }
+
+    async fn set_cross_signature(
+        &self,
+        address: UserAddress,
+        signature: CrossSignature,
+    ) -> Result<(), crate::servers::error::Error> {
+        self.cross_signatures
+            .write()
+            .await
+            .insert(address, signature);
+        Ok(())
+    }
+
+    async fn cross_signatures(
+        &self,
+    ) -> Result<HashMap<UserAddress, CrossSignature>, crate::servers::error::Error> {
+        Ok(self.cross_signatures.read().await.clone())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::identity::IdtAmount;
 
     #[async_std::test]
     async fn test_basic() {
@@ -70,6 +185,7 @@ mod tests {
         let server_info = ServerInfo {
             url: "http://example.com".to_string(),
             scale: Rational::default(),
+            ..Default::default()
         };
 
         // initially, there should be no servers
@@ -96,6 +212,35 @@ mod tests {
         assert!(storage.servers().await.unwrap().is_empty());
     }
 
+    #[async_std::test]
+    async fn test_cross_signatures() {
+        let storage = InMemoryServerStorage::default();
+        let server = "server1".to_string();
+        let signature = CrossSignature {
+            signature: "0x01".to_string(),
+            nonce: 1,
+            expires_at: 10,
+            version: None,
+            received_at: 5,
+        };
+        storage
+            .add_server(server.clone(), ServerInfo::default())
+            .await
+            .unwrap();
+        storage
+            .set_cross_signature(server.clone(), signature.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.cross_signatures().await.unwrap(),
+            HashMap::from([(server.clone(), signature)])
+        );
+
+        // removed with the server
+        storage.remove_server(server).await.unwrap();
+        assert!(storage.cross_signatures().await.unwrap().is_empty());
+    }
+
     #[async_std::test]
     async fn test_multiple_servers() {
         let storage = InMemoryServerStorage::default();
@@ -105,11 +250,13 @@ mod tests {
         let info1 = ServerInfo {
             url: "http://example1.com".to_string(),
             scale: Rational::default(),
+            ..Default::default()
         };
 
         let info2 = ServerInfo {
             url: "http://example2.com".to_string(),
             scale: Rational::new(2, 1).unwrap(),
+            ..Default::default()
         };
 
         // add two servers
@@ -152,11 +299,13 @@ mod tests {
         let info1 = ServerInfo {
             url: "http://example1.com".to_string(),
             scale: Rational::default(),
+            ..Default::default()
         };
 
         let info2 = ServerInfo {
             url: "http://example2.com".to_string(),
             scale: Rational::new(2, 1).unwrap(),
+            ..Default::default()
         };
 
         // add a server
@@ -192,4 +341,80 @@ mod tests {
         // servers should still be empty
         assert!(storage.servers().await.unwrap().is_empty());
     }
+
+    #[test]
+    fn test_attestation_weight() {
+        let info = ServerInfo {
+            url: "http://example.com".to_string(),
+            scale: Rational::default(),
+            last_attested: 1000,
+            ..Default::default()
+        };
+        let config = FederationSection {
+            attestation_interval: 100,
+            attestation_decay: 200,
+            ..Default::default()
+        };
+        assert_eq!(info.attestation_weight(1100, &config), Rational::default());
+        assert_eq!(
+            info.attestation_weight(1150, &config)
+                .mul(IdtAmount::new(100)),
+            IdtAmount::new(75)
+        );
+        assert_eq!(
+            info.attestation_weight(1300, &config)
+                .mul(IdtAmount::new(100)),
+            IdtAmount::new(0)
+        );
+        assert_eq!(
+            info.attestation_weight(5000, &config)
+                .mul(IdtAmount::new(100)),
+            IdtAmount::new(0)
+        );
+        // decay is disabled
+        assert_eq!(
+            info.attestation_weight(5000, &FederationSection::default()),
+            Rational::default()
+        );
+    }
+
+    #[test]
+    fn test_schedule_weight() {
+        let info = ServerInfo {
+            url: "http://example.com".to_string(),
+            scale: Rational::default(),
+            last_attested: 1000,
+            schedule: ScaleSchedule::Linear {
+                initial: Rational::new(1, 5).unwrap(),
+                duration: 100,
+            },
+            registered_at: 1000,
+        };
+        let config = FederationSection::default();
+        assert_eq!(
+            info.schedule_weight(1000, &config).mul(IdtAmount::new(100)),
+            IdtAmount::new(20)
+        );
+        assert_eq!(
+            info.schedule_weight(1050, &config).mul(IdtAmount::new(100)),
+            IdtAmount::new(60)
+        );
+        assert_eq!(info.schedule_weight(1100, &config), Rational::default());
+        assert_eq!(info.schedule_weight(5000, &config), Rational::default());
+        // the scale stops growing once the attestation is overdue
+        let config = FederationSection {
+            attestation_interval: 30,
+            ..Default::default()
+        };
+        assert_eq!(
+            info.schedule_weight(1050, &config).mul(IdtAmount::new(100)),
+            IdtAmount::new(44)
+        );
+        // static schedule
+        let info = ServerInfo {
+            scale: Rational::new(1, 2).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(info.schedule_weight(0, &config), Rational::default());
+    }
 }
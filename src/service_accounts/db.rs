@@ -0,0 +1,117 @@
+use async_trait::async_trait;
+use sqlx::{Row, any::AnyRow};
+
+use crate::{
+    identity::UserAddress,
+    pools::{self, MeteredPool},
+    service_accounts::{ServiceAccount, error::Error, storage::ServiceAccountStorage},
+};
+
+pub struct DatabaseServiceAccountStorage {
+    pool: MeteredPool,
+}
+
+impl DatabaseServiceAccountStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        let pool = pools::connect("service_accounts", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS service_accounts (address TEXT PRIMARY KEY, scopes TEXT NOT NULL, added_by TEXT NOT NULL, added_at INTEGER NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        Ok(Self { pool })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+// scopes are stored as a JSON array
+fn decode(row: &AnyRow) -> Result<ServiceAccount, Error> {
+    Ok(ServiceAccount {
+        address: row.get(0),
+        scopes: serde_json::from_str(&row.get::<String, _>(1))?,
+        added_by: row.get(2),
+        added_at: row.get::<i64, _>(3) as u64,
+    })
+}
+
+#[async_trait]
+impl ServiceAccountStorage for DatabaseServiceAccountStorage {
+    async fn add_account(&self, account: ServiceAccount) -> Result<(), Error> {
+        sqlx::query(
+            "REPLACE INTO service_accounts (address, scopes, added_by, added_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(account.address)
+        .bind(serde_json::to_string(&account.scopes)?)
+        .bind(account.added_by)
+        .bind(account.added_at as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_account(&self, address: &UserAddress) -> Result<bool, Error> {
+        let removed = sqlx::query("DELETE FROM service_accounts WHERE address = ?")
+            .bind(address)
+            .execute(&self.pool)
+            .await?;
+        Ok(removed.rows_affected() > 0)
+    }
+
+    async fn account(&self, address: &UserAddress) -> Result<Option<ServiceAccount>, Error> {
+        let row = sqlx::query(
+            "SELECT address, scopes, added_by, added_at FROM service_accounts WHERE address = ?",
+        )
+        .bind(address)
+        .fetch_optional(&self.pool)
+        .await?;
+        row.as_ref().map(decode).transpose()
+    }
+
+    async fn accounts(&self) -> Result<Vec<ServiceAccount>, Error> {
+        let rows = sqlx::query(
+            "SELECT address, scopes, added_by, added_at FROM service_accounts ORDER BY address",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(decode).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseServiceAccountStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        let account = ServiceAccount {
+            address: "service".into(),
+            scopes: BTreeSet::from(["idt".to_string(), "vouch".to_string()]),
+            added_by: "admin".into(),
+            added_at: 10,
+        };
+        storage.add_account(account.clone()).await.unwrap();
+        assert_eq!(
+            storage.account(&"service".into()).await.unwrap(),
+            Some(account.clone())
+        );
+        let other = ServiceAccount {
+            address: "another".into(),
+            scopes: BTreeSet::new(),
+            ..account.clone()
+        };
+        storage.add_account(other.clone()).await.unwrap();
+        assert_eq!(storage.accounts().await.unwrap(), vec![other, account]);
+
+        assert!(storage.remove_account(&"service".into()).await.unwrap());
+        assert!(!storage.remove_account(&"service".into()).await.unwrap());
+        assert_eq!(storage.account(&"service".into()).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,62 @@
+// Keys of services that act on the server with limited rights.
+//
+// Admins register a service account with the routes it may call. Signed requests from the
+// address of a service account are rejected for every other route, see
+// `routes::service_accounts::ServiceAccountMiddleware`.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::identity::UserAddress;
+
+pub mod db;
+pub mod error;
+pub mod storage;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceAccount {
+    pub address: UserAddress,
+    // first path segments of the routes the account may call, e.g. `vouch` for `/vouch/:user`
+    pub scopes: BTreeSet<String>,
+    pub added_by: UserAddress,
+    pub added_at: u64,
+}
+
+impl ServiceAccount {
+    pub fn allows(&self, path: &str) -> bool {
+        self.scopes.contains(scope(path))
+    }
+}
+
+// scope that grants access to the route at `path`
+pub fn scope(path: &str) -> &str {
+    path.trim_start_matches('/')
+        .split('/')
+        .next()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope() {
+        assert_eq!(scope("/vouch/userA"), "vouch");
+        assert_eq!(scope("/idt/userA/projection"), "idt");
+        assert_eq!(scope("/servers"), "servers");
+        assert_eq!(scope("/"), "");
+
+        let account = ServiceAccount {
+            address: "service".into(),
+            scopes: BTreeSet::from(["idt".to_string(), "vouch".to_string()]),
+            added_by: "admin".into(),
+            added_at: 0,
+        };
+        assert!(account.allows("/vouch/userA"));
+        assert!(account.allows("/idt/userA"));
+        assert!(!account.allows("/punish/userA"));
+        assert!(!account.allows("/vouchers/userA"));
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::{
+    identity::UserAddress,
+    service_accounts::{ServiceAccount, error::Error},
+};
+
+#[async_trait]
+pub trait ServiceAccountStorage: Send + Sync {
+    // replaces the scopes of an already registered account
+    async fn add_account(&self, account: ServiceAccount) -> Result<(), Error>;
+    // returns false if the account is not registered
+    async fn remove_account(&self, address: &UserAddress) -> Result<bool, Error>;
+    async fn account(&self, address: &UserAddress) -> Result<Option<ServiceAccount>, Error>;
+    // every account ordered by address
+    async fn accounts(&self) -> Result<Vec<ServiceAccount>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryServiceAccountStorage {
+    accounts: RwLock<BTreeMap<UserAddress, ServiceAccount>>,
+}
+
+#[async_trait]
+impl ServiceAccountStorage for InMemoryServiceAccountStorage {
+    async fn add_account(&self, account: ServiceAccount) -> Result<(), Error> {
+        self.accounts
+            .write()
+            .await
+            .insert(account.address.clone(), account);
+        Ok(())
+    }
+
+    async fn remove_account(&self, address: &UserAddress) -> Result<bool, Error> {
+        Ok(self.accounts.write().await.remove(address).is_some())
+    }
+
+    async fn account(&self, address: &UserAddress) -> Result<Option<ServiceAccount>, Error> {
+        Ok(self.accounts.read().await.get(address).cloned())
+    }
+
+    async fn accounts(&self) -> Result<Vec<ServiceAccount>, Error> {
+        Ok(self.accounts.read().await.values().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryServiceAccountStorage::default();
+        let account = ServiceAccount {
+            address: "service".into(),
+            scopes: BTreeSet::from(["idt".to_string()]),
+            added_by: "admin".into(),
+            added_at: 10,
+        };
+        storage.add_account(account.clone()).await.unwrap();
+        assert_eq!(
+            storage.account(&"service".into()).await.unwrap(),
+            Some(account.clone())
+        );
+        let account = ServiceAccount {
+            scopes: BTreeSet::from(["vouch".to_string()]),
+            ..account
+        };
+        storage.add_account(account.clone()).await.unwrap();
+        assert_eq!(storage.accounts().await.unwrap(), vec![account]);
+
+        assert!(storage.remove_account(&"service".into()).await.unwrap());
+        assert!(!storage.remove_account(&"service".into()).await.unwrap());
+        assert_eq!(storage.account(&"service".into()).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,48 @@
+// exit codes follow sysexits.h, so supervisors can tell configuration errors that need
+// an operator from outages that a restart may fix
+pub const EXIT_UNAVAILABLE: i32 = 69;
+pub const EXIT_SOFTWARE: i32 = 70;
+pub const EXIT_IO: i32 = 74;
+pub const EXIT_CONFIG: i32 = 78;
+
+#[derive(thiserror::Error, Debug)]
+pub enum StartupError {
+    #[error("Invalid SERVER_PRIVATE_KEY: {0}")]
+    PrivateKeyError(#[from] crate::verify::error::Error),
+    #[error("Failed to load configuration: {0}")]
+    ConfigError(std::io::Error),
+    #[error("Failed to load genesis configuration: {0}")]
+    GenesisError(std::io::Error),
+    #[error("Failed to load screening denylist: {0}")]
+    DenylistError(std::io::Error),
+    #[error("Invalid storage configuration: {0}")]
+    StorageConfigError(std::io::Error),
+    #[error("Failed to connect to storage after {attempts} attempts: {source}")]
+    StorageError {
+        attempts: u32,
+        source: std::io::Error,
+    },
+    #[error("Failed to set genesis balances: {0}")]
+    IdentityError(#[from] crate::identity::error::Error),
+    #[error("Failed to start server: {0}")]
+    ServerError(std::io::Error),
+}
+
+impl StartupError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StartupError::PrivateKeyError(_)
+            | StartupError::ConfigError(_)
+            | StartupError::GenesisError(_)
+            | StartupError::DenylistError(_)
+            | StartupError::StorageConfigError(_) => EXIT_CONFIG,
+            StartupError::StorageError { .. } => EXIT_UNAVAILABLE,
+            // the operator has to fix the genesis file or the storage
+            StartupError::IdentityError(crate::identity::error::Error::GenesisDrift(_)) => {
+                EXIT_CONFIG
+            }
+            StartupError::IdentityError(_) => EXIT_SOFTWARE,
+            StartupError::ServerError(_) => EXIT_IO,
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::{io::ErrorKind, time::Duration};
+
+use crate::{
+    config::StartupSection,
+    startup::error::StartupError,
+    storage::{Storage, StorageOptions, StorageRegistry},
+};
+
+pub mod error;
+pub mod version;
+
+// `initial` doubled after every failed attempt but the first, capped at `max`
+pub fn exponential_backoff(initial: u64, max: u64, attempt: u32) -> u64 {
+    let factor = 1u64
+        .checked_shl(attempt.saturating_sub(1))
+        .unwrap_or(u64::MAX);
+    initial.saturating_mul(factor).min(max)
+}
+
+// delay before the retry following the failed `attempt`, starting from 1
+pub fn backoff(params: &StartupSection, attempt: u32) -> Duration {
+    Duration::from_millis(exponential_backoff(
+        params.initial_backoff_ms,
+        params.max_backoff_ms,
+        attempt,
+    ))
+}
+
+// creates the storage, retrying with exponential backoff while the database is
+// unreachable. Invalid urls and keys are not retried.
+pub async fn connect_storage(
+    registry: &StorageRegistry,
+    url: &str,
+    options: &StorageOptions,
+    params: &StartupSection,
+) -> Result<Storage, StartupError> {
+    let attempts = params.connect_attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        let e = match registry.create(url, options).await {
+            Ok(storage) => return Ok(storage),
+            Err(e) if e.kind() == ErrorKind::InvalidInput => {
+                return Err(StartupError::StorageConfigError(e));
+            }
+            Err(e) => e,
+        };
+        if attempt >= attempts {
+            return Err(StartupError::StorageError {
+                attempts,
+                source: e,
+            });
+        }
+        let delay = backoff(params, attempt);
+        log::warn!(
+            "Failed to connect to storage (attempt {}/{}): {}, retrying in {:?}",
+            attempt,
+            attempts,
+            e,
+            delay
+        );
+        async_std::task::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        io::Error,
+        sync::{
+            Arc,
+            atomic::{AtomicU32, Ordering},
+        },
+    };
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::storage::{MEMORY_STORAGE_URL, StorageFactory};
+
+    fn options() -> StorageOptions {
+        StorageOptions {
+            admins: HashSet::new(),
+            moderators: HashSet::new(),
+            nonce_file: None,
+            event_log: false,
+            snapshot_interval: 0,
+            write_queue: Default::default(),
+            chaos: Default::default(),
+        }
+    }
+
+    fn params(connect_attempts: u32) -> StartupSection {
+        StartupSection {
+            connect_attempts,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 4,
+        }
+    }
+
+    // fails until `failures` attempts were made, then creates memory storage
+    struct FlakyFactory {
+        failures: u32,
+        attempts: AtomicU32,
+    }
+
+    #[async_trait]
+    impl StorageFactory for FlakyFactory {
+        async fn create(&self, _url: &str, options: &StorageOptions) -> Result<Storage, Error> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(Error::new(ErrorKind::NotConnected, "connection refused"));
+            }
+            StorageRegistry::default()
+                .create(MEMORY_STORAGE_URL, options)
+                .await
+        }
+    }
+
+    fn flaky_registry(failures: u32) -> (StorageRegistry, Arc<FlakyFactory>) {
+        let factory = Arc::new(FlakyFactory {
+            failures,
+            attempts: AtomicU32::new(0),
+        });
+        let mut registry = StorageRegistry::empty();
+        registry.register("flaky", factory.clone());
+        (registry, factory)
+    }
+
+    #[test]
+    fn test_backoff() {
+        let params = StartupSection {
+            connect_attempts: 10,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+        };
+        assert_eq!(backoff(&params, 1), Duration::from_millis(100));
+        assert_eq!(backoff(&params, 2), Duration::from_millis(200));
+        assert_eq!(backoff(&params, 4), Duration::from_millis(800));
+        assert_eq!(backoff(&params, 5), Duration::from_millis(1000));
+        assert_eq!(backoff(&params, 100), Duration::from_millis(1000));
+    }
+
+    #[async_std::test]
+    async fn test_connect_storage() {
+        let (registry, factory) = flaky_registry(2);
+        connect_storage(&registry, "flaky://", &options(), &params(3))
+            .await
+            .unwrap();
+        assert_eq!(factory.attempts.load(Ordering::SeqCst), 3);
+
+        let (registry, factory) = flaky_registry(5);
+        let error = connect_storage(&registry, "flaky://", &options(), &params(3))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            StartupError::StorageError { attempts: 3, .. }
+        ));
+        assert_eq!(error.exit_code(), error::EXIT_UNAVAILABLE);
+        assert_eq!(factory.attempts.load(Ordering::SeqCst), 3);
+
+        // unsupported schemes are not retried
+        let error = connect_storage(&registry, "redis://", &options(), &params(3))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.exit_code(), error::EXIT_CONFIG);
+    }
+}
@@ -0,0 +1,72 @@
+use serde::Serialize;
+
+use crate::{
+    config::{Config, LATEST_MESSAGE_VERSION},
+    numbers::Rounding,
+    storage::StorageInfo,
+};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+// commit of the build, "unknown" if built outside of a checkout, see `build.rs`
+pub const GIT_COMMIT: &str = env!("GIT_COMMIT");
+
+// Cargo features the server was built with
+pub fn features() -> Vec<&'static str> {
+    let mut features = vec![];
+    if cfg!(feature = "core") {
+        features.push("core");
+    }
+    if cfg!(feature = "storage-sql") {
+        features.push("storage-sql");
+    }
+    if cfg!(feature = "federation") {
+        features.push("federation");
+    }
+    if cfg!(feature = "http-api") {
+        features.push("http-api");
+    }
+    if cfg!(feature = "sled") {
+        features.push("sled");
+    }
+    if cfg!(feature = "ui") {
+        features.push("ui");
+    }
+    if cfg!(feature = "wasm") {
+        features.push("wasm");
+    }
+    if cfg!(feature = "test-utils") {
+        features.push("test-utils");
+    }
+    if cfg!(feature = "chaos") {
+        features.push("chaos");
+    }
+    features
+}
+
+// build and runtime description logged at startup and returned by `GET /version`
+#[derive(Clone, Debug, Serialize)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    pub features: Vec<&'static str>,
+    pub storage: StorageInfo,
+    // accepted versions of the signed message format
+    pub message_versions: Vec<u32>,
+    pub latest_message_version: u32,
+    // rounding of scaled amounts, balances differ between servers with different modes
+    pub rounding: Rounding,
+}
+
+impl VersionInfo {
+    pub fn new(config: &Config, storage: &StorageInfo) -> Self {
+        Self {
+            version: VERSION,
+            git_commit: GIT_COMMIT,
+            features: features(),
+            storage: storage.clone(),
+            message_versions: config.signatures.supported_versions(),
+            latest_message_version: LATEST_MESSAGE_VERSION,
+            rounding: config.identity.rounding,
+        }
+    }
+}
@@ -1,27 +1,146 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     env,
     io::{Error, ErrorKind},
     sync::Arc,
 };
 
+use async_trait::async_trait;
+use serde::Serialize;
+
+#[cfg(feature = "chaos")]
+use crate::chaos::inject_faults;
 use crate::{
-    admins::{AdminStorage, db::DatabaseAdminStorage},
+    admins::{AdminStorage, InMemoryAdminStorage, db::DatabaseAdminStorage},
+    archive::{
+        db::DatabaseArchiveStorage,
+        storage::{ArchiveStorage, InMemoryArchiveStorage},
+    },
+    attestations::{
+        db::DatabaseAttestationStorage,
+        storage::{AttestationStorage, InMemoryAttestationStorage},
+    },
+    changes::{
+        db::DatabaseChangeLog,
+        recorder::record_changes,
+        storage::{ChangeLog, InMemoryChangeLog},
+    },
+    config::{ChaosSection, WriteQueueSection},
+    encryption::FieldCipher,
+    events::{db::DatabaseEventLog, storage::EventSourcedStorage},
+    federation::{
+        db::DatabaseHomeStorage,
+        storage::{HomeStorage, InMemoryHomeStorage},
+    },
+    flags::{
+        db::DatabaseFlagStorage,
+        storage::{FlagStorage, InMemoryFlagStorage},
+    },
     identity::{
         UserAddress,
-        proof::{db::DatabaseProofStorage, storage::ProofStorage},
-        punish::{db::DatabasePenaltyStorage, storage::PenaltyStorage},
-        vouch::{db::DatabaseVouchStorage, storage::VouchStorage},
-        vouch_external::{db::DatabaseExternalVouchStorage, storage::ExternalVouchStorage},
+        activity::{
+            db::DatabaseActivityStorage,
+            storage::{ActivityStorage, InMemoryActivityStorage},
+        },
+        balances::{
+            db::DatabaseBalanceStorage,
+            storage::{BalanceStorage, InMemoryBalanceStorage},
+        },
+        categories::{
+            db::DatabaseCategoryStorage,
+            storage::{CategoryStorage, InMemoryCategoryStorage},
+        },
+        clock::SystemClock,
+        moderators::{
+            db::DatabaseModeratorStatsStorage,
+            storage::{InMemoryModeratorStatsStorage, ModeratorStatsStorage},
+        },
+        penalty_reasons::{
+            db::DatabasePenaltyReasonStorage,
+            storage::{InMemoryPenaltyReasonStorage, PenaltyReasonStorage},
+        },
+        proof::{
+            db::DatabaseProofStorage,
+            storage::{InMemoryProofStorage, ProofStorage},
+        },
+        proof_limits::{
+            db::DatabaseProofLimitStorage,
+            storage::{InMemoryProofLimitStorage, ProofLimitStorage},
+        },
+        punish::{
+            db::DatabasePenaltyStorage,
+            storage::{InMemoryPenaltyStorage, PenaltyStorage},
+        },
+        stakes::{
+            db::DatabaseStakeStorage,
+            storage::{InMemoryStakeStorage, StakeStorage},
+        },
+        vouch::{
+            db::DatabaseVouchStorage,
+            storage::{InMemoryVouchStorage, VouchStorage},
+        },
+        vouch_external::{
+            db::DatabaseExternalVouchStorage,
+            storage::{ExternalVouchStorage, InMemoryExternalVouchStorage},
+        },
+    },
+    notifications::{
+        db::DatabaseContactStorage,
+        storage::{ContactStorage, InMemoryContactStorage},
+    },
+    outbox::{
+        db::DatabaseOutboxStorage,
+        storage::{InMemoryOutboxStorage, OutboxStorage},
+    },
+    pending_penalties::{
+        db::DatabasePendingPenaltyStorage,
+        storage::{InMemoryPendingPenaltyStorage, PendingPenaltyStorage},
+    },
+    pending_vouches::{
+        db::DatabasePendingVouchStorage,
+        storage::{InMemoryPendingVouchStorage, PendingVouchStorage},
+    },
+    petnames::{
+        db::DatabasePetnameStorage,
+        storage::{InMemoryPetnameStorage, PetnameStorage},
+    },
+    pools::Pools,
+    reports::{
+        db::DatabaseReportStorage,
+        storage::{InMemoryReportStorage, ReportStorage},
+    },
+    restitution::{
+        db::DatabaseRestitutionStorage,
+        storage::{InMemoryRestitutionStorage, RestitutionStorage},
+    },
+    servers::{
+        db::DatabaseServerStorage,
+        storage::{InMemoryServerStorage, ServerStorage},
     },
-    servers::{db::DatabaseServerStorage, storage::ServerStorage},
-    verify::nonce::{NonceManager, db::DatabaseNonceManager},
+    service_accounts::{
+        db::DatabaseServiceAccountStorage,
+        storage::{InMemoryServiceAccountStorage, ServiceAccountStorage},
+    },
+    summaries::{
+        db::DatabaseSummaryStorage,
+        storage::{InMemorySummaryStorage, SummaryStorage},
+    },
+    verify::nonce::{
+        InMemoryNonceManager, NonceManager, db::DatabaseNonceManager, file::FileNonceManager,
+    },
+    vouch_notes::{
+        db::DatabaseVouchNoteStorage,
+        storage::{InMemoryVouchNoteStorage, VouchNoteStorage},
+    },
+    write_queue::queue_writes,
 };
 
 pub const DEFAULT_MYSQL_USER: &str = "root";
 pub const DEFAULT_MYSQL_HOST: &str = "localhost";
 pub const DEFAULT_MYSQL_PORT: u32 = 3306;
 pub const DEFAULT_MYSQL_DATABASE: &str = "identity";
+pub const DEFAULT_SLED_PATH: &str = "identity.sled";
+pub const MEMORY_STORAGE_URL: &str = "memory://";
 
 pub struct Storage {
     pub vouch_storage: Arc<dyn VouchStorage>,
@@ -31,34 +150,169 @@ pub struct Storage {
     pub admin_storage: Arc<dyn AdminStorage>,
     pub nonce_manager: Arc<dyn NonceManager>,
     pub server_storage: Arc<dyn ServerStorage>,
+    pub home_storage: Arc<dyn HomeStorage>,
+    pub balance_storage: Arc<dyn BalanceStorage>,
+    pub moderator_stats_storage: Arc<dyn ModeratorStatsStorage>,
+    pub category_storage: Arc<dyn CategoryStorage>,
+    pub activity_storage: Arc<dyn ActivityStorage>,
+    pub proof_limit_storage: Arc<dyn ProofLimitStorage>,
+    pub penalty_reason_storage: Arc<dyn PenaltyReasonStorage>,
+    pub stake_storage: Arc<dyn StakeStorage>,
+    pub contact_storage: Arc<dyn ContactStorage>,
+    pub flag_storage: Arc<dyn FlagStorage>,
+    pub archive_storage: Arc<dyn ArchiveStorage>,
+    pub attestation_storage: Arc<dyn AttestationStorage>,
+    pub report_storage: Arc<dyn ReportStorage>,
+    pub service_account_storage: Arc<dyn ServiceAccountStorage>,
+    pub pending_vouch_storage: Arc<dyn PendingVouchStorage>,
+    pub petname_storage: Arc<dyn PetnameStorage>,
+    pub outbox_storage: Arc<dyn OutboxStorage>,
+    pub summary_storage: Arc<dyn SummaryStorage>,
+    pub pending_penalty_storage: Arc<dyn PendingPenaltyStorage>,
+    pub vouch_note_storage: Arc<dyn VouchNoteStorage>,
+    pub restitution_storage: Arc<dyn RestitutionStorage>,
+    // connection pools of the SQL storages, reported by `GET /metrics`
+    pub pools: Arc<Pools>,
+    // mutations of vouches, proofs and penalties, see `changes::recorder::record_changes`
+    pub change_log: Arc<dyn ChangeLog>,
+    // set if vouches, proofs and penalties are event sourced
+    pub history: Option<Arc<EventSourcedStorage>>,
 }
 
 pub async fn create_database_storage(
+    db_url: &str,
     admins: HashSet<UserAddress>,
     moderators: HashSet<UserAddress>,
 ) -> Result<Storage, Error> {
-    let db_url = setup_database_url();
-    let vouch_storage_connect = DatabaseVouchStorage::new(&db_url)
+    let cipher =
+        FieldCipher::from_env().map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+    if cipher.is_enabled() {
+        log::info!("User addresses are encrypted in the database");
+    }
+    let vouch_storage_connect = DatabaseVouchStorage::with_cipher(db_url, cipher.clone())
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let external_vouch_storage_connect =
+        DatabaseExternalVouchStorage::with_cipher(db_url, cipher.clone())
+            .await
+            .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let proof_storage_connect = DatabaseProofStorage::with_cipher(db_url, cipher.clone())
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let penalty_storage_connect = DatabasePenaltyStorage::with_cipher(db_url, cipher.clone())
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let admin_storage_connect = DatabaseAdminStorage::new(db_url, admins, moderators)
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let server_storage_connect = DatabaseServerStorage::new(db_url)
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let balance_storage_connect = DatabaseBalanceStorage::with_cipher(db_url, cipher.clone())
         .await
         .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
-    let external_vouch_storage_connect = DatabaseExternalVouchStorage::new(&db_url)
+    let moderator_stats_storage_connect = DatabaseModeratorStatsStorage::new(db_url)
         .await
         .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
-    let proof_storage_connect = DatabaseProofStorage::new(&db_url)
+    let category_storage_connect = DatabaseCategoryStorage::with_cipher(db_url, cipher.clone())
         .await
         .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
-    let penalty_storage_connect = DatabasePenaltyStorage::new(&db_url)
+    let activity_storage_connect = DatabaseActivityStorage::with_cipher(db_url, cipher.clone())
         .await
         .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
-    let admin_storage_connect = DatabaseAdminStorage::new(&db_url, admins, moderators)
+    let proof_limit_storage_connect =
+        DatabaseProofLimitStorage::with_cipher(db_url, cipher.clone())
+            .await
+            .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let penalty_reason_storage_connect =
+        DatabasePenaltyReasonStorage::with_cipher(db_url, cipher.clone())
+            .await
+            .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let stake_storage_connect = DatabaseStakeStorage::with_cipher(db_url, cipher.clone())
         .await
         .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
-    let server_storage_connect = DatabaseServerStorage::new(&db_url)
+    let home_storage_connect = DatabaseHomeStorage::with_cipher(db_url, cipher.clone())
         .await
         .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
-    let nonce_manager = DatabaseNonceManager::new(&db_url)
+    let contact_storage_connect = DatabaseContactStorage::with_cipher(db_url, cipher.clone())
         .await
         .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let flag_storage_connect = DatabaseFlagStorage::new(db_url)
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let archive_storage_connect = DatabaseArchiveStorage::with_cipher(db_url, cipher.clone())
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let attestation_storage_connect =
+        DatabaseAttestationStorage::with_cipher(db_url, cipher.clone())
+            .await
+            .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let report_storage_connect = DatabaseReportStorage::with_cipher(db_url, cipher.clone())
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let service_account_storage_connect = DatabaseServiceAccountStorage::new(db_url)
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let pending_vouch_storage_connect =
+        DatabasePendingVouchStorage::with_cipher(db_url, cipher.clone())
+            .await
+            .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let petname_storage_connect = DatabasePetnameStorage::with_cipher(db_url, cipher.clone())
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let outbox_storage_connect = DatabaseOutboxStorage::with_cipher(db_url, cipher.clone())
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let summary_storage_connect = DatabaseSummaryStorage::with_cipher(db_url, cipher.clone())
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let pending_penalty_storage_connect =
+        DatabasePendingPenaltyStorage::with_cipher(db_url, cipher.clone())
+            .await
+            .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let vouch_note_storage_connect = DatabaseVouchNoteStorage::with_cipher(db_url, cipher.clone())
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let restitution_storage_connect =
+        DatabaseRestitutionStorage::with_cipher(db_url, cipher.clone())
+            .await
+            .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let change_log_connect = DatabaseChangeLog::with_cipher(db_url, cipher.clone())
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let nonce_manager = DatabaseNonceManager::new(db_url)
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let pools = Pools::default();
+    pools.register(vouch_storage_connect.connection_pool());
+    pools.register(external_vouch_storage_connect.connection_pool());
+    pools.register(proof_storage_connect.connection_pool());
+    pools.register(penalty_storage_connect.connection_pool());
+    pools.register(admin_storage_connect.connection_pool());
+    pools.register(server_storage_connect.connection_pool());
+    pools.register(balance_storage_connect.connection_pool());
+    pools.register(moderator_stats_storage_connect.connection_pool());
+    pools.register(category_storage_connect.connection_pool());
+    pools.register(activity_storage_connect.connection_pool());
+    pools.register(proof_limit_storage_connect.connection_pool());
+    pools.register(penalty_reason_storage_connect.connection_pool());
+    pools.register(stake_storage_connect.connection_pool());
+    pools.register(home_storage_connect.connection_pool());
+    pools.register(contact_storage_connect.connection_pool());
+    pools.register(flag_storage_connect.connection_pool());
+    pools.register(archive_storage_connect.connection_pool());
+    pools.register(attestation_storage_connect.connection_pool());
+    pools.register(report_storage_connect.connection_pool());
+    pools.register(service_account_storage_connect.connection_pool());
+    pools.register(pending_vouch_storage_connect.connection_pool());
+    pools.register(petname_storage_connect.connection_pool());
+    pools.register(outbox_storage_connect.connection_pool());
+    pools.register(summary_storage_connect.connection_pool());
+    pools.register(pending_penalty_storage_connect.connection_pool());
+    pools.register(vouch_note_storage_connect.connection_pool());
+    pools.register(restitution_storage_connect.connection_pool());
+    pools.register(change_log_connect.connection_pool());
+    pools.register(nonce_manager.connection_pool());
     Ok(Storage {
         vouch_storage: Arc::new(vouch_storage_connect),
         external_vouch_storage: Arc::new(external_vouch_storage_connect),
@@ -67,9 +321,340 @@ pub async fn create_database_storage(
         admin_storage: Arc::new(admin_storage_connect),
         nonce_manager: Arc::new(nonce_manager),
         server_storage: Arc::new(server_storage_connect),
+        home_storage: Arc::new(home_storage_connect),
+        balance_storage: Arc::new(balance_storage_connect),
+        moderator_stats_storage: Arc::new(moderator_stats_storage_connect),
+        category_storage: Arc::new(category_storage_connect),
+        activity_storage: Arc::new(activity_storage_connect),
+        proof_limit_storage: Arc::new(proof_limit_storage_connect),
+        penalty_reason_storage: Arc::new(penalty_reason_storage_connect),
+        stake_storage: Arc::new(stake_storage_connect),
+        contact_storage: Arc::new(contact_storage_connect),
+        flag_storage: Arc::new(flag_storage_connect),
+        archive_storage: Arc::new(archive_storage_connect),
+        attestation_storage: Arc::new(attestation_storage_connect),
+        report_storage: Arc::new(report_storage_connect),
+        service_account_storage: Arc::new(service_account_storage_connect),
+        pending_vouch_storage: Arc::new(pending_vouch_storage_connect),
+        petname_storage: Arc::new(petname_storage_connect),
+        outbox_storage: Arc::new(outbox_storage_connect),
+        summary_storage: Arc::new(summary_storage_connect),
+        pending_penalty_storage: Arc::new(pending_penalty_storage_connect),
+        vouch_note_storage: Arc::new(vouch_note_storage_connect),
+        restitution_storage: Arc::new(restitution_storage_connect),
+        pools: Arc::new(pools),
+        change_log: Arc::new(change_log_connect),
+        history: None,
+    })
+}
+
+// database storage where vouches, proofs and penalties are appended to the `events` table
+// instead of being updated in place
+pub async fn create_event_storage(
+    db_url: &str,
+    admins: HashSet<UserAddress>,
+    moderators: HashSet<UserAddress>,
+    snapshot_interval: u64,
+) -> Result<Storage, Error> {
+    let mut storage = create_database_storage(db_url, admins, moderators).await?;
+    let cipher =
+        FieldCipher::from_env().map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+    let log = DatabaseEventLog::with_cipher(db_url, cipher)
+        .await
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    storage.pools.register(log.connection_pool());
+    let events = EventSourcedStorage::open(Arc::new(log), Arc::new(SystemClock), snapshot_interval)
+        .await
+        .map_err(|e| Error::other(e.to_string()))?;
+    let events = Arc::new(events);
+    storage.vouch_storage = events.clone();
+    storage.proof_storage = events.clone();
+    storage.penalty_storage = events.clone();
+    storage.history = Some(events);
+    Ok(storage)
+}
+
+// keeps everything in memory. Used nonces are appended to `nonce_file` if set,
+// so signatures cannot be replayed after a restart.
+pub async fn create_memory_storage(
+    admins: HashSet<UserAddress>,
+    moderators: HashSet<UserAddress>,
+    nonce_file: Option<&str>,
+) -> Result<Storage, Error> {
+    let nonce_manager: Arc<dyn NonceManager> = match nonce_file {
+        Some(path) => Arc::new(
+            FileNonceManager::open(path)
+                .await
+                .map_err(|e| Error::other(e.to_string()))?,
+        ),
+        None => {
+            log::warn!("NONCE_FILE is not set, used nonces are lost on restart");
+            Arc::new(InMemoryNonceManager::default())
+        }
+    };
+    Ok(Storage {
+        vouch_storage: Arc::new(InMemoryVouchStorage::default()),
+        external_vouch_storage: Arc::new(InMemoryExternalVouchStorage::default()),
+        proof_storage: Arc::new(InMemoryProofStorage::default()),
+        penalty_storage: Arc::new(InMemoryPenaltyStorage::default()),
+        admin_storage: Arc::new(InMemoryAdminStorage::new(admins, moderators)),
+        nonce_manager,
+        server_storage: Arc::new(InMemoryServerStorage::default()),
+        home_storage: Arc::new(InMemoryHomeStorage::default()),
+        balance_storage: Arc::new(InMemoryBalanceStorage::default()),
+        moderator_stats_storage: Arc::new(InMemoryModeratorStatsStorage::default()),
+        category_storage: Arc::new(InMemoryCategoryStorage::default()),
+        activity_storage: Arc::new(InMemoryActivityStorage::default()),
+        proof_limit_storage: Arc::new(InMemoryProofLimitStorage::default()),
+        penalty_reason_storage: Arc::new(InMemoryPenaltyReasonStorage::default()),
+        stake_storage: Arc::new(InMemoryStakeStorage::default()),
+        contact_storage: Arc::new(InMemoryContactStorage::default()),
+        flag_storage: Arc::new(InMemoryFlagStorage::default()),
+        archive_storage: Arc::new(InMemoryArchiveStorage::default()),
+        attestation_storage: Arc::new(InMemoryAttestationStorage::default()),
+        report_storage: Arc::new(InMemoryReportStorage::default()),
+        service_account_storage: Arc::new(InMemoryServiceAccountStorage::default()),
+        pending_vouch_storage: Arc::new(InMemoryPendingVouchStorage::default()),
+        petname_storage: Arc::new(InMemoryPetnameStorage::default()),
+        outbox_storage: Arc::new(InMemoryOutboxStorage::default()),
+        summary_storage: Arc::new(InMemorySummaryStorage::default()),
+        pending_penalty_storage: Arc::new(InMemoryPendingPenaltyStorage::default()),
+        vouch_note_storage: Arc::new(InMemoryVouchNoteStorage::default()),
+        restitution_storage: Arc::new(InMemoryRestitutionStorage::default()),
+        pools: Arc::default(),
+        change_log: Arc::new(InMemoryChangeLog::default()),
+        history: None,
+    })
+}
+
+// keeps everything in a sled database at `path`, no SQL server is required
+#[cfg(feature = "sled")]
+pub fn create_sled_storage(
+    path: &str,
+    admins: HashSet<UserAddress>,
+    moderators: HashSet<UserAddress>,
+) -> Result<Storage, Error> {
+    let storage = crate::kv::SledStorage::open(path, admins, moderators)
+        .map_err(|e| Error::new(ErrorKind::NotConnected, e.to_string()))?;
+    let storage = Arc::new(storage);
+    Ok(Storage {
+        vouch_storage: storage.clone(),
+        external_vouch_storage: storage.clone(),
+        proof_storage: storage.clone(),
+        penalty_storage: storage.clone(),
+        admin_storage: storage.clone(),
+        nonce_manager: storage.clone(),
+        server_storage: storage.clone(),
+        home_storage: storage.clone(),
+        balance_storage: storage.clone(),
+        moderator_stats_storage: storage.clone(),
+        category_storage: storage.clone(),
+        activity_storage: storage.clone(),
+        proof_limit_storage: storage.clone(),
+        penalty_reason_storage: storage.clone(),
+        stake_storage: storage.clone(),
+        contact_storage: storage.clone(),
+        flag_storage: storage.clone(),
+        archive_storage: storage.clone(),
+        attestation_storage: storage.clone(),
+        report_storage: storage.clone(),
+        service_account_storage: storage.clone(),
+        pending_vouch_storage: storage.clone(),
+        petname_storage: storage.clone(),
+        outbox_storage: storage.clone(),
+        summary_storage: storage.clone(),
+        pending_penalty_storage: storage.clone(),
+        vouch_note_storage: storage.clone(),
+        restitution_storage: storage.clone(),
+        pools: Arc::default(),
+        change_log: storage,
+        history: None,
     })
 }
 
+pub struct StorageOptions {
+    pub admins: HashSet<UserAddress>,
+    pub moderators: HashSet<UserAddress>,
+    // file where memory storage appends used nonces
+    pub nonce_file: Option<String>,
+    // keep vouches, proofs and penalties in an event log, SQL storage only
+    pub event_log: bool,
+    pub snapshot_interval: u64,
+    pub write_queue: WriteQueueSection,
+    pub chaos: ChaosSection,
+}
+
+// scheme selecting the backend of a storage url
+pub fn storage_scheme(url: &str) -> &str {
+    url.split_once(':').map(|(scheme, _)| scheme).unwrap_or(url)
+}
+
+// backend description safe to publish, unlike the url which may carry credentials
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct StorageInfo {
+    pub backend: String,
+    pub event_log: bool,
+    pub write_queue: bool,
+}
+
+impl StorageInfo {
+    pub fn new(url: &str, options: &StorageOptions) -> Self {
+        Self {
+            backend: storage_scheme(url).to_string(),
+            event_log: options.event_log,
+            write_queue: options.write_queue.enabled,
+        }
+    }
+}
+
+impl Default for StorageInfo {
+    fn default() -> Self {
+        Self {
+            backend: "memory".to_string(),
+            event_log: false,
+            write_queue: false,
+        }
+    }
+}
+
+// builds the storage bundle for urls of a single scheme
+#[async_trait]
+pub trait StorageFactory: Send + Sync {
+    async fn create(&self, url: &str, options: &StorageOptions) -> Result<Storage, Error>;
+}
+
+// `mysql://` and `sqlite://` urls, passed to sqlx as is
+pub struct DatabaseStorageFactory;
+
+#[async_trait]
+impl StorageFactory for DatabaseStorageFactory {
+    async fn create(&self, url: &str, options: &StorageOptions) -> Result<Storage, Error> {
+        let (admins, moderators) = (options.admins.clone(), options.moderators.clone());
+        if options.event_log {
+            return create_event_storage(url, admins, moderators, options.snapshot_interval).await;
+        }
+        create_database_storage(url, admins, moderators).await
+    }
+}
+
+// `memory://`, the rest of the url is ignored
+pub struct MemoryStorageFactory;
+
+#[async_trait]
+impl StorageFactory for MemoryStorageFactory {
+    async fn create(&self, _url: &str, options: &StorageOptions) -> Result<Storage, Error> {
+        if options.event_log {
+            log::warn!("Event log is not supported by memory storage");
+        }
+        create_memory_storage(
+            options.admins.clone(),
+            options.moderators.clone(),
+            options.nonce_file.as_deref(),
+        )
+        .await
+    }
+}
+
+// `sled://<path>`
+#[cfg(feature = "sled")]
+pub struct SledStorageFactory;
+
+#[cfg(feature = "sled")]
+#[async_trait]
+impl StorageFactory for SledStorageFactory {
+    async fn create(&self, url: &str, options: &StorageOptions) -> Result<Storage, Error> {
+        if options.event_log {
+            log::warn!("Event log is not supported by sled storage");
+        }
+        let path = url.strip_prefix("sled://").unwrap_or_default();
+        let path = if path.is_empty() {
+            DEFAULT_SLED_PATH
+        } else {
+            path
+        };
+        create_sled_storage(path, options.admins.clone(), options.moderators.clone())
+    }
+}
+
+// storage factories by url scheme. Downstream crates can register their own backends,
+// e.g. `redis`, before creating the storage.
+pub struct StorageRegistry {
+    factories: HashMap<String, Arc<dyn StorageFactory>>,
+}
+
+impl StorageRegistry {
+    // registry without any backends
+    pub fn empty() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    // replaces the factory previously registered for the scheme
+    pub fn register(&mut self, scheme: &str, factory: Arc<dyn StorageFactory>) {
+        self.factories.insert(scheme.to_string(), factory);
+    }
+
+    pub fn schemes(&self) -> Vec<String> {
+        let mut schemes: Vec<String> = self.factories.keys().cloned().collect();
+        schemes.sort();
+        schemes
+    }
+
+    // mutations of the created storage are recorded to its change log, vouch and penalty
+    // writes are batched if the write queue is enabled
+    pub async fn create(&self, url: &str, options: &StorageOptions) -> Result<Storage, Error> {
+        let scheme = storage_scheme(url);
+        let Some(factory) = self.factories.get(scheme) else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Unsupported storage scheme {:?}, expected one of {}",
+                    scheme,
+                    self.schemes().join(", ")
+                ),
+            ));
+        };
+        let mut storage = factory.create(url, options).await?;
+        if options.chaos.enabled {
+            #[cfg(feature = "chaos")]
+            {
+                log::warn!("Injecting faults into the storage, see `storage.chaos`");
+                storage = inject_faults(storage, &options.chaos);
+            }
+            #[cfg(not(feature = "chaos"))]
+            log::warn!("storage.chaos is ignored, the server is built without the chaos feature");
+        }
+        // changes are recorded once the queued write is applied
+        if options.write_queue.enabled {
+            storage = queue_writes(storage, &options.write_queue);
+        }
+        Ok(record_changes(storage, Arc::new(SystemClock)))
+    }
+}
+
+impl Default for StorageRegistry {
+    // built-in backends
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register("mysql", Arc::new(DatabaseStorageFactory));
+        registry.register("sqlite", Arc::new(DatabaseStorageFactory));
+        registry.register("memory", Arc::new(MemoryStorageFactory));
+        #[cfg(feature = "sled")]
+        registry.register("sled", Arc::new(SledStorageFactory));
+        registry
+    }
+}
+
+// storage url for the `STORAGE` variable, used if `storage.url` is not configured
+pub fn storage_url_from_env() -> String {
+    match env::var("STORAGE").unwrap_or_default().as_str() {
+        "memory" => MEMORY_STORAGE_URL.to_string(),
+        "sled" => format!("sled://{}", env::var("SLED_PATH").unwrap_or_default()),
+        _ => setup_database_url(),
+    }
+}
+
 pub fn setup_database_url() -> String {
     let db_user = match env::var("MYSQL_USER").unwrap_or_default().as_str() {
         "" => DEFAULT_MYSQL_USER.to_string(),
@@ -90,3 +675,95 @@ pub fn setup_database_url() -> String {
     };
     format!("mysql://{db_user}:{db_password}@{db_host}:{db_port}/{db_name}")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> StorageOptions {
+        StorageOptions {
+            admins: HashSet::from(["admin".to_string()]),
+            moderators: HashSet::new(),
+            nonce_file: None,
+            event_log: false,
+            snapshot_interval: 0,
+            write_queue: WriteQueueSection::default(),
+            chaos: ChaosSection::default(),
+        }
+    }
+
+    struct FailingFactory;
+
+    #[async_trait]
+    impl StorageFactory for FailingFactory {
+        async fn create(&self, url: &str, _options: &StorageOptions) -> Result<Storage, Error> {
+            Err(Error::other(url.to_string()))
+        }
+    }
+
+    #[async_std::test]
+    async fn test_registry() {
+        let mut registry = StorageRegistry::default();
+        let storage = registry
+            .create(MEMORY_STORAGE_URL, &options())
+            .await
+            .unwrap();
+        assert!(
+            storage
+                .admin_storage
+                .check_admin(&"admin".to_string())
+                .await
+                .is_ok()
+        );
+        let storage = registry
+            .create("sqlite::memory:", &options())
+            .await
+            .unwrap();
+        assert!(storage.history.is_none());
+        let storage = registry
+            .create(
+                "sqlite::memory:",
+                &StorageOptions {
+                    event_log: true,
+                    ..options()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(storage.history.is_some());
+        let storage = registry
+            .create(
+                "sqlite::memory:",
+                &StorageOptions {
+                    write_queue: WriteQueueSection {
+                        enabled: true,
+                        ..Default::default()
+                    },
+                    ..options()
+                },
+            )
+            .await
+            .unwrap();
+        storage
+            .vouch_storage
+            .vouch("userA".into(), "userB".into(), 1)
+            .await
+            .unwrap();
+        // queued writes are still recorded
+        assert_eq!(storage.change_log.latest().await.unwrap().unwrap().seq, 1);
+
+        let error = registry
+            .create("redis://localhost", &options())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        registry.register("redis", Arc::new(FailingFactory));
+        let error = registry
+            .create("redis://localhost", &options())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "redis://localhost");
+    }
+}
@@ -0,0 +1,92 @@
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    pools::{self, MeteredPool},
+    summaries::{StoredSummary, error::Error, storage::SummaryStorage},
+};
+
+// only the latest summary is kept, stored as JSON and encrypted as a whole since it lists
+// user addresses
+pub struct DatabaseSummaryStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabaseSummaryStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("summaries", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS summaries (id INTEGER PRIMARY KEY, data TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        rotate_column(&pool, &cipher, "summaries", "data").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl SummaryStorage for DatabaseSummaryStorage {
+    async fn set_latest(&self, summary: StoredSummary) -> Result<(), Error> {
+        let data = serde_json::to_string(&summary)?;
+        sqlx::query("REPLACE INTO summaries (id, data) VALUES (1, ?)")
+            .bind(self.cipher.encode(&data))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn latest(&self) -> Result<Option<StoredSummary>, Error> {
+        let row = sqlx::query("SELECT data FROM summaries WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let data = self.cipher.decode(&row.get::<String, _>(0))?;
+        Ok(Some(serde_json::from_str(&data)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{identity::IdtAmount, summaries::Summary};
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseSummaryStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        assert_eq!(storage.latest().await.unwrap(), None);
+        let summary = StoredSummary {
+            summary: Summary {
+                period_start: 10,
+                period_end: 20,
+                vouches: 2,
+                ..Default::default()
+            },
+            last_seq: 3,
+            balances: BTreeMap::from([("a".to_string(), IdtAmount::new(10))]),
+        };
+        storage.set_latest(summary.clone()).await.unwrap();
+        let replaced = StoredSummary {
+            last_seq: 5,
+            ..summary
+        };
+        storage.set_latest(replaced.clone()).await.unwrap();
+        assert_eq!(storage.latest().await.unwrap(), Some(replaced));
+    }
+}
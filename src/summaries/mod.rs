@@ -0,0 +1,468 @@
+// Scheduled summaries of the identity graph for admins.
+//
+// With `summaries.enabled`, a summary is generated every `summaries.interval` seconds: users
+// seen for the first time, vouches and penalties recorded in the change log, and the users
+// whose balances changed the most since the previous summary. Summaries are delivered to
+// `summaries.contacts` through the notification transports, and the latest one is served
+// by `GET /reports/latest`. Every summary keeps the balances it was computed with as the
+// baseline of the next one, so the first summary counts every user as new.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    sync::Arc,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    changes::storage::ChangeLog,
+    config::SummariesSection,
+    events::Event,
+    identity::{IdentityService, IdtAmount, UserAddress, idt::balance},
+    notifications::{Contact, ContactKind, Notification, NotificationDispatcher},
+    summaries::{error::Error, storage::SummaryStorage},
+};
+
+pub mod db;
+pub mod error;
+pub mod storage;
+
+// changes read from the log at once
+const PAGE_SIZE: usize = 1000;
+// user passed to the notification transports for admin contacts
+const RECIPIENT: &str = "admin";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mover {
+    pub user: UserAddress,
+    pub before: IdtAmount,
+    pub after: IdtAmount,
+}
+
+impl Mover {
+    fn change(&self) -> String {
+        if self.after >= self.before {
+            format!("+{}", self.after - self.before)
+        } else {
+            format!("-{}", self.before - self.after)
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+    pub period_start: u64,
+    pub period_end: u64,
+    pub new_users: u64,
+    pub vouches: u64,
+    pub removed_vouches: u64,
+    pub penalties: u64,
+    pub penalty_amount: IdtAmount,
+    // greatest balance changes first
+    pub top_movers: Vec<Mover>,
+}
+
+// summary with the state the next summary is compared to
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredSummary {
+    pub summary: Summary,
+    // last change of the log counted by the summary
+    pub last_seq: u64,
+    pub balances: BTreeMap<UserAddress, IdtAmount>,
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Summary {
+    fn counts(&self) -> [(&'static str, String); 5] {
+        [
+            ("New users", self.new_users.to_string()),
+            ("Vouches", self.vouches.to_string()),
+            ("Removed vouches", self.removed_vouches.to_string()),
+            ("Penalties", self.penalties.to_string()),
+            ("Penalized IDT", self.penalty_amount.to_string()),
+        ]
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = format!(
+            "Summary from {} to {}.\n",
+            self.period_start, self.period_end
+        );
+        for (name, count) in self.counts() {
+            let _ = writeln!(text, "{name}: {count}");
+        }
+        if !self.top_movers.is_empty() {
+            text.push_str("Top movers:\n");
+        }
+        for mover in &self.top_movers {
+            let _ = writeln!(
+                text,
+                "{}: {} -> {} IDT ({})",
+                mover.user,
+                mover.before,
+                mover.after,
+                mover.change()
+            );
+        }
+        text
+    }
+
+    pub fn html(&self) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Summary</title></head><body>\n<h1>Summary from {} to {}</h1>\n<table>\n",
+            self.period_start, self.period_end
+        );
+        for (name, count) in self.counts() {
+            let _ = writeln!(html, "<tr><th>{name}</th><td>{count}</td></tr>");
+        }
+        html.push_str("</table>\n");
+        if !self.top_movers.is_empty() {
+            html.push_str("<h2>Top movers</h2>\n<table>\n<tr><th>User</th><th>Before</th><th>After</th><th>Change</th></tr>\n");
+            for mover in &self.top_movers {
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    escape_html(&mover.user),
+                    mover.before,
+                    mover.after,
+                    mover.change()
+                );
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body></html>\n");
+        html
+    }
+}
+
+// users with a proof or a vouch
+async fn known_users(service: &IdentityService) -> Result<BTreeSet<UserAddress>, Error> {
+    let mut users: BTreeSet<_> = service.proofs.proven_users().await?.into_iter().collect();
+    for (voucher, vouchee, _) in service.vouches.all_vouches().await? {
+        users.insert(voucher);
+        users.insert(vouchee);
+    }
+    Ok(users)
+}
+
+// counts the changes recorded after `period_start`, reading the log after `after_seq`.
+// Returns the last read seq.
+async fn count_changes(
+    log: &dyn ChangeLog,
+    summary: &mut Summary,
+    mut after_seq: u64,
+) -> Result<u64, Error> {
+    loop {
+        let page = log.changes(after_seq, PAGE_SIZE).await?;
+        let Some(last) = page.last() else {
+            return Ok(after_seq);
+        };
+        after_seq = last.seq;
+        for recorded in page {
+            if recorded.recorded_at <= summary.period_start {
+                continue;
+            }
+            match recorded.event {
+                Event::Vouch { .. } => summary.vouches += 1,
+                Event::RemoveVouch { .. } => summary.removed_vouches += 1,
+                Event::SetModeratorPenalty { penalty, .. } => {
+                    summary.penalties += 1;
+                    summary.penalty_amount = summary.penalty_amount.saturating_add(penalty.amount);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// summary of the changes since the previous summary
+pub async fn summarize(
+    service: &IdentityService,
+    log: &dyn ChangeLog,
+    previous: Option<&StoredSummary>,
+    period_start: u64,
+    top_movers: usize,
+) -> Result<StoredSummary, Error> {
+    let mut summary = Summary {
+        period_start,
+        period_end: service.now(),
+        ..Default::default()
+    };
+    let last_seq = count_changes(log, &mut summary, previous.map_or(0, |p| p.last_seq)).await?;
+
+    let baseline = previous.map(|p| p.balances.clone()).unwrap_or_default();
+    let mut balances = BTreeMap::new();
+    for user in known_users(service).await? {
+        if !baseline.contains_key(&user) {
+            summary.new_users += 1;
+        }
+        balances.insert(user.clone(), balance(service, &user).await?);
+    }
+    let users: BTreeSet<_> = baseline.keys().chain(balances.keys()).collect();
+    let mut movers: Vec<_> = users
+        .into_iter()
+        .map(|user| Mover {
+            user: user.clone(),
+            before: baseline.get(user).copied().unwrap_or_default(),
+            after: balances.get(user).copied().unwrap_or_default(),
+        })
+        .filter(|mover| mover.before != mover.after)
+        .collect();
+    // equal changes are ordered by address
+    movers.sort_by_key(|mover| std::cmp::Reverse(mover.before.abs_diff(mover.after)));
+    movers.truncate(top_movers);
+    summary.top_movers = movers;
+
+    Ok(StoredSummary {
+        summary,
+        last_seq,
+        balances,
+    })
+}
+
+// sends the summary to every admin contact, contacts of unknown kinds are skipped
+pub async fn deliver(
+    dispatcher: &NotificationDispatcher,
+    config: &SummariesSection,
+    summary: &Summary,
+) {
+    let notification = Notification::Summary(summary.clone());
+    for contact in &config.contacts {
+        let Some(kind) = ContactKind::parse(&contact.kind) else {
+            log::warn!("Unknown kind {} of summary contact", contact.kind);
+            continue;
+        };
+        let contact = Contact {
+            kind,
+            address: contact.address.clone(),
+        };
+        dispatcher
+            .notify_contact(&RECIPIENT.to_string(), &contact, &notification)
+            .await;
+    }
+}
+
+// generates, stores and delivers a summary if the interval passed since the previous one.
+// Without a previous summary the period starts at `started_at`.
+pub async fn summarize_due(
+    service: &IdentityService,
+    log: &dyn ChangeLog,
+    storage: &dyn SummaryStorage,
+    dispatcher: &NotificationDispatcher,
+    config: &SummariesSection,
+    started_at: u64,
+) -> Result<Option<Summary>, Error> {
+    let previous = storage.latest().await?;
+    let period_start = previous
+        .as_ref()
+        .map_or(started_at, |p| p.summary.period_end);
+    if service.now() < period_start.saturating_add(config.interval) {
+        return Ok(None);
+    }
+    let stored = summarize(
+        service,
+        log,
+        previous.as_ref(),
+        period_start,
+        config.top_movers,
+    )
+    .await?;
+    storage.set_latest(stored.clone()).await?;
+    deliver(dispatcher, config, &stored.summary).await;
+    Ok(Some(stored.summary))
+}
+
+// generates summaries forever
+pub async fn summarize_periodically(
+    service: IdentityService,
+    log: Arc<dyn ChangeLog>,
+    storage: Arc<dyn SummaryStorage>,
+    dispatcher: Arc<NotificationDispatcher>,
+    config: SummariesSection,
+) {
+    let started_at = service.now();
+    // summaries are due at most an hour late
+    let interval = Duration::from_secs(config.interval.clamp(1, 60 * 60));
+    loop {
+        match summarize_due(&service, &*log, &*storage, &dispatcher, &config, started_at).await {
+            Ok(Some(summary)) => log::info!(
+                "Generated summary from {} to {}",
+                summary.period_start,
+                summary.period_end
+            ),
+            Ok(None) => {}
+            Err(e) => log::error!("Failed to generate summary: {:?}", e),
+        }
+        async_std::task::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        changes::{recorder::ChangeRecorder, storage::InMemoryChangeLog},
+        config::AdminContact,
+        identity::{
+            clock::MockClock,
+            proof::{prove, storage::InMemoryProofStorage},
+            punish::{punish, storage::InMemoryPenaltyStorage},
+            tests::{MODERATOR, PROOF_ID, START_TIMESTAMP, USER_A},
+            vouch::{storage::InMemoryVouchStorage, vouch},
+        },
+        notifications::{InMemoryNotifier, Notifier},
+        summaries::storage::InMemorySummaryStorage,
+    };
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn recorded_service() -> (IdentityService, Arc<InMemoryChangeLog>, Arc<MockClock>) {
+        let log = Arc::new(InMemoryChangeLog::default());
+        let clock = Arc::new(MockClock::new(START_TIMESTAMP));
+        let recorder = Arc::new(ChangeRecorder::new(
+            Arc::new(InMemoryVouchStorage::default()),
+            Arc::new(InMemoryProofStorage::default()),
+            Arc::new(InMemoryPenaltyStorage::default()),
+            log.clone(),
+            clock.clone(),
+        ));
+        let service = IdentityService {
+            vouches: recorder.clone(),
+            proofs: recorder.clone(),
+            penalties: recorder,
+            clock: clock.clone(),
+            ..Default::default()
+        };
+        (service, log, clock)
+    }
+
+    #[async_std::test]
+    async fn test_summarize() {
+        let (service, log, clock) = recorded_service();
+        let user_b = "userB".to_string();
+        prove(
+            &service,
+            USER_A.into(),
+            MODERATOR.into(),
+            IdtAmount::new(1000),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, USER_A.into(), user_b.clone())
+            .await
+            .unwrap();
+
+        let first = summarize(&service, &*log, None, START_TIMESTAMP - 1, 10)
+            .await
+            .unwrap();
+        assert_eq!(first.summary.new_users, 2);
+        assert_eq!(first.summary.vouches, 1);
+        assert_eq!(first.last_seq, 2);
+        assert_eq!(first.summary.top_movers[0].user, USER_A);
+        assert_eq!(first.balances[&user_b], IdtAmount::new(100));
+
+        clock.advance(DAY);
+        punish(
+            &service,
+            user_b.clone(),
+            MODERATOR.into(),
+            IdtAmount::new(50),
+            PROOF_ID,
+        )
+        .await
+        .unwrap();
+        vouch(&service, "userC".into(), user_b.clone())
+            .await
+            .unwrap();
+        let second = summarize(&service, &*log, Some(&first), first.summary.period_end, 1)
+            .await
+            .unwrap();
+        let summary = &second.summary;
+        assert_eq!(summary.period_start, START_TIMESTAMP);
+        assert_eq!(summary.period_end, START_TIMESTAMP + DAY);
+        assert_eq!(
+            (summary.new_users, summary.vouches, summary.penalties),
+            (1, 1, 1)
+        );
+        assert_eq!(summary.penalty_amount, IdtAmount::new(50));
+        // B lost 50 IDT and some of the decayed balance of A, A only lost 6 IDT to decay
+        assert_eq!(
+            summary.top_movers,
+            vec![Mover {
+                user: user_b,
+                before: IdtAmount::new(100),
+                after: IdtAmount::from_milli(48_400),
+            }]
+        );
+        assert!(summary.text().contains("userB: 100 -> 48.4 IDT (-51.6)"));
+        assert!(summary.html().contains("<td>userB</td>"));
+    }
+
+    #[async_std::test]
+    async fn test_summarize_due() {
+        let (service, log, clock) = recorded_service();
+        let storage = InMemorySummaryStorage::default();
+        let notifier = Arc::new(InMemoryNotifier::default());
+        let dispatcher = NotificationDispatcher {
+            transports: HashMap::from([(
+                ContactKind::Email,
+                notifier.clone() as Arc<dyn Notifier>,
+            )]),
+            ..Default::default()
+        };
+        let config = SummariesSection {
+            enabled: true,
+            interval: DAY,
+            contacts: vec![
+                AdminContact {
+                    kind: "email".into(),
+                    address: "admin@example.com".into(),
+                },
+                AdminContact {
+                    kind: "pager".into(),
+                    address: "123".into(),
+                },
+            ],
+            ..Default::default()
+        };
+        let due = || {
+            summarize_due(
+                &service,
+                &*log,
+                &storage,
+                &dispatcher,
+                &config,
+                START_TIMESTAMP,
+            )
+        };
+        assert_eq!(due().await.unwrap(), None);
+
+        clock.advance(DAY);
+        let summary = due().await.unwrap().unwrap();
+        assert_eq!(summary.period_start, START_TIMESTAMP);
+        assert_eq!(storage.latest().await.unwrap().unwrap().summary, summary);
+        let sent = notifier.sent().await;
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].1.address, "admin@example.com");
+        assert_eq!(sent[0].2, Notification::Summary(summary.clone()));
+
+        // the next period starts where the previous ended
+        clock.advance(DAY - 1);
+        assert_eq!(due().await.unwrap(), None);
+        clock.advance(1);
+        let next = due().await.unwrap().unwrap();
+        assert_eq!(next.period_start, summary.period_end);
+    }
+}
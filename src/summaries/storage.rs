@@ -0,0 +1,49 @@
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::summaries::{StoredSummary, error::Error};
+
+#[async_trait]
+pub trait SummaryStorage: Send + Sync {
+    // replaces the latest summary
+    async fn set_latest(&self, summary: StoredSummary) -> Result<(), Error>;
+    async fn latest(&self) -> Result<Option<StoredSummary>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemorySummaryStorage {
+    latest: RwLock<Option<StoredSummary>>,
+}
+
+#[async_trait]
+impl SummaryStorage for InMemorySummaryStorage {
+    async fn set_latest(&self, summary: StoredSummary) -> Result<(), Error> {
+        *self.latest.write().await = Some(summary);
+        Ok(())
+    }
+
+    async fn latest(&self) -> Result<Option<StoredSummary>, Error> {
+        Ok(self.latest.read().await.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::identity::IdtAmount;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemorySummaryStorage::default();
+        assert_eq!(storage.latest().await.unwrap(), None);
+        let summary = StoredSummary {
+            last_seq: 3,
+            balances: BTreeMap::from([("a".to_string(), IdtAmount::new(10))]),
+            ..Default::default()
+        };
+        storage.set_latest(summary.clone()).await.unwrap();
+        assert_eq!(storage.latest().await.unwrap(), Some(summary));
+    }
+}
@@ -1,4 +1,13 @@
-use crate::identity::UserAddress;
+use crate::{
+    identity::{IdtAmount, UserAddress},
+    pending_penalties::PendingPenaltyId,
+};
+
+// admin messages signed without the domain carry the address of the target server, so they
+// cannot be replayed on another server where the signer is also an admin
+pub fn admin_bound_message_prefix(server: &UserAddress, message_prefix: &str) -> String {
+    format!("{server}/{message_prefix}")
+}
 
 pub fn admin_message_prefix(user: UserAddress) -> String {
     format!("admin/{user}")
@@ -11,3 +20,73 @@ pub fn admin_set_moderator_message_prefix(user: UserAddress) -> String {
 pub fn admin_set_server_message_prefix(user: UserAddress) -> String {
     format!("set_server/{user}")
 }
+
+pub fn admin_set_home_message_prefix(user: UserAddress, server: Option<UserAddress>) -> String {
+    format!("set_home/{user}/{}", server.unwrap_or_default())
+}
+
+pub fn admin_restore_user_message_prefix(user: UserAddress) -> String {
+    format!("restore_user/{user}")
+}
+
+pub fn admin_revoke_moderator_proofs_message_prefix(moderator: UserAddress) -> String {
+    format!("revoke_moderator_proofs/{moderator}")
+}
+
+pub fn admin_set_flag_message_prefix(flag: &str, enabled: bool) -> String {
+    format!("set_flag/{flag}/{enabled}")
+}
+
+pub fn admin_add_service_account_message_prefix(address: UserAddress, scopes: &[String]) -> String {
+    format!("add_service_account/{address}/{}", scopes.join(","))
+}
+
+pub fn admin_remove_service_account_message_prefix(address: UserAddress) -> String {
+    format!("remove_service_account/{address}")
+}
+
+pub fn admin_check_integrity_message_prefix(repair: bool) -> String {
+    format!("check_integrity/{repair}")
+}
+
+pub fn admin_outbox_message_prefix() -> String {
+    "outbox".to_string()
+}
+
+pub fn admin_attest_server_message_prefix(server: UserAddress) -> String {
+    format!("attest_server/{server}")
+}
+
+pub fn admin_set_successor_message_prefix(
+    moderator: UserAddress,
+    successor: Option<UserAddress>,
+) -> String {
+    format!(
+        "set_successor/{moderator}/{}",
+        successor.unwrap_or_default()
+    )
+}
+
+pub fn admin_set_proof_limit_message_prefix(
+    moderator: UserAddress,
+    limit: Option<IdtAmount>,
+) -> String {
+    format!(
+        "set_proof_limit/{moderator}/{}",
+        limit.map(|limit| limit.to_string()).unwrap_or_default()
+    )
+}
+
+pub fn admin_decide_penalty_message_prefix(id: PendingPenaltyId, approved: bool) -> String {
+    let decision = if approved { "approve" } else { "reject" };
+    format!("{decision}_penalty/{id}")
+}
+
+pub fn admin_decide_appeal_message_prefix(user: UserAddress, approved: bool) -> String {
+    let decision = if approved { "approve" } else { "reject" };
+    format!("{decision}_appeal/{user}")
+}
+
+pub fn admin_grant_restitution_message_prefix(user: UserAddress, amount: IdtAmount) -> String {
+    format!("grant_restitution/{user}/{amount}")
+}
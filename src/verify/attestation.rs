@@ -0,0 +1,98 @@
+use crate::{
+    attestations::Claim,
+    identity::UserAddress,
+    verify::{
+        error::Error,
+        nonce::NonceManager,
+        sign_message,
+        signature::{Freshness, Signature, generate, verify},
+        verify_message,
+    },
+};
+
+pub async fn attestation_start_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    provider: &str,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &attestation_start_message_prefix(provider),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn attestation_start_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    provider: &str,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &attestation_start_message_prefix(provider),
+        nonce_manager,
+    )
+    .await
+}
+
+// signature of the verifier over the claim. No nonce is needed, the challenge can be
+// completed only once.
+pub async fn claim_sign(
+    verifier_private_key_hex: &str,
+    domain: &UserAddress,
+    claim: &Claim,
+) -> Result<String, Error> {
+    generate(verifier_private_key_hex, claim_message(domain, claim)).await
+}
+
+pub fn claim_verify(signature: &str, domain: &UserAddress, claim: &Claim) -> Result<(), Error> {
+    verify(signature, &claim.verifier, claim_message(domain, claim))
+}
+
+fn attestation_start_message_prefix(provider: &str) -> String {
+    format!("attestation_start/{provider}")
+}
+
+fn claim_message(domain: &UserAddress, claim: &Claim) -> String {
+    format!(
+        "{}/attestation/{}/{}/{}/{}",
+        domain, claim.user, claim.provider, claim.account, claim.challenge
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verify::{random_keypair, tests::DOMAIN};
+
+    #[async_std::test]
+    async fn test_claim() {
+        let (private_key, verifier) = random_keypair();
+        let claim = Claim {
+            user: "user".to_string(),
+            provider: "github".to_string(),
+            account: "alice".to_string(),
+            challenge: "abc".to_string(),
+            verifier,
+        };
+        let signature = claim_sign(&private_key, &DOMAIN.to_string(), &claim)
+            .await
+            .unwrap();
+        assert!(claim_verify(&signature, &DOMAIN.to_string(), &claim).is_ok());
+        assert!(claim_verify(&signature, &"other".to_string(), &claim).is_err());
+        let other_account = Claim {
+            account: "bob".to_string(),
+            ..claim
+        };
+        assert!(claim_verify(&signature, &DOMAIN.to_string(), &other_account).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{
+    identity::{IdtAmount, UserAddress},
+    verify::{
+        error::Error,
+        nonce::NonceManager,
+        sign_message,
+        signature::{Freshness, Signature},
+        verify_message,
+    },
+};
+
+// new balance of a local `user`, pushed to the `server` that holds external vouches for them
+pub async fn balance_update_sign(
+    server_private_key_hex: &str,
+    server: &UserAddress,
+    user: &UserAddress,
+    idt: IdtAmount,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        server_private_key_hex,
+        server,
+        &balance_update_message_prefix(user, idt),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn balance_update_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    user: &UserAddress,
+    idt: IdtAmount,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &balance_update_message_prefix(user, idt),
+        nonce_manager,
+    )
+    .await
+}
+
+pub fn balance_update_message_prefix(user: &UserAddress, idt: IdtAmount) -> String {
+    format!("balance_update/{user}/{idt}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::verify::{expires_in, nonce::InMemoryNonceManager, random_keypair};
+
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, address) = random_keypair();
+        let (_, server) = random_keypair();
+        let user = "user".to_string();
+        let nonce_manager = InMemoryNonceManager::default();
+        let signature = balance_update_sign(
+            &private_key,
+            &server,
+            &user,
+            IdtAmount::new(100),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        assert_eq!(signature.signer, address);
+        assert!(
+            balance_update_verify(
+                signature.signature.clone(),
+                &address,
+                &signature.freshness,
+                &user,
+                IdtAmount::new(101),
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            balance_update_verify(
+                signature.signature,
+                &address,
+                &signature.freshness,
+                &user,
+                IdtAmount::new(100),
+                &nonce_manager
+            )
+            .await
+            .is_ok()
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{
+    identity::UserAddress,
+    verify::{
+        error::Error,
+        nonce::NonceManager,
+        sign_message,
+        signature::{Freshness, Signature},
+        verify_message,
+    },
+};
+
+pub async fn category_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    user: UserAddress,
+    category: Option<&str>,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &category_message_prefix(user, category),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn category_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    user: UserAddress,
+    category: Option<&str>,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &category_message_prefix(user, category),
+        nonce_manager,
+    )
+    .await
+}
+
+// empty category removes the category of the user
+pub fn category_message_prefix(user: UserAddress, category: Option<&str>) -> String {
+    format!("set_category/{user}/{}", category.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::verify::{expires_in, nonce::InMemoryNonceManager, random_keypair, tests::DOMAIN};
+
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, _) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let user = "user".to_string();
+        let signature = category_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            Some("bot"),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        assert!(
+            category_verify(
+                signature.signature.clone(),
+                &signature.signer,
+                &signature.freshness,
+                user.clone(),
+                None,
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            category_verify(
+                signature.signature,
+                &signature.signer,
+                &signature.freshness,
+                user,
+                Some("bot"),
+                &nonce_manager
+            )
+            .await
+            .is_ok()
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    identity::UserAddress,
+    verify::{
+        error::Error,
+        nonce::NonceManager,
+        private_key_to_address, sign_message,
+        signature::{Freshness, Signature},
+        verify_message,
+    },
+};
+
+// declaration that the `domain` server is the home of the signing user
+pub async fn claim_home_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    let user = private_key_to_address(private_key_hex)?;
+    sign_message(
+        private_key_hex,
+        domain,
+        &claim_home_message_prefix(&user),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn claim_home_verify(
+    signature: String,
+    user: &UserAddress,
+    freshness: &Freshness,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        user,
+        freshness,
+        &claim_home_message_prefix(user),
+        nonce_manager,
+    )
+    .await
+}
+
+pub fn claim_home_message_prefix(user: &UserAddress) -> String {
+    format!("claim_home/{user}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::verify::{expires_in, nonce::InMemoryNonceManager, random_keypair, tests::DOMAIN};
+
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, address) = random_keypair();
+        let (_, other) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let signature = claim_home_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        assert_eq!(signature.signer, address);
+        assert!(
+            claim_home_verify(
+                signature.signature.clone(),
+                &other,
+                &signature.freshness,
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            claim_home_verify(
+                signature.signature,
+                &address,
+                &signature.freshness,
+                &nonce_manager
+            )
+            .await
+            .is_ok()
+        );
+    }
+}
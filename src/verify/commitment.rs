@@ -0,0 +1,51 @@
+use crate::{
+    commitment::Hash,
+    identity::UserAddress,
+    verify::{
+        error::Error,
+        signature::{generate, verify},
+    },
+};
+
+// signature of the server over the commitments of a user, see `commitment`
+pub async fn commitment_sign(
+    server_private_key_hex: &str,
+    user: &UserAddress,
+    voucher_root: &Hash,
+    balance_commitment: &Hash,
+    timestamp: u64,
+) -> Result<String, Error> {
+    generate(
+        server_private_key_hex,
+        commitment_message(user, voucher_root, balance_commitment, timestamp),
+    )
+    .await
+}
+
+pub fn commitment_verify(
+    signature: &str,
+    server: &UserAddress,
+    user: &UserAddress,
+    voucher_root: &Hash,
+    balance_commitment: &Hash,
+    timestamp: u64,
+) -> Result<(), Error> {
+    verify(
+        signature,
+        server,
+        commitment_message(user, voucher_root, balance_commitment, timestamp),
+    )
+}
+
+fn commitment_message(
+    user: &UserAddress,
+    voucher_root: &Hash,
+    balance_commitment: &Hash,
+    timestamp: u64,
+) -> String {
+    format!(
+        "commitment/{user}/0x{}/0x{}/{timestamp}",
+        hex::encode(voucher_root),
+        hex::encode(balance_commitment)
+    )
+}
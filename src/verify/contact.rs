@@ -0,0 +1,108 @@
+use crate::{
+    identity::UserAddress,
+    notifications::Contact,
+    verify::{
+        error::Error,
+        nonce::NonceManager,
+        sign_message,
+        signature::{Freshness, Signature},
+        verify_message,
+    },
+};
+
+pub async fn contact_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    contact: Option<&Contact>,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &contact_message_prefix(contact),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn contact_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    contact: Option<&Contact>,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &contact_message_prefix(contact),
+        nonce_manager,
+    )
+    .await
+}
+
+// missing contact removes the registered one
+fn contact_message_prefix(contact: Option<&Contact>) -> String {
+    match contact {
+        Some(contact) => format!("contact/{}/{}", contact.kind.as_str(), contact.address),
+        None => "contact/none".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        notifications::ContactKind,
+        verify::{expires_in, nonce::InMemoryNonceManager, random_keypair, tests::DOMAIN},
+    };
+
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, _) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let contact = Contact {
+            kind: ContactKind::Email,
+            address: "user@example.com".to_string(),
+        };
+        let signature = contact_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            Some(&contact),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        let other = Contact {
+            address: "other@example.com".to_string(),
+            ..contact.clone()
+        };
+        assert!(
+            contact_verify(
+                signature.signature.clone(),
+                &signature.signer,
+                &signature.freshness,
+                Some(&other),
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            contact_verify(
+                signature.signature,
+                &signature.signer,
+                &signature.freshness,
+                Some(&contact),
+                &nonce_manager
+            )
+            .await
+            .is_ok()
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    identity::UserAddress,
+    verify::{
+        error::Error,
+        nonce::NonceManager,
+        sign_message,
+        signature::{Freshness, Signature},
+        verify_message,
+    },
+};
+
+// attestation of the `server` by the signing server, submitted to the attested server
+pub async fn cross_sign(
+    server_private_key_hex: &str,
+    server: &UserAddress,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        server_private_key_hex,
+        server,
+        &cross_sign_message_prefix(server),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+// checks the attestation of this `server` by the `signer` server
+pub async fn cross_sign_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    server: &UserAddress,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &cross_sign_message_prefix(server),
+        nonce_manager,
+    )
+    .await
+}
+
+pub fn cross_sign_message_prefix(server: &UserAddress) -> String {
+    format!("cross_sign/{server}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::verify::{expires_in, nonce::InMemoryNonceManager, random_keypair};
+
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, address) = random_keypair();
+        let (_, server) = random_keypair();
+        let (_, other) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let signature = cross_sign(&private_key, &server, expires_in(60), &nonce_manager)
+            .await
+            .expect("Should generate signature");
+        assert_eq!(signature.signer, address);
+        assert_eq!(signature.freshness.domain, Some(server.clone()));
+        assert!(
+            cross_sign_verify(
+                signature.signature.clone(),
+                &address,
+                &signature.freshness,
+                &other,
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            cross_sign_verify(
+                signature.signature,
+                &address,
+                &signature.freshness,
+                &server,
+                &nonce_manager
+            )
+            .await
+            .is_ok()
+        );
+    }
+}
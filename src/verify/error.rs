@@ -16,6 +16,16 @@ pub enum Error {
     AddressParseError(String),
     #[error("Nonce error: {0}")]
     NonceError(#[from] crate::verify::nonce::error::Error),
+    #[error("Signature expired at {0}")]
+    SignatureExpired(u64),
+    #[error("Signature expiry {0} is too far in the future")]
+    ExpiryTooFar(u64),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[error("Unsupported message version {0}")]
+    UnsupportedMessageVersion(u32),
+    #[error("Canonical signature without the canonical request body")]
+    MissingCanonicalBody,
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
 }
@@ -2,21 +2,25 @@ use crate::{
     identity::UserAddress,
     verify::{
         error::Error,
-        nonce::{Nonce, NonceManager},
+        nonce::NonceManager,
         sign_message,
-        signature::Signature,
+        signature::{Freshness, Signature},
         verify_message,
     },
 };
 
 pub async fn forget_sign(
     private_key_hex: &str,
+    domain: &UserAddress,
     vouchee: UserAddress,
+    expires_at: u64,
     nonce_manager: &dyn NonceManager,
 ) -> Result<Signature, Error> {
     sign_message(
         private_key_hex,
+        domain,
         &forget_message_prefix(vouchee),
+        expires_at,
         nonce_manager,
     )
     .await
@@ -25,27 +29,68 @@ pub async fn forget_sign(
 pub async fn forget_verify(
     signature: String,
     signer: &UserAddress,
-    nonce: Nonce,
+    freshness: &Freshness,
     vouchee: UserAddress,
     nonce_manager: &dyn NonceManager,
 ) -> Result<(), Error> {
     verify_message(
         signature,
         signer,
-        nonce,
+        freshness,
         &forget_message_prefix(vouchee),
         nonce_manager,
     )
     .await
 }
 
+// forget stored with the client `timestamp` instead of the server time
+pub async fn forget_at_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    vouchee: UserAddress,
+    timestamp: u64,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &forget_at_message_prefix(vouchee, timestamp),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn forget_at_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    vouchee: UserAddress,
+    timestamp: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &forget_at_message_prefix(vouchee, timestamp),
+        nonce_manager,
+    )
+    .await
+}
+
 fn forget_message_prefix(user: UserAddress) -> String {
     format!("forget/{user}")
 }
 
+fn forget_at_message_prefix(user: UserAddress, timestamp: u64) -> String {
+    format!("forget_at/{user}/{timestamp}")
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::verify::{nonce::InMemoryNonceManager, random_keypair};
+    use crate::verify::{expires_in, nonce::InMemoryNonceManager, random_keypair, tests::DOMAIN};
 
     use super::*;
 
@@ -54,15 +99,74 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let signature = forget_sign(&private_key, user.clone(), &nonce_manager)
+        let signature = forget_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        assert!(
+            forget_verify(
+                signature.signature,
+                &signature.signer,
+                &signature.freshness,
+                user,
+                &nonce_manager
+            )
             .await
-            .expect("Should generate signature");
+            .is_ok()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_timestamp() {
+        let (private_key, _) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let user = "user".to_string();
+        let signature = forget_at_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            100,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        // the timestamp is part of the signed message
+        assert!(
+            forget_at_verify(
+                signature.signature.clone(),
+                &signature.signer,
+                &signature.freshness,
+                user.clone(),
+                101,
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
         assert!(
             forget_verify(
+                signature.signature.clone(),
+                &signature.signer,
+                &signature.freshness,
+                user.clone(),
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            forget_at_verify(
                 signature.signature,
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 user,
+                100,
                 &nonce_manager
             )
             .await
@@ -75,15 +179,21 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let signature = forget_sign(&private_key, user.clone(), &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = forget_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         let bad_user = "bad user".to_string();
         assert!(
             forget_verify(
                 signature.signature,
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 bad_user,
                 &nonce_manager
             )
@@ -97,15 +207,24 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let signature = forget_sign(&private_key, user.clone(), &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = forget_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         let bad_nonce = 6060;
         assert!(
             forget_verify(
                 signature.signature,
                 &signature.signer,
-                bad_nonce,
+                &Freshness {
+                    nonce: bad_nonce,
+                    ..signature.freshness.clone()
+                },
                 user,
                 &nonce_manager
             )
@@ -119,14 +238,20 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let signature = forget_sign(&private_key, user.clone(), &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = forget_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         assert!(
             forget_verify(
                 signature.signature.clone(),
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 user.clone(),
                 &nonce_manager
             )
@@ -137,7 +262,7 @@ mod tests {
         let err = forget_verify(
             signature.signature,
             &signature.signer,
-            signature.nonce,
+            &signature.freshness,
             user,
             &nonce_manager,
         )
@@ -0,0 +1,92 @@
+use crate::{
+    identity::UserAddress,
+    verify::{
+        error::Error,
+        nonce::NonceManager,
+        private_key_to_address, sign_message,
+        signature::{Freshness, Signature},
+        verify_message,
+    },
+};
+
+// proof of the server that it controls its address, signed for the `domain` server that
+// registers it
+pub async fn handshake_sign(
+    server_private_key_hex: &str,
+    domain: &UserAddress,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    let server = private_key_to_address(server_private_key_hex)?;
+    sign_message(
+        server_private_key_hex,
+        domain,
+        &handshake_message_prefix(server),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn handshake_verify(
+    signature: String,
+    server: &UserAddress,
+    freshness: &Freshness,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        server,
+        freshness,
+        &handshake_message_prefix(server.clone()),
+        nonce_manager,
+    )
+    .await
+}
+
+fn handshake_message_prefix(server: UserAddress) -> String {
+    format!("server_handshake/{server}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::verify::{expires_in, nonce::InMemoryNonceManager, random_keypair, tests::DOMAIN};
+
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, address) = random_keypair();
+        let (_, other) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let signature = handshake_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        assert_eq!(signature.signer, address);
+        assert!(
+            handshake_verify(
+                signature.signature.clone(),
+                &other,
+                &signature.freshness,
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            handshake_verify(
+                signature.signature,
+                &address,
+                &signature.freshness,
+                &nonce_manager
+            )
+            .await
+            .is_ok()
+        );
+    }
+}
@@ -2,20 +2,32 @@ use ethers_core::{rand, types::H160};
 use ethers_signers::{LocalWallet, Signer};
 
 use crate::{
-    identity::UserAddress,
+    identity::{UserAddress, next_timestamp},
     verify::{
         error::Error,
-        nonce::{Nonce, NonceManager},
-        signature::{Signature, consume, generate},
+        nonce::NonceManager,
+        signature::{Freshness, Signature, canonical_body, consume, generate},
     },
 };
 
 pub mod admins;
+pub mod attestation;
+pub mod balance_update;
+pub mod category;
+pub mod claim_home;
+pub mod commitment;
+pub mod contact;
+pub mod cross_sign;
 pub mod error;
 pub mod forget;
+pub mod handshake;
 pub mod nonce;
+pub mod petname;
 pub mod proof;
+pub mod proxy;
 pub mod punish;
+pub mod report;
+pub mod restitution;
 pub mod signature;
 pub mod vouch;
 
@@ -43,29 +55,344 @@ pub fn private_key_to_address(private_key_hex: &str) -> Result<UserAddress, Erro
     Ok(address_to_string(&wallet.address()))
 }
 
+// expiry timestamp for a signature that should stay valid for `seconds`
+pub fn expires_in(seconds: u64) -> u64 {
+    next_timestamp().saturating_add(seconds)
+}
+
+// signatures are accepted until they expire. Expiry further than `max_age` from now
+// is rejected, so a captured signature cannot stay valid for long.
+pub fn check_expiry(expires_at: u64, now: u64, max_age: u64) -> Result<(), Error> {
+    if expires_at < now {
+        return Err(Error::SignatureExpired(expires_at));
+    }
+    if expires_at - now > max_age {
+        return Err(Error::ExpiryTooFar(expires_at));
+    }
+    Ok(())
+}
+
+// messages are bound to the server they are signed for, so a signature cannot be
+// replayed on another deployment where the nonce is still fresh
+fn signed_message(message_prefix: &str, freshness: &Freshness) -> Result<String, Error> {
+    let (message, separator) = match freshness.version.unwrap_or(0) {
+        0 => (unversioned_message(message_prefix, freshness), '/'),
+        1 => (
+            format!(
+                "v1|{}|{}|{}|{}",
+                freshness.domain.as_deref().unwrap_or_default(),
+                message_prefix,
+                freshness.expires_at,
+                freshness.nonce
+            ),
+            '|',
+        ),
+        version => return Err(Error::UnsupportedMessageVersion(version)),
+    };
+    Ok(match &freshness.body {
+        Some(body) => format!("{message}{separator}{body}"),
+        None => message,
+    })
+}
+
+fn unversioned_message(message_prefix: &str, freshness: &Freshness) -> String {
+    match &freshness.domain {
+        Some(domain) => format!(
+            "{}/{}/{}/{}",
+            domain, message_prefix, freshness.expires_at, freshness.nonce
+        ),
+        None => format!(
+            "{}/{}/{}",
+            message_prefix, freshness.expires_at, freshness.nonce
+        ),
+    }
+}
+
 pub async fn verify_message(
     signature: String,
     signer: &UserAddress,
-    nonce: Nonce,
+    freshness: &Freshness,
     message_prefix: &str,
     nonce_manager: &dyn NonceManager,
 ) -> Result<(), Error> {
-    let message = format!("{}/{}", message_prefix, nonce);
-    consume(signature, signer, message, nonce, nonce_manager).await
+    // fail closed if the route did not read the body for a canonical signature
+    if freshness.canonical && freshness.body.is_none() {
+        return Err(Error::MissingCanonicalBody);
+    }
+    let message = signed_message(message_prefix, freshness)?;
+    consume(signature, signer, message, freshness.nonce, nonce_manager).await
 }
 
 pub async fn sign_message(
     private_key_hex: &str,
+    domain: &UserAddress,
+    message_prefix: &str,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_versioned_message(
+        private_key_hex,
+        domain,
+        message_prefix,
+        expires_at,
+        None,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn sign_versioned_message(
+    private_key_hex: &str,
+    domain: &UserAddress,
     message_prefix: &str,
+    expires_at: u64,
+    version: Option<u32>,
     nonce_manager: &dyn NonceManager,
 ) -> Result<Signature, Error> {
     let sender = private_key_to_address(private_key_hex)?;
-    let nonce = nonce_manager.next_nonce(&sender).await?;
-    let message = format!("{}/{}", message_prefix, nonce);
+    let freshness = Freshness {
+        nonce: nonce_manager.next_nonce(&sender).await?,
+        expires_at,
+        domain: Some(domain.clone()),
+        canonical: false,
+        version,
+        body: None,
+    };
+    let message = signed_message(message_prefix, &freshness)?;
     let signature = generate(private_key_hex, message).await?;
     Ok(Signature {
         signer: sender,
         signature,
-        nonce,
+        freshness,
     })
 }
+
+// signs the message together with the canonical JSON of the request `body`, which has to
+// contain every other field of the request. Returns the body with the freshness fields and
+// the signature added.
+pub async fn sign_body(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    message_prefix: &str,
+    mut body: serde_json::Value,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<serde_json::Value, Error> {
+    let sender = private_key_to_address(private_key_hex)?;
+    let mut freshness = Freshness {
+        nonce: nonce_manager.next_nonce(&sender).await?,
+        expires_at,
+        domain: Some(domain.clone()),
+        canonical: true,
+        version: None,
+        body: None,
+    };
+    if let (Some(fields), serde_json::Value::Object(freshness_fields)) =
+        (body.as_object_mut(), serde_json::to_value(&freshness)?)
+    {
+        fields.extend(freshness_fields);
+    }
+    freshness.body = Some(canonical_body(&body));
+    let message = signed_message(message_prefix, &freshness)?;
+    let signature = generate(private_key_hex, message).await?;
+    if let Some(fields) = body.as_object_mut() {
+        fields.insert("signature".into(), signature.into());
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{config::LATEST_MESSAGE_VERSION, verify::nonce::InMemoryNonceManager};
+
+    // server address the test messages are signed for
+    pub const DOMAIN: &str = "server";
+
+    #[async_std::test]
+    async fn test_domain() {
+        let nonce_manager = InMemoryNonceManager::default();
+        let (private_key, _) = random_keypair();
+        let signature = sign_message(
+            &private_key,
+            &DOMAIN.to_string(),
+            "message",
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .unwrap();
+        let other_domain = Freshness {
+            domain: Some("other".to_string()),
+            ..signature.freshness.clone()
+        };
+        assert!(
+            verify_message(
+                signature.signature.clone(),
+                &signature.signer,
+                &other_domain,
+                "message",
+                &nonce_manager,
+            )
+            .await
+            .is_err()
+        );
+        let no_domain = Freshness {
+            domain: None,
+            ..signature.freshness.clone()
+        };
+        assert!(
+            verify_message(
+                signature.signature.clone(),
+                &signature.signer,
+                &no_domain,
+                "message",
+                &nonce_manager,
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            verify_message(
+                signature.signature,
+                &signature.signer,
+                &signature.freshness,
+                "message",
+                &nonce_manager,
+            )
+            .await
+            .is_ok()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_versions() {
+        let nonce_manager = InMemoryNonceManager::default();
+        let (private_key, _) = random_keypair();
+        let signature = sign_versioned_message(
+            &private_key,
+            &DOMAIN.to_string(),
+            "message",
+            expires_in(60),
+            Some(LATEST_MESSAGE_VERSION),
+            &nonce_manager,
+        )
+        .await
+        .unwrap();
+        let unversioned = Freshness {
+            version: None,
+            ..signature.freshness.clone()
+        };
+        assert!(
+            verify_message(
+                signature.signature.clone(),
+                &signature.signer,
+                &unversioned,
+                "message",
+                &nonce_manager,
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            verify_message(
+                signature.signature,
+                &signature.signer,
+                &signature.freshness,
+                "message",
+                &nonce_manager,
+            )
+            .await
+            .is_ok()
+        );
+        assert!(matches!(
+            sign_versioned_message(
+                &private_key,
+                &DOMAIN.to_string(),
+                "message",
+                expires_in(60),
+                Some(LATEST_MESSAGE_VERSION + 1),
+                &nonce_manager,
+            )
+            .await,
+            Err(Error::UnsupportedMessageVersion(_))
+        ));
+    }
+
+    #[async_std::test]
+    async fn test_canonical_body() {
+        let nonce_manager = InMemoryNonceManager::default();
+        let (private_key, signer) = random_keypair();
+        let body = sign_body(
+            &private_key,
+            &DOMAIN.to_string(),
+            "message",
+            serde_json::json!({"from": signer, "extra": 1}),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .unwrap();
+        assert_eq!(body["canonical"], true);
+        let signature = body["signature"].as_str().unwrap().to_string();
+        let freshness = |body: &serde_json::Value| Freshness {
+            body: Some(canonical_body(body)),
+            ..serde_json::from_value(body.clone()).unwrap()
+        };
+
+        // every field is covered, including the ones the message does not mention
+        let mut tampered = body.clone();
+        tampered["extra"] = 2.into();
+        assert!(
+            verify_message(
+                signature.clone(),
+                &signer,
+                &freshness(&tampered),
+                "message",
+                &nonce_manager,
+            )
+            .await
+            .is_err()
+        );
+        let missing_body = Freshness {
+            body: None,
+            ..freshness(&body)
+        };
+        assert!(matches!(
+            verify_message(
+                signature.clone(),
+                &signer,
+                &missing_body,
+                "message",
+                &nonce_manager,
+            )
+            .await,
+            Err(Error::MissingCanonicalBody)
+        ));
+        assert!(
+            verify_message(
+                signature,
+                &signer,
+                &freshness(&body),
+                "message",
+                &nonce_manager,
+            )
+            .await
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_check_expiry() {
+        assert!(check_expiry(100, 100, 60).is_ok());
+        assert!(check_expiry(160, 100, 60).is_ok());
+        assert!(matches!(
+            check_expiry(99, 100, 60),
+            Err(Error::SignatureExpired(99))
+        ));
+        assert!(matches!(
+            check_expiry(161, 100, 60),
+            Err(Error::ExpiryTooFar(161))
+        ));
+    }
+}
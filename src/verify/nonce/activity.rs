@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    identity::{IdentityService, UserAddress},
+    verify::nonce::{Nonce, NonceManager, error::Error},
+};
+
+// every verified signature uses a nonce of its signer, so the signer is marked active
+// once its nonce is accepted
+pub struct ActivityNonceManager {
+    inner: Arc<dyn NonceManager>,
+    service: IdentityService,
+}
+
+impl ActivityNonceManager {
+    pub fn new(inner: Arc<dyn NonceManager>, service: IdentityService) -> Self {
+        Self { inner, service }
+    }
+}
+
+#[async_trait]
+impl NonceManager for ActivityNonceManager {
+    async fn use_nonce(&self, user: &UserAddress, nonce: Nonce) -> Result<(), Error> {
+        self.inner.use_nonce(user, nonce).await?;
+        // the signature is valid, failing to track the activity does not reject it
+        if let Err(e) = self.service.record_activity(user).await {
+            log::warn!("Failed to record activity of {}: {}", user, e);
+        }
+        Ok(())
+    }
+
+    async fn next_nonce(&self, user: &UserAddress) -> Result<Nonce, Error> {
+        self.inner.next_nonce(user).await
+    }
+
+    // nonces reserved for signing are not verified signatures, the signer is not active
+    async fn reserve_nonce(&self, user: &UserAddress) -> Result<Nonce, Error> {
+        self.inner.reserve_nonce(user).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        identity::tests::{START_TIMESTAMP, USER_A, service_with_mock_clock},
+        verify::nonce::InMemoryNonceManager,
+    };
+
+    #[async_std::test]
+    async fn test_nonce_marks_active() {
+        let (service, clock) = service_with_mock_clock();
+        let nonces =
+            ActivityNonceManager::new(Arc::new(InMemoryNonceManager::default()), service.clone());
+        let user = USER_A.to_string();
+        assert_eq!(service.last_active(&user).await.unwrap(), None);
+        nonces.use_nonce(&user, 1).await.unwrap();
+        assert_eq!(
+            service.last_active(&user).await.unwrap(),
+            Some(START_TIMESTAMP)
+        );
+        // rejected nonces are not activity
+        clock.advance(100);
+        assert!(nonces.use_nonce(&user, 1).await.is_err());
+        assert_eq!(
+            service.last_active(&user).await.unwrap(),
+            Some(START_TIMESTAMP)
+        );
+        nonces.use_nonce(&user, 2).await.unwrap();
+        assert_eq!(
+            service.last_active(&user).await.unwrap(),
+            Some(START_TIMESTAMP + 100)
+        );
+    }
+}
@@ -1,22 +1,18 @@
 use async_trait::async_trait;
-use sqlx::any::AnyPoolOptions;
-use sqlx::{Acquire, AnyPool, Row};
+use sqlx::Row;
 
 use crate::identity::UserAddress;
+use crate::pools::{self, MeteredPool};
 use crate::verify::nonce::error::Error;
 use crate::verify::nonce::{Nonce, NonceManager};
 
 pub struct DatabaseNonceManager {
-    pool: AnyPool,
+    pool: MeteredPool,
 }
 
 impl DatabaseNonceManager {
     pub async fn new(url: &str) -> Result<Self, Error> {
-        sqlx::any::install_default_drivers();
-        let pool = AnyPoolOptions::new()
-            .max_connections(1)
-            .connect(url)
-            .await?;
+        let pool = pools::connect("nonces", url).await?;
         sqlx::query(
             "CREATE TABLE IF NOT EXISTS nonces (user TEXT PRIMARY KEY, used_nonce INTEGER NOT NULL)"
         )
@@ -24,34 +20,50 @@ impl DatabaseNonceManager {
         .await?;
         Ok(Self { pool })
     }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
 }
 
 #[async_trait]
 impl NonceManager for DatabaseNonceManager {
     async fn use_nonce(&self, user: &UserAddress, nonce: Nonce) -> Result<(), Error> {
-        // start a transaction for atomic operations
-        let mut tx = self.pool.begin().await?;
-        let row = sqlx::query("SELECT used_nonce FROM nonces WHERE user = ?")
-            .bind(user)
-            .fetch_optional(tx.acquire().await?)
-            .await?;
-
-        let mut used = 0;
-        if let Some(ref r) = row {
-            used = r.get::<i64, _>(0) as Nonce;
-        }
-
-        if used >= nonce {
+        // nonces start at 1, a user without a row has used nonce 0 already
+        if nonce == 0 {
             return Err(Error::NonceUsedError(nonce));
         }
+        // does not support u64 directly, so we use i64 when writing to DB
+        let nonce_value = nonce as i64;
+        // the row may be inserted concurrently between the update and the insert,
+        // so the update is retried once after a conflicting insert
+        for _ in 0..2 {
+            // compare-and-set in a single statement, so the same nonce cannot be
+            // accepted by two concurrent requests
+            let updated =
+                sqlx::query("UPDATE nonces SET used_nonce = ? WHERE user = ? AND used_nonce < ?")
+                    .bind(nonce_value)
+                    .bind(user)
+                    .bind(nonce_value)
+                    .execute(&self.pool)
+                    .await?;
+            if updated.rows_affected() == 1 {
+                return Ok(());
+            }
 
-        sqlx::query("REPLACE INTO nonces (user, used_nonce) VALUES(?, ?)")
-            .bind(user)
-            .bind(nonce as i64)
-            .execute(tx.acquire().await?)
-            .await?;
-        tx.commit().await?;
-        Ok(())
+            // no row with a lower nonce, either the user has no row yet or the nonce is used
+            let inserted = sqlx::query("INSERT INTO nonces (user, used_nonce) VALUES(?, ?)")
+                .bind(user)
+                .bind(nonce_value)
+                .execute(&self.pool)
+                .await;
+            match inserted {
+                Ok(_) => return Ok(()),
+                Err(sqlx::Error::Database(e)) if e.is_unique_violation() => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(Error::NonceUsedError(nonce))
     }
 
     async fn next_nonce(&self, user: &UserAddress) -> Result<Nonce, Error> {
@@ -72,6 +84,10 @@ impl NonceManager for DatabaseNonceManager {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
+    use futures::future::join_all;
+
     use super::*;
     use crate::verify::random_keypair;
 
@@ -82,6 +98,8 @@ mod tests {
 
         // next nonce should be 1
         assert_eq!(manager.next_nonce(&user).await.unwrap(), 1);
+        // nonce 0 is never accepted, even for a new user
+        assert!(manager.use_nonce(&user, 0).await.is_err());
         // next nonce should still be 1 until we use it
         assert_eq!(manager.next_nonce(&user).await.unwrap(), 1);
 
@@ -94,4 +112,39 @@ mod tests {
         // next nonce does not increment if use_nonce fails
         assert_eq!(manager.next_nonce(&user).await.unwrap(), 2);
     }
+
+    #[async_std::test]
+    async fn test_concurrent() {
+        let (_priv, user) = random_keypair();
+        let manager = Arc::new(DatabaseNonceManager::new("sqlite::memory:").await.unwrap());
+
+        // many requests with the same nonce, only one of them is accepted
+        for nonce in 1..=10 {
+            let tasks: Vec<_> = (0..20)
+                .map(|_| {
+                    let manager = manager.clone();
+                    let user = user.clone();
+                    async_std::task::spawn(async move { manager.use_nonce(&user, nonce).await })
+                })
+                .collect();
+            let accepted = join_all(tasks)
+                .await
+                .into_iter()
+                .filter(|r| r.is_ok())
+                .count();
+            assert_eq!(accepted, 1);
+        }
+        assert_eq!(manager.next_nonce(&user).await.unwrap(), 11);
+
+        // racing increasing nonces never lowers the used nonce
+        let tasks: Vec<_> = (11..=50)
+            .map(|nonce| {
+                let manager = manager.clone();
+                let user = user.clone();
+                async_std::task::spawn(async move { manager.use_nonce(&user, nonce).await })
+            })
+            .collect();
+        join_all(tasks).await;
+        assert_eq!(manager.next_nonce(&user).await.unwrap(), 51);
+    }
 }
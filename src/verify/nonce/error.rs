@@ -8,4 +8,9 @@ pub enum Error {
     NonceOverflowError,
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
+    #[cfg(feature = "sled")]
+    #[error("Storage error: {0}")]
+    StorageError(#[from] crate::kv::error::Error),
+    #[error("Nonce file error: {0}")]
+    FileError(#[from] std::io::Error),
 }
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use async_std::{
+    fs::{self, File, OpenOptions},
+    io::{BufReader, prelude::*},
+    stream::StreamExt,
+    sync::Mutex,
+};
+use async_trait::async_trait;
+
+use crate::identity::UserAddress;
+use crate::verify::nonce::error::Error;
+use crate::verify::nonce::{Nonce, NonceManager};
+
+struct NonceLog {
+    used_nonce: HashMap<UserAddress, Nonce>,
+    file: File,
+}
+
+// keeps nonces in memory and appends every used nonce to a file, so in-memory
+// deployments do not accept old signatures again after a restart.
+// Each line of the file is `<user> <nonce>`, the file is compacted on startup.
+pub struct FileNonceManager {
+    log: Mutex<NonceLog>,
+}
+
+impl FileNonceManager {
+    pub async fn open(path: &str) -> Result<Self, Error> {
+        let used_nonce = match File::open(path).await {
+            Ok(file) => read_log(file).await?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        // rewrite the log with the last nonce of each user only
+        let compacted = format!("{path}.tmp");
+        let mut file = File::create(&compacted).await?;
+        for (user, nonce) in &used_nonce {
+            file.write_all(format!("{user} {nonce}\n").as_bytes())
+                .await?;
+        }
+        file.sync_all().await?;
+        fs::rename(&compacted, path).await?;
+
+        let file = OpenOptions::new().append(true).open(path).await?;
+        Ok(Self {
+            log: Mutex::new(NonceLog { used_nonce, file }),
+        })
+    }
+}
+
+async fn read_log(file: File) -> Result<HashMap<UserAddress, Nonce>, Error> {
+    let mut used_nonce: HashMap<UserAddress, Nonce> = HashMap::new();
+    let mut lines = BufReader::new(file).lines();
+    while let Some(line) = lines.next().await {
+        let line = line?;
+        // last line may be cut if the server stopped while writing it
+        let Some((user, nonce)) = line.split_once(' ') else {
+            continue;
+        };
+        let Ok(nonce) = nonce.parse::<Nonce>() else {
+            continue;
+        };
+        let last_nonce = used_nonce.entry(user.to_string()).or_default();
+        *last_nonce = (*last_nonce).max(nonce);
+    }
+    Ok(used_nonce)
+}
+
+#[async_trait]
+impl NonceManager for FileNonceManager {
+    async fn use_nonce(&self, user: &UserAddress, nonce: Nonce) -> Result<(), Error> {
+        let mut log = self.log.lock().await;
+        let last_nonce = log.used_nonce.get(user).copied().unwrap_or_default();
+        if last_nonce >= nonce {
+            return Err(Error::NonceUsedError(nonce));
+        }
+
+        // persist before accepting the nonce, so an accepted nonce is never lost. The data
+        // reaches the disk, a crash right after the request cannot let it be replayed.
+        log.file
+            .write_all(format!("{user} {nonce}\n").as_bytes())
+            .await?;
+        log.file.flush().await?;
+        log.file.sync_data().await?;
+        log.used_nonce.insert(user.clone(), nonce);
+        Ok(())
+    }
+
+    async fn next_nonce(&self, user: &UserAddress) -> Result<Nonce, Error> {
+        let log = self.log.lock().await;
+        log.used_nonce
+            .get(user)
+            .copied()
+            .unwrap_or_default()
+            .checked_add(1)
+            .ok_or(Error::NonceOverflowError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::verify::random_keypair;
+
+    #[async_std::test]
+    async fn test_restart() {
+        let dir = TempDir::new("nonces").unwrap();
+        let path = dir.path().join("nonces.log");
+        let path = path.to_str().unwrap();
+        let (_priv, user) = random_keypair();
+
+        let manager = FileNonceManager::open(path).await.unwrap();
+        assert_eq!(manager.next_nonce(&user).await.unwrap(), 1);
+        manager.use_nonce(&user, 1).await.unwrap();
+        manager.use_nonce(&user, 5).await.unwrap();
+        assert!(manager.use_nonce(&user, 5).await.is_err());
+        drop(manager);
+
+        // used nonces survive a restart
+        let manager = FileNonceManager::open(path).await.unwrap();
+        assert_eq!(manager.next_nonce(&user).await.unwrap(), 6);
+        assert!(manager.use_nonce(&user, 5).await.is_err());
+        manager.use_nonce(&user, 6).await.unwrap();
+        drop(manager);
+
+        // log is compacted to a single line per user
+        let manager = FileNonceManager::open(path).await.unwrap();
+        assert_eq!(manager.next_nonce(&user).await.unwrap(), 7);
+        assert_eq!(
+            fs::read_to_string(path).await.unwrap(),
+            format!("{user} 6\n")
+        );
+    }
+
+    #[async_std::test]
+    async fn test_truncated_line() {
+        let dir = TempDir::new("nonces").unwrap();
+        let path = dir.path().join("nonces.log");
+        let path = path.to_str().unwrap();
+        fs::write(path, "userA 3\nuserB").await.unwrap();
+
+        let manager = FileNonceManager::open(path).await.unwrap();
+        assert_eq!(manager.next_nonce(&"userA".to_string()).await.unwrap(), 4);
+        assert_eq!(manager.next_nonce(&"userB".to_string()).await.unwrap(), 1);
+    }
+}
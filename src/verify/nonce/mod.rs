@@ -6,8 +6,10 @@ use async_trait::async_trait;
 use crate::identity::UserAddress;
 use crate::verify::nonce::error::Error;
 
+pub mod activity;
 pub mod db;
 pub mod error;
+pub mod file;
 
 pub type Nonce = u64;
 
@@ -16,6 +18,35 @@ pub type Nonce = u64;
 pub trait NonceManager: Send + Sync {
     async fn use_nonce(&self, user: &UserAddress, nonce: Nonce) -> Result<(), Error>;
     async fn next_nonce(&self, user: &UserAddress) -> Result<Nonce, Error>;
+
+    // marks the next nonce of the user as used and returns it. `use_nonce` is a
+    // compare-and-set, so a nonce taken concurrently is skipped and every caller gets its own.
+    async fn reserve_nonce(&self, user: &UserAddress) -> Result<Nonce, Error> {
+        loop {
+            let nonce = self.next_nonce(user).await?;
+            match self.use_nonce(user, nonce).await {
+                Err(Error::NonceUsedError(_)) => continue,
+                result => return result.map(|_| nonce),
+            }
+        }
+    }
+}
+
+// nonces of the messages this server signs for other servers. `next_nonce` only reads the
+// last used nonce, the receiving server marks it as used, so outbound messages reserve their
+// nonces here instead. Otherwise every message would carry the same nonce and the receiver
+// would reject all but the first one as a replay.
+pub struct OutboundNonces<'a>(pub &'a dyn NonceManager);
+
+#[async_trait]
+impl NonceManager for OutboundNonces<'_> {
+    async fn use_nonce(&self, user: &UserAddress, nonce: Nonce) -> Result<(), Error> {
+        self.0.use_nonce(user, nonce).await
+    }
+
+    async fn next_nonce(&self, user: &UserAddress) -> Result<Nonce, Error> {
+        self.0.reserve_nonce(user).await
+    }
 }
 
 #[derive(Default)]
@@ -62,6 +93,8 @@ mod tests {
 
         // next nonce should be 1
         assert_eq!(manager.next_nonce(&user).await.unwrap(), 1);
+        // nonce 0 is never accepted, even for a new user
+        assert!(manager.use_nonce(&user, 0).await.is_err());
         // next nonce should still be 1 until we use it
         assert_eq!(manager.next_nonce(&user).await.unwrap(), 1);
 
@@ -74,4 +107,15 @@ mod tests {
         // next nonce does not increment if use_nonce fails
         assert_eq!(manager.next_nonce(&user).await.unwrap(), 2);
     }
+
+    #[async_std::test]
+    async fn test_outbound() {
+        let (_priv, user) = random_keypair();
+        let manager = InMemoryNonceManager::default();
+        let outbound = OutboundNonces(&manager);
+        // every outbound nonce is reserved
+        assert_eq!(outbound.next_nonce(&user).await.unwrap(), 1);
+        assert_eq!(outbound.next_nonce(&user).await.unwrap(), 2);
+        assert_eq!(manager.next_nonce(&user).await.unwrap(), 3);
+    }
 }
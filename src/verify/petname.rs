@@ -0,0 +1,99 @@
+use crate::{
+    identity::UserAddress,
+    verify::{
+        error::Error,
+        nonce::NonceManager,
+        sign_message,
+        signature::{Freshness, Signature},
+        verify_message,
+    },
+};
+
+pub async fn petname_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    address: UserAddress,
+    name: Option<&str>,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &petname_message_prefix(address, name),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn petname_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    address: UserAddress,
+    name: Option<&str>,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &petname_message_prefix(address, name),
+        nonce_manager,
+    )
+    .await
+}
+
+// missing name removes the petname
+fn petname_message_prefix(address: UserAddress, name: Option<&str>) -> String {
+    format!("petname/{address}/{}", name.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::verify::{expires_in, nonce::InMemoryNonceManager, random_keypair, tests::DOMAIN};
+
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, _) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let address = "address".to_string();
+        let signature = petname_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            address.clone(),
+            Some("Alice"),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        assert!(
+            petname_verify(
+                signature.signature.clone(),
+                &signature.signer,
+                &signature.freshness,
+                address.clone(),
+                Some("Mallory"),
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            petname_verify(
+                signature.signature,
+                &signature.signer,
+                &signature.freshness,
+                address,
+                Some("Alice"),
+                &nonce_manager
+            )
+            .await
+            .is_ok()
+        );
+    }
+}
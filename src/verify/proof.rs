@@ -1,24 +1,30 @@
+use sha2::{Digest, Sha256};
+
 use crate::{
-    identity::{IdtAmount, ProofId, UserAddress},
+    identity::{IdtAmount, ProofId, UserAddress, proof::ProofBatchEntry},
     verify::{
         error::Error,
-        nonce::{Nonce, NonceManager},
+        nonce::NonceManager,
         sign_message,
-        signature::Signature,
+        signature::{Freshness, Signature},
         verify_message,
     },
 };
 
 pub async fn proof_sign(
     private_key_hex: &str,
+    domain: &UserAddress,
     user: UserAddress,
     amount: IdtAmount,
     proof_id: ProofId,
+    expires_at: u64,
     nonce_manager: &dyn NonceManager,
 ) -> Result<Signature, Error> {
     sign_message(
         private_key_hex,
+        domain,
         &proof_message_prefix(user, amount, proof_id),
+        expires_at,
         nonce_manager,
     )
     .await
@@ -27,7 +33,7 @@ pub async fn proof_sign(
 pub async fn proof_verify(
     signature: String,
     signer: &UserAddress,
-    nonce: Nonce,
+    freshness: &Freshness,
     user: UserAddress,
     amount: IdtAmount,
     proof_id: ProofId,
@@ -36,20 +42,157 @@ pub async fn proof_verify(
     verify_message(
         signature,
         signer,
-        nonce,
+        freshness,
         &proof_message_prefix(user, amount, proof_id),
         nonce_manager,
     )
     .await
 }
 
-fn proof_message_prefix(user: UserAddress, amount: IdtAmount, proof_id: ProofId) -> String {
+// proven user acknowledges the verification by `moderator`
+pub async fn proof_consent_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    moderator: UserAddress,
+    amount: IdtAmount,
+    proof_id: ProofId,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &proof_consent_message_prefix(moderator, amount, proof_id),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn proof_consent_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    moderator: UserAddress,
+    amount: IdtAmount,
+    proof_id: ProofId,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &proof_consent_message_prefix(moderator, amount, proof_id),
+        nonce_manager,
+    )
+    .await
+}
+
+// successor takes over all proofs of a retiring `moderator`
+pub async fn transfer_proofs_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    moderator: UserAddress,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &transfer_proofs_message_prefix(moderator),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn transfer_proofs_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    moderator: UserAddress,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &transfer_proofs_message_prefix(moderator),
+        nonce_manager,
+    )
+    .await
+}
+
+// moderator signs the hash of all entries of a batch, see `proof_batch_hash`
+pub async fn proof_batch_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    entries: &[ProofBatchEntry],
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &proof_batch_message_prefix(entries),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn proof_batch_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    entries: &[ProofBatchEntry],
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &proof_batch_message_prefix(entries),
+        nonce_manager,
+    )
+    .await
+}
+
+// hex SHA-256 of the `<user>/<amount>/<proof_id>` lines of the entries in order
+pub fn proof_batch_hash(entries: &[ProofBatchEntry]) -> String {
+    let mut hasher = Sha256::new();
+    for entry in entries {
+        hasher.update(format!(
+            "{}/{}/{}\n",
+            entry.user, entry.amount, entry.proof_id
+        ));
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+pub fn proof_batch_message_prefix(entries: &[ProofBatchEntry]) -> String {
+    format!("proof_batch/{}", proof_batch_hash(entries))
+}
+
+pub fn proof_message_prefix(user: UserAddress, amount: IdtAmount, proof_id: ProofId) -> String {
     format!("proof/{user}/{amount}/{proof_id}")
 }
 
+fn proof_consent_message_prefix(
+    moderator: UserAddress,
+    amount: IdtAmount,
+    proof_id: ProofId,
+) -> String {
+    format!("proof_consent/{moderator}/{amount}/{proof_id}")
+}
+
+pub fn transfer_proofs_message_prefix(moderator: UserAddress) -> String {
+    format!("transfer_proofs/{moderator}")
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::verify::{nonce::InMemoryNonceManager, random_keypair};
+    use crate::verify::{expires_in, nonce::InMemoryNonceManager, random_keypair, tests::DOMAIN};
 
     use super::*;
 
@@ -58,16 +201,24 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let amount = 100;
+        let amount = IdtAmount::new(100);
         let proof_id = 123;
-        let signature = proof_sign(&private_key, user.clone(), amount, proof_id, &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = proof_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            amount,
+            proof_id,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         assert!(
             proof_verify(
                 signature.signature,
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 user,
                 amount,
                 proof_id,
@@ -83,17 +234,25 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let amount = 100;
+        let amount = IdtAmount::new(100);
         let proof_id = 123;
-        let signature = proof_sign(&private_key, user.clone(), amount, proof_id, &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = proof_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            amount,
+            proof_id,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         let bad_user = "bad user".to_string();
         assert!(
             proof_verify(
                 signature.signature,
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 bad_user,
                 amount,
                 proof_id,
@@ -109,17 +268,25 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let amount = 100;
+        let amount = IdtAmount::new(100);
         let proof_id = 123;
-        let signature = proof_sign(&private_key, user.clone(), amount, proof_id, &nonce_manager)
-            .await
-            .expect("Should generate signature");
-        let bad_amount = 200;
+        let signature = proof_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            amount,
+            proof_id,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        let bad_amount = IdtAmount::new(200);
         assert!(
             proof_verify(
                 signature.signature,
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 user,
                 bad_amount,
                 proof_id,
@@ -135,17 +302,28 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let amount = 100;
+        let amount = IdtAmount::new(100);
         let proof_id = 123;
-        let signature = proof_sign(&private_key, user.clone(), amount, proof_id, &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = proof_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            amount,
+            proof_id,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         let bad_nonce = 6060;
         assert!(
             proof_verify(
                 signature.signature,
                 &signature.signer,
-                bad_nonce,
+                &Freshness {
+                    nonce: bad_nonce,
+                    ..signature.freshness.clone()
+                },
                 user,
                 amount,
                 proof_id,
@@ -161,17 +339,25 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let amount = 100;
+        let amount = IdtAmount::new(100);
         let proof_id = 123;
-        let signature = proof_sign(&private_key, user.clone(), amount, proof_id, &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = proof_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            amount,
+            proof_id,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         let bad_proof_id = 6060;
         assert!(
             proof_verify(
                 signature.signature,
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 user,
                 amount,
                 bad_proof_id,
@@ -187,16 +373,24 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let amount = 100;
+        let amount = IdtAmount::new(100);
         let proof_id = 123;
-        let signature = proof_sign(&private_key, user.clone(), amount, proof_id, &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = proof_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            amount,
+            proof_id,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         assert!(
             proof_verify(
                 signature.signature.clone(),
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 user.clone(),
                 amount,
                 proof_id,
@@ -209,7 +403,7 @@ mod tests {
         let err = proof_verify(
             signature.signature,
             &signature.signer,
-            signature.nonce,
+            &signature.freshness,
             user,
             amount,
             proof_id,
@@ -219,4 +413,139 @@ mod tests {
         .unwrap_err();
         assert!(matches!(err, Error::NonceError(_)));
     }
+
+    #[async_std::test]
+    async fn test_consent() {
+        let (private_key, _) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let moderator = "moderator".to_string();
+        let signature = proof_consent_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            moderator.clone(),
+            IdtAmount::new(100),
+            123,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        // consent is bound to the amount
+        assert!(
+            proof_consent_verify(
+                signature.signature.clone(),
+                &signature.signer,
+                &signature.freshness,
+                moderator.clone(),
+                IdtAmount::new(200),
+                123,
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            proof_consent_verify(
+                signature.signature,
+                &signature.signer,
+                &signature.freshness,
+                moderator,
+                IdtAmount::new(100),
+                123,
+                &nonce_manager
+            )
+            .await
+            .is_ok()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_transfer() {
+        let (private_key, _) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let moderator = "moderator".to_string();
+        let signature = transfer_proofs_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            moderator.clone(),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        assert!(
+            transfer_proofs_verify(
+                signature.signature.clone(),
+                &signature.signer,
+                &signature.freshness,
+                "other".to_string(),
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            transfer_proofs_verify(
+                signature.signature,
+                &signature.signer,
+                &signature.freshness,
+                moderator,
+                &nonce_manager
+            )
+            .await
+            .is_ok()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_batch() {
+        let (private_key, _) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let entries = vec![
+            ProofBatchEntry {
+                user: "user1".to_string(),
+                amount: IdtAmount::new(100),
+                proof_id: 1,
+            },
+            ProofBatchEntry {
+                user: "user2".to_string(),
+                amount: IdtAmount::new(200),
+                proof_id: 2,
+            },
+        ];
+        let signature = proof_batch_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            &entries,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        // the order of the entries is signed
+        let reordered = vec![entries[1].clone(), entries[0].clone()];
+        assert_ne!(proof_batch_hash(&entries), proof_batch_hash(&reordered));
+        assert!(
+            proof_batch_verify(
+                signature.signature.clone(),
+                &signature.signer,
+                &signature.freshness,
+                &reordered,
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            proof_batch_verify(
+                signature.signature,
+                &signature.signer,
+                &signature.freshness,
+                &entries,
+                &nonce_manager
+            )
+            .await
+            .is_ok()
+        );
+    }
 }
@@ -0,0 +1,64 @@
+use ethers_core::utils::keccak256;
+
+use crate::{
+    identity::UserAddress,
+    verify::{
+        error::Error,
+        signature::{generate, verify},
+    },
+};
+
+// signature of the relaying server over a forwarded request, the timestamp lets the
+// receiving server reject stale requests
+pub async fn proxy_sign(
+    private_key_hex: &str,
+    method: &str,
+    path: &str,
+    body: &str,
+    timestamp: u64,
+) -> Result<String, Error> {
+    generate(
+        private_key_hex,
+        proxy_message(method, path, body, timestamp),
+    )
+    .await
+}
+
+pub fn proxy_verify(
+    signature: &str,
+    relay: &UserAddress,
+    method: &str,
+    path: &str,
+    body: &str,
+    timestamp: u64,
+) -> Result<(), Error> {
+    verify(
+        signature,
+        relay,
+        proxy_message(method, path, body, timestamp),
+    )
+}
+
+fn proxy_message(method: &str, path: &str, body: &str, timestamp: u64) -> String {
+    let body_hash = hex::encode(keccak256(body.as_bytes()));
+    format!("proxy/{method}/{path}/0x{body_hash}/{timestamp}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::verify::random_keypair;
+
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, relay) = random_keypair();
+        let signature = proxy_sign(&private_key, "POST", "/vouch/user", "{}", 10)
+            .await
+            .unwrap();
+        assert!(proxy_verify(&signature, &relay, "POST", "/vouch/user", "{}", 10).is_ok());
+        assert!(proxy_verify(&signature, &relay, "POST", "/vouch/user", "{\"a\":1}", 10).is_err());
+        assert!(proxy_verify(&signature, &relay, "POST", "/vouch/user", "{}", 11).is_err());
+        assert!(proxy_verify(&signature, &relay, "GET", "/vouch/user", "{}", 10).is_err());
+    }
+}
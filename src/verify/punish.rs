@@ -2,23 +2,27 @@ use crate::{
     identity::{IdtAmount, ProofId, UserAddress},
     verify::{
         error::Error,
-        nonce::{Nonce, NonceManager},
+        nonce::NonceManager,
         sign_message,
-        signature::Signature,
+        signature::{Freshness, Signature},
         verify_message,
     },
 };
 
 pub async fn punish_sign(
     private_key_hex: &str,
+    domain: &UserAddress,
     user: UserAddress,
     amount: IdtAmount,
     proof_id: ProofId,
+    expires_at: u64,
     nonce_manager: &dyn NonceManager,
 ) -> Result<Signature, Error> {
     sign_message(
         private_key_hex,
-        &punish_message_prefix(user, amount, proof_id),
+        domain,
+        &punish_message_prefix(user, amount, proof_id, None),
+        expires_at,
         nonce_manager,
     )
     .await
@@ -27,7 +31,7 @@ pub async fn punish_sign(
 pub async fn punish_verify(
     signature: String,
     signer: &UserAddress,
-    nonce: Nonce,
+    freshness: &Freshness,
     user: UserAddress,
     amount: IdtAmount,
     proof_id: ProofId,
@@ -36,20 +40,29 @@ pub async fn punish_verify(
     verify_message(
         signature,
         signer,
-        nonce,
-        &punish_message_prefix(user.clone(), amount, proof_id),
+        freshness,
+        &punish_message_prefix(user.clone(), amount, proof_id, None),
         nonce_manager,
     )
     .await
 }
 
-fn punish_message_prefix(user: UserAddress, amount: IdtAmount, proof_id: ProofId) -> String {
-    format!("punish/{user}/{amount}/{proof_id}")
+// penalties without a reason keep the message of clients unaware of reasons
+pub fn punish_message_prefix(
+    user: UserAddress,
+    amount: IdtAmount,
+    proof_id: ProofId,
+    reason: Option<String>,
+) -> String {
+    match reason {
+        Some(reason) => format!("punish/{user}/{amount}/{proof_id}/{reason}"),
+        None => format!("punish/{user}/{amount}/{proof_id}"),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::verify::{nonce::InMemoryNonceManager, random_keypair};
+    use crate::verify::{expires_in, nonce::InMemoryNonceManager, random_keypair, tests::DOMAIN};
 
     use super::*;
 
@@ -58,16 +71,24 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let amount = 100;
+        let amount = IdtAmount::new(100);
         let proof_id = 123;
-        let signature = punish_sign(&private_key, user.clone(), amount, proof_id, &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = punish_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            amount,
+            proof_id,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         assert!(
             punish_verify(
                 signature.signature,
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 user,
                 amount,
                 proof_id,
@@ -83,17 +104,25 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let amount = 100;
+        let amount = IdtAmount::new(100);
         let proof_id = 123;
-        let signature = punish_sign(&private_key, user.clone(), amount, proof_id, &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = punish_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            amount,
+            proof_id,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         let bad_user = "bad user".to_string();
         assert!(
             punish_verify(
                 signature.signature,
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 bad_user,
                 amount,
                 proof_id,
@@ -109,17 +138,25 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let amount = 100;
+        let amount = IdtAmount::new(100);
         let proof_id = 123;
-        let signature = punish_sign(&private_key, user.clone(), amount, proof_id, &nonce_manager)
-            .await
-            .expect("Should generate signature");
-        let bad_amount = 200;
+        let signature = punish_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            amount,
+            proof_id,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        let bad_amount = IdtAmount::new(200);
         assert!(
             punish_verify(
                 signature.signature,
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 user,
                 bad_amount,
                 proof_id,
@@ -135,17 +172,28 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let amount = 100;
+        let amount = IdtAmount::new(100);
         let proof_id = 123;
-        let signature = punish_sign(&private_key, user.clone(), amount, proof_id, &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = punish_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            amount,
+            proof_id,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         let bad_nonce = 6060;
         assert!(
             punish_verify(
                 signature.signature,
                 &signature.signer,
-                bad_nonce,
+                &Freshness {
+                    nonce: bad_nonce,
+                    ..signature.freshness.clone()
+                },
                 user,
                 amount,
                 proof_id,
@@ -161,17 +209,25 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let amount = 100;
+        let amount = IdtAmount::new(100);
         let proof_id = 123;
-        let signature = punish_sign(&private_key, user.clone(), amount, proof_id, &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = punish_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            amount,
+            proof_id,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         let bad_proof_id = 6060;
         assert!(
             punish_verify(
                 signature.signature,
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 user,
                 amount,
                 bad_proof_id,
@@ -187,16 +243,24 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let user = "user".to_string();
-        let amount = 100;
+        let amount = IdtAmount::new(100);
         let proof_id = 123;
-        let signature = punish_sign(&private_key, user.clone(), amount, proof_id, &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = punish_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            user.clone(),
+            amount,
+            proof_id,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         assert!(
             punish_verify(
                 signature.signature.clone(),
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 user.clone(),
                 amount,
                 proof_id,
@@ -209,7 +273,7 @@ mod tests {
         let err = punish_verify(
             signature.signature,
             &signature.signer,
-            signature.nonce,
+            &signature.freshness,
             user,
             amount,
             proof_id,
@@ -219,4 +283,53 @@ mod tests {
         .unwrap_err();
         assert!(matches!(err, Error::NonceError(_)));
     }
+
+    #[async_std::test]
+    async fn test_reason() {
+        let (private_key, _) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let user = "user".to_string();
+        let prefix = |reason: Option<&str>| {
+            punish_message_prefix(
+                user.clone(),
+                IdtAmount::new(100),
+                123,
+                reason.map(String::from),
+            )
+        };
+        let signature = sign_message(
+            &private_key,
+            &DOMAIN.to_string(),
+            &prefix(Some("spam")),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        // the reason cannot be dropped or replaced
+        for reason in [None, Some("fraud")] {
+            assert!(
+                verify_message(
+                    signature.signature.clone(),
+                    &signature.signer,
+                    &signature.freshness,
+                    &prefix(reason),
+                    &nonce_manager
+                )
+                .await
+                .is_err()
+            );
+        }
+        assert!(
+            verify_message(
+                signature.signature,
+                &signature.signer,
+                &signature.freshness,
+                &prefix(Some("spam")),
+                &nonce_manager
+            )
+            .await
+            .is_ok()
+        );
+    }
 }
@@ -0,0 +1,190 @@
+use crate::{
+    identity::UserAddress,
+    reports::{ReportAction, ReportId},
+    verify::{
+        error::Error,
+        nonce::NonceManager,
+        sign_message,
+        signature::{Freshness, Signature},
+        verify_message,
+    },
+};
+
+pub async fn report_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    user: &UserAddress,
+    reason: &str,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &report_message_prefix(user, reason),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn report_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    user: &UserAddress,
+    reason: &str,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &report_message_prefix(user, reason),
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn resolve_report_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    id: ReportId,
+    action: &ReportAction,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &resolve_report_message_prefix(id, action),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn resolve_report_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    id: ReportId,
+    action: &ReportAction,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &resolve_report_message_prefix(id, action),
+        nonce_manager,
+    )
+    .await
+}
+
+fn report_message_prefix(user: &UserAddress, reason: &str) -> String {
+    format!("report/{user}/{reason}")
+}
+
+pub fn resolve_report_message_prefix(id: ReportId, action: &ReportAction) -> String {
+    match action {
+        ReportAction::Dismiss => format!("resolve_report/{id}/dismiss"),
+        ReportAction::Punish { amount, proof_id } => {
+            format!("resolve_report/{id}/punish/{amount}/{proof_id}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::IdtAmount;
+    use crate::verify::{expires_in, nonce::InMemoryNonceManager, random_keypair, tests::DOMAIN};
+
+    #[async_std::test]
+    async fn test_report() {
+        let (private_key, _) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let user = "user".to_string();
+        let signature = report_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            &user,
+            "spam",
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .unwrap();
+        assert!(
+            report_verify(
+                signature.signature.clone(),
+                &signature.signer,
+                &signature.freshness,
+                &user,
+                "other reason",
+                &nonce_manager,
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            report_verify(
+                signature.signature,
+                &signature.signer,
+                &signature.freshness,
+                &user,
+                "spam",
+                &nonce_manager,
+            )
+            .await
+            .is_ok()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_resolve_report() {
+        let (private_key, _) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let action = ReportAction::Punish {
+            amount: IdtAmount::new(100),
+            proof_id: 1,
+        };
+        let signature = resolve_report_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            1,
+            &action,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .unwrap();
+        for (id, action) in [(2, action.clone()), (1, ReportAction::Dismiss)] {
+            assert!(
+                resolve_report_verify(
+                    signature.signature.clone(),
+                    &signature.signer,
+                    &signature.freshness,
+                    id,
+                    &action,
+                    &nonce_manager,
+                )
+                .await
+                .is_err()
+            );
+        }
+        assert!(
+            resolve_report_verify(
+                signature.signature,
+                &signature.signer,
+                &signature.freshness,
+                1,
+                &action,
+                &nonce_manager,
+            )
+            .await
+            .is_ok()
+        );
+    }
+}
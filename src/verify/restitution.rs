@@ -0,0 +1,138 @@
+use crate::{
+    identity::{IdtAmount, UserAddress},
+    verify::{
+        error::Error,
+        nonce::NonceManager,
+        sign_message,
+        signature::{Freshness, Signature},
+        verify_message,
+    },
+};
+
+// contribution to the restitution pool, or withdrawal from it if `withdraw` is set
+pub async fn restitution_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    amount: IdtAmount,
+    withdraw: bool,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &restitution_message_prefix(amount, withdraw),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn restitution_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    amount: IdtAmount,
+    withdraw: bool,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &restitution_message_prefix(amount, withdraw),
+        nonce_manager,
+    )
+    .await
+}
+
+// appeal of a penalized user, granted restitution is drawn for approved appeals
+pub async fn appeal_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    reason: &str,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &appeal_message_prefix(reason),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn appeal_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    reason: &str,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &appeal_message_prefix(reason),
+        nonce_manager,
+    )
+    .await
+}
+
+fn appeal_message_prefix(reason: &str) -> String {
+    format!("restitution/appeal/{reason}")
+}
+
+fn restitution_message_prefix(amount: IdtAmount, withdraw: bool) -> String {
+    let action = if withdraw { "withdraw" } else { "contribute" };
+    format!("restitution/{action}/{amount}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::verify::{expires_in, nonce::InMemoryNonceManager, random_keypair, tests::DOMAIN};
+
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let (private_key, _) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let signature = restitution_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            IdtAmount::new(10),
+            false,
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        assert!(
+            restitution_verify(
+                signature.signature.clone(),
+                &signature.signer,
+                &signature.freshness,
+                IdtAmount::new(10),
+                true,
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            restitution_verify(
+                signature.signature,
+                &signature.signer,
+                &signature.freshness,
+                IdtAmount::new(10),
+                false,
+                &nonce_manager
+            )
+            .await
+            .is_ok()
+        );
+    }
+}
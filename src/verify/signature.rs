@@ -3,6 +3,7 @@ use std::str::FromStr;
 use ethers_core::types::{H160, Signature as EthSignature};
 use ethers_signers::Signer;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::{
     identity::UserAddress,
@@ -17,7 +18,62 @@ use crate::{
 pub struct Signature {
     pub signer: UserAddress,
     pub signature: String,
+    #[serde(flatten)]
+    pub freshness: Freshness,
+}
+
+// replay protection of a signed message: the nonce can be used only once, the
+// signature is not accepted after `expires_at` and only by the `domain` server
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Freshness {
     pub nonce: Nonce,
+    pub expires_at: u64,
+    // address of the server the message is signed for, missing in messages signed
+    // before domain separation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<UserAddress>,
+    // the signature also covers the canonical JSON of the whole request body
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub canonical: bool,
+    // format of the signed message, see `signed_message`. Missing in messages signed
+    // before versioning, which are version 0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    // canonical request body, set by the server for canonical signatures
+    #[serde(skip)]
+    pub body: Option<String>,
+}
+
+// JSON with sorted object keys and without whitespace, so the same value is always
+// encoded with the same bytes
+pub fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let entries: Vec<String> = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    format!("{}:{}", Value::from(key.as_str()), canonical_json(value))
+                })
+                .collect();
+            format!("{{{}}}", entries.join(","))
+        }
+        Value::Array(values) => {
+            let values: Vec<String> = values.iter().map(canonical_json).collect();
+            format!("[{}]", values.join(","))
+        }
+        scalar => scalar.to_string(),
+    }
+}
+
+// part of a request body covered by a canonical signature, every field except the signature
+pub fn canonical_body(body: &Value) -> String {
+    let mut body = body.clone();
+    if let Value::Object(map) = &mut body {
+        map.remove("signature");
+    }
+    canonical_json(&body)
 }
 
 pub async fn generate(private_key_hex: &str, message: String) -> Result<String, Error> {
@@ -26,6 +82,23 @@ pub async fn generate(private_key_hex: &str, message: String) -> Result<String,
     Ok(format!("0x{}", eth_signature))
 }
 
+// parses untrusted signature and signer strings, must never panic
+pub fn parse(signature: &str, signer: &str) -> Result<(EthSignature, H160), Error> {
+    let eth_signature = EthSignature::from_str(signature)?;
+    let signer_address = H160::from_str(signer).map_err(|e| {
+        Error::AddressParseError(format!("Failed to parse signer address: {:?}", e))
+    })?;
+    Ok((eth_signature, signer_address))
+}
+
+// checks the signature without consuming any nonce, replay protection is up to the caller
+pub fn verify(signature: &str, signer: &UserAddress, message: String) -> Result<(), Error> {
+    let (eth_signature, signer_address) = parse(signature, signer)?;
+    eth_signature
+        .verify(message, signer_address)
+        .map_err(Error::SignatureVerificationFailed)
+}
+
 pub async fn consume(
     signature: String,
     signer: &UserAddress,
@@ -33,23 +106,54 @@ pub async fn consume(
     nonce: Nonce,
     nonce_manager: &dyn NonceManager,
 ) -> Result<(), Error> {
-    let eth_signature = EthSignature::from_str(signature.as_str())?;
-    let signer_address = H160::from_str(signer).map_err(|e| {
-        Error::AddressParseError(format!("Failed to parse signer address: {:?}", e))
-    })?;
-    eth_signature
-        .verify(message, signer_address)
-        .map_err(Error::SignatureVerificationFailed)?;
+    verify(&signature, signer, message)?;
     nonce_manager.use_nonce(signer, nonce).await?;
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use crate::verify::{error::Error, nonce::InMemoryNonceManager, random_keypair};
 
     use super::*;
 
+    proptest! {
+        #[test]
+        fn test_parse_arbitrary_input(signature in ".*", signer in ".*") {
+            let _ = parse(&signature, &signer);
+        }
+
+        #[test]
+        fn test_parse_hex_like_input(
+            signature in "(0x)?[0-9a-fA-F]{0,140}",
+            signer in "(0x)?[0-9a-fA-F]{0,50}",
+        ) {
+            let _ = parse(&signature, &signer);
+        }
+    }
+
+    #[test]
+    fn test_canonical_json() {
+        let value: Value = serde_json::from_str(
+            r#"{ "b": [1, {"d": null, "c": "x\"y"}], "a": true, "signature": "0x1" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            canonical_json(&value),
+            r#"{"a":true,"b":[1,{"c":"x\"y","d":null}],"signature":"0x1"}"#
+        );
+        assert_eq!(
+            canonical_body(&value),
+            r#"{"a":true,"b":[1,{"c":"x\"y","d":null}]}"#
+        );
+        let reordered: Value =
+            serde_json::from_str(r#"{"a":true,"b":[1,{"c":"x\"y","d":null}]}"#).unwrap();
+        assert_eq!(canonical_body(&reordered), canonical_body(&value));
+        assert_eq!(canonical_json(&Value::from(1.5)), "1.5");
+    }
+
     #[async_std::test]
     async fn test_generate_and_verify_signature() {
         let nonce_manager = InMemoryNonceManager::default();
@@ -4,19 +4,23 @@ use crate::{
         error::Error,
         nonce::{Nonce, NonceManager},
         sign_message,
-        signature::Signature,
+        signature::{Freshness, Signature, generate, verify},
         verify_message,
     },
 };
 
 pub async fn vouch_sign(
     private_key_hex: &str,
+    domain: &UserAddress,
     vouchee: UserAddress,
+    expires_at: u64,
     nonce_manager: &dyn NonceManager,
 ) -> Result<Signature, Error> {
     sign_message(
         private_key_hex,
+        domain,
         &vouch_message_prefix(vouchee),
+        expires_at,
         nonce_manager,
     )
     .await
@@ -25,27 +29,239 @@ pub async fn vouch_sign(
 pub async fn vouch_verify(
     signature: String,
     signer: &UserAddress,
-    nonce: Nonce,
+    freshness: &Freshness,
     vouchee: UserAddress,
     nonce_manager: &dyn NonceManager,
 ) -> Result<(), Error> {
     verify_message(
         signature,
         signer,
-        nonce,
+        freshness,
         &vouch_message_prefix(vouchee),
         nonce_manager,
     )
     .await
 }
 
+// vouch stored with the client `timestamp` instead of the server time
+pub async fn vouch_at_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    vouchee: UserAddress,
+    timestamp: u64,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &vouch_at_message_prefix(vouchee, timestamp),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn vouch_at_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    vouchee: UserAddress,
+    timestamp: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &vouch_at_message_prefix(vouchee, timestamp),
+        nonce_manager,
+    )
+    .await
+}
+
+// vouchee agrees to be vouched for by `voucher`
+pub async fn vouch_consent_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    voucher: UserAddress,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &vouch_consent_message_prefix(voucher),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn vouch_consent_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    voucher: UserAddress,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &vouch_consent_message_prefix(voucher),
+        nonce_manager,
+    )
+    .await
+}
+
+// counter-signature of the external server for a vouch made by its user, bound
+// to the nonce of the user signature so it cannot be replayed on its own
+pub async fn external_vouch_sign(
+    server_private_key_hex: &str,
+    voucher: UserAddress,
+    vouchee: UserAddress,
+    nonce: Nonce,
+) -> Result<String, Error> {
+    generate(
+        server_private_key_hex,
+        external_vouch_message(voucher, vouchee, nonce),
+    )
+    .await
+}
+
+pub fn external_vouch_verify(
+    signature: &str,
+    server: &UserAddress,
+    voucher: UserAddress,
+    vouchee: UserAddress,
+    nonce: Nonce,
+) -> Result<(), Error> {
+    verify(
+        signature,
+        server,
+        external_vouch_message(voucher, vouchee, nonce),
+    )
+}
+
+// voucher signature of the encrypted note sent with a vouch, bound to the nonce of the
+// vouch signature, see `vouch_notes`
+pub async fn vouch_note_sign(
+    private_key_hex: &str,
+    vouchee: UserAddress,
+    note: &str,
+    nonce: Nonce,
+) -> Result<String, Error> {
+    generate(private_key_hex, vouch_note_message(vouchee, note, nonce)).await
+}
+
+pub fn vouch_note_verify(
+    signature: &str,
+    voucher: &UserAddress,
+    vouchee: UserAddress,
+    note: &str,
+    nonce: Nonce,
+) -> Result<(), Error> {
+    verify(signature, voucher, vouch_note_message(vouchee, note, nonce))
+}
+
+// request of the vouchee to read the notes of its vouchers
+pub async fn vouch_notes_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        VOUCH_NOTES_MESSAGE_PREFIX,
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn vouch_notes_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        VOUCH_NOTES_MESSAGE_PREFIX,
+        nonce_manager,
+    )
+    .await
+}
+
+// confirmation of a pending vouch, see `pending_vouches`
+pub async fn vouch_confirm_sign(
+    private_key_hex: &str,
+    domain: &UserAddress,
+    vouchee: UserAddress,
+    expires_at: u64,
+    nonce_manager: &dyn NonceManager,
+) -> Result<Signature, Error> {
+    sign_message(
+        private_key_hex,
+        domain,
+        &vouch_confirm_message_prefix(vouchee),
+        expires_at,
+        nonce_manager,
+    )
+    .await
+}
+
+pub async fn vouch_confirm_verify(
+    signature: String,
+    signer: &UserAddress,
+    freshness: &Freshness,
+    vouchee: UserAddress,
+    nonce_manager: &dyn NonceManager,
+) -> Result<(), Error> {
+    verify_message(
+        signature,
+        signer,
+        freshness,
+        &vouch_confirm_message_prefix(vouchee),
+        nonce_manager,
+    )
+    .await
+}
+
 fn vouch_message_prefix(user: UserAddress) -> String {
     format!("vouch/{user}")
 }
 
+fn vouch_at_message_prefix(user: UserAddress, timestamp: u64) -> String {
+    format!("vouch_at/{user}/{timestamp}")
+}
+
+fn vouch_confirm_message_prefix(user: UserAddress) -> String {
+    format!("confirm_vouch/{user}")
+}
+
+fn vouch_consent_message_prefix(voucher: UserAddress) -> String {
+    format!("vouch_consent/{voucher}")
+}
+
+fn external_vouch_message(voucher: UserAddress, vouchee: UserAddress, nonce: Nonce) -> String {
+    format!("external_vouch/{voucher}/{vouchee}/{nonce}")
+}
+
+const VOUCH_NOTES_MESSAGE_PREFIX: &str = "vouch_notes";
+
+fn vouch_note_message(vouchee: UserAddress, note: &str, nonce: Nonce) -> String {
+    format!("vouch_note/{vouchee}/{note}/{nonce}")
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::verify::{nonce::InMemoryNonceManager, random_keypair};
+    use crate::verify::{expires_in, nonce::InMemoryNonceManager, random_keypair, tests::DOMAIN};
 
     use super::*;
 
@@ -54,14 +270,20 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let vouchee: String = "vouchee".to_string();
-        let signature = vouch_sign(&private_key, vouchee.clone(), &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = vouch_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            vouchee.clone(),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         assert!(
             vouch_verify(
                 signature.signature,
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 vouchee,
                 &nonce_manager
             )
@@ -75,15 +297,21 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let vouchee = "vouchee".to_string();
-        let signature = vouch_sign(&private_key, vouchee.clone(), &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = vouch_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            vouchee.clone(),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         let bad_user = "bad user".to_string();
         assert!(
             vouch_verify(
                 signature.signature,
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 bad_user,
                 &nonce_manager
             )
@@ -97,15 +325,24 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let vouchee = "vouchee".to_string();
-        let signature = vouch_sign(&private_key, vouchee.clone(), &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = vouch_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            vouchee.clone(),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         let bad_nonce = 6060;
         assert!(
             vouch_verify(
                 signature.signature,
                 &signature.signer,
-                bad_nonce,
+                &Freshness {
+                    nonce: bad_nonce,
+                    ..signature.freshness.clone()
+                },
                 vouchee,
                 &nonce_manager
             )
@@ -119,14 +356,20 @@ mod tests {
         let (private_key, _) = random_keypair();
         let nonce_manager = InMemoryNonceManager::default();
         let vouchee = "vouchee".to_string();
-        let signature = vouch_sign(&private_key, vouchee.clone(), &nonce_manager)
-            .await
-            .expect("Should generate signature");
+        let signature = vouch_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            vouchee.clone(),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
         assert!(
             vouch_verify(
                 signature.signature.clone(),
                 &signature.signer,
-                signature.nonce,
+                &signature.freshness,
                 vouchee.clone(),
                 &nonce_manager
             )
@@ -137,7 +380,7 @@ mod tests {
         let err = vouch_verify(
             signature.signature,
             &signature.signer,
-            signature.nonce,
+            &signature.freshness,
             vouchee,
             &nonce_manager,
         )
@@ -145,4 +388,95 @@ mod tests {
         .unwrap_err();
         assert!(matches!(err, Error::NonceError(_)));
     }
+
+    #[async_std::test]
+    async fn test_consent() {
+        let (private_key, vouchee) = random_keypair();
+        let nonce_manager = InMemoryNonceManager::default();
+        let voucher = "voucher".to_string();
+        let signature = vouch_consent_sign(
+            &private_key,
+            &DOMAIN.to_string(),
+            voucher.clone(),
+            expires_in(60),
+            &nonce_manager,
+        )
+        .await
+        .expect("Should generate signature");
+        // consent is bound to the voucher and is not a vouch
+        assert!(
+            vouch_verify(
+                signature.signature.clone(),
+                &vouchee,
+                &signature.freshness,
+                voucher.clone(),
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            vouch_consent_verify(
+                signature.signature.clone(),
+                &vouchee,
+                &signature.freshness,
+                "other".to_string(),
+                &nonce_manager
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            vouch_consent_verify(
+                signature.signature,
+                &vouchee,
+                &signature.freshness,
+                voucher,
+                &nonce_manager
+            )
+            .await
+            .is_ok()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_external_vouch() {
+        let (server_key, server) = random_keypair();
+        let (_, other_server) = random_keypair();
+        let voucher = "voucher".to_string();
+        let vouchee = "vouchee".to_string();
+        let signature = external_vouch_sign(&server_key, voucher.clone(), vouchee.clone(), 1)
+            .await
+            .expect("Should generate signature");
+        assert!(
+            external_vouch_verify(&signature, &server, voucher.clone(), vouchee.clone(), 1).is_ok()
+        );
+        assert!(
+            external_vouch_verify(
+                &signature,
+                &other_server,
+                voucher.clone(),
+                vouchee.clone(),
+                1
+            )
+            .is_err()
+        );
+        assert!(
+            external_vouch_verify(&signature, &server, voucher.clone(), vouchee.clone(), 2)
+                .is_err()
+        );
+        assert!(external_vouch_verify(&signature, &server, vouchee, voucher, 1).is_err());
+    }
+
+    #[async_std::test]
+    async fn test_vouch_note() {
+        let (private_key, voucher) = random_keypair();
+        let vouchee = "vouchee".to_string();
+        let signature = vouch_note_sign(&private_key, vouchee.clone(), "0x01", 1)
+            .await
+            .expect("Should generate signature");
+        assert!(vouch_note_verify(&signature, &voucher, vouchee.clone(), "0x01", 1).is_ok());
+        assert!(vouch_note_verify(&signature, &voucher, vouchee.clone(), "0x02", 1).is_err());
+        assert!(vouch_note_verify(&signature, &voucher, vouchee, "0x01", 2).is_err());
+    }
 }
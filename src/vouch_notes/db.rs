@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use sqlx::Row;
+
+use crate::{
+    encryption::{FieldCipher, rotate_column},
+    identity::UserAddress,
+    pools::{self, MeteredPool},
+    vouch_notes::{error::Error, storage::VouchNoteStorage},
+};
+
+pub struct DatabaseVouchNoteStorage {
+    pool: MeteredPool,
+    cipher: FieldCipher,
+}
+
+impl DatabaseVouchNoteStorage {
+    pub async fn new(url: &str) -> Result<Self, Error> {
+        Self::with_cipher(url, FieldCipher::default()).await
+    }
+
+    pub async fn with_cipher(url: &str, cipher: FieldCipher) -> Result<Self, Error> {
+        let pool = pools::connect("vouch_notes", url).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS vouch_notes (vouchee TEXT NOT NULL, voucher TEXT NOT NULL, note TEXT NOT NULL, PRIMARY KEY(vouchee, voucher))",
+        )
+        .execute(&pool)
+        .await?;
+        // notes are encrypted by the voucher, only the addresses are encrypted here
+        rotate_column(&pool, &cipher, "vouch_notes", "vouchee").await?;
+        rotate_column(&pool, &cipher, "vouch_notes", "voucher").await?;
+        Ok(Self { pool, cipher })
+    }
+
+    pub fn connection_pool(&self) -> &MeteredPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl VouchNoteStorage for DatabaseVouchNoteStorage {
+    async fn set_note(
+        &self,
+        voucher: UserAddress,
+        vouchee: UserAddress,
+        note: String,
+    ) -> Result<(), Error> {
+        sqlx::query("REPLACE INTO vouch_notes (vouchee, voucher, note) VALUES (?, ?, ?)")
+            .bind(self.cipher.encode(&vouchee))
+            .bind(self.cipher.encode(&voucher))
+            .bind(note)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn notes(&self, vouchee: &UserAddress) -> Result<HashMap<UserAddress, String>, Error> {
+        let rows = sqlx::query("SELECT voucher, note FROM vouch_notes WHERE vouchee = ?")
+            .bind(self.cipher.encode(vouchee))
+            .fetch_all(&self.pool)
+            .await?;
+        let mut notes = HashMap::new();
+        for row in rows {
+            notes.insert(
+                self.cipher.decode(&row.get::<String, _>(0))?,
+                row.get::<String, _>(1),
+            );
+        }
+        Ok(notes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = DatabaseVouchNoteStorage::new("sqlite::memory:")
+            .await
+            .unwrap();
+        let vouchee = "vouchee".to_string();
+        assert!(storage.notes(&vouchee).await.unwrap().is_empty());
+        storage
+            .set_note("a".to_string(), vouchee.clone(), "0x01".to_string())
+            .await
+            .unwrap();
+        storage
+            .set_note("a".to_string(), vouchee.clone(), "0x02".to_string())
+            .await
+            .unwrap();
+        storage
+            .set_note("b".to_string(), vouchee.clone(), "0x03".to_string())
+            .await
+            .unwrap();
+        storage
+            .set_note("a".to_string(), "other".to_string(), "0x04".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.notes(&vouchee).await.unwrap(),
+            HashMap::from([
+                ("a".to_string(), "0x02".to_string()),
+                ("b".to_string(), "0x03".to_string()),
+            ])
+        );
+    }
+}
@@ -0,0 +1,43 @@
+// Notes attached to vouches for the vouchee.
+//
+// A voucher may send a `note` with `POST /vouch/<user>`, encrypted by the client to the public
+// key of the vouchee (ECIES), so the server only stores and returns opaque bytes. Notes of the
+// current vouchers are served only to the vouchee, by a `POST /vouch_notes` request signed by
+// the vouchee. Vouching again without a note keeps the previous one.
+
+pub mod db;
+pub mod error;
+pub mod storage;
+
+// longest encrypted note in bytes, enough for a few sentences with the ECIES overhead
+pub const MAX_NOTE_BYTES: usize = 512;
+
+// notes are `0x` prefixed hex of the ciphertext
+pub fn is_valid_note(note: &str) -> bool {
+    let Some(hex) = note.strip_prefix("0x") else {
+        return false;
+    };
+    match hex::decode(hex) {
+        Ok(bytes) => !bytes.is_empty() && bytes.len() <= MAX_NOTE_BYTES,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_note() {
+        assert!(is_valid_note("0x04ab"));
+        assert!(is_valid_note(&format!("0x{}", "ab".repeat(MAX_NOTE_BYTES))));
+        assert!(!is_valid_note(&format!(
+            "0x{}",
+            "ab".repeat(MAX_NOTE_BYTES + 1)
+        )));
+        assert!(!is_valid_note("04ab"));
+        assert!(!is_valid_note("0x"));
+        assert!(!is_valid_note("0x4ab"));
+        assert!(!is_valid_note("0xhello"));
+    }
+}
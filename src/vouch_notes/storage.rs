@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::{identity::UserAddress, vouch_notes::error::Error};
+
+// encrypted notes of vouchers to their vouchees
+#[async_trait]
+pub trait VouchNoteStorage: Send + Sync {
+    async fn set_note(
+        &self,
+        voucher: UserAddress,
+        vouchee: UserAddress,
+        note: String,
+    ) -> Result<(), Error>;
+    // notes for the vouchee by voucher
+    async fn notes(&self, vouchee: &UserAddress) -> Result<HashMap<UserAddress, String>, Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryVouchNoteStorage {
+    // vouchee - voucher - note
+    notes: RwLock<HashMap<UserAddress, HashMap<UserAddress, String>>>,
+}
+
+#[async_trait]
+impl VouchNoteStorage for InMemoryVouchNoteStorage {
+    async fn set_note(
+        &self,
+        voucher: UserAddress,
+        vouchee: UserAddress,
+        note: String,
+    ) -> Result<(), Error> {
+        self.notes
+            .write()
+            .await
+            .entry(vouchee)
+            .or_default()
+            .insert(voucher, note);
+        Ok(())
+    }
+
+    async fn notes(&self, vouchee: &UserAddress) -> Result<HashMap<UserAddress, String>, Error> {
+        Ok(self
+            .notes
+            .read()
+            .await
+            .get(vouchee)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_basic() {
+        let storage = InMemoryVouchNoteStorage::default();
+        let vouchee = "vouchee".to_string();
+        assert!(storage.notes(&vouchee).await.unwrap().is_empty());
+        storage
+            .set_note("a".to_string(), vouchee.clone(), "0x01".to_string())
+            .await
+            .unwrap();
+        storage
+            .set_note("a".to_string(), vouchee.clone(), "0x02".to_string())
+            .await
+            .unwrap();
+        storage
+            .set_note("a".to_string(), "other".to_string(), "0x03".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.notes(&vouchee).await.unwrap(),
+            HashMap::from([("a".to_string(), "0x02".to_string())])
+        );
+    }
+}
@@ -0,0 +1,13 @@
+// JavaScript bindings to verify balances without trusting the server. The subgraph is the
+// response of `/subgraph/:user`, the balance is recomputed at its timestamp.
+
+use wasm_bindgen::prelude::*;
+
+use crate::identity::subgraph::Subgraph;
+
+// balance of the exported user as a decimal string, amounts may not fit JavaScript numbers
+#[wasm_bindgen(js_name = verifyBalance)]
+pub async fn verify_balance(subgraph: String) -> Result<String, JsError> {
+    let subgraph: Subgraph = serde_json::from_str(&subgraph)?;
+    Ok(subgraph.balance().await?.to_string())
+}
@@ -0,0 +1,505 @@
+// Batches vouch and penalty writes of concurrent requests.
+//
+// Writes are queued and applied in order by a single task, in batches of up to `max_batch`
+// writes collected for at most `flush_interval_ms` after the first write of a batch. SQL
+// storages commit a batch in one transaction instead of one per write.
+//
+// A write returns only after its batch was applied, so an acknowledged write is as durable
+// as without the queue and is seen by every following read. Writes still queued when the
+// process stops were never acknowledged. When `capacity` writes are queued, new writes wait
+// for a free slot. A failed batch is retried write by write, so every caller gets the result
+// of its own write. If the task stopped, writes fail instead of being reported as applied.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_std::channel::{Receiver, Sender, bounded};
+use async_trait::async_trait;
+
+use crate::{
+    config::WriteQueueSection,
+    identity::{
+        ModeratorProof, SystemPenalty, UserAddress,
+        error::Error,
+        punish::storage::{PenaltyStorage, PenaltyWrite},
+        vouch::storage::{VouchStorage, VouchWrite},
+    },
+    storage::Storage,
+};
+
+struct Pending<W> {
+    write: W,
+    done: Sender<Result<(), Error>>,
+}
+
+// storage the queued writes are applied to
+#[async_trait]
+trait BatchTarget<W>: Send + Sync + 'static {
+    async fn apply(&self, writes: Vec<W>) -> Result<(), Error>;
+}
+
+#[async_trait]
+impl BatchTarget<VouchWrite> for Arc<dyn VouchStorage> {
+    async fn apply(&self, writes: Vec<VouchWrite>) -> Result<(), Error> {
+        self.write_batch(writes).await
+    }
+}
+
+#[async_trait]
+impl BatchTarget<PenaltyWrite> for Arc<dyn PenaltyStorage> {
+    async fn apply(&self, writes: Vec<PenaltyWrite>) -> Result<(), Error> {
+        self.write_batch(writes).await
+    }
+}
+
+struct Queue<W> {
+    sender: Sender<Pending<W>>,
+}
+
+impl<W: Clone + Send + 'static> Queue<W> {
+    // the task stops when the queue is dropped
+    fn spawn(target: impl BatchTarget<W>, config: &WriteQueueSection) -> Self {
+        let (sender, receiver) = bounded(config.capacity.max(1));
+        async_std::task::spawn(run(
+            receiver,
+            target,
+            config.max_batch.max(1),
+            Duration::from_millis(config.flush_interval_ms),
+        ));
+        Self { sender }
+    }
+
+    async fn write(&self, write: W) -> Result<(), Error> {
+        let (done, result) = bounded(1);
+        // the task lives as long as the sender unless it panics. A write without a reply may
+        // have been lost, so it is never reported as applied.
+        self.sender
+            .send(Pending { write, done })
+            .await
+            .map_err(|_| Error::WriteQueueClosed)?;
+        result.recv().await.map_err(|_| Error::WriteQueueClosed)?
+    }
+}
+
+async fn run<W: Clone>(
+    receiver: Receiver<Pending<W>>,
+    target: impl BatchTarget<W>,
+    max_batch: usize,
+    flush_interval: Duration,
+) {
+    while let Ok(first) = receiver.recv().await {
+        let deadline = Instant::now() + flush_interval;
+        let mut batch = vec![first];
+        while batch.len() < max_batch {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match async_std::future::timeout(remaining, receiver.recv()).await {
+                Ok(Ok(pending)) => batch.push(pending),
+                _ => break,
+            }
+        }
+
+        let writes = batch.iter().map(|pending| pending.write.clone()).collect();
+        if let Err(e) = target.apply(writes).await {
+            log::warn!(
+                "Failed to apply a batch of {} writes, retrying one by one: {}",
+                batch.len(),
+                e
+            );
+            for pending in batch {
+                let result = target.apply(vec![pending.write]).await;
+                let _ = pending.done.send(result).await;
+            }
+            continue;
+        }
+        for pending in batch {
+            let _ = pending.done.send(Ok(())).await;
+        }
+    }
+}
+
+// vouch and penalty storage that passes writes through a queue, reads go directly to the
+// wrapped storages
+pub struct QueuedStorage {
+    vouches: Arc<dyn VouchStorage>,
+    penalties: Arc<dyn PenaltyStorage>,
+    vouch_queue: Queue<VouchWrite>,
+    penalty_queue: Queue<PenaltyWrite>,
+}
+
+impl QueuedStorage {
+    pub fn new(
+        vouches: Arc<dyn VouchStorage>,
+        penalties: Arc<dyn PenaltyStorage>,
+        config: &WriteQueueSection,
+    ) -> Self {
+        Self {
+            vouch_queue: Queue::spawn(vouches.clone(), config),
+            penalty_queue: Queue::spawn(penalties.clone(), config),
+            vouches,
+            penalties,
+        }
+    }
+}
+
+// replaces vouch and penalty storages of the bundle with a queue in front of them
+pub fn queue_writes(mut storage: Storage, config: &WriteQueueSection) -> Storage {
+    let queue = Arc::new(QueuedStorage::new(
+        storage.vouch_storage,
+        storage.penalty_storage,
+        config,
+    ));
+    storage.vouch_storage = queue.clone();
+    storage.penalty_storage = queue;
+    storage
+}
+
+#[async_trait]
+impl VouchStorage for QueuedStorage {
+    async fn vouch(&self, from: UserAddress, to: UserAddress, timestamp: u64) -> Result<(), Error> {
+        self.vouch_queue
+            .write(VouchWrite::Vouch {
+                from,
+                to,
+                timestamp,
+            })
+            .await
+    }
+
+    async fn vouchers_with_time(
+        &self,
+        user: &UserAddress,
+    ) -> Result<HashMap<UserAddress, u64>, Error> {
+        self.vouches.vouchers_with_time(user).await
+    }
+
+    async fn vouchees_with_time(
+        &self,
+        user: &UserAddress,
+    ) -> Result<HashMap<UserAddress, u64>, Error> {
+        self.vouches.vouchees_with_time(user).await
+    }
+
+    async fn remove_vouch(&self, voucher: UserAddress, vouchee: UserAddress) -> Result<(), Error> {
+        self.vouch_queue
+            .write(VouchWrite::RemoveVouch { voucher, vouchee })
+            .await
+    }
+
+    async fn all_vouches(&self) -> Result<Vec<(UserAddress, UserAddress, u64)>, Error> {
+        self.vouches.all_vouches().await
+    }
+}
+
+#[async_trait]
+impl PenaltyStorage for QueuedStorage {
+    async fn set_moderator_penalty(
+        &self,
+        user: UserAddress,
+        proof: ModeratorProof,
+    ) -> Result<(), Error> {
+        self.penalty_queue
+            .write(PenaltyWrite::SetModeratorPenalty { user, proof })
+            .await
+    }
+
+    async fn set_forgotten_penalty(
+        &self,
+        user: UserAddress,
+        vouchee: UserAddress,
+        penalty: SystemPenalty,
+    ) -> Result<(), Error> {
+        self.penalty_queue
+            .write(PenaltyWrite::SetForgottenPenalty {
+                user,
+                vouchee,
+                penalty,
+            })
+            .await
+    }
+
+    async fn remove_forgotten(
+        &self,
+        user: UserAddress,
+        forgotten: &UserAddress,
+    ) -> Result<(), Error> {
+        self.penalty_queue
+            .write(PenaltyWrite::RemoveForgotten {
+                user,
+                forgotten: forgotten.clone(),
+            })
+            .await
+    }
+
+    async fn moderator_penalty(&self, user: &UserAddress) -> Result<Option<ModeratorProof>, Error> {
+        self.penalties.moderator_penalty(user).await
+    }
+
+    async fn remove_moderator_penalty(&self, user: &UserAddress) -> Result<(), Error> {
+        self.penalty_queue
+            .write(PenaltyWrite::RemoveModeratorPenalty { user: user.clone() })
+            .await
+    }
+
+    async fn forgotten_penalty(
+        &self,
+        user: &UserAddress,
+        forgotten: &UserAddress,
+    ) -> Result<Option<SystemPenalty>, Error> {
+        self.penalties.forgotten_penalty(user, forgotten).await
+    }
+
+    async fn forgotten_users(&self, user: &UserAddress) -> Result<HashSet<UserAddress>, Error> {
+        self.penalties.forgotten_users(user).await
+    }
+
+    async fn all_moderator_penalties(&self) -> Result<Vec<(UserAddress, ModeratorProof)>, Error> {
+        self.penalties.all_moderator_penalties().await
+    }
+
+    async fn recent_moderator_penalties(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<(UserAddress, ModeratorProof)>, Error> {
+        self.penalties.recent_moderator_penalties(limit).await
+    }
+
+    async fn all_forgotten_penalties(
+        &self,
+    ) -> Result<Vec<(UserAddress, UserAddress, SystemPenalty)>, Error> {
+        self.penalties.all_forgotten_penalties().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures::future::join_all;
+
+    use super::*;
+    use crate::identity::{
+        IdtAmount, punish::db::DatabasePenaltyStorage, punish::storage::InMemoryPenaltyStorage,
+        vouch::db::DatabaseVouchStorage, vouch::storage::InMemoryVouchStorage,
+    };
+
+    // records the size of every batch, batches vouching for `rejected` fail
+    #[derive(Default)]
+    struct RecordingStorage {
+        vouches: InMemoryVouchStorage,
+        batches: Mutex<Vec<usize>>,
+    }
+
+    const REJECTED: &str = "rejected";
+
+    #[async_trait]
+    impl VouchStorage for RecordingStorage {
+        async fn vouch(
+            &self,
+            from: UserAddress,
+            to: UserAddress,
+            timestamp: u64,
+        ) -> Result<(), Error> {
+            self.vouches.vouch(from, to, timestamp).await
+        }
+
+        async fn vouchers_with_time(
+            &self,
+            user: &UserAddress,
+        ) -> Result<HashMap<UserAddress, u64>, Error> {
+            self.vouches.vouchers_with_time(user).await
+        }
+
+        async fn vouchees_with_time(
+            &self,
+            user: &UserAddress,
+        ) -> Result<HashMap<UserAddress, u64>, Error> {
+            self.vouches.vouchees_with_time(user).await
+        }
+
+        async fn remove_vouch(
+            &self,
+            voucher: UserAddress,
+            vouchee: UserAddress,
+        ) -> Result<(), Error> {
+            self.vouches.remove_vouch(voucher, vouchee).await
+        }
+
+        async fn all_vouches(&self) -> Result<Vec<(UserAddress, UserAddress, u64)>, Error> {
+            self.vouches.all_vouches().await
+        }
+
+        // all or nothing, like a transaction
+        async fn write_batch(&self, writes: Vec<VouchWrite>) -> Result<(), Error> {
+            self.batches.lock().unwrap().push(writes.len());
+            if writes
+                .iter()
+                .any(|w| matches!(w, VouchWrite::Vouch { to, .. } if to == REJECTED))
+            {
+                return Err(Error::MaxBalanceExceeded);
+            }
+            self.vouches.write_batch(writes).await
+        }
+    }
+
+    fn config(max_batch: usize, capacity: usize) -> WriteQueueSection {
+        WriteQueueSection {
+            enabled: true,
+            max_batch,
+            flush_interval_ms: 50,
+            capacity,
+        }
+    }
+
+    #[async_std::test]
+    async fn test_batches() {
+        let recording = Arc::new(RecordingStorage::default());
+        let storage = QueuedStorage::new(
+            recording.clone(),
+            Arc::new(InMemoryPenaltyStorage::default()),
+            &config(4, 100),
+        );
+        let results =
+            join_all((0..10).map(|i| storage.vouch("a".into(), format!("user{i}"), i))).await;
+        assert!(results.iter().all(Result::is_ok));
+        // every acknowledged write is visible
+        assert_eq!(
+            storage.vouchees_with_time(&"a".into()).await.unwrap().len(),
+            10
+        );
+        let batches = recording.batches.lock().unwrap().clone();
+        assert_eq!(batches.iter().sum::<usize>(), 10);
+        assert!(batches.len() < 10);
+        assert!(batches.iter().all(|size| *size <= 4));
+
+        storage
+            .remove_vouch("a".into(), "user0".into())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.vouchees_with_time(&"a".into()).await.unwrap().len(),
+            9
+        );
+    }
+
+    #[async_std::test]
+    async fn test_failed_batch() {
+        let recording = Arc::new(RecordingStorage::default());
+        let storage = QueuedStorage::new(
+            recording.clone(),
+            Arc::new(InMemoryPenaltyStorage::default()),
+            &config(10, 100),
+        );
+        let results = join_all(
+            ["userA", REJECTED, "userB"].map(|to| storage.vouch("a".into(), to.into(), 1)),
+        )
+        .await;
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(Error::MaxBalanceExceeded)));
+        assert!(results[2].is_ok());
+        let vouchees = storage.vouchees_with_time(&"a".into()).await.unwrap();
+        assert_eq!(vouchees.len(), 2);
+        assert!(!vouchees.contains_key(REJECTED));
+    }
+
+    #[async_std::test]
+    async fn test_backpressure() {
+        let recording = Arc::new(RecordingStorage::default());
+        // a single slot, writes wait for each other but none is lost
+        let storage = QueuedStorage::new(
+            recording.clone(),
+            Arc::new(InMemoryPenaltyStorage::default()),
+            &config(1, 1),
+        );
+        let results =
+            join_all((0..5).map(|i| storage.vouch("a".into(), format!("user{i}"), i))).await;
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(*recording.batches.lock().unwrap(), vec![1; 5]);
+        assert_eq!(
+            storage.vouchees_with_time(&"a".into()).await.unwrap().len(),
+            5
+        );
+    }
+
+    #[async_std::test]
+    async fn test_dead_worker() {
+        // the worker is gone before the write is sent
+        let (sender, receiver) = bounded(1);
+        drop(receiver);
+        let queue = Queue::<VouchWrite> { sender };
+        let write = VouchWrite::RemoveVouch {
+            voucher: "a".into(),
+            vouchee: "b".into(),
+        };
+        assert!(matches!(
+            queue.write(write.clone()).await,
+            Err(Error::WriteQueueClosed)
+        ));
+
+        // the worker takes the write and stops without a reply
+        let (sender, receiver) = bounded::<Pending<VouchWrite>>(1);
+        let worker = async_std::task::spawn(async move {
+            drop(receiver.recv().await);
+        });
+        let queue = Queue { sender };
+        assert!(matches!(
+            queue.write(write).await,
+            Err(Error::WriteQueueClosed)
+        ));
+        worker.await;
+    }
+
+    #[async_std::test]
+    async fn test_database() {
+        let url = "sqlite::memory:";
+        let storage = QueuedStorage::new(
+            Arc::new(DatabaseVouchStorage::new(url).await.unwrap()),
+            Arc::new(DatabasePenaltyStorage::new(url).await.unwrap()),
+            &config(10, 100),
+        );
+        let results =
+            join_all((0..5).map(|i| storage.vouch("a".into(), format!("user{i}"), i))).await;
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(storage.all_vouches().await.unwrap().len(), 5);
+
+        let proof = ModeratorProof {
+            moderator: "moderator".into(),
+            amount: IdtAmount::new(100),
+            proof_id: 1,
+            timestamp: 2,
+        };
+        let penalty = SystemPenalty {
+            amount: IdtAmount::new(10),
+            timestamp: 3,
+        };
+        let (set, forgotten) = futures::join!(
+            storage.set_moderator_penalty("user0".into(), proof.clone()),
+            storage.set_forgotten_penalty("user1".into(), "user2".into(), penalty.clone())
+        );
+        set.unwrap();
+        forgotten.unwrap();
+        assert_eq!(
+            storage.moderator_penalty(&"user0".into()).await.unwrap(),
+            Some(proof)
+        );
+        assert_eq!(
+            storage
+                .forgotten_penalty(&"user1".into(), &"user2".into())
+                .await
+                .unwrap(),
+            Some(penalty)
+        );
+        storage
+            .remove_moderator_penalty(&"user0".into())
+            .await
+            .unwrap();
+        storage
+            .remove_forgotten("user1".into(), &"user2".into())
+            .await
+            .unwrap();
+        assert!(storage.all_moderator_penalties().await.unwrap().is_empty());
+        assert!(storage.all_forgotten_penalties().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,267 @@
+// End-to-end test of the server binary: starts it on a free port with a sqlite file database,
+// walks users through their whole lifecycle with real HTTP requests and restarts the server to
+// check that the state survives.
+
+use std::{
+    net::TcpListener,
+    path::Path,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use identity_server::{
+    identity::{IdtAmount, UserAddress},
+    verify::{
+        admins::admin_set_moderator_message_prefix,
+        expires_in,
+        forget::forget_sign,
+        nonce::{InMemoryNonceManager, NonceManager},
+        private_key_to_address,
+        proof::proof_sign,
+        punish::punish_sign,
+        random_keypair, sign_message,
+        signature::Signature,
+        vouch::vouch_sign,
+    },
+};
+use serde_json::{Value, json};
+use tempdir::TempDir;
+
+const GENESIS_BALANCE: u64 = 1000;
+// seconds to wait for the server to accept requests
+const STARTUP_TIMEOUT: u64 = 30;
+
+struct TestServer {
+    process: Child,
+    url: String,
+}
+
+impl TestServer {
+    async fn start(dir: &Path, private_key: &str) -> Self {
+        // the port is released right away, so the server can bind it
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let process = Command::new(env!("CARGO_BIN_EXE_identity_server"))
+            .current_dir(dir)
+            .env("HOST", "127.0.0.1")
+            .env("PORT", port.to_string())
+            .env("SERVER_PRIVATE_KEY", private_key)
+            .env("RUST_LOG", "warn")
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("Should start the server");
+        let server = Self {
+            process,
+            url: format!("http://127.0.0.1:{port}"),
+        };
+        for _ in 0..STARTUP_TIMEOUT * 10 {
+            // the body is read, so the pooled connection can be reused
+            if surf::get(format!("{}/time", server.url))
+                .recv_string()
+                .await
+                .is_ok()
+            {
+                return server;
+            }
+            async_std::task::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("server did not start in {STARTUP_TIMEOUT} seconds");
+    }
+
+    async fn get(&self, path: &str) -> Value {
+        let mut response = surf::get(format!("{}{path}", self.url)).await.unwrap();
+        assert_eq!(response.status(), 200, "GET {path}");
+        response.body_json().await.unwrap()
+    }
+
+    async fn post(&self, path: &str, body: Value) -> Value {
+        let mut response = surf::post(format!("{}{path}", self.url))
+            .body_json(&body)
+            .unwrap()
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.body_string().await.unwrap();
+        assert_eq!(status, 200, "POST {path}: {body}");
+        serde_json::from_str(&body).unwrap_or(Value::Null)
+    }
+
+    async fn balance(&self, user: &UserAddress) -> IdtAmount {
+        self.get(&format!("/idt/{user}")).await["idt"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+// request body signed by `signature`, `from` is the signer unless set in `body`.
+// The nonce is marked as used so that the next signature of the signer gets a new one
+async fn signed(nonces: &InMemoryNonceManager, signature: Signature, mut body: Value) -> Value {
+    nonces
+        .use_nonce(&signature.signer, signature.freshness.nonce)
+        .await
+        .unwrap();
+    let fields = body.as_object_mut().unwrap();
+    fields
+        .entry("from")
+        .or_insert_with(|| signature.signer.clone().into());
+    fields.insert("signature".into(), signature.signature.into());
+    fields.insert("nonce".into(), signature.freshness.nonce.into());
+    fields.insert("expires_at".into(), signature.freshness.expires_at.into());
+    fields.insert("domain".into(), signature.freshness.domain.into());
+    body
+}
+
+#[async_std::test]
+async fn test_lifecycle() {
+    let dir = TempDir::new("e2e").unwrap();
+    let (server_key, _) = random_keypair();
+    let domain = private_key_to_address(&server_key).unwrap();
+    let (admin_key, admin) = random_keypair();
+    let (moderator_key, moderator) = random_keypair();
+    let (genesis_key, genesis_user) = random_keypair();
+    let (user_a_key, user_a) = random_keypair();
+    let (user_b_key, user_b) = random_keypair();
+    let (_, user_c) = random_keypair();
+    let nonces = InMemoryNonceManager::default();
+
+    let database = dir.path().join("identity.db");
+    let config = json!({
+        "admins": {"admins": [admin], "moderators": []},
+        "storage": {"url": format!("sqlite://{}?mode=rwc", database.display())},
+    });
+    std::fs::write(dir.path().join("config.json"), config.to_string()).unwrap();
+    let genesis = json!({ genesis_user.clone(): GENESIS_BALANCE });
+    std::fs::write(dir.path().join("genesis.json"), genesis.to_string()).unwrap();
+
+    let server = TestServer::start(dir.path(), &server_key).await;
+    assert_eq!(
+        server.balance(&genesis_user).await,
+        IdtAmount::new(GENESIS_BALANCE)
+    );
+
+    let prefix = admin_set_moderator_message_prefix(moderator.clone());
+    let signature = sign_message(&admin_key, &domain, &prefix, expires_in(60), &nonces)
+        .await
+        .unwrap();
+    server
+        .post(
+            &format!("/add_moderator/{moderator}"),
+            signed(&nonces, signature, json!({})).await,
+        )
+        .await;
+    assert_eq!(
+        server.get(&format!("/is_moderator/{moderator}")).await["is_moderator"],
+        true
+    );
+
+    let signature = proof_sign(
+        &moderator_key,
+        &domain,
+        user_a.clone(),
+        IdtAmount::new(100),
+        1,
+        expires_in(60),
+        &nonces,
+    )
+    .await
+    .unwrap();
+    server
+        .post(
+            &format!("/proof/{user_a}"),
+            signed(&nonces, signature, json!({"amount": 100, "proof_id": 1})).await,
+        )
+        .await;
+    assert_eq!(server.balance(&user_a).await, IdtAmount::new(100));
+
+    // genesis -> A -> B -> C
+    for (key, vouchee) in [
+        (&genesis_key, &user_a),
+        (&user_a_key, &user_b),
+        (&user_b_key, &user_c),
+    ] {
+        let signature = vouch_sign(key, &domain, vouchee.clone(), expires_in(60), &nonces)
+            .await
+            .unwrap();
+        let from = json!({"user": signature.signer});
+        server
+            .post(
+                &format!("/vouch/{vouchee}"),
+                signed(&nonces, signature, json!({"from": from})).await,
+            )
+            .await;
+    }
+    let balance_a = server.balance(&user_a).await;
+    let balance_b = server.balance(&user_b).await;
+    assert!(balance_a > IdtAmount::new(100));
+    assert!(balance_b > IdtAmount::new(0));
+    assert!(server.balance(&user_c).await > IdtAmount::new(0));
+
+    let signature = punish_sign(
+        &moderator_key,
+        &domain,
+        user_c.clone(),
+        IdtAmount::new(50),
+        2,
+        expires_in(60),
+        &nonces,
+    )
+    .await
+    .unwrap();
+    server
+        .post(
+            &format!("/punish/{user_c}"),
+            signed(&nonces, signature, json!({"amount": 50, "proof_id": 2})).await,
+        )
+        .await;
+    assert_eq!(server.balance(&user_c).await, IdtAmount::new(0));
+    // the penalty propagates to the voucher
+    let punished_b = server.balance(&user_b).await;
+    assert!(punished_b < balance_b);
+
+    let signature = forget_sign(
+        &user_b_key,
+        &domain,
+        user_c.clone(),
+        expires_in(60),
+        &nonces,
+    )
+    .await
+    .unwrap();
+    let from = json!({"user": signature.signer});
+    server
+        .post(
+            &format!("/forget/{user_c}"),
+            signed(&nonces, signature, json!({"from": from})).await,
+        )
+        .await;
+    let vouchers = server.get(&format!("/vouchers/{user_c}")).await;
+    assert_eq!(vouchers["vouchers"], json!([]));
+    // the forget penalty is larger than the propagated one
+    let forgotten_b = server.balance(&user_b).await;
+    assert!(forgotten_b < punished_b);
+
+    // the state is kept in the database
+    let users = [&genesis_user, &user_a, &user_b, &user_c];
+    let mut balances = Vec::new();
+    for user in users {
+        balances.push(server.balance(user).await);
+    }
+    drop(server);
+    let server = TestServer::start(dir.path(), &server_key).await;
+    for (user, balance) in users.into_iter().zip(balances) {
+        assert_eq!(server.balance(user).await, balance);
+    }
+    assert_eq!(server.balance(&user_b).await, forgotten_b);
+}
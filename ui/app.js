@@ -0,0 +1,213 @@
+// dashboard for operators, only uses public JSON endpoints of the server
+"use strict";
+
+// vouch graph is loaded breadth first up to these limits
+const GRAPH_DEPTH = 2;
+const GRAPH_NODES = 40;
+
+async function getJson(path) {
+  const response = await fetch(path);
+  const body = await response.json();
+  if (!response.ok) {
+    throw new Error(body.error || `request failed with status ${response.status}`);
+  }
+  return body;
+}
+
+function element(tag, attributes = {}, text = "") {
+  const svg = ["svg", "line", "circle", "text", "defs", "marker", "path"].includes(tag);
+  const node = svg
+    ? document.createElementNS("http://www.w3.org/2000/svg", tag)
+    : document.createElement(tag);
+  for (const [name, value] of Object.entries(attributes)) {
+    node.setAttribute(name, value);
+  }
+  node.textContent = text;
+  return node;
+}
+
+function shortAddress(address) {
+  return address.length > 12 ? `${address.slice(0, 6)}…${address.slice(-4)}` : address;
+}
+
+function showError(target, error) {
+  target.replaceChildren(element("span", { class: "error" }, error.message));
+}
+
+function formatTime(timestamp) {
+  return timestamp ? new Date(timestamp * 1000).toISOString() : "-";
+}
+
+function table(headers, rows) {
+  const result = element("table");
+  const head = element("tr");
+  headers.forEach((h) => head.append(element("th", {}, h)));
+  result.append(head);
+  rows.forEach((row) => {
+    const tr = element("tr");
+    row.forEach((cell) => tr.append(element("td", {}, String(cell))));
+    result.append(tr);
+  });
+  return result;
+}
+
+async function showBalance(user) {
+  const target = document.getElementById("balance");
+  try {
+    const [idt, trust] = await Promise.all([
+      getJson(`/idt/${user}`),
+      getJson(`/trust/${user}`),
+    ]);
+    let status = null;
+    try {
+      status = await getJson(`/proofs/${user}/status`);
+    } catch (_) {
+      // user has no proof
+    }
+    const rows = [
+      ["IDT", idt.idt],
+      ["proven", idt.breakdown ? idt.breakdown.proven : "-"],
+      ["proof decay", idt.breakdown ? idt.breakdown.proof_decay : "-"],
+      ["penalty", idt.breakdown ? idt.breakdown.penalty : "-"],
+      ["trust score", trust.score.toFixed(1)],
+      ["proof expires", status ? formatTime(status.expires_at) : "-"],
+    ];
+    target.replaceChildren();
+    rows.forEach(([name, value]) => {
+      target.append(element("dt", {}, name), element("dd", {}, value));
+    });
+  } catch (error) {
+    showError(target, error);
+  }
+}
+
+async function showPenalties(user) {
+  const target = document.getElementById("penalties");
+  try {
+    const penalties = await getJson(`/penalties/${user}`);
+    const rows = [];
+    if (penalties.moderator) {
+      const p = penalties.moderator;
+      rows.push([`moderator ${shortAddress(p.moderator)}`, p.amount, p.remaining, formatTime(p.timestamp)]);
+    }
+    penalties.forgotten.forEach((p) => {
+      rows.push([`forgot ${shortAddress(p.user)}`, p.amount, p.remaining, formatTime(p.timestamp)]);
+    });
+    target.className = "";
+    target.replaceChildren(
+      element("p", {}, `Total penalty: ${penalties.total} IDT`),
+      table(["source", "amount", "remaining", "time"], rows),
+    );
+  } catch (error) {
+    showError(target, error);
+  }
+}
+
+async function loadGraph(root) {
+  const nodes = [root];
+  const edges = [];
+  let frontier = [root];
+  for (let depth = 0; depth < GRAPH_DEPTH && frontier.length > 0; depth++) {
+    const next = [];
+    for (const user of frontier) {
+      const { vouchers } = await getJson(`/vouchers/${user}`);
+      for (const voucher of vouchers) {
+        edges.push([voucher, user]);
+        if (!nodes.includes(voucher) && nodes.length < GRAPH_NODES) {
+          nodes.push(voucher);
+          next.push(voucher);
+        }
+      }
+    }
+    frontier = next;
+  }
+  return { nodes, edges: edges.filter(([from, to]) => nodes.includes(from) && nodes.includes(to)) };
+}
+
+async function showGraph(user) {
+  const svg = document.getElementById("graph");
+  svg.replaceChildren();
+  let graph;
+  try {
+    graph = await loadGraph(user);
+  } catch (error) {
+    svg.append(element("text", { x: 300, y: 200 }, error.message));
+    return;
+  }
+  const defs = element("defs");
+  const marker = element("marker", {
+    id: "arrow", viewBox: "0 0 10 10", refX: 18, refY: 5,
+    markerWidth: 6, markerHeight: 6, orient: "auto",
+  });
+  marker.append(element("path", { d: "M 0 0 L 10 5 L 0 10 z", fill: "#9ca3af" }));
+  defs.append(marker);
+  svg.append(defs);
+
+  // root in the center, vouchers on a circle around it
+  const position = new Map();
+  position.set(graph.nodes[0], [300, 200]);
+  const others = graph.nodes.slice(1);
+  others.forEach((node, i) => {
+    const angle = (2 * Math.PI * i) / others.length;
+    position.set(node, [300 + 160 * Math.cos(angle), 200 + 160 * Math.sin(angle)]);
+  });
+  graph.edges.forEach(([from, to]) => {
+    const [x1, y1] = position.get(from);
+    const [x2, y2] = position.get(to);
+    svg.append(element("line", { x1, y1, x2, y2 }));
+  });
+  graph.nodes.forEach((node, i) => {
+    const [x, y] = position.get(node);
+    const circle = element("circle", { cx: x, cy: y, r: 8, class: i === 0 ? "root" : "" });
+    circle.append(element("title", {}, node));
+    circle.addEventListener("click", () => lookup(node));
+    svg.append(circle, element("text", { x, y: y + 20 }, shortAddress(node)));
+  });
+  if (others.length === 0) {
+    svg.append(element("text", { x: 300, y: 240 }, "no vouchers"));
+  }
+}
+
+async function showServers() {
+  const target = document.getElementById("servers");
+  try {
+    const servers = await getJson("/servers");
+    const rows = Object.entries(servers).map(([address, info]) => [
+      shortAddress(address), info.url, JSON.stringify(info.scale),
+    ]);
+    target.replaceChildren(table(["server", "url", "scale"], rows));
+  } catch (error) {
+    showError(target, error);
+  }
+}
+
+async function showConfig() {
+  const target = document.getElementById("config");
+  try {
+    target.textContent = JSON.stringify(await getJson("/ui/config"), null, 2);
+  } catch (error) {
+    showError(target, error);
+  }
+}
+
+function lookup(user) {
+  document.getElementById("user").value = user;
+  window.location.hash = user;
+  showBalance(user);
+  showPenalties(user);
+  showGraph(user);
+}
+
+document.getElementById("lookup").addEventListener("submit", (event) => {
+  event.preventDefault();
+  const user = document.getElementById("user").value.trim();
+  if (user) {
+    lookup(user);
+  }
+});
+
+showServers();
+showConfig();
+if (window.location.hash.length > 1) {
+  lookup(decodeURIComponent(window.location.hash.slice(1)));
+}